@@ -0,0 +1,212 @@
+//! `redact_paths_mode`: absolute paths in the session context rewritten
+//! before they go into a prompt, so a transcript does not give away where
+//! on disk (and under which client's name) the work happened. Only prompts
+//! are rewritten; the record's stat never holds absolute paths.
+
+use crate::config::{self, Config, RedactPathsMode};
+use crate::git;
+use crate::sha256;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Hex digits of the hash standing in for the project directory.
+const HASH_LEN: usize = 8;
+
+/// Rewrites paths in text: each `(from, to)` prefix, longest first.
+#[derive(Debug, Default)]
+pub struct Anonymizer {
+    rewrites: Vec<(String, String)>,
+}
+
+impl Anonymizer {
+    /// `repo_roots` are every spelling of the repository root, the first
+    /// being the one the hash is taken from; a symlinked checkout has two.
+    /// `home` is the home directory. `None` as `mode` rewrites nothing.
+    pub fn new(
+        mode: Option<RedactPathsMode>,
+        repo_roots: &[PathBuf],
+        home: Option<&Path>,
+    ) -> Anonymizer {
+        let spell = |path: &Path| path.to_string_lossy().trim_end_matches('/').to_string();
+        let mut rewrites = Vec::new();
+        match mode {
+            None => {}
+            Some(RedactPathsMode::RepoRelative) => {
+                for root in repo_roots {
+                    rewrites.push((format!("{}/", spell(root)), String::new()));
+                    rewrites.push((spell(root), ".".to_string()));
+                }
+            }
+            Some(RedactPathsMode::HomeTilde) => {
+                rewrites.extend(home.map(|home| (spell(home), "~".to_string())));
+            }
+            Some(RedactPathsMode::HashProject) => {
+                let name = repo_roots
+                    .first()
+                    .and_then(|root| root.file_name())
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                let hashed = format!("project-{}", &sha256::hex(name.as_bytes())[..HASH_LEN]);
+                for root in repo_roots {
+                    let parent = root.parent().map(spell).unwrap_or_default();
+                    rewrites.push((spell(root), format!("{parent}/{hashed}")));
+                }
+            }
+        }
+        rewrites.retain(|(from, _)| !from.is_empty() && from != "/");
+        rewrites.sort_by_key(|(from, _)| std::cmp::Reverse(from.len()));
+        Anonymizer { rewrites }
+    }
+
+    /// The anonymizer `config` asks for in the current repository.
+    pub fn for_repo(config: &Config) -> Anonymizer {
+        let Some(mode) = config.redact_paths_mode else {
+            return Anonymizer::default();
+        };
+        let home = config::home_dir();
+        Anonymizer::new(Some(mode), &repo_roots(), home.as_deref())
+    }
+
+    pub fn text(&self, text: &str) -> String {
+        self.rewrites
+            .iter()
+            .fold(text.to_string(), |text, (from, to)| {
+                replace_path(&text, from, to)
+            })
+    }
+}
+
+/// The repository root as git reports it, its canonical form, and the
+/// spelling `$PWD` uses when the shell entered it through a symlink.
+fn repo_roots() -> Vec<PathBuf> {
+    let Some(root) = git::repo_root() else {
+        return Vec::new();
+    };
+    let mut roots = vec![root.clone()];
+    let canonical = fs::canonicalize(&root).unwrap_or(root);
+    roots.push(canonical.clone());
+    if let Some(pwd) = env::var_os("PWD").map(PathBuf::from)
+        && let Ok(real) = fs::canonicalize(&pwd)
+        && let Ok(inside) = real.strip_prefix(&canonical)
+        && let Some(logical) = pwd.ancestors().nth(inside.components().count())
+    {
+        roots.push(logical.to_path_buf());
+    }
+    roots.dedup();
+    roots
+}
+
+/// `text` with every whole-path occurrence of `from` replaced by `to`. A
+/// match must not continue a longer path on the left, and unless `from`
+/// ends with `/`, must end where a path component does.
+fn replace_path(text: &str, from: &str, to: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut copied = 0;
+    for (at, _) in text.match_indices(from) {
+        let before = text[..at].chars().next_back();
+        let after = text[at + from.len()..].chars().next();
+        let starts = before.is_none_or(|c| !is_path_char(c));
+        let ends = from.ends_with('/') || after.is_none_or(|c| c == '/' || !is_path_char(c));
+        if starts && ends {
+            out.push_str(&text[copied..at]);
+            out.push_str(to);
+            copied = at + from.len();
+        }
+    }
+    out.push_str(&text[copied..]);
+    out
+}
+
+fn is_path_char(c: char) -> bool {
+    c.is_alphanumeric() || "/._-~".contains(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOME: &str = "/Users/kenta";
+    const ROOT: &str = "/Users/kenta/secret-client-project";
+    /// `~/work` is a symlink to `/Users/kenta`.
+    const LINKED: &str = "/Users/kenta/work/secret-client-project";
+
+    fn anonymize(mode: RedactPathsMode, text: &str) -> String {
+        let roots = [PathBuf::from(ROOT), PathBuf::from(LINKED)];
+        Anonymizer::new(Some(mode), &roots, Some(Path::new(HOME))).text(text)
+    }
+
+    #[test]
+    fn repo_relative_strips_the_root_from_nested_paths() {
+        assert_eq!(
+            anonymize(
+                RedactPathsMode::RepoRelative,
+                "- /Users/kenta/secret-client-project/src/billing/invoice.rs\n\
+                 - cd /Users/kenta/secret-client-project && cargo test"
+            ),
+            "- src/billing/invoice.rs\n- cd . && cargo test"
+        );
+    }
+
+    #[test]
+    fn repo_relative_handles_the_symlinked_spelling() {
+        assert_eq!(
+            anonymize(
+                RedactPathsMode::RepoRelative,
+                "`/Users/kenta/work/secret-client-project/src/lib.rs`"
+            ),
+            "`src/lib.rs`"
+        );
+    }
+
+    #[test]
+    fn non_matching_paths_are_left_alone() {
+        let text = "/Users/kenta/secret-client-project-old/a.rs \
+                    /opt/Users/kenta/secret-client-project/b.rs /tmp/c.rs";
+
+        assert_eq!(anonymize(RedactPathsMode::RepoRelative, text), text);
+        assert_eq!(
+            anonymize(RedactPathsMode::HomeTilde, "/Users/kentaro/a.rs /tmp/c.rs"),
+            "/Users/kentaro/a.rs /tmp/c.rs"
+        );
+        assert_eq!(Anonymizer::default().text(ROOT), ROOT);
+    }
+
+    #[test]
+    fn home_tilde_replaces_the_home_directory() {
+        assert_eq!(
+            anonymize(
+                RedactPathsMode::HomeTilde,
+                "/Users/kenta/secret-client-project/src/a.rs and /Users/kenta"
+            ),
+            "~/secret-client-project/src/a.rs and ~"
+        );
+    }
+
+    #[test]
+    fn hash_project_replaces_only_the_project_segment() {
+        let hashed = anonymize(
+            RedactPathsMode::HashProject,
+            "/Users/kenta/secret-client-project/src/a.rs",
+        );
+        let linked = anonymize(
+            RedactPathsMode::HashProject,
+            "/Users/kenta/work/secret-client-project/src/a.rs",
+        );
+
+        assert!(!hashed.contains("secret-client"), "{hashed}");
+        let segment = hashed
+            .strip_prefix("/Users/kenta/")
+            .and_then(|rest| rest.strip_suffix("/src/a.rs"))
+            .unwrap();
+        // Stable across runs and machines: the hash is of the name alone.
+        assert_eq!(
+            segment,
+            format!(
+                "project-{}",
+                &sha256::hex(b"secret-client-project")[..HASH_LEN]
+            )
+        );
+        assert_eq!(linked, format!("/Users/kenta/work/{segment}/src/a.rs"));
+    }
+}
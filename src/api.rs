@@ -0,0 +1,366 @@
+use crate::claude::{ClaudeResult, Usage};
+use crate::config::Config;
+use crate::log::warn;
+use crate::signal;
+use serde::Deserialize;
+use serde_json::json;
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::process::CommandExt;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+pub(crate) const API_KEY_ENV: &str = "ANTHROPIC_API_KEY";
+const API_VERSION: &str = "2023-06-01";
+const MAX_TOKENS: u32 = 8192;
+const RETRY_BACKOFF: Duration = Duration::from_secs(1);
+
+#[derive(Deserialize)]
+struct MessagesResponse {
+    content: Vec<ContentBlock>,
+    usage: Option<ApiUsage>,
+}
+
+#[derive(Deserialize)]
+struct ContentBlock {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct ApiUsage {
+    #[serde(default)]
+    input_tokens: u64,
+    #[serde(default)]
+    output_tokens: u64,
+}
+
+enum Attempt {
+    Done(ClaudeResult),
+    Retry(String),
+    Fail(String),
+}
+
+pub fn run(prompt: &str, config: &Config, model: &str) -> Result<ClaudeResult, String> {
+    let api_key = std::env::var(API_KEY_ENV)
+        .ok()
+        .filter(|k| !k.is_empty())
+        .ok_or_else(|| format!("{API_KEY_ENV} is not set; cannot use the api backend"))?;
+    send_with_retries(prompt, config, model, &api_key, RETRY_BACKOFF)
+}
+
+fn send_with_retries(
+    prompt: &str,
+    config: &Config,
+    model: &str,
+    api_key: &str,
+    backoff: Duration,
+) -> Result<ClaudeResult, String> {
+    let body = build_body(prompt, model);
+    let mut last_failure = String::new();
+    for attempt in 0..=config.max_retries {
+        if attempt > 0 {
+            warn!("Anthropic API request failed ({last_failure}), retrying...");
+            thread::sleep(backoff * 2u32.pow(attempt - 1));
+        }
+        match send(&body, config, api_key) {
+            Attempt::Done(result) => return Ok(result),
+            Attempt::Fail(reason) => {
+                return Err(format!("Anthropic API request failed: {reason}"));
+            }
+            Attempt::Retry(reason) => last_failure = reason,
+        }
+    }
+    Err(format!("Anthropic API request failed: {last_failure}"))
+}
+
+/// Maps the CLI-style model aliases to full API model IDs; anything else
+/// is assumed to already be a model ID.
+fn model_id(model: &str) -> &str {
+    match model {
+        "opus" => "claude-opus-4-1",
+        "sonnet" => "claude-sonnet-4-5",
+        "haiku" => "claude-haiku-4-5",
+        other => other,
+    }
+}
+
+fn build_body(prompt: &str, model: &str) -> String {
+    json!({
+        "model": model_id(model),
+        "max_tokens": MAX_TOKENS,
+        "messages": [{ "role": "user", "content": prompt }],
+    })
+    .to_string()
+}
+
+/// Builds a curl config read from stdin, so neither the key nor the prompt
+/// appears in the process arguments.
+fn build_curl_config(body: &str, config: &Config, api_key: &str) -> String {
+    let url = format!("{}/v1/messages", config.api_base_url.trim_end_matches('/'));
+    let lines = [
+        format!("url = \"{}\"", curl_quote(&url)),
+        format!("header = \"x-api-key: {}\"", curl_quote(api_key)),
+        format!("header = \"anthropic-version: {API_VERSION}\""),
+        "header = \"content-type: application/json\"".to_string(),
+        format!("data-binary = \"{}\"", curl_quote(body)),
+    ];
+    lines.join("\n") + "\n"
+}
+
+fn curl_quote(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn send(body: &str, config: &Config, api_key: &str) -> Attempt {
+    let mut command = Command::new("curl");
+    command
+        .args([
+            "--silent",
+            "--show-error",
+            "--config",
+            "-",
+            "--max-time",
+            &config.timeout_secs.to_string(),
+            "--write-out",
+            "\n%{http_code}",
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    #[cfg(unix)]
+    command.process_group(0);
+    let mut child = match command.spawn() {
+        Ok(c) => c,
+        Err(e) => return Attempt::Fail(format!("cannot start curl: {e}")),
+    };
+    let _tracked = signal::track_child(child.id());
+
+    if let Some(mut stdin) = child.stdin.take()
+        && let Err(e) = stdin.write_all(build_curl_config(body, config, api_key).as_bytes())
+    {
+        let _ = child.kill();
+        let _ = child.wait();
+        return Attempt::Fail(format!("cannot write request to curl: {e}"));
+    }
+
+    let output = match child.wait_with_output() {
+        Ok(o) => o,
+        Err(e) => return Attempt::Fail(format!("failed to wait for curl: {e}")),
+    };
+    if !output.status.success() {
+        return Attempt::Retry(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let (response, status) = stdout.rsplit_once('\n').unwrap_or(("", &stdout));
+    classify_response(status.trim(), response)
+}
+
+fn classify_response(status: &str, response: &str) -> Attempt {
+    match status.parse::<u16>() {
+        Ok(200) => parse_response(response)
+            .map(Attempt::Done)
+            .unwrap_or_else(|| Attempt::Fail("unexpected response body".to_string())),
+        Ok(code @ (408 | 429 | 500..=599)) => Attempt::Retry(format!("HTTP {code}")),
+        Ok(code) => Attempt::Fail(format!("HTTP {code}: {}", error_message(response))),
+        Err(_) => Attempt::Retry(format!("unexpected HTTP status {status:?}")),
+    }
+}
+
+fn parse_response(response: &str) -> Option<ClaudeResult> {
+    let parsed: MessagesResponse = serde_json::from_str(response).ok()?;
+    let text: String = parsed
+        .content
+        .iter()
+        .filter(|b| b.kind == "text")
+        .map(|b| b.text.as_str())
+        .collect();
+    Some(ClaudeResult {
+        text,
+        usage: parsed.usage.map(|u| Usage {
+            input_tokens: u.input_tokens,
+            output_tokens: u.output_tokens,
+        }),
+        ..ClaudeResult::default()
+    })
+}
+
+fn error_message(response: &str) -> String {
+    serde_json::from_str::<serde_json::Value>(response)
+        .ok()
+        .and_then(|v| {
+            v.pointer("/error/message")
+                .and_then(|m| m.as_str())
+                .map(String::from)
+        })
+        .unwrap_or_else(|| response.chars().take(200).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+
+    struct Captured {
+        headers: Vec<String>,
+        body: String,
+    }
+
+    /// Serves the given (status, body) responses in order on an ephemeral
+    /// port and reports each captured request.
+    fn mock_server(responses: Vec<(u16, &'static str)>) -> (String, mpsc::Receiver<Captured>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            for (status, body) in responses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut headers = Vec::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let line = line.trim_end().to_string();
+                    if line.is_empty() {
+                        break;
+                    }
+                    headers.push(line);
+                }
+                let length: usize = headers
+                    .iter()
+                    .find_map(|h| {
+                        h.to_ascii_lowercase()
+                            .strip_prefix("content-length:")
+                            .map(|v| v.trim().parse().unwrap())
+                    })
+                    .unwrap_or(0);
+                let mut request_body = vec![0u8; length];
+                reader.read_exact(&mut request_body).unwrap();
+                tx.send(Captured {
+                    headers,
+                    body: String::from_utf8(request_body).unwrap(),
+                })
+                .unwrap();
+
+                let mut stream = stream;
+                write!(
+                    stream,
+                    "HTTP/1.1 {status} X\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                    body.len()
+                )
+                .unwrap();
+            }
+        });
+        (base_url, rx)
+    }
+
+    const OK_RESPONSE: &str = r###"{"content":[{"type":"text","text":"## 変更概要\n\nok"}],"usage":{"input_tokens":120,"output_tokens":30}}"###;
+
+    #[test]
+    fn model_id_maps_aliases_and_passes_through_full_ids() {
+        assert_eq!(model_id("sonnet"), "claude-sonnet-4-5");
+        assert_eq!(
+            model_id("claude-3-5-haiku-latest"),
+            "claude-3-5-haiku-latest"
+        );
+    }
+
+    #[test]
+    fn curl_config_quotes_body_and_key() {
+        let config = Config::default();
+        let cfg = build_curl_config(r#"{"a":"x\"y"}"#, &config, "sk-test");
+
+        assert!(cfg.contains(r#"url = "https://api.anthropic.com/v1/messages""#));
+        assert!(cfg.contains(r#"header = "x-api-key: sk-test""#));
+        assert!(cfg.contains(r#"data-binary = "{\"a\":\"x\\\"y\"}""#));
+    }
+
+    #[test]
+    fn classify_response_retries_overload_and_fails_client_errors() {
+        assert!(matches!(classify_response("529", ""), Attempt::Retry(_)));
+        assert!(matches!(classify_response("429", ""), Attempt::Retry(_)));
+        let body = r#"{"error":{"message":"invalid x-api-key"}}"#;
+        match classify_response("401", body) {
+            Attempt::Fail(reason) => assert_eq!(reason, "HTTP 401: invalid x-api-key"),
+            _ => panic!("expected Fail"),
+        }
+    }
+
+    #[test]
+    fn send_posts_messages_request_with_auth_header() {
+        let (base_url, requests) = mock_server(vec![(200, OK_RESPONSE)]);
+        let config = Config {
+            api_base_url: base_url,
+            ..Config::default()
+        };
+
+        let result = send_with_retries(
+            "explain <diff>",
+            &config,
+            "haiku",
+            "sk-test-key",
+            Duration::ZERO,
+        )
+        .unwrap();
+
+        assert_eq!(result.text, "## 変更概要\n\nok");
+        assert_eq!(
+            result.usage,
+            Some(Usage {
+                input_tokens: 120,
+                output_tokens: 30,
+            })
+        );
+        let captured = requests.recv().unwrap();
+        assert!(captured.headers[0].starts_with("POST /v1/messages "));
+        assert!(
+            captured
+                .headers
+                .contains(&"x-api-key: sk-test-key".to_string())
+        );
+        assert!(
+            captured
+                .headers
+                .contains(&"anthropic-version: 2023-06-01".to_string())
+        );
+        let body: serde_json::Value = serde_json::from_str(&captured.body).unwrap();
+        assert_eq!(body["model"], "claude-haiku-4-5");
+        assert_eq!(body["messages"][0]["role"], "user");
+        assert_eq!(body["messages"][0]["content"], "explain <diff>");
+    }
+
+    #[test]
+    fn send_retries_server_errors() {
+        let (base_url, requests) = mock_server(vec![(500, "{}"), (200, OK_RESPONSE)]);
+        let config = Config {
+            api_base_url: base_url,
+            max_retries: 1,
+            ..Config::default()
+        };
+
+        let result = send_with_retries("prompt", &config, "sonnet", "sk-test", Duration::ZERO);
+
+        assert!(result.is_ok());
+        assert_eq!(requests.iter().take(2).count(), 2);
+    }
+
+    #[test]
+    fn send_gives_up_after_max_retries() {
+        let (base_url, _requests) = mock_server(vec![(503, "{}"), (503, "{}")]);
+        let config = Config {
+            api_base_url: base_url,
+            max_retries: 1,
+            ..Config::default()
+        };
+
+        let err =
+            send_with_retries("prompt", &config, "sonnet", "sk-test", Duration::ZERO).unwrap_err();
+        assert_eq!(err, "Anthropic API request failed: HTTP 503");
+    }
+}
@@ -0,0 +1,164 @@
+//! Which of the changed files Claude edited in the session and which were
+//! edited by hand, so the record does not make up reasons for the latter.
+
+use crate::git::NumstatPath;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::{Component, Path};
+
+/// The diff's files, split by whether the session edited them. Paths are
+/// relative to the repository root, in diff order.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Authorship {
+    pub claude: Vec<String>,
+    pub manual: Vec<String>,
+}
+
+impl Authorship {
+    /// Splits `changed` by whether `session_files` (as the transcript names
+    /// them, absolute or relative) holds the file under its current or its
+    /// pre-rename path.
+    pub fn compare(
+        changed: &[NumstatPath],
+        session_files: &BTreeSet<String>,
+        root: Option<&Path>,
+    ) -> Authorship {
+        let edited: BTreeSet<String> = session_files
+            .iter()
+            .filter_map(|file| normalize(file, root))
+            .collect();
+        let mut authorship = Authorship::default();
+        for file in changed {
+            let by_claude = edited.contains(&file.path)
+                || file
+                    .renamed_from
+                    .as_ref()
+                    .is_some_and(|from| edited.contains(from));
+            if by_claude {
+                authorship.claude.push(file.path.clone());
+            } else {
+                authorship.manual.push(file.path.clone());
+            }
+        }
+        authorship
+    }
+
+    /// The two lists as the IDR prompt shows them.
+    pub fn render(&self) -> String {
+        let list = |files: &[String]| -> String {
+            if files.is_empty() {
+                return "- (none)\n".to_string();
+            }
+            files.iter().map(|file| format!("- {file}\n")).collect()
+        };
+        format!(
+            "Files edited with Claude:\n{}\nFiles edited manually:\n{}",
+            list(&self.claude),
+            list(&self.manual)
+        )
+    }
+}
+
+/// `file` relative to the repository `root`, with `.` and `..` resolved.
+/// `None` for an absolute path outside `root`, or any absolute path when
+/// the root is unknown.
+pub fn normalize(file: &str, root: Option<&Path>) -> Option<String> {
+    let path = Path::new(file);
+    let relative = if path.is_absolute() {
+        path.strip_prefix(root?).ok()?
+    } else {
+        path
+    };
+    let mut parts: Vec<&str> = Vec::new();
+    for component in relative.components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_str()?),
+            Component::ParentDir => {
+                parts.pop()?;
+            }
+            Component::CurDir => {}
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    (!parts.is_empty()).then(|| parts.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn changed(path: &str, renamed_from: Option<&str>) -> NumstatPath {
+        NumstatPath {
+            path: path.to_string(),
+            renamed_from: renamed_from.map(str::to_string),
+        }
+    }
+
+    fn files(paths: &[&str]) -> BTreeSet<String> {
+        paths.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn normalize_makes_paths_repo_relative() {
+        let root = Some(Path::new("/ws/repo"));
+
+        assert_eq!(
+            normalize("/ws/repo/src/a.rs", root).as_deref(),
+            Some("src/a.rs")
+        );
+        assert_eq!(normalize("src/a.rs", root).as_deref(), Some("src/a.rs"));
+        assert_eq!(normalize("./src/a.rs", None).as_deref(), Some("src/a.rs"));
+        assert_eq!(
+            normalize("/ws/repo/src/../lib/b.rs", root).as_deref(),
+            Some("lib/b.rs")
+        );
+        assert_eq!(normalize("/ws/other/a.rs", root), None);
+        assert_eq!(normalize("/ws/repo/a.rs", None), None);
+        assert_eq!(normalize("../outside.rs", root), None);
+        assert_eq!(normalize("/ws/repo", root), None);
+    }
+
+    #[test]
+    fn compare_splits_claude_and_manual_files() {
+        let session = files(&["/ws/repo/src/api.rs", "./docs/guide.md", "/tmp/scratch.txt"]);
+        let diff = [
+            changed("src/api.rs", None),
+            changed("src/config.rs", None),
+            changed("docs/guide.md", None),
+        ];
+
+        let authorship = Authorship::compare(&diff, &session, Some(Path::new("/ws/repo")));
+
+        assert_eq!(authorship.claude, ["src/api.rs", "docs/guide.md"]);
+        assert_eq!(authorship.manual, ["src/config.rs"]);
+    }
+
+    #[test]
+    fn compare_follows_renames_either_way() {
+        let root = Some(Path::new("/ws/repo"));
+        let diff = [
+            changed("src/client.rs", Some("src/api.rs")),
+            changed("src/new_name.rs", Some("src/old_name.rs")),
+            changed("src/moved.rs", Some("src/orig.rs")),
+        ];
+        let session = files(&["/ws/repo/src/api.rs", "/ws/repo/src/new_name.rs"]);
+
+        let authorship = Authorship::compare(&diff, &session, root);
+
+        assert_eq!(authorship.claude, ["src/client.rs", "src/new_name.rs"]);
+        assert_eq!(authorship.manual, ["src/moved.rs"]);
+    }
+
+    #[test]
+    fn render_lists_both_groups() {
+        let authorship = Authorship {
+            claude: vec!["src/api.rs".to_string()],
+            manual: Vec::new(),
+        };
+
+        assert_eq!(
+            authorship.render(),
+            "Files edited with Claude:\n- src/api.rs\n\nFiles edited manually:\n- (none)\n"
+        );
+    }
+}
@@ -0,0 +1,234 @@
+//! `claude-idr --post-commit`: records the commit a pre-commit IDR ended up
+//! describing, once that commit exists.
+
+use crate::config::Format;
+use crate::path;
+use crate::sidecar::{self, CommitRef};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+const FOOTER_PREFIX: &str = "> Commit: ";
+/// How old a record without a matching diff hash may be and still be taken
+/// as the one for this commit.
+const RECENT_WINDOW: Duration = Duration::from_secs(10 * 60);
+
+/// Adds `commit` to the record in `dir` that describes `patch`: the one
+/// whose sidecar has the same diff hash, or else the newest record written
+/// within the last few minutes that has no commit yet. Returns the record
+/// updated, if any. Running it twice for the same commit changes nothing.
+pub fn run(
+    dir: &Path,
+    format: Format,
+    commit: &CommitRef,
+    patch: &str,
+    now: SystemTime,
+) -> io::Result<Option<PathBuf>> {
+    let Some(record) = find_target(dir, format, patch, now) else {
+        return Ok(None);
+    };
+    let text = fs::read_to_string(&record)?;
+    let updated = set_commit_line(&text, short(&commit.sha));
+    if updated != text {
+        fs::write(&record, updated)?;
+    }
+    if let Some(mut sidecar) = sidecar::load_metadata(&record)
+        && sidecar.commit.as_ref() != Some(commit)
+    {
+        sidecar.commit = Some(commit.clone());
+        sidecar::write(&record, &sidecar)?;
+    }
+    Ok(Some(record))
+}
+
+fn find_target(dir: &Path, format: Format, patch: &str, now: SystemTime) -> Option<PathBuf> {
+    let hash = sidecar::diff_hash(patch);
+    let records: Vec<PathBuf> = path::list_records(dir, format)
+        .into_iter()
+        .rev()
+        .map(|(_, p)| p)
+        .collect();
+    let by_hash = records
+        .iter()
+        .find(|record| sidecar::load_metadata(record).is_some_and(|s| s.diff_sha256 == hash));
+    if let Some(record) = by_hash {
+        return Some(record.clone());
+    }
+    records.into_iter().find(|record| {
+        let recent = fs::metadata(record)
+            .and_then(|m| m.modified())
+            .is_ok_and(|mtime| {
+                now.duration_since(mtime)
+                    .is_ok_and(|age| age <= RECENT_WINDOW)
+            });
+        recent && sidecar::load_metadata(record).is_some_and(|s| s.commit.is_none())
+    })
+}
+
+/// `text` with a `> Commit: <sha>` line: an existing one is replaced,
+/// otherwise the line goes before the trailing metadata comment (or at the
+/// end when that was edited away).
+pub fn set_commit_line(text: &str, sha: &str) -> String {
+    let line = format!("{FOOTER_PREFIX}{sha}");
+    let mut lines: Vec<&str> = text.lines().collect();
+    if let Some(existing) = lines
+        .iter()
+        .position(|l| l.trim_start().starts_with(FOOTER_PREFIX))
+    {
+        lines[existing] = &line;
+        return lines.join("\n") + "\n";
+    }
+    match lines
+        .iter()
+        .rposition(|l| l.trim_start().starts_with("<!-- claude-idr:"))
+    {
+        Some(at) => {
+            lines.insert(at, "");
+            lines.insert(at, &line);
+            lines.join("\n") + "\n"
+        }
+        None => format!("{}\n\n{line}\n", text.trim_end()),
+    }
+}
+
+/// The `> Commit: <sha>` footer line for a record written after its commit.
+pub fn commit_line(sha: &str) -> String {
+    format!("{FOOTER_PREFIX}{}", short(sha))
+}
+
+fn short(sha: &str) -> &str {
+    &sha[..sha.len().min(7)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sidecar::{DiffSource, SCHEMA_VERSION, Sidecar};
+    use tempfile::TempDir;
+
+    const RECORD: &str = "# IDR: Use JWT\n\n> 2026-02-07 14:30\n\nbody\n\n---\n\n### git diff --stat\n```\n stat\n```\n\n<!-- claude-idr: version=0.1.1 prompt=v3 model=sonnet generated=2026-02-07T05:30:00Z -->\n";
+
+    fn commit() -> CommitRef {
+        CommitRef {
+            sha: "abc1234def5678".to_string(),
+            subject: "Use JWT".to_string(),
+        }
+    }
+
+    fn write_record(dir: &Path, name: &str, diff: &str, commit: Option<CommitRef>) -> PathBuf {
+        let record = dir.join(name);
+        fs::write(&record, RECORD).unwrap();
+        let sidecar = Sidecar {
+            schema: SCHEMA_VERSION,
+            diff_source: DiffSource::Staged,
+            diff_sha256: sidecar::diff_hash(diff),
+            session_path: None,
+            session_id: None,
+            purpose_model: None,
+            body_model: "sonnet".to_string(),
+            started: String::new(),
+            generated: String::new(),
+            usage: None,
+            issues: Vec::new(),
+            change_type: None,
+            commit,
+            failure: None,
+            title_source: None,
+            authorship: None,
+            effort: None,
+        };
+        sidecar::write(&record, &sidecar).unwrap();
+        record
+    }
+
+    #[test]
+    fn set_commit_line_goes_before_metadata_comment() {
+        let updated = set_commit_line(RECORD, "abc1234");
+
+        assert!(updated.ends_with(
+            "```\n\n> Commit: abc1234\n\n<!-- claude-idr: version=0.1.1 prompt=v3 model=sonnet generated=2026-02-07T05:30:00Z -->\n"
+        ));
+    }
+
+    #[test]
+    fn set_commit_line_is_idempotent_and_replaces_old_sha() {
+        let once = set_commit_line(RECORD, "abc1234");
+
+        assert_eq!(set_commit_line(&once, "abc1234"), once);
+        let amended = set_commit_line(&once, "fff0000");
+        assert!(amended.contains("> Commit: fff0000\n"));
+        assert!(!amended.contains("abc1234"));
+    }
+
+    #[test]
+    fn set_commit_line_appends_when_comment_was_removed() {
+        let edited = "# IDR: hand edited\n\nnotes\n\n\n";
+
+        assert_eq!(
+            set_commit_line(edited, "abc1234"),
+            "# IDR: hand edited\n\nnotes\n\n> Commit: abc1234\n"
+        );
+    }
+
+    #[test]
+    fn run_matches_record_by_diff_hash() {
+        let dir = TempDir::new().unwrap();
+        let wanted = write_record(dir.path(), "idr-01.md", "patch one", None);
+        write_record(dir.path(), "idr-02.md", "patch two", None);
+
+        let updated = run(
+            dir.path(),
+            Format::Idr,
+            &commit(),
+            "patch one",
+            SystemTime::now(),
+        )
+        .unwrap();
+
+        assert_eq!(updated, Some(wanted.clone()));
+        assert!(
+            fs::read_to_string(&wanted)
+                .unwrap()
+                .contains("> Commit: abc1234\n")
+        );
+        assert_eq!(
+            sidecar::load_metadata(&wanted).unwrap().commit,
+            Some(commit())
+        );
+        assert!(
+            !fs::read_to_string(dir.path().join("idr-02.md"))
+                .unwrap()
+                .contains("Commit:")
+        );
+    }
+
+    #[test]
+    fn run_falls_back_to_recent_record_without_commit() {
+        let dir = TempDir::new().unwrap();
+        let older = write_record(dir.path(), "idr-01.md", "a", None);
+        write_record(dir.path(), "idr-02.md", "b", Some(commit()));
+
+        let updated = run(
+            dir.path(),
+            Format::Idr,
+            &commit(),
+            "edited before commit",
+            SystemTime::now(),
+        )
+        .unwrap();
+
+        assert_eq!(updated, Some(older));
+    }
+
+    #[test]
+    fn run_ignores_stale_records() {
+        let dir = TempDir::new().unwrap();
+        write_record(dir.path(), "idr-01.md", "a", None);
+        let later = SystemTime::now() + RECENT_WINDOW + Duration::from_secs(60);
+
+        let updated = run(dir.path(), Format::Idr, &commit(), "b", later).unwrap();
+
+        assert_eq!(updated, None);
+    }
+}
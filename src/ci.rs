@@ -0,0 +1,70 @@
+//! Recognizing CI build agents, where the commit hooks run without a Claude
+//! Code session or a claude CLI to talk to. Generation skips there unless
+//! `run_in_ci` or `--force` says otherwise.
+
+use std::collections::BTreeMap;
+use std::env;
+
+/// Variables CI services set, checked in order; the first one set wins.
+const INDICATORS: &[&str] = &[
+    "CI",
+    "GITHUB_ACTIONS",
+    "GITLAB_CI",
+    "BUILDKITE",
+    "JENKINS_URL",
+];
+
+/// The environment as a map, for [`detect`].
+pub fn snapshot() -> BTreeMap<String, String> {
+    env::vars_os()
+        .filter_map(|(k, v)| Some((k.into_string().ok()?, v.into_string().ok()?)))
+        .collect()
+}
+
+/// The variable in `vars` that marks a CI run, if any. Empty values and
+/// `false`/`0` (as in `CI=false`) do not count.
+pub fn detect(vars: &BTreeMap<String, String>) -> Option<&'static str> {
+    INDICATORS.iter().copied().find(|name| {
+        vars.get(*name).is_some_and(|value| {
+            let value = value.trim();
+            !value.is_empty() && !value.eq_ignore_ascii_case("false") && value != "0"
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn detect_recognizes_each_indicator() {
+        for (name, value) in [
+            ("CI", "true"),
+            ("GITHUB_ACTIONS", "true"),
+            ("GITLAB_CI", "true"),
+            ("BUILDKITE", "true"),
+            ("JENKINS_URL", "https://jenkins.example.com/"),
+        ] {
+            assert_eq!(detect(&vars(&[(name, value), ("HOME", "/h")])), Some(name));
+        }
+    }
+
+    #[test]
+    fn detect_ignores_unset_empty_and_false_values() {
+        assert_eq!(detect(&vars(&[("HOME", "/h")])), None);
+        assert_eq!(detect(&vars(&[("CI", "")])), None);
+        assert_eq!(detect(&vars(&[("CI", "false")])), None);
+        assert_eq!(detect(&vars(&[("CI", "0")])), None);
+        assert_eq!(
+            detect(&vars(&[("CI", "false"), ("GITLAB_CI", "true")])),
+            Some("GITLAB_CI")
+        );
+    }
+}
@@ -1,63 +1,1280 @@
-use crate::config::Config;
-use std::io::Write;
+use crate::api;
+use crate::config::{Backend, Config};
+use crate::error::IdrError;
+use crate::log::{info, warn};
+use crate::signal;
+use serde::{Deserialize, Serialize};
+use std::ffi::OsStr;
+use std::io::{Read, Write};
+#[cfg(unix)]
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
 
-pub fn run(prompt: &str, config: &Config) -> Option<String> {
-    let mut child = Command::new("claude")
-        .args(build_command(config))
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Usage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+impl std::ops::Add for Usage {
+    type Output = Usage;
+
+    fn add(self, other: Usage) -> Usage {
+        Usage {
+            input_tokens: self.input_tokens + other.input_tokens,
+            output_tokens: self.output_tokens + other.output_tokens,
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct ClaudeResult {
+    pub text: String,
+    pub usage: Option<Usage>,
+    pub cost_usd: Option<f64>,
+    pub session_id: Option<String>,
+    /// The model that produced the result, after any fallbacks.
+    pub model: String,
+}
+
+#[derive(Deserialize)]
+struct Envelope {
+    result: String,
+    #[serde(default)]
+    is_error: bool,
+    session_id: Option<String>,
+    total_cost_usd: Option<f64>,
+    usage: Option<EnvelopeUsage>,
+}
+
+#[derive(Deserialize)]
+struct EnvelopeUsage {
+    #[serde(default)]
+    input_tokens: u64,
+    #[serde(default)]
+    cache_creation_input_tokens: u64,
+    #[serde(default)]
+    cache_read_input_tokens: u64,
+    #[serde(default)]
+    output_tokens: u64,
+}
+
+pub fn run(prompt: &str, config: &Config) -> Result<ClaudeResult, IdrError> {
+    run_with_session(prompt, config, None)
+}
+
+/// Like [`run`], but first tries to answer the prompt inside a fork of the
+/// given Claude Code session (`--resume`), so the model sees the original
+/// conversation. Falls back to a stateless call if resuming fails. The
+/// error is that of the last model tried; callers decide how to report it.
+pub fn run_with_session(
+    prompt: &str,
+    config: &Config,
+    session_id: Option<&str>,
+) -> Result<ClaudeResult, IdrError> {
+    if let Some(id) = session_id.filter(|_| config.backend == Backend::Cli) {
+        match run_model(prompt, config, &config.model, Some(id)) {
+            Ok(mut result) => {
+                result.text = sanitize_output(&result.text);
+                result.model = config.model.clone();
+                return Ok(result);
+            }
+            Err(e) => warn!("resuming session {id} failed ({e}), falling back to a stateless call"),
+        }
+    }
+
+    let models = model_chain(config);
+    let mut last_error = None;
+    for (i, model) in models.iter().enumerate() {
+        match run_model(prompt, config, model, None) {
+            Ok(mut result) => {
+                if i > 0 {
+                    info!("generated with fallback model {model}");
+                }
+                result.text = sanitize_output(&result.text);
+                result.model = model.clone();
+                return Ok(result);
+            }
+            Err(e) if is_model_error(&e.to_string()) && i + 1 < models.len() => {
+                warn!(
+                    "model {model} was rejected ({e}), retrying with {}",
+                    models[i + 1]
+                );
+                last_error = Some(e);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| IdrError::claude("no model configured")))
+}
+
+fn run_model(
+    prompt: &str,
+    config: &Config,
+    model: &str,
+    resume: Option<&str>,
+) -> Result<ClaudeResult, IdrError> {
+    if config.backend == Backend::Api {
+        return api::run(prompt, config, model).map_err(IdrError::claude);
+    }
+    let stdout = run_program(
+        launcher(config).as_os_str(),
+        &build_command(config, model, resume),
+        prompt,
+        HEARTBEAT_INTERVAL,
+        Duration::from_secs(config.timeout_secs),
+    )?;
+    parse_output(&stdout)
+}
+
+/// The configured model followed by `model_fallbacks`, without duplicates.
+fn model_chain(config: &Config) -> Vec<String> {
+    let mut chain = vec![config.model.clone()];
+    for model in &config.model_fallbacks {
+        if !chain.contains(model) {
+            chain.push(model.clone());
+        }
+    }
+    chain
+}
+
+/// Whether a failure message says the requested model is unknown or not
+/// available to this account, as opposed to timeouts, authentication, or
+/// IO problems that another model would not fix. The phrase has to sit
+/// near the word "model", so "file not found" or "access token expired" in
+/// a message that merely names the model do not count.
+fn is_model_error(message: &str) -> bool {
+    let lower = message.to_lowercase();
+    const NOT_MODEL: [&str; 11] = [
+        "timed out",
+        "api key",
+        "authentication",
+        "log in",
+        "login",
+        "cannot start",
+        "token",
+        "permission denied",
+        "no such file",
+        "os error",
+        "network",
+    ];
+    const MODEL_PROBLEM: [&str; 8] = [
+        "not found",
+        "not_found",
+        "does not exist",
+        "not available",
+        "not supported",
+        "invalid model",
+        "unknown model",
+        "does not have access",
+    ];
+    /// How far from the phrase, in bytes, "model" may be.
+    const NEAR: usize = 60;
+    if NOT_MODEL.iter().any(|p| lower.contains(p)) {
+        return false;
+    }
+    MODEL_PROBLEM.iter().any(|phrase| {
+        lower.match_indices(phrase).any(|(at, _)| {
+            let mut start = at.saturating_sub(NEAR);
+            while !lower.is_char_boundary(start) {
+                start -= 1;
+            }
+            let mut end = (at + phrase.len() + NEAR).min(lower.len());
+            while !lower.is_char_boundary(end) {
+                end += 1;
+            }
+            lower[start..end].contains("model")
+        })
+    })
+}
+
+/// Removes ANSI CSI/OSC escape sequences, carriage returns, and other control
+/// characters (keeping newline and tab), then drops trailing blank lines.
+fn sanitize_output(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\u{1b}' => match chars.next() {
+                // CSI: parameters and intermediates, then one final byte.
+                Some('[') => {
+                    for c in chars.by_ref() {
+                        if ('\u{40}'..='\u{7e}').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+                // OSC: terminated by BEL or ST (ESC \).
+                Some(']') => {
+                    while let Some(c) = chars.next() {
+                        if c == '\u{7}' {
+                            break;
+                        }
+                        if c == '\u{1b}' && chars.peek() == Some(&'\\') {
+                            chars.next();
+                            break;
+                        }
+                    }
+                }
+                // Other escapes: optional intermediates, then one final byte.
+                Some(c) if ('\u{20}'..='\u{2f}').contains(&c) => {
+                    chars.next();
+                }
+                _ => {}
+            },
+            '\n' | '\t' => out.push(c),
+            c if c.is_control() => {}
+            c => out.push(c),
+        }
+    }
+
+    let mut lines: Vec<&str> = out.split('\n').collect();
+    while lines.len() > 1 && lines.last().is_some_and(|l| l.trim().is_empty()) {
+        lines.pop();
+    }
+    let mut sanitized = lines.join("\n");
+    if out.ends_with('\n') && !sanitized.is_empty() {
+        sanitized.push('\n');
+    }
+    sanitized
+}
+
+/// Checks that the configured claude binary exists and, when `check_version`
+/// is set, that `claude --version` succeeds. Returns an actionable message on
+/// failure so the run can be skipped before any prompt is built.
+pub fn probe(config: &Config, check_version: bool) -> Result<(), String> {
+    probe_bin(
+        &config.claude_bin,
+        std::env::var_os("PATH").as_deref(),
+        check_version,
+    )
+}
+
+pub(crate) fn probe_bin(
+    bin: &Path,
+    path_var: Option<&OsStr>,
+    check_version: bool,
+) -> Result<(), String> {
+    let Some(resolved) = find_executable(bin, path_var) else {
+        return Err(if bin.components().count() > 1 {
+            format!(
+                "claude_bin {} not found or not executable; fix the path in your config",
+                bin.display()
+            )
+        } else {
+            let searched: Vec<String> = launcher_names(bin, cfg!(windows))
+                .iter()
+                .map(|name| name.display().to_string())
+                .collect();
+            format!(
+                "{} not found on PATH; install it (https://docs.anthropic.com/en/docs/claude-code) or set claude_bin",
+                searched.join(", ")
+            )
+        });
+    };
+
+    if !check_version {
+        return Ok(());
+    }
+    let output = Command::new(&resolved)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("cannot run {} --version: {e}", resolved.display()))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} --version failed ({}); run `claude` once to log in, or reinstall it: {}",
+            resolved.display(),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+/// The program to spawn for `claude_bin`. On Windows the CLI is usually a
+/// `claude.cmd` shim that `Command::new("claude")` cannot start, so bare
+/// names are resolved against PATH first; elsewhere the name is used as is.
+fn launcher(config: &Config) -> PathBuf {
+    if cfg!(windows) {
+        find_executable(&config.claude_bin, std::env::var_os("PATH").as_deref())
+            .unwrap_or_else(|| config.claude_bin.clone())
+    } else {
+        config.claude_bin.clone()
+    }
+}
+
+/// Resolves `bin` like `which`: paths with a directory component are checked
+/// directly, bare names are searched for in `path_var`.
+pub(crate) fn find_executable(bin: &Path, path_var: Option<&OsStr>) -> Option<PathBuf> {
+    resolve_launcher(bin, path_var, cfg!(windows), is_executable)
+}
+
+/// File names tried for a bare `bin`, in order. Windows needs an explicit
+/// extension unless the user already gave one.
+fn launcher_names(bin: &Path, windows: bool) -> Vec<PathBuf> {
+    if windows && bin.extension().is_none() {
+        ["exe", "cmd", "bat"]
+            .iter()
+            .map(|ext| bin.with_extension(ext))
+            .collect()
+    } else {
+        vec![bin.to_path_buf()]
+    }
+}
+
+fn resolve_launcher(
+    bin: &Path,
+    path_var: Option<&OsStr>,
+    windows: bool,
+    is_launcher: impl Fn(&Path) -> bool,
+) -> Option<PathBuf> {
+    if bin.components().count() > 1 {
+        return is_launcher(bin).then(|| bin.to_path_buf());
+    }
+    let names = launcher_names(bin, windows);
+    std::env::split_paths(path_var?)
+        .flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+        .find(|candidate| is_launcher(candidate))
+}
+
+fn is_executable(path: &Path) -> bool {
+    let Ok(meta) = path.metadata() else {
+        return false;
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        meta.is_file() && meta.permissions().mode() & 0o111 != 0
+    }
+    #[cfg(not(unix))]
+    {
+        meta.is_file()
+    }
+}
+
+/// Parses the `--output-format json` envelope. CLIs that ignore the flag
+/// print plain text, which is passed through as the result unchanged.
+fn parse_output(stdout: &str) -> Result<ClaudeResult, IdrError> {
+    let Ok(envelope) = serde_json::from_str::<Envelope>(stdout.trim()) else {
+        return Ok(ClaudeResult {
+            text: stdout.to_string(),
+            ..ClaudeResult::default()
+        });
+    };
+
+    if envelope.is_error {
+        return Err(IdrError::claude(format!(
+            "claude CLI reported an error: {}",
+            envelope.result
+        )));
+    }
+
+    Ok(ClaudeResult {
+        text: envelope.result,
+        usage: envelope.usage.map(|u| Usage {
+            input_tokens: u.input_tokens
+                + u.cache_creation_input_tokens
+                + u.cache_read_input_tokens,
+            output_tokens: u.output_tokens,
+        }),
+        cost_usd: envelope.total_cost_usd,
+        session_id: envelope.session_id,
+        ..ClaudeResult::default()
+    })
+}
+
+/// Runs `program` with `prompt` on stdin. The prompt is written from a
+/// separate thread so a child that fills its stdout pipe before reading all
+/// of stdin cannot deadlock us; stdin is closed as soon as the write ends.
+fn run_program(
+    program: &OsStr,
+    args: &[String],
+    prompt: &str,
+    heartbeat: Duration,
+    timeout: Duration,
+) -> Result<String, IdrError> {
+    let mut command = Command::new(program);
+    command
+        .args(args)
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
-        .map_err(|e| eprintln!("claude-idr: cannot start claude CLI: {e}"))
-        .ok()?;
+        .stderr(Stdio::piped());
+    // Own process group, so an interrupt can take down claude and anything
+    // it spawned in one go.
+    #[cfg(unix)]
+    command.process_group(0);
+    let mut child = command.spawn().map_err(IdrError::ClaudeSpawn)?;
+    let _tracked = signal::track_child(child.id());
 
-    if let Some(mut stdin) = child.stdin.take()
-        && let Err(e) = stdin.write_all(prompt.as_bytes())
-    {
-        eprintln!("claude-idr: warning: failed to write prompt: {e}");
-        if let Err(e) = child.kill() {
-            eprintln!("claude-idr: warning: failed to kill claude process: {e}");
+    let (Some(mut stdin), Some(stdout), Some(mut stderr)) =
+        (child.stdin.take(), child.stdout.take(), child.stderr.take())
+    else {
+        let _ = child.kill();
+        let _ = child.wait();
+        return Err(IdrError::claude("cannot attach to claude CLI pipes"));
+    };
+
+    let prompt = prompt.to_owned();
+    let stdin_writer = thread::spawn(move || stdin.write_all(prompt.as_bytes()));
+    let stderr_reader = thread::spawn(move || {
+        let mut buf = Vec::new();
+        let _ = stderr.read_to_end(&mut buf);
+        buf
+    });
+
+    let Some(stdout) = read_with_progress(stdout, heartbeat, timeout) else {
+        // The whole group, or whatever claude spawned outlives the timeout.
+        #[cfg(unix)]
+        // SAFETY: kill(2) on the group `command` put the child in.
+        let killed = match unsafe { libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL) } {
+            0 => Ok(()),
+            _ => Err(std::io::Error::last_os_error()),
+        };
+        #[cfg(not(unix))]
+        let killed = child.kill();
+        if let Err(e) = killed {
+            warn!("failed to kill claude process: {e}");
         }
-        if let Err(e) = child.wait() {
-            eprintln!("claude-idr: warning: failed to wait for claude process: {e}");
+        let _ = child.wait();
+        return Err(IdrError::ClaudeTimeout {
+            secs: timeout.as_secs(),
+        });
+    };
+    let write_result = stdin_writer
+        .join()
+        .unwrap_or_else(|_| Err(std::io::Error::other("prompt writer panicked")));
+    let stderr = stderr_reader.join().unwrap_or_default();
+
+    let status = child
+        .wait()
+        .map_err(|e| IdrError::claude(format!("failed to wait for claude CLI: {e}")))?;
+    let stderr = String::from_utf8_lossy(&stderr).into_owned();
+    match (status.success(), write_result) {
+        (true, Ok(())) => Ok(String::from_utf8_lossy(&stdout).into_owned()),
+        (true, Err(e)) => Err(IdrError::claude(format!(
+            "claude CLI exited before reading the whole prompt: {e}"
+        ))),
+        (false, Ok(())) => Err(IdrError::ClaudeFailed {
+            reason: format!("claude CLI failed ({status})"),
+            stderr,
+        }),
+        (false, Err(e)) => Err(IdrError::ClaudeFailed {
+            reason: format!(
+                "claude CLI failed ({status}) while the prompt was being written ({e})"
+            ),
+            stderr,
+        }),
+    }
+}
+
+/// Reads `source` to the end on a background thread, emitting a heartbeat
+/// every `heartbeat` so a slow CLI can be told apart from a hung one.
+/// Returns `None` if the source is still open after `timeout`.
+fn read_with_progress(
+    mut source: impl Read + Send + 'static,
+    heartbeat: Duration,
+    timeout: Duration,
+) -> Option<Vec<u8>> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut chunk = [0u8; 8192];
+        loop {
+            match source.read(&mut chunk) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    if tx.send(chunk[..n].to_vec()).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+
+    let started = Instant::now();
+    let deadline = started + timeout;
+    let mut next_beat = started + heartbeat;
+    let mut buf = Vec::new();
+    loop {
+        let wake = next_beat.min(deadline);
+        match rx.recv_timeout(wake.saturating_duration_since(Instant::now())) {
+            Ok(chunk) => buf.extend_from_slice(&chunk),
+            Err(RecvTimeoutError::Timeout) if Instant::now() >= deadline => return None,
+            Err(RecvTimeoutError::Timeout) => {
+                info!("{}", format_progress(&buf, started.elapsed()));
+                next_beat += heartbeat;
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
         }
-        return None;
     }
+    Some(buf)
+}
 
-    let output = child
-        .wait_with_output()
-        .map_err(|e| eprintln!("claude-idr: warning: failed to wait for claude CLI: {e}"))
-        .ok()?;
-    if output.status.success() {
-        Some(String::from_utf8_lossy(&output.stdout).into_owned())
+fn format_progress(received: &[u8], elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    if received.is_empty() {
+        return format!("waiting for claude ({secs}s elapsed)...");
+    }
+    let lines = received.iter().filter(|&&b| b == b'\n').count();
+    format!(
+        "received {} ({lines} lines, {secs}s elapsed)...",
+        format_bytes(received.len())
+    )
+}
+
+fn format_bytes(n: usize) -> String {
+    const KB: f64 = 1024.0;
+    let n_f = n as f64;
+    if n_f < KB {
+        format!("{n} B")
+    } else if n_f < KB * KB {
+        format!("{:.1} KB", n_f / KB)
     } else {
-        eprintln!(
-            "claude-idr: claude CLI failed: {}",
-            String::from_utf8_lossy(&output.stderr)
+        format!("{:.1} MB", n_f / (KB * KB))
+    }
+}
+
+/// How a prompt is sent to the configured model: the CLI command line,
+/// quoted for a shell, or the API endpoint.
+pub fn command_line(config: &Config) -> String {
+    if config.backend == Backend::Api {
+        return format!(
+            "POST {}/v1/messages (model {})",
+            config.api_base_url.trim_end_matches('/'),
+            config.model
         );
-        None
+    }
+    std::iter::once(launcher(config).display().to_string())
+        .chain(build_command(config, &config.model, None))
+        .map(|arg| shell_quote(&arg))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn shell_quote(arg: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "-_./=:,@%+".contains(c);
+    if !arg.is_empty() && arg.chars().all(plain) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
     }
 }
 
-fn build_command(config: &Config) -> Vec<String> {
-    vec![
-        "-p".to_string(),
-        "--model".to_string(),
-        config.model.clone(),
-    ]
+fn build_command(config: &Config, model: &str, resume: Option<&str>) -> Vec<String> {
+    let mut args = vec!["-p".to_string(), "--model".to_string(), model.to_string()];
+    if let Some(session_id) = resume {
+        args.extend([
+            "--resume".to_string(),
+            session_id.to_string(),
+            "--fork-session".to_string(),
+        ]);
+    }
+    if config.json_output {
+        args.extend(["--output-format".to_string(), "json".to_string()]);
+    }
+    args.extend(config.claude_args.iter().cloned());
+    args
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    #[cfg(unix)]
+    use crate::testutil::write_script;
+    use tempfile::TempDir;
+
+    #[cfg(unix)]
+    #[test]
+    fn find_executable_searches_path_entries_in_order() {
+        let first = TempDir::new().unwrap();
+        let second = TempDir::new().unwrap();
+        std::fs::write(first.path().join("claude"), "not executable").unwrap();
+        let expected = write_script(second.path(), "claude", "exit 0\n");
+        let path_var = std::env::join_paths([first.path(), second.path()]).unwrap();
+
+        let found = find_executable(Path::new("claude"), Some(&path_var));
+
+        assert_eq!(found, Some(expected));
+    }
+
+    #[test]
+    fn find_executable_returns_none_without_path() {
+        assert!(find_executable(Path::new("claude"), None).is_none());
+    }
+
+    fn windows_path(dirs: &[&str]) -> std::ffi::OsString {
+        std::env::join_paths(dirs).unwrap()
+    }
+
+    #[test]
+    fn resolve_launcher_prefers_exe_on_windows() {
+        let path_var = windows_path(&["/bin"]);
+        let found = resolve_launcher(Path::new("claude"), Some(&path_var), true, |p| {
+            p == Path::new("/bin/claude.exe") || p == Path::new("/bin/claude.cmd")
+        });
+        assert_eq!(found, Some(PathBuf::from("/bin/claude.exe")));
+    }
+
+    #[test]
+    fn resolve_launcher_finds_cmd_shim_on_windows() {
+        let path_var = windows_path(&["/npm"]);
+        let found = resolve_launcher(Path::new("claude"), Some(&path_var), true, |p| {
+            p == Path::new("/npm/claude.cmd")
+        });
+        assert_eq!(found, Some(PathBuf::from("/npm/claude.cmd")));
+    }
+
+    #[test]
+    fn resolve_launcher_finds_bat_on_windows() {
+        let path_var = windows_path(&["/a", "/b"]);
+        let found = resolve_launcher(Path::new("claude"), Some(&path_var), true, |p| {
+            p == Path::new("/b/claude.bat")
+        });
+        assert_eq!(found, Some(PathBuf::from("/b/claude.bat")));
+    }
+
+    #[test]
+    fn resolve_launcher_ignores_extensionless_file_on_windows() {
+        let path_var = windows_path(&["/bin"]);
+        let found = resolve_launcher(Path::new("claude"), Some(&path_var), true, |p| {
+            p == Path::new("/bin/claude")
+        });
+        assert_eq!(found, None);
+    }
+
+    #[test]
+    fn resolve_launcher_keeps_explicit_extension_and_configured_path() {
+        let path_var = windows_path(&["/bin"]);
+        let found = resolve_launcher(Path::new("claude.cmd"), Some(&path_var), true, |p| {
+            p == Path::new("/bin/claude.cmd")
+        });
+        assert_eq!(found, Some(PathBuf::from("/bin/claude.cmd")));
+
+        let configured = Path::new("/opt/claude/claude.ps1");
+        let found = resolve_launcher(configured, Some(&path_var), true, |p| p == configured);
+        assert_eq!(found, Some(configured.to_path_buf()));
+    }
+
+    #[test]
+    fn launcher_names_lists_what_is_searched() {
+        assert_eq!(
+            launcher_names(Path::new("claude"), true),
+            vec![
+                PathBuf::from("claude.exe"),
+                PathBuf::from("claude.cmd"),
+                PathBuf::from("claude.bat")
+            ]
+        );
+        assert_eq!(
+            launcher_names(Path::new("claude"), false),
+            vec![PathBuf::from("claude")]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn probe_bin_reports_missing_binary_on_path() {
+        let dir = TempDir::new().unwrap();
+        let path_var = dir.path().as_os_str().to_owned();
+
+        let err = probe_bin(Path::new("claude"), Some(&path_var), false).unwrap_err();
+
+        assert_eq!(
+            err,
+            "claude not found on PATH; install it (https://docs.anthropic.com/en/docs/claude-code) or set claude_bin"
+        );
+    }
+
+    #[test]
+    fn probe_bin_reports_missing_configured_path() {
+        let err = probe_bin(Path::new("/nonexistent/bin/claude"), None, false).unwrap_err();
+
+        assert!(err.starts_with("claude_bin /nonexistent/bin/claude not found"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn probe_bin_reports_failing_version_check() {
+        let dir = TempDir::new().unwrap();
+        let script = write_script(dir.path(), "claude", "echo 'Invalid API key' >&2\nexit 1\n");
+
+        let err = probe_bin(&script, None, true).unwrap_err();
+
+        assert!(err.contains("--version failed"));
+        assert!(err.contains("run `claude` once to log in"));
+        assert!(err.contains("Invalid API key"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn probe_bin_accepts_working_binary() {
+        let dir = TempDir::new().unwrap();
+        let script = write_script(dir.path(), "claude", "echo '2.0.0 (Claude Code)'\n");
+
+        assert!(probe_bin(&script, None, true).is_ok());
+    }
 
     #[test]
     fn build_command_uses_model_from_config() {
-        let mut config = Config::default();
-        config.model = "opus".to_string();
-        let args = build_command(&config);
+        let config = Config {
+            model: "opus".to_string(),
+            json_output: false,
+            ..Config::default()
+        };
+        let args = build_command(&config, &config.model, None);
 
         assert_eq!(args, vec!["-p", "--model", "opus"]);
     }
+
+    #[test]
+    fn build_command_requests_json_output_by_default() {
+        let args = build_command(&Config::default(), "sonnet", None);
+
+        assert_eq!(
+            args,
+            vec!["-p", "--model", "sonnet", "--output-format", "json"]
+        );
+    }
+
+    #[test]
+    fn build_command_appends_user_args_after_builtins() {
+        let config = Config {
+            json_output: false,
+            claude_args: vec!["--append-system-prompt".to_string(), "Be terse".to_string()],
+            ..Config::default()
+        };
+
+        let args = build_command(&config, &config.model, None);
+
+        assert_eq!(
+            args,
+            vec![
+                "-p",
+                "--model",
+                "sonnet",
+                "--append-system-prompt",
+                "Be terse"
+            ]
+        );
+    }
+
+    #[test]
+    fn build_command_keeps_print_flag_first_with_user_args() {
+        let config = Config {
+            claude_args: vec!["--model".to_string(), "opus".to_string()],
+            ..Config::default()
+        };
+
+        let args = build_command(&config, &config.model, None);
+
+        assert_eq!(args[0], "-p");
+        assert_eq!(&args[args.len() - 2..], ["--model", "opus"]);
+    }
+
+    #[test]
+    fn build_command_resumes_session_after_print_flag() {
+        let config = Config {
+            json_output: false,
+            claude_args: vec!["--verbose".to_string()],
+            ..Config::default()
+        };
+
+        let args = build_command(&config, "sonnet", Some("4f9c2a1e"));
+
+        assert_eq!(
+            args,
+            vec![
+                "-p",
+                "--model",
+                "sonnet",
+                "--resume",
+                "4f9c2a1e",
+                "--fork-session",
+                "--verbose"
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn command_line_quotes_what_a_shell_would_split() {
+        let config = Config {
+            claude_args: vec![
+                "--append-system-prompt".to_string(),
+                "Be brief.".to_string(),
+            ],
+            ..Config::default()
+        };
+        assert_eq!(
+            command_line(&config),
+            "claude -p --model sonnet --output-format json --append-system-prompt 'Be brief.'"
+        );
+
+        let api = Config {
+            backend: Backend::Api,
+            ..Config::default()
+        };
+        assert_eq!(
+            command_line(&api),
+            "POST https://api.anthropic.com/v1/messages (model sonnet)"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_with_session_falls_back_to_stateless_call() {
+        let dir = TempDir::new().unwrap();
+        let script = write_script(
+            dir.path(),
+            "fake-claude",
+            "cat >/dev/null\ncase \"$*\" in *--resume*) echo 'No conversation found' >&2; exit 1;; esac\necho stateless\n",
+        );
+        let config = Config {
+            claude_bin: script,
+            json_output: false,
+            ..Config::default()
+        };
+
+        let result = run_with_session("prompt", &config, Some("missing-id")).unwrap();
+
+        assert_eq!(result.text, "stateless\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_with_session_uses_resumed_answer() {
+        let dir = TempDir::new().unwrap();
+        let script = write_script(
+            dir.path(),
+            "fake-claude",
+            "cat >/dev/null\ncase \"$*\" in *'--resume abc'*) echo resumed; exit 0;; esac\nexit 1\n",
+        );
+        let config = Config {
+            claude_bin: script,
+            json_output: false,
+            ..Config::default()
+        };
+
+        let result = run_with_session("prompt", &config, Some("abc")).unwrap();
+
+        assert_eq!(result.text, "resumed\n");
+    }
+
+    #[test]
+    fn parse_output_reads_success_envelope() {
+        let result =
+            parse_output(include_str!("../tests/fixtures/claude-json-success.json")).unwrap();
+
+        assert_eq!(result.text, "## 変更概要\n\nRetry logic was added.");
+        assert_eq!(
+            result.usage,
+            Some(Usage {
+                input_tokens: 13830,
+                output_tokens: 412,
+            })
+        );
+        assert_eq!(result.cost_usd, Some(0.0421));
+        assert_eq!(
+            result.session_id.as_deref(),
+            Some("4f9c2a1e-7b3d-4e8a-9c1f-2d6e8b0a5c3f")
+        );
+    }
+
+    #[test]
+    fn parse_output_rejects_error_envelope() {
+        assert_eq!(
+            parse_output(include_str!("../tests/fixtures/claude-json-error.json"))
+                .unwrap_err()
+                .to_string(),
+            "claude CLI reported an error: API Error: overloaded"
+        );
+    }
+
+    #[test]
+    fn parse_output_falls_back_to_plain_text() {
+        let stdout = "## 変更概要\n\nplain output from an older CLI\n";
+
+        let result = parse_output(stdout).unwrap();
+
+        assert_eq!(result.text, stdout);
+        assert!(result.usage.is_none());
+        assert!(result.cost_usd.is_none());
+    }
+
+    #[test]
+    fn parse_output_treats_non_envelope_json_as_text() {
+        let stdout = r#"{"summary": "not an envelope"}"#;
+
+        let result = parse_output(stdout).unwrap();
+
+        assert_eq!(result.text, stdout);
+    }
+
+    #[test]
+    fn model_chain_appends_fallbacks_without_duplicates() {
+        let config = Config {
+            model: "opus".to_string(),
+            model_fallbacks: vec![
+                "sonnet".to_string(),
+                "opus".to_string(),
+                "haiku".to_string(),
+            ],
+            ..Config::default()
+        };
+
+        assert_eq!(model_chain(&config), vec!["opus", "sonnet", "haiku"]);
+    }
+
+    #[test]
+    fn is_model_error_detects_unknown_or_unavailable_models() {
+        assert!(is_model_error(
+            "claude CLI failed (exit status: 1): API Error: 404 {\"type\":\"error\",\"error\":{\"type\":\"not_found_error\",\"message\":\"model: claude-opus-9\"}}"
+        ));
+        assert!(is_model_error(
+            "claude CLI reported an error: Claude Opus is not available with the Claude Pro plan. Switch to a different model."
+        ));
+        assert!(is_model_error(
+            "HTTP 403: Your organization does not have access to this model"
+        ));
+    }
+
+    #[test]
+    fn is_model_error_ignores_timeouts_and_auth_failures() {
+        assert!(!is_model_error(
+            "claude CLI timed out after 300s while running model opus"
+        ));
+        assert!(!is_model_error(
+            "claude CLI reported an error: Invalid API key · Please run /login"
+        ));
+        assert!(!is_model_error(
+            "claude CLI failed (exit status: 1): overloaded"
+        ));
+        assert!(!is_model_error("HTTP 401: invalid x-api-key"));
+        assert!(!is_model_error(
+            "HTTP 401: OAuth access token expired; request for model opus was not authorized"
+        ));
+    }
+
+    #[test]
+    fn is_model_error_ignores_io_failures_that_mention_permission_or_access() {
+        assert!(!is_model_error(
+            "cannot write /work/model/idr-01.md: Permission denied (os error 13)"
+        ));
+        assert!(!is_model_error(
+            "claude CLI failed (exit status: 1): no access to /home/u/.claude; running model opus"
+        ));
+        assert!(!is_model_error(
+            "claude CLI failed (exit status: 1): service unavailable, network unreachable (model opus)"
+        ));
+        assert!(!is_model_error(
+            "claude CLI failed (exit status: 1): access denied by proxy; retry later. Model: opus"
+        ));
+        assert!(!is_model_error(
+            "claude CLI failed (exit status: 1): settings file not found at /etc/claude/managed.json, \
+             a long way from the words that mention the requested model"
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_falls_back_to_next_model_on_model_error() {
+        let dir = TempDir::new().unwrap();
+        let script = write_script(
+            dir.path(),
+            "fake-claude",
+            "cat >/dev/null\ncase \"$*\" in *opus*) echo 'model opus is not available' >&2; exit 1;; esac\necho \"ok from $3\"\n",
+        );
+        let config = Config {
+            claude_bin: script,
+            json_output: false,
+            model: "opus".to_string(),
+            model_fallbacks: vec!["sonnet".to_string(), "haiku".to_string()],
+            ..Config::default()
+        };
+
+        let result = run("prompt", &config).unwrap();
+
+        assert_eq!(result.text, "ok from sonnet\n");
+        assert_eq!(result.model, "sonnet");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_stops_when_fallback_chain_is_exhausted() {
+        let dir = TempDir::new().unwrap();
+        let calls = dir.path().join("calls");
+        let script = write_script(
+            dir.path(),
+            "fake-claude",
+            &format!(
+                "cat >/dev/null\necho \"$3\" >> {}\necho 'model not found' >&2\nexit 1\n",
+                calls.display()
+            ),
+        );
+        let config = Config {
+            claude_bin: script,
+            json_output: false,
+            model: "opus".to_string(),
+            model_fallbacks: vec!["sonnet".to_string()],
+            ..Config::default()
+        };
+
+        assert!(matches!(
+            run("prompt", &config),
+            Err(IdrError::ClaudeFailed { stderr, .. }) if stderr.contains("model not found")
+        ));
+        assert_eq!(std::fs::read_to_string(calls).unwrap(), "opus\nsonnet\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_does_not_fall_back_on_other_failures() {
+        let dir = TempDir::new().unwrap();
+        let calls = dir.path().join("calls");
+        let script = write_script(
+            dir.path(),
+            "fake-claude",
+            &format!(
+                "cat >/dev/null\necho \"$3\" >> {}\necho 'Invalid API key' >&2\nexit 1\n",
+                calls.display()
+            ),
+        );
+        let config = Config {
+            claude_bin: script,
+            json_output: false,
+            model_fallbacks: vec!["haiku".to_string()],
+            ..Config::default()
+        };
+
+        assert!(matches!(
+            run("prompt", &config),
+            Err(IdrError::ClaudeFailed { stderr, .. }) if stderr.contains("Invalid API key")
+        ));
+        assert_eq!(std::fs::read_to_string(calls).unwrap(), "sonnet\n");
+    }
+
+    #[test]
+    fn usage_adds_componentwise() {
+        let a = Usage {
+            input_tokens: 10,
+            output_tokens: 2,
+        };
+        let b = Usage {
+            input_tokens: 5,
+            output_tokens: 3,
+        };
+        assert_eq!(
+            a + b,
+            Usage {
+                input_tokens: 15,
+                output_tokens: 5,
+            }
+        );
+    }
+
+    #[test]
+    fn sanitize_output_strips_color_codes() {
+        assert_eq!(
+            sanitize_output("\u{1b}[1;31mwarning\u{1b}[0m: ok\n"),
+            "warning: ok\n"
+        );
+    }
+
+    #[test]
+    fn sanitize_output_strips_cursor_movement_and_spinner_remnants() {
+        assert_eq!(
+            sanitize_output("\u{1b}[2K\u{1b}[1G⠋ thinking\r\u{1b}[2K## 変更概要\r\n"),
+            "⠋ thinking## 変更概要\n"
+        );
+    }
+
+    #[test]
+    fn sanitize_output_strips_osc_sequences() {
+        assert_eq!(
+            sanitize_output("\u{1b}]0;claude\u{7}title\u{1b}]8;;http://x\u{1b}\\link"),
+            "titlelink"
+        );
+    }
+
+    #[test]
+    fn sanitize_output_strips_other_control_chars_but_keeps_tabs() {
+        assert_eq!(
+            sanitize_output("a\u{0}b\u{8}c\td\u{7f}\u{1b}(Be"),
+            "abc\tde"
+        );
+    }
+
+    #[test]
+    fn sanitize_output_trims_trailing_blank_lines() {
+        assert_eq!(sanitize_output("body\n\n  \n\n"), "body\n");
+    }
+
+    #[test]
+    fn sanitize_output_leaves_clean_text_untouched() {
+        let clean = "## 変更概要\n\n- item\twith tab\n\n```diff\n+ added\n```\n";
+        assert_eq!(sanitize_output(clean), clean);
+    }
+
+    #[test]
+    fn format_bytes_picks_unit() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(2355), "2.3 KB");
+        assert_eq!(format_bytes(3 * 1024 * 1024), "3.0 MB");
+    }
+
+    #[test]
+    fn format_progress_reports_waiting_before_first_byte() {
+        assert_eq!(
+            format_progress(b"", Duration::from_secs(12)),
+            "waiting for claude (12s elapsed)..."
+        );
+    }
+
+    #[test]
+    fn format_progress_reports_bytes_and_lines() {
+        assert_eq!(
+            format_progress(b"## a\n## b\n", Duration::from_secs(5)),
+            "received 10 B (2 lines, 5s elapsed)..."
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_program_collects_slowly_dribbled_output() {
+        let dir = TempDir::new().unwrap();
+        let script = write_script(
+            dir.path(),
+            "fake-claude",
+            "cat >/dev/null\nfor i in 1 2 3; do echo \"line $i\"; sleep 0.1; done\n",
+        );
+
+        let result = run_program(
+            script.as_os_str(),
+            &[],
+            "prompt",
+            Duration::from_millis(50),
+            Duration::from_secs(10),
+        );
+
+        assert_eq!(result.unwrap(), "line 1\nline 2\nline 3\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_program_drains_large_stderr_without_deadlock() {
+        let dir = TempDir::new().unwrap();
+        let script = write_script(
+            dir.path(),
+            "fake-claude",
+            "cat >/dev/null\nhead -c 300000 /dev/zero | tr '\\0' e >&2\necho partial\nexit 1\n",
+        );
+
+        let result = run_program(
+            script.as_os_str(),
+            &[],
+            "prompt",
+            HEARTBEAT_INTERVAL,
+            Duration::from_secs(10),
+        );
+
+        match result.unwrap_err() {
+            IdrError::ClaudeFailed { reason, stderr } => {
+                assert_eq!(reason, "claude CLI failed (exit status: 1)");
+                assert_eq!(stderr.len(), 300000);
+            }
+            other => panic!("expected ClaudeFailed, got {other:?}"),
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_program_reports_broken_pipe_when_child_ignores_stdin() {
+        let dir = TempDir::new().unwrap();
+        let script = write_script(dir.path(), "fake-claude", "exit 0\n");
+        let prompt = "x".repeat(4 * 1024 * 1024);
+
+        let result = run_program(
+            script.as_os_str(),
+            &[],
+            &prompt,
+            HEARTBEAT_INTERVAL,
+            Duration::from_secs(10),
+        );
+
+        let err = result.unwrap_err().to_string();
+        assert!(
+            err.starts_with("claude CLI exited before reading the whole prompt"),
+            "{err}"
+        );
+        assert!(err.to_lowercase().contains("broken pipe"), "{err}");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_program_handles_prompt_larger_than_pipe_buffer() {
+        let dir = TempDir::new().unwrap();
+        let script = write_script(dir.path(), "fake-claude", "wc -c\n");
+        let prompt = "x".repeat(1024 * 1024);
+
+        let result = run_program(
+            script.as_os_str(),
+            &[],
+            &prompt,
+            HEARTBEAT_INTERVAL,
+            Duration::from_secs(10),
+        );
+
+        assert_eq!(result.unwrap().trim(), "1048576");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_program_gives_up_after_timeout() {
+        let dir = TempDir::new().unwrap();
+        let script = write_script(
+            dir.path(),
+            "fake-claude",
+            "cat >/dev/null
+(sleep 1; touch \"$0.survived\") &
+exec sleep 30
+",
+        );
+
+        let started = Instant::now();
+        let result = run_program(
+            script.as_os_str(),
+            &[],
+            "prompt",
+            HEARTBEAT_INTERVAL,
+            Duration::from_millis(200),
+        );
+
+        assert!(matches!(result, Err(IdrError::ClaudeTimeout { secs: 0 })));
+        assert!(started.elapsed() < Duration::from_secs(10));
+        // A grandchild still in the group would have touched this by now.
+        thread::sleep(Duration::from_secs(2).saturating_sub(started.elapsed()));
+        assert!(!dir.path().join("fake-claude.survived").exists());
+    }
+
+    #[test]
+    fn run_program_reports_spawn_failure() {
+        let dir = TempDir::new().unwrap();
+
+        let result = run_program(
+            dir.path().join("no-such-claude").as_os_str(),
+            &[],
+            "prompt",
+            HEARTBEAT_INTERVAL,
+            Duration::from_secs(10),
+        );
+
+        assert!(matches!(result, Err(IdrError::ClaudeSpawn(_))));
+    }
 }
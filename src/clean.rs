@@ -0,0 +1,289 @@
+//! `claude-idr clean`: archives or deletes old `planning/YYYY-MM-DD`
+//! directories.
+
+use crate::config::Format;
+use crate::index;
+use crate::latest;
+use crate::lock;
+use crate::monthly;
+use crate::path;
+use crate::sidecar;
+use crate::translate;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_MAX_AGE_DAYS: u32 = 90;
+const ARCHIVE_DIR: &str = "archive";
+
+/// What `clean` would do with the directories under `planning/`.
+#[derive(Debug, Default, PartialEq)]
+pub struct Plan {
+    /// Old enough and safe to move.
+    pub stale: Vec<PathBuf>,
+    /// Old enough but left alone, with the reason.
+    pub skipped: Vec<(PathBuf, String)>,
+}
+
+/// Parses an age such as `90d`, `12w`, or a bare number of days.
+pub fn parse_age(text: &str) -> Option<u32> {
+    let text = text.trim();
+    let (number, factor) = match text.as_bytes().last()? {
+        b'd' => (&text[..text.len() - 1], 1),
+        b'w' => (&text[..text.len() - 1], 7),
+        _ => (text, 1),
+    };
+    number.parse::<u32>().ok()?.checked_mul(factor)
+}
+
+/// The date in a `YYYY-MM-DD` directory name.
+pub fn parse_date_dir(name: &str) -> Option<(i32, u32, u32)> {
+    let bytes = name.as_bytes();
+    if bytes.len() != 10 || bytes[4] != b'-' || bytes[7] != b'-' {
+        return None;
+    }
+    let y = name[..4].parse().ok()?;
+    let m = name[5..7].parse().ok()?;
+    let d = name[8..].parse().ok()?;
+    ((1..=12).contains(&m) && (1..=days_in_month(y, m)).contains(&d)).then_some((y, m, d))
+}
+
+fn days_in_month(y: i32, m: u32) -> u32 {
+    match m {
+        2 if (y % 4 == 0 && y % 100 != 0) || y % 400 == 0 => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since 1970-01-01 (the inverse of `path::epoch_to_civil_utc`).
+pub fn days_from_civil(y: i32, m: u32, d: u32) -> i64 {
+    let y = i64::from(if m <= 2 { y - 1 } else { y });
+    let era = if y >= 0 { y } else { y - 399 } / 400;
+    let yoe = y - era * 400;
+    let mp = i64::from(if m > 2 { m - 3 } else { m + 9 });
+    let doy = (153 * mp + 2) / 5 + i64::from(d) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// Whether `date` lies more than `max_age_days` before `today`.
+pub fn is_older(date: (i32, u32, u32), today: (i32, u32, u32), max_age_days: u32) -> bool {
+    days_from_civil(today.0, today.1, today.2) - days_from_civil(date.0, date.1, date.2)
+        > i64::from(max_age_days)
+}
+
+/// Why `dir` must not be cleaned, if anything: it holds the directory
+/// `.current-sow` points at, or a file claude-idr did not write.
+pub fn skip_reason(dir: &Path, sow_dir: Option<&Path>) -> Option<String> {
+    if let Some(sow_dir) = sow_dir
+        && let Ok(real) = fs::canonicalize(dir)
+        && sow_dir.starts_with(&real)
+    {
+        return Some("referenced by .current-sow".to_string());
+    }
+    foreign_file(dir).map(|name| format!("contains non-IDR file {name}"))
+}
+
+/// The first file under `dir` (recursively, for by-branch layouts) that is
+/// not a record or one of the files maintained next to them.
+fn foreign_file(dir: &Path) -> Option<String> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Some("(unreadable)".to_string());
+    };
+    for entry in entries.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if path.is_dir() {
+            if let Some(name) = foreign_file(&path) {
+                return Some(name);
+            }
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !is_generated(&name) {
+            return Some(name);
+        }
+    }
+    None
+}
+
+fn is_generated(name: &str) -> bool {
+    path::record_number(name, Format::Idr).is_some()
+        || path::record_number(name, Format::Madr).is_some()
+        || monthly::is_journal_name(name)
+        || sidecar::is_sidecar_name(name)
+        || translate::is_file_name(name)
+        || name == index::FILE_NAME
+        || name == latest::FILE_NAME
+        || name == lock::FILE_NAME
+}
+
+/// Date directories in `planning_dir` older than `max_age_days`.
+pub fn plan(
+    planning_dir: &Path,
+    today: (i32, u32, u32),
+    max_age_days: u32,
+    sow_dir: Option<&Path>,
+) -> io::Result<Plan> {
+    let mut dirs: Vec<(PathBuf, (i32, u32, u32))> = fs::read_dir(planning_dir)?
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_dir())
+        .filter_map(|e| {
+            let date = parse_date_dir(e.file_name().to_str()?)?;
+            Some((e.path(), date))
+        })
+        .filter(|(_, date)| is_older(*date, today, max_age_days))
+        .collect();
+    dirs.sort();
+
+    let mut plan = Plan::default();
+    for (dir, _) in dirs {
+        match skip_reason(&dir, sow_dir) {
+            Some(reason) => plan.skipped.push((dir, reason)),
+            None => plan.stale.push(dir),
+        }
+    }
+    Ok(plan)
+}
+
+/// Where `dir` goes when archived: `planning/archive/<year>/<name>`.
+pub fn archive_path(planning_dir: &Path, dir: &Path) -> PathBuf {
+    let name = dir.file_name().unwrap_or_default();
+    let year = name.to_string_lossy().chars().take(4).collect::<String>();
+    planning_dir.join(ARCHIVE_DIR).join(year).join(name)
+}
+
+/// Moves every stale directory into the archive, or deletes it.
+pub fn apply(planning_dir: &Path, plan: &Plan, delete: bool) -> io::Result<()> {
+    for dir in &plan.stale {
+        if delete {
+            fs::remove_dir_all(dir)?;
+            continue;
+        }
+        let target = archive_path(planning_dir, dir);
+        if target.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} is already archived", target.display()),
+            ));
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(dir, &target)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn parse_age_accepts_days_and_weeks() {
+        assert_eq!(parse_age("90d"), Some(90));
+        assert_eq!(parse_age("90"), Some(90));
+        assert_eq!(parse_age("2w"), Some(14));
+        assert_eq!(parse_age("d"), None);
+        assert_eq!(parse_age("3m"), None);
+        assert_eq!(parse_age("-1d"), None);
+    }
+
+    #[test]
+    fn parse_date_dir_validates_calendar() {
+        assert_eq!(parse_date_dir("2026-02-07"), Some((2026, 2, 7)));
+        assert_eq!(parse_date_dir("2024-02-29"), Some((2024, 2, 29)));
+        assert_eq!(parse_date_dir("2026-02-29"), None);
+        assert_eq!(parse_date_dir("2026-13-01"), None);
+        assert_eq!(parse_date_dir("2026-2-7"), None);
+        assert_eq!(parse_date_dir("archive"), None);
+    }
+
+    #[test]
+    fn days_from_civil_matches_epoch() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2000, 3, 1), 11017);
+        assert_eq!(
+            days_from_civil(2026, 1, 1) - days_from_civil(2025, 12, 31),
+            1
+        );
+    }
+
+    #[test]
+    fn is_older_uses_strict_cutoff() {
+        let today = (2026, 5, 10);
+        assert!(!is_older((2026, 2, 9), today, 90));
+        assert!(is_older((2026, 2, 8), today, 90));
+        assert!(!is_older((2026, 5, 10), today, 0));
+        assert!(is_older((2026, 5, 9), today, 0));
+    }
+
+    fn write(dir: &Path, name: &str) {
+        fs::create_dir_all(dir).unwrap();
+        fs::write(dir.join(name), "x").unwrap();
+    }
+
+    #[test]
+    fn skip_reason_flags_foreign_files_and_sow() {
+        let tmp = TempDir::new().unwrap();
+        let records = tmp.path().join("2026-01-01");
+        write(&records, "idr-01.md");
+        write(&records, "index.md");
+        write(&records.join("feature-x"), "idr-01-use-jwt.md");
+        let notes = tmp.path().join("2026-01-02");
+        write(&notes, "idr-01.md");
+        write(&notes.join("feature-x"), "notes.txt");
+
+        assert_eq!(skip_reason(&records, None), None);
+        assert_eq!(
+            skip_reason(&notes, None).as_deref(),
+            Some("contains non-IDR file notes.txt")
+        );
+        let sow_dir = fs::canonicalize(records.join("feature-x")).unwrap();
+        assert_eq!(
+            skip_reason(&records, Some(&sow_dir)).as_deref(),
+            Some("referenced by .current-sow")
+        );
+    }
+
+    #[test]
+    fn plan_and_apply_archive_by_year() {
+        let tmp = TempDir::new().unwrap();
+        let planning = tmp.path();
+        write(&planning.join("2025-12-31"), "idr-01.md");
+        write(&planning.join("2026-01-15"), "idr-01.md");
+        write(&planning.join("2026-01-16"), "draft.txt");
+        write(&planning.join("2026-05-01"), "idr-01.md");
+        write(&planning.join("notes"), "idr-01.md");
+
+        let plan = plan(planning, (2026, 5, 10), 90, None).unwrap();
+
+        assert_eq!(
+            plan.stale,
+            vec![planning.join("2025-12-31"), planning.join("2026-01-15")]
+        );
+        assert_eq!(plan.skipped.len(), 1);
+
+        apply(planning, &plan, false).unwrap();
+
+        assert!(planning.join("archive/2025/2025-12-31/idr-01.md").exists());
+        assert!(planning.join("archive/2026/2026-01-15/idr-01.md").exists());
+        assert!(!planning.join("2025-12-31").exists());
+        assert!(planning.join("2026-01-16").exists());
+        assert!(planning.join("2026-05-01").exists());
+    }
+
+    #[test]
+    fn apply_deletes_with_delete() {
+        let tmp = TempDir::new().unwrap();
+        write(&tmp.path().join("2020-01-01"), "idr-01.md");
+
+        let plan = plan(tmp.path(), (2026, 5, 10), 90, None).unwrap();
+        apply(tmp.path(), &plan, true).unwrap();
+
+        assert!(!tmp.path().join("2020-01-01").exists());
+        assert!(!tmp.path().join(ARCHIVE_DIR).exists());
+    }
+}
@@ -0,0 +1,916 @@
+use std::path::PathBuf;
+
+/// Subcommands; without one, claude-idr generates an IDR for the staged diff.
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    /// `index [DIR]`: rebuild `index.md` in DIR (default: the output directory).
+    Index { dir: Option<PathBuf> },
+    /// `renumber [DIR] [--width N]`: pad every record number in DIR to N
+    /// digits (default: `number_width`).
+    Renumber {
+        dir: Option<PathBuf>,
+        width: Option<usize>,
+    },
+
+    /// `clean [--older-than AGE] [--delete]`: archive (or delete) old
+    /// `planning/YYYY-MM-DD` directories.
+    Clean {
+        older_than: Option<String>,
+        delete: bool,
+    },
+    /// `sow set|show|clear`: manage the `.current-sow` pointer.
+    Sow(SowCommand),
+    /// `config get|set|unset`: read or change one key of the config file.
+    Config(ConfigCommand),
+    /// `pr [--base REF] [--out PATH] [--publish [--overwrite]]`: describe
+    /// the current branch as a pull request.
+    Pr {
+        base: Option<String>,
+        out: Option<PathBuf>,
+        publish: bool,
+        overwrite: bool,
+    },
+    /// `export [DIR] [--format html|json] [--out PATH]`: write the records in DIR
+    /// (default: the output directory) as standalone files.
+    Export {
+        dir: Option<PathBuf>,
+        format: Option<String>,
+        out: Option<PathBuf>,
+    },
+    /// `digest --since DATE [--until DATE] [DIR | --all] [--no-llm]
+    /// [--out PATH]`: summarize the records of a period.
+    Digest {
+        dir: Option<PathBuf>,
+        since: Option<String>,
+        until: Option<String>,
+        all: bool,
+        no_llm: bool,
+        out: Option<PathBuf>,
+    },
+    /// `search <QUERY> [--dir DIR] [--title-only] [--type TAG]`: records
+    /// matching QUERY, best first.
+    Search {
+        /// The query's words, joined with spaces.
+        query: Vec<String>,
+        dir: Option<PathBuf>,
+        title_only: bool,
+        tag: Option<String>,
+    },
+    /// `stats [DIR | --all]`: counts and totals over the records in DIR
+    /// (default: the output directory).
+    Stats { dir: Option<PathBuf>, all: bool },
+    /// `serve [DIR] [--port N]`: browse the records in DIR (default: the
+    /// workspace) over HTTP on localhost.
+    Serve {
+        dir: Option<PathBuf>,
+        port: Option<u16>,
+    },
+    /// `show <N|latest> [--path]`: print a record from the output directory,
+    /// or only its path.
+    Show {
+        target: Option<String>,
+        path_only: bool,
+    },
+    /// `regenerate --failed [DIR]`: ask claude again for the records in DIR
+    /// (default: the output directory) written with the failure
+    /// placeholder.
+    Regenerate { dir: Option<PathBuf>, failed: bool },
+    /// `retry-last`: send the prompt of the newest debug bundle again and
+    /// write the record it was for.
+    RetryLast,
+    /// `doctor [--json]`: check the environment claude-idr runs in.
+    Doctor,
+    /// `history [--limit N] [--json]`: the newest runs in the run history.
+    History { limit: Option<usize> },
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SowCommand {
+    /// `sow set <PATH> [--create]`
+    Set {
+        path: Option<PathBuf>,
+        create: bool,
+    },
+    Show,
+    Clear,
+    /// Missing or unknown action; the string is what was given.
+    Invalid(String),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ConfigCommand {
+    /// `config get <KEY>`
+    Get { key: Option<String> },
+    /// `config set <KEY> <VALUE>`
+    Set {
+        key: Option<String>,
+        value: Option<String>,
+    },
+    /// `config unset <KEY>`
+    Unset { key: Option<String> },
+    /// Missing or unknown action; the string is what was given.
+    Invalid(String),
+}
+
+impl Command {
+    /// Where the next positional argument goes, if anywhere.
+    fn positional_mut(&mut self) -> Option<&mut Option<PathBuf>> {
+        match self {
+            Command::Index { dir }
+            | Command::Renumber { dir, .. }
+            | Command::Export { dir, .. }
+            | Command::Digest { dir, .. }
+            | Command::Stats { dir, .. }
+            | Command::Serve { dir, .. }
+            | Command::Regenerate { dir, .. } => Some(dir),
+            Command::Sow(SowCommand::Set { path, .. }) => Some(path),
+            Command::Clean { .. }
+            | Command::Sow(_)
+            | Command::Config(_)
+            | Command::Pr { .. }
+            | Command::Search { .. }
+            | Command::Show { .. }
+            | Command::RetryLast
+            | Command::Doctor
+            | Command::History { .. } => None,
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Args {
+    pub command: Option<Command>,
+    pub help: bool,
+    pub version: bool,
+    pub config: Option<PathBuf>,
+    pub dry_run: bool,
+    pub json: bool,
+    pub verbose: bool,
+    /// Only warnings and errors on stderr, and no notification.
+    pub quiet: bool,
+    pub review: bool,
+    pub refine: bool,
+    pub no_dedupe: bool,
+    pub post_commit: bool,
+    pub notes: bool,
+    pub hook_stdin: bool,
+    /// `--from-session`: write a session report instead of documenting a
+    /// diff.
+    pub from_session: bool,
+    /// The transcript `--from-session` names; `None` with `--recent`.
+    pub session_path: Option<PathBuf>,
+    pub split_by_file: bool,
+    pub no_redact: bool,
+    /// Exit 1 when `post_generate_command` fails instead of only warning.
+    pub strict: bool,
+    /// Copy the record's path to the clipboard even with `clipboard` off.
+    pub copy: bool,
+    /// Generate even on a CI build agent, and ignore `fail_on_skip`.
+    pub force: bool,
+    pub fail_on_skip: bool,
+    /// `--on-failure`: overrides `on_failure`; checked when applied.
+    pub on_failure: Option<String>,
+    pub claude_args: Vec<String>,
+    pub commit_msg_out: Option<PathBuf>,
+    /// `prepare-commit-msg`'s message file, which gains an `IDR:` trailer.
+    pub commit_msg_file: Option<PathBuf>,
+    /// `prepare-commit-msg`'s source argument (`message`, `merge`, ...).
+    pub commit_msg_source: Option<String>,
+}
+
+/// Parses `args` (including the program name). Unknown arguments are ignored
+/// so a hook passing extra positional arguments never breaks a commit; a
+/// subcommand is only recognized as the first argument.
+pub fn parse(args: &[String]) -> Args {
+    let mut parsed = Args::default();
+    let mut iter = args.iter().skip(1).peekable();
+    parsed.command = match iter.peek().map(|a| a.as_str()) {
+        Some("index") => Some(Command::Index { dir: None }),
+        Some("renumber") => Some(Command::Renumber {
+            dir: None,
+            width: None,
+        }),
+        Some("clean") => Some(Command::Clean {
+            older_than: None,
+            delete: false,
+        }),
+        Some("sow") => Some(Command::Sow(SowCommand::Invalid(String::new()))),
+        Some("config") => Some(Command::Config(ConfigCommand::Invalid(String::new()))),
+        Some("pr") => Some(Command::Pr {
+            base: None,
+            out: None,
+            publish: false,
+            overwrite: false,
+        }),
+        Some("export") => Some(Command::Export {
+            dir: None,
+            format: None,
+            out: None,
+        }),
+        Some("digest") => Some(Command::Digest {
+            dir: None,
+            since: None,
+            until: None,
+            all: false,
+            no_llm: false,
+            out: None,
+        }),
+        Some("search") => Some(Command::Search {
+            query: Vec::new(),
+            dir: None,
+            title_only: false,
+            tag: None,
+        }),
+        Some("stats") => Some(Command::Stats {
+            dir: None,
+            all: false,
+        }),
+        Some("serve") => Some(Command::Serve {
+            dir: None,
+            port: None,
+        }),
+        Some("show") => Some(Command::Show {
+            target: None,
+            path_only: false,
+        }),
+        Some("regenerate") => Some(Command::Regenerate {
+            dir: None,
+            failed: false,
+        }),
+        Some("retry-last") => Some(Command::RetryLast),
+        Some("doctor") => Some(Command::Doctor),
+        Some("history") => Some(Command::History { limit: None }),
+        _ => None,
+    };
+    if parsed.command.is_some() {
+        iter.next();
+    }
+    if let Some(Command::Sow(action)) = &mut parsed.command {
+        *action = match iter.next_if(|a| !a.starts_with('-')).map(|a| a.as_str()) {
+            Some("set") => SowCommand::Set {
+                path: None,
+                create: false,
+            },
+            Some("show") => SowCommand::Show,
+            Some("clear") => SowCommand::Clear,
+            other => SowCommand::Invalid(other.unwrap_or_default().to_string()),
+        };
+    }
+    if let Some(Command::Config(action)) = &mut parsed.command {
+        let verb = iter.next_if(|a| !a.starts_with('-')).cloned();
+        let key = iter.next_if(|a| !a.starts_with('-')).cloned();
+        *action = match verb.as_deref() {
+            Some("get") => ConfigCommand::Get { key },
+            // The value is taken as given, so `-1` or `--x` can be set.
+            Some("set") => ConfigCommand::Set {
+                key,
+                value: iter.next().cloned(),
+            },
+            Some("unset") => ConfigCommand::Unset { key },
+            other => ConfigCommand::Invalid(other.unwrap_or_default().to_string()),
+        };
+    }
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--help" | "-h" => parsed.help = true,
+            "--version" => parsed.version = true,
+            "--dry-run" => parsed.dry_run = true,
+            "--json" => parsed.json = true,
+            "--verbose" | "-v" => parsed.verbose = true,
+            "--quiet" | "-q" => parsed.quiet = true,
+            "--review" => parsed.review = true,
+            "--refine" => parsed.refine = true,
+            "--no-dedupe" => parsed.no_dedupe = true,
+            "--split-by-file" => parsed.split_by_file = true,
+            "--no-redact" => parsed.no_redact = true,
+            "--strict" => parsed.strict = true,
+            "--copy" => parsed.copy = true,
+            "--force" => parsed.force = true,
+            "--fail-on-skip" => parsed.fail_on_skip = true,
+            "--post-commit" => parsed.post_commit = true,
+            "--notes" => parsed.notes = true,
+            "--hook-stdin" => parsed.hook_stdin = true,
+            "--from-session" => {
+                parsed.from_session = true;
+                if iter.next_if(|a| *a == "--recent").is_none() {
+                    parsed.session_path = iter.next_if(|a| !a.starts_with('-')).map(PathBuf::from);
+                }
+            }
+            "--config" => parsed.config = iter.next().map(PathBuf::from),
+            "--on-failure" => parsed.on_failure = iter.next().cloned(),
+            "--claude-arg" => parsed.claude_args.extend(iter.next().cloned()),
+            "--commit-msg-out" => parsed.commit_msg_out = iter.next().map(PathBuf::from),
+            "--commit-msg-file" => parsed.commit_msg_file = iter.next().map(PathBuf::from),
+            "--commit-msg-source" => parsed.commit_msg_source = iter.next().cloned(),
+            "--older-than" => {
+                let value = iter.next().cloned();
+                if let Some(Command::Clean { older_than, .. }) = &mut parsed.command {
+                    *older_than = value;
+                }
+            }
+            "--delete" => {
+                if let Some(Command::Clean { delete, .. }) = &mut parsed.command {
+                    *delete = true;
+                }
+            }
+            "--create" => {
+                if let Some(Command::Sow(SowCommand::Set { create, .. })) = &mut parsed.command {
+                    *create = true;
+                }
+            }
+            "--base" => {
+                let value = iter.next().cloned();
+                if let Some(Command::Pr { base, .. }) = &mut parsed.command {
+                    *base = value;
+                }
+            }
+            "--out" => {
+                let value = iter.next().map(PathBuf::from);
+                if let Some(
+                    Command::Pr { out, .. }
+                    | Command::Export { out, .. }
+                    | Command::Digest { out, .. },
+                ) = &mut parsed.command
+                {
+                    *out = value;
+                }
+            }
+            "--since" => {
+                let value = iter.next().cloned();
+                if let Some(Command::Digest { since, .. }) = &mut parsed.command {
+                    *since = value;
+                }
+            }
+            "--until" => {
+                let value = iter.next().cloned();
+                if let Some(Command::Digest { until, .. }) = &mut parsed.command {
+                    *until = value;
+                }
+            }
+            "--all" => {
+                if let Some(Command::Digest { all, .. } | Command::Stats { all, .. }) =
+                    &mut parsed.command
+                {
+                    *all = true;
+                }
+            }
+            "--dir" => {
+                let value = iter.next().map(PathBuf::from);
+                if let Some(Command::Search { dir, .. }) = &mut parsed.command {
+                    *dir = value;
+                }
+            }
+            "--title-only" => {
+                if let Some(Command::Search { title_only, .. }) = &mut parsed.command {
+                    *title_only = true;
+                }
+            }
+            "--type" => {
+                let value = iter.next().cloned();
+                if let Some(Command::Search { tag, .. }) = &mut parsed.command {
+                    *tag = value;
+                }
+            }
+            "--failed" => {
+                if let Some(Command::Regenerate { failed, .. }) = &mut parsed.command {
+                    *failed = true;
+                }
+            }
+            "--path" => {
+                if let Some(Command::Show { path_only, .. }) = &mut parsed.command {
+                    *path_only = true;
+                }
+            }
+            "--no-llm" => {
+                if let Some(Command::Digest { no_llm, .. }) = &mut parsed.command {
+                    *no_llm = true;
+                }
+            }
+            "--format" => {
+                let value = iter.next().cloned();
+                if let Some(Command::Export { format, .. }) = &mut parsed.command {
+                    *format = value;
+                }
+            }
+            "--publish" => {
+                if let Some(Command::Pr { publish, .. }) = &mut parsed.command {
+                    *publish = true;
+                }
+            }
+            "--overwrite" => {
+                if let Some(Command::Pr { overwrite, .. }) = &mut parsed.command {
+                    *overwrite = true;
+                }
+            }
+            "--port" => {
+                let value = iter.next().and_then(|v| v.parse().ok());
+                if let Some(Command::Serve { port, .. }) = &mut parsed.command {
+                    *port = value;
+                }
+            }
+            "--limit" => {
+                let value = iter.next().and_then(|v| v.parse().ok());
+                if let Some(Command::History { limit }) = &mut parsed.command {
+                    *limit = value;
+                }
+            }
+            "--width" => {
+                let value = iter.next().and_then(|v| v.parse().ok());
+                if let Some(Command::Renumber { width, .. }) = &mut parsed.command {
+                    *width = value;
+                }
+            }
+            other => {
+                if let Some(value) = other.strip_prefix("--claude-arg=") {
+                    parsed.claude_args.push(value.to_string());
+                } else if let Some(value) = other.strip_prefix("--config=") {
+                    parsed.config = Some(PathBuf::from(value));
+                } else if let Some(value) = other.strip_prefix("--commit-msg-out=") {
+                    parsed.commit_msg_out = Some(PathBuf::from(value));
+                } else if let Some(value) = other.strip_prefix("--commit-msg-file=") {
+                    parsed.commit_msg_file = Some(PathBuf::from(value));
+                } else if let Some(value) = other.strip_prefix("--commit-msg-source=") {
+                    parsed.commit_msg_source = Some(value.to_string());
+                } else if let Some(Command::Search { query, .. }) = &mut parsed.command
+                    && !other.starts_with('-')
+                {
+                    query.push(other.to_string());
+                } else if let Some(Command::Show { target, .. }) = &mut parsed.command
+                    && !other.starts_with('-')
+                {
+                    target.get_or_insert_with(|| other.to_string());
+                } else if let Some(slot) = parsed.command.as_mut().and_then(Command::positional_mut)
+                    && !other.starts_with('-')
+                {
+                    slot.get_or_insert_with(|| PathBuf::from(other));
+                }
+            }
+        }
+    }
+    parsed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        std::iter::once("claude-idr")
+            .chain(list.iter().copied())
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn parse_reads_flags() {
+        let parsed = parse(&args(&[
+            "--dry-run",
+            "--json",
+            "-v",
+            "--review",
+            "--refine",
+            "--no-dedupe",
+            "--post-commit",
+            "--notes",
+            "--hook-stdin",
+            "--split-by-file",
+            "--no-redact",
+            "--strict",
+            "--copy",
+            "-q",
+            "--force",
+            "--fail-on-skip",
+        ]));
+
+        assert!(parsed.dry_run);
+        assert!(parsed.json);
+        assert!(parsed.verbose);
+        assert!(parsed.review);
+        assert!(parsed.refine);
+        assert!(parsed.no_dedupe);
+        assert!(parsed.post_commit);
+        assert!(parsed.notes);
+        assert!(parsed.hook_stdin);
+        assert!(parsed.split_by_file);
+        assert!(parsed.no_redact);
+        assert!(parsed.strict);
+        assert!(parsed.copy);
+        assert!(parsed.quiet);
+        assert!(parsed.force);
+        assert!(parsed.fail_on_skip);
+        assert!(!parsed.help);
+    }
+
+    #[test]
+    fn parse_reads_from_session_path_or_recent() {
+        let parsed = parse(&args(&["--from-session", "/tmp/s.jsonl", "--dry-run"]));
+        assert!(parsed.from_session);
+        assert_eq!(parsed.session_path, Some(PathBuf::from("/tmp/s.jsonl")));
+        assert!(parsed.dry_run);
+
+        let parsed = parse(&args(&["--from-session", "--recent", "--json"]));
+        assert!(parsed.from_session);
+        assert_eq!(parsed.session_path, None);
+        assert!(parsed.json);
+
+        assert!(!parse(&args(&["--recent"])).from_session);
+    }
+
+    #[test]
+    fn parse_reads_config_path_in_both_forms() {
+        assert_eq!(
+            parse(&args(&["--config", "/tmp/a.json"])).config,
+            Some(PathBuf::from("/tmp/a.json"))
+        );
+        assert_eq!(
+            parse(&args(&["--config=/tmp/b.json"])).config,
+            Some(PathBuf::from("/tmp/b.json"))
+        );
+    }
+
+    #[test]
+    fn parse_reads_index_subcommand() {
+        assert_eq!(
+            parse(&args(&["index"])).command,
+            Some(Command::Index { dir: None })
+        );
+        let parsed = parse(&args(&["index", "docs/idr", "--config", "c.json"]));
+        assert_eq!(
+            parsed.command,
+            Some(Command::Index {
+                dir: Some(PathBuf::from("docs/idr"))
+            })
+        );
+        assert_eq!(parsed.config, Some(PathBuf::from("c.json")));
+    }
+
+    #[test]
+    fn parse_reads_renumber_subcommand() {
+        let parsed = parse(&args(&["renumber", "docs", "--width", "3", "--dry-run"]));
+
+        assert_eq!(
+            parsed.command,
+            Some(Command::Renumber {
+                dir: Some(PathBuf::from("docs")),
+                width: Some(3),
+            })
+        );
+        assert!(parsed.dry_run);
+        assert_eq!(
+            parse(&args(&["renumber"])).command,
+            Some(Command::Renumber {
+                dir: None,
+                width: None
+            })
+        );
+    }
+
+    #[test]
+    fn parse_reads_clean_subcommand() {
+        let parsed = parse(&args(&[
+            "clean",
+            "--older-than",
+            "30d",
+            "--delete",
+            "--dry-run",
+        ]));
+
+        assert_eq!(
+            parsed.command,
+            Some(Command::Clean {
+                older_than: Some("30d".to_string()),
+                delete: true,
+            })
+        );
+        assert!(parsed.dry_run);
+        assert_eq!(
+            parse(&args(&["--delete"])).command,
+            None,
+            "--delete means nothing without the subcommand"
+        );
+    }
+
+    #[test]
+    fn parse_reads_pr_subcommand() {
+        let parsed = parse(&args(&[
+            "pr",
+            "--base",
+            "origin/main",
+            "--out",
+            "pr.md",
+            "--publish",
+            "--overwrite",
+            "--dry-run",
+        ]));
+
+        assert_eq!(
+            parsed.command,
+            Some(Command::Pr {
+                base: Some("origin/main".to_string()),
+                out: Some(PathBuf::from("pr.md")),
+                publish: true,
+                overwrite: true,
+            })
+        );
+        assert!(parsed.dry_run);
+        assert_eq!(
+            parse(&args(&["pr"])).command,
+            Some(Command::Pr {
+                base: None,
+                out: None,
+                publish: false,
+                overwrite: false,
+            })
+        );
+    }
+
+    #[test]
+    fn parse_reads_export_subcommand() {
+        let parsed = parse(&args(&[
+            "export", "--format", "html", "docs/idr", "--out", "site",
+        ]));
+
+        assert_eq!(
+            parsed.command,
+            Some(Command::Export {
+                dir: Some(PathBuf::from("docs/idr")),
+                format: Some("html".to_string()),
+                out: Some(PathBuf::from("site")),
+            })
+        );
+        assert_eq!(
+            parse(&args(&["--format", "html"])).command,
+            None,
+            "--format means nothing without the subcommand"
+        );
+    }
+
+    #[test]
+    fn parse_reads_digest_subcommand() {
+        let parsed = parse(&args(&[
+            "digest",
+            "--since",
+            "2026-02-01",
+            "--until",
+            "2026-02-14",
+            "--all",
+            "--no-llm",
+            "--out",
+            "d.md",
+        ]));
+
+        assert_eq!(
+            parsed.command,
+            Some(Command::Digest {
+                dir: None,
+                since: Some("2026-02-01".to_string()),
+                until: Some("2026-02-14".to_string()),
+                all: true,
+                no_llm: true,
+                out: Some(PathBuf::from("d.md")),
+            })
+        );
+        assert!(matches!(
+            parse(&args(&["digest", "docs/idr"])).command,
+            Some(Command::Digest { dir: Some(d), .. }) if d == Path::new("docs/idr")
+        ));
+        assert_eq!(
+            parse(&args(&["--since", "2026-02-01"])).command,
+            None,
+            "--since means nothing without the subcommand"
+        );
+    }
+
+    #[test]
+    fn parse_reads_search_subcommand() {
+        let parsed = parse(&args(&[
+            "search",
+            "exponential",
+            "--type",
+            "fix",
+            "backoff",
+            "--dir",
+            "docs/idr",
+            "--title-only",
+            "--json",
+        ]));
+
+        assert_eq!(
+            parsed.command,
+            Some(Command::Search {
+                query: vec!["exponential".to_string(), "backoff".to_string()],
+                dir: Some(PathBuf::from("docs/idr")),
+                title_only: true,
+                tag: Some("fix".to_string()),
+            })
+        );
+        assert!(parsed.json);
+        assert_eq!(
+            parse(&args(&["--title-only"])).command,
+            None,
+            "--title-only means nothing without the subcommand"
+        );
+    }
+
+    #[test]
+    fn parse_reads_stats_subcommand() {
+        assert_eq!(
+            parse(&args(&["stats", "docs/idr", "--json"])).command,
+            Some(Command::Stats {
+                dir: Some(PathBuf::from("docs/idr")),
+                all: false,
+            })
+        );
+        assert_eq!(
+            parse(&args(&["stats", "--all"])).command,
+            Some(Command::Stats {
+                dir: None,
+                all: true,
+            })
+        );
+    }
+
+    #[test]
+    fn parse_reads_serve_subcommand() {
+        assert_eq!(
+            parse(&args(&["serve", "--port", "8080", "docs/idr"])).command,
+            Some(Command::Serve {
+                dir: Some(PathBuf::from("docs/idr")),
+                port: Some(8080),
+            })
+        );
+        assert_eq!(
+            parse(&args(&["--port", "8080"])).command,
+            None,
+            "--port means nothing without the subcommand"
+        );
+    }
+
+    #[test]
+    fn parse_reads_show_subcommand() {
+        assert_eq!(
+            parse(&args(&["show", "--path", "7", "8"])).command,
+            Some(Command::Show {
+                target: Some("7".to_string()),
+                path_only: true,
+            })
+        );
+        assert_eq!(
+            parse(&args(&["show"])).command,
+            Some(Command::Show {
+                target: None,
+                path_only: false,
+            })
+        );
+    }
+
+    #[test]
+    fn parse_reads_regenerate_subcommand() {
+        assert_eq!(
+            parse(&args(&["regenerate", "--failed", "docs/idr"])).command,
+            Some(Command::Regenerate {
+                dir: Some(PathBuf::from("docs/idr")),
+                failed: true,
+            })
+        );
+        let parsed = parse(&args(&["--on-failure", "skip", "--failed"]));
+        assert_eq!(parsed.command, None);
+        assert_eq!(parsed.on_failure.as_deref(), Some("skip"));
+        let parsed = parse(&args(&["retry-last", "--dry-run", "extra"]));
+        assert_eq!(parsed.command, Some(Command::RetryLast));
+        assert!(parsed.dry_run);
+        let parsed = parse(&args(&["doctor", "--json"]));
+        assert_eq!(parsed.command, Some(Command::Doctor));
+        assert!(parsed.json);
+        assert_eq!(
+            parse(&args(&["history", "--limit", "5"])).command,
+            Some(Command::History { limit: Some(5) })
+        );
+    }
+
+    #[test]
+    fn parse_reads_sow_subcommands() {
+        assert_eq!(
+            parse(&args(&["sow", "set", "sow/project-x", "--create"])).command,
+            Some(Command::Sow(SowCommand::Set {
+                path: Some(PathBuf::from("sow/project-x")),
+                create: true,
+            }))
+        );
+        assert_eq!(
+            parse(&args(&["sow", "show"])).command,
+            Some(Command::Sow(SowCommand::Show))
+        );
+        assert_eq!(
+            parse(&args(&["sow", "clear", "extra"])).command,
+            Some(Command::Sow(SowCommand::Clear))
+        );
+        assert_eq!(
+            parse(&args(&["sow", "bogus"])).command,
+            Some(Command::Sow(SowCommand::Invalid("bogus".to_string())))
+        );
+        let parsed = parse(&args(&["sow", "--verbose"]));
+        assert_eq!(
+            parsed.command,
+            Some(Command::Sow(SowCommand::Invalid(String::new())))
+        );
+        assert!(parsed.verbose);
+    }
+
+    #[test]
+    fn parse_reads_config_subcommands() {
+        assert_eq!(
+            parse(&args(&["config", "get", "model"])).command,
+            Some(Command::Config(ConfigCommand::Get {
+                key: Some("model".to_string())
+            }))
+        );
+        let parsed = parse(&args(&[
+            "config",
+            "set",
+            "max_diff_lines",
+            "-1",
+            "--verbose",
+        ]));
+        assert_eq!(
+            parsed.command,
+            Some(Command::Config(ConfigCommand::Set {
+                key: Some("max_diff_lines".to_string()),
+                value: Some("-1".to_string()),
+            }))
+        );
+        assert!(parsed.verbose);
+        assert_eq!(
+            parse(&args(&["config", "unset"])).command,
+            Some(Command::Config(ConfigCommand::Unset { key: None }))
+        );
+        assert_eq!(
+            parse(&args(&["config", "list"])).command,
+            Some(Command::Config(ConfigCommand::Invalid("list".to_string())))
+        );
+        let parsed = parse(&args(&["config", "--config", "/tmp/c.json"]));
+        assert_eq!(
+            parsed.command,
+            Some(Command::Config(ConfigCommand::Invalid(String::new())))
+        );
+        assert_eq!(parsed.config, Some(PathBuf::from("/tmp/c.json")));
+    }
+
+    #[test]
+    fn parse_ignores_subcommand_names_after_first_argument() {
+        let parsed = parse(&args(&["--dry-run", "index"]));
+
+        assert_eq!(parsed.command, None);
+        assert!(parsed.dry_run);
+    }
+
+    #[test]
+    fn parse_collects_repeated_claude_args_verbatim() {
+        let parsed = parse(&args(&[
+            "--claude-arg",
+            "--append-system-prompt",
+            "--claude-arg=Be terse",
+            "--claude-arg",
+            "--dry-run",
+        ]));
+
+        assert_eq!(
+            parsed.claude_args,
+            vec!["--append-system-prompt", "Be terse", "--dry-run"]
+        );
+        assert!(!parsed.dry_run);
+    }
+
+    #[test]
+    fn parse_reads_commit_msg_out() {
+        assert_eq!(
+            parse(&args(&["--commit-msg-out", ".git/COMMIT_EDITMSG"])).commit_msg_out,
+            Some(PathBuf::from(".git/COMMIT_EDITMSG"))
+        );
+        assert_eq!(
+            parse(&args(&["--commit-msg-out=/tmp/msg"])).commit_msg_out,
+            Some(PathBuf::from("/tmp/msg"))
+        );
+    }
+
+    #[test]
+    fn parse_reads_commit_msg_file_and_source() {
+        let parsed = parse(&args(&[
+            "--commit-msg-file",
+            ".git/COMMIT_EDITMSG",
+            "--commit-msg-source=merge",
+        ]));
+        assert_eq!(
+            parsed.commit_msg_file,
+            Some(PathBuf::from(".git/COMMIT_EDITMSG"))
+        );
+        assert_eq!(parsed.commit_msg_source.as_deref(), Some("merge"));
+    }
+
+    #[test]
+    fn parse_ignores_unknown_arguments() {
+        let parsed = parse(&args(&["--unknown", "positional"]));
+        assert_eq!(parsed, Args::default());
+    }
+}
@@ -0,0 +1,215 @@
+//! `clipboard` / `--copy`: the new record's path or content put on the
+//! system clipboard through whichever tool the platform has.
+
+use crate::config::{ClipboardMode, Config};
+use crate::log::warn;
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+/// Environment variable naming a file that receives the text instead of
+/// the clipboard, so tests never touch the real one.
+pub const OUT_ENV: &str = "CLAUDE_IDR_CLIPBOARD_OUT";
+
+/// A clipboard tool and the arguments that make it read stdin.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tool {
+    pub program: &'static str,
+    pub args: &'static [&'static str],
+}
+
+const PBCOPY: Tool = Tool {
+    program: "pbcopy",
+    args: &[],
+};
+const WL_COPY: Tool = Tool {
+    program: "wl-copy",
+    args: &[],
+};
+const XCLIP: Tool = Tool {
+    program: "xclip",
+    args: &["-selection", "clipboard"],
+};
+const XSEL: Tool = Tool {
+    program: "xsel",
+    args: &["--clipboard", "--input"],
+};
+const CLIP: Tool = Tool {
+    program: "clip.exe",
+    args: &[],
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Platform {
+    MacOs,
+    /// `wayland` when the session runs under Wayland.
+    Linux {
+        wayland: bool,
+    },
+    /// Windows, or Linux under WSL, where `clip.exe` reaches the Windows
+    /// clipboard.
+    Windows,
+}
+
+/// The tool to use on `platform`, given the candidate programs found on
+/// `PATH`. On Linux, `wl-copy` comes first under Wayland and last
+/// otherwise.
+pub fn tool(platform: Platform, on_path: &[&str]) -> Option<Tool> {
+    let candidates: &[Tool] = match platform {
+        Platform::MacOs => &[PBCOPY],
+        Platform::Linux { wayland: true } => &[WL_COPY, XCLIP, XSEL],
+        Platform::Linux { wayland: false } => &[XCLIP, XSEL, WL_COPY],
+        Platform::Windows => &[CLIP],
+    };
+    candidates
+        .iter()
+        .find(|tool| on_path.contains(&tool.program))
+        .copied()
+}
+
+/// The text `mode` puts on the clipboard for `record`.
+pub fn text(mode: ClipboardMode, record: &Path) -> Option<String> {
+    match mode {
+        ClipboardMode::Off => None,
+        ClipboardMode::Path => Some(record.display().to_string()),
+        ClipboardMode::Content => fs::read_to_string(record).ok(),
+    }
+}
+
+/// Copies what `config.clipboard` (or `--copy`, as `forced`) asks for.
+/// Only warns when that fails.
+pub fn copy_record(record: &Path, config: &Config, forced: bool) {
+    let mode = match config.clipboard {
+        ClipboardMode::Off if forced => ClipboardMode::Path,
+        mode => mode,
+    };
+    let Some(text) = text(mode, record) else {
+        return;
+    };
+    if let Err(e) = copy(&text) {
+        warn!("cannot copy to the clipboard: {e}");
+    }
+}
+
+fn copy(text: &str) -> Result<(), String> {
+    if let Some(out) = env::var_os(OUT_ENV) {
+        return fs::write(&out, text).map_err(|e| e.to_string());
+    }
+    let tool =
+        detected().ok_or("no clipboard tool found (pbcopy, wl-copy, xclip, xsel, clip.exe)")?;
+    let mut child = command(tool)
+        .spawn()
+        .map_err(|e| format!("cannot run {}: {e}", tool.program))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(text.as_bytes())
+            .map_err(|e| format!("cannot write to {}: {e}", tool.program))?;
+    }
+    let status = child.wait().map_err(|e| e.to_string())?;
+    if !status.success() {
+        return Err(format!("{} failed ({status})", tool.program));
+    }
+    Ok(())
+}
+
+fn command(tool: Tool) -> Command {
+    let mut command = Command::new(tool.program);
+    command
+        .args(tool.args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    command
+}
+
+/// [`tool`] for this machine, looked up once per process.
+fn detected() -> Option<Tool> {
+    static TOOL: OnceLock<Option<Tool>> = OnceLock::new();
+    *TOOL.get_or_init(|| {
+        let candidates = [PBCOPY, WL_COPY, XCLIP, XSEL, CLIP];
+        let on_path: Vec<&str> = candidates
+            .iter()
+            .map(|tool| tool.program)
+            .filter(|program| on_path(program))
+            .collect();
+        tool(platform(), &on_path)
+    })
+}
+
+fn platform() -> Platform {
+    if cfg!(target_os = "macos") {
+        Platform::MacOs
+    } else if cfg!(windows) || is_wsl() {
+        Platform::Windows
+    } else {
+        Platform::Linux {
+            wayland: env::var_os("WAYLAND_DISPLAY").is_some(),
+        }
+    }
+}
+
+fn is_wsl() -> bool {
+    fs::read_to_string("/proc/version").is_ok_and(|v| v.to_lowercase().contains("microsoft"))
+}
+
+fn on_path(program: &str) -> bool {
+    env::var_os("PATH")
+        .is_some_and(|path| env::split_paths(&path).any(|dir| dir.join(program).is_file()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn tool_picks_by_platform_and_availability() {
+        let linux = Platform::Linux { wayland: false };
+        let wayland = Platform::Linux { wayland: true };
+
+        assert_eq!(tool(Platform::MacOs, &["pbcopy", "xclip"]), Some(PBCOPY));
+        assert_eq!(tool(linux, &["xsel", "wl-copy"]), Some(XSEL));
+        assert_eq!(tool(linux, &["wl-copy"]), Some(WL_COPY));
+        assert_eq!(tool(wayland, &["xclip", "wl-copy"]), Some(WL_COPY));
+        assert_eq!(tool(wayland, &["xclip"]), Some(XCLIP));
+        assert_eq!(tool(Platform::Windows, &["clip.exe", "xclip"]), Some(CLIP));
+        assert_eq!(tool(linux, &["pbcopy", "clip.exe"]), None);
+        assert_eq!(tool(Platform::MacOs, &[]), None);
+    }
+
+    #[test]
+    fn command_passes_the_tool_arguments() {
+        let xclip = command(XCLIP);
+        assert_eq!(xclip.get_program(), "xclip");
+        assert_eq!(
+            xclip.get_args().collect::<Vec<_>>(),
+            ["-selection", "clipboard"]
+        );
+        let xsel = command(XSEL);
+        assert_eq!(
+            xsel.get_args().collect::<Vec<_>>(),
+            ["--clipboard", "--input"]
+        );
+        assert_eq!(command(PBCOPY).get_args().count(), 0);
+    }
+
+    #[test]
+    fn text_is_the_path_or_the_content() {
+        let dir = TempDir::new().unwrap();
+        let record = dir.path().join("idr-01.md");
+        fs::write(&record, "# IDR: x\n").unwrap();
+
+        assert_eq!(
+            text(ClipboardMode::Path, &record),
+            Some(record.display().to_string())
+        );
+        assert_eq!(
+            text(ClipboardMode::Content, &record).as_deref(),
+            Some("# IDR: x\n")
+        );
+        assert_eq!(text(ClipboardMode::Off, &record), None);
+    }
+}
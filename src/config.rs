@@ -1,12 +1,160 @@
-use serde::Deserialize;
+use crate::error::IdrError;
+use crate::log::warn;
+use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    Cli,
+    Api,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Idr,
+    Madr,
+}
+
+/// What to do when the staged diff exceeds `max_diff_lines`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LargeDiffStrategy {
+    Skip,
+    Summarize,
+}
+
+/// Whether records share the resolved directory or get one per branch.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputLayout {
+    Flat,
+    ByBranch,
+}
+
+/// One file per record, or one rolling journal per month.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputMode {
+    Files,
+    Monthly,
+}
+
+/// Whether records go under `workspace_dir` or into the repository being
+/// committed to.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputLocation {
+    Workspace,
+    Repo,
+    /// `repo` when `repo_output_subdir` already exists in the repository.
+    Auto,
+}
+
+/// How absolute paths in the session context are rewritten before they go
+/// into a prompt.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RedactPathsMode {
+    /// Paths inside the repository become relative to its root.
+    RepoRelative,
+    /// The home directory becomes `~`.
+    HomeTilde,
+    /// The repository's own directory name becomes a short hash.
+    HashProject,
+}
+
+/// What `--copy` and `clipboard` put on the clipboard after a record is
+/// written.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClipboardMode {
+    Off,
+    Path,
+    Content,
+}
+
+/// What happens when claude cannot write a record's body.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OnFailure {
+    /// Write the record with the failure body, marked for
+    /// `regenerate --failed`.
+    Placeholder,
+    /// Write nothing and warn.
+    Skip,
+    /// Write nothing and exit non-zero.
+    Fail,
+}
+
+impl OnFailure {
+    /// The mode named `name`, as `--on-failure` takes it.
+    pub fn parse(name: &str) -> Option<OnFailure> {
+        match name {
+            "placeholder" => Some(OnFailure::Placeholder),
+            "skip" => Some(OnFailure::Skip),
+            "fail" => Some(OnFailure::Fail),
+            _ => None,
+        }
+    }
+}
+
+/// How `latest.md` points at the newest record.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LatestMode {
+    Symlink,
+    Copy,
+}
+
+/// Where the second language of a bilingual record goes.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BilingualLayout {
+    /// `## 日本語` and `## English` sections in the record.
+    Sections,
+    /// `idr-NN-slug.en.md` next to the record.
+    Files,
+}
+
+/// Documentation site whose navigation lists the records.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PublishIntegration {
+    /// An entry in mdBook's `SUMMARY.md`.
+    Mdbook,
+    /// An entry in the `nav` of `mkdocs.yml`.
+    Mkdocs,
+}
+
+/// Per-heading overrides of the built-in table in `prompt::headings`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SectionHeadings {
+    pub summary: Option<String>,
+    pub changes: Option<String>,
+    pub decisions: Option<String>,
+    pub rationale: Option<String>,
+    pub risks: Option<String>,
+    pub diagram: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     #[serde(default = "default_enabled")]
     pub enabled: bool,
+    /// `ja`, `en`, or `auto` to follow the session's requests; `ja+en`
+    /// adds a translation into the second language.
     #[serde(default = "default_language")]
     pub language: String,
+    /// What `auto` settles on when the requests do not decide it.
+    #[serde(default = "default_language")]
+    pub language_fallback: String,
+    #[serde(default = "default_bilingual_layout")]
+    pub bilingual_layout: BilingualLayout,
+    /// Model of the translation call; unset uses `model`.
+    #[serde(default)]
+    pub translation_model: Option<String>,
     #[serde(default = "default_model")]
     pub model: String,
     #[serde(default)]
@@ -17,6 +165,188 @@ pub struct Config {
     pub session_max_age_min: u64,
     #[serde(default = "default_max_diff_lines")]
     pub max_diff_lines: u64,
+    #[serde(default = "default_context_max_chars")]
+    pub context_max_chars: usize,
+    #[serde(default = "default_json_output")]
+    pub json_output: bool,
+    #[serde(default)]
+    pub usage_in_footer: bool,
+    #[serde(default = "default_backend")]
+    pub backend: Backend,
+    #[serde(default = "default_api_base_url")]
+    pub api_base_url: String,
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    #[serde(default)]
+    pub claude_args: Vec<String>,
+    #[serde(default = "default_claude_bin")]
+    pub claude_bin: PathBuf,
+    #[serde(default = "default_probe_claude")]
+    pub probe_claude: bool,
+    #[serde(default)]
+    pub model_fallbacks: Vec<String>,
+    #[serde(default)]
+    pub resume_session: bool,
+    #[serde(default)]
+    pub section_headings: SectionHeadings,
+    /// Replaces the body written when generation fails; `{summary}` and
+    /// `{stat}` are filled in.
+    #[serde(default)]
+    pub failure_body_template: Option<String>,
+    #[serde(default = "default_on_failure")]
+    pub on_failure: OnFailure,
+    /// Debug bundles of failed claude calls kept (see `failures`); 0 saves
+    /// none.
+    #[serde(default = "default_debug_bundles")]
+    pub debug_bundles: usize,
+    /// Size in bytes past which the run history is rotated (see `runs`); 0
+    /// keeps no history.
+    #[serde(default = "default_history_max_bytes")]
+    pub history_max_bytes: u64,
+    #[serde(default)]
+    pub missing_purpose_text: Option<String>,
+    #[serde(default = "default_format")]
+    pub format: Format,
+    #[serde(default = "default_madr_status")]
+    pub madr_status: String,
+    #[serde(default = "default_max_prompt_tokens")]
+    pub max_prompt_tokens: usize,
+    #[serde(default)]
+    pub continuity: bool,
+    #[serde(default = "default_continuity_count")]
+    pub continuity_count: usize,
+    /// Show claude the last commits that touched each changed file.
+    #[serde(default)]
+    pub history_context: bool,
+    /// Link new records to earlier ones that changed the same files.
+    #[serde(default = "default_cross_link")]
+    pub cross_link: bool,
+    #[serde(default)]
+    pub suggest_commit_message: bool,
+    #[serde(default)]
+    pub review_section: bool,
+    #[serde(default)]
+    pub include_context_in_idr: bool,
+    #[serde(default)]
+    pub diagram: bool,
+    #[serde(default)]
+    pub refine: bool,
+    #[serde(default)]
+    pub front_matter: bool,
+    /// Wikilinks, inline tags, and a daily-note link for Obsidian vaults.
+    #[serde(default)]
+    pub obsidian_mode: bool,
+    /// Put the change type in record file names (`idr-07-fix-...`).
+    #[serde(default)]
+    pub classify_in_filename: bool,
+    #[serde(default = "default_latest_mode")]
+    pub latest_mode: LatestMode,
+    #[serde(default = "default_output_layout")]
+    pub output_layout: OutputLayout,
+    #[serde(default = "default_number_width")]
+    pub number_width: usize,
+    #[serde(default = "default_output_mode")]
+    pub output_mode: OutputMode,
+    #[serde(default)]
+    pub notes_mode: bool,
+    #[serde(default)]
+    pub notes_keep_files: bool,
+    /// Seconds to wait for another run in the same work tree before
+    /// giving up on this one.
+    #[serde(default)]
+    pub lock_wait_secs: u64,
+    /// Where Claude Code keeps session transcripts, instead of
+    /// `~/.claude/projects`.
+    #[serde(default)]
+    pub projects_dir: Option<PathBuf>,
+    #[serde(default = "default_output_location")]
+    pub output_location: OutputLocation,
+    #[serde(default = "default_repo_output_subdir")]
+    pub repo_output_subdir: PathBuf,
+    #[serde(default = "default_skip_rules")]
+    pub skip_rules: Vec<String>,
+    #[serde(default = "default_merge_rules")]
+    pub merge_rules: Vec<String>,
+    #[serde(default = "default_large_diff_strategy")]
+    pub large_diff_strategy: LargeDiffStrategy,
+    #[serde(default = "default_summary_model")]
+    pub summary_model: String,
+    /// Extra secret patterns (see `pattern`) redacted from prompts.
+    #[serde(default)]
+    pub redact_patterns: Vec<String>,
+    /// Files whose hunks are never sent to claude, as globs (`.env*`).
+    #[serde(default = "default_redact_paths")]
+    pub redact_paths: Vec<String>,
+    /// Unset leaves paths in the session context as they are.
+    #[serde(default)]
+    pub redact_paths_mode: Option<RedactPathsMode>,
+    /// Files `--split-by-file` generates records for at the same time.
+    #[serde(default = "default_split_concurrency")]
+    pub split_concurrency: usize,
+    /// Exit non-zero when a change goes undocumented (see `Gate`).
+    #[serde(default)]
+    pub fail_on_skip: bool,
+    /// Generate on CI build agents too, instead of skipping (see `ci`).
+    #[serde(default)]
+    pub run_in_ci: bool,
+    #[serde(default = "default_clipboard")]
+    pub clipboard: ClipboardMode,
+    /// A desktop notification (or a terminal bell) when generation ends.
+    #[serde(default)]
+    pub notify: bool,
+    /// Shell command the diff is piped through before prompts are built
+    /// (see `diff_filter`).
+    #[serde(default)]
+    pub diff_filter_command: Option<String>,
+    #[serde(default = "default_diff_filter_timeout_secs")]
+    pub diff_filter_timeout_secs: u64,
+    /// Drop whitespace-only hunks, reordered imports, and `noise_paths`
+    /// files from the diff prompts see (see `noise`).
+    #[serde(default = "default_strip_noise")]
+    pub strip_noise: bool,
+    /// Files whose hunks are noise, as globs (`*.min.js`).
+    #[serde(default = "default_noise_paths")]
+    pub noise_paths: Vec<String>,
+    /// Line prefixes that mark an import; a hunk that only reorders such
+    /// lines is noise. Empty keeps reordered imports.
+    #[serde(default = "default_import_prefixes")]
+    pub import_prefixes: Vec<String>,
+    /// Unchanged lines kept around each change in the prompt diff; unset
+    /// keeps git's three.
+    #[serde(default)]
+    pub prompt_context_lines: Option<usize>,
+    /// Share of replacement and control characters at or above which a
+    /// file's hunks count as binary and are left out of prompts (see
+    /// `sanitize`).
+    #[serde(default = "default_binary_density")]
+    pub binary_density: f64,
+    /// Fewest such characters a file needs before it is judged at all.
+    #[serde(default = "default_binary_min_chars")]
+    pub binary_min_chars: usize,
+    /// Shell command run after a record is written, with `IDR_*` variables
+    /// describing it (see `post_generate`).
+    #[serde(default)]
+    pub post_generate_command: Option<String>,
+    /// Also run `post_generate_command` after skips and errors.
+    #[serde(default)]
+    pub post_generate_on_skip: bool,
+    #[serde(default = "default_post_generate_timeout_secs")]
+    pub post_generate_timeout_secs: u64,
+    /// Matches issue ids in the branch name and user requests (see
+    /// `pattern` for the supported syntax); empty turns extraction off.
+    #[serde(default = "default_issue_pattern")]
+    pub issue_pattern: String,
+    /// Links each issue id, e.g. `https://jira.example.com/browse/{id}`.
+    #[serde(default)]
+    pub issue_url_template: Option<String>,
+    /// Where `export` points links to repository files, e.g.
+    /// `https://github.com/o/r/blob/main/{path}`; unset, they become text.
+    #[serde(default)]
+    pub export_link_template: Option<String>,
+    #[serde(default)]
+    pub publish_integration: Option<PublishIntegration>,
 }
 
 fn default_enabled() -> bool {
@@ -25,17 +355,43 @@ fn default_enabled() -> bool {
 fn default_language() -> String {
     "ja".to_string()
 }
+fn default_bilingual_layout() -> BilingualLayout {
+    BilingualLayout::Sections
+}
 fn default_model() -> String {
     "sonnet".to_string()
 }
+/// Empty without a home directory; see [`Config::missing_home`].
 fn default_workspace_dir() -> PathBuf {
-    dirs::home_dir()
-        .unwrap_or_else(|| {
-            eprintln!("claude-idr: warning: cannot determine home directory, using current dir");
-            PathBuf::new()
-        })
-        .join(".claude")
-        .join("workspace")
+    home_dir()
+        .map(|home| home.join(".claude").join("workspace"))
+        .unwrap_or_default()
+}
+
+/// The home directory, if it can be determined and exists. In containers
+/// `HOME` may be unset or point at a directory that was never created.
+pub fn home_dir() -> Option<PathBuf> {
+    dirs::home_dir().filter(|home| home.is_dir())
+}
+
+/// `claude-idr` in the XDG state directory (`~/.local/state` where it is
+/// not set), for debug bundles and the run history. `XDG_STATE_HOME` is
+/// honoured on macOS too, where `dirs` has no state directory.
+pub fn state_dir() -> Option<PathBuf> {
+    let state = std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(dirs::state_dir)
+        .or_else(|| Some(home_dir()?.join(".local/state")))?;
+    Some(state.join("claude-idr"))
+}
+
+/// `path` with a leading `~` replaced by the home directory.
+pub fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
 }
 fn default_session_max_age_min() -> u64 {
     30
@@ -43,42 +399,217 @@ fn default_session_max_age_min() -> u64 {
 fn default_max_diff_lines() -> u64 {
     500
 }
+fn default_context_max_chars() -> usize {
+    8000
+}
+fn default_json_output() -> bool {
+    true
+}
+fn default_backend() -> Backend {
+    Backend::Cli
+}
+fn default_api_base_url() -> String {
+    "https://api.anthropic.com".to_string()
+}
+fn default_timeout_secs() -> u64 {
+    300
+}
+fn default_max_retries() -> u32 {
+    2
+}
+fn default_claude_bin() -> PathBuf {
+    PathBuf::from("claude")
+}
+fn default_probe_claude() -> bool {
+    true
+}
+fn default_format() -> Format {
+    Format::Idr
+}
+fn default_madr_status() -> String {
+    "accepted".to_string()
+}
+fn default_max_prompt_tokens() -> usize {
+    50_000
+}
+fn default_continuity_count() -> usize {
+    2
+}
+fn default_cross_link() -> bool {
+    true
+}
+fn default_skip_rules() -> Vec<String> {
+    [
+        "formatting-only",
+        "whitespace-only",
+        "auto-generated changes",
+    ]
+    .map(String::from)
+    .to_vec()
+}
+fn default_merge_rules() -> Vec<String> {
+    vec!["adjacent hunks with same intent into single entry".to_string()]
+}
+fn default_latest_mode() -> LatestMode {
+    LatestMode::Symlink
+}
+fn default_output_layout() -> OutputLayout {
+    OutputLayout::Flat
+}
+fn default_number_width() -> usize {
+    2
+}
+fn default_output_mode() -> OutputMode {
+    OutputMode::Files
+}
+fn default_output_location() -> OutputLocation {
+    OutputLocation::Workspace
+}
+fn default_repo_output_subdir() -> PathBuf {
+    PathBuf::from("docs/decisions")
+}
+fn default_large_diff_strategy() -> LargeDiffStrategy {
+    LargeDiffStrategy::Skip
+}
+fn default_summary_model() -> String {
+    "haiku".to_string()
+}
+fn default_redact_paths() -> Vec<String> {
+    [".env*", "*.pem", "*.key"]
+        .into_iter()
+        .map(String::from)
+        .collect()
+}
+fn default_split_concurrency() -> usize {
+    4
+}
+fn default_on_failure() -> OnFailure {
+    OnFailure::Placeholder
+}
+fn default_clipboard() -> ClipboardMode {
+    ClipboardMode::Off
+}
+fn default_diff_filter_timeout_secs() -> u64 {
+    30
+}
+fn default_strip_noise() -> bool {
+    true
+}
+fn default_noise_paths() -> Vec<String> {
+    [
+        "*.snap",
+        "**/__snapshots__/**",
+        "*.min.js",
+        "*.min.css",
+        "*.js.map",
+        "*.css.map",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect()
+}
+fn default_import_prefixes() -> Vec<String> {
+    [
+        "use ",
+        "pub use ",
+        "import ",
+        "from ",
+        "#include ",
+        "require ",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect()
+}
+fn default_binary_density() -> f64 {
+    0.3
+}
+fn default_binary_min_chars() -> usize {
+    16
+}
+fn default_debug_bundles() -> usize {
+    10
+}
+fn default_history_max_bytes() -> u64 {
+    1024 * 1024
+}
+fn default_post_generate_timeout_secs() -> u64 {
+    10
+}
+fn default_issue_pattern() -> String {
+    crate::issues::DEFAULT_PATTERN.to_string()
+}
+
+/// Environment variable that replaces `claude_bin`, e.g. to point a test
+/// run at a fake CLI without writing a config file.
+pub const CLAUDE_BIN_ENV: &str = "CLAUDE_IDR_CLAUDE_BIN";
 
 impl Config {
     pub fn load(path: Option<&Path>) -> Config {
-        let config_path = path.map(PathBuf::from).unwrap_or_else(Self::default_path);
-
-        let content = match std::fs::read_to_string(&config_path) {
-            Ok(c) => c,
-            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Config::default(),
+        match Self::try_load(path) {
+            Ok(config) => config,
+            Err(e @ IdrError::Io { .. }) => {
+                warn!("cannot read config {e}");
+                Config::default()
+            }
             Err(e) => {
-                eprintln!(
-                    "claude-idr: warning: cannot read config {}: {}",
-                    config_path.display(),
-                    e
-                );
-                return Config::default();
+                warn!("{e}");
+                Config::default()
             }
+        }
+    }
+
+    /// Like [`Config::load`], but returns unreadable or invalid files as
+    /// errors instead of warning. A missing file is still the defaults, as is
+    /// having no `--config` and no home directory to look in.
+    /// [`CLAUDE_BIN_ENV`] overrides `claude_bin` either way.
+    pub fn try_load(path: Option<&Path>) -> Result<Config, IdrError> {
+        let Some(config_path) = path.map(PathBuf::from).or_else(Self::default_path) else {
+            return Ok(Config::default().with_env());
         };
 
-        match serde_json::from_str(&content) {
-            Ok(config) => config,
-            Err(e) => {
-                eprintln!(
-                    "claude-idr: warning: invalid config {}: {}",
-                    config_path.display(),
-                    e
-                );
-                Config::default()
+        let config: Config = match std::fs::read_to_string(&config_path) {
+            Ok(content) => serde_json::from_str(&content).map_err(|e| IdrError::ConfigInvalid {
+                path: config_path,
+                reason: e.to_string(),
+            })?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Config::default(),
+            Err(source) => {
+                return Err(IdrError::Io {
+                    path: config_path,
+                    source,
+                });
             }
+        };
+        Ok(config.with_env())
+    }
+
+    fn with_env(mut self) -> Config {
+        if let Some(bin) = std::env::var_os(CLAUDE_BIN_ENV).filter(|b| !b.is_empty()) {
+            self.claude_bin = PathBuf::from(bin);
         }
+        self
     }
 
-    fn default_path() -> PathBuf {
-        dirs::config_dir()
-            .unwrap_or_else(|| dirs::home_dir().unwrap_or_default().join(".config"))
-            .join("claude-idr")
-            .join("config.json")
+    /// `claude-idr/config.json` in the user's config directory, read when
+    /// no `--config` is given.
+    pub fn default_path() -> Option<PathBuf> {
+        let dir = dirs::config_dir().or_else(|| Some(home_dir()?.join(".config")))?;
+        Some(dir.join("claude-idr").join("config.json"))
+    }
+
+    /// Why records have nowhere sensible to go: without a home directory the
+    /// default `workspace_dir` is empty, and unless `output_dir` is set or
+    /// records go into the repository, they would land wherever the hook
+    /// happens to run.
+    pub fn missing_home(&self) -> Option<&'static str> {
+        let placed = self.output_dir.is_some()
+            || !self.workspace_dir.as_os_str().is_empty()
+            || self.output_location == OutputLocation::Repo;
+        (!placed).then_some(
+            "cannot determine the home directory (HOME is unset or does not exist); \
+             set workspace_dir or output_dir in the config (see --config) to run without it",
+        )
     }
 }
 
@@ -87,11 +618,84 @@ impl Default for Config {
         Config {
             enabled: default_enabled(),
             language: default_language(),
+            language_fallback: default_language(),
+            bilingual_layout: default_bilingual_layout(),
+            translation_model: None,
             model: default_model(),
             output_dir: None,
             workspace_dir: default_workspace_dir(),
             session_max_age_min: default_session_max_age_min(),
             max_diff_lines: default_max_diff_lines(),
+            context_max_chars: default_context_max_chars(),
+            json_output: default_json_output(),
+            usage_in_footer: false,
+            backend: default_backend(),
+            api_base_url: default_api_base_url(),
+            timeout_secs: default_timeout_secs(),
+            max_retries: default_max_retries(),
+            claude_args: Vec::new(),
+            claude_bin: default_claude_bin(),
+            probe_claude: default_probe_claude(),
+            model_fallbacks: Vec::new(),
+            resume_session: false,
+            section_headings: SectionHeadings::default(),
+            failure_body_template: None,
+            on_failure: default_on_failure(),
+            debug_bundles: default_debug_bundles(),
+            history_max_bytes: default_history_max_bytes(),
+            missing_purpose_text: None,
+            format: default_format(),
+            madr_status: default_madr_status(),
+            max_prompt_tokens: default_max_prompt_tokens(),
+            continuity: false,
+            continuity_count: default_continuity_count(),
+            history_context: false,
+            cross_link: default_cross_link(),
+            suggest_commit_message: false,
+            review_section: false,
+            include_context_in_idr: false,
+            diagram: false,
+            refine: false,
+            front_matter: false,
+            obsidian_mode: false,
+            classify_in_filename: false,
+            latest_mode: default_latest_mode(),
+            output_layout: default_output_layout(),
+            number_width: default_number_width(),
+            output_mode: default_output_mode(),
+            notes_mode: false,
+            notes_keep_files: false,
+            lock_wait_secs: 0,
+            projects_dir: None,
+            output_location: default_output_location(),
+            repo_output_subdir: default_repo_output_subdir(),
+            skip_rules: default_skip_rules(),
+            merge_rules: default_merge_rules(),
+            large_diff_strategy: default_large_diff_strategy(),
+            summary_model: default_summary_model(),
+            redact_patterns: Vec::new(),
+            redact_paths: default_redact_paths(),
+            redact_paths_mode: None,
+            split_concurrency: default_split_concurrency(),
+            fail_on_skip: false,
+            run_in_ci: false,
+            clipboard: default_clipboard(),
+            notify: false,
+            diff_filter_command: None,
+            diff_filter_timeout_secs: default_diff_filter_timeout_secs(),
+            strip_noise: default_strip_noise(),
+            noise_paths: default_noise_paths(),
+            import_prefixes: default_import_prefixes(),
+            prompt_context_lines: None,
+            binary_density: default_binary_density(),
+            binary_min_chars: default_binary_min_chars(),
+            post_generate_command: None,
+            post_generate_on_skip: false,
+            post_generate_timeout_secs: default_post_generate_timeout_secs(),
+            issue_pattern: default_issue_pattern(),
+            issue_url_template: None,
+            export_link_template: None,
+            publish_integration: None,
         }
     }
 }
@@ -110,6 +714,7 @@ mod tests {
         assert_eq!(config.language, "ja");
         assert_eq!(config.model, "sonnet");
         assert_eq!(config.session_max_age_min, 30);
+        assert_eq!(config.context_max_chars, 8000);
         assert!(config.output_dir.is_none());
     }
 
@@ -130,6 +735,38 @@ mod tests {
         assert_eq!(config.session_max_age_min, 60);
     }
 
+    #[test]
+    fn load_reads_language_fallback() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, r#"{{"language": "auto", "language_fallback": "en"}}"#).unwrap();
+
+        let config = Config::load(Some(file.path()));
+
+        assert_eq!(config.language, "auto");
+        assert_eq!(config.language_fallback, "en");
+        assert_eq!(Config::default().language_fallback, "ja");
+    }
+
+    #[test]
+    fn load_reads_bilingual_settings() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(
+            file,
+            r#"{{"language": "ja+en", "bilingual_layout": "files", "translation_model": "haiku"}}"#
+        )
+        .unwrap();
+
+        let config = Config::load(Some(file.path()));
+
+        assert_eq!(config.language, "ja+en");
+        assert_eq!(config.bilingual_layout, BilingualLayout::Files);
+        assert_eq!(config.translation_model.as_deref(), Some("haiku"));
+        assert_eq!(
+            Config::default().bilingual_layout,
+            BilingualLayout::Sections
+        );
+    }
+
     #[test]
     fn load_with_partial_config_uses_defaults_for_missing_fields() {
         let mut file = NamedTempFile::new().unwrap();
@@ -152,6 +789,477 @@ mod tests {
         assert_eq!(config.model, "sonnet");
     }
 
+    #[test]
+    fn try_load_reports_invalid_json() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, "{{ invalid json }}").unwrap();
+
+        let err = Config::try_load(Some(file.path())).unwrap_err();
+
+        assert!(
+            matches!(&err, IdrError::ConfigInvalid { path, .. } if path == file.path()),
+            "{err:?}"
+        );
+    }
+
+    #[test]
+    fn load_reads_api_backend_from_config() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, r#"{{"backend": "api", "timeout_secs": 60}}"#).unwrap();
+
+        let config = Config::load(Some(file.path()));
+
+        assert_eq!(config.backend, Backend::Api);
+        assert_eq!(config.timeout_secs, 60);
+        assert_eq!(config.max_retries, 2);
+    }
+
+    #[test]
+    fn load_reads_madr_format_with_default_status() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, r#"{{"format": "madr"}}"#).unwrap();
+
+        let config = Config::load(Some(file.path()));
+
+        assert_eq!(config.format, Format::Madr);
+        assert_eq!(config.madr_status, "accepted");
+    }
+
+    #[test]
+    fn load_reads_large_diff_strategy() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, r#"{{"large_diff_strategy": "summarize"}}"#).unwrap();
+
+        let config = Config::load(Some(file.path()));
+
+        assert_eq!(config.large_diff_strategy, LargeDiffStrategy::Summarize);
+        assert_eq!(config.summary_model, "haiku");
+    }
+
+    #[test]
+    fn load_reads_redaction_settings() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(
+            file,
+            r#"{{"redact_patterns": ["acme-[0-9]{{6}}"], "redact_paths": ["secrets/*"]}}"#
+        )
+        .unwrap();
+
+        let config = Config::load(Some(file.path()));
+
+        assert_eq!(config.redact_patterns, ["acme-[0-9]{6}"]);
+        assert_eq!(config.redact_paths, ["secrets/*"]);
+        assert_eq!(Config::default().redact_paths, [".env*", "*.pem", "*.key"]);
+    }
+
+    #[test]
+    fn load_reads_redact_paths_mode() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, r#"{{"redact_paths_mode": "hash-project"}}"#).unwrap();
+
+        let config = Config::load(Some(file.path()));
+
+        assert_eq!(config.redact_paths_mode, Some(RedactPathsMode::HashProject));
+        assert_eq!(Config::default().redact_paths_mode, None);
+    }
+
+    #[test]
+    fn load_reads_fail_on_skip() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, r#"{{"fail_on_skip": true}}"#).unwrap();
+
+        assert!(Config::load(Some(file.path())).fail_on_skip);
+        assert!(!Config::default().fail_on_skip);
+    }
+
+    #[test]
+    fn load_reads_run_in_ci() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, r#"{{"run_in_ci": true}}"#).unwrap();
+
+        assert!(Config::load(Some(file.path())).run_in_ci);
+        assert!(!Config::default().run_in_ci);
+    }
+
+    #[test]
+    fn load_reads_on_failure() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, r#"{{"on_failure": "skip"}}"#).unwrap();
+
+        assert_eq!(Config::load(Some(file.path())).on_failure, OnFailure::Skip);
+        assert_eq!(Config::default().on_failure, OnFailure::Placeholder);
+        assert_eq!(OnFailure::parse("fail"), Some(OnFailure::Fail));
+        assert_eq!(OnFailure::parse("retry"), None);
+    }
+
+    #[test]
+    fn load_reads_debug_bundles() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, r#"{{"debug_bundles": 0}}"#).unwrap();
+
+        assert_eq!(Config::load(Some(file.path())).debug_bundles, 0);
+        assert_eq!(Config::default().debug_bundles, 10);
+    }
+
+    #[test]
+    fn load_reads_history_max_bytes() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, r#"{{"history_max_bytes": 0}}"#).unwrap();
+
+        assert_eq!(Config::load(Some(file.path())).history_max_bytes, 0);
+        assert_eq!(Config::default().history_max_bytes, 1024 * 1024);
+    }
+
+    #[test]
+    fn load_reads_history_context() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, r#"{{"history_context": true}}"#).unwrap();
+
+        assert!(Config::load(Some(file.path())).history_context);
+        assert!(!Config::default().history_context);
+    }
+
+    #[test]
+    fn load_reads_cross_link() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, r#"{{"cross_link": false}}"#).unwrap();
+
+        assert!(!Config::load(Some(file.path())).cross_link);
+        assert!(Config::default().cross_link);
+    }
+
+    #[test]
+    fn load_reads_clipboard() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, r#"{{"clipboard": "content"}}"#).unwrap();
+
+        let config = Config::load(Some(file.path()));
+
+        assert_eq!(config.clipboard, ClipboardMode::Content);
+        assert_eq!(Config::default().clipboard, ClipboardMode::Off);
+    }
+
+    #[test]
+    fn load_reads_notify() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, r#"{{"notify": true}}"#).unwrap();
+
+        assert!(Config::load(Some(file.path())).notify);
+        assert!(!Config::default().notify);
+    }
+
+    #[test]
+    fn load_reads_diff_filter_settings() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(
+            file,
+            r#"{{"diff_filter_command": "strip-generated", "diff_filter_timeout_secs": 5}}"#
+        )
+        .unwrap();
+
+        let config = Config::load(Some(file.path()));
+
+        assert_eq!(
+            config.diff_filter_command.as_deref(),
+            Some("strip-generated")
+        );
+        assert_eq!(config.diff_filter_timeout_secs, 5);
+        assert_eq!(Config::default().diff_filter_timeout_secs, 30);
+    }
+
+    #[test]
+    fn load_reads_noise_settings() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(
+            file,
+            r#"{{"strip_noise": false, "noise_paths": ["dist/**"], "import_prefixes": []}}"#
+        )
+        .unwrap();
+
+        let config = Config::load(Some(file.path()));
+
+        assert!(!config.strip_noise);
+        assert_eq!(config.noise_paths, ["dist/**"]);
+        assert!(config.import_prefixes.is_empty());
+        assert!(Config::default().strip_noise);
+        assert!(
+            Config::default()
+                .noise_paths
+                .contains(&"*.min.js".to_string())
+        );
+    }
+
+    #[test]
+    fn load_reads_prompt_context_lines() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, r#"{{"prompt_context_lines": 0}}"#).unwrap();
+
+        assert_eq!(
+            Config::load(Some(file.path())).prompt_context_lines,
+            Some(0)
+        );
+        assert_eq!(Config::default().prompt_context_lines, None);
+    }
+
+    #[test]
+    fn load_reads_binary_thresholds() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, r#"{{"binary_density": 0.5, "binary_min_chars": 4}}"#).unwrap();
+
+        let config = Config::load(Some(file.path()));
+
+        assert_eq!(config.binary_density, 0.5);
+        assert_eq!(config.binary_min_chars, 4);
+        assert_eq!(Config::default().binary_density, 0.3);
+        assert_eq!(Config::default().binary_min_chars, 16);
+    }
+
+    #[test]
+    fn load_reads_post_generate_settings() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(
+            file,
+            r#"{{"post_generate_command": "notify-send \"$IDR_TITLE\"", "post_generate_on_skip": true, "post_generate_timeout_secs": 3}}"#
+        )
+        .unwrap();
+
+        let config = Config::load(Some(file.path()));
+
+        assert_eq!(
+            config.post_generate_command.as_deref(),
+            Some("notify-send \"$IDR_TITLE\"")
+        );
+        assert!(config.post_generate_on_skip);
+        assert_eq!(config.post_generate_timeout_secs, 3);
+        assert_eq!(Config::default().post_generate_timeout_secs, 10);
+    }
+
+    #[test]
+    fn load_reads_split_concurrency() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, r#"{{"split_concurrency": 2}}"#).unwrap();
+
+        let config = Config::load(Some(file.path()));
+
+        assert_eq!(config.split_concurrency, 2);
+        assert_eq!(Config::default().split_concurrency, 4);
+    }
+
+    #[test]
+    fn load_keeps_default_rules_unless_set() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, r#"{{"skip_rules": []}}"#).unwrap();
+
+        let config = Config::load(Some(file.path()));
+
+        assert!(config.skip_rules.is_empty());
+        assert_eq!(config.merge_rules, default_merge_rules());
+    }
+
+    #[test]
+    fn load_reads_by_branch_layout() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, r#"{{"output_layout": "by-branch"}}"#).unwrap();
+
+        let config = Config::load(Some(file.path()));
+
+        assert_eq!(config.output_layout, OutputLayout::ByBranch);
+    }
+
+    #[test]
+    fn load_reads_monthly_output_mode() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, r#"{{"output_mode": "monthly"}}"#).unwrap();
+
+        let config = Config::load(Some(file.path()));
+
+        assert_eq!(config.output_mode, OutputMode::Monthly);
+    }
+
+    #[test]
+    fn load_reads_repo_output_location() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(
+            file,
+            r#"{{"output_location": "repo", "repo_output_subdir": "doc/adr"}}"#
+        )
+        .unwrap();
+
+        let config = Config::load(Some(file.path()));
+
+        assert_eq!(config.output_location, OutputLocation::Repo);
+        assert_eq!(config.repo_output_subdir, PathBuf::from("doc/adr"));
+    }
+
+    #[test]
+    fn load_reads_notes_mode() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, r#"{{"notes_mode": true, "notes_keep_files": true}}"#).unwrap();
+
+        let config = Config::load(Some(file.path()));
+
+        assert!(config.notes_mode);
+        assert!(config.notes_keep_files);
+    }
+
+    #[test]
+    fn load_reads_lock_wait_secs() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, r#"{{"lock_wait_secs": 30}}"#).unwrap();
+
+        let config = Config::load(Some(file.path()));
+
+        assert_eq!(config.lock_wait_secs, 30);
+    }
+
+    #[test]
+    fn load_reads_projects_dir() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, r#"{{"projects_dir": "/srv/claude/projects"}}"#).unwrap();
+
+        let config = Config::load(Some(file.path()));
+
+        assert_eq!(
+            config.projects_dir.as_deref(),
+            Some(Path::new("/srv/claude/projects"))
+        );
+    }
+
+    #[test]
+    fn missing_home_only_when_nothing_places_the_output() {
+        let homeless = Config {
+            workspace_dir: PathBuf::new(),
+            ..Config::default()
+        };
+        assert!(homeless.missing_home().is_some());
+        assert!(
+            Config {
+                output_dir: Some(PathBuf::from("/srv/idr")),
+                ..homeless.clone()
+            }
+            .missing_home()
+            .is_none()
+        );
+        assert!(
+            Config {
+                workspace_dir: PathBuf::from("/srv/workspace"),
+                ..homeless.clone()
+            }
+            .missing_home()
+            .is_none()
+        );
+        assert!(
+            Config {
+                output_location: OutputLocation::Repo,
+                ..homeless
+            }
+            .missing_home()
+            .is_none()
+        );
+    }
+
+    #[test]
+    fn load_reads_failure_text_overrides() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(
+            file,
+            r#"{{"failure_body_template": "TODO\n{{stat}}", "missing_purpose_text": "untitled"}}"#
+        )
+        .unwrap();
+
+        let config = Config::load(Some(file.path()));
+
+        assert_eq!(
+            config.failure_body_template.as_deref(),
+            Some("TODO\n{stat}")
+        );
+        assert_eq!(config.missing_purpose_text.as_deref(), Some("untitled"));
+    }
+
+    #[test]
+    fn load_reads_claude_args_from_config() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(
+            file,
+            r#"{{"claude_args": ["--settings", "/tmp/settings.json"]}}"#
+        )
+        .unwrap();
+
+        let config = Config::load(Some(file.path()));
+
+        assert_eq!(config.claude_args, vec!["--settings", "/tmp/settings.json"]);
+    }
+
+    #[test]
+    fn load_reads_obsidian_mode() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, r#"{{"obsidian_mode": true, "front_matter": true}}"#).unwrap();
+
+        let config = Config::load(Some(file.path()));
+
+        assert!(config.obsidian_mode);
+        assert!(config.front_matter);
+        assert!(!Config::default().obsidian_mode);
+    }
+
+    #[test]
+    fn load_reads_classify_in_filename() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, r#"{{"classify_in_filename": true}}"#).unwrap();
+
+        let config = Config::load(Some(file.path()));
+
+        assert!(config.classify_in_filename);
+        assert!(!Config::default().classify_in_filename);
+    }
+
+    #[test]
+    fn load_reads_issue_settings() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(
+            file,
+            r#"{{"issue_pattern": "GH-[0-9]+", "issue_url_template": "https://x/{{id}}"}}"#
+        )
+        .unwrap();
+
+        let config = Config::load(Some(file.path()));
+
+        assert_eq!(config.issue_pattern, "GH-[0-9]+");
+        assert_eq!(config.issue_url_template.as_deref(), Some("https://x/{id}"));
+        assert_eq!(
+            Config::default().issue_pattern,
+            crate::issues::DEFAULT_PATTERN
+        );
+    }
+
+    #[test]
+    fn load_reads_export_link_template() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(
+            file,
+            r#"{{"export_link_template": "https://example.com/blob/main/{{path}}"}}"#
+        )
+        .unwrap();
+
+        let config = Config::load(Some(file.path()));
+
+        assert_eq!(
+            config.export_link_template.as_deref(),
+            Some("https://example.com/blob/main/{path}")
+        );
+    }
+
+    #[test]
+    fn load_reads_publish_integration() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, r#"{{"publish_integration": "mkdocs"}}"#).unwrap();
+
+        let config = Config::load(Some(file.path()));
+
+        assert_eq!(config.publish_integration, Some(PublishIntegration::Mkdocs));
+        assert_eq!(Config::default().publish_integration, None);
+    }
+
     #[test]
     fn load_reads_output_dir_from_config() {
         let mut file = NamedTempFile::new().unwrap();
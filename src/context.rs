@@ -1,60 +1,315 @@
-use crate::jsonl;
-use serde_json::Value;
+use crate::effort::{self, Effort, Tally};
+use crate::jsonl::{self, ContentBlock, SessionEntry};
+use crate::log::verbose;
 use std::collections::BTreeSet;
 use std::path::Path;
 
-pub fn extract(session: &Path) -> Option<String> {
-    let mut changed_files = BTreeSet::new();
-    let mut user_requests = Vec::new();
+const MAX_USER_REQUESTS: usize = 20;
+const MAX_ENTRY_CHARS: usize = 150;
+/// Longest plan or final reply kept for a session report.
+const MAX_BLOCK_CHARS: usize = 2000;
+const TRUNCATED_MARKER: &str = "[context truncated]\n";
 
-    for v in jsonl::iter_values(session) {
-        extract_changed_files(&v, &mut changed_files);
-        extract_user_request(&v, &mut user_requests);
+#[derive(Debug, Default)]
+pub struct SessionContext {
+    pub changed_files: BTreeSet<String>,
+    pub user_requests: Vec<String>,
+    pub commands: Vec<String>,
+    /// First line of each tool result marked as an error.
+    pub errors: Vec<String>,
+    /// The last plan presented with `ExitPlanMode`.
+    pub plan: Option<String>,
+    /// The assistant's last text reply, which usually sums up the session.
+    pub final_reply: Option<String>,
+    /// The last summary entry, written when the session was compacted.
+    pub summary: Option<String>,
+    pub effort: Effort,
+}
+
+pub fn extract(session: &Path) -> Option<SessionContext> {
+    let mut ctx = SessionContext::default();
+
+    let mut tally = Tally::default();
+    let mut values = jsonl::iter_values(session);
+    for value in values.by_ref() {
+        let timestamp = value.get("timestamp").and_then(effort::timestamp_millis);
+        let entry = SessionEntry::from(value);
+        tally.add(timestamp, &entry);
+        extract_tool_uses(&entry, &mut ctx);
+        extract_user_request(&entry, &mut ctx.user_requests);
+        extract_errors(&entry, &mut ctx.errors);
+        if let SessionEntry::Summary(summary) = &entry {
+            ctx.summary = Some(summary.clone());
+        }
+        if matches!(entry, SessionEntry::Assistant(_))
+            && let Some(reply) = last_text(&entry)
+        {
+            ctx.final_reply = Some(reply.to_string());
+        }
     }
+    verbose!("session {}: {}", session.display(), values.stats());
+    ctx.effort = tally.finish();
 
-    if changed_files.is_empty() && user_requests.is_empty() {
+    if ctx.changed_files.is_empty()
+        && ctx.user_requests.is_empty()
+        && ctx.commands.is_empty()
+        && ctx.errors.is_empty()
+        && ctx.plan.is_none()
+        && ctx.final_reply.is_none()
+        && ctx.summary.is_none()
+    {
         return None;
     }
+    Some(ctx)
+}
+
+impl SessionContext {
+    pub fn render(&self) -> String {
+        render_sections(&self.changed_files, self.recent_requests(), &self.commands)
+    }
+
+    /// Renders the context within `max_chars`, dropping the oldest user
+    /// requests and the longest commands first. The changed-file list is
+    /// always kept, even if it alone exceeds the budget.
+    pub fn render_within(&self, max_chars: usize) -> String {
+        let full = self.render();
+        if full.chars().count() <= max_chars {
+            return full;
+        }
+
+        let requests = self.recent_requests();
+        let requests_len = entries_len(requests);
+        let commands_len = entries_len(&self.commands);
+        let total = requests_len + commands_len;
+        if total == 0 {
+            return full;
+        }
+
+        let mut fixed = render_sections(&self.changed_files, &[], &[])
+            .chars()
+            .count()
+            + TRUNCATED_MARKER.len()
+            + 1;
+        if !self.commands.is_empty() {
+            fixed += COMMANDS_HEADER.len() + 1;
+        }
+        let available = max_chars.saturating_sub(fixed);
+        let requests_budget = available * requests_len / total;
+        let commands_budget = available - requests_budget;
+
+        let kept_requests = keep_newest(requests, requests_budget);
+        let kept_commands = drop_longest(&self.commands, commands_budget);
+
+        let mut output = render_sections(&self.changed_files, &kept_requests, &kept_commands);
+        output.push('\n');
+        output.push_str(TRUNCATED_MARKER);
+        output
+    }
 
+    /// Everything extracted, for a session report: every request, command,
+    /// and error, the plan, and the final reply, within `max_chars`. When
+    /// over budget, the oldest requests and errors and the longest commands
+    /// go first; the plan and final reply are cut to [`MAX_BLOCK_CHARS`]
+    /// but always kept.
+    pub fn render_report(&self, max_chars: usize) -> String {
+        let files: Vec<String> = self.changed_files.iter().cloned().collect();
+        let render = |requests: &[String], commands: &[String], errors: &[String]| {
+            let lists = [
+                (REQUESTS_HEADER, requests),
+                (COMMANDS_HEADER, commands),
+                (ERRORS_HEADER, errors),
+                (FILES_HEADER, &files[..]),
+            ];
+            let mut out = String::new();
+            for (header, entries) in lists.iter().filter(|(_, e)| !e.is_empty()) {
+                out.push_str(header);
+                for entry in entries.iter() {
+                    out.push_str(&format!("- {entry}\n"));
+                }
+                out.push('\n');
+            }
+            if let Some(plan) = &self.plan {
+                out.push_str(&format!("# Plan:\n{}\n\n", truncate_block(plan)));
+            }
+            if let Some(reply) = &self.final_reply {
+                out.push_str(&format!(
+                    "# Final assistant message:\n{}\n\n",
+                    truncate_block(reply)
+                ));
+            }
+            out
+        };
+
+        let full = render(&self.user_requests, &self.commands, &self.errors);
+        if full.chars().count() <= max_chars {
+            return full;
+        }
+        let sizes = [
+            entries_len(&self.user_requests),
+            entries_len(&self.commands),
+            entries_len(&self.errors),
+        ];
+        let total: usize = sizes.iter().sum();
+        let headers = [REQUESTS_HEADER, COMMANDS_HEADER, ERRORS_HEADER]
+            .iter()
+            .map(|h| h.len() + 1)
+            .sum::<usize>();
+        let fixed = render(&[], &[], &[]).chars().count() + headers + TRUNCATED_MARKER.len();
+        let available = max_chars.saturating_sub(fixed);
+        let share = |size: usize| available * size / total.max(1);
+        let mut output = render(
+            &keep_newest(&self.user_requests, share(sizes[0])),
+            &drop_longest(&self.commands, share(sizes[1])),
+            &keep_newest(&self.errors, share(sizes[2])),
+        );
+        output.push_str(TRUNCATED_MARKER);
+        output
+    }
+
+    fn recent_requests(&self) -> &[String] {
+        &self.user_requests[..self.user_requests.len().min(MAX_USER_REQUESTS)]
+    }
+}
+
+const REQUESTS_HEADER: &str = "# User requests in this session:\n";
+const COMMANDS_HEADER: &str = "# Commands run:\n";
+const ERRORS_HEADER: &str = "# Errors:\n";
+const FILES_HEADER: &str = "# Changed files:\n";
+
+fn render_sections(files: &BTreeSet<String>, requests: &[String], commands: &[String]) -> String {
     let mut output = String::new();
 
-    output.push_str("# Changed files:\n");
-    for file_path in &changed_files {
+    output.push_str(FILES_HEADER);
+    for file_path in files {
         output.push_str(&format!("- {file_path}\n"));
     }
 
     output.push('\n');
-    output.push_str("# User requests in this session:\n");
-    const MAX_USER_REQUESTS: usize = 20;
-    for req in user_requests.iter().take(MAX_USER_REQUESTS) {
+    output.push_str(REQUESTS_HEADER);
+    for req in requests {
         output.push_str(&format!("- {req}\n"));
     }
 
-    Some(output)
+    if !commands.is_empty() {
+        output.push('\n');
+        output.push_str(COMMANDS_HEADER);
+        for cmd in commands {
+            output.push_str(&format!("- {cmd}\n"));
+        }
+    }
+
+    output
 }
 
-fn extract_changed_files(v: &Value, out: &mut BTreeSet<String>) {
-    let Some(arr) = v.pointer("/message/content").and_then(|c| c.as_array()) else {
-        return;
-    };
-    for item in arr {
-        if matches!(
-            item.get("name").and_then(|n| n.as_str()),
-            Some("Write" | "Edit")
-        ) && let Some(file_path) = item.pointer("/input/file_path").and_then(|p| p.as_str())
-        {
-            out.insert(file_path.to_string());
+fn entry_len(entry: &str) -> usize {
+    entry.chars().count() + "- \n".len()
+}
+
+fn entries_len(entries: &[String]) -> usize {
+    entries.iter().map(|e| entry_len(e)).sum()
+}
+
+fn keep_newest(entries: &[String], budget: usize) -> Vec<String> {
+    let mut used = 0;
+    let start = entries
+        .iter()
+        .rposition(|e| {
+            used += entry_len(e);
+            used > budget
+        })
+        .map_or(0, |i| i + 1);
+    entries[start..].to_vec()
+}
+
+fn drop_longest(entries: &[String], budget: usize) -> Vec<String> {
+    let mut kept: Vec<&String> = entries.iter().collect();
+    let mut used = entries_len(entries);
+    while used > budget {
+        let Some((idx, _)) = kept
+            .iter()
+            .enumerate()
+            .max_by_key(|(i, e)| (e.chars().count(), std::cmp::Reverse(*i)))
+        else {
+            break;
+        };
+        used -= entry_len(kept.remove(idx));
+    }
+    kept.into_iter().cloned().collect()
+}
+
+fn truncate_entry(text: &str) -> String {
+    text.chars().take(MAX_ENTRY_CHARS).collect()
+}
+
+fn truncate_block(text: &str) -> String {
+    let text = text.trim();
+    if text.chars().count() <= MAX_BLOCK_CHARS {
+        return text.to_string();
+    }
+    let kept: String = text.chars().take(MAX_BLOCK_CHARS).collect();
+    format!("{kept}…")
+}
+
+fn extract_tool_uses(entry: &SessionEntry, ctx: &mut SessionContext) {
+    for (name, input) in entry.tool_uses() {
+        match name {
+            "Write" | "Edit" => {
+                if let Some(file_path) = input.get("file_path").and_then(|p| p.as_str()) {
+                    ctx.changed_files.insert(file_path.to_string());
+                }
+            }
+            "ExitPlanMode" => {
+                if let Some(plan) = input.get("plan").and_then(|p| p.as_str()) {
+                    ctx.plan = Some(plan.to_string());
+                }
+            }
+            "Bash" => {
+                if let Some(command) = input.get("command").and_then(|c| c.as_str())
+                    && let Some(first_line) = command.lines().find(|l| !l.trim().is_empty())
+                {
+                    ctx.commands.push(truncate_entry(first_line.trim()));
+                }
+            }
+            _ => {}
         }
     }
 }
 
-fn extract_user_request(v: &Value, out: &mut Vec<String>) {
-    if v.get("type").and_then(|t| t.as_str()) != Some("user") {
-        return;
+/// The first line of each failed tool result in `entry`.
+fn extract_errors(entry: &SessionEntry, out: &mut Vec<String>) {
+    for block in entry.blocks() {
+        let ContentBlock::ToolResult {
+            content,
+            is_error: true,
+        } = block
+        else {
+            continue;
+        };
+        let text = match content {
+            serde_json::Value::String(text) => text.as_str(),
+            serde_json::Value::Array(items) => items
+                .iter()
+                .find_map(|item| item.get("text")?.as_str())
+                .unwrap_or_default(),
+            _ => "",
+        };
+        if let Some(line) = text.lines().find(|l| !l.trim().is_empty()) {
+            out.push(truncate_entry(line.trim()));
+        }
     }
-    if let Some(content) = v.pointer("/message/content").and_then(|c| c.as_str()) {
-        let truncated: String = content.chars().take(150).collect();
-        out.push(truncated);
+}
+
+/// The last non-empty text block of `entry`.
+fn last_text(entry: &SessionEntry) -> Option<&str> {
+    entry.blocks().iter().rev().find_map(|block| match block {
+        ContentBlock::Text(text) if !text.trim().is_empty() => Some(text.as_str()),
+        _ => None,
+    })
+}
+
+fn extract_user_request(entry: &SessionEntry, out: &mut Vec<String>) {
+    if let Some(text) = entry.user_text() {
+        out.push(truncate_entry(text));
     }
 }
 
@@ -99,12 +354,37 @@ mod tests {
             ],
         );
 
-        let result = extract(&jsonl).unwrap();
+        let result = extract(&jsonl).unwrap().render();
         assert!(result.contains("# Changed files:"));
         assert!(result.contains("- src/lib.rs"));
         assert!(result.contains("- src/main.rs"));
     }
 
+    #[test]
+    fn extract_reads_windows_and_half_synced_transcripts_like_clean_ones() {
+        let dir = TempDir::new().unwrap();
+        let lines = [
+            r#"{"type":"user","message":{"content":"add retries"}}"#,
+            r#"{"type":"assistant","message":{"content":[{"type":"tool_use","name":"Edit","input":{"file_path":"src/net.rs"}}]}}"#,
+        ];
+        let clean = write_jsonl(dir.path(), "clean.jsonl", &lines);
+        let messy = dir.path().join("messy.jsonl");
+        std::fs::write(
+            &messy,
+            format!(
+                "\u{feff}{}\r\n{}\r\n{{\"type\":\"user\",\"mess",
+                lines[0], lines[1]
+            ),
+        )
+        .unwrap();
+
+        assert_eq!(
+            extract(&messy).unwrap().render(),
+            extract(&clean).unwrap().render()
+        );
+        assert!(crate::session::has_write_or_edit(&messy));
+    }
+
     #[test]
     fn extract_deduplicates_changed_files() {
         let dir = TempDir::new().unwrap();
@@ -117,7 +397,7 @@ mod tests {
             ],
         );
 
-        let result = extract(&jsonl).unwrap();
+        let result = extract(&jsonl).unwrap().render();
         let count = result.matches("- src/main.rs").count();
         assert_eq!(count, 1);
     }
@@ -135,12 +415,30 @@ mod tests {
             ],
         );
 
-        let result = extract(&jsonl).unwrap();
+        let result = extract(&jsonl).unwrap().render();
         assert!(result.contains("# User requests in this session:"));
         assert!(result.contains("- fix the bug in auth module"));
         assert!(result.contains("- looks good, thanks"));
     }
 
+    #[test]
+    fn extract_keeps_the_last_summary() {
+        let dir = TempDir::new().unwrap();
+        let jsonl = write_jsonl(
+            dir.path(),
+            "session.jsonl",
+            &[
+                r#"{"type":"summary","summary":"Auth rework","leafUuid":"a"}"#,
+                r#"{"type":"summary","summary":"JWT migration","leafUuid":"b"}"#,
+            ],
+        );
+
+        assert_eq!(
+            extract(&jsonl).unwrap().summary.as_deref(),
+            Some("JWT migration")
+        );
+    }
+
     #[test]
     fn extract_truncates_long_user_messages() {
         let dir = TempDir::new().unwrap();
@@ -148,7 +446,7 @@ mod tests {
         let line = format!(r#"{{"type":"user","message":{{"content":"{long_msg}"}}}}"#);
         let jsonl = write_jsonl(dir.path(), "session.jsonl", &[&line]);
 
-        let result = extract(&jsonl).unwrap();
+        let result = extract(&jsonl).unwrap().render();
         let expected_truncated = "a".repeat(150);
         assert!(result.contains(&expected_truncated));
         let too_long = "a".repeat(151);
@@ -167,7 +465,7 @@ mod tests {
             ],
         );
 
-        let result = extract(&jsonl).unwrap();
+        let result = extract(&jsonl).unwrap().render();
         assert!(result.contains("# Changed files:"));
         assert!(result.contains("- x.rs"));
         assert!(!result.contains("image"));
@@ -187,7 +485,7 @@ mod tests {
             ],
         );
 
-        let result = extract(&jsonl).unwrap();
+        let result = extract(&jsonl).unwrap().render();
         assert!(result.contains("- a.rs"));
         assert!(result.contains("- hello"));
     }
@@ -205,7 +503,7 @@ mod tests {
             ],
         );
 
-        let result = extract(&jsonl).unwrap();
+        let result = extract(&jsonl).unwrap().render();
         let expected = "\
 # Changed files:
 - src/bar.ts
@@ -226,11 +524,198 @@ mod tests {
         let line_refs: Vec<&str> = lines.iter().map(|s| s.as_str()).collect();
         let jsonl = write_jsonl(dir.path(), "session.jsonl", &line_refs);
 
-        let result = extract(&jsonl).unwrap();
+        let result = extract(&jsonl).unwrap().render();
         let count = result.matches("\n- request ").count();
         assert_eq!(count, 20);
         assert!(result.contains("- request 0"));
         assert!(result.contains("- request 19"));
         assert!(!result.contains("- request 20"));
     }
+
+    #[test]
+    fn extract_collects_bash_commands() {
+        let dir = TempDir::new().unwrap();
+        let jsonl = write_jsonl(
+            dir.path(),
+            "session.jsonl",
+            &[
+                r#"{"message":{"content":[{"name":"Bash","input":{"command":"cargo test"}}]}}"#,
+                r#"{"message":{"content":[{"name":"Bash","input":{"command":"\ngit status\ngit diff"}}]}}"#,
+            ],
+        );
+
+        let ctx = extract(&jsonl).unwrap();
+        assert_eq!(ctx.commands, vec!["cargo test", "git status"]);
+        assert!(
+            ctx.render()
+                .contains("# Commands run:\n- cargo test\n- git status\n")
+        );
+    }
+
+    #[test]
+    fn render_omits_commands_section_when_empty() {
+        let ctx = SessionContext {
+            user_requests: vec!["hi".to_string()],
+            ..SessionContext::default()
+        };
+        assert!(!ctx.render().contains("# Commands run:"));
+    }
+
+    fn budget_fixture() -> SessionContext {
+        SessionContext {
+            changed_files: ["src/a.rs", "src/b.rs"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            user_requests: (0..5)
+                .map(|i| format!("request {i} {}", "x".repeat(20)))
+                .collect(),
+            commands: vec![
+                "cargo test".to_string(),
+                format!("grep -rn {}", "y".repeat(60)),
+                "git status".to_string(),
+            ],
+            ..SessionContext::default()
+        }
+    }
+
+    #[test]
+    fn render_within_returns_full_render_when_it_fits() {
+        let ctx = budget_fixture();
+        let full = ctx.render();
+        assert_eq!(ctx.render_within(full.chars().count()), full);
+        assert!(!full.contains("[context truncated]"));
+    }
+
+    #[test]
+    fn render_within_drops_oldest_requests_and_longest_commands() {
+        let ctx = budget_fixture();
+
+        let result = ctx.render_within(250);
+
+        let expected = "\
+# Changed files:
+- src/a.rs
+- src/b.rs
+
+# User requests in this session:
+- request 3 xxxxxxxxxxxxxxxxxxxx
+- request 4 xxxxxxxxxxxxxxxxxxxx
+
+# Commands run:
+- cargo test
+- git status
+
+[context truncated]
+";
+        assert_eq!(result, expected);
+        assert!(result.chars().count() <= 250);
+    }
+
+    #[test]
+    fn render_within_keeps_changed_files_when_budget_is_tiny() {
+        let ctx = budget_fixture();
+
+        let result = ctx.render_within(10);
+
+        let expected = "\
+# Changed files:
+- src/a.rs
+- src/b.rs
+
+# User requests in this session:
+
+[context truncated]
+";
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn render_within_is_deterministic_for_equal_length_commands() {
+        let ctx = SessionContext {
+            commands: vec!["a".repeat(20), "b".repeat(20), "c".repeat(5)],
+            ..SessionContext::default()
+        };
+
+        let result = ctx.render_within(120);
+
+        assert!(!result.contains(&"a".repeat(20)));
+        assert!(result.contains(&"b".repeat(20)));
+        assert!(result.contains(&"c".repeat(5)));
+        assert!(result.ends_with("[context truncated]\n"));
+    }
+
+    #[test]
+    fn render_within_counts_chars_not_bytes() {
+        let ctx = SessionContext {
+            user_requests: vec!["バグを直して".to_string()],
+            ..SessionContext::default()
+        };
+        let full = ctx.render();
+        assert_eq!(ctx.render_within(full.chars().count()), full);
+    }
+
+    fn investigation() -> SessionContext {
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/session-investigation.jsonl");
+        extract(&fixture).unwrap()
+    }
+
+    #[test]
+    fn extract_collects_errors_plan_and_final_reply() {
+        let ctx = investigation();
+
+        assert!(ctx.changed_files.is_empty());
+        assert_eq!(
+            ctx.errors,
+            ["Error from server (NotFound): jobs.batch \"nightly-sync\" not found"]
+        );
+        assert_eq!(
+            ctx.plan.as_deref(),
+            Some("1. Confirm the reindex job holds the lock\n2. Compare both schedules")
+        );
+        assert!(
+            ctx.final_reply
+                .as_deref()
+                .unwrap()
+                .starts_with("The sync job waits on the accounts lock")
+        );
+    }
+
+    #[test]
+    fn render_report_includes_every_section() {
+        let report = investigation().render_report(8000);
+
+        assert!(report.starts_with(
+            "# User requests in this session:\n\
+             - The nightly sync job times out against staging. Find out why.\n\
+             - ok, check the schedules\n\n\
+             # Commands run:\n\
+             - kubectl logs job/nightly-sync --tail=200\n"
+        ));
+        assert!(report.contains("\n# Errors:\n- Error from server (NotFound)"));
+        assert!(report.contains("\n# Plan:\n1. Confirm the reindex job"));
+        assert!(report.contains("\n# Final assistant message:\nThe sync job waits"));
+        assert!(!report.contains("# Changed files:"));
+        assert!(!report.contains("[context truncated]"));
+    }
+
+    #[test]
+    fn render_report_drops_lists_before_plan_and_reply() {
+        let ctx = SessionContext {
+            user_requests: (0..50).map(|i| format!("request {i}")).collect(),
+            plan: Some("the plan".to_string()),
+            final_reply: Some("all done".to_string()),
+            ..SessionContext::default()
+        };
+
+        let report = ctx.render_report(200);
+
+        assert!(report.chars().count() <= 200, "{report}");
+        assert!(report.contains("- request 49\n"));
+        assert!(!report.contains("- request 0\n"));
+        assert!(report.contains("# Plan:\nthe plan\n"));
+        assert!(report.contains("# Final assistant message:\nall done\n"));
+        assert!(report.ends_with("[context truncated]\n"));
+    }
 }
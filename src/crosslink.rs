@@ -0,0 +1,258 @@
+//! Links between records of one directory that changed the same files: a
+//! related-records section in the new record, and a line in the closest
+//! earlier one pointing forward to it.
+
+use crate::config::{Config, Format, OutputMode};
+use crate::lock;
+use crate::log::warn;
+use crate::notes;
+use crate::path;
+use crate::record;
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Most earlier records listed in the related-records section.
+const MAX_RELATED: usize = 5;
+
+/// An earlier record in the directory, reduced to what ranking needs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sibling {
+    pub file_name: String,
+    pub number: u32,
+    pub title: String,
+    /// Paths of its per-file headings.
+    pub files: BTreeSet<String>,
+}
+
+/// The records of `dir`, parsed for their titles and per-file headings.
+pub fn siblings(dir: &Path, config: &Config) -> Vec<Sibling> {
+    path::list_records(dir, config.format)
+        .into_iter()
+        .filter_map(|(number, file)| {
+            let text = fs::read_to_string(&file).ok()?;
+            let parsed = record::parse(&text, config);
+            Some(Sibling {
+                file_name: file.file_name()?.to_string_lossy().into_owned(),
+                number,
+                title: parsed.title,
+                files: parsed.files.into_iter().map(|f| f.path).collect(),
+            })
+        })
+        .collect()
+}
+
+/// The siblings that share any of `files`, most shared files first and the
+/// most recent first among equals, at most [`MAX_RELATED`].
+pub fn rank<'a>(files: &BTreeSet<String>, siblings: &'a [Sibling]) -> Vec<&'a Sibling> {
+    let mut scored: Vec<(usize, &Sibling)> = siblings
+        .iter()
+        .map(|sibling| (sibling.files.intersection(files).count(), sibling))
+        .filter(|(shared, _)| *shared > 0)
+        .collect();
+    scored.sort_by(|(a, x), (b, y)| b.cmp(a).then(y.number.cmp(&x.number)));
+    scored
+        .into_iter()
+        .take(MAX_RELATED)
+        .map(|(_, sibling)| sibling)
+        .collect()
+}
+
+/// The `## 関連IDR` section listing `related`, to close the new record's
+/// body; empty when there are none.
+pub fn section(related: &[&Sibling], config: &Config) -> String {
+    if related.is_empty() {
+        return String::new();
+    }
+    let links: String = related
+        .iter()
+        .map(|sibling| {
+            let name = &sibling.file_name;
+            format!("- [{name}]({name}): {}\n", sibling.title)
+        })
+        .collect();
+    format!("## {}\n\n{}", labels(config).0, links.trim_end())
+}
+
+/// `> 後続IDR: [name](name): title`, the line [`add_backlink`] appends.
+fn backlink_line(target: &str, title: &str, config: &Config) -> String {
+    format!("> {}: [{target}]({target}): {title}", labels(config).1)
+}
+
+/// Appends a line pointing to `target` to the end of `text`, or `None`
+/// when `text` already has one.
+pub fn with_backlink(text: &str, target: &str, title: &str, config: &Config) -> Option<String> {
+    let label = format!("> {}: ", labels(config).1);
+    let link = format!("{label}[{target}]({target})");
+    if text.lines().any(|line| line.starts_with(&link)) {
+        return None;
+    }
+    let text = text.trim_end_matches('\n');
+    // Backlinks sit together, a blank line below the rest of the record.
+    let separator = match text.lines().last() {
+        Some(last) if last.starts_with(&label) => "\n",
+        _ => "\n\n",
+    };
+    let line = backlink_line(target, title, config);
+    Some(format!("{text}{separator}{line}\n"))
+}
+
+/// Adds a backlink to `target` at the end of `record`, leaving the rest of
+/// the file as it is. Returns whether the file changed.
+pub fn add_backlink(record: &Path, target: &str, title: &str, config: &Config) -> io::Result<bool> {
+    let dir = record.parent().unwrap_or(Path::new("."));
+    let _lock = lock::acquire_warn(dir);
+    let text = fs::read_to_string(record)?;
+    match with_backlink(&text, target, title, config) {
+        Some(updated) => fs::write(record, updated).map(|()| true),
+        None => Ok(false),
+    }
+}
+
+/// Like [`add_backlink`], but only warns.
+pub fn add_backlink_warn(record: &Path, target: &str, title: &str, config: &Config) {
+    if let Err(e) = add_backlink(record, target, title, config) {
+        warn!("cannot link {} to {target}: {e}", record.display());
+    }
+}
+
+/// Whether records in `config`'s output are cross-linked at all: they must
+/// be IDR files that stay in the output directory, with per-file headings
+/// to compare.
+pub fn enabled(config: &Config) -> bool {
+    config.cross_link
+        && config.format == Format::Idr
+        && config.output_mode == OutputMode::Files
+        && !notes::replaces_files(config)
+}
+
+/// (section heading, backlink label) in the configured language.
+fn labels(config: &Config) -> (&'static str, &'static str) {
+    match config.language.as_str() {
+        "ja" => ("関連IDR", "後続IDR"),
+        _ => ("Related records", "Followed up by"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn sibling(number: u32, files: &[&str]) -> Sibling {
+        Sibling {
+            file_name: format!("idr-{number:02}.md"),
+            number,
+            title: format!("Record {number}"),
+            files: files.iter().map(|f| f.to_string()).collect(),
+        }
+    }
+
+    fn files(paths: &[&str]) -> BTreeSet<String> {
+        paths.iter().map(|p| p.to_string()).collect()
+    }
+
+    fn numbers(ranked: &[&Sibling]) -> Vec<u32> {
+        ranked.iter().map(|s| s.number).collect()
+    }
+
+    #[test]
+    fn rank_orders_by_shared_files_then_recency() {
+        let siblings = [
+            sibling(1, &["src/a.rs", "src/b.rs"]),
+            sibling(2, &["src/c.rs"]),
+            sibling(3, &["src/a.rs"]),
+            sibling(4, &["src/b.rs", "src/z.rs"]),
+            sibling(5, &[]),
+        ];
+
+        let ranked = rank(&files(&["src/a.rs", "src/b.rs"]), &siblings);
+
+        assert_eq!(numbers(&ranked), [1, 4, 3]);
+        assert!(rank(&files(&["README.md"]), &siblings).is_empty());
+        assert!(rank(&BTreeSet::new(), &siblings).is_empty());
+    }
+
+    #[test]
+    fn rank_keeps_at_most_max_related() {
+        let siblings: Vec<Sibling> = (1..=8).map(|n| sibling(n, &["src/a.rs"])).collect();
+
+        let ranked = rank(&files(&["src/a.rs"]), &siblings);
+
+        assert_eq!(numbers(&ranked), [8, 7, 6, 5, 4]);
+    }
+
+    #[test]
+    fn section_links_titles_in_the_configured_language() {
+        let siblings = [sibling(3, &["a"]), sibling(1, &["a"])];
+        let related: Vec<&Sibling> = siblings.iter().collect();
+        let en = Config {
+            language: "en".to_string(),
+            ..Config::default()
+        };
+
+        assert_eq!(
+            section(&related, &Config::default()),
+            "## 関連IDR\n\n- [idr-03.md](idr-03.md): Record 3\n- [idr-01.md](idr-01.md): Record 1"
+        );
+        assert!(section(&related, &en).starts_with("## Related records\n\n"));
+        assert_eq!(section(&[], &en), "");
+    }
+
+    #[test]
+    fn with_backlink_appends_once() {
+        let config = Config::default();
+        let text = "# IDR: Old\n\nbody\n\n<!-- idr-metadata v=1 -->\n\n";
+
+        let once = with_backlink(text, "idr-07-new.md", "New", &config).unwrap();
+
+        assert_eq!(
+            once,
+            "# IDR: Old\n\nbody\n\n<!-- idr-metadata v=1 -->\n\n> 後続IDR: [idr-07-new.md](idr-07-new.md): New\n"
+        );
+        assert_eq!(
+            with_backlink(&once, "idr-07-new.md", "Renamed", &config),
+            None
+        );
+        let twice = with_backlink(&once, "idr-08.md", "Newer", &config).unwrap();
+        assert_eq!(
+            twice,
+            format!("{once}> 後続IDR: [idr-08.md](idr-08.md): Newer\n")
+        );
+    }
+
+    #[test]
+    fn siblings_and_add_backlink_work_on_a_directory() {
+        let dir = TempDir::new().unwrap();
+        let config = Config::default();
+        let old = "# IDR: Add retries\n\n> 2026-10-01 10:00\n\n## 変更概要\n\nRetries.\n\n## 主要な変更\n\n### [src/api.rs](src/api.rs)\n\n**理由**: flaky network\n\n---\n\n### git diff --stat\n```\n src/api.rs | 3 +++\n```\n";
+        fs::write(dir.path().join("idr-01-add-retries.md"), old).unwrap();
+        fs::write(dir.path().join("notes.md"), "not a record\n").unwrap();
+
+        let found = siblings(dir.path(), &config);
+        assert_eq!(
+            found,
+            [Sibling {
+                file_name: "idr-01-add-retries.md".to_string(),
+                number: 1,
+                title: "Add retries".to_string(),
+                files: files(&["src/api.rs"]),
+            }]
+        );
+
+        let record = dir.path().join("idr-01-add-retries.md");
+        assert!(add_backlink(&record, "idr-02-backoff.md", "Backoff", &config).unwrap());
+        assert!(!add_backlink(&record, "idr-02-backoff.md", "Backoff", &config).unwrap());
+        let text = fs::read_to_string(&record).unwrap();
+        assert_eq!(
+            text,
+            format!("{old}\n> 後続IDR: [idr-02-backoff.md](idr-02-backoff.md): Backoff\n")
+        );
+        assert_eq!(
+            siblings(dir.path(), &config)[0].files,
+            files(&["src/api.rs"])
+        );
+        assert!(!dir.path().join(lock::FILE_NAME).exists());
+    }
+}
@@ -0,0 +1,891 @@
+//! Per-file view of a unified diff, and the planner that trims it to fit a
+//! token budget.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileDiff {
+    pub path: String,
+    /// `diff --git`, index, mode, and `---`/`+++` lines.
+    pub header: Vec<String>,
+    pub hunks: Vec<Hunk>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hunk {
+    /// The `@@ -a,b +c,d @@` line.
+    pub header: String,
+    pub lines: Vec<String>,
+}
+
+/// Lockfiles and snapshots: large, machine-written, and rarely worth a
+/// sentence in an IDR.
+const LOW_VALUE_SUFFIXES: &[&str] = &[
+    "Cargo.lock",
+    "package-lock.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "Gemfile.lock",
+    "poetry.lock",
+    "composer.lock",
+    "go.sum",
+    ".snap",
+];
+
+pub fn parse(diff: &str) -> Vec<FileDiff> {
+    let mut files: Vec<FileDiff> = Vec::new();
+    for line in diff.lines() {
+        if let Some(rest) = line.strip_prefix("diff --git ") {
+            let path = rest.split_once(" b/").map_or(rest, |(_, b)| b).to_string();
+            files.push(FileDiff {
+                path,
+                header: vec![line.to_string()],
+                hunks: Vec::new(),
+            });
+            continue;
+        }
+        let Some(file) = files.last_mut() else {
+            continue;
+        };
+        if line.starts_with("@@") {
+            file.hunks.push(Hunk {
+                header: line.to_string(),
+                lines: Vec::new(),
+            });
+        } else if let Some(hunk) = file.hunks.last_mut() {
+            hunk.lines.push(line.to_string());
+        } else {
+            file.header.push(line.to_string());
+        }
+    }
+    files
+}
+
+pub fn render(files: &[FileDiff]) -> String {
+    let mut out = String::new();
+    for file in files {
+        for line in &file.header {
+            out.push_str(line);
+            out.push('\n');
+        }
+        for hunk in &file.hunks {
+            out.push_str(&hunk.header);
+            out.push('\n');
+            for line in &hunk.lines {
+                out.push_str(line);
+                out.push('\n');
+            }
+        }
+    }
+    out
+}
+
+/// Added plus removed lines, as `git diff --numstat` would total them.
+pub fn changed_lines(diff: &str) -> u64 {
+    parse(diff)
+        .iter()
+        .flat_map(|file| &file.hunks)
+        .flat_map(|hunk| &hunk.lines)
+        .filter(|line| line.starts_with('+') || line.starts_with('-'))
+        .count() as u64
+}
+
+/// Line numbers from a `@@ -a,b +c,d @@` header. An omitted length is 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HunkRange {
+    pub old_start: u32,
+    pub old_len: u32,
+    pub new_start: u32,
+    pub new_len: u32,
+}
+
+pub fn parse_hunk_header(header: &str) -> Option<HunkRange> {
+    let ranges = header.strip_prefix("@@ ")?.split(" @@").next()?;
+    let (old, new) = ranges.split_once(' ')?;
+    let side = |s: &str| -> Option<(u32, u32)> {
+        match s.split_once(',') {
+            Some((start, len)) => Some((start.parse().ok()?, len.parse().ok()?)),
+            None => Some((s.parse().ok()?, 1)),
+        }
+    };
+    let (old_start, old_len) = side(old.strip_prefix('-')?)?;
+    let (new_start, new_len) = side(new.strip_prefix('+')?)?;
+    Some(HunkRange {
+        old_start,
+        old_len,
+        new_start,
+        new_len,
+    })
+}
+
+impl FileDiff {
+    pub fn is_deleted(&self) -> bool {
+        self.header
+            .iter()
+            .any(|l| l.starts_with("deleted file mode") || l == "+++ /dev/null")
+    }
+
+    pub fn is_new(&self) -> bool {
+        self.header
+            .iter()
+            .any(|l| l.starts_with("new file mode") || l == "--- /dev/null")
+    }
+
+    /// The path a renamed file had before the diff.
+    pub fn renamed_from(&self) -> Option<&str> {
+        self.header
+            .iter()
+            .find_map(|l| l.strip_prefix("rename from "))
+    }
+}
+
+/// `L{start}-{end}` for one side of a hunk; a side with no lines (pure
+/// insertion or removal) is placed after the line it follows.
+fn format_range(start: u32, len: u32) -> String {
+    if len == 0 {
+        format!("after L{start} (no lines on this side)")
+    } else {
+        format!("L{start}-{}", start + len - 1)
+    }
+}
+
+/// One line per file listing the line range of each hunk: new-file lines,
+/// or old-file lines for deleted files.
+pub fn line_index(files: &[FileDiff]) -> String {
+    let mut out = String::new();
+    for file in files {
+        let deleted = file.is_deleted();
+        let ranges: Vec<String> = file
+            .hunks
+            .iter()
+            .filter_map(|h| parse_hunk_header(&h.header))
+            .enumerate()
+            .map(|(i, r)| {
+                let range = if deleted {
+                    format_range(r.old_start, r.old_len)
+                } else {
+                    format_range(r.new_start, r.new_len)
+                };
+                format!("hunk {} → {range}", i + 1)
+            })
+            .collect();
+        if ranges.is_empty() {
+            continue;
+        }
+        let marker = if deleted { " (deleted)" } else { "" };
+        out.push_str(&format!("{}{marker}: {}\n", file.path, ranges.join(", ")));
+    }
+    out
+}
+
+pub fn is_low_value_path(path: &str) -> bool {
+    path.contains("__snapshots__/") || LOW_VALUE_SUFFIXES.iter().any(|s| path.ends_with(s))
+}
+
+impl Hunk {
+    /// True when every change only adds, removes, or moves whitespace
+    /// outside string literals: reindentation, rewrapping, blank lines.
+    /// Whitespace inside quotes counts, and so does any hunk that leaves a
+    /// literal open, since it may start or end inside one.
+    pub fn is_whitespace_only(&self) -> bool {
+        self.has_changes()
+            && matches!(
+                (self.significant('-'), self.significant('+')),
+                (Some(old), Some(new)) if old == new
+            )
+    }
+
+    /// True when the changed lines are all imports (lines starting with one
+    /// of `prefixes`, or blank) and the added ones are the removed ones in
+    /// another order.
+    pub fn is_import_reorder(&self, prefixes: &[String]) -> bool {
+        let is_import = |line: &str| prefixes.iter().any(|p| line.starts_with(p.as_str()));
+        let mut removed = Vec::new();
+        let mut added = Vec::new();
+        for line in &self.lines {
+            let (side, text) = match line.split_at_checked(1) {
+                Some(("-", text)) => (&mut removed, text.trim()),
+                Some(("+", text)) => (&mut added, text.trim()),
+                _ => continue,
+            };
+            if text.is_empty() {
+                continue;
+            }
+            if !is_import(text) {
+                return false;
+            }
+            side.push(text);
+        }
+        removed.sort_unstable();
+        added.sort_unstable();
+        !removed.is_empty() && removed == added
+    }
+
+    fn has_changes(&self) -> bool {
+        self.lines
+            .iter()
+            .any(|l| l.starts_with('+') || l.starts_with('-'))
+    }
+
+    /// The context and `prefix` lines of the hunk as one text, with
+    /// whitespace outside string literals squeezed out (one space is kept
+    /// between two words). `None` when a literal is still open at the end.
+    fn significant(&self, prefix: char) -> Option<String> {
+        let mut out = String::new();
+        let mut quote: Option<char> = None;
+        let mut gap = false;
+        for line in &self.lines {
+            let text = match line.chars().next() {
+                None => "",
+                Some(c) if c == prefix || c == ' ' => &line[1..],
+                Some(_) => continue,
+            };
+            let mut chars = text.chars();
+            while let Some(c) = chars.next() {
+                if let Some(open) = quote {
+                    out.push(c);
+                    if c == '\\' {
+                        out.extend(chars.next());
+                    } else if c == open {
+                        quote = None;
+                    }
+                } else if c.is_whitespace() {
+                    gap = true;
+                } else {
+                    let word = |c: char| c.is_alphanumeric() || c == '_';
+                    if gap && word(c) && out.chars().next_back().is_some_and(word) {
+                        out.push(' ');
+                    }
+                    gap = false;
+                    out.push(c);
+                    if matches!(c, '"' | '\'' | '`') {
+                        quote = Some(c);
+                    }
+                }
+            }
+            match quote {
+                // Single quotes are apostrophes and lifetimes as often as
+                // literals, which rarely span lines anyway.
+                Some('\'') => quote = None,
+                Some(_) => out.push('\n'),
+                None => gap = true,
+            }
+        }
+        quote.is_none().then_some(out)
+    }
+
+    /// The hunk with at most `context` unchanged lines around each change,
+    /// split where more than twice that many separate two changes, and
+    /// with `@@` headers that match: what `git diff -U<context>` prints for
+    /// any `context` up to the one the diff was made with, except that every
+    /// piece keeps the original header's function text. A hunk whose header
+    /// cannot be read is kept whole.
+    pub fn with_context(&self, context: usize) -> Vec<Hunk> {
+        let Some(range) = parse_hunk_header(&self.header) else {
+            return vec![self.clone()];
+        };
+        // The text git puts after the ranges, usually the enclosing function.
+        let section = self
+            .header
+            .strip_prefix("@@ ")
+            .and_then(|rest| rest.split_once(" @@"))
+            .map_or("", |(_, section)| section);
+        let is_context = |line: &str| line.is_empty() || line.starts_with(' ');
+        let changed: Vec<usize> = self
+            .lines
+            .iter()
+            .enumerate()
+            .filter(|(_, l)| l.starts_with('+') || l.starts_with('-'))
+            .map(|(i, _)| i)
+            .collect();
+        let keep = |i: usize| {
+            changed
+                .iter()
+                .any(|&c| i + context >= c && i <= c + context)
+        };
+
+        let mut pieces = Vec::new();
+        let mut piece: Option<(HunkRange, Vec<String>)> = None;
+        // A side with no lines names the line before, so the next line on it
+        // is one further.
+        let mut old_next = range.old_start + u32::from(range.old_len == 0);
+        let mut new_next = range.new_start + u32::from(range.new_len == 0);
+        let mut kept_previous = false;
+        for (i, line) in self.lines.iter().enumerate() {
+            // `\ No newline at end of file` belongs to the line above.
+            if line.starts_with('\\') {
+                if kept_previous && let Some((_, lines)) = piece.as_mut() {
+                    lines.push(line.clone());
+                }
+                continue;
+            }
+            let (old, new) = if is_context(line) {
+                (1, 1)
+            } else if line.starts_with('-') {
+                (1, 0)
+            } else {
+                (0, 1)
+            };
+            kept_previous = !is_context(line) || keep(i);
+            if kept_previous {
+                let (range, lines) = piece.get_or_insert_with(|| {
+                    let start = HunkRange {
+                        old_start: old_next,
+                        old_len: 0,
+                        new_start: new_next,
+                        new_len: 0,
+                    };
+                    (start, Vec::new())
+                });
+                range.old_len += old;
+                range.new_len += new;
+                lines.push(line.clone());
+            } else if let Some(done) = piece.take() {
+                pieces.push(done);
+            }
+            old_next += old;
+            new_next += new;
+        }
+        pieces.extend(piece);
+        pieces
+            .into_iter()
+            .map(|(range, lines)| Hunk {
+                header: format_hunk_header(range, section),
+                lines,
+            })
+            .collect()
+    }
+}
+
+/// `@@ -a,b +c,d @@` the way git writes it: a length of 1 is left out, and
+/// a side with no lines starts at the line before.
+fn format_hunk_header(range: HunkRange, section: &str) -> String {
+    let side = |start: u32, len: u32| match len {
+        0 => format!("{},0", start.saturating_sub(1)),
+        1 => start.to_string(),
+        _ => format!("{start},{len}"),
+    };
+    format!(
+        "@@ -{} +{} @@{section}",
+        side(range.old_start, range.old_len),
+        side(range.new_start, range.new_len)
+    )
+}
+
+/// Every hunk in `files` cut down to `context` unchanged lines around its
+/// changes (see [`Hunk::with_context`]).
+pub fn reduce_context(files: &mut [FileDiff], context: usize) {
+    for file in files {
+        file.hunks = file
+            .hunks
+            .iter()
+            .flat_map(|hunk| hunk.with_context(context))
+            .collect();
+    }
+}
+
+/// `diff` with at most `context` unchanged lines around each change.
+pub fn narrow(diff: &str, context: usize) -> String {
+    let mut files = parse(diff);
+    reduce_context(&mut files, context);
+    render(&files)
+}
+
+/// Outcome of [`plan_trim`]: the diff to send plus a human-readable note for
+/// every trimming step taken, in order.
+#[derive(Debug, Default, PartialEq)]
+pub struct Trimmed {
+    pub files: Vec<FileDiff>,
+    pub notes: Vec<String>,
+}
+
+/// Trims `files` until `estimate(render(files)) <= budget`, in increasing
+/// order of damage: drop low-value hunks (lockfiles, snapshots, whitespace
+/// only), shrink context to 1 line then 0, and finally omit hunks from the
+/// end, leaving a marker in each affected file.
+pub fn plan_trim(
+    mut files: Vec<FileDiff>,
+    budget: usize,
+    estimate: impl Fn(&str) -> usize,
+) -> Trimmed {
+    let fits = |files: &[FileDiff]| estimate(&render(files)) <= budget;
+    let mut notes = Vec::new();
+    if fits(&files) {
+        return Trimmed { files, notes };
+    }
+
+    let mut dropped = Vec::new();
+    'drop: for f in 0..files.len() {
+        let low_value_file = is_low_value_path(&files[f].path);
+        let mut h = 0;
+        while h < files[f].hunks.len() {
+            if low_value_file || files[f].hunks[h].is_whitespace_only() {
+                files[f].hunks.remove(h);
+                if !dropped.contains(&files[f].path) {
+                    dropped.push(files[f].path.clone());
+                }
+                if fits(&files) {
+                    break 'drop;
+                }
+            } else {
+                h += 1;
+            }
+        }
+    }
+    if !dropped.is_empty() {
+        notes.push(format!("dropped low-value hunks in {}", dropped.join(", ")));
+    }
+    if fits(&files) {
+        return Trimmed { files, notes };
+    }
+
+    for context in [1, 0] {
+        reduce_context(&mut files, context);
+        notes.push(format!("reduced context to {context} line(s)"));
+        if fits(&files) {
+            return Trimmed { files, notes };
+        }
+    }
+
+    let mut omitted = 0;
+    'omit: for f in (0..files.len()).rev() {
+        let mut omitted_here = 0;
+        while !files[f].hunks.is_empty() {
+            files[f].hunks.pop();
+            omitted_here += 1;
+            set_omitted_marker(&mut files[f], omitted_here);
+            if fits(&files) {
+                omitted += omitted_here;
+                break 'omit;
+            }
+        }
+        omitted += omitted_here;
+    }
+    if omitted > 0 {
+        notes.push(format!(
+            "omitted {omitted} hunk(s) from the end of the diff"
+        ));
+    }
+    Trimmed { files, notes }
+}
+
+const OMITTED_MARKER: &str = "[... ";
+
+fn set_omitted_marker(file: &mut FileDiff, count: usize) {
+    if file
+        .header
+        .last()
+        .is_some_and(|l| l.starts_with(OMITTED_MARKER))
+    {
+        file.header.pop();
+    }
+    file.header.push(format!(
+        "{OMITTED_MARKER}{count} hunk(s) omitted to fit the prompt budget]"
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "\
+diff --git a/src/lib.rs b/src/lib.rs
+index 111..222 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,5 +1,5 @@
+ use std::io;
+
+-fn old() {}
++fn new() {}
+
+ fn keep() {}
+diff --git a/Cargo.lock b/Cargo.lock
+--- a/Cargo.lock
++++ b/Cargo.lock
+@@ -10,3 +10,3 @@
+ name = \"serde\"
+-version = \"1.0.0\"
++version = \"1.0.1\"
+";
+
+    fn chars(text: &str) -> usize {
+        text.len()
+    }
+
+    #[test]
+    fn changed_lines_counts_hunk_lines_not_headers() {
+        assert_eq!(changed_lines(SAMPLE), 4);
+        assert_eq!(changed_lines(""), 0);
+    }
+
+    #[test]
+    fn parse_splits_files_headers_and_hunks() {
+        let files = parse(SAMPLE);
+
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].path, "src/lib.rs");
+        assert_eq!(files[0].header.len(), 4);
+        assert_eq!(files[0].hunks.len(), 1);
+        assert_eq!(files[0].hunks[0].header, "@@ -1,5 +1,5 @@");
+        assert_eq!(files[0].hunks[0].lines.len(), 6);
+        assert_eq!(files[1].path, "Cargo.lock");
+    }
+
+    #[test]
+    fn render_round_trips_parse() {
+        assert_eq!(render(&parse(SAMPLE)), SAMPLE);
+    }
+
+    #[test]
+    fn parse_hunk_header_reads_both_sides() {
+        assert_eq!(
+            parse_hunk_header("@@ -10,3 +12,5 @@ fn main() {"),
+            Some(HunkRange {
+                old_start: 10,
+                old_len: 3,
+                new_start: 12,
+                new_len: 5,
+            })
+        );
+        assert_eq!(
+            parse_hunk_header("@@ -1 +1 @@"),
+            Some(HunkRange {
+                old_start: 1,
+                old_len: 1,
+                new_start: 1,
+                new_len: 1,
+            })
+        );
+        assert_eq!(parse_hunk_header("@@ garbage @@"), None);
+    }
+
+    #[test]
+    fn line_index_lists_new_side_ranges_per_hunk() {
+        let diff = "\
+diff --git a/src/auth.rs b/src/auth.rs
+@@ -38,20 +40,23 @@ fn login() {
++x
+@@ -99,10 +101,10 @@
++y
+";
+        assert_eq!(
+            line_index(&parse(diff)),
+            "src/auth.rs: hunk 1 → L40-62, hunk 2 → L101-110\n"
+        );
+    }
+
+    #[test]
+    fn line_index_marks_removal_only_hunks() {
+        let diff = "diff --git a/a.rs b/a.rs\n@@ -5,2 +4,0 @@\n-a\n-b\n";
+        assert_eq!(
+            line_index(&parse(diff)),
+            "a.rs: hunk 1 → after L4 (no lines on this side)\n"
+        );
+    }
+
+    #[test]
+    fn line_index_uses_old_ranges_for_deleted_files() {
+        let diff = "\
+diff --git a/old.rs b/old.rs
+deleted file mode 100644
+--- a/old.rs
++++ /dev/null
+@@ -1,20 +0,0 @@
+-gone
+";
+        assert_eq!(
+            line_index(&parse(diff)),
+            "old.rs (deleted): hunk 1 → L1-20\n"
+        );
+    }
+
+    #[test]
+    fn line_index_skips_files_without_hunks() {
+        let diff = "diff --git a/img.png b/img.png\nBinary files differ\n";
+        assert_eq!(line_index(&parse(diff)), "");
+    }
+
+    #[test]
+    fn is_low_value_path_matches_lockfiles_and_snapshots() {
+        assert!(is_low_value_path("Cargo.lock"));
+        assert!(is_low_value_path("web/package-lock.json"));
+        assert!(is_low_value_path("src/__snapshots__/app.test.js.snap"));
+        assert!(is_low_value_path("tests/snapshots/render.snap"));
+        assert!(!is_low_value_path("src/lock.rs"));
+    }
+
+    #[test]
+    fn is_whitespace_only_detects_reindentation() {
+        let hunk = Hunk {
+            header: "@@".to_string(),
+            lines: vec!["-    foo();".to_string(), "+\tfoo();".to_string()],
+        };
+        assert!(hunk.is_whitespace_only());
+
+        let hunk = Hunk {
+            header: "@@".to_string(),
+            lines: vec!["-foo();".to_string(), "+bar();".to_string()],
+        };
+        assert!(!hunk.is_whitespace_only());
+    }
+
+    fn hunk(lines: &[&str]) -> Hunk {
+        Hunk {
+            header: "@@".to_string(),
+            lines: lines.iter().map(|l| l.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn is_whitespace_only_accepts_rewrapping_and_blank_lines() {
+        assert!(hunk(&["-foo(a,b);", "+foo(a, b);"]).is_whitespace_only());
+        assert!(
+            !hunk(&["-call(a, b);", "+call(", "+    a,", "+    b,", "+);"]).is_whitespace_only()
+        );
+        assert!(hunk(&["-call(a, b);", "+call(", "+    a, b", "+);"]).is_whitespace_only());
+        assert!(hunk(&[" fn a() {}", "+", " fn b() {}"]).is_whitespace_only());
+        assert!(
+            hunk(&["-x = 1;   ", "+x = 1;", "\\ No newline at end of file"]).is_whitespace_only()
+        );
+    }
+
+    #[test]
+    fn is_whitespace_only_keeps_changes_that_join_or_split_words() {
+        assert!(!hunk(&["-let x = 1;", "+letx = 1;"]).is_whitespace_only());
+        assert!(!hunk(&["-return value;", "+returnvalue;"]).is_whitespace_only());
+        assert!(!hunk(&[" unchanged();"]).is_whitespace_only());
+    }
+
+    #[test]
+    fn is_whitespace_only_keeps_whitespace_inside_strings() {
+        assert!(!hunk(&["-    msg(\"a  b\");", "+  msg(\"a b\");"]).is_whitespace_only());
+        assert!(!hunk(&["-sep = ' ';", "+sep = '  ';"]).is_whitespace_only());
+        assert!(!hunk(&["-s = `x y`;", "+s = `x\ty`;"]).is_whitespace_only());
+        assert!(hunk(&["-  msg(\"a  b\");", "+    msg(\"a  b\");"]).is_whitespace_only());
+        // An escaped quote does not end the literal.
+        assert!(!hunk(&["-s = \"say \\\" hi\";", "+s = \"say \\\"  hi\";"]).is_whitespace_only());
+    }
+
+    #[test]
+    fn is_whitespace_only_keeps_reindentation_inside_multiline_strings() {
+        let help = hunk(&[
+            " const HELP: &str = \"",
+            "-usage:",
+            "-  claude-idr [options]",
+            "+usage:",
+            "+    claude-idr [options]",
+            " \";",
+        ]);
+        assert!(!help.is_whitespace_only());
+
+        let docstring = hunk(&[
+            " def f():",
+            "-  \"\"\"Indented",
+            "-  text.\"\"\"",
+            "+    \"\"\"Indented",
+            "+    text.\"\"\"",
+        ]);
+        assert!(!docstring.is_whitespace_only());
+        let one_line = hunk(&[" def f():", "-  \"\"\"Doc.\"\"\"", "+    \"\"\"Doc.\"\"\""]);
+        assert!(one_line.is_whitespace_only());
+    }
+
+    #[test]
+    fn is_whitespace_only_keeps_hunks_that_leave_a_literal_open() {
+        // The hunk may start inside a literal whose quote is above it.
+        let hunk = hunk(&["-  indented text", "+    indented text", " \";"]);
+        assert!(!hunk.is_whitespace_only());
+    }
+
+    #[test]
+    fn is_whitespace_only_closes_single_quotes_at_line_end() {
+        let hunk = hunk(&[
+            " // don't touch this",
+            "-    if x {",
+            "+  if x {",
+            " fn f<'a>(s: &'a str) {}",
+        ]);
+        assert!(hunk.is_whitespace_only());
+    }
+
+    #[test]
+    fn is_import_reorder_accepts_a_permutation_of_imports() {
+        let prefixes = crate::config::Config::default().import_prefixes;
+        let sorted = hunk(&[
+            " use std::fs;",
+            "-use std::io;",
+            "-use crate::diff;",
+            "+use crate::diff;",
+            "+",
+            "+use std::io;",
+        ]);
+        assert!(sorted.is_import_reorder(&prefixes));
+        let python = hunk(&[
+            "-import sys",
+            "-from os import path",
+            "+from os import path",
+            "+import sys",
+        ]);
+        assert!(python.is_import_reorder(&prefixes));
+        assert!(!sorted.is_import_reorder(&[]));
+    }
+
+    #[test]
+    fn is_import_reorder_rejects_changed_or_non_import_lines() {
+        let prefixes = crate::config::Config::default().import_prefixes;
+        let added = hunk(&["-use std::io;", "+use std::fs;", "+use std::io;"]);
+        assert!(!added.is_import_reorder(&prefixes));
+        let renamed = hunk(&["-use std::io;", "+use std::io as sio;"]);
+        assert!(!renamed.is_import_reorder(&prefixes));
+        let code = hunk(&["-let a = 1;", "-let b = 2;", "+let b = 2;", "+let a = 1;"]);
+        assert!(!code.is_import_reorder(&prefixes));
+        let mixed = hunk(&["-use std::io;", "-fn f() {}", "+fn f() {}", "+use std::io;"]);
+        assert!(!mixed.is_import_reorder(&prefixes));
+        assert!(!hunk(&["-", "+"]).is_import_reorder(&prefixes));
+    }
+
+    const U3: &str = include_str!("../tests/fixtures/context/u3.diff");
+    const U1: &str = include_str!("../tests/fixtures/context/u1.diff");
+    const U0: &str = include_str!("../tests/fixtures/context/u0.diff");
+
+    #[test]
+    fn narrow_matches_git_with_less_context() {
+        assert_eq!(narrow(U3, 1), U1);
+        assert_eq!(narrow(U3, 0), U0);
+        assert_eq!(narrow(U1, 0), U0);
+        assert_eq!(narrow(U3, 3), U3);
+        assert_eq!(narrow(U1, 3), U1);
+    }
+
+    #[test]
+    fn with_context_merges_changes_that_stay_within_reach() {
+        // Two context lines between the changes: one piece at context 1,
+        // two at context 0.
+        let hunk = hunk(&[
+            " 027",
+            " 028",
+            " 029",
+            "-030",
+            " 031",
+            " 032",
+            "-033",
+            "+033 changed",
+            " 034",
+            " 035",
+            " 036",
+        ]);
+        let hunk = Hunk {
+            header: "@@ -27,10 +28,9 @@ fn main() {".to_string(),
+            ..hunk
+        };
+
+        let one = hunk.with_context(1);
+        assert_eq!(one.len(), 1);
+        assert_eq!(one[0].header, "@@ -29,6 +30,5 @@ fn main() {");
+        assert_eq!(one[0].lines.first().map(String::as_str), Some(" 029"));
+        assert_eq!(one[0].lines.last().map(String::as_str), Some(" 034"));
+
+        let zero: Vec<String> = hunk.with_context(0).into_iter().map(|h| h.header).collect();
+        assert_eq!(
+            zero,
+            ["@@ -30 +30,0 @@ fn main() {", "@@ -33 +33 @@ fn main() {"]
+        );
+    }
+
+    #[test]
+    fn with_context_handles_new_files_and_pure_insertions() {
+        let new_file = Hunk {
+            header: "@@ -0,0 +1,2 @@".to_string(),
+            ..hunk(&["+a", "+b"])
+        };
+        assert_eq!(new_file.with_context(0), vec![new_file.clone()]);
+
+        let insertion = Hunk {
+            header: "@@ -4,0 +5 @@".to_string(),
+            ..hunk(&["+x"])
+        };
+        assert_eq!(insertion.with_context(2)[0].header, "@@ -4,0 +5 @@");
+
+        let garbled = Hunk {
+            header: "@@ nonsense @@".to_string(),
+            ..hunk(&[" a", "-b", " c"])
+        };
+        assert_eq!(garbled.with_context(0), vec![garbled.clone()]);
+        let unchanged = Hunk {
+            header: "@@ -1 +1 @@".to_string(),
+            ..hunk(&[" only context"])
+        };
+        assert!(unchanged.with_context(1).is_empty());
+    }
+
+    #[test]
+    fn plan_trim_leaves_fitting_diff_untouched() {
+        let files = parse(SAMPLE);
+
+        let trimmed = plan_trim(files.clone(), usize::MAX, chars);
+
+        assert_eq!(trimmed.files, files);
+        assert!(trimmed.notes.is_empty());
+    }
+
+    #[test]
+    fn plan_trim_drops_low_value_hunks_first() {
+        let budget = SAMPLE.len() - 1;
+
+        let trimmed = plan_trim(parse(SAMPLE), budget, chars);
+
+        assert!(trimmed.files[1].hunks.is_empty());
+        assert_eq!(trimmed.files[0], parse(SAMPLE)[0]);
+        assert_eq!(trimmed.notes, vec!["dropped low-value hunks in Cargo.lock"]);
+    }
+
+    #[test]
+    fn plan_trim_shrinks_context_before_omitting() {
+        let files: Vec<FileDiff> = parse(SAMPLE).into_iter().take(1).collect();
+        let full = render(&files).len();
+
+        let trimmed = plan_trim(files, full - 1, chars);
+
+        assert_eq!(
+            trimmed.files[0].hunks[0].lines,
+            vec!["", "-fn old() {}", "+fn new() {}", ""]
+        );
+        assert_eq!(trimmed.notes, vec!["reduced context to 1 line(s)"]);
+    }
+
+    #[test]
+    fn plan_trim_omits_hunks_with_marker_as_last_resort() {
+        let diff = "\
+diff --git a/a.rs b/a.rs
+@@ -1 +1 @@
+-a
++b
+@@ -9 +9 @@
+-c
++d
+diff --git a/b.rs b/b.rs
+@@ -1 +1 @@
+-e
++f
+";
+        let hunks = |text: &str| text.lines().filter(|l| l.starts_with("@@")).count();
+
+        let trimmed = plan_trim(parse(diff), 1, hunks);
+
+        assert_eq!(trimmed.files[0].hunks.len(), 1);
+        assert!(trimmed.files[1].hunks.is_empty());
+        let rendered = render(&trimmed.files);
+        assert!(
+            rendered
+                .contains("a.rs\n[... 1 hunk(s) omitted to fit the prompt budget]\n@@ -1 +1 @@")
+        );
+        assert!(rendered.ends_with("b.rs\n[... 1 hunk(s) omitted to fit the prompt budget]\n"));
+        assert_eq!(
+            trimmed.notes,
+            vec![
+                "reduced context to 1 line(s)",
+                "reduced context to 0 line(s)",
+                "omitted 2 hunk(s) from the end of the diff"
+            ]
+        );
+    }
+}
@@ -0,0 +1,134 @@
+//! `diff_filter_command`: a command of the user's that rewrites the diff
+//! before prompts are built from it, for instance to strip generated
+//! sections. It reads the diff on stdin and writes the diff to use on
+//! stdout. The stat and the sidecar hash still come from the original.
+
+use crate::config::Config;
+use crate::log::{verbose, warn};
+use crate::shell;
+use std::io::{Read, Write};
+use std::process::Stdio;
+use std::thread;
+use std::time::Duration;
+
+/// The footer line of a record whose prompt saw a filtered diff.
+pub const FOOTER: &str =
+    "> diff filtered by diff_filter_command for the prompt; the stat above is unfiltered";
+
+/// `diff` run through `diff_filter_command`, or `None` when none is set.
+/// A filter that fails, times out, or prints nothing leaves the diff as it
+/// was, with a warning.
+pub fn apply(diff: &str, config: &Config) -> Option<String> {
+    let shell_command = config
+        .diff_filter_command
+        .as_deref()
+        .filter(|c| !c.trim().is_empty())?;
+    let timeout = Duration::from_secs(config.diff_filter_timeout_secs);
+    match run(shell_command, diff, timeout) {
+        Ok(filtered) if filtered.trim().is_empty() => {
+            warn!("diff_filter_command printed nothing, using the unfiltered diff");
+            None
+        }
+        Ok(filtered) => Some(filtered),
+        Err(e) => {
+            warn!("{e}, using the unfiltered diff");
+            None
+        }
+    }
+}
+
+fn run(shell_command: &str, diff: &str, timeout: Duration) -> Result<String, String> {
+    let mut child = shell::command(shell_command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("cannot run diff_filter_command: {e}"))?;
+    let (Some(mut stdin), Some(mut stdout), Some(mut stderr)) =
+        (child.stdin.take(), child.stdout.take(), child.stderr.take())
+    else {
+        let _ = child.kill();
+        let _ = child.wait();
+        return Err("cannot attach to diff_filter_command pipes".to_string());
+    };
+    // Separate threads, so a filter that writes before it has read all of
+    // its input cannot deadlock us.
+    let input = diff.to_owned();
+    let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let reader = thread::spawn(move || {
+        let mut out = Vec::new();
+        stdout.read_to_end(&mut out).map(|_| out)
+    });
+    let errors = thread::spawn(move || {
+        let mut out = Vec::new();
+        let _ = stderr.read_to_end(&mut out);
+        out
+    });
+
+    let status = shell::wait(&mut child, timeout)
+        .map_err(|e| format!("cannot wait for diff_filter_command: {e}"))?;
+    let _ = writer.join();
+    let output = reader.join().ok().and_then(Result::ok).unwrap_or_default();
+    for line in String::from_utf8_lossy(&errors.join().unwrap_or_default()).lines() {
+        verbose!("diff_filter_command: {line}");
+    }
+    match status {
+        None => Err(format!(
+            "diff_filter_command did not finish within {}s",
+            timeout.as_secs()
+        )),
+        Some(status) if !status.success() => Err(format!("diff_filter_command failed ({status})")),
+        Some(_) => Ok(String::from_utf8_lossy(&output).into_owned()),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    const DIFF: &str = "\
+diff --git a/src/api.rs b/src/api.rs
+--- a/src/api.rs
++++ b/src/api.rs
+@@ -1,2 +1,3 @@
++// @generated begin
++fn stub() {}
+ fn real() {}
+";
+
+    fn config(command: &str) -> Config {
+        Config {
+            diff_filter_command: Some(command.to_string()),
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn apply_uses_the_filter_output() {
+        let filtered = apply(DIFF, &config("sed '/@generated/d'")).unwrap();
+
+        assert!(!filtered.contains("@generated"), "{filtered}");
+        assert!(filtered.contains("+fn stub() {}"), "{filtered}");
+    }
+
+    #[test]
+    fn apply_falls_back_when_the_filter_fails_or_hangs() {
+        assert_eq!(apply(DIFF, &Config::default()), None);
+        assert_eq!(apply(DIFF, &config("cat; exit 2")), None);
+        assert_eq!(apply(DIFF, &config("true")), None);
+
+        let mut slow = config("sleep 30");
+        slow.diff_filter_timeout_secs = 0;
+        assert_eq!(apply(DIFF, &slow), None);
+    }
+
+    #[test]
+    fn run_reports_why_a_filter_failed() {
+        let error = run("echo oops >&2; exit 2", DIFF, Duration::from_secs(5)).unwrap_err();
+        assert!(error.contains("failed"), "{error}");
+        assert_eq!(
+            run("tr a-z A-Z", "abc\n", Duration::from_secs(5)).unwrap(),
+            "ABC\n"
+        );
+    }
+}
@@ -0,0 +1,331 @@
+//! `claude-idr digest`: one page about the records of a period, either a
+//! narrative Claude writes from their titles and summaries or, with
+//! `--no-llm`, a plain outline of them.
+
+use crate::claude;
+use crate::config::{Backend, Config};
+use crate::error::IdrError;
+use crate::export;
+use crate::path;
+use crate::prompt;
+use crate::record;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The days a digest covers, both ends included, as `YYYY-MM-DD`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Period {
+    pub since: String,
+    pub until: String,
+}
+
+impl Period {
+    /// `until` defaults to `today`.
+    pub fn new(since: &str, until: Option<&str>, today: &str) -> Result<Period, String> {
+        let date = |text: &str| {
+            let valid = text.len() == 10
+                && text.char_indices().all(|(i, c)| match i {
+                    4 | 7 => c == '-',
+                    _ => c.is_ascii_digit(),
+                });
+            valid
+                .then(|| text.to_string())
+                .ok_or_else(|| format!("invalid date `{text}` (expected YYYY-MM-DD)"))
+        };
+        let period = Period {
+            since: date(since)?,
+            until: date(until.unwrap_or(today))?,
+        };
+        if period.until < period.since {
+            return Err(format!(
+                "--until {} is before --since {}",
+                period.until, period.since
+            ));
+        }
+        Ok(period)
+    }
+
+    /// Whether the date at the start of `datetime` falls in the period.
+    fn contains(&self, datetime: &str) -> bool {
+        let date = datetime.get(..10).unwrap_or(datetime);
+        self.since.as_str() <= date && date <= self.until.as_str()
+    }
+
+    pub fn label(&self) -> String {
+        format!("{} to {}", self.since, self.until)
+    }
+
+    /// `digest-2026-02-01_2026-02-14.md`.
+    pub fn file_name(&self) -> String {
+        format!("digest-{}_{}.md", self.since, self.until)
+    }
+}
+
+/// One record in a digest.
+#[derive(Debug, PartialEq)]
+pub struct Entry {
+    pub path: PathBuf,
+    pub date: String,
+    pub title: String,
+    pub summary: Option<String>,
+}
+
+/// The records in `dirs` dated within `period`, oldest first. The date
+/// comes from the front matter or the date line under the title; records
+/// without one are left out.
+pub fn collect(dirs: &[PathBuf], period: &Period, config: &Config) -> Vec<Entry> {
+    let mut entries: Vec<Entry> = dirs
+        .iter()
+        .flat_map(|dir| path::list_records(dir, config.format))
+        .filter_map(|(_, file)| {
+            let text = String::from_utf8_lossy(&fs::read(&file).ok()?).into_owned();
+            let parsed = record::parse(&text, config);
+            let date = parsed.datetime.filter(|d| period.contains(d))?;
+            Some(Entry {
+                date: date.get(..10).unwrap_or(&date).to_string(),
+                title: parsed.title,
+                summary: parsed.summary,
+                path: file,
+            })
+        })
+        .collect();
+    entries.sort_by(|a, b| (&a.date, &a.path).cmp(&(&b.date, &b.path)));
+    entries
+}
+
+/// `root` and every directory below it, skipping hidden ones and exports,
+/// for `--all`.
+pub fn record_dirs(root: &Path) -> Vec<PathBuf> {
+    let mut dirs = vec![root.to_path_buf()];
+    let mut next = 0;
+    while let Some(dir) = dirs.get(next).cloned() {
+        next += 1;
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        let mut children: Vec<PathBuf> = entries
+            .filter_map(|e| e.ok())
+            .filter(|e| {
+                let name = e.file_name().to_string_lossy().into_owned();
+                !name.starts_with('.') && name != export::DEFAULT_DIR
+            })
+            .map(|e| e.path())
+            .filter(|p| p.is_dir())
+            .collect();
+        children.sort();
+        dirs.extend(children);
+    }
+    dirs
+}
+
+/// `entry` as a markdown link relative to the digest's directory `base`.
+fn link(entry: &Entry, base: &Path) -> String {
+    let target = entry.path.strip_prefix(base).unwrap_or(&entry.path);
+    let target = target.to_string_lossy().replace('\\', "/");
+    let title = if entry.title.is_empty() {
+        "(untitled)"
+    } else {
+        &entry.title
+    };
+    format!("[{title}]({})", target.replace(' ', "%20"))
+}
+
+/// The `--no-llm` digest: every record's date, title, and summary.
+pub fn outline(entries: &[Entry], period: &Period, base: &Path) -> String {
+    let mut out = format!(
+        "# Digest: {}\n\n{} record{}\n",
+        period.label(),
+        entries.len(),
+        if entries.len() == 1 { "" } else { "s" }
+    );
+    for entry in entries {
+        out.push_str(&format!("\n## {} {}\n\n", entry.date, link(entry, base)));
+        out.push_str(entry.summary.as_deref().unwrap_or("(no summary)"));
+        out.push('\n');
+    }
+    out
+}
+
+/// The prompt [`narrate`] sends.
+pub fn prompt(entries: &[Entry], period: &Period, config: &Config) -> String {
+    let records: Vec<String> = entries
+        .iter()
+        .map(|entry| {
+            format!(
+                "### {} {}\n\n{}\n",
+                entry.date,
+                entry.title,
+                entry.summary.as_deref().unwrap_or("(no summary)")
+            )
+        })
+        .collect();
+    prompt::build_digest_prompt(&records.join("\n"), &period.label(), config)
+}
+
+/// The digest Claude writes for `entries`, followed by the list of records
+/// it was written from.
+pub fn narrate(
+    entries: &[Entry],
+    period: &Period,
+    base: &Path,
+    config: &Config,
+) -> Result<String, IdrError> {
+    if config.backend == Backend::Cli {
+        claude::probe(config, config.probe_claude).map_err(IdrError::claude)?;
+    }
+    let result = claude::run(&prompt(entries, period, config), config)?;
+    let mut out = format!(
+        "# Digest: {}\n\n{}\n\n## Records\n\n",
+        period.label(),
+        result.text.trim()
+    );
+    for entry in entries {
+        out.push_str(&format!("- {} {}\n", entry.date, link(entry, base)));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn period(since: &str, until: &str) -> Period {
+        Period::new(since, Some(until), "2026-12-31").unwrap()
+    }
+
+    fn write(dir: &Path, name: &str, text: &str) {
+        fs::create_dir_all(dir).unwrap();
+        fs::write(dir.join(name), text).unwrap();
+    }
+
+    #[test]
+    fn period_checks_dates_and_defaults_until_to_today() {
+        assert_eq!(
+            Period::new("2026-02-01", None, "2026-02-14"),
+            Ok(Period {
+                since: "2026-02-01".to_string(),
+                until: "2026-02-14".to_string(),
+            })
+        );
+        assert!(Period::new("2026-2-1", None, "2026-02-14").is_err());
+        assert!(Period::new("2026-02-10", Some("2026-02-01"), "2026-02-14").is_err());
+        assert_eq!(
+            period("2026-02-01", "2026-02-14").file_name(),
+            "digest-2026-02-01_2026-02-14.md"
+        );
+    }
+
+    #[test]
+    fn period_includes_both_ends() {
+        let period = period("2026-02-01", "2026-02-14");
+
+        assert!(period.contains("2026-02-01 09:00"));
+        assert!(period.contains("2026-02-14T23:59:00Z"));
+        assert!(!period.contains("2026-01-31 23:59"));
+        assert!(!period.contains("2026-02-15"));
+    }
+
+    #[test]
+    fn collect_filters_by_header_or_front_matter_date() {
+        let dir = TempDir::new().unwrap();
+        write(
+            dir.path(),
+            "idr-01-old.md",
+            "# IDR: Old\n\n> 2026-01-20 10:00\n\n## 変更概要\n\nOld work.\n",
+        );
+        write(
+            dir.path(),
+            "idr-02-use-jwt.md",
+            "---\ntitle: \"Use JWT\"\ndate: \"2026-02-05T01:00:00Z\"\n---\n\n> 2026-02-05 10:00\n\n## Summary of changes\n\nStateless auth.\n",
+        );
+        write(
+            dir.path(),
+            "idr-03-retry.md",
+            "# IDR: Retry\n\n> 2026-02-03 09:00\n\n## 変更概要\n\n再試行を追加。\n\n## 主要な変更\n\n### [a](a)\n",
+        );
+        write(dir.path(), "idr-04-undated.md", "# IDR: Undated\n\nbody\n");
+
+        let entries = collect(
+            &[dir.path().to_path_buf()],
+            &period("2026-02-01", "2026-02-14"),
+            &Config::default(),
+        );
+
+        let titles: Vec<&str> = entries.iter().map(|e| e.title.as_str()).collect();
+        assert_eq!(titles, ["Retry", "Use JWT"]);
+        assert_eq!(entries[0].date, "2026-02-03");
+        assert_eq!(entries[0].summary.as_deref(), Some("再試行を追加。"));
+        assert_eq!(entries[1].summary.as_deref(), Some("Stateless auth."));
+    }
+
+    #[test]
+    fn record_dirs_walks_workspace_but_skips_hidden_and_exports() {
+        let root = TempDir::new().unwrap();
+        for dir in [
+            "planning/2026-02-01",
+            "sow/x",
+            ".git/objects",
+            "planning/export",
+        ] {
+            fs::create_dir_all(root.path().join(dir)).unwrap();
+        }
+
+        let dirs: Vec<PathBuf> = record_dirs(root.path())
+            .into_iter()
+            .map(|d| d.strip_prefix(root.path()).unwrap().to_path_buf())
+            .collect();
+
+        assert_eq!(
+            dirs,
+            ["", "planning", "sow", "planning/2026-02-01", "sow/x"]
+                .map(PathBuf::from)
+                .to_vec()
+        );
+    }
+
+    #[test]
+    fn outline_lists_records_with_relative_links() {
+        let base = Path::new("/ws");
+        let entries = [
+            Entry {
+                path: PathBuf::from("/ws/planning/2026-02-03/idr-01-retry.md"),
+                date: "2026-02-03".to_string(),
+                title: "Retry".to_string(),
+                summary: Some("Retries.".to_string()),
+            },
+            Entry {
+                path: PathBuf::from("/ws/a b/idr-02.md"),
+                date: "2026-02-05".to_string(),
+                title: String::new(),
+                summary: None,
+            },
+        ];
+
+        assert_eq!(
+            outline(&entries, &period("2026-02-01", "2026-02-14"), base),
+            "# Digest: 2026-02-01 to 2026-02-14\n\n2 records\n\
+             \n## 2026-02-03 [Retry](planning/2026-02-03/idr-01-retry.md)\n\nRetries.\n\
+             \n## 2026-02-05 [(untitled)](a%20b/idr-02.md)\n\n(no summary)\n"
+        );
+    }
+
+    #[test]
+    fn prompt_carries_each_record() {
+        let entries = [Entry {
+            path: PathBuf::from("idr-01.md"),
+            date: "2026-02-03".to_string(),
+            title: "Retry".to_string(),
+            summary: Some("Retries.".to_string()),
+        }];
+
+        let prompt = prompt(
+            &entries,
+            &period("2026-02-01", "2026-02-14"),
+            &Config::default(),
+        );
+
+        assert!(prompt.contains("<records>\n### 2026-02-03 Retry\n\nRetries.\n\n</records>"));
+        assert!(prompt.contains("from 2026-02-01 to 2026-02-14."));
+    }
+}
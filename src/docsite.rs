@@ -0,0 +1,537 @@
+//! `publish_integration`: lists each new record in the navigation of the
+//! documentation site it was written into, mdBook's `SUMMARY.md` or the
+//! `nav` of `mkdocs.yml`. Entries go under a "Decision Records" section,
+//! sorted by record number; every other byte of the file is kept.
+
+use crate::config::{Format, PublishIntegration};
+use crate::log::{verbose, warn};
+use crate::monthly;
+use crate::path;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+pub const SECTION: &str = "Decision Records";
+const SUMMARY_FILE: &str = "SUMMARY.md";
+const MKDOCS_FILE: &str = "mkdocs.yml";
+const DEFAULT_DOCS_DIR: &str = "docs";
+
+/// Adds `record` to the navigation file of `integration` found in its
+/// directory or one above it, up to `root`. Returns the file when it
+/// changed; an entry that is already there changes nothing.
+pub fn update(
+    record: &Path,
+    title: &str,
+    integration: PublishIntegration,
+    root: &Path,
+    format: Format,
+) -> Result<Option<PathBuf>, String> {
+    let dir = record.parent().unwrap_or(Path::new(""));
+    let name = match integration {
+        PublishIntegration::Mdbook => SUMMARY_FILE,
+        PublishIntegration::Mkdocs => MKDOCS_FILE,
+    };
+    let nav_file = find_upwards(dir, root, name).ok_or_else(|| {
+        format!(
+            "no {name} in {} or above it inside {}",
+            dir.display(),
+            root.display()
+        )
+    })?;
+    let text = fs::read_to_string(&nav_file)
+        .map_err(|e| format!("cannot read {}: {e}", nav_file.display()))?;
+    let base = nav_file.parent().unwrap_or(Path::new(""));
+    let updated = match integration {
+        PublishIntegration::Mdbook => {
+            let link = relative_link(record, base)?.replace(' ', "%20");
+            splice_summary(&text, title, &link, format)
+        }
+        PublishIntegration::Mkdocs => {
+            let link = relative_link(record, &base.join(docs_dir(&text)))?;
+            splice_mkdocs(&text, title, &link, format)
+                .map_err(|e| format!("{}: {e}", nav_file.display()))?
+        }
+    };
+    if updated == text {
+        return Ok(None);
+    }
+    fs::write(&nav_file, updated)
+        .map_err(|e| format!("cannot write {}: {e}", nav_file.display()))?;
+    Ok(Some(nav_file))
+}
+
+/// Like [`update`] from the repository of the current directory, but only
+/// warns: a navigation file left alone never fails a run.
+pub fn update_warn(record: &Path, title: &str, integration: PublishIntegration, format: Format) {
+    let Some(root) = crate::git::repo_root() else {
+        warn!("publish_integration needs a git repository");
+        return;
+    };
+    // Compare like with like when the output directory was configured
+    // through a symlink or relative to the current directory.
+    let record = fs::canonicalize(record).unwrap_or_else(|_| record.to_path_buf());
+    let root = fs::canonicalize(&root).unwrap_or(root);
+    match update(&record, title, integration, &root, format) {
+        Ok(Some(file)) => verbose!("listed {} in {}", record.display(), file.display()),
+        Ok(None) => {}
+        Err(e) => warn!("publish_integration: {e}"),
+    }
+}
+
+fn find_upwards(dir: &Path, root: &Path, name: &str) -> Option<PathBuf> {
+    if !dir.starts_with(root) {
+        return None;
+    }
+    dir.ancestors()
+        .take_while(|d| d.starts_with(root))
+        .map(|d| d.join(name))
+        .find(|candidate| candidate.is_file())
+}
+
+/// `record` relative to `base`, with `/` separators.
+fn relative_link(record: &Path, base: &Path) -> Result<String, String> {
+    let relative = record
+        .strip_prefix(base)
+        .map_err(|_| format!("{} is not inside {}", record.display(), base.display()))?;
+    Ok(relative
+        .components()
+        .filter_map(|c| match c {
+            Component::Normal(part) => Some(part.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/"))
+}
+
+/// The top-level `docs_dir` of `mkdocs.yml`, `docs` by default.
+fn docs_dir(mkdocs: &str) -> String {
+    mkdocs
+        .lines()
+        .find_map(|line| line.strip_prefix("docs_dir:"))
+        .map(|value| unquote(value.split(" #").next().unwrap_or(value)).to_string())
+        .filter(|dir| !dir.is_empty())
+        .unwrap_or_else(|| DEFAULT_DOCS_DIR.to_string())
+}
+
+fn unquote(value: &str) -> &str {
+    let value = value.trim();
+    ['"', '\'']
+        .iter()
+        .find_map(|q| value.strip_prefix(*q)?.strip_suffix(*q))
+        .unwrap_or(value)
+}
+
+/// Sort key of a linked record: its number, then its file name, so monthly
+/// journals (which have no number) sort by month.
+fn sort_key(link: &str, format: Format) -> Option<(u32, String)> {
+    let name = link.rsplit('/').next().unwrap_or(link).replace("%20", " ");
+    match path::record_number(&name, format) {
+        Some(number) => Some((number, name)),
+        None if monthly::is_journal_name(&name) => Some((u32::MAX, name)),
+        None => None,
+    }
+}
+
+fn parent_of(link: &str) -> &str {
+    link.rsplit_once('/').map_or("", |(dir, _)| dir)
+}
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+fn is_blank(line: &str) -> bool {
+    line.trim().is_empty()
+}
+
+/// `text` with `line` inserted before `lines[at]`, ending an unterminated
+/// line before it if needed.
+fn insert(lines: &[&str], at: usize, line: &str, nl: &str) -> String {
+    let mut out = lines[..at].concat();
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push_str(nl);
+    }
+    out.push_str(line);
+    out.push_str(&lines[at..].concat());
+    out
+}
+
+fn newline(text: &str) -> &'static str {
+    if text.contains("\r\n") { "\r\n" } else { "\n" }
+}
+
+/// Where a new sibling with `key` goes: before the first sibling with a
+/// greater key, else after the last sibling and the lines nested under it,
+/// else after the section's last non-blank line.
+fn insertion_point(
+    lines: &[&str],
+    siblings: &[(usize, (u32, String))],
+    key: &(u32, String),
+    start: usize,
+    end: usize,
+) -> usize {
+    if let Some((at, _)) = siblings.iter().find(|(_, k)| k > key) {
+        return *at;
+    }
+    if let Some((at, _)) = siblings.last() {
+        let indent = indent_of(lines[*at]);
+        return (at + 1..end)
+            .find(|&i| is_blank(lines[i]) || indent_of(lines[i]) <= indent)
+            .unwrap_or(end);
+    }
+    (start..end)
+        .rev()
+        .find(|&i| !is_blank(lines[i]))
+        .map_or(start, |i| i + 1)
+}
+
+/// The link target of a `- [Title](target)` list item.
+fn summary_target(line: &str) -> Option<&str> {
+    let item = line.trim_start().strip_prefix(['-', '*'])?.trim_start();
+    let (_, rest) = item.strip_prefix('[')?.rsplit_once("](")?;
+    rest.trim_end().strip_suffix(')')
+}
+
+fn is_summary_section(line: &str) -> bool {
+    line.strip_prefix('#')
+        .map(|rest| rest.trim_start_matches('#').trim())
+        .is_some_and(|title| title.eq_ignore_ascii_case(SECTION))
+}
+
+/// `summary` with a `- [title](link)` entry under the `# Decision Records`
+/// part title, which is appended when missing. The entry copies the
+/// indentation and list marker of the records already listed there.
+pub fn splice_summary(summary: &str, title: &str, link: &str, format: Format) -> String {
+    let lines: Vec<&str> = summary.split_inclusive('\n').collect();
+    if lines.iter().any(|l| summary_target(l) == Some(link)) {
+        return summary.to_string();
+    }
+    let nl = newline(summary);
+    let title = title.replace('[', "\\[").replace(']', "\\]");
+    let Some(heading) = lines.iter().position(|l| is_summary_section(l)) else {
+        let mut out = summary.to_string();
+        if !out.is_empty() {
+            if !out.ends_with('\n') {
+                out.push_str(nl);
+            }
+            if !out.ends_with(&format!("{nl}{nl}")) {
+                out.push_str(nl);
+            }
+        }
+        out.push_str(&format!("# {SECTION}{nl}{nl}- [{title}]({link}){nl}"));
+        return out;
+    };
+    let start = heading + 1;
+    let end = (start..lines.len())
+        .find(|&i| lines[i].starts_with('#') || lines[i].trim() == "---")
+        .unwrap_or(lines.len());
+    let siblings: Vec<(usize, (u32, String))> = (start..end)
+        .filter_map(|i| {
+            let target = summary_target(lines[i])?;
+            (parent_of(target) == parent_of(link))
+                .then(|| sort_key(target, format))
+                .flatten()
+                .map(|key| (i, key))
+        })
+        .collect();
+    let (indent, marker) = siblings.first().map_or(("", '-'), |(i, _)| {
+        let line = lines[*i];
+        let indent = &line[..indent_of(line)];
+        (indent, line.trim_start().chars().next().unwrap_or('-'))
+    });
+    let entry = format!("{indent}{marker} [{title}]({link}){nl}");
+    let key = sort_key(link, format).unwrap_or((u32::MAX, String::new()));
+    let at = insertion_point(&lines, &siblings, &key, start, end);
+    if at == start {
+        // An empty section: keep a blank line under the part title.
+        return insert(&lines, at, &format!("{nl}{entry}"), nl);
+    }
+    insert(&lines, at, &entry, nl)
+}
+
+/// The `(title, path)` of a `- Title: path` nav item.
+fn nav_item(line: &str) -> Option<(&str, &str)> {
+    let item = line.trim_start().strip_prefix("- ")?.trim_end();
+    let (title, value) = item.rsplit_once(": ")?;
+    Some((unquote(title), unquote(value)))
+}
+
+/// `mkdocs` with a `- "title": link` item under a `- Decision Records:`
+/// entry of the block-style `nav`, which is added at the end of the nav
+/// when missing. A config without a `nav` is an error: MkDocs builds the
+/// navigation itself then, and adding one would hide every other page.
+pub fn splice_mkdocs(
+    mkdocs: &str,
+    title: &str,
+    link: &str,
+    format: Format,
+) -> Result<String, String> {
+    let lines: Vec<&str> = mkdocs.split_inclusive('\n').collect();
+    let nav = lines
+        .iter()
+        .position(|l| l.starts_with("nav:"))
+        .ok_or("there is no nav; MkDocs lists the pages by itself without one")?;
+    if !lines[nav]["nav:".len()..]
+        .split(" #")
+        .next()
+        .unwrap_or("")
+        .trim()
+        .is_empty()
+    {
+        return Err("only a block-style nav list can be edited".to_string());
+    }
+    let end = (nav + 1..lines.len())
+        .find(|&i| {
+            let line = lines[i];
+            !is_blank(line) && !line.starts_with([' ', '-', '#'])
+        })
+        .unwrap_or(lines.len());
+    if (nav + 1..end).any(|i| nav_item(lines[i]).is_some_and(|(_, path)| path == link)) {
+        return Ok(mkdocs.to_string());
+    }
+    let nl = newline(mkdocs);
+    let is_item = |i: usize| lines[i].trim_start().starts_with("- ");
+    let item_indent = (nav + 1..end)
+        .find(|&i| is_item(i))
+        .map_or(2, |i| indent_of(lines[i]));
+    let title = serde_json::to_string(title).unwrap_or_default();
+    let section = (nav + 1..end).find(|&i| {
+        is_item(i)
+            && indent_of(lines[i]) == item_indent
+            && lines[i]
+                .trim()
+                .strip_prefix("- ")
+                .and_then(|s| s.strip_suffix(':'))
+                .is_some_and(|s| unquote(s) == SECTION)
+    });
+    let Some(section) = section else {
+        let at = (nav + 1..end)
+            .rev()
+            .find(|&i| !is_blank(lines[i]) && !lines[i].trim_start().starts_with('#'))
+            .map_or(nav + 1, |i| i + 1);
+        let pad = " ".repeat(item_indent);
+        let child = " ".repeat(item_indent + 4);
+        return Ok(insert(
+            &lines,
+            at,
+            &format!("{pad}- {SECTION}:{nl}{child}- {title}: {link}{nl}"),
+            nl,
+        ));
+    };
+    let start = section + 1;
+    let children_end = (start..end)
+        .find(|&i| !is_blank(lines[i]) && indent_of(lines[i]) <= item_indent)
+        .unwrap_or(end);
+    let child_indent = (start..children_end)
+        .find(|&i| is_item(i))
+        .map_or(item_indent + 4, |i| indent_of(lines[i]));
+    let siblings: Vec<(usize, (u32, String))> = (start..children_end)
+        .filter(|&i| indent_of(lines[i]) == child_indent)
+        .filter_map(|i| {
+            let (_, path) = nav_item(lines[i])?;
+            (parent_of(path) == parent_of(link))
+                .then(|| sort_key(path, format))
+                .flatten()
+                .map(|key| (i, key))
+        })
+        .collect();
+    let key = sort_key(link, format).unwrap_or((u32::MAX, String::new()));
+    let at = insertion_point(&lines, &siblings, &key, start, children_end);
+    let entry = format!("{}- {title}: {link}{nl}", " ".repeat(child_indent));
+    Ok(insert(&lines, at, &entry, nl))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const SUMMARY: &str = include_str!("../tests/fixtures/docsite/SUMMARY.md");
+    const MKDOCS: &str = include_str!("../tests/fixtures/docsite/mkdocs.yml");
+
+    #[test]
+    fn splice_summary_inserts_entry_in_number_order() {
+        let result = splice_summary(
+            SUMMARY,
+            "Cache [session] lookups",
+            "decisions/idr-03-cache.md",
+            Format::Idr,
+        );
+
+        assert_eq!(
+            result,
+            include_str!("../tests/fixtures/docsite/SUMMARY.expected.md")
+        );
+    }
+
+    #[test]
+    fn splice_summary_is_idempotent() {
+        let once = splice_summary(SUMMARY, "t", "decisions/idr-03-cache.md", Format::Idr);
+        let twice = splice_summary(&once, "t", "decisions/idr-03-cache.md", Format::Idr);
+
+        assert_eq!(once, twice);
+        assert_eq!(
+            splice_summary(SUMMARY, "t", "decisions/idr-02-use-jwt.md", Format::Idr),
+            SUMMARY
+        );
+    }
+
+    #[test]
+    fn splice_summary_appends_after_last_record_and_its_children() {
+        let result = splice_summary(SUMMARY, "Later", "decisions/idr-10-later.md", Format::Idr);
+
+        assert!(
+            result.contains(
+                "    - [Follow-up notes](decisions/retry-notes.md)\n  - [Later](decisions/idr-10-later.md)\n\n---\n"
+            ),
+            "{result}"
+        );
+    }
+
+    #[test]
+    fn splice_summary_creates_missing_section() {
+        let summary = "# Summary\n\n[Introduction](README.md)\n\n- [Guide](guide.md)";
+
+        let result = splice_summary(summary, "First", "idr-01.md", Format::Idr);
+
+        assert_eq!(
+            result,
+            "# Summary\n\n[Introduction](README.md)\n\n- [Guide](guide.md)\n\n# Decision Records\n\n- [First](idr-01.md)\n"
+        );
+    }
+
+    #[test]
+    fn splice_summary_fills_empty_section_and_keeps_crlf() {
+        let summary =
+            "# Summary\r\n\r\n# Decision Records\r\n\r\n# Reference\r\n\r\n- [API](api.md)\r\n";
+
+        let result = splice_summary(summary, "First", "idr-01.md", Format::Idr);
+
+        assert_eq!(
+            result,
+            "# Summary\r\n\r\n# Decision Records\r\n\r\n- [First](idr-01.md)\r\n\r\n# Reference\r\n\r\n- [API](api.md)\r\n"
+        );
+    }
+
+    #[test]
+    fn splice_mkdocs_inserts_entry_in_number_order() {
+        let result = splice_mkdocs(
+            MKDOCS,
+            "Cache: session lookups",
+            "decisions/idr-03-cache.md",
+            Format::Idr,
+        )
+        .unwrap();
+
+        assert_eq!(
+            result,
+            include_str!("../tests/fixtures/docsite/mkdocs.expected.yml")
+        );
+        assert_eq!(
+            splice_mkdocs(&result, "x", "decisions/idr-03-cache.md", Format::Idr).unwrap(),
+            result
+        );
+    }
+
+    #[test]
+    fn splice_mkdocs_creates_missing_section_at_end_of_nav() {
+        let mkdocs = "site_name: Docs\nnav:\n- Home: index.md\n- Guide:\n  - Setup: guide/setup.md\n\n# theme below\ntheme: material\n";
+
+        let result = splice_mkdocs(mkdocs, "First", "decisions/idr-01.md", Format::Idr).unwrap();
+
+        assert_eq!(
+            result,
+            "site_name: Docs\nnav:\n- Home: index.md\n- Guide:\n  - Setup: guide/setup.md\n- Decision Records:\n    - \"First\": decisions/idr-01.md\n\n# theme below\ntheme: material\n"
+        );
+    }
+
+    #[test]
+    fn splice_mkdocs_rejects_missing_or_inline_nav() {
+        assert!(splice_mkdocs("site_name: Docs\n", "t", "idr-01.md", Format::Idr).is_err());
+        assert!(splice_mkdocs("nav: [index.md]\n", "t", "idr-01.md", Format::Idr).is_err());
+    }
+
+    #[test]
+    fn docs_dir_reads_setting_or_defaults() {
+        assert_eq!(docs_dir("site_name: x\ndocs_dir: 'site' # src\n"), "site");
+        assert_eq!(docs_dir("site_name: x\n"), "docs");
+    }
+
+    #[test]
+    fn update_finds_summary_above_record() {
+        let root = TempDir::new().unwrap();
+        let src = root.path().join("book/src");
+        fs::create_dir_all(src.join("decisions")).unwrap();
+        fs::write(src.join(SUMMARY_FILE), "# Summary\n\n- [Intro](intro.md)\n").unwrap();
+        let record = src.join("decisions/idr-01-use jwt.md");
+
+        let changed = update(
+            &record,
+            "Use JWT",
+            PublishIntegration::Mdbook,
+            root.path(),
+            Format::Idr,
+        )
+        .unwrap();
+
+        assert_eq!(changed, Some(src.join(SUMMARY_FILE)));
+        assert!(
+            fs::read_to_string(src.join(SUMMARY_FILE))
+                .unwrap()
+                .ends_with("- [Use JWT](decisions/idr-01-use%20jwt.md)\n")
+        );
+        assert_eq!(
+            update(
+                &record,
+                "Use JWT",
+                PublishIntegration::Mdbook,
+                root.path(),
+                Format::Idr
+            ),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn update_links_relative_to_mkdocs_docs_dir() {
+        let root = TempDir::new().unwrap();
+        fs::create_dir_all(root.path().join("site/adr")).unwrap();
+        fs::write(
+            root.path().join(MKDOCS_FILE),
+            "docs_dir: site\nnav:\n  - Home: index.md\n",
+        )
+        .unwrap();
+        let record = root.path().join("site/adr/idr-01.md");
+
+        update(
+            &record,
+            "First",
+            PublishIntegration::Mkdocs,
+            root.path(),
+            Format::Idr,
+        )
+        .unwrap();
+
+        assert!(
+            fs::read_to_string(root.path().join(MKDOCS_FILE))
+                .unwrap()
+                .ends_with("  - Decision Records:\n      - \"First\": adr/idr-01.md\n")
+        );
+    }
+
+    #[test]
+    fn update_reports_missing_navigation_file() {
+        let root = TempDir::new().unwrap();
+        let record = root.path().join("docs/idr-01.md");
+
+        let err = update(
+            &record,
+            "t",
+            PublishIntegration::Mdbook,
+            root.path(),
+            Format::Idr,
+        )
+        .unwrap_err();
+
+        assert!(err.starts_with("no SUMMARY.md in "), "{err}");
+    }
+}
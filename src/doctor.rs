@@ -0,0 +1,678 @@
+//! `claude-idr doctor`: checks of what claude-idr needs around it, from the
+//! claude CLI to the hook that runs it. Each check stands alone and takes
+//! what it looks at as arguments, so tests can point it at fakes.
+
+use crate::api::API_KEY_ENV;
+use crate::claude;
+use crate::config::{self, Backend, Config};
+use crate::git;
+use crate::path;
+use crate::session;
+use crate::settings;
+use serde::Serialize;
+use serde_json::Value;
+use std::ffi::OsStr;
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::SystemTime;
+
+/// Hooks claude-idr is meant to run from, per the README's Setup.
+const GIT_HOOKS: &[&str] = &["pre-commit", "post-commit", "prepare-commit-msg"];
+
+/// Variables that log the claude CLI in without a stored login.
+const LOGIN_ENV: &[&str] = &[
+    API_KEY_ENV,
+    "CLAUDE_CODE_OAUTH_TOKEN",
+    "CLAUDE_CODE_USE_BEDROCK",
+    "CLAUDE_CODE_USE_VERTEX",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl std::fmt::Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Status::Pass => "PASS",
+            Status::Warn => "WARN",
+            Status::Fail => "FAIL",
+        })
+    }
+}
+
+/// The outcome of one check.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub status: Status,
+    /// What was found.
+    pub detail: String,
+    /// What to do about a warning or failure.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+}
+
+impl CheckResult {
+    fn pass(name: &'static str, detail: impl Into<String>) -> CheckResult {
+        CheckResult {
+            name,
+            status: Status::Pass,
+            detail: detail.into(),
+            hint: None,
+        }
+    }
+
+    fn warn(name: &'static str, detail: impl Into<String>, hint: impl Into<String>) -> CheckResult {
+        CheckResult {
+            status: Status::Warn,
+            hint: Some(hint.into()),
+            ..CheckResult::pass(name, detail)
+        }
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>, hint: impl Into<String>) -> CheckResult {
+        CheckResult {
+            status: Status::Fail,
+            hint: Some(hint.into()),
+            ..CheckResult::pass(name, detail)
+        }
+    }
+}
+
+/// Every check, against the config file at `config_path` and the current
+/// directory's repository.
+pub fn run(config_path: &Path) -> Vec<CheckResult> {
+    let config = Config::try_load(Some(config_path)).unwrap_or_default();
+    let path_var = std::env::var_os("PATH");
+    let home = config::home_dir();
+    let repo_root = git::repo_root();
+    let mut claude_settings: Vec<PathBuf> = repo_root
+        .iter()
+        .flat_map(|root| {
+            ["settings.json", "settings.local.json"].map(|name| root.join(".claude").join(name))
+        })
+        .collect();
+    claude_settings.extend(home.as_ref().map(|home| home.join(".claude/settings.json")));
+    vec![
+        config_file(config_path),
+        git_cli(path_var.as_deref()),
+        claude_cli(&config, path_var.as_deref()),
+        login(&config, home.as_deref(), |variable| {
+            std::env::var_os(variable).is_some_and(|v| !v.is_empty())
+        }),
+        projects(
+            session::projects_dir(&config).as_deref(),
+            &config,
+            SystemTime::now(),
+        ),
+        workspace(&config),
+        hooks(git::hooks_dir().as_deref(), &claude_settings),
+    ]
+}
+
+/// Whether any check failed, which makes `doctor` exit 1.
+pub fn failed(results: &[CheckResult]) -> bool {
+    results.iter().any(|result| result.status == Status::Fail)
+}
+
+/// One `PASS name: detail` line per check, each warning or failure followed
+/// by its hint.
+pub fn render(results: &[CheckResult]) -> String {
+    let mut text = String::new();
+    for result in results {
+        text.push_str(&format!(
+            "{} {}: {}\n",
+            result.status, result.name, result.detail
+        ));
+        if let Some(hint) = &result.hint {
+            text.push_str(&format!("     -> {hint}\n"));
+        }
+    }
+    text
+}
+
+/// The results as `--json` prints them.
+pub fn to_json(results: &[CheckResult]) -> Value {
+    serde_json::json!({
+        "ok": !failed(results),
+        "checks": results,
+    })
+}
+
+/// The config file parses, and every key in it is one claude-idr knows.
+/// A missing file is fine: the defaults apply.
+pub fn config_file(path: &Path) -> CheckResult {
+    const NAME: &str = "config";
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return CheckResult::pass(
+                NAME,
+                format!("{} not found; using the defaults", path.display()),
+            );
+        }
+        Err(e) => {
+            return CheckResult::fail(
+                NAME,
+                format!("cannot read {}: {e}", path.display()),
+                "fix the file's permissions",
+            );
+        }
+    };
+    if let Err(e) = serde_json::from_str::<Config>(&text) {
+        return CheckResult::fail(
+            NAME,
+            format!("{}: {e}", path.display()),
+            "fix the file; until then every run ignores it and uses the defaults",
+        );
+    }
+    let known = settings::keys();
+    let unknown: Vec<String> = match serde_json::from_str::<Value>(&text) {
+        Ok(Value::Object(map)) => map.into_iter().map(|(key, _)| key).collect(),
+        _ => Vec::new(),
+    }
+    .into_iter()
+    .filter(|key| !known.contains(key))
+    .collect();
+    if unknown.is_empty() {
+        CheckResult::pass(NAME, path.display().to_string())
+    } else {
+        CheckResult::warn(
+            NAME,
+            format!("{}: unknown key(s) {}", path.display(), unknown.join(", ")),
+            "unknown keys are ignored; check the spelling against the README's Configuration table",
+        )
+    }
+}
+
+/// `git --version` runs, with `git` looked up in `path_var`.
+pub fn git_cli(path_var: Option<&OsStr>) -> CheckResult {
+    const NAME: &str = "git";
+    let Some(git) = claude::find_executable(Path::new("git"), path_var) else {
+        return CheckResult::fail(NAME, "git not found on PATH", "install git");
+    };
+    match version(&git) {
+        Ok(version) => CheckResult::pass(NAME, version),
+        Err(e) => CheckResult::fail(NAME, e, "reinstall git"),
+    }
+}
+
+/// `claude_bin` is found in `path_var` and `--version` runs. Passes without
+/// looking under `backend: "api"`, which does not use the CLI.
+pub fn claude_cli(config: &Config, path_var: Option<&OsStr>) -> CheckResult {
+    const NAME: &str = "claude";
+    if config.backend == Backend::Api {
+        return CheckResult::pass(NAME, "not used with backend \"api\"");
+    }
+    let bin = &config.claude_bin;
+    let Some(resolved) = claude::find_executable(bin, path_var) else {
+        return CheckResult::fail(
+            NAME,
+            format!("{} not found or not executable", bin.display()),
+            "install Claude Code (https://docs.anthropic.com/en/docs/claude-code), \
+             or set claude_bin to its full path",
+        );
+    };
+    match version(&resolved) {
+        Ok(version) => CheckResult::pass(NAME, format!("{} ({version})", resolved.display())),
+        Err(e) => CheckResult::fail(
+            NAME,
+            e,
+            "run `claude` once to finish its setup, or reinstall it",
+        ),
+    }
+}
+
+/// The first line `bin --version` prints.
+fn version(bin: &Path) -> Result<String, String> {
+    let output = Command::new(bin)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("cannot run {} --version: {e}", bin.display()))?;
+    if !output.status.success() {
+        return Err(format!(
+            "{} --version failed ({}): {}",
+            bin.display(),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_string())
+}
+
+/// Signs that claude can authenticate: `ANTHROPIC_API_KEY` for the api
+/// backend; for the CLI, a login variable (`is_set` says which are set), or
+/// a login stored under `home`. A keychain login cannot be seen, so finding
+/// none only warns.
+pub fn login(config: &Config, home: Option<&Path>, is_set: impl Fn(&str) -> bool) -> CheckResult {
+    const NAME: &str = "login";
+    if config.backend == Backend::Api {
+        return if is_set(API_KEY_ENV) {
+            CheckResult::pass(NAME, format!("{API_KEY_ENV} is set"))
+        } else {
+            CheckResult::fail(
+                NAME,
+                format!("{API_KEY_ENV} is not set"),
+                format!("export {API_KEY_ENV}, or set backend to \"cli\""),
+            )
+        };
+    }
+    if let Some(variable) = LOGIN_ENV.iter().find(|variable| is_set(variable)) {
+        return CheckResult::pass(NAME, format!("{variable} is set"));
+    }
+    if let Some(home) = home {
+        let credentials = home.join(".claude/.credentials.json");
+        if credentials.is_file() {
+            return CheckResult::pass(NAME, credentials.display().to_string());
+        }
+        let state = home.join(".claude.json");
+        let logged_in = fs::read_to_string(&state)
+            .ok()
+            .and_then(|text| serde_json::from_str::<Value>(&text).ok())
+            .is_some_and(|state| state.get("oauthAccount").is_some_and(|a| !a.is_null()));
+        if logged_in {
+            return CheckResult::pass(NAME, format!("logged in ({})", state.display()));
+        }
+    }
+    CheckResult::warn(
+        NAME,
+        "no Claude Code login found",
+        "run `claude` once and log in; a login kept in the macOS keychain cannot be checked",
+    )
+}
+
+/// The transcripts directory exists and has a session recent enough to be
+/// used (`session_max_age_min`); an old one only warns, since there is no
+/// session between working with Claude Code.
+pub fn projects(dir: Option<&Path>, config: &Config, now: SystemTime) -> CheckResult {
+    const NAME: &str = "sessions";
+    let Some(dir) = dir else {
+        return CheckResult::fail(
+            NAME,
+            "cannot determine the home directory",
+            format!("set projects_dir or {}", session::PROJECTS_DIR_ENV),
+        );
+    };
+    if !dir.is_dir() {
+        return CheckResult::fail(
+            NAME,
+            format!("{} does not exist", dir.display()),
+            format!(
+                "use Claude Code once, or point projects_dir or {} at its transcripts",
+                session::PROJECTS_DIR_ENV
+            ),
+        );
+    }
+    match session::find_recent_in(config, now, dir) {
+        Ok(path) => CheckResult::pass(NAME, format!("newest session: {}", path.display())),
+        Err(_) => CheckResult::warn(
+            NAME,
+            format!(
+                "no session in {} from the last {} minute(s)",
+                dir.display(),
+                config.session_max_age_min
+            ),
+            "commits made this long after a session are skipped; raise session_max_age_min if that is too soon",
+        ),
+    }
+}
+
+/// Records can be written where the next one would go: the directory
+/// itself, or the nearest existing directory above it when it is still to
+/// be created.
+pub fn workspace(config: &Config) -> CheckResult {
+    const NAME: &str = "workspace";
+    if let Some(problem) = config.missing_home() {
+        return CheckResult::fail(
+            NAME,
+            problem,
+            "set workspace_dir or output_dir with `claude-idr config set`",
+        );
+    }
+    let dir = path::current_dir(config);
+    let Some(existing) = dir.ancestors().find(|ancestor| ancestor.is_dir()) else {
+        return CheckResult::fail(
+            NAME,
+            format!("{}: no existing parent directory", dir.display()),
+            "set output_dir or workspace_dir to a directory you can write to",
+        );
+    };
+    match probe_write(existing) {
+        Ok(()) if existing == dir => CheckResult::pass(NAME, dir.display().to_string()),
+        Ok(()) => CheckResult::pass(
+            NAME,
+            format!("{} (created with the first record)", dir.display()),
+        ),
+        Err(e) => CheckResult::fail(
+            NAME,
+            format!("cannot write to {}: {e}", existing.display()),
+            "fix its permissions, or set output_dir or workspace_dir elsewhere",
+        ),
+    }
+}
+
+fn probe_write(dir: &Path) -> io::Result<()> {
+    let probe = dir.join(format!(".claude-idr-doctor-{}", std::process::id()));
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe)?;
+    fs::remove_file(&probe)
+}
+
+/// A git hook in `hooks_dir` or a Claude Code settings file among
+/// `claude_settings` runs claude-idr. A git hook that mentions it but is not
+/// executable fails, since git skips it without a word.
+pub fn hooks(hooks_dir: Option<&Path>, claude_settings: &[PathBuf]) -> CheckResult {
+    const NAME: &str = "hooks";
+    let mentions = |path: &Path| fs::read_to_string(path).is_ok_and(|t| t.contains("claude-idr"));
+    let mut found = Vec::new();
+    if let Some(dir) = hooks_dir {
+        for name in GIT_HOOKS {
+            let hook = dir.join(name);
+            if !mentions(&hook) {
+                continue;
+            }
+            if claude::find_executable(&hook, None).is_none() {
+                return CheckResult::fail(
+                    NAME,
+                    format!("{} runs claude-idr but is not executable", hook.display()),
+                    format!("chmod +x {}", hook.display()),
+                );
+            }
+            found.push(name.to_string());
+        }
+    }
+    found.extend(
+        claude_settings
+            .iter()
+            .filter(|settings| mentions(settings))
+            .map(|settings| settings.display().to_string()),
+    );
+    if !found.is_empty() {
+        return CheckResult::pass(NAME, format!("claude-idr runs from {}", found.join(", ")));
+    }
+    match hooks_dir {
+        Some(dir) => CheckResult::warn(
+            NAME,
+            format!("no hook in {} runs claude-idr", dir.display()),
+            "add `claude-idr` to .git/hooks/pre-commit, or a Stop hook running \
+             `claude-idr --hook-stdin` (see Setup in the README)",
+        ),
+        None => CheckResult::warn(
+            NAME,
+            "not in a git repository",
+            "run `claude-idr doctor` inside a repository to check its hooks",
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{write_jsonl, write_script};
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    #[test]
+    fn config_file_passes_missing_and_valid_files() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config.json");
+
+        let missing = config_file(&path);
+        assert_eq!(missing.status, Status::Pass);
+        assert!(missing.detail.contains("using the defaults"));
+
+        fs::write(&path, r#"{"model": "haiku"}"#).unwrap();
+        assert_eq!(config_file(&path).status, Status::Pass);
+    }
+
+    #[test]
+    fn config_file_fails_invalid_and_warns_unknown_keys() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config.json");
+
+        fs::write(&path, r#"{"max_diff_lines": "many"}"#).unwrap();
+        let invalid = config_file(&path);
+        assert_eq!(invalid.status, Status::Fail);
+        assert!(invalid.hint.is_some());
+
+        fs::write(&path, r#"{"modle": "haiku", "model": "opus"}"#).unwrap();
+        let unknown = config_file(&path);
+        assert_eq!(unknown.status, Status::Warn);
+        assert!(unknown.detail.ends_with("unknown key(s) modle"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn claude_cli_finds_the_binary_on_the_given_path() {
+        let bin = TempDir::new().unwrap();
+        write_script(bin.path(), "claude", "echo '2.1.0 (Claude Code)'");
+        let config = Config {
+            claude_bin: PathBuf::from("claude"),
+            ..Config::default()
+        };
+
+        let found = claude_cli(&config, Some(bin.path().as_os_str()));
+        assert_eq!(found.status, Status::Pass);
+        assert!(found.detail.ends_with("(2.1.0 (Claude Code))"));
+
+        let empty = TempDir::new().unwrap();
+        let missing = claude_cli(&config, Some(empty.path().as_os_str()));
+        assert_eq!(missing.status, Status::Fail);
+        assert!(missing.hint.unwrap().contains("claude_bin"));
+
+        let api = Config {
+            backend: Backend::Api,
+            ..config
+        };
+        assert_eq!(
+            claude_cli(&api, Some(empty.path().as_os_str())).status,
+            Status::Pass
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn claude_cli_fails_when_version_fails() {
+        let bin = TempDir::new().unwrap();
+        write_script(bin.path(), "claude", "echo 'broken install' >&2; exit 1");
+        let config = Config {
+            claude_bin: PathBuf::from("claude"),
+            ..Config::default()
+        };
+
+        let broken = claude_cli(&config, Some(bin.path().as_os_str()));
+
+        assert_eq!(broken.status, Status::Fail);
+        assert!(
+            broken.detail.contains("broken install"),
+            "{}",
+            broken.detail
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn git_cli_looks_on_the_given_path() {
+        let bin = TempDir::new().unwrap();
+        let empty = TempDir::new().unwrap();
+        write_script(bin.path(), "git", "echo 'git version 2.45.0'");
+
+        assert_eq!(
+            git_cli(Some(bin.path().as_os_str())),
+            CheckResult::pass("git", "git version 2.45.0")
+        );
+        assert_eq!(git_cli(Some(empty.path().as_os_str())).status, Status::Fail);
+    }
+
+    #[test]
+    fn login_looks_at_variables_and_stored_logins() {
+        let home = TempDir::new().unwrap();
+        let cli = Config::default();
+        let api = Config {
+            backend: Backend::Api,
+            ..Config::default()
+        };
+        let none = |_: &str| false;
+
+        assert_eq!(login(&api, None, none).status, Status::Fail);
+        assert_eq!(login(&api, None, |v| v == API_KEY_ENV).status, Status::Pass);
+        assert_eq!(login(&cli, Some(home.path()), none).status, Status::Warn);
+        assert_eq!(
+            login(&cli, None, |v| v == "CLAUDE_CODE_OAUTH_TOKEN").detail,
+            "CLAUDE_CODE_OAUTH_TOKEN is set"
+        );
+
+        fs::write(
+            home.path().join(".claude.json"),
+            r#"{"oauthAccount": null}"#,
+        )
+        .unwrap();
+        assert_eq!(login(&cli, Some(home.path()), none).status, Status::Warn);
+        fs::write(
+            home.path().join(".claude.json"),
+            r#"{"oauthAccount": {"emailAddress": "a@example.com"}}"#,
+        )
+        .unwrap();
+        assert_eq!(login(&cli, Some(home.path()), none).status, Status::Pass);
+    }
+
+    #[test]
+    fn projects_needs_the_directory_and_warns_without_a_recent_session() {
+        let dir = TempDir::new().unwrap();
+        let config = Config::default();
+        let now = SystemTime::now();
+
+        let missing = projects(Some(&dir.path().join("projects")), &config, now);
+        assert_eq!(missing.status, Status::Fail);
+        assert_eq!(projects(None, &config, now).status, Status::Fail);
+
+        assert_eq!(
+            projects(Some(dir.path()), &config, now).status,
+            Status::Warn
+        );
+        let session = write_jsonl(dir.path(), "-repo/abc.jsonl", &["{}"]);
+        let found = projects(Some(dir.path()), &config, now + Duration::from_secs(60));
+        assert_eq!(found.status, Status::Pass);
+        assert!(found.detail.ends_with(&session.display().to_string()));
+        let later = now + Duration::from_secs(config.session_max_age_min * 60 + 60);
+        assert_eq!(
+            projects(Some(dir.path()), &config, later).status,
+            Status::Warn
+        );
+    }
+
+    #[test]
+    fn workspace_checks_the_directory_records_go_to() {
+        let dir = TempDir::new().unwrap();
+        let existing = Config {
+            output_dir: Some(dir.path().to_path_buf()),
+            ..Config::default()
+        };
+        let planned = Config {
+            output_dir: Some(dir.path().join("docs/decisions")),
+            ..Config::default()
+        };
+        let homeless = Config {
+            workspace_dir: PathBuf::new(),
+            ..Config::default()
+        };
+
+        assert_eq!(
+            workspace(&existing),
+            CheckResult::pass("workspace", dir.path().display().to_string())
+        );
+        let to_create = workspace(&planned);
+        assert_eq!(to_create.status, Status::Pass);
+        assert!(
+            to_create
+                .detail
+                .ends_with("(created with the first record)")
+        );
+        assert_eq!(workspace(&homeless).status, Status::Fail);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hooks_finds_git_and_claude_code_hooks() {
+        let dir = TempDir::new().unwrap();
+        let hooks_dir = dir.path().join("hooks");
+        fs::create_dir(&hooks_dir).unwrap();
+        let settings = dir.path().join("settings.json");
+
+        assert_eq!(hooks(None, &[]).status, Status::Warn);
+        assert_eq!(
+            hooks(Some(&hooks_dir), std::slice::from_ref(&settings)).status,
+            Status::Warn
+        );
+
+        fs::write(
+            &settings,
+            r#"{"hooks": {"Stop": [{"hooks": [{"type": "command", "command": "claude-idr --hook-stdin"}]}]}}"#,
+        )
+        .unwrap();
+        let claude_code = hooks(None, std::slice::from_ref(&settings));
+        assert_eq!(claude_code.status, Status::Pass);
+        assert!(claude_code.detail.contains("settings.json"));
+
+        write_script(&hooks_dir, "pre-commit", "claude-idr\n");
+        assert_eq!(
+            hooks(Some(&hooks_dir), &[]),
+            CheckResult::pass("hooks", "claude-idr runs from pre-commit")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hooks_fails_a_hook_git_cannot_run() {
+        let dir = TempDir::new().unwrap();
+        fs::write(
+            dir.path().join("post-commit"),
+            "#!/bin/sh\nclaude-idr --post-commit\n",
+        )
+        .unwrap();
+
+        let result = hooks(Some(dir.path()), &[]);
+
+        assert_eq!(result.status, Status::Fail);
+        assert!(result.hint.unwrap().starts_with("chmod +x "));
+    }
+
+    #[test]
+    fn render_and_json_list_every_result() {
+        let results = [
+            CheckResult::pass("git", "git version 2.45.0"),
+            CheckResult::warn("hooks", "not in a git repository", "run it in one"),
+        ];
+
+        assert_eq!(
+            render(&results),
+            "PASS git: git version 2.45.0\nWARN hooks: not in a git repository\n     -> run it in one\n"
+        );
+        assert!(!failed(&results));
+        let json = to_json(&results);
+        assert_eq!(json["ok"], true);
+        assert_eq!(json["checks"][1]["status"], "warn");
+        assert!(json["checks"][0].get("hint").is_none());
+        assert!(failed(&[CheckResult::fail(
+            "git",
+            "missing",
+            "install git"
+        )]));
+    }
+}
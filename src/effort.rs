@@ -0,0 +1,301 @@
+//! How much work went into a session: how long it ran, how often the user
+//! spoke, and which tools Claude called how often. Shown in the record's
+//! footer and stored in the sidecar.
+
+use crate::clean;
+use crate::jsonl::SessionEntry;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Tools named in the rendered line; the rest are summed up as "others".
+const MAX_TOOLS_SHOWN: usize = 3;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Effort {
+    /// First to last timestamped entry; `None` when the timestamps are
+    /// missing or all the same.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_secs: Option<u64>,
+    pub user_turns: usize,
+    pub tool_calls: usize,
+    /// Calls per tool name.
+    pub tools: BTreeMap<String, usize>,
+    /// Distinct files passed to Write or Edit.
+    pub files_edited: usize,
+}
+
+/// Running totals while a transcript is read, entry by entry.
+#[derive(Debug, Default)]
+pub struct Tally {
+    first: Option<i64>,
+    last: Option<i64>,
+    user_turns: usize,
+    tools: BTreeMap<String, usize>,
+    edited: BTreeSet<String>,
+}
+
+impl Tally {
+    /// Counts one transcript line and its timestamp (see
+    /// [`timestamp_millis`]).
+    pub fn add(&mut self, timestamp: Option<i64>, entry: &SessionEntry) {
+        if let Some(millis) = timestamp {
+            self.first = Some(self.first.map_or(millis, |first| first.min(millis)));
+            self.last = Some(self.last.map_or(millis, |last| last.max(millis)));
+        }
+        if entry.user_text().is_some() {
+            self.user_turns += 1;
+        }
+        for (name, input) in entry.tool_uses() {
+            *self.tools.entry(name.to_string()).or_default() += 1;
+            if matches!(name, "Write" | "Edit")
+                && let Some(file) = input.get("file_path").and_then(Value::as_str)
+            {
+                self.edited.insert(file.to_string());
+            }
+        }
+    }
+
+    pub fn finish(&self) -> Effort {
+        let duration_secs = match (self.first, self.last) {
+            (Some(first), Some(last)) if last > first => Some(((last - first) / 1000) as u64),
+            _ => None,
+        };
+        Effort {
+            duration_secs,
+            user_turns: self.user_turns,
+            tool_calls: self.tools.values().sum(),
+            tools: self.tools.clone(),
+            files_edited: self.edited.len(),
+        }
+    }
+}
+
+impl Effort {
+    /// Totals over transcript lines already read.
+    pub fn from_values<'a>(values: impl IntoIterator<Item = &'a Value>) -> Effort {
+        let mut tally = Tally::default();
+        for value in values {
+            let timestamp = value.get("timestamp").and_then(timestamp_millis);
+            tally.add(timestamp, &SessionEntry::from(value.clone()));
+        }
+        tally.finish()
+    }
+
+    /// Whether the session left anything to count.
+    pub fn is_empty(&self) -> bool {
+        self.user_turns == 0 && self.tool_calls == 0
+    }
+
+    /// `1h 42m, 23 user turns, 58 tool calls (Edit 31, Bash 19, Read 8), 5
+    /// files edited`, leaving out the duration when it is unknown.
+    pub fn render(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(secs) = self.duration_secs {
+            parts.push(duration(secs));
+        }
+        parts.push(count(self.user_turns, "user turn", "user turns"));
+        let mut calls = count(self.tool_calls, "tool call", "tool calls");
+        if !self.tools.is_empty() {
+            let mut tools: Vec<(&String, &usize)> = self.tools.iter().collect();
+            tools.sort_by(|(a, x), (b, y)| y.cmp(x).then(a.cmp(b)));
+            let mut shown: Vec<String> = tools
+                .iter()
+                .take(MAX_TOOLS_SHOWN)
+                .map(|(name, n)| format!("{name} {n}"))
+                .collect();
+            let others: usize = tools.iter().skip(MAX_TOOLS_SHOWN).map(|(_, n)| **n).sum();
+            if others > 0 {
+                shown.push(format!("others {others}"));
+            }
+            calls.push_str(&format!(" ({})", shown.join(", ")));
+        }
+        parts.push(calls);
+        parts.push(count(self.files_edited, "file edited", "files edited"));
+        parts.join(", ")
+    }
+}
+
+fn count(n: usize, one: &str, many: &str) -> String {
+    format!("{n} {}", if n == 1 { one } else { many })
+}
+
+/// `1h 42m`, `12m`, or `40s`.
+fn duration(secs: u64) -> String {
+    let (hours, minutes) = (secs / 3600, secs % 3600 / 60);
+    match (hours, minutes) {
+        (0, 0) => format!("{secs}s"),
+        (0, m) => format!("{m}m"),
+        (h, m) => format!("{h}h {m}m"),
+    }
+}
+
+/// A transcript timestamp in epoch milliseconds. Transcripts write either
+/// an ISO 8601 string (`2026-02-07T08:30:00.123Z`, possibly with an offset)
+/// or epoch milliseconds, as a number or a string of digits.
+pub fn timestamp_millis(value: &Value) -> Option<i64> {
+    match value {
+        Value::Number(n) => n.as_i64().or_else(|| n.as_f64().map(|f| f as i64)),
+        Value::String(text) if !text.is_empty() && text.bytes().all(|b| b.is_ascii_digit()) => {
+            text.parse().ok()
+        }
+        Value::String(text) => iso8601_millis(text),
+        _ => None,
+    }
+}
+
+fn iso8601_millis(text: &str) -> Option<i64> {
+    let text = text.trim();
+    let number = |range: std::ops::Range<usize>| -> Option<i64> {
+        let part = text.get(range)?;
+        part.bytes()
+            .all(|b| b.is_ascii_digit())
+            .then(|| part.parse().ok())?
+    };
+    let separators = text.as_bytes();
+    if separators.len() < 19
+        || separators[4] != b'-'
+        || separators[7] != b'-'
+        || !matches!(separators[10], b'T' | b't' | b' ')
+        || separators[13] != b':'
+        || separators[16] != b':'
+    {
+        return None;
+    }
+    let (year, month, day) = (number(0..4)?, number(5..7)?, number(8..10)?);
+    let (hour, minute, second) = (number(11..13)?, number(14..16)?, number(17..19)?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 {
+        return None;
+    }
+
+    let mut rest = &text[19..];
+    let mut millis = 0;
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let digits = fraction.bytes().take_while(u8::is_ascii_digit).count();
+        let padded = format!("{:0<3}", &fraction[..digits.min(3)]);
+        millis = padded.parse().ok()?;
+        rest = &fraction[digits..];
+    }
+    let offset_secs = match rest {
+        "" | "Z" | "z" => 0,
+        _ => {
+            let sign = match rest.as_bytes()[0] {
+                b'+' => 1,
+                b'-' => -1,
+                _ => return None,
+            };
+            let offset = rest[1..].replace(':', "");
+            if offset.len() != 4 || !offset.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            let hours: i64 = offset[..2].parse().ok()?;
+            let minutes: i64 = offset[2..].parse().ok()?;
+            sign * (hours * 3600 + minutes * 60)
+        }
+    };
+
+    let days = clean::days_from_civil(year as i32, month as u32, day as u32);
+    let secs = days * 86400 + hour * 3600 + minute * 60 + second - offset_secs;
+    Some(secs * 1000 + millis)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn lines(texts: &[&str]) -> Vec<Value> {
+        texts
+            .iter()
+            .map(|text| serde_json::from_str(text).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn from_values_counts_turns_tools_and_files() {
+        let values = lines(&[
+            r#"{"type":"user","timestamp":"2026-02-07T08:00:00.000Z","message":{"content":"add retries"}}"#,
+            r#"{"type":"assistant","timestamp":"2026-02-07T08:01:00.000Z","message":{"content":[{"type":"tool_use","name":"Read","input":{"file_path":"src/api.rs"}},{"type":"tool_use","name":"Edit","input":{"file_path":"src/api.rs"}}]}}"#,
+            r#"{"type":"user","timestamp":"2026-02-07T08:02:00.000Z","message":{"content":[{"type":"tool_result","content":"ok"}]}}"#,
+            r#"{"type":"assistant","timestamp":"2026-02-07T08:30:00.000Z","message":{"content":[{"type":"tool_use","name":"Edit","input":{"file_path":"src/api.rs"}},{"type":"tool_use","name":"Write","input":{"file_path":"src/retry.rs"}},{"type":"tool_use","name":"Bash","input":{"command":"cargo test"}}]}}"#,
+            r#"{"type":"user","timestamp":"2026-02-07T09:42:10.500Z","message":{"content":"thanks"}}"#,
+            r#"{"type":"summary","summary":"Retries"}"#,
+        ]);
+
+        let effort = Effort::from_values(&values);
+
+        assert_eq!(effort.duration_secs, Some(6130));
+        assert_eq!(effort.user_turns, 2);
+        assert_eq!(effort.tool_calls, 5);
+        assert_eq!(
+            effort.tools,
+            BTreeMap::from([
+                ("Bash".to_string(), 1),
+                ("Edit".to_string(), 2),
+                ("Read".to_string(), 1),
+                ("Write".to_string(), 1),
+            ])
+        );
+        assert_eq!(effort.files_edited, 2);
+        assert_eq!(
+            effort.render(),
+            "1h 42m, 2 user turns, 5 tool calls (Edit 2, Bash 1, Read 1, others 1), 2 files edited"
+        );
+    }
+
+    #[test]
+    fn from_values_mixes_iso_and_epoch_millis_timestamps() {
+        let values = lines(&[
+            r#"{"type":"user","timestamp":1770451200000,"message":{"content":"go"}}"#,
+            r#"{"type":"assistant","message":{"content":[{"type":"tool_use","name":"Bash","input":{}}]}}"#,
+            r#"{"type":"assistant","timestamp":"2026-02-07T17:12:00+09:00","message":{"content":"done"}}"#,
+        ]);
+
+        let effort = Effort::from_values(&values);
+
+        assert_eq!(effort.duration_secs, Some(720));
+        assert_eq!(
+            effort.render(),
+            "12m, 1 user turn, 1 tool call (Bash 1), 0 files edited"
+        );
+    }
+
+    #[test]
+    fn render_leaves_out_an_unknown_duration() {
+        let values = lines(&[r#"{"type":"user","message":{"content":"hello"}}"#]);
+
+        let effort = Effort::from_values(&values);
+
+        assert_eq!(effort.duration_secs, None);
+        assert_eq!(effort.render(), "1 user turn, 0 tool calls, 0 files edited");
+        assert!(!effort.is_empty());
+        assert!(Effort::default().is_empty());
+    }
+
+    #[test]
+    fn timestamp_millis_reads_each_form() {
+        let base = 1770451200000;
+        assert_eq!(timestamp_millis(&json!(base)), Some(base));
+        assert_eq!(timestamp_millis(&json!("1770451200000")), Some(base));
+        assert_eq!(timestamp_millis(&json!("2026-02-07T08:00:00Z")), Some(base));
+        assert_eq!(
+            timestamp_millis(&json!("2026-02-07T08:00:00.25Z")),
+            Some(base + 250)
+        );
+        assert_eq!(
+            timestamp_millis(&json!("2026-02-07 03:00:00-05:00")),
+            Some(base)
+        );
+        assert_eq!(timestamp_millis(&json!("2026-02-07")), None);
+        assert_eq!(timestamp_millis(&json!("yesterday")), None);
+        assert_eq!(timestamp_millis(&json!(null)), None);
+    }
+
+    #[test]
+    fn duration_picks_the_largest_units() {
+        assert_eq!(duration(40), "40s");
+        assert_eq!(duration(720), "12m");
+        assert_eq!(duration(3600), "1h 0m");
+        assert_eq!(duration(6130), "1h 42m");
+    }
+}
@@ -0,0 +1,104 @@
+//! Failures that end a step without a result. Each variant carries what a
+//! caller needs to explain it; the binary decides how to phrase and where to
+//! print it, so the library itself stays quiet.
+
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+#[derive(Debug)]
+pub enum IdrError {
+    /// A git command could not be started or exited non-zero.
+    Git { args: Vec<String>, stderr: String },
+    /// The claude CLI could not be started.
+    ClaudeSpawn(io::Error),
+    /// claude (CLI or API) ran but gave no usable answer. `stderr` is the
+    /// CLI's own output, empty when there is none.
+    ClaudeFailed { reason: String, stderr: String },
+    /// The claude CLI did not finish within `timeout_secs`.
+    ClaudeTimeout { secs: u64 },
+    /// No transcript qualifies as the current session.
+    SessionNotFound(String),
+    /// Reading or writing `path` failed.
+    Io { path: PathBuf, source: io::Error },
+    /// The config file exists but is not valid.
+    ConfigInvalid { path: PathBuf, reason: String },
+    /// The staged diff is over `max_diff_lines`.
+    DiffTooLarge { lines: u64, limit: u64 },
+}
+
+impl fmt::Display for IdrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IdrError::Git { args, stderr } => {
+                write!(f, "git {} failed: {}", args.join(" "), stderr.trim())
+            }
+            IdrError::ClaudeSpawn(e) => write!(f, "cannot start claude CLI: {e}"),
+            IdrError::ClaudeFailed { reason, stderr } if stderr.trim().is_empty() => {
+                write!(f, "{reason}")
+            }
+            IdrError::ClaudeFailed { reason, stderr } => write!(f, "{reason}: {}", stderr.trim()),
+            IdrError::ClaudeTimeout { secs } => write!(f, "claude CLI timed out after {secs}s"),
+            IdrError::SessionNotFound(reason) => write!(f, "{reason}"),
+            IdrError::Io { path, source } => write!(f, "{}: {source}", path.display()),
+            IdrError::ConfigInvalid { path, reason } => {
+                write!(f, "invalid config {}: {reason}", path.display())
+            }
+            IdrError::DiffTooLarge { lines, limit } => {
+                write!(f, "diff too large ({lines} lines > {limit} limit)")
+            }
+        }
+    }
+}
+
+impl std::error::Error for IdrError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            IdrError::ClaudeSpawn(e) | IdrError::Io { source: e, .. } => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl IdrError {
+    /// A claude failure with no captured stderr.
+    pub(crate) fn claude(reason: impl Into<String>) -> IdrError {
+        IdrError::ClaudeFailed {
+            reason: reason.into(),
+            stderr: String::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_keeps_the_established_messages() {
+        let git = IdrError::Git {
+            args: vec!["diff".to_string(), "--cached".to_string()],
+            stderr: "fatal: not a git repository\n".to_string(),
+        };
+        assert_eq!(
+            git.to_string(),
+            "git diff --cached failed: fatal: not a git repository"
+        );
+        assert_eq!(
+            IdrError::ClaudeTimeout { secs: 300 }.to_string(),
+            "claude CLI timed out after 300s"
+        );
+        assert_eq!(
+            IdrError::DiffTooLarge {
+                lines: 900,
+                limit: 500
+            }
+            .to_string(),
+            "diff too large (900 lines > 500 limit)"
+        );
+        assert_eq!(
+            IdrError::claude("claude CLI reported an error: overloaded").to_string(),
+            "claude CLI reported an error: overloaded"
+        );
+    }
+}
@@ -0,0 +1,445 @@
+//! `claude-idr export`: the records of an output directory as standalone
+//! HTML pages for readers outside the repository, or as one JSON file of
+//! parsed records (see [`crate::record`]) for tools.
+
+use crate::config::Config;
+use crate::front_matter;
+use crate::history;
+use crate::index;
+use crate::monthly;
+use crate::path;
+use crate::record;
+use pulldown_cmark::{CodeBlockKind, CowStr, Event, Options, Parser, Tag, TagEnd, html};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Where exports go when `--out` is not given, under the exported directory.
+pub const DEFAULT_DIR: &str = "export";
+/// The file `--format json` writes in the export directory.
+pub const JSON_FILE_NAME: &str = "records.json";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Html,
+    Json,
+}
+
+impl ExportFormat {
+    pub fn parse(name: &str) -> Option<ExportFormat> {
+        match name {
+            "html" => Some(ExportFormat::Html),
+            "json" => Some(ExportFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Exports `dir` to `out` in `format`, returning the files written: a page
+/// for every record, journal, and `index.md`, or [`JSON_FILE_NAME`] with
+/// every record in number order.
+pub fn export(
+    dir: &Path,
+    out: &Path,
+    format: ExportFormat,
+    config: &Config,
+) -> io::Result<Vec<PathBuf>> {
+    match format {
+        ExportFormat::Html => export_html(dir, out, config),
+        ExportFormat::Json => export_json(dir, out, config),
+    }
+}
+
+fn no_records(dir: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("no records in {}", dir.display()),
+    )
+}
+
+fn export_json(dir: &Path, out: &Path, config: &Config) -> io::Result<Vec<PathBuf>> {
+    let records = path::list_records(dir, config.format);
+    if records.is_empty() {
+        return Err(no_records(dir));
+    }
+    let mut parsed = Vec::new();
+    for (number, source) in records {
+        let text = String::from_utf8_lossy(&fs::read(&source)?).into_owned();
+        parsed.push(record::ParsedRecord {
+            file: source
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned()),
+            number: Some(number),
+            ..record::parse(&text, config)
+        });
+    }
+    let json = serde_json::to_string_pretty(&parsed).map_err(io::Error::other)?;
+    fs::create_dir_all(out)?;
+    let target = out.join(JSON_FILE_NAME);
+    fs::write(&target, json + "\n")?;
+    Ok(vec![target])
+}
+
+fn export_html(dir: &Path, out: &Path, config: &Config) -> io::Result<Vec<PathBuf>> {
+    let sources = sources(dir, config);
+    if sources.is_empty() {
+        return Err(no_records(dir));
+    }
+    let names: Vec<String> = sources
+        .iter()
+        .filter_map(|p| Some(p.file_name()?.to_string_lossy().into_owned()))
+        .collect();
+    fs::create_dir_all(out)?;
+    let mut written = Vec::new();
+    for (source, name) in sources.iter().zip(&names) {
+        let text = String::from_utf8_lossy(&fs::read(source)?).into_owned();
+        let target = out.join(html_name(name));
+        fs::write(&target, render(&text, &names, config))?;
+        written.push(target);
+    }
+    Ok(written)
+}
+
+/// `text` as an HTML page, with links to the files in `names` pointing at
+/// their pages next to it.
+pub(crate) fn render(text: &str, names: &[String], config: &Config) -> String {
+    let links = Links {
+        exported: names,
+        template: config.export_link_template.as_deref(),
+    };
+    page(text, &links, &config.language)
+}
+
+/// The files an HTML export reads: `index.md` first, then records and monthly
+/// journals in file name order.
+pub(crate) fn sources(dir: &Path, config: &Config) -> Vec<PathBuf> {
+    let index = dir.join(index::FILE_NAME);
+    let mut files: Vec<PathBuf> = path::list_records(dir, config.format)
+        .into_iter()
+        .map(|(_, p)| p)
+        .collect();
+    if let Ok(entries) = fs::read_dir(dir) {
+        files.extend(
+            entries
+                .filter_map(|e| e.ok())
+                .filter(|e| monthly::is_journal_name(&e.file_name().to_string_lossy()))
+                .map(|e| e.path()),
+        );
+    }
+    files.sort();
+    files.dedup();
+    if index.is_file() {
+        files.insert(0, index);
+    }
+    files
+}
+
+/// `idr-07-use-jwt.md` -> `idr-07-use-jwt.html`.
+pub(crate) fn html_name(name: &str) -> String {
+    format!("{}.html", name.strip_suffix(".md").unwrap_or(name))
+}
+
+/// How links in a record are rewritten for the export.
+struct Links<'a> {
+    /// File names of everything exported; links to them point at the
+    /// exported copies.
+    exported: &'a [String],
+    /// `export_link_template`; without one, links to repository files
+    /// become plain text.
+    template: Option<&'a str>,
+}
+
+impl Links<'_> {
+    /// The new target of a link to `url`, or `None` to drop the link and
+    /// keep its text.
+    fn rewrite(&self, url: &str) -> Option<String> {
+        if url.starts_with('#') || url.contains("://") || url.starts_with("mailto:") {
+            return Some(url.to_string());
+        }
+        let file = url.replace("%20", " ");
+        if self.exported.contains(&file) {
+            return Some(html_name(url));
+        }
+        self.template
+            .map(|template| template.replace("{path}", url.trim_start_matches("./")))
+    }
+}
+
+const STYLE: &str = "\
+body { max-width: 52rem; margin: 2rem auto; padding: 0 1rem; font: 16px/1.6 system-ui, sans-serif; color: #1f2328; }
+h1, h2, h3, h4 { line-height: 1.3; }
+blockquote { margin: 0; padding: 0 1rem; color: #59636e; border-left: 0.25rem solid #d1d9e0; }
+code { font: 0.875rem ui-monospace, monospace; }
+pre { padding: 1rem; overflow: auto; background: #f6f8fa; border-radius: 6px; }
+pre .add { color: #116329; background: #dafbe1; }
+pre .del { color: #82071e; background: #ffebe9; }
+pre .hunk { color: #59636e; }
+table { border-collapse: collapse; }
+th, td { padding: 0.25rem 0.75rem; border: 1px solid #d1d9e0; }
+";
+
+/// A record as a standalone HTML page.
+fn page(markdown: &str, links: &Links, language: &str) -> String {
+    let title = history::title(markdown);
+    let (front_matter, body) = front_matter::split(markdown);
+    let heading = match front_matter {
+        Some(_) => format!("<h1>{}</h1>\n", escape(&title)),
+        None => String::new(),
+    };
+    document(&title, &(heading + &to_html(body, links)), language)
+}
+
+/// A standalone HTML page around the fragment `body`.
+pub(crate) fn document(title: &str, body: &str, language: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n\
+         <html lang=\"{}\">\n\
+         <head>\n\
+         <meta charset=\"utf-8\">\n\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+         <title>{}</title>\n\
+         <style>\n{STYLE}</style>\n\
+         </head>\n\
+         <body>\n\
+         {body}\
+         </body>\n\
+         </html>\n",
+        escape(language),
+        escape(title),
+    )
+}
+
+/// Markdown to an HTML fragment. Raw HTML in the record is shown as text,
+/// except comments (the metadata line), which are dropped; diff blocks get
+/// a class per line for coloring.
+fn to_html(markdown: &str, links: &Links) -> String {
+    let mut events = Vec::new();
+    let mut diff: Option<String> = None;
+    let mut kept_links = Vec::new();
+    for event in Parser::new_ext(markdown, Options::ENABLE_TABLES) {
+        match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(lang))) if &*lang == "diff" => {
+                diff = Some(String::new());
+            }
+            Event::Text(text) if diff.is_some() => {
+                if let Some(code) = &mut diff {
+                    code.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::CodeBlock) if diff.is_some() => {
+                let code = diff.take().unwrap_or_default();
+                events.push(Event::Html(CowStr::from(diff_block(&code))));
+            }
+            Event::Start(Tag::Link {
+                link_type,
+                dest_url,
+                title,
+                id,
+            }) => match links.rewrite(&dest_url) {
+                Some(url) => {
+                    kept_links.push(true);
+                    events.push(Event::Start(Tag::Link {
+                        link_type,
+                        dest_url: CowStr::from(url),
+                        title,
+                        id,
+                    }));
+                }
+                None => kept_links.push(false),
+            },
+            Event::End(TagEnd::Link) => {
+                if kept_links.pop().unwrap_or(true) {
+                    events.push(Event::End(TagEnd::Link));
+                }
+            }
+            Event::Html(raw) | Event::InlineHtml(raw) => {
+                if !raw.trim_start().starts_with("<!--") {
+                    events.push(Event::Text(raw));
+                }
+            }
+            other => events.push(other),
+        }
+    }
+    let mut out = String::new();
+    html::push_html(&mut out, events.into_iter());
+    out
+}
+
+/// A `diff` code block with each added, removed, and hunk header line in a
+/// span of its own.
+fn diff_block(code: &str) -> String {
+    let mut out = String::from("<pre><code class=\"language-diff\">");
+    for line in code.split_inclusive('\n') {
+        let (text, newline) = match line.strip_suffix('\n') {
+            Some(text) => (text, "\n"),
+            None => (line, ""),
+        };
+        let class = if text.starts_with("+++") || text.starts_with("---") {
+            None
+        } else if text.starts_with('+') {
+            Some("add")
+        } else if text.starts_with('-') {
+            Some("del")
+        } else if text.starts_with("@@") {
+            Some("hunk")
+        } else {
+            None
+        };
+        match class {
+            Some(class) => out.push_str(&format!(
+                "<span class=\"{class}\">{}</span>{newline}",
+                escape(text)
+            )),
+            None => out.push_str(&format!("{}{newline}", escape(text))),
+        }
+    }
+    out.push_str("</code></pre>\n");
+    out
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const NO_LINKS: Links = Links {
+        exported: &[],
+        template: None,
+    };
+
+    #[test]
+    fn page_matches_golden_html() {
+        let record = include_str!("../tests/fixtures/export-record.md");
+        let exported = ["idr-01-retry.md".to_string()];
+        let links = Links {
+            exported: &exported,
+            template: None,
+        };
+
+        assert_eq!(
+            page(record, &links, "ja"),
+            include_str!("../tests/fixtures/export-record.html")
+        );
+    }
+
+    #[test]
+    fn page_of_front_matter_record_gets_title_heading() {
+        let record = "---\ntitle: \"Use <JWT>\"\ndate: \"2026-02-07\"\n---\n\n> 2026-02-07 17:30\n";
+
+        let html = page(record, &NO_LINKS, "en");
+
+        assert!(html.contains("<title>Use &lt;JWT&gt;</title>"), "{html}");
+        assert!(html.contains("<body>\n<h1>Use &lt;JWT&gt;</h1>\n<blockquote>"));
+        assert!(!html.contains("date:"));
+    }
+
+    #[test]
+    fn diff_block_colors_changed_lines_only() {
+        assert_eq!(
+            diff_block("@@ -1 +1 @@\n-a < b\n+a > b\n--- old\n ctx"),
+            "<pre><code class=\"language-diff\"><span class=\"hunk\">@@ -1 +1 @@</span>\n\
+             <span class=\"del\">-a &lt; b</span>\n<span class=\"add\">+a &gt; b</span>\n\
+             --- old\n ctx</code></pre>\n"
+        );
+    }
+
+    #[test]
+    fn links_follow_template_or_become_text() {
+        let exported = ["idr-02.md".to_string(), "index.md".to_string()];
+        let template = Links {
+            exported: &exported,
+            template: Some("https://example.com/blob/main/{path}"),
+        };
+
+        assert_eq!(
+            template.rewrite("src/a.rs").as_deref(),
+            Some("https://example.com/blob/main/src/a.rs")
+        );
+        assert_eq!(
+            template.rewrite("idr-02.md").as_deref(),
+            Some("idr-02.html")
+        );
+        assert_eq!(
+            template.rewrite("https://x.test/a.md").as_deref(),
+            Some("https://x.test/a.md")
+        );
+        assert_eq!(NO_LINKS.rewrite("src/a.rs"), None);
+        assert_eq!(
+            to_html("[src/a.rs](src/a.rs)", &NO_LINKS),
+            "<p>src/a.rs</p>\n"
+        );
+    }
+
+    #[test]
+    fn raw_html_is_shown_as_text() {
+        assert_eq!(
+            to_html(
+                "a <script>x</script>\n\n<!-- claude-idr: v -->\n",
+                &NO_LINKS
+            ),
+            "<p>a &lt;script&gt;x&lt;/script&gt;</p>\n"
+        );
+    }
+
+    #[test]
+    fn export_writes_records_and_index() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("idr-01-a.md"), "# IDR: A\n").unwrap();
+        fs::write(
+            dir.path().join("index.md"),
+            "| # | Title |\n|---|---|\n| 1 | [A](idr-01-a.md) |\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("notes.md"), "# not a record\n").unwrap();
+        let out = dir.path().join(DEFAULT_DIR);
+
+        let written = export(dir.path(), &out, ExportFormat::Html, &Config::default()).unwrap();
+
+        assert_eq!(written, [out.join("index.html"), out.join("idr-01-a.html")]);
+        let index = fs::read_to_string(out.join("index.html")).unwrap();
+        assert!(index.contains("<a href=\"idr-01-a.html\">A</a>"), "{index}");
+    }
+
+    #[test]
+    fn export_json_writes_parsed_records_in_number_order() {
+        let dir = TempDir::new().unwrap();
+        fs::write(
+            dir.path().join("idr-10-b.md"),
+            "# IDR: B\n\n## Summary of changes\n\nBee.\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("idr-02-a.md"), "# IDR: A\n").unwrap();
+        fs::write(dir.path().join("index.md"), "# Decision records\n").unwrap();
+        let out = dir.path().join(DEFAULT_DIR);
+
+        let written = export(dir.path(), &out, ExportFormat::Json, &Config::default()).unwrap();
+
+        assert_eq!(written, [out.join(JSON_FILE_NAME)]);
+        let json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&written[0]).unwrap()).unwrap();
+        let records = json.as_array().unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["file"], "idr-02-a.md");
+        assert_eq!(records[1]["number"], 10);
+        assert_eq!(records[1]["title"], "B");
+        assert_eq!(records[1]["summary"], "Bee.");
+    }
+
+    #[test]
+    fn export_of_empty_directory_fails() {
+        let dir = TempDir::new().unwrap();
+        let out = dir.path().join(DEFAULT_DIR);
+
+        for format in [ExportFormat::Html, ExportFormat::Json] {
+            assert!(export(dir.path(), &out, format, &Config::default()).is_err());
+        }
+        assert!(!out.exists());
+    }
+}
@@ -0,0 +1,328 @@
+//! Debug bundles: what a failed IDR call was given, saved under
+//! `$XDG_STATE_HOME/claude-idr/failures/<timestamp>/` so `retry-last` can
+//! replay it later without finding the session or the diff again.
+
+use crate::claude::{self, ClaudeResult};
+use crate::config::{self, Config};
+use crate::error::IdrError;
+use crate::generate;
+use crate::log::warn;
+use crate::path;
+use crate::regenerate;
+use crate::sidecar::Sidecar;
+use crate::validate;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const PROMPT_FILE: &str = "prompt.md";
+const STDERR_FILE: &str = "stderr.txt";
+const COMMAND_FILE: &str = "command.txt";
+const CONFIG_FILE: &str = "config.txt";
+const ERROR_FILE: &str = "error.txt";
+const TARGET_FILE: &str = "target.json";
+
+/// Everything saved about one failed call.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bundle {
+    /// The IDR prompt exactly as it was sent, already redacted.
+    pub prompt: String,
+    /// What the claude CLI printed on stderr; empty after a timeout.
+    pub stderr: String,
+    /// How the prompt was sent (see [`claude::command_line`]).
+    pub command: String,
+    /// The effective config of the run.
+    pub config: String,
+    pub error: String,
+    pub target: Target,
+}
+
+/// What the record would have been made of, apart from its body.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Target {
+    pub record_dir: PathBuf,
+    pub title: String,
+    /// The purpose claude extracted, which names the file.
+    pub purpose: Option<String>,
+    pub stat: String,
+    /// Footer lines, without the generation-failure line.
+    pub footer: Option<String>,
+    pub related: Option<String>,
+    pub branch: Option<String>,
+    pub sidecar: Sidecar,
+    /// The placeholder record written for the call (`on_failure:
+    /// "placeholder"`), which a replay fills in instead of writing anew.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub record: Option<PathBuf>,
+}
+
+impl Bundle {
+    pub fn new(prompt: &str, error: &IdrError, config: &Config, target: Target) -> Bundle {
+        let stderr = match error {
+            IdrError::ClaudeFailed { stderr, .. } => stderr.clone(),
+            _ => String::new(),
+        };
+        Bundle {
+            prompt: prompt.to_string(),
+            stderr,
+            command: claude::command_line(config),
+            config: format!("{config:#?}\n"),
+            error: format!("{error}\n"),
+            target,
+        }
+    }
+
+    pub fn load(dir: &Path) -> io::Result<Bundle> {
+        let read = |name: &str| fs::read_to_string(dir.join(name));
+        let target = serde_json::from_str(&read(TARGET_FILE)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(Bundle {
+            prompt: read(PROMPT_FILE)?,
+            stderr: read(STDERR_FILE)?,
+            command: read(COMMAND_FILE)?,
+            config: read(CONFIG_FILE)?,
+            error: read(ERROR_FILE)?,
+            target,
+        })
+    }
+
+    fn write(&self, dir: &Path) -> io::Result<()> {
+        fs::write(dir.join(PROMPT_FILE), &self.prompt)?;
+        fs::write(dir.join(STDERR_FILE), &self.stderr)?;
+        fs::write(dir.join(COMMAND_FILE), &self.command)?;
+        fs::write(dir.join(CONFIG_FILE), &self.config)?;
+        fs::write(dir.join(ERROR_FILE), &self.error)?;
+        write_target(dir, &self.target)
+    }
+}
+
+fn write_target(dir: &Path, target: &Target) -> io::Result<()> {
+    let json = serde_json::to_string_pretty(target).map_err(io::Error::other)?;
+    fs::write(dir.join(TARGET_FILE), json + "\n")
+}
+
+/// `claude-idr/failures` in the XDG state directory (`~/.local/state`
+/// where it is not set).
+pub fn root() -> Option<PathBuf> {
+    Some(config::state_dir()?.join("failures"))
+}
+
+/// Saves `bundle` in a new directory of `root` named by the time, then
+/// removes the oldest bundles beyond `keep`.
+pub fn save(root: &Path, bundle: &Bundle, keep: usize) -> io::Result<PathBuf> {
+    fs::create_dir_all(root)?;
+    let stamp: String = path::now_iso8601()
+        .chars()
+        .filter(|c| !matches!(c, '-' | ':'))
+        .collect();
+    let mut dir = root.join(&stamp);
+    let mut n = 1;
+    loop {
+        match fs::create_dir(&dir) {
+            Ok(()) => break,
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                dir = root.join(format!("{stamp}-{n}"));
+                n += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    bundle.write(&dir)?;
+    prune(root, keep);
+    Ok(dir)
+}
+
+/// Like [`save`] into [`root`], keeping `debug_bundles`; only warns when
+/// that fails. `None` when nothing was saved.
+pub fn save_warn(bundle: &Bundle, config: &Config) -> Option<PathBuf> {
+    if config.debug_bundles == 0 {
+        return None;
+    }
+    let Some(root) = root() else {
+        warn!("no state directory for a debug bundle");
+        return None;
+    };
+    save(&root, bundle, config.debug_bundles)
+        .inspect_err(|e| warn!("cannot save debug bundle in {}: {e}", root.display()))
+        .ok()
+}
+
+/// Notes in the bundle at `dir` that a placeholder `record` was written.
+pub fn record_written(dir: &Path, record: &Path) {
+    let saved = Bundle::load(dir).and_then(|bundle| {
+        let target = Target {
+            record: Some(record.to_path_buf()),
+            ..bundle.target
+        };
+        write_target(dir, &target)
+    });
+    if let Err(e) = saved {
+        warn!("cannot update debug bundle {}: {e}", dir.display());
+    }
+}
+
+/// Bundle directories in `root`, oldest first.
+fn list(root: &Path) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = fs::read_dir(root)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|dir| dir.join(TARGET_FILE).is_file())
+        .collect();
+    dirs.sort();
+    dirs
+}
+
+/// The newest bundle in `root`.
+pub fn latest(root: &Path) -> Option<PathBuf> {
+    list(root).pop()
+}
+
+fn prune(root: &Path, keep: usize) {
+    let dirs = list(root);
+    let excess = dirs.len().saturating_sub(keep);
+    for dir in &dirs[..excess] {
+        if let Err(e) = fs::remove_dir_all(dir) {
+            warn!("cannot remove old debug bundle {}: {e}", dir.display());
+        }
+    }
+}
+
+/// Sends the prompt saved in `dir` again and writes the record: into the
+/// placeholder when one was written and is still untouched, otherwise as a
+/// new record where the failed one would have gone. The bundle is removed
+/// once the record is written.
+pub fn replay(dir: &Path, config: &Config) -> Result<PathBuf, String> {
+    let bundle = Bundle::load(dir).map_err(|e| format!("cannot read {}: {e}", dir.display()))?;
+    let target = bundle.target;
+    let placeholder = target
+        .record
+        .clone()
+        .filter(|record| regenerate::pending(record, config));
+    let files_changed = !target.stat.trim().is_empty();
+    let result: ClaudeResult = validate::run(&bundle.prompt, config, files_changed, |prompt| {
+        claude::run(prompt, config)
+    })
+    .map_err(|e| e.to_string())?;
+    let record = match placeholder {
+        Some(record) => {
+            regenerate::fill(&record, result, config)?;
+            record
+        }
+        None => generate::write_replayed(&target, result, config).map_err(|e| e.to_string())?,
+    };
+    if let Err(e) = fs::remove_dir_all(dir) {
+        warn!("cannot remove debug bundle {}: {e}", dir.display());
+    }
+    Ok(record)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sidecar::{DiffSource, SCHEMA_VERSION};
+    use tempfile::TempDir;
+
+    fn bundle() -> Bundle {
+        let error = IdrError::ClaudeFailed {
+            reason: "claude CLI failed (exit status: 1)".to_string(),
+            stderr: "overloaded\n".to_string(),
+        };
+        let target = Target {
+            record_dir: PathBuf::from("/ws/planning"),
+            title: "Add retries".to_string(),
+            purpose: Some("Add retries".to_string()),
+            stat: " src/api.rs | 3 +++".to_string(),
+            footer: Some("> Commit: abc1234".to_string()),
+            related: None,
+            branch: Some("main".to_string()),
+            sidecar: Sidecar {
+                schema: SCHEMA_VERSION,
+                diff_source: DiffSource::Staged,
+                diff_sha256: "00".repeat(32),
+                session_path: None,
+                session_id: None,
+                purpose_model: None,
+                body_model: "sonnet".to_string(),
+                started: "2026-10-16T01:00:00Z".to_string(),
+                generated: "2026-10-16T01:00:00Z".to_string(),
+                usage: None,
+                issues: Vec::new(),
+                change_type: None,
+                commit: None,
+                failure: Some("claude CLI failed".to_string()),
+                title_source: Some("request".to_string()),
+                authorship: None,
+                effort: None,
+            },
+            record: None,
+        };
+        Bundle::new("idr prompt\n", &error, &Config::default(), target)
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let root = TempDir::new().unwrap();
+        let saved = bundle();
+
+        let dir = save(root.path(), &saved, 10).unwrap();
+
+        assert_eq!(Bundle::load(&dir).unwrap(), saved);
+        assert_eq!(saved.stderr, "overloaded\n");
+        assert!(saved.command.starts_with("claude -p --model sonnet"));
+        assert!(saved.config.contains("model: \"sonnet\""));
+        assert_eq!(
+            fs::read_to_string(dir.join(PROMPT_FILE)).unwrap(),
+            "idr prompt\n"
+        );
+    }
+
+    #[test]
+    fn record_written_updates_the_target() {
+        let root = TempDir::new().unwrap();
+        let dir = save(root.path(), &bundle(), 10).unwrap();
+
+        record_written(&dir, Path::new("/ws/planning/idr-01-add-retries.md"));
+
+        let target = Bundle::load(&dir).unwrap().target;
+        assert_eq!(
+            target.record.as_deref(),
+            Some(Path::new("/ws/planning/idr-01-add-retries.md"))
+        );
+        assert_eq!(target.title, "Add retries");
+    }
+
+    #[test]
+    fn save_prunes_the_oldest_beyond_keep() {
+        let root = TempDir::new().unwrap();
+        for name in ["20261014T010000Z", "20261015T010000Z"] {
+            let dir = root.path().join(name);
+            fs::create_dir(&dir).unwrap();
+            bundle().write(&dir).unwrap();
+        }
+        fs::create_dir(root.path().join("unrelated")).unwrap();
+
+        let newest = save(root.path(), &bundle(), 2).unwrap();
+
+        assert_eq!(
+            list(root.path()),
+            vec![root.path().join("20261015T010000Z"), newest.clone()]
+        );
+        assert_eq!(latest(root.path()), Some(newest));
+        assert!(root.path().join("unrelated").is_dir());
+    }
+
+    #[test]
+    fn save_never_reuses_a_directory() {
+        let root = TempDir::new().unwrap();
+
+        let first = save(root.path(), &bundle(), 10).unwrap();
+        let second = save(root.path(), &bundle(), 10).unwrap();
+
+        assert_ne!(first, second);
+        assert_eq!(latest(root.path()), Some(second));
+        assert_eq!(latest(&root.path().join("missing")), None);
+    }
+}
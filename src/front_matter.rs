@@ -0,0 +1,242 @@
+//! YAML front matter for records (`front_matter`), for static-site and
+//! note-taking tools that read metadata from a leading `---` block.
+
+const DELIMITER: &str = "---";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrontMatter {
+    pub title: String,
+    pub date: String,
+    pub model: String,
+    pub session_id: Option<String>,
+    pub branch: Option<String>,
+    pub files_changed: u64,
+    pub insertions: u64,
+    pub deletions: u64,
+    pub tags: Vec<String>,
+}
+
+impl FrontMatter {
+    /// The block including both `---` lines. Strings are always
+    /// double-quoted so colons, quotes, and leading symbols stay literal.
+    pub fn render(&self) -> String {
+        let optional = |value: &Option<String>| value.as_deref().map_or("null".to_string(), quote);
+        let tags: Vec<String> = self.tags.iter().map(|t| quote(t)).collect();
+        format!(
+            "{DELIMITER}\n\
+             title: {}\n\
+             date: {}\n\
+             model: {}\n\
+             session_id: {}\n\
+             branch: {}\n\
+             files_changed: {}\n\
+             insertions: {}\n\
+             deletions: {}\n\
+             tags: [{}]\n\
+             {DELIMITER}\n",
+            quote(&self.title),
+            quote(&self.date),
+            quote(&self.model),
+            optional(&self.session_id),
+            optional(&self.branch),
+            self.files_changed,
+            self.insertions,
+            self.deletions,
+            tags.join(", ")
+        )
+    }
+}
+
+fn quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Splits a record into its front matter block (without delimiters) and
+/// the rest. Legacy records without front matter return `None` and the
+/// whole text.
+pub fn split(text: &str) -> (Option<&str>, &str) {
+    let Some(rest) = text
+        .strip_prefix(DELIMITER)
+        .and_then(|r| r.strip_prefix('\n').or_else(|| r.strip_prefix("\r\n")))
+    else {
+        return (None, text);
+    };
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == DELIMITER {
+            return (Some(&rest[..offset]), &rest[offset + line.len()..]);
+        }
+        offset += line.len();
+    }
+    (None, text)
+}
+
+/// A top-level string value from a front matter block, unquoted.
+pub fn field(block: &str, key: &str) -> Option<String> {
+    let value = block.lines().find_map(|line| {
+        let (k, v) = line.split_once(':')?;
+        (k.trim() == key).then(|| v.trim())
+    })?;
+    match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        Some(inner) => Some(unquote(inner)),
+        None if value == "null" || value == "~" => None,
+        None => Some(value.trim_matches('\'').to_string()),
+    }
+}
+
+/// A top-level flow sequence (`tags: ["idr", "fix"]`) from a front matter
+/// block; empty when the key is missing or holds something else.
+pub fn list(block: &str, key: &str) -> Vec<String> {
+    let Some(value) = block.lines().find_map(|line| {
+        let (k, v) = line.split_once(':')?;
+        (k.trim() == key).then(|| v.trim())
+    }) else {
+        return Vec::new();
+    };
+    let Some(items) = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) else {
+        return Vec::new();
+    };
+    items
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(
+            |item| match item.strip_prefix('"').and_then(|i| i.strip_suffix('"')) {
+                Some(inner) => unquote(inner),
+                None => item.trim_matches('\'').to_string(),
+            },
+        )
+        .collect()
+}
+
+fn unquote(inner: &str) -> String {
+    let mut out = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('u') => {
+                let hex: String = chars.by_ref().take(4).collect();
+                out.extend(u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32));
+            }
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> FrontMatter {
+        FrontMatter {
+            title: "Switch auth to JWT".to_string(),
+            date: "2026-02-07T05:30:00Z".to_string(),
+            model: "sonnet".to_string(),
+            session_id: Some("5f0c3a2e".to_string()),
+            branch: None,
+            files_changed: 3,
+            insertions: 42,
+            deletions: 7,
+            tags: vec!["idr".to_string()],
+        }
+    }
+
+    #[test]
+    fn render_emits_yaml_block() {
+        assert_eq!(
+            sample().render(),
+            "---\n\
+             title: \"Switch auth to JWT\"\n\
+             date: \"2026-02-07T05:30:00Z\"\n\
+             model: \"sonnet\"\n\
+             session_id: \"5f0c3a2e\"\n\
+             branch: null\n\
+             files_changed: 3\n\
+             insertions: 42\n\
+             deletions: 7\n\
+             tags: [\"idr\"]\n\
+             ---\n"
+        );
+    }
+
+    #[test]
+    fn list_reads_rendered_tags() {
+        let front_matter = FrontMatter {
+            tags: vec!["idr".to_string(), "fix".to_string()],
+            ..sample()
+        };
+        let text = front_matter.render();
+        let (block, _) = split(&text);
+
+        assert_eq!(list(block.unwrap(), "tags"), ["idr", "fix"]);
+        assert_eq!(list("tags: [a, 'b']", "tags"), ["a", "b"]);
+        assert!(list("tags: []", "tags").is_empty());
+        assert!(list("title: x", "tags").is_empty());
+    }
+
+    #[test]
+    fn quote_escapes_yaml_special_characters() {
+        assert_eq!(
+            quote("fix: handle \"quoted\" keys"),
+            r#""fix: handle \"quoted\" keys""#
+        );
+        assert_eq!(quote(r"C:\path"), r#""C:\\path""#);
+        assert_eq!(quote("a\nb"), r#""a\nb""#);
+        assert_eq!(quote("# not a comment"), "\"# not a comment\"");
+    }
+
+    #[test]
+    fn field_round_trips_escaped_title() {
+        let front_matter = FrontMatter {
+            title: "fix: \"quoted\" C:\\path\nnext\u{7}".to_string(),
+            ..sample()
+        };
+        let text = front_matter.render() + "\nbody\n";
+
+        let (block, _) = split(&text);
+
+        assert_eq!(field(block.unwrap(), "title"), Some(front_matter.title));
+        assert_eq!(field(block.unwrap(), "branch"), None);
+        assert_eq!(field(block.unwrap(), "model").as_deref(), Some("sonnet"));
+    }
+
+    #[test]
+    fn split_detects_front_matter() {
+        let text = sample().render() + "\n## 変更概要\n";
+
+        let (block, body) = split(&text);
+
+        assert!(block.unwrap().starts_with("title: "));
+        assert_eq!(body, "\n## 変更概要\n");
+    }
+
+    #[test]
+    fn split_leaves_legacy_records_alone() {
+        let legacy = "# IDR: old\n\n> 2026-01-01 00:00\n\n---\n\nstat\n";
+        assert_eq!(split(legacy), (None, legacy));
+
+        let unterminated = "---\ntitle: x\n";
+        assert_eq!(split(unterminated), (None, unterminated));
+    }
+}
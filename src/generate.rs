@@ -0,0 +1,1941 @@
+//! The generation pipeline behind `claude-idr` with no subcommand: find the
+//! session, build the prompts, call claude, and write the record.
+
+use crate::anonymize::Anonymizer;
+use crate::authorship::Authorship;
+use crate::backfill;
+use crate::claude::{self, ClaudeResult, Usage};
+use crate::config::{self, Backend, Config, LargeDiffStrategy, OnFailure};
+use crate::context::{self, SessionContext};
+use crate::crosslink;
+use crate::diff;
+use crate::diff_filter;
+use crate::docsite;
+use crate::effort::Effort;
+use crate::error::IdrError;
+use crate::failures;
+use crate::front_matter;
+use crate::git;
+use crate::history;
+use crate::ignore;
+use crate::index;
+use crate::issues;
+use crate::jsonl;
+use crate::language;
+use crate::latest;
+use crate::lock;
+use crate::log::{verbose, warn};
+use crate::metadata;
+use crate::monthly;
+use crate::noise;
+use crate::notes;
+use crate::obsidian;
+use crate::path;
+use crate::prompt;
+use crate::redact;
+use crate::regenerate;
+use crate::sanitize;
+use crate::session;
+use crate::sidecar;
+use crate::split;
+use crate::summarize;
+use crate::timing::{Timer, Timing};
+use crate::title::{self, Title, TitleInputs};
+use crate::translate;
+use crate::validate;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+/// Budget for earlier records quoted in the IDR prompt (`continuity`).
+const PREVIOUS_IDRS_MAX_CHARS: usize = 3000;
+/// Changed files whose history is looked up (`history_context`), the
+/// commits shown for each, and the budget for all of it.
+const FILE_HISTORY_MAX_FILES: usize = 20;
+const FILE_HISTORY_COMMITS: usize = 3;
+const FILE_HISTORY_MAX_CHARS: usize = 3000;
+/// Tag of records written by [`session_report`], in place of a change type.
+const SESSION_REPORT_TAG: &str = "session-report";
+
+/// Per-run choices that are not part of [`Config`].
+#[derive(Clone, Copy, Default)]
+pub struct GenerateOptions<'a> {
+    /// Build the prompts without calling claude; they are returned in
+    /// [`GenerateReport::prompts`].
+    pub dry_run: bool,
+    /// Generate even when a record for the same diff and session exists.
+    pub no_dedupe: bool,
+    /// Receives progress lines such as `generating IDR...`.
+    pub progress: Option<&'a dyn Fn(&str)>,
+    /// Use this transcript instead of looking for the most recent session,
+    /// as when a Claude Code hook names it.
+    pub session: Option<&'a Path>,
+    /// Document the HEAD commit (against its first parent) instead of the
+    /// staged changes, as the post-commit hook does.
+    pub from_head: bool,
+    /// Write a session report from the transcript alone, without a diff
+    /// (`--from-session`); see [`session_report`].
+    pub session_only: bool,
+    /// Write one record per changed file and an umbrella record linking
+    /// them (`--split-by-file`); see [`split_by_file`].
+    pub split_by_file: bool,
+    /// Send the diff and session context to claude without redacting
+    /// secrets first (`--no-redact`).
+    pub no_redact: bool,
+}
+
+/// The outcome of [`generate`]; serialized as the `--json` run report.
+#[derive(Debug, Default, Serialize)]
+pub struct GenerateReport {
+    /// `generated`, `skipped`, `dry_run`, `error`, or `recorded` when a
+    /// post-commit run only noted the commit in an existing record.
+    pub status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cost_usd: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit_message: Option<String>,
+    /// feat, fix, refactor, docs, test, or chore.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub change_type: Option<&'static str>,
+    /// Time spent per phase, for runs that called claude.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timing: Option<Timing>,
+    /// For a skip, the gate that stopped a change worth documenting.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gate: Option<Gate>,
+    /// In a dry run, each prompt that would have been sent, with a label.
+    #[serde(skip)]
+    pub prompts: Vec<(String, String)>,
+}
+
+/// A skip that left a change undocumented, as opposed to one with nothing
+/// to document (no staged changes, `enabled: false`). `--fail-on-skip`
+/// turns these into failures.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Gate {
+    /// No session transcript recent enough.
+    NoSession,
+    /// The session never wrote or edited a file.
+    NoEdits,
+    /// Over `max_diff_lines` with `large_diff_strategy: "skip"`.
+    DiffTooLarge,
+}
+
+impl Gate {
+    /// The exit status of a run this gate stopped under `--fail-on-skip`.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Gate::NoSession => 3,
+            Gate::NoEdits => 4,
+            Gate::DiffTooLarge => 5,
+        }
+    }
+
+    /// What the committer can do about it.
+    pub fn advice(self) -> &'static str {
+        match self {
+            Gate::NoSession => {
+                "no Claude Code session recent enough was found; commit from the session that made the change, or raise session_max_age_min"
+            }
+            Gate::NoEdits => {
+                "the session did not write or edit any file; make the change through Claude Code, or document it by hand"
+            }
+            Gate::DiffTooLarge => {
+                "the diff is over max_diff_lines; split the commit, or set large_diff_strategy to \"summarize\""
+            }
+        }
+    }
+}
+
+impl GenerateReport {
+    fn skipped(reason: impl Into<String>) -> GenerateReport {
+        GenerateReport {
+            status: "skipped",
+            reason: Some(reason.into()),
+            ..GenerateReport::default()
+        }
+    }
+
+    fn gated(gate: Gate, reason: impl Into<String>) -> GenerateReport {
+        GenerateReport {
+            gate: Some(gate),
+            ..GenerateReport::skipped(reason)
+        }
+    }
+
+    /// The report for a run that ended in `error`.
+    pub fn error(error: &IdrError) -> GenerateReport {
+        GenerateReport {
+            status: "error",
+            reason: Some(error.to_string()),
+            ..GenerateReport::default()
+        }
+    }
+}
+
+/// A skip at `gate`, found before the diff is read. Without a change to
+/// document it is no gate at all.
+fn undocumented(gate: Gate, reason: String, options: &GenerateOptions) -> GenerateReport {
+    let paths = if options.from_head {
+        git::head_paths()
+    } else {
+        git::staged_paths()
+    };
+    GenerateReport {
+        gate: (!paths.is_empty()).then_some(gate),
+        ..GenerateReport::skipped(reason)
+    }
+}
+
+/// Generates a record for the staged changes and the most recent session.
+///
+/// Expected outcomes such as "no staged changes" are `Ok` reports with
+/// status `skipped`; `Err` is for failures that need attention. Lower-level
+/// warnings (a malformed sidecar, a failed index refresh) go to the sink
+/// installed with [`log::set_sink`](crate::log::set_sink); without one,
+/// nothing is printed.
+///
+/// ```no_run
+/// use claude_idr::config::Config;
+/// use claude_idr::{GenerateOptions, generate};
+///
+/// let config = Config::load(None);
+/// let report = generate(&config, &GenerateOptions::default())?;
+/// if let Some(path) = &report.output {
+///     println!("wrote {}", path.display());
+/// }
+/// # Ok::<(), claude_idr::IdrError>(())
+/// ```
+pub fn generate(config: &Config, options: &GenerateOptions) -> Result<GenerateReport, IdrError> {
+    let progress = |line: &str| {
+        if let Some(progress) = options.progress {
+            progress(line);
+        }
+    };
+
+    if !config.enabled {
+        return Ok(GenerateReport::skipped("disabled by config"));
+    }
+    if options.session_only {
+        return session_report(config, options, &progress);
+    }
+    if options.split_by_file && config.output_mode == config::OutputMode::Monthly {
+        return Ok(GenerateReport::skipped(
+            "--split-by-file needs output_mode \"files\"",
+        ));
+    }
+
+    let timer = Timer::new();
+    let found = timer.time("scan", || {
+        given_session(options.session)
+            .unwrap_or_else(|| session::find_recent(config))
+            .map(|p| {
+                let edited = session::has_write_or_edit(&p);
+                (p, edited)
+            })
+    });
+    let session_path = match found {
+        Err(e) => return Ok(undocumented(Gate::NoSession, e.to_string(), options)),
+        Ok((p, false)) => {
+            let (_, stats) = jsonl::read_with_stats(&p);
+            verbose!("session {}: {stats}", p.display());
+            let mut reason = format!(
+                "session found but no code changes via Claude detected: {}",
+                p.display()
+            );
+            if let Some(diagnosis) = stats.diagnosis() {
+                reason.push_str(&format!(" ({diagnosis})"));
+            }
+            return Ok(undocumented(Gate::NoEdits, reason, options));
+        }
+        Ok((p, true)) => p,
+    };
+
+    let commit = if options.from_head {
+        match git::head_commit() {
+            Some((sha, subject)) => Some(sidecar::CommitRef { sha, subject }),
+            None => return Ok(GenerateReport::skipped("no commit to document yet")),
+        }
+    } else {
+        None
+    };
+    let ignore = ignore::Rules::for_repo(config);
+    let (diff, stat, changed_lines, changed_paths) = if commit.is_some() {
+        let exclude = ignore.excluded(&git::head_paths());
+        let diff = git::head_diff(&exclude)?;
+        if diff.is_empty() {
+            return Ok(GenerateReport::skipped(if exclude.is_empty() {
+                "HEAD changes no files"
+            } else {
+                "HEAD changes only files matched by .idrignore"
+            }));
+        }
+        (
+            diff,
+            git::head_stat(&exclude),
+            git::head_changed_lines(&exclude),
+            git::head_numstat_paths(&exclude),
+        )
+    } else {
+        let exclude = ignore.excluded(&git::staged_paths());
+        let diff = git::staged_diff(&exclude)?;
+        if diff.is_empty() {
+            return Ok(GenerateReport::skipped(if exclude.is_empty() {
+                "no staged changes"
+            } else {
+                "only files matched by .idrignore are staged"
+            }));
+        }
+        (
+            diff,
+            git::staged_stat(&exclude),
+            git::staged_changed_lines(&exclude),
+            git::staged_numstat_paths(&exclude),
+        )
+    };
+    // The filtered, sanitized, denoised diff is what prompts see, so it is
+    // what the limit checks.
+    let filtered = diff_filter::apply(&diff, config);
+    let sanitized = sanitize::apply(filtered.as_deref().unwrap_or(&diff), config);
+    let prompt_source = sanitized
+        .as_ref()
+        .map(|s| s.diff.as_str())
+        .or(filtered.as_deref());
+    let denoised = noise::strip(prompt_source.unwrap_or(&diff), config);
+    let prompt_source = denoised.as_ref().map(|d| d.diff.as_str()).or(prompt_source);
+    let changed_lines = prompt_source.map_or(changed_lines, diff::changed_lines);
+    let narrowed = config.prompt_context_lines.map(|context| {
+        verbose!("prompt diff narrowed to {context} context line(s)");
+        diff::narrow(prompt_source.unwrap_or(&diff), context)
+    });
+    let prompt_source = narrowed.as_deref().or(prompt_source);
+    let oversized = changed_lines > config.max_diff_lines;
+    if oversized && config.large_diff_strategy == LargeDiffStrategy::Skip {
+        let too_large = IdrError::DiffTooLarge {
+            lines: changed_lines,
+            limit: config.max_diff_lines,
+        };
+        return Ok(GenerateReport::gated(
+            Gate::DiffTooLarge,
+            format!("{too_large}, skipping. Split your commit for IDR generation."),
+        ));
+    }
+
+    if !options.no_dedupe
+        && let Some(documented) = sidecar::find_duplicate(
+            &notes::record_dir(config),
+            config.format,
+            &diff,
+            &session_path,
+        )
+    {
+        return Ok(GenerateReport::skipped(format!(
+            "identical change already documented in {}",
+            documented.file_name().unwrap_or_default().to_string_lossy()
+        )));
+    }
+
+    if !options.dry_run
+        && config.backend == Backend::Cli
+        && let Err(reason) = claude::probe(config, config.probe_claude)
+    {
+        return Ok(GenerateReport::skipped(reason));
+    }
+
+    let resume_id = if config.resume_session {
+        session::session_id(&session_path)
+    } else {
+        None
+    };
+
+    let extracted_context = timer
+        .time("context", || context::extract(&session_path))
+        .map(|mut ctx| {
+            ignore.retain_files(&mut ctx.changed_files, git::repo_root().as_deref());
+            ctx
+        });
+    let requests = extracted_context
+        .as_ref()
+        .map_or(&[][..], |ctx| &ctx.user_requests[..]);
+    let second_language = language::split(&config.language).1.map(str::to_string);
+    let detected = language::resolve(config, requests);
+    let config = detected.as_ref().unwrap_or(config);
+    let second_language = second_language.filter(|second| *second != config.language);
+    let authorship = extracted_context.as_ref().map(|ctx| {
+        Authorship::compare(
+            &changed_paths,
+            &ctx.changed_files,
+            git::repo_root().as_deref(),
+        )
+    });
+    let effort = extracted_context
+        .as_ref()
+        .map(|ctx| ctx.effort.clone())
+        .filter(|effort| !effort.is_empty());
+    if let Some(authorship) = &authorship {
+        let list = |files: &[String]| match files {
+            [] => "none".to_string(),
+            files => files.join(", "),
+        };
+        verbose!(
+            "files edited with Claude: {}; manually: {}",
+            list(&authorship.claude),
+            list(&authorship.manual)
+        );
+    }
+    let redactor = redact::Redactor::new(config, !options.no_redact);
+    let anonymizer = Anonymizer::for_repo(config);
+    let mut secrets = redact::Counts::default();
+    let session_context = extracted_context.as_ref().map(|ctx| {
+        let rendered = anonymizer.text(&ctx.render_within(config.context_max_chars));
+        redactor.text(&rendered, &mut secrets)
+    });
+    let background = prompt::Background {
+        session_context: session_context
+            .clone()
+            .filter(|_| config.include_context_in_idr),
+        previous_idrs: config
+            .continuity
+            .then(|| {
+                history::recent(
+                    &path::resolve(config),
+                    config.format,
+                    config.continuity_count,
+                )
+            })
+            .filter(|previous| !previous.is_empty())
+            .map(|previous| history::render(&previous, PREVIOUS_IDRS_MAX_CHARS)),
+        authorship: authorship.clone().filter(|a| !a.manual.is_empty()),
+        file_history: config
+            .history_context
+            .then(|| file_history(&diff, commit.is_some()))
+            .filter(|history| !history.is_empty())
+            .map(|history| redactor.text(&anonymizer.text(&history), &mut secrets)),
+    };
+    if options.split_by_file {
+        let change = Change {
+            diff: &diff,
+            filtered: filtered.as_deref(),
+            sanitized: sanitized.as_ref(),
+            denoised: denoised.as_ref(),
+            prompt_source,
+            stat: &stat,
+            session_path: &session_path,
+            commit,
+            context: extracted_context.as_ref(),
+            authorship: authorship.as_ref(),
+            session_context: session_context.as_deref(),
+            background: &background,
+            redactor: &redactor,
+        };
+        return split_by_file(config, options, &change, secrets, &timer, &progress);
+    }
+    // The stat, the sidecar hash, and deduplication keep the original.
+    let prompt_diff = redactor.diff(prompt_source.unwrap_or(&diff), &mut secrets);
+    if let Some(summary) = secrets.summary() {
+        progress(&summary);
+    }
+    if let Some(note) = sanitized.as_ref().and_then(sanitize::Sanitized::note) {
+        progress(&note);
+    }
+    if let Some(denoised) = &denoised {
+        progress(&format!("dropped noise: {}", denoised.elided.summary()));
+    }
+
+    // Over-limit diffs never reach a single prompt; see `summarize::run`.
+    let (idr_prompt, trim_notes) = if oversized {
+        (None, Vec::new())
+    } else {
+        let (prompt, notes) =
+            prompt::build_idr_prompt_within(&prompt_diff, &stat, &background, config);
+        (Some(prompt), notes)
+    };
+    for note in &trim_notes {
+        verbose!("diff trimmed to fit max_prompt_tokens: {note}");
+    }
+
+    if options.dry_run {
+        match &resume_id {
+            Some(id) => progress(&format!("dry-run mode (resuming session {id})")),
+            None => progress("dry-run mode"),
+        }
+        let prompts = match idr_prompt {
+            Some(idr_prompt) => vec![(
+                format!("IDR prompt ({} chars)", idr_prompt.len()),
+                idr_prompt,
+            )],
+            None => {
+                let chunks = summarize::chunk_prompts(&prompt_diff, config);
+                let total = chunks.len();
+                chunks
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, (label, chunk_prompt))| {
+                        (
+                            format!("chunk {}/{total} prompt: {label}", i + 1),
+                            chunk_prompt,
+                        )
+                    })
+                    .collect()
+            }
+        };
+        return Ok(GenerateReport {
+            status: "dry_run",
+            prompts,
+            ..GenerateReport::default()
+        });
+    }
+
+    progress("generating IDR...");
+    let started = path::now_iso8601();
+    let purpose_prompt = session_context
+        .as_deref()
+        .map(|ctx| prompt::build_purpose_prompt(ctx, config));
+    let (purpose_result, idr_result) = run_claude_calls(
+        config,
+        &timer,
+        purpose_prompt.as_deref(),
+        || match &idr_prompt {
+            Some(idr_prompt) => validate::run(idr_prompt, config, changed_lines > 0, |prompt| {
+                claude::run_with_session(prompt, config, resume_id.as_deref())
+            }),
+            None => summarize::run(&prompt_diff, &stat, &background, config),
+        },
+    );
+    let purpose_result =
+        purpose_result.and_then(|r| r.inspect_err(|e| progress(&e.to_string())).ok());
+    let (idr_result, idr_error) = match idr_result {
+        Ok(result) => (Some(result), None),
+        Err(e) => (None, Some(e)),
+    };
+    let failure = idr_error.as_ref().map(IdrError::to_string);
+    log_usage("purpose", purpose_result.as_ref());
+    log_usage("IDR", idr_result.as_ref());
+    let (change_type, idr_result) = match idr_result {
+        Some(mut result) => {
+            let (body, change_type) =
+                prompt::split_change_type(&validate::strip_outer_fence(&result.text));
+            result.text = body;
+            (change_type, Some(result))
+        }
+        None => (prompt::DEFAULT_CHANGE_TYPE, None),
+    };
+    let idr_result = match idr_result {
+        Some(draft) if config.refine => Some(refine(
+            draft,
+            &stat,
+            changed_lines > 0,
+            config,
+            &timer,
+            &progress,
+        )),
+        other => other,
+    };
+
+    let results = [purpose_result.as_ref(), idr_result.as_ref()];
+    let mut usage = results
+        .iter()
+        .flatten()
+        .filter_map(|r| r.usage)
+        .reduce(|a, b| a + b);
+    let mut cost_usd = results
+        .iter()
+        .flatten()
+        .filter_map(|r| r.cost_usd)
+        .reduce(|a, b| a + b);
+
+    let model = idr_result.as_ref().map(|r| r.model.clone());
+    let purpose_model = purpose_result.as_ref().map(|r| r.model.clone());
+    let chosen = choose_title(
+        purpose_result.as_ref(),
+        extracted_context.as_ref(),
+        commit.as_ref(),
+        config,
+    );
+    let extracted = chosen.purpose().map(str::to_string);
+    let (idr_content, commit_message) = match idr_result.map(|r| r.text) {
+        Some(text) if config.suggest_commit_message => prompt::split_commit_message(&text),
+        Some(text) => (text, None),
+        None => (prompt::failure_body(config, &stat), None),
+    };
+    let mut idr_content = if config.obsidian_mode {
+        obsidian::wikilink_headings(&idr_content)
+    } else {
+        idr_content
+    };
+    let mut translation = None;
+    let mut translation_failure = None;
+    if let Some(target) = second_language.as_deref().filter(|_| failure.is_none()) {
+        progress(&format!("translating IDR to {target}..."));
+        match timer.time("translate", || {
+            translate::run(&chosen.text, &idr_content, target, config)
+        }) {
+            Ok(translated) => {
+                log_usage("translation", Some(&translated.result));
+                usage = [usage, translated.result.usage]
+                    .into_iter()
+                    .flatten()
+                    .reduce(|a, b| a + b);
+                cost_usd = [cost_usd, translated.result.cost_usd]
+                    .into_iter()
+                    .flatten()
+                    .reduce(|a, b| a + b);
+                if translate::separate_file(config) {
+                    translation = Some((target, translated));
+                } else {
+                    idr_content = translate::sections(
+                        &config.language,
+                        &idr_content,
+                        target,
+                        &translated.body,
+                    );
+                }
+            }
+            Err(e) => {
+                progress(&format!(
+                    "warning: translation to {target} failed ({e}), keeping the {} record",
+                    config.language
+                ));
+                translation_failure = Some(format!("> translation to {target} failed: {e}"));
+            }
+        }
+    }
+    // Earlier records that changed the same files, linked from this one.
+    let siblings = if crosslink::enabled(config) {
+        crosslink::siblings(&notes::record_dir(config), config)
+    } else {
+        Vec::new()
+    };
+    let changed_files: BTreeSet<String> = changed_paths.iter().map(|p| p.path.clone()).collect();
+    let related_records = crosslink::rank(&changed_files, &siblings);
+    let related_section = crosslink::section(&related_records, config);
+    if !related_section.is_empty() {
+        idr_content = format!("{}\n\n{related_section}", idr_content.trim_end());
+    }
+
+    let title = chosen.text.clone();
+    let branch = git::current_branch();
+    let (issues, related) = find_issues(branch.as_deref(), extracted_context.as_ref(), config);
+
+    let mut footer_lines = Vec::new();
+    if let Some(commit) = &commit {
+        footer_lines.push(backfill::commit_line(&commit.sha));
+    }
+    if oversized {
+        footer_lines.push(format!(
+            "> per-hunk diffs omitted due to size ({changed_lines} lines > {} limit); written from per-file summaries",
+            config.max_diff_lines
+        ));
+    }
+    footer_lines.extend(preparation_notes(
+        filtered.is_some(),
+        sanitized.as_ref(),
+        denoised.as_ref(),
+    ));
+    if !trim_notes.is_empty() {
+        footer_lines.push(format!(
+            "> diff trimmed to fit max_prompt_tokens: {}",
+            trim_notes.join("; ")
+        ));
+    }
+    footer_lines.extend(fallback_note(model.as_deref(), config));
+    footer_lines.extend(translation_failure);
+    footer_lines.extend(usage_note(usage, cost_usd, config));
+    let replay_footer = (!footer_lines.is_empty()).then(|| footer_lines.join("\n"));
+    if let Some(reason) = &failure {
+        footer_lines.insert(
+            usize::from(commit.is_some()),
+            regenerate::footer_line(reason),
+        );
+    }
+    let footer = (!footer_lines.is_empty()).then(|| footer_lines.join("\n"));
+    let metadata = provenance(
+        model.as_deref().unwrap_or(&config.model),
+        purpose_model.as_deref(),
+        Some(&session_path),
+        branch.as_deref(),
+        commit.as_ref(),
+        effort.as_ref(),
+    );
+    let tags = tags(change_type, config);
+    let front_matter = front_matter(&title, &metadata, branch.as_deref(), &stat, &tags, config);
+    let sidecar = SidecarBase {
+        diff_source: diff_source(commit.as_ref()),
+        session_path: &session_path,
+        metadata: &metadata,
+        purpose_model,
+        started,
+        usage,
+        issues,
+        title_source: chosen.source.as_str(),
+        authorship: authorship.as_ref(),
+        effort: effort.as_ref(),
+    }
+    .sidecar(&diff, Some(change_type), commit, failure);
+    let record = path::Record {
+        title: &title,
+        content: &idr_content,
+        stat: Some(&stat),
+        footer: footer.as_deref(),
+        related: related.as_deref(),
+        tags: &tags,
+        metadata: Some(&metadata),
+        front_matter: front_matter.as_ref(),
+        sidecar: Some(&sidecar),
+    };
+    // A failed IDR call leaves a debug bundle, whatever on_failure does.
+    let bundle = idr_error
+        .as_ref()
+        .zip(idr_prompt.as_deref())
+        .and_then(|(error, prompt)| {
+            let target = failures::Target {
+                record_dir: notes::record_dir(config),
+                title: title.clone(),
+                purpose: extracted.clone(),
+                stat: stat.clone(),
+                footer: replay_footer.clone(),
+                related: related.clone(),
+                branch: branch.clone(),
+                sidecar: sidecar.clone(),
+                record: None,
+            };
+            let dir = failures::save_warn(
+                &failures::Bundle::new(prompt, error, config, target),
+                config,
+            )?;
+            progress(&format!(
+                "debug bundle saved to {}; replay it with `claude-idr retry-last`",
+                dir.display()
+            ));
+            Some(dir)
+        });
+    if let Some(error) = idr_error
+        && let Some(report) = on_failure(error, config, &progress)?
+    {
+        return Ok(report);
+    }
+    let slug_source = slug_source(extracted.as_deref(), change_type, config);
+    let output_file = timer.time("write", || {
+        write_record(
+            &notes::record_dir(config),
+            &record,
+            slug_source.as_deref(),
+            config,
+        )
+    })?;
+    if let Some(dir) = &bundle {
+        failures::record_written(dir, &output_file);
+    }
+    if let Some((target, translated)) = &translation {
+        match translate::write(&output_file, &title, target, translated) {
+            Ok(written) => progress(&format!("translation written: {}", written.display())),
+            Err(e) => warn!(
+                "cannot write the {target} translation of {}: {e}",
+                output_file.display()
+            ),
+        }
+    }
+    if let (Some(closest), Some(name)) = (related_records.first(), output_file.file_name()) {
+        crosslink::add_backlink_warn(
+            &output_file.with_file_name(&closest.file_name),
+            &name.to_string_lossy(),
+            &title,
+            config,
+        );
+    }
+    progress(&format!("IDR generated: {}", output_file.display()));
+
+    Ok(GenerateReport {
+        status: "generated",
+        output: Some(output_file),
+        usage,
+        cost_usd,
+        model,
+        commit_message,
+        change_type: Some(change_type),
+        timing: Some(timer.finish()),
+        ..GenerateReport::default()
+    })
+}
+
+/// Applies `on_failure` to a body claude could not write: `Ok(None)` to
+/// write the placeholder, a skip report to write nothing, or the error
+/// itself to fail the run.
+fn on_failure(
+    error: IdrError,
+    config: &Config,
+    progress: &dyn Fn(&str),
+) -> Result<Option<GenerateReport>, IdrError> {
+    match config.on_failure {
+        OnFailure::Placeholder => {
+            progress(&error.to_string());
+            Ok(None)
+        }
+        OnFailure::Skip => Ok(Some(GenerateReport::skipped(format!(
+            "{error}; no IDR written (on_failure: skip)"
+        )))),
+        OnFailure::Fail => Err(error),
+    }
+}
+
+/// The recent commits of the files `diff` changes, before the change:
+/// from HEAD for a staged diff, from HEAD's parent when the diff is HEAD's
+/// own. A renamed file's history is looked up under its old path.
+fn file_history(diff: &str, committed: bool) -> String {
+    let rev = if committed { "HEAD^" } else { "HEAD" };
+    let files: Vec<prompt::FileHistory> = diff::parse(diff)
+        .iter()
+        .take(FILE_HISTORY_MAX_FILES)
+        .map(|file| {
+            let new = file.is_new();
+            let path = file.renamed_from().unwrap_or(&file.path);
+            prompt::FileHistory {
+                path: file.path.clone(),
+                commits: if new {
+                    Vec::new()
+                } else {
+                    git::file_log(rev, path, FILE_HISTORY_COMMITS)
+                },
+                new,
+            }
+        })
+        .collect();
+    prompt::render_file_history(&files, FILE_HISTORY_MAX_CHARS)
+}
+
+/// Issue ids named by the branch and the session's requests, and the
+/// `Related:` line that links them.
+fn find_issues(
+    branch: Option<&str>,
+    context: Option<&context::SessionContext>,
+    config: &Config,
+) -> (Vec<String>, Option<String>) {
+    let sources: Vec<&str> = branch
+        .into_iter()
+        .chain(
+            context
+                .iter()
+                .flat_map(|ctx| ctx.user_requests.iter().map(String::as_str)),
+        )
+        .collect();
+    let issues = issues::collect(config, &sources);
+    let related = issues::related(&issues, config.issue_url_template.as_deref());
+    (issues, related)
+}
+
+/// Footer lines on what was done to the diff before claude saw it.
+fn preparation_notes(
+    filtered: bool,
+    sanitized: Option<&sanitize::Sanitized>,
+    denoised: Option<&noise::Stripped>,
+) -> Vec<String> {
+    let mut lines = Vec::new();
+    if filtered {
+        lines.push(diff_filter::FOOTER.to_string());
+    }
+    if let Some(note) = sanitized.and_then(sanitize::Sanitized::note) {
+        lines.push(format!("> {note}"));
+    }
+    if let Some(denoised) = denoised {
+        lines.push(denoised.elided.footer());
+    }
+    lines
+}
+
+/// The footer line naming the fallback model, if one wrote the record.
+fn fallback_note(model: Option<&str>, config: &Config) -> Option<String> {
+    model
+        .filter(|m| *m != config.model)
+        .map(|m| format!("> model: {m} (fallback from {})", config.model))
+}
+
+/// The footer line with the run's usage, with `usage_in_footer`.
+fn usage_note(usage: Option<Usage>, cost_usd: Option<f64>, config: &Config) -> Option<String> {
+    usage
+        .filter(|_| config.usage_in_footer)
+        .map(|u| format!("> {}", format_usage(u, cost_usd)))
+}
+
+fn diff_source(commit: Option<&sidecar::CommitRef>) -> sidecar::DiffSource {
+    match commit {
+        Some(commit) => sidecar::DiffSource::Commit {
+            sha: commit.sha.clone(),
+        },
+        None => sidecar::DiffSource::Staged,
+    }
+}
+
+/// What the sidecars of one run's records have in common.
+struct SidecarBase<'a> {
+    diff_source: sidecar::DiffSource,
+    session_path: &'a Path,
+    metadata: &'a metadata::Metadata,
+    purpose_model: Option<String>,
+    started: String,
+    usage: Option<Usage>,
+    issues: Vec<String>,
+    title_source: &'a str,
+    authorship: Option<&'a Authorship>,
+    effort: Option<&'a Effort>,
+}
+
+impl SidecarBase<'_> {
+    /// The sidecar of a record written from `diff`. `commit` goes only on
+    /// the record that stands for the whole commit.
+    fn sidecar(
+        &self,
+        diff: &str,
+        change_type: Option<&str>,
+        commit: Option<sidecar::CommitRef>,
+        failure: Option<String>,
+    ) -> sidecar::Sidecar {
+        sidecar::Sidecar {
+            schema: sidecar::SCHEMA_VERSION,
+            diff_source: self.diff_source.clone(),
+            diff_sha256: sidecar::diff_hash(diff),
+            session_path: Some(self.session_path.to_path_buf()),
+            session_id: self.metadata.session.clone(),
+            purpose_model: self.purpose_model.clone(),
+            body_model: self.metadata.model.clone(),
+            started: self.started.clone(),
+            generated: self.metadata.generated.clone(),
+            usage: self.usage,
+            issues: self.issues.clone(),
+            change_type: change_type.map(str::to_string),
+            commit,
+            failure,
+            title_source: Some(self.title_source.to_string()),
+            authorship: self.authorship.cloned(),
+            effort: self.effort.cloned(),
+        }
+    }
+}
+
+/// [`metadata::Metadata::new`] with where the record came from: the
+/// session, its project directory and effort, the purpose model, and the
+/// branch and commit.
+fn provenance(
+    model: &str,
+    purpose_model: Option<&str>,
+    session_path: Option<&Path>,
+    branch: Option<&str>,
+    commit: Option<&sidecar::CommitRef>,
+    effort: Option<&Effort>,
+) -> metadata::Metadata {
+    let project = session_path
+        .and_then(Path::parent)
+        .and_then(Path::file_name)
+        .map(|name| name.to_string_lossy().into_owned());
+    metadata::Metadata {
+        purpose_model: purpose_model.map(str::to_string),
+        project,
+        branch: branch.map(str::to_string),
+        commit: commit.map(|c| c.sha.chars().take(7).collect()),
+        effort: effort.map(Effort::render),
+        ..metadata::Metadata::new(model, session_path.and_then(session::session_id))
+    }
+}
+
+/// [`title::choose_title`] over what a run has at hand.
+fn choose_title(
+    purpose: Option<&ClaudeResult>,
+    context: Option<&SessionContext>,
+    commit: Option<&sidecar::CommitRef>,
+    config: &Config,
+) -> Title {
+    let inputs = TitleInputs {
+        purpose: purpose.map(|r| r.text.as_str()),
+        summary: context.and_then(|ctx| ctx.summary.as_deref()),
+        requests: context.map_or(&[], |ctx| &ctx.user_requests),
+        commit_message: commit.map(|c| c.subject.as_str()),
+    };
+    title::choose_title(&inputs, config)
+}
+
+/// Writes the record a debug bundle was saved for, with `result` as its
+/// body, the way [`generate`] would have (see `failures::replay`).
+pub(crate) fn write_replayed(
+    target: &failures::Target,
+    result: ClaudeResult,
+    config: &Config,
+) -> Result<PathBuf, IdrError> {
+    let (body, change_type) = prompt::split_change_type(&validate::strip_outer_fence(&result.text));
+    let body = if config.suggest_commit_message {
+        prompt::split_commit_message(&body).0
+    } else {
+        body
+    };
+    let body = if config.obsidian_mode {
+        obsidian::wikilink_headings(&body)
+    } else {
+        body
+    };
+    let metadata = provenance(
+        &result.model,
+        target.sidecar.purpose_model.as_deref(),
+        target.sidecar.session_path.as_deref(),
+        target.branch.as_deref(),
+        target.sidecar.commit.as_ref(),
+        target.sidecar.effort.as_ref(),
+    );
+    let tags = tags(change_type, config);
+    let front_matter = front_matter(
+        &target.title,
+        &metadata,
+        target.branch.as_deref(),
+        &target.stat,
+        &tags,
+        config,
+    );
+    let sidecar = sidecar::Sidecar {
+        body_model: metadata.model.clone(),
+        generated: metadata.generated.clone(),
+        usage: result.usage,
+        change_type: Some(change_type.to_string()),
+        failure: None,
+        ..target.sidecar.clone()
+    };
+    let record = path::Record {
+        title: &target.title,
+        content: &body,
+        stat: Some(&target.stat),
+        footer: target.footer.as_deref(),
+        related: target.related.as_deref(),
+        tags: &tags,
+        metadata: Some(&metadata),
+        front_matter: front_matter.as_ref(),
+        sidecar: Some(&sidecar),
+    };
+    let slug_source = slug_source(target.purpose.as_deref(), change_type, config);
+    write_record(&target.record_dir, &record, slug_source.as_deref(), config)
+}
+
+/// The format's tag and the change type.
+fn tags(change_type: &str, config: &Config) -> Vec<String> {
+    vec![
+        match config.format {
+            config::Format::Idr => "idr".to_string(),
+            config::Format::Madr => "adr".to_string(),
+        },
+        change_type.to_string(),
+    ]
+}
+
+fn front_matter(
+    title: &str,
+    metadata: &metadata::Metadata,
+    branch: Option<&str>,
+    stat: &str,
+    tags: &[String],
+    config: &Config,
+) -> Option<front_matter::FrontMatter> {
+    config.front_matter.then(|| {
+        let (files_changed, insertions, deletions) = git::stat_totals(stat);
+        front_matter::FrontMatter {
+            title: title.to_string(),
+            date: metadata.generated.clone(),
+            model: metadata.model.clone(),
+            session_id: metadata.session.clone(),
+            branch: branch.map(str::to_string),
+            files_changed,
+            insertions,
+            deletions,
+            tags: tags.to_vec(),
+        }
+    })
+}
+
+/// What the file name is made from: the purpose, led by the change type
+/// with `classify_in_filename`.
+fn slug_source(purpose: Option<&str>, change_type: &str, config: &Config) -> Option<String> {
+    match purpose {
+        Some(purpose) if config.classify_in_filename => Some(format!("{change_type} {purpose}")),
+        None if config.classify_in_filename => Some(change_type.to_string()),
+        other => other.map(str::to_string),
+    }
+}
+
+/// Writes `record` to `dir` under the next free number, or appends it to
+/// the month's journal, and refreshes what lists it.
+fn write_record(
+    dir: &Path,
+    record: &path::Record,
+    slug_source: Option<&str>,
+    config: &Config,
+) -> Result<PathBuf, IdrError> {
+    let _lock = lock::acquire_warn(dir);
+    let written = match config.output_mode {
+        config::OutputMode::Files => path::allocate_and_write(dir, slug_source, record, config),
+        config::OutputMode::Monthly => monthly::append(dir, record).map(|(path, _)| path),
+    };
+    let output_file = written.map_err(|source| IdrError::Io {
+        path: dir.to_path_buf(),
+        source,
+    })?;
+    refresh_listings(&[(&output_file, record.title)], config);
+    Ok(output_file)
+}
+
+/// Brings the index, `latest.md`, and the documentation site up to date
+/// with records just written to one directory; the last becomes latest.
+fn refresh_listings(written: &[(&Path, &str)], config: &Config) {
+    let Some((last, _)) = written.last() else {
+        return;
+    };
+    let Some(dir) = last.parent() else {
+        return;
+    };
+    if notes::replaces_files(config) {
+        return;
+    }
+    if config.output_mode == config::OutputMode::Files {
+        index::refresh_warn(dir, config.format);
+    }
+    latest::update_warn(dir, last, config.latest_mode);
+    if let Some(integration) = config.publish_integration {
+        for (file, title) in written {
+            docsite::update_warn(file, title, integration, config.format);
+        }
+    }
+}
+
+/// What [`split_by_file`] documents, as [`generate`] found it.
+struct Change<'a> {
+    diff: &'a str,
+    /// `diff_filter_command`'s output, which prompts see in place of `diff`.
+    filtered: Option<&'a str>,
+    /// Files left out of prompts as binary-like, and stray bytes removed.
+    sanitized: Option<&'a sanitize::Sanitized>,
+    /// The filtered diff without its noise hunks, when any were dropped.
+    denoised: Option<&'a noise::Stripped>,
+    /// What prompts see in place of `diff`: filtered, without noise, and
+    /// cut to `prompt_context_lines`.
+    prompt_source: Option<&'a str>,
+    stat: &'a str,
+    session_path: &'a Path,
+    commit: Option<sidecar::CommitRef>,
+    context: Option<&'a context::SessionContext>,
+    authorship: Option<&'a Authorship>,
+    /// Rendered and redacted.
+    session_context: Option<&'a str>,
+    background: &'a prompt::Background,
+    redactor: &'a redact::Redactor,
+}
+
+/// `--split-by-file`: one IDR call per changed file, at most
+/// `split_concurrency` at a time, each written to `idr-NN-<file>.md` under
+/// a shared header, then an umbrella record linking them. A file whose
+/// call fails gets the fallback body; the others are written regardless.
+/// The umbrella takes the first number of the block and carries the
+/// sidecar for the whole diff, so deduplication sees the change as one.
+fn split_by_file(
+    config: &Config,
+    options: &GenerateOptions,
+    change: &Change,
+    mut secrets: redact::Counts,
+    timer: &Timer,
+    progress: &dyn Fn(&str),
+) -> Result<GenerateReport, IdrError> {
+    let parts = split::plan(change.prompt_source.unwrap_or(change.diff));
+    let prompts: Vec<String> = parts
+        .iter()
+        .map(|part| {
+            let diff = change.redactor.diff(&part.diff, &mut secrets);
+            prompt::build_idr_prompt_within(&diff, &part.stat, change.background, config).0
+        })
+        .collect();
+    let total = parts.len();
+    if let Some(summary) = secrets.summary() {
+        progress(&summary);
+    }
+    if let Some(note) = change.sanitized.and_then(sanitize::Sanitized::note) {
+        progress(&note);
+    }
+    if let Some(denoised) = change.denoised {
+        progress(&format!("dropped noise: {}", denoised.elided.summary()));
+    }
+
+    if options.dry_run {
+        progress("dry-run mode");
+        return Ok(GenerateReport {
+            status: "dry_run",
+            prompts: parts
+                .iter()
+                .zip(prompts)
+                .enumerate()
+                .map(|(i, (part, prompt))| {
+                    (
+                        format!("file {}/{total} prompt: {}", i + 1, part.path),
+                        prompt,
+                    )
+                })
+                .collect(),
+            ..GenerateReport::default()
+        });
+    }
+
+    progress(&format!("generating {total} IDRs, one per file..."));
+    let started = path::now_iso8601();
+    let purpose_prompt = change
+        .session_context
+        .map(|ctx| prompt::build_purpose_prompt(ctx, config));
+    let jobs: Vec<(&split::Part, &String)> = parts.iter().zip(&prompts).collect();
+    let (purpose_result, part_results) =
+        run_claude_calls(config, timer, purpose_prompt.as_deref(), || {
+            split::map_limited(&jobs, config.split_concurrency, |(part, prompt)| {
+                let hunks = diff::changed_lines(&part.diff) > 0;
+                validate::run(prompt, config, hunks, |prompt| claude::run(prompt, config))
+                    .inspect_err(|e| warn!("IDR for {} failed: {e}", part.path))
+                    .ok()
+            })
+        });
+    let purpose_result =
+        purpose_result.and_then(|r| r.inspect_err(|e| progress(&e.to_string())).ok());
+    log_usage("purpose", purpose_result.as_ref());
+    for (part, result) in parts.iter().zip(&part_results) {
+        log_usage(&part.path, result.as_ref());
+    }
+
+    let results: Vec<&ClaudeResult> = purpose_result
+        .iter()
+        .chain(part_results.iter().flatten())
+        .collect();
+    let usage = results.iter().filter_map(|r| r.usage).reduce(|a, b| a + b);
+    let cost_usd = results
+        .iter()
+        .filter_map(|r| r.cost_usd)
+        .reduce(|a, b| a + b);
+    let model = part_results
+        .iter()
+        .flatten()
+        .next()
+        .map(|r| r.model.clone());
+    let purpose_model = purpose_result.as_ref().map(|r| r.model.clone());
+    let chosen = choose_title(
+        purpose_result.as_ref(),
+        change.context,
+        change.commit.as_ref(),
+        config,
+    );
+    let extracted = chosen.purpose().map(str::to_string);
+    let title = chosen.text.clone();
+
+    // (body, change type), or `None` where the file's call failed.
+    let bodies: Vec<Option<(String, &'static str)>> = part_results
+        .into_iter()
+        .map(|result| {
+            let text = validate::strip_outer_fence(&result?.text);
+            let (body, change_type) = prompt::split_change_type(&text);
+            let (body, _) = prompt::split_commit_message(&body);
+            let body = if config.obsidian_mode {
+                obsidian::wikilink_headings(&body)
+            } else {
+                body
+            };
+            Some((body, change_type))
+        })
+        .collect();
+    let failed = bodies.iter().filter(|b| b.is_none()).count();
+    // The type most files have, the earlier in `CHANGE_TYPES` on a tie.
+    let change_type = prompt::CHANGE_TYPES
+        .iter()
+        .rev()
+        .copied()
+        .max_by_key(|t| bodies.iter().flatten().filter(|(_, c)| c == t).count())
+        .filter(|t| bodies.iter().flatten().any(|(_, c)| c == t))
+        .unwrap_or(prompt::DEFAULT_CHANGE_TYPE);
+
+    let branch = git::current_branch();
+    let (issues, related) = find_issues(branch.as_deref(), change.context, config);
+    let effort = change
+        .context
+        .map(|ctx| &ctx.effort)
+        .filter(|effort| !effort.is_empty());
+    let metadata = provenance(
+        model.as_deref().unwrap_or(&config.model),
+        purpose_model.as_deref(),
+        Some(change.session_path),
+        branch.as_deref(),
+        change.commit.as_ref(),
+        effort,
+    );
+    let sidecars = SidecarBase {
+        diff_source: diff_source(change.commit.as_ref()),
+        session_path: change.session_path,
+        metadata: &metadata,
+        purpose_model,
+        started,
+        usage,
+        issues,
+        title_source: chosen.source.as_str(),
+        authorship: change.authorship,
+        effort,
+    };
+
+    let mut footer_lines = Vec::new();
+    if let Some(commit) = &change.commit {
+        footer_lines.push(backfill::commit_line(&commit.sha));
+    }
+    if failed > 0 {
+        footer_lines.push(format!(
+            "> {failed} of {total} file records failed to generate"
+        ));
+    }
+    footer_lines.extend(preparation_notes(
+        change.filtered.is_some(),
+        change.sanitized,
+        change.denoised,
+    ));
+    footer_lines.extend(fallback_note(model.as_deref(), config));
+    footer_lines.extend(usage_note(usage, cost_usd, config));
+    let footer = (!footer_lines.is_empty()).then(|| footer_lines.join("\n"));
+
+    let dir = notes::record_dir(config);
+    let io_error = |source| IdrError::Io {
+        path: dir.clone(),
+        source,
+    };
+    let umbrella = timer.time("write", || -> Result<PathBuf, IdrError> {
+        let _lock = lock::acquire_warn(&dir);
+        let umbrella_slug = match &extracted {
+            _ if config.format == config::Format::Idr => None,
+            Some(purpose) => Some(purpose.as_str()),
+            None => None,
+        };
+        let slugs: Vec<Option<&str>> = std::iter::once(umbrella_slug)
+            .chain(parts.iter().map(|p| Some(p.path.as_str())))
+            .collect();
+        let reserved = path::claim_block(&dir, &slugs, config).map_err(io_error)?;
+        let claimed = &reserved.paths;
+        let name = |p: &Path| {
+            p.file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned()
+        };
+        let umbrella_name = name(&claimed[0]);
+        let part_titles: Vec<String> = parts
+            .iter()
+            .map(|p| format!("{title}: {}", p.path))
+            .collect();
+
+        let mut written: Vec<(&Path, &str)> = Vec::new();
+        for (i, ((part, body), file)) in parts.iter().zip(&bodies).zip(&claimed[1..]).enumerate() {
+            let (body, part_type) = match body {
+                Some((body, part_type)) => (body.clone(), *part_type),
+                None => (
+                    prompt::failure_body(config, &part.stat),
+                    prompt::DEFAULT_CHANGE_TYPE,
+                ),
+            };
+            let content = format!(
+                "{}\n\n{body}",
+                split::header(i + 1, total, &umbrella_name, &title, change.stat)
+            );
+            let tags = tags(part_type, config);
+            let front_matter = front_matter(
+                &part_titles[i],
+                &metadata,
+                branch.as_deref(),
+                &part.stat,
+                &tags,
+                config,
+            );
+            let sidecar = sidecars.sidecar(&part.diff, Some(part_type), None, None);
+            let record = path::Record {
+                title: &part_titles[i],
+                content: &content,
+                stat: Some(&part.stat),
+                footer: None,
+                related: related.as_deref(),
+                tags: &tags,
+                metadata: Some(&metadata),
+                front_matter: front_matter.as_ref(),
+                sidecar: Some(&sidecar),
+            };
+            if let Err(e) = path::write_claimed(file, &record, config) {
+                for unwritten in claimed.iter().skip(i + 1) {
+                    let _ = std::fs::remove_file(unwritten);
+                }
+                let _ = std::fs::remove_file(&claimed[0]);
+                return Err(io_error(e));
+            }
+            written.push((file, &part_titles[i]));
+        }
+
+        let names: Vec<String> = claimed[1..].iter().map(|p| name(p)).collect();
+        let entries: Vec<split::Entry> = parts
+            .iter()
+            .zip(&names)
+            .zip(&bodies)
+            .map(|((part, name), body)| split::Entry {
+                path: &part.path,
+                name,
+                failed: body.is_none(),
+            })
+            .collect();
+        let content = split::umbrella_content(&entries, config);
+        let tags = tags(change_type, config);
+        let front_matter = front_matter(
+            &title,
+            &metadata,
+            branch.as_deref(),
+            change.stat,
+            &tags,
+            config,
+        );
+        let sidecar = sidecars.sidecar(change.diff, Some(change_type), change.commit.clone(), None);
+        let record = path::Record {
+            title: &title,
+            content: &content,
+            stat: Some(change.stat),
+            footer: footer.as_deref(),
+            related: related.as_deref(),
+            tags: &tags,
+            metadata: Some(&metadata),
+            front_matter: front_matter.as_ref(),
+            sidecar: Some(&sidecar),
+        };
+        path::write_claimed(&claimed[0], &record, config).map_err(io_error)?;
+        written.push((&claimed[0], &title));
+        refresh_listings(&written, config);
+        Ok(claimed[0].clone())
+    })?;
+    progress(&format!(
+        "{} IDRs generated: {}",
+        total + 1,
+        umbrella.display()
+    ));
+
+    Ok(GenerateReport {
+        status: "generated",
+        output: Some(umbrella),
+        usage,
+        cost_usd,
+        model,
+        change_type: Some(change_type),
+        timing: Some(timer.finish()),
+        ..GenerateReport::default()
+    })
+}
+
+/// `--from-session`: a record of what a session investigated and
+/// concluded, for work that ended without a change worth committing. The
+/// staged diff is never read, the session need not have edited anything,
+/// and the record carries [`path::SESSION_REPORT_MARKER`] instead of a
+/// diff stat.
+fn session_report(
+    config: &Config,
+    options: &GenerateOptions,
+    progress: &dyn Fn(&str),
+) -> Result<GenerateReport, IdrError> {
+    let timer = Timer::new();
+    let found = timer.time("scan", || {
+        given_session(options.session).unwrap_or_else(|| session::find_recent(config))
+    });
+    let session_path = match found {
+        Ok(p) => p,
+        Err(e) => return Ok(GenerateReport::gated(Gate::NoSession, e.to_string())),
+    };
+    let Some(extracted) = timer.time("context", || context::extract(&session_path)) else {
+        return Ok(GenerateReport::skipped(format!(
+            "nothing to report in session {}",
+            session_path.display()
+        )));
+    };
+    let detected = language::resolve(config, &extracted.user_requests);
+    let config = detected.as_ref().unwrap_or(config);
+    let redactor = redact::Redactor::new(config, !options.no_redact);
+    let anonymizer = Anonymizer::for_repo(config);
+    let mut secrets = redact::Counts::default();
+    let report_prompt = prompt::build_session_report_prompt(
+        &redactor.text(
+            &anonymizer.text(&extracted.render_report(config.context_max_chars)),
+            &mut secrets,
+        ),
+        config,
+    );
+    let purpose_context = redactor.text(
+        &anonymizer.text(&extracted.render_within(config.context_max_chars)),
+        &mut redact::Counts::default(),
+    );
+    if let Some(summary) = secrets.summary() {
+        progress(&summary);
+    }
+
+    if options.dry_run {
+        progress("dry-run mode");
+        return Ok(GenerateReport {
+            status: "dry_run",
+            prompts: vec![(
+                format!("session report prompt ({} chars)", report_prompt.len()),
+                report_prompt,
+            )],
+            ..GenerateReport::default()
+        });
+    }
+    if config.backend == Backend::Cli
+        && let Err(reason) = claude::probe(config, config.probe_claude)
+    {
+        return Ok(GenerateReport::skipped(reason));
+    }
+
+    progress("generating session report...");
+    let started = path::now_iso8601();
+    let purpose_prompt = prompt::build_purpose_prompt(&purpose_context, config);
+    let (purpose_result, report_result) =
+        run_claude_calls(config, &timer, Some(&purpose_prompt), || {
+            claude::run(&report_prompt, config)
+        });
+    let purpose_result =
+        purpose_result.and_then(|r| r.inspect_err(|e| progress(&e.to_string())).ok());
+    let (report_result, failure) = match report_result {
+        Ok(result) => (Some(result), None),
+        Err(e) => {
+            let reason = e.to_string();
+            if let Some(report) = on_failure(e, config, progress)? {
+                return Ok(report);
+            }
+            (None, Some(reason))
+        }
+    };
+    log_usage("purpose", purpose_result.as_ref());
+    log_usage("session report", report_result.as_ref());
+
+    let results = [purpose_result.as_ref(), report_result.as_ref()];
+    let usage = results
+        .iter()
+        .flatten()
+        .filter_map(|r| r.usage)
+        .reduce(|a, b| a + b);
+    let cost_usd = results
+        .iter()
+        .flatten()
+        .filter_map(|r| r.cost_usd)
+        .reduce(|a, b| a + b);
+    let model = report_result.as_ref().map(|r| r.model.clone());
+    let purpose_model = purpose_result.as_ref().map(|r| r.model.clone());
+    let chosen = choose_title(purpose_result.as_ref(), Some(&extracted), None, config);
+    let extracted_purpose = chosen.purpose().map(str::to_string);
+    let content = match report_result {
+        Some(result) => result.text.trim().to_string(),
+        None => prompt::failure_body(config, ""),
+    };
+    let title = chosen.text.clone();
+
+    let (issues, related) = find_issues(None, Some(&extracted), config);
+    let mut footer_lines = Vec::new();
+    if let Some(reason) = &failure {
+        footer_lines.push(regenerate::footer_line(reason));
+    }
+    footer_lines.extend(fallback_note(model.as_deref(), config));
+    footer_lines.extend(usage_note(usage, cost_usd, config));
+    let footer = (!footer_lines.is_empty()).then(|| footer_lines.join("\n"));
+    let effort = Some(&extracted.effort).filter(|effort| !effort.is_empty());
+    let metadata = provenance(
+        model.as_deref().unwrap_or(&config.model),
+        purpose_model.as_deref(),
+        Some(&session_path),
+        git::current_branch().as_deref(),
+        None,
+        effort,
+    );
+    let tags = tags(SESSION_REPORT_TAG, config);
+    let front_matter = front_matter(&title, &metadata, None, "", &tags, config);
+    let sidecar = SidecarBase {
+        diff_source: sidecar::DiffSource::Session,
+        session_path: &session_path,
+        metadata: &metadata,
+        purpose_model,
+        started,
+        usage,
+        issues,
+        title_source: chosen.source.as_str(),
+        authorship: None,
+        effort,
+    }
+    .sidecar("", None, None, failure);
+    let record = path::Record {
+        title: &title,
+        content: &content,
+        stat: None,
+        footer: footer.as_deref(),
+        related: related.as_deref(),
+        tags: &tags,
+        metadata: Some(&metadata),
+        front_matter: front_matter.as_ref(),
+        sidecar: Some(&sidecar),
+    };
+    // Notes mode keeps records for the next commit; a report has none.
+    let output_file = timer.time("write", || {
+        write_record(
+            &path::resolve(config),
+            &record,
+            extracted_purpose.as_deref(),
+            config,
+        )
+    })?;
+    progress(&format!(
+        "session report written: {}",
+        output_file.display()
+    ));
+
+    Ok(GenerateReport {
+        status: "generated",
+        output: Some(output_file),
+        usage,
+        cost_usd,
+        model,
+        timing: Some(timer.finish()),
+        ..GenerateReport::default()
+    })
+}
+
+/// The transcript named by the caller, if any. It is taken as given: a hook
+/// names the session that just ran, however old its last entry is.
+fn given_session(path: Option<&Path>) -> Option<Result<PathBuf, IdrError>> {
+    let path = path?;
+    Some(if path.is_file() {
+        Ok(path.to_path_buf())
+    } else {
+        Err(IdrError::SessionNotFound(format!(
+            "session transcript {} does not exist",
+            path.display()
+        )))
+    })
+}
+
+/// Runs purpose extraction on a separate thread while the IDR call(s) run on
+/// the current one, so the wall time is the slower of the two, not the sum.
+/// A failure (or panic) in one call never affects the other. The purpose
+/// result is `None` when there was no purpose prompt to send.
+fn run_claude_calls<T>(
+    config: &Config,
+    timer: &Timer,
+    purpose_prompt: Option<&str>,
+    idr_call: impl FnOnce() -> T,
+) -> (Option<Result<ClaudeResult, IdrError>>, T) {
+    thread::scope(|scope| {
+        let purpose = scope.spawn(|| {
+            timer.time("purpose", || {
+                purpose_prompt.map(|prompt| claude::run(prompt, config))
+            })
+        });
+        let idr = timer.time("idr", idr_call);
+        let purpose = purpose.join().unwrap_or_else(|_| {
+            warn!("purpose extraction failed unexpectedly");
+            None
+        });
+        (purpose, idr)
+    })
+}
+
+/// Second pass over a successful draft. Usage and cost of both calls are
+/// summed; if the refinement fails, is not a record, or drops the draft's
+/// commit message, the draft is kept.
+fn refine(
+    draft: ClaudeResult,
+    stat: &str,
+    files_changed: bool,
+    config: &Config,
+    timer: &Timer,
+    progress: &dyn Fn(&str),
+) -> ClaudeResult {
+    progress("refining IDR...");
+    let refine_prompt = prompt::build_refine_prompt(&draft.text, stat, config);
+    let refined = match timer.time("refine", || claude::run(&refine_prompt, config)) {
+        Ok(refined) => refined,
+        Err(e) => {
+            progress(&format!(
+                "warning: refinement failed ({e}), keeping the first draft"
+            ));
+            return draft;
+        }
+    };
+    log_usage("refine", Some(&refined));
+    let text = validate::strip_outer_fence(&refined.text);
+    let problem = validate::problem(&text, config, files_changed).or_else(|| {
+        // The commit message is split off only after refinement, so a
+        // rewrite without the block would lose it.
+        let had_message = prompt::split_commit_message(&draft.text).1.is_some();
+        (had_message && prompt::split_commit_message(&text).1.is_none())
+            .then(|| "the commit message block is missing".to_string())
+    });
+    let text = match problem {
+        Some(problem) => {
+            progress(&format!(
+                "warning: refinement was unusable ({problem}), keeping the first draft"
+            ));
+            draft.text.clone()
+        }
+        None => text,
+    };
+    ClaudeResult {
+        text,
+        usage: [draft.usage, refined.usage]
+            .into_iter()
+            .flatten()
+            .reduce(|a, b| a + b),
+        cost_usd: [draft.cost_usd, refined.cost_usd]
+            .into_iter()
+            .flatten()
+            .reduce(|a, b| a + b),
+        ..draft
+    }
+}
+
+fn log_usage(label: &str, result: Option<&ClaudeResult>) {
+    if let Some(usage) = result.and_then(|r| r.usage) {
+        verbose!(
+            "{label} call used {}",
+            format_usage(usage, result.and_then(|r| r.cost_usd))
+        );
+    }
+}
+
+fn format_usage(usage: Usage, cost_usd: Option<f64>) -> String {
+    let tokens = format!(
+        "tokens: {} in / {} out",
+        usage.input_tokens, usage.output_tokens
+    );
+    match cost_usd {
+        Some(cost) => format!("{tokens}, cost ${cost:.4}"),
+        None => tokens,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(unix)]
+    use crate::testutil::write_script;
+    #[cfg(unix)]
+    use std::cell::RefCell;
+    #[cfg(unix)]
+    use std::time::{Duration, Instant};
+    #[cfg(unix)]
+    use tempfile::TempDir;
+
+    #[cfg(unix)]
+    #[test]
+    fn run_claude_calls_runs_purpose_and_idr_concurrently() {
+        let dir = TempDir::new().unwrap();
+        let script = write_script(
+            dir.path(),
+            "fake-claude",
+            "cat >/dev/null\nsleep 1\necho generated\n",
+        );
+        let purpose_prompt =
+            prompt::build_purpose_prompt("User requests:\n- add retries", &Config::default());
+        let config = Config {
+            claude_bin: script,
+            json_output: false,
+            ..Config::default()
+        };
+
+        let started = Instant::now();
+        let (purpose, idr) =
+            run_claude_calls(&config, &Timer::new(), Some(&purpose_prompt), || {
+                claude::run("idr prompt", &config)
+            });
+
+        assert!(started.elapsed() < Duration::from_millis(1800));
+        assert_eq!(purpose.unwrap().unwrap().text, "generated\n");
+        assert_eq!(idr.unwrap().text, "generated\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_claude_calls_keeps_idr_when_purpose_fails() {
+        let dir = TempDir::new().unwrap();
+        let script = write_script(
+            dir.path(),
+            "fake-claude",
+            "if grep -q 'main purpose'; then exit 1; fi\necho idr body\n",
+        );
+        let purpose_prompt =
+            prompt::build_purpose_prompt("User requests:\n- add retries", &Config::default());
+        let config = Config {
+            claude_bin: script,
+            json_output: false,
+            ..Config::default()
+        };
+
+        let (purpose, idr) =
+            run_claude_calls(&config, &Timer::new(), Some(&purpose_prompt), || {
+                claude::run("idr prompt", &config)
+            });
+
+        assert!(matches!(purpose, Some(Err(IdrError::ClaudeFailed { .. }))));
+        assert_eq!(idr.unwrap().text, "idr body\n");
+    }
+
+    #[cfg(unix)]
+    fn draft() -> ClaudeResult {
+        ClaudeResult {
+            text: "## 変更概要\n\ndraft\n".to_string(),
+            usage: Some(Usage {
+                input_tokens: 100,
+                output_tokens: 10,
+            }),
+            cost_usd: Some(0.01),
+            session_id: None,
+            model: "sonnet".to_string(),
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn refine_replaces_draft_and_sums_usage() {
+        let dir = TempDir::new().unwrap();
+        let script = write_script(
+            dir.path(),
+            "fake-claude",
+            "grep -q '<draft>' || exit 1\nprintf '%s\\n' '{\"type\":\"result\",\"result\":\"## 変更概要\\n\\nrefined\\n\",\"total_cost_usd\":0.02,\"usage\":{\"input_tokens\":50,\"output_tokens\":5}}'\n",
+        );
+        let config = Config {
+            claude_bin: script,
+            ..Config::default()
+        };
+
+        let result = refine(draft(), "stat", false, &config, &Timer::new(), &|_| {});
+
+        assert_eq!(result.text, "## 変更概要\n\nrefined\n");
+        assert_eq!(
+            result.usage,
+            Some(Usage {
+                input_tokens: 150,
+                output_tokens: 15,
+            })
+        );
+        assert_eq!(result.cost_usd, Some(0.03));
+        assert_eq!(result.model, "sonnet");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn refine_keeps_draft_when_second_call_fails() {
+        let dir = TempDir::new().unwrap();
+        let script = write_script(dir.path(), "fake-claude", "cat >/dev/null\nexit 1\n");
+        let config = Config {
+            claude_bin: script,
+            json_output: false,
+            ..Config::default()
+        };
+
+        let result = refine(draft(), "stat", false, &config, &Timer::new(), &|_| {});
+
+        assert_eq!(result.text, "## 変更概要\n\ndraft\n");
+        assert_eq!(result.cost_usd, Some(0.01));
+    }
+
+    #[cfg(unix)]
+    fn refining_claude(dir: &Path, refined: &str) -> PathBuf {
+        let output = serde_json::json!({
+            "type": "result",
+            "result": refined,
+            "total_cost_usd": 0.02,
+        });
+        write_script(
+            dir,
+            "fake-claude",
+            &format!("cat >/dev/null\ncat <<'EOF'\n{output}\nEOF\n"),
+        )
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn refine_strips_a_fence_around_the_refined_record() {
+        let dir = TempDir::new().unwrap();
+        let config = Config {
+            claude_bin: refining_claude(dir.path(), "```markdown\n## 変更概要\n\nrefined\n```\n"),
+            ..Config::default()
+        };
+
+        let result = refine(draft(), "stat", false, &config, &Timer::new(), &|_| {});
+
+        assert_eq!(result.text.trim_end(), "## 変更概要\n\nrefined");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn refine_keeps_draft_when_the_refinement_is_not_a_record() {
+        let dir = TempDir::new().unwrap();
+        let config = Config {
+            claude_bin: refining_claude(dir.path(), "Here is the improved record."),
+            ..Config::default()
+        };
+        let warnings = RefCell::new(Vec::new());
+
+        let result = refine(draft(), "stat", false, &config, &Timer::new(), &|m| {
+            warnings.borrow_mut().push(m.to_string())
+        });
+
+        assert_eq!(result.text, "## 変更概要\n\ndraft\n");
+        assert_eq!(result.cost_usd, Some(0.03));
+        assert!(
+            warnings
+                .borrow()
+                .iter()
+                .any(|m| m.starts_with("warning: refinement was unusable"))
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn refine_keeps_draft_when_the_refinement_drops_the_commit_message() {
+        let dir = TempDir::new().unwrap();
+        let config = Config {
+            claude_bin: refining_claude(dir.path(), "## 変更概要\n\nrefined\n"),
+            suggest_commit_message: true,
+            ..Config::default()
+        };
+        let draft = ClaudeResult {
+            text: "## 変更概要\n\ndraft\n\n```commit-message\nfix: thing\n```\n".to_string(),
+            ..draft()
+        };
+
+        let result = refine(draft, "stat", false, &config, &Timer::new(), &|_| {});
+
+        assert_eq!(
+            prompt::split_commit_message(&result.text).1.as_deref(),
+            Some("fix: thing")
+        );
+    }
+
+    #[test]
+    fn format_usage_includes_cost_when_known() {
+        let usage = Usage {
+            input_tokens: 1830,
+            output_tokens: 412,
+        };
+        assert_eq!(
+            format_usage(usage, Some(0.0421)),
+            "tokens: 1830 in / 412 out, cost $0.0421"
+        );
+        assert_eq!(format_usage(usage, None), "tokens: 1830 in / 412 out");
+    }
+
+    #[test]
+    fn report_serializes_without_empty_fields() {
+        let report = GenerateReport {
+            status: "skipped",
+            reason: Some("no staged changes".to_string()),
+            ..GenerateReport::default()
+        };
+        assert_eq!(
+            serde_json::to_string(&report).unwrap(),
+            r#"{"status":"skipped","reason":"no staged changes"}"#
+        );
+    }
+}
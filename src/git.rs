@@ -1,19 +1,306 @@
-use std::process::Command;
+use crate::error::IdrError;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
 
-pub fn staged_diff() -> Option<String> {
-    run_git(&["diff", "--cached"])
+// The diff readers below leave out `exclude`: paths relative to the
+// repository root, as `.idrignore` selects them from the `*_paths` lists.
+
+pub fn staged_diff(exclude: &[String]) -> Result<String, IdrError> {
+    run_git_excluding(&["diff", "--cached"], exclude)
 }
 
-pub fn staged_stat() -> String {
-    run_git(&["diff", "--cached", "--stat"]).unwrap_or_default()
+pub fn staged_stat(exclude: &[String]) -> String {
+    run_git_excluding(&["diff", "--cached", "--stat"], exclude).unwrap_or_default()
 }
 
-pub fn staged_changed_lines() -> u64 {
-    run_git(&["diff", "--cached", "-M", "--numstat"])
+pub fn staged_changed_lines(exclude: &[String]) -> u64 {
+    run_git_excluding(&["diff", "--cached", "-M", "--numstat"], exclude)
         .map(|s| parse_numstat(&s))
         .unwrap_or(0)
 }
 
+/// Files the staged diff changes, from `--numstat`, renames included.
+pub fn staged_numstat_paths(exclude: &[String]) -> Vec<NumstatPath> {
+    run_git_excluding(&["diff", "--cached", "-M", "--numstat", "-z"], exclude)
+        .map(|s| parse_numstat_paths(&s))
+        .unwrap_or_default()
+}
+
+/// The last `count` commits reachable from `rev` that touched `path`, as
+/// `<short hash> <date> <subject>` lines, newest first. Empty when there
+/// are none, or `rev` names no commit yet.
+pub fn file_log(rev: &str, path: &str, count: usize) -> Vec<String> {
+    let args = file_log_args(rev, path, count);
+    name_only(run_git(
+        &args.iter().map(String::as_str).collect::<Vec<_>>(),
+    ))
+}
+
+fn file_log_args(rev: &str, path: &str, count: usize) -> Vec<String> {
+    [
+        "log",
+        &format!("-n{count}"),
+        "--date=short",
+        "--format=%h %ad %s",
+        rev,
+        "--",
+        &format!(":(top,literal){path}"),
+    ]
+    .map(str::to_string)
+    .into()
+}
+
+/// Paths with staged changes, relative to the repository root.
+pub fn staged_paths() -> Vec<String> {
+    name_only(run_git(&["diff", "--cached", "--name-only"]))
+}
+
+/// The checked-out branch, or `None` on a detached HEAD.
+pub fn current_branch() -> Option<String> {
+    let output = Command::new("git")
+        .args(["symbolic-ref", "--quiet", "--short", "HEAD"])
+        .output()
+        .ok()?;
+    let branch = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !branch.is_empty()).then_some(branch)
+}
+
+/// Top-level directory of the repository the current directory is in.
+pub fn repo_root() -> Option<PathBuf> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--show-toplevel"])
+        .output()
+        .ok()?;
+    let root = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !root.is_empty()).then(|| PathBuf::from(root))
+}
+
+/// Abbreviated hash of HEAD, or `None` before the first commit.
+pub fn short_head() -> Option<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()?;
+    let sha = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !sha.is_empty()).then_some(sha)
+}
+
+/// Full hash and subject of HEAD.
+pub fn head_commit() -> Option<(String, String)> {
+    let out = run_git(&["log", "-1", "--format=%H%x00%s"]).ok()?;
+    let (sha, subject) = out.trim_end_matches('\n').split_once('\0')?;
+    Some((sha.to_string(), subject.to_string()))
+}
+
+/// The patch HEAD introduced against its first parent, formatted like
+/// [`staged_diff`] was before the commit.
+pub fn head_diff(exclude: &[String]) -> Result<String, IdrError> {
+    diff_since(&head_base()?, exclude)
+}
+
+pub fn head_stat(exclude: &[String]) -> String {
+    head_base()
+        .map(|base| stat_since(&base, exclude))
+        .unwrap_or_default()
+}
+
+pub fn head_changed_lines(exclude: &[String]) -> u64 {
+    head_base()
+        .map(|base| changed_lines_since(&base, exclude))
+        .unwrap_or(0)
+}
+
+pub fn head_numstat_paths(exclude: &[String]) -> Vec<NumstatPath> {
+    head_base()
+        .and_then(|base| {
+            run_git_excluding(&["diff", "-M", "--numstat", "-z", &base, "HEAD"], exclude)
+        })
+        .map(|s| parse_numstat_paths(&s))
+        .unwrap_or_default()
+}
+
+pub fn head_paths() -> Vec<String> {
+    head_base()
+        .map(|base| paths_since(&base))
+        .unwrap_or_default()
+}
+
+/// The patch commit `sha` introduced, like [`head_diff`] for HEAD.
+pub fn commit_diff(sha: &str, exclude: &[String]) -> Result<String, IdrError> {
+    run_git_excluding(&["diff", &base_of(sha)?, sha], exclude)
+}
+
+pub fn commit_stat(sha: &str, exclude: &[String]) -> String {
+    base_of(sha)
+        .and_then(|base| run_git_excluding(&["diff", "--stat", &base, sha], exclude))
+        .unwrap_or_default()
+}
+
+pub fn commit_paths(sha: &str) -> Vec<String> {
+    name_only(base_of(sha).and_then(|base| run_git(&["diff", "--name-only", &base, sha])))
+}
+
+/// Changes from `base` to HEAD.
+pub fn diff_since(base: &str, exclude: &[String]) -> Result<String, IdrError> {
+    run_git_excluding(&["diff", base, "HEAD"], exclude)
+}
+
+pub fn stat_since(base: &str, exclude: &[String]) -> String {
+    run_git_excluding(&["diff", "--stat", base, "HEAD"], exclude).unwrap_or_default()
+}
+
+pub fn changed_lines_since(base: &str, exclude: &[String]) -> u64 {
+    run_git_excluding(&["diff", "-M", "--numstat", base, "HEAD"], exclude)
+        .map(|s| parse_numstat(&s))
+        .unwrap_or(0)
+}
+
+pub fn paths_since(base: &str) -> Vec<String> {
+    name_only(run_git(&["diff", "--name-only", base, "HEAD"]))
+}
+
+fn name_only(output: Result<String, IdrError>) -> Vec<String> {
+    output
+        .unwrap_or_default()
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
+
+/// The commit HEAD and `base` last had in common.
+pub fn merge_base(base: &str) -> Result<String, IdrError> {
+    run_git(&["merge-base", base, "HEAD"]).map(|sha| sha.trim().to_string())
+}
+
+/// `git log --oneline from..HEAD`, oldest first.
+pub fn log_oneline(from: &str) -> Result<String, IdrError> {
+    run_git(&["log", "--oneline", "--reverse", &format!("{from}..HEAD")])
+}
+
+/// The remote's default branch (`origin/HEAD`, e.g. `origin/main`), if
+/// the clone recorded one.
+pub fn default_remote_branch() -> Option<String> {
+    run_git(&["rev-parse", "--abbrev-ref", "origin/HEAD"])
+        .ok()
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty() && name != "origin/HEAD")
+}
+
+/// What HEAD is diffed against: its first parent, or the empty tree when
+/// HEAD is the first commit. Fails before there is any commit.
+fn head_base() -> Result<String, IdrError> {
+    base_of("HEAD")
+}
+
+/// What `rev` is diffed against, as [`head_base`] for HEAD. Fails when
+/// `rev` names no commit.
+fn base_of(rev: &str) -> Result<String, IdrError> {
+    run_git(&[
+        "rev-parse",
+        "--verify",
+        "--quiet",
+        &format!("{rev}^{{commit}}"),
+    ])?;
+    run_git(&["rev-parse", "--verify", "--quiet", &format!("{rev}^1")])
+        .or_else(|_| run_git(&["hash-object", "-t", "tree", "--stdin"]))
+        .map(|base| base.trim().to_string())
+}
+
+/// Absolute path of the repository's `.git` directory.
+pub fn git_dir() -> Option<PathBuf> {
+    run_git(&["rev-parse", "--absolute-git-dir"])
+        .ok()
+        .map(|out| PathBuf::from(out.trim()))
+}
+
+/// Where git looks for this repository's hooks: `.git/hooks`, or
+/// `core.hooksPath` when that is set.
+pub fn hooks_dir() -> Option<PathBuf> {
+    run_git(&["rev-parse", "--path-format=absolute", "--git-path", "hooks"])
+        .ok()
+        .map(|out| PathBuf::from(out.trim()))
+}
+
+/// The character that starts a comment line in commit messages
+/// (`core.commentChar`, `#` by default or when set to `auto`).
+pub fn comment_char() -> char {
+    run_git(&["config", "--get", "core.commentChar"])
+        .ok()
+        .map(|value| value.trim().to_string())
+        .filter(|value| value != "auto")
+        .and_then(|value| value.chars().next())
+        .unwrap_or('#')
+}
+
+/// Attaches `body` to `sha` as a note under `refs/notes/<notes_ref>`,
+/// replacing any note already there (e.g. from before an amend). The error
+/// is git's own message, so a protected or rejected ref is explained.
+pub fn add_note(notes_ref: &str, sha: &str, body: &str) -> Result<(), String> {
+    let mut child = Command::new("git")
+        .args([
+            "notes",
+            &format!("--ref={notes_ref}"),
+            "add",
+            "-f",
+            "-F",
+            "-",
+            sha,
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("cannot run git: {e}"))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(body.as_bytes())
+            .map_err(|e| format!("cannot write note: {e}"))?;
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("cannot run git: {e}"))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+/// The note on `sha` under `refs/notes/<notes_ref>`, if any.
+pub fn show_note(notes_ref: &str, sha: &str) -> Option<String> {
+    let output = Command::new("git")
+        .args(["notes", &format!("--ref={notes_ref}"), "show", sha])
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Files changed, insertions, and deletions from the summary line of
+/// `git diff --stat`.
+pub fn stat_totals(stat: &str) -> (u64, u64, u64) {
+    let Some(summary) = stat.lines().rev().find(|l| l.contains("changed")) else {
+        return (0, 0, 0);
+    };
+    let mut totals = (0, 0, 0);
+    for part in summary.split(',') {
+        let mut words = part.split_whitespace();
+        let Some(count) = words.next().and_then(|n| n.parse().ok()) else {
+            continue;
+        };
+        match words.next() {
+            Some(w) if w.starts_with("file") => totals.0 = count,
+            Some(w) if w.starts_with("insertion") => totals.1 = count,
+            Some(w) if w.starts_with("deletion") => totals.2 = count,
+            _ => {}
+        }
+    }
+    totals
+}
+
 fn parse_numstat(output: &str) -> u64 {
     output
         .lines()
@@ -26,27 +313,160 @@ fn parse_numstat(output: &str) -> u64 {
         .sum()
 }
 
-fn run_git(args: &[&str]) -> Option<String> {
-    match Command::new("git").args(args).output() {
-        Ok(o) if o.status.success() => Some(String::from_utf8_lossy(&o.stdout).into_owned()),
-        Ok(o) => {
-            eprintln!(
-                "claude-idr: git error: {}",
-                String::from_utf8_lossy(&o.stderr)
-            );
-            None
-        }
-        Err(e) => {
-            eprintln!("claude-idr: cannot run git: {e}");
-            None
+/// A file in `git diff --numstat` output, relative to the repository root.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NumstatPath {
+    pub path: String,
+    /// Where a renamed file used to be.
+    pub renamed_from: Option<String>,
+}
+
+/// Reads `git diff --numstat -z` output, where a rename leaves the path
+/// field empty and follows it with the old and new paths.
+fn parse_numstat_paths(output: &str) -> Vec<NumstatPath> {
+    let mut fields = output.split('\0');
+    let mut paths = Vec::new();
+    while let Some(field) = fields.next() {
+        let Some(path) = field.splitn(3, '\t').nth(2) else {
+            continue;
+        };
+        if !path.is_empty() {
+            paths.push(NumstatPath {
+                path: path.to_string(),
+                renamed_from: None,
+            });
+        } else if let (Some(from), Some(to)) = (fields.next(), fields.next()) {
+            paths.push(NumstatPath {
+                path: to.to_string(),
+                renamed_from: Some(from.to_string()),
+            });
         }
     }
+    paths
+}
+
+fn run_git_excluding(args: &[&str], exclude: &[String]) -> Result<String, IdrError> {
+    let args = with_excludes(args, exclude);
+    run_git(&args.iter().map(String::as_str).collect::<Vec<_>>())
+}
+
+/// `args` followed by pathspecs for the whole tree minus `exclude`. The
+/// whole tree is named with `:/`, so the result does not depend on the
+/// current directory.
+fn with_excludes(args: &[&str], exclude: &[String]) -> Vec<String> {
+    let mut all: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+    if !exclude.is_empty() {
+        all.extend(["--".to_string(), ":/".to_string()]);
+        all.extend(exclude.iter().map(|p| format!(":(top,literal,exclude){p}")));
+    }
+    all
+}
+
+fn run_git(args: &[&str]) -> Result<String, IdrError> {
+    let failed = |stderr: String| IdrError::Git {
+        args: args.iter().map(|a| a.to_string()).collect(),
+        stderr,
+    };
+    match Command::new("git").args(args).output() {
+        Ok(o) if o.status.success() => Ok(String::from_utf8_lossy(&o.stdout).into_owned()),
+        Ok(o) => Err(failed(String::from_utf8_lossy(&o.stderr).into_owned())),
+        Err(e) => Err(failed(format!("cannot run git: {e}"))),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn with_excludes_appends_pathspecs_only_when_needed() {
+        assert_eq!(
+            with_excludes(&["diff", "--cached"], &[]),
+            ["diff", "--cached"]
+        );
+        assert_eq!(
+            with_excludes(&["diff", "--cached"], &["vendor/a b.rs".to_string()]),
+            [
+                "diff",
+                "--cached",
+                "--",
+                ":/",
+                ":(top,literal,exclude)vendor/a b.rs"
+            ]
+        );
+    }
+
+    #[test]
+    fn stat_totals_reads_summary_line() {
+        let stat = " src/a.rs | 10 ++++++++--\n src/b.rs |  3 +++\n 2 files changed, 11 insertions(+), 2 deletions(-)\n";
+        assert_eq!(stat_totals(stat), (2, 11, 2));
+    }
+
+    #[test]
+    fn stat_totals_handles_missing_parts() {
+        assert_eq!(stat_totals(" 1 file changed, 1 insertion(+)"), (1, 1, 0));
+        assert_eq!(stat_totals(" 1 file changed, 4 deletions(-)"), (1, 0, 4));
+        assert_eq!(stat_totals(""), (0, 0, 0));
+    }
+
+    #[test]
+    fn run_git_reports_args_and_stderr_outside_a_repository() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let cwd = dir.path().to_str().unwrap();
+
+        let err = run_git(&["-C", cwd, "rev-parse", "--git-dir"]).unwrap_err();
+
+        match err {
+            IdrError::Git { args, stderr } => {
+                assert_eq!(args, ["-C", cwd, "rev-parse", "--git-dir"]);
+                assert!(stderr.contains("not a git repository"), "{stderr}");
+            }
+            other => panic!("expected IdrError::Git, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn file_log_lists_the_newest_commits_touching_a_path() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let cwd = dir.path().to_str().unwrap();
+        let git = |args: &[&str]| {
+            let mut all = vec![
+                "-C",
+                cwd,
+                "-c",
+                "user.name=t",
+                "-c",
+                "user.email=t@example.com",
+            ];
+            all.extend(args);
+            run_git(&all).unwrap()
+        };
+        git(&["init", "-q"]);
+        for (file, subject) in [("a b.rs", "one"), ("other.rs", "two"), ("a b.rs", "three")] {
+            std::fs::write(dir.path().join(file), subject).unwrap();
+            git(&["add", file]);
+            git(&["commit", "-q", "-m", subject]);
+        }
+        let log = |rev: &str, path: &str, count: usize| {
+            let mut args = vec!["-C".to_string(), cwd.to_string()];
+            args.extend(file_log_args(rev, path, count));
+            name_only(run_git(
+                &args.iter().map(String::as_str).collect::<Vec<_>>(),
+            ))
+        };
+
+        let lines = log("HEAD", "a b.rs", 3);
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with(" three"), "{lines:?}");
+        assert!(lines[1].ends_with(" one"), "{lines:?}");
+        let (hash, rest) = lines[0].split_once(' ').unwrap();
+        assert!(hash.len() >= 7 && hash.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_eq!(rest.len(), "2026-10-16 three".len());
+        assert_eq!(log("HEAD", "a b.rs", 1).len(), 1);
+        assert_eq!(log("HEAD^", "a b.rs", 3).len(), 1);
+        assert!(log("HEAD", "missing.rs", 3).is_empty());
+    }
+
     #[test]
     fn parse_numstat_empty_input() {
         assert_eq!(parse_numstat(""), 0);
@@ -69,6 +489,33 @@ mod tests {
         assert_eq!(parse_numstat("-\t-\timage.png"), 0);
     }
 
+    #[test]
+    fn parse_numstat_paths_reads_renames() {
+        let output = [
+            "3\t1\tsrc/a b.rs",
+            "-\t-\timage.png",
+            "0\t2\t",
+            "src/old.rs",
+            "src/new.rs",
+            "",
+        ]
+        .join("\0");
+        let path = |p: &str, from: Option<&str>| NumstatPath {
+            path: p.to_string(),
+            renamed_from: from.map(str::to_string),
+        };
+
+        assert_eq!(
+            parse_numstat_paths(&output),
+            [
+                path("src/a b.rs", None),
+                path("image.png", None),
+                path("src/new.rs", Some("src/old.rs")),
+            ]
+        );
+        assert_eq!(parse_numstat_paths(""), []);
+    }
+
     #[test]
     fn parse_numstat_mixed_binary_and_text() {
         let input = "10\t2\tsrc/main.rs\n-\t-\timage.png\n5\t0\tREADME.md";
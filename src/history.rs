@@ -0,0 +1,229 @@
+//! Earlier records in the output directory, summarized for the IDR prompt so
+//! new records can build on them.
+
+use crate::config::Format;
+use crate::front_matter;
+use crate::path;
+use std::fs;
+use std::path::Path;
+
+/// Cap on a single record's summary before the whole block is budgeted.
+const SUMMARY_MAX_CHARS: usize = 600;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreviousIdr {
+    pub file_name: String,
+    pub title: String,
+    pub summary: String,
+}
+
+/// The last `count` records in `dir`, oldest first. Unreadable files and
+/// files with nothing recognizable in them are skipped.
+pub fn recent(dir: &Path, format: Format, count: usize) -> Vec<PreviousIdr> {
+    let records = path::list_records(dir, format);
+    let start = records.len().saturating_sub(count);
+    records[start..]
+        .iter()
+        .filter_map(|(_, p)| {
+            let text = fs::read_to_string(p).ok()?;
+            extract(&p.file_name()?.to_string_lossy(), &text)
+        })
+        .collect()
+}
+
+/// The title of a record: front matter `title:`, else the first `# `
+/// heading minus an `IDR:` prefix. Empty when there is neither.
+pub fn title(text: &str) -> String {
+    let (front_matter, text) = front_matter::split(text);
+    front_matter
+        .and_then(|block| front_matter::field(block, "title"))
+        .or_else(|| {
+            let heading = text.lines().find_map(|l| l.strip_prefix("# "))?;
+            Some(
+                heading
+                    .strip_prefix("IDR:")
+                    .unwrap_or(heading)
+                    .trim()
+                    .to_string(),
+            )
+        })
+        .unwrap_or_default()
+}
+
+/// Pulls the title (see [`title`]) and the first `##` section out of a
+/// record. Hand-edited files without sections fall back to the first plain
+/// paragraph after the title.
+fn extract(file_name: &str, text: &str) -> Option<PreviousIdr> {
+    let title = title(text);
+    let (_, text) = front_matter::split(text);
+    let lines: Vec<&str> = text.lines().collect();
+    let title_at = lines.iter().position(|l| l.starts_with("# "));
+
+    let rest = &lines[title_at.map_or(0, |i| i + 1)..];
+    let body = match rest.iter().position(|l| l.starts_with("## ")) {
+        Some(i) => &rest[i + 1..],
+        None => rest,
+    };
+    let summary: Vec<&str> = body
+        .iter()
+        .map(|l| l.trim())
+        .skip_while(|l| l.is_empty() || is_preamble(l))
+        .take_while(|l| !l.is_empty() && !l.starts_with('#') && *l != "---")
+        .collect();
+    let summary = truncate(&summary.join(" "), SUMMARY_MAX_CHARS);
+
+    if title.is_empty() && summary.is_empty() {
+        return None;
+    }
+    Some(PreviousIdr {
+        file_name: file_name.to_string(),
+        title,
+        summary,
+    })
+}
+
+/// Date quotes and MADR status lines sit between the title and the body.
+fn is_preamble(line: &str) -> bool {
+    line.starts_with('>') || line.starts_with("* Status:") || line.starts_with("* Date:")
+}
+
+/// Renders the records for the prompt within `max_chars`, dropping the
+/// oldest first.
+pub fn render(previous: &[PreviousIdr], max_chars: usize) -> String {
+    let blocks: Vec<String> = previous
+        .iter()
+        .map(|p| format!("{}: {}\n{}\n", p.file_name, p.title, p.summary))
+        .collect();
+    let mut start = 0;
+    while start + 1 < blocks.len()
+        && blocks[start..].iter().map(|b| b.len()).sum::<usize>() > max_chars
+    {
+        start += 1;
+    }
+    truncate(&blocks[start..].join("\n"), max_chars)
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let mut cut: String = text.chars().take(max_chars.saturating_sub(3)).collect();
+    cut.push_str("...");
+    cut
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const GENERATED: &str = "\
+# IDR: Add retries to the API client
+
+> 2026-02-07 14:30
+
+## 変更概要
+
+Retries 429 and 5xx responses
+with exponential backoff.
+
+## 主要な変更
+
+### [src/api.rs](src/api.rs)
+";
+
+    #[test]
+    fn extract_reads_title_and_first_section() {
+        let idr = extract("idr-03.md", GENERATED).unwrap();
+
+        assert_eq!(idr.title, "Add retries to the API client");
+        assert_eq!(
+            idr.summary,
+            "Retries 429 and 5xx responses with exponential backoff."
+        );
+    }
+
+    #[test]
+    fn extract_reads_madr_records() {
+        let text = "# Use JWT\n\n* Status: accepted\n* Date: 2026-02-07\n\n## Context and Problem Statement\n\nSessions do not scale.\n";
+
+        let idr = extract("0004-use-jwt.md", text).unwrap();
+
+        assert_eq!(idr.title, "Use JWT");
+        assert_eq!(idr.summary, "Sessions do not scale.");
+    }
+
+    #[test]
+    fn extract_reads_title_from_front_matter() {
+        let text = "---\ntitle: \"fix: \\\"quoted\\\" keys\"\nmodel: \"sonnet\"\n---\n\n> 2026-02-07 14:30\n\n## 変更概要\n\nQuotes keys.\n";
+
+        let idr = extract("idr-08.md", text).unwrap();
+
+        assert_eq!(idr.title, "fix: \"quoted\" keys");
+        assert_eq!(idr.summary, "Quotes keys.");
+    }
+
+    #[test]
+    fn extract_tolerates_hand_edited_files() {
+        let idr = extract("idr-05.md", "Notes from the call\nabout caching.\n").unwrap();
+        assert_eq!(idr.title, "");
+        assert_eq!(idr.summary, "Notes from the call about caching.");
+
+        let idr = extract("idr-06.md", "# IDR: Title only\n").unwrap();
+        assert_eq!(idr.title, "Title only");
+        assert_eq!(idr.summary, "");
+
+        assert!(extract("idr-07.md", "\n\n").is_none());
+    }
+
+    #[test]
+    fn extract_caps_long_summaries() {
+        let text = format!("# IDR: t\n\n## 変更概要\n\n{}\n", "x".repeat(1000));
+
+        let idr = extract("idr-01.md", &text).unwrap();
+
+        assert_eq!(idr.summary.chars().count(), SUMMARY_MAX_CHARS);
+        assert!(idr.summary.ends_with("..."));
+    }
+
+    #[test]
+    fn recent_returns_last_records_oldest_first() {
+        let dir = TempDir::new().unwrap();
+        for n in 1..=4 {
+            fs::write(
+                dir.path().join(format!("idr-{n:02}.md")),
+                format!("# IDR: record {n}\n\n## 変更概要\n\nsummary {n}\n"),
+            )
+            .unwrap();
+        }
+        fs::write(dir.path().join("notes.md"), "# not a record\n").unwrap();
+
+        let previous = recent(dir.path(), Format::Idr, 2);
+
+        let titles: Vec<_> = previous.iter().map(|p| p.title.as_str()).collect();
+        assert_eq!(titles, vec!["record 3", "record 4"]);
+        assert_eq!(previous[1].file_name, "idr-04.md");
+    }
+
+    #[test]
+    fn render_drops_oldest_records_to_fit() {
+        let previous = vec![
+            PreviousIdr {
+                file_name: "idr-01.md".to_string(),
+                title: "old".to_string(),
+                summary: "a".repeat(50),
+            },
+            PreviousIdr {
+                file_name: "idr-02.md".to_string(),
+                title: "new".to_string(),
+                summary: "b".repeat(50),
+            },
+        ];
+
+        assert!(render(&previous, 1000).starts_with("idr-01.md: old\n"));
+        let capped = render(&previous, 80);
+        assert!(capped.starts_with("idr-02.md: new\n"));
+        assert!(!capped.contains("idr-01.md"));
+        assert!(render(&previous, 20).chars().count() <= 20);
+    }
+}
@@ -0,0 +1,152 @@
+//! `--hook-stdin`: running as a Claude Code hook (`Stop`, `PostToolUse`).
+//! The payload on stdin names the transcript and working directory, so no
+//! session discovery is needed, and the answer on stdout is the JSON
+//! envelope Claude Code expects from a hook.
+
+use crate::config;
+use crate::generate::GenerateReport;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::io::Read;
+use std::path::PathBuf;
+
+/// The fields claude-idr uses from a hook payload; the rest (tool input,
+/// permission mode, ...) is ignored.
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct Payload {
+    #[serde(default)]
+    pub session_id: Option<String>,
+    pub transcript_path: PathBuf,
+    #[serde(default)]
+    pub cwd: Option<PathBuf>,
+    #[serde(default)]
+    pub hook_event_name: Option<String>,
+}
+
+/// Reads and parses the payload from stdin.
+pub fn read_stdin() -> Result<Payload, String> {
+    let mut text = String::new();
+    std::io::stdin()
+        .read_to_string(&mut text)
+        .map_err(|e| format!("cannot read hook payload from stdin: {e}"))?;
+    parse(&text)
+}
+
+/// Parses a payload, expanding a leading `~/` in `transcript_path` (older
+/// Claude Code versions send it unexpanded).
+pub fn parse(text: &str) -> Result<Payload, String> {
+    if text.trim().is_empty() {
+        return Err(
+            "no hook payload on stdin (is --hook-stdin set outside a Claude Code hook?)"
+                .to_string(),
+        );
+    }
+    let mut payload: Payload =
+        serde_json::from_str(text).map_err(|e| format!("invalid hook payload: {e}"))?;
+    payload.transcript_path = config::expand_home(&payload.transcript_path);
+    Ok(payload)
+}
+
+/// The hook's stdout: keep claude-idr out of the transcript, and tell the
+/// user about a new record or a failure. Never blocks Claude from stopping.
+pub fn response(report: &GenerateReport) -> Value {
+    let mut response = json!({ "suppressOutput": true });
+    let message = match report.status {
+        "generated" => report
+            .output
+            .as_ref()
+            .map(|path| format!("claude-idr: IDR generated: {}", path.display())),
+        "error" => report
+            .reason
+            .as_ref()
+            .map(|reason| format!("claude-idr: {reason}")),
+        _ => None,
+    };
+    if let Some(message) = message {
+        response["systemMessage"] = json!(message);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_reads_stop_payload() {
+        let payload = parse(include_str!("../tests/fixtures/hook-stop.json")).unwrap();
+
+        assert_eq!(
+            payload,
+            Payload {
+                session_id: Some("00893aaf-19fa-41d2-8238-13269b9b3ca0".to_string()),
+                transcript_path: PathBuf::from(
+                    "/Users/me/.claude/projects/-Users-me-src-app/00893aaf-19fa-41d2-8238-13269b9b3ca0.jsonl"
+                ),
+                cwd: Some(PathBuf::from("/Users/me/src/app")),
+                hook_event_name: Some("Stop".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn parse_reads_post_tool_use_payload_and_expands_home() {
+        let payload = parse(include_str!("../tests/fixtures/hook-post-tool-use.json")).unwrap();
+
+        assert_eq!(payload.hook_event_name.as_deref(), Some("PostToolUse"));
+        assert_eq!(payload.cwd, Some(PathBuf::from("/home/me/app")));
+        if let Some(home) = config::home_dir() {
+            assert_eq!(
+                payload.transcript_path,
+                home.join(
+                    ".claude/projects/-home-me-app/4f9c2a1e-7b3d-4e8a-9c1f-2d6e8b0a5c3f.jsonl"
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn parse_rejects_empty_and_incomplete_payloads() {
+        assert!(parse("").unwrap_err().contains("no hook payload"));
+        assert!(
+            parse(r#"{"session_id":"abc","cwd":"/tmp"}"#)
+                .unwrap_err()
+                .contains("transcript_path")
+        );
+    }
+
+    #[test]
+    fn response_is_quiet_for_skips() {
+        let report = GenerateReport {
+            status: "skipped",
+            reason: Some("no staged changes".to_string()),
+            ..GenerateReport::default()
+        };
+        assert_eq!(response(&report), json!({ "suppressOutput": true }));
+    }
+
+    #[test]
+    fn response_reports_generated_record_and_errors() {
+        let generated = GenerateReport {
+            status: "generated",
+            output: Some(PathBuf::from("/w/idr-01-add-retries.md")),
+            ..GenerateReport::default()
+        };
+        assert_eq!(
+            response(&generated),
+            json!({
+                "suppressOutput": true,
+                "systemMessage": "claude-idr: IDR generated: /w/idr-01-add-retries.md"
+            })
+        );
+        let failed = GenerateReport {
+            status: "error",
+            reason: Some("claude CLI timed out after 300s".to_string()),
+            ..GenerateReport::default()
+        };
+        assert_eq!(
+            response(&failed)["systemMessage"],
+            "claude-idr: claude CLI timed out after 300s"
+        );
+    }
+}
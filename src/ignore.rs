@@ -0,0 +1,321 @@
+//! `.idrignore`: gitignore-style patterns for files a record should not
+//! cover, read from the repository root and the workspace directory. The
+//! git commands reading a diff exclude the matching paths, and the session
+//! context drops them from its changed files.
+//!
+//! Matching follows gitignore: the last matching pattern wins, `!`
+//! re-includes, a trailing `/` matches directories only, a pattern with a
+//! `/` before its end is anchored to the root while one without matches at
+//! any depth, `*` and `?` stop at `/`, `**` crosses it, and nothing inside
+//! an ignored directory can be re-included.
+
+use crate::config::Config;
+use crate::git;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+pub const FILE_NAME: &str = ".idrignore";
+
+#[derive(Debug, Default)]
+pub struct Rules {
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug)]
+struct Rule {
+    glob: Vec<char>,
+    negated: bool,
+    dir_only: bool,
+    anchored: bool,
+}
+
+impl Rules {
+    /// The patterns in `.idrignore` at `workspace` and then at `repo_root`,
+    /// so the repository's own file has the last word. Missing files add
+    /// nothing.
+    pub fn load(repo_root: Option<&Path>, workspace: &Path) -> Rules {
+        let mut rules = Rules::default();
+        for dir in std::iter::once(workspace).chain(repo_root) {
+            if let Ok(text) = fs::read_to_string(dir.join(FILE_NAME)) {
+                rules.rules.extend(Rules::parse(&text).rules);
+            }
+        }
+        rules
+    }
+
+    /// The rules for the current repository and `config`'s workspace.
+    pub fn for_repo(config: &Config) -> Rules {
+        Rules::load(git::repo_root().as_deref(), &config.workspace_dir)
+    }
+
+    pub fn parse(text: &str) -> Rules {
+        Rules {
+            rules: text.lines().filter_map(Rule::parse).collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Whether `path` (relative to the repository root, `/`-separated) is
+    /// ignored, itself or through one of its directories.
+    pub fn is_ignored(&self, path: &str) -> bool {
+        let path = path.trim_start_matches("./");
+        let mut dirs = path.match_indices('/').map(|(at, _)| &path[..at]);
+        dirs.any(|dir| self.verdict(dir, true)) || self.verdict(path, false)
+    }
+
+    /// The paths in `paths` that are ignored.
+    pub fn excluded(&self, paths: &[String]) -> Vec<String> {
+        if self.is_empty() {
+            return Vec::new();
+        }
+        paths
+            .iter()
+            .filter(|p| self.is_ignored(p))
+            .cloned()
+            .collect()
+    }
+
+    /// Drops ignored files from `files`, which may be absolute; those under
+    /// `root` are matched relative to it.
+    pub fn retain_files(&self, files: &mut BTreeSet<String>, root: Option<&Path>) {
+        if self.is_empty() {
+            return;
+        }
+        files.retain(|file| {
+            let relative = root
+                .and_then(|root| Path::new(file).strip_prefix(root).ok())
+                .map_or_else(|| file.clone(), |p| p.to_string_lossy().into_owned());
+            !self.is_ignored(&relative)
+        });
+    }
+
+    /// Whether the last rule matching `path` ignores it.
+    fn verdict(&self, path: &str, is_dir: bool) -> bool {
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| rule.matches(path, is_dir))
+            .is_some_and(|rule| !rule.negated)
+    }
+}
+
+impl Rule {
+    fn parse(line: &str) -> Option<Rule> {
+        let line = trim_unescaped_trailing_spaces(line);
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let anchored = line.contains('/');
+        let line = line.strip_prefix('/').unwrap_or(line);
+        if line.is_empty() {
+            return None;
+        }
+        Some(Rule {
+            glob: line.chars().collect(),
+            negated,
+            dir_only,
+            anchored,
+        })
+    }
+
+    fn matches(&self, path: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        let subject = if self.anchored {
+            path
+        } else {
+            path.rsplit('/').next().unwrap_or(path)
+        };
+        glob(&self.glob, &subject.chars().collect::<Vec<_>>())
+    }
+}
+
+/// Whether `path` matches the gitignore-style `pattern`, as a file.
+pub fn matches(pattern: &str, path: &str) -> bool {
+    Rule::parse(pattern).is_some_and(|rule| rule.matches(path, false))
+}
+
+/// `line` without trailing spaces, except one escaped with `\`.
+fn trim_unescaped_trailing_spaces(line: &str) -> &str {
+    let trimmed = line.trim_end_matches(' ');
+    if trimmed.ends_with('\\') && trimmed.len() < line.len() {
+        &line[..trimmed.len() + 1]
+    } else {
+        trimmed
+    }
+}
+
+fn glob(pattern: &[char], text: &[char]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        ['*', '*', '/', rest @ ..] => {
+            glob(rest, text)
+                || (0..text.len()).any(|i| text[i] == '/' && glob(rest, &text[i + 1..]))
+        }
+        ['*', '*', rest @ ..] => (0..=text.len()).any(|i| glob(rest, &text[i..])),
+        ['*', rest @ ..] => (0..=text.len())
+            .take_while(|&i| i == 0 || text[i - 1] != '/')
+            .any(|i| glob(rest, &text[i..])),
+        ['?', rest @ ..] => text.first().is_some_and(|&c| c != '/') && glob(rest, &text[1..]),
+        ['[', rest @ ..] => match class(rest) {
+            Some((matches, after)) => {
+                text.first().is_some_and(|&c| c != '/' && matches(c)) && glob(after, &text[1..])
+            }
+            // Like git, an unclosed class matches nothing.
+            None => false,
+        },
+        ['\\', c, rest @ ..] => literal(*c, rest, text),
+        [c, rest @ ..] => literal(*c, rest, text),
+    }
+}
+
+fn literal(c: char, rest: &[char], text: &[char]) -> bool {
+    text.first() == Some(&c) && glob(rest, &text[1..])
+}
+
+/// The `[...]` class starting after `[`: a matcher and the pattern after
+/// `]`, or `None` when it is never closed.
+fn class(pattern: &[char]) -> Option<(impl Fn(char) -> bool, &[char])> {
+    let (negated, body) = match pattern.first() {
+        Some('!' | '^') => (true, &pattern[1..]),
+        _ => (false, pattern),
+    };
+    // A `]` right after the opening bracket is literal.
+    let close = body.iter().skip(1).position(|&c| c == ']')? + 1;
+    let members = &body[..close];
+    let mut ranges = Vec::new();
+    let mut i = 0;
+    while i < members.len() {
+        if i + 2 < members.len() && members[i + 1] == '-' {
+            ranges.push((members[i], members[i + 2]));
+            i += 3;
+        } else {
+            ranges.push((members[i], members[i]));
+            i += 1;
+        }
+    }
+    let matcher = move |c: char| ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi) != negated;
+    Some((matcher, &body[close + 1..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// (patterns, path, ignored) as `git check-ignore` answers them.
+    const CASES: &[(&str, &str, bool)] = &[
+        ("*.log", "debug.log", true),
+        ("*.log", "logs/debug.log", true),
+        ("*.log", "debug.log.txt", false),
+        ("vendor/", "vendor/lib.rs", true),
+        ("vendor/", "src/vendor/lib.rs", true),
+        ("vendor/", "vendor", false),
+        ("/build", "build/out.o", true),
+        ("/build", "build", true),
+        ("/build", "src/build/out.o", false),
+        ("doc/*.txt", "doc/notes.txt", true),
+        ("doc/*.txt", "doc/server/arch.txt", false),
+        ("doc/*.txt", "src/doc/notes.txt", false),
+        ("**/fixtures", "fixtures/a.json", true),
+        ("**/fixtures", "tests/deep/fixtures/a.json", true),
+        ("a/**/b", "a/b", true),
+        ("a/**/b", "a/x/y/b", true),
+        ("a/**/b", "a/xb", false),
+        ("abc/**", "abc/x/y", true),
+        ("abc/**", "abc", false),
+        ("*.lock\n!important.lock", "Cargo.lock", true),
+        ("*.lock\n!important.lock", "important.lock", false),
+        ("*.lock\n!important.lock", "sub/important.lock", false),
+        ("!important.lock\n*.lock", "important.lock", true),
+        ("vendor/\n!vendor/keep.rs", "vendor/keep.rs", true),
+        ("vendor/*\n!vendor/keep.rs", "vendor/keep.rs", false),
+        ("vendor/*\n!vendor/keep.rs", "vendor/drop.rs", true),
+        ("# comment", "# comment", false),
+        ("\\#file", "#file", true),
+        ("\\!bang", "!bang", true),
+        ("[ab].txt", "a.txt", true),
+        ("[ab].txt", "c.txt", false),
+        ("[!ab].txt", "c.txt", true),
+        ("[a-c]x", "bx", true),
+        ("[a-c]x", "dx", false),
+        ("?.rs", "a.rs", true),
+        ("?.rs", "ab.rs", false),
+        ("foo   ", "foo", true),
+        ("foo\\ ", "foo ", true),
+        ("", "anything", false),
+        ("src/*", "src/a/b.rs", true),
+        ("*", "a/b", true),
+        ("[unclosed", "[unclosed", false),
+    ];
+
+    #[test]
+    fn is_ignored_agrees_with_gitignore_table() {
+        for (patterns, path, expected) in CASES {
+            assert_eq!(
+                Rules::parse(patterns).is_ignored(path),
+                *expected,
+                "patterns {patterns:?}, path {path:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn excluded_lists_ignored_paths() {
+        let rules = Rules::parse("*.lock\n/generated/\n");
+        let paths = [
+            "Cargo.lock".to_string(),
+            "generated/api.rs".to_string(),
+            "src/main.rs".to_string(),
+        ];
+
+        assert_eq!(rules.excluded(&paths), ["Cargo.lock", "generated/api.rs"]);
+        assert!(Rules::default().excluded(&paths).is_empty());
+    }
+
+    #[test]
+    fn retain_files_matches_absolute_paths_relative_to_root() {
+        let rules = Rules::parse("/vendor/\n");
+        let mut files: BTreeSet<String> = [
+            "/repo/vendor/a.rs",
+            "/repo/src/b.rs",
+            "/elsewhere/vendor/c.rs",
+        ]
+        .map(String::from)
+        .into();
+
+        rules.retain_files(&mut files, Some(Path::new("/repo")));
+
+        assert_eq!(
+            files.into_iter().collect::<Vec<_>>(),
+            ["/elsewhere/vendor/c.rs", "/repo/src/b.rs"]
+        );
+    }
+
+    #[test]
+    fn load_reads_workspace_then_repository_file() {
+        let repo = TempDir::new().unwrap();
+        let workspace = TempDir::new().unwrap();
+        fs::write(workspace.path().join(FILE_NAME), "*.lock\n").unwrap();
+        fs::write(repo.path().join(FILE_NAME), "!Cargo.lock\n").unwrap();
+
+        let rules = Rules::load(Some(repo.path()), workspace.path());
+
+        assert!(!rules.is_ignored("Cargo.lock"));
+        assert!(rules.is_ignored("yarn.lock"));
+        assert!(Rules::load(None, Path::new("/nonexistent")).is_empty());
+    }
+}
@@ -0,0 +1,240 @@
+//! `index.md` in an output directory: a table of the records in it, newest
+//! first, rebuilt from the files on disk after every write.
+
+use crate::config::Format;
+use crate::front_matter;
+use crate::git;
+use crate::history;
+use crate::log::warn;
+use crate::path;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+pub const FILE_NAME: &str = "index.md";
+/// Everything above this line is kept as written; everything below it is
+/// regenerated.
+const MARKER: &str = "<!-- claude-idr:index (generated below this line) -->";
+const DEFAULT_HEADER: &str = "# Decision records\n\n";
+
+#[derive(Debug, PartialEq)]
+struct Entry {
+    number: u32,
+    date: String,
+    title: String,
+    file_name: String,
+    totals: Option<(u64, u64)>,
+}
+
+/// Rebuilds `dir/index.md` from the records in `dir`.
+pub fn refresh(dir: &Path, format: Format) -> io::Result<()> {
+    let index_path = dir.join(FILE_NAME);
+    let existing = match fs::read_to_string(&index_path) {
+        Ok(text) => Some(text),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e),
+    };
+    let entries: Vec<Entry> = path::list_records(dir, format)
+        .into_iter()
+        .rev()
+        .filter_map(|(number, p)| {
+            let bytes = fs::read(&p).ok()?;
+            let file_name = p.file_name()?.to_string_lossy().into_owned();
+            Some(entry(number, file_name, &String::from_utf8_lossy(&bytes)))
+        })
+        .collect();
+    fs::write(&index_path, render(existing.as_deref(), &entries))
+}
+
+/// Like [`refresh`], but only warns: a stale index never fails a run.
+pub fn refresh_warn(dir: &Path, format: Format) {
+    if let Err(e) = refresh(dir, format) {
+        warn!("cannot update {}: {e}", dir.join(FILE_NAME).display());
+    }
+}
+
+fn entry(number: u32, file_name: String, text: &str) -> Entry {
+    let (block, body) = front_matter::split(text);
+    let date = block
+        .and_then(|b| front_matter::field(b, "date"))
+        .or_else(|| {
+            body.lines().find_map(|l| {
+                l.strip_prefix("> ")
+                    .or_else(|| l.strip_prefix("* Date: "))
+                    .filter(|d| d.len() >= 10 && d.as_bytes()[4] == b'-')
+                    .map(str::to_string)
+            })
+        })
+        .map(|d| d.chars().take(10).collect())
+        .unwrap_or_default();
+    let totals = body
+        .split_once("### git diff --stat")
+        .map(|(_, stat)| git::stat_totals(stat))
+        .filter(|(files, _, _)| *files > 0)
+        .map(|(_, insertions, deletions)| (insertions, deletions));
+    Entry {
+        number,
+        date,
+        title: history::title(text),
+        file_name,
+        totals,
+    }
+}
+
+fn render(existing: Option<&str>, entries: &[Entry]) -> String {
+    let header = match existing {
+        Some(text) => match text.find(MARKER) {
+            Some(at) => text[..at].to_string(),
+            None if text.trim().is_empty() => DEFAULT_HEADER.to_string(),
+            None => format!("{}\n\n", text.trim_end()),
+        },
+        None => DEFAULT_HEADER.to_string(),
+    };
+
+    let mut out = format!("{header}{MARKER}\n\n| # | Date | Title | +/- |\n|---|---|---|---|\n");
+    for e in entries {
+        let title = if e.title.is_empty() {
+            "(untitled)".to_string()
+        } else {
+            escape_cell(&e.title)
+        };
+        let totals = e
+            .totals
+            .map_or(String::new(), |(i, d)| format!("+{i} / -{d}"));
+        out.push_str(&format!(
+            "| {} | {} | [{title}]({}) | {totals} |\n",
+            e.number,
+            e.date,
+            e.file_name.replace(' ', "%20")
+        ));
+    }
+    let known: Vec<(u64, u64)> = entries.iter().filter_map(|e| e.totals).collect();
+    if !known.is_empty() {
+        let (insertions, deletions) = known
+            .iter()
+            .fold((0, 0), |(ai, ad), (i, d)| (ai + i, ad + d));
+        out.push_str(&format!(
+            "\n{} records, +{insertions} / -{deletions} lines in total\n",
+            entries.len()
+        ));
+    }
+    out
+}
+
+fn escape_cell(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('|', "\\|")
+        .replace(['\n', '\r'], " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn idr(title: &str, date: &str, summary_line: &str) -> String {
+        format!(
+            "# IDR: {title}\n\n> {date}\n\n## 変更概要\n\nbody\n\n---\n\n### git diff --stat\n```\n src/a.rs | 3 ++-\n{summary_line}\n```\n"
+        )
+    }
+
+    fn fixture() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        let p = dir.path();
+        fs::write(
+            p.join("idr-01.md"),
+            idr(
+                "First | step",
+                "2026-02-01 10:00",
+                " 1 file changed, 2 insertions(+), 1 deletion(-)",
+            ),
+        )
+        .unwrap();
+        fs::write(
+            p.join("idr-02-use-jwt.md"),
+            "---\ntitle: \"Use JWT\"\ndate: \"2026-02-03T01:00:00Z\"\n---\n\n> 2026-02-03 10:00\n\n---\n\n### git diff --stat\n```\n 2 files changed, 10 insertions(+)\n```\n",
+        )
+        .unwrap();
+        // Malformed: no title, no date, not even valid UTF-8.
+        fs::write(p.join("idr-03.md"), b"\xff\xfe garbage").unwrap();
+        fs::write(p.join("notes.md"), "# not a record\n").unwrap();
+        dir
+    }
+
+    #[test]
+    fn refresh_lists_records_newest_first() {
+        let dir = fixture();
+
+        refresh(dir.path(), Format::Idr).unwrap();
+
+        let index = fs::read_to_string(dir.path().join(FILE_NAME)).unwrap();
+        assert_eq!(
+            index,
+            format!(
+                "# Decision records\n\n{MARKER}\n\n\
+                 | # | Date | Title | +/- |\n|---|---|---|---|\n\
+                 | 3 |  | [(untitled)](idr-03.md) |  |\n\
+                 | 2 | 2026-02-03 | [Use JWT](idr-02-use-jwt.md) | +10 / -0 |\n\
+                 | 1 | 2026-02-01 | [First \\| step](idr-01.md) | +2 / -1 |\n\
+                 \n3 records, +12 / -1 lines in total\n"
+            )
+        );
+    }
+
+    #[test]
+    fn refresh_keeps_hand_written_text_above_marker() {
+        let dir = fixture();
+        let intro = "# Auth rewrite\n\nSee the design doc.\n\n";
+        fs::write(
+            dir.path().join(FILE_NAME),
+            format!("{intro}{MARKER}\n\n| stale | rows |\n"),
+        )
+        .unwrap();
+
+        refresh(dir.path(), Format::Idr).unwrap();
+
+        let index = fs::read_to_string(dir.path().join(FILE_NAME)).unwrap();
+        assert!(index.starts_with(&format!("{intro}{MARKER}\n")));
+        assert!(!index.contains("stale"));
+    }
+
+    #[test]
+    fn refresh_self_heals_after_deletion() {
+        let dir = fixture();
+        refresh(dir.path(), Format::Idr).unwrap();
+
+        fs::remove_file(dir.path().join("idr-02-use-jwt.md")).unwrap();
+        refresh(dir.path(), Format::Idr).unwrap();
+
+        let index = fs::read_to_string(dir.path().join(FILE_NAME)).unwrap();
+        assert!(!index.contains("Use JWT"));
+        assert!(index.contains("2 records, +2 / -1 lines in total"));
+    }
+
+    #[test]
+    fn refresh_keeps_unmarked_index_content() {
+        let dir = fixture();
+        fs::write(dir.path().join(FILE_NAME), "My notes\n").unwrap();
+
+        refresh(dir.path(), Format::Idr).unwrap();
+
+        let index = fs::read_to_string(dir.path().join(FILE_NAME)).unwrap();
+        assert!(index.starts_with(&format!("My notes\n\n{MARKER}\n")));
+    }
+
+    #[test]
+    fn refresh_reads_madr_records() {
+        let dir = TempDir::new().unwrap();
+        fs::write(
+            dir.path().join("0001-use-jwt.md"),
+            "# Use JWT\n\n* Status: accepted\n* Date: 2026-02-07\n\nbody\n",
+        )
+        .unwrap();
+
+        refresh(dir.path(), Format::Madr).unwrap();
+
+        let index = fs::read_to_string(dir.path().join(FILE_NAME)).unwrap();
+        assert!(index.contains("| 1 | 2026-02-07 | [Use JWT](0001-use-jwt.md) |  |\n"));
+        assert!(!index.contains("in total"));
+    }
+}
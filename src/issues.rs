@@ -0,0 +1,166 @@
+//! Issue and ticket references (`PROJ-1234`, `#512`) picked out of the
+//! branch name and the session's user requests, for the record's
+//! `Related:` line and its sidecar.
+
+use crate::config::Config;
+use crate::log::warn;
+use crate::pattern::Pattern;
+
+/// JIRA-style `KEY-123` and GitHub-style `#123`.
+pub const DEFAULT_PATTERN: &str = "[A-Z][A-Z0-9]+-[0-9]+|#[0-9]+";
+
+/// Prefixes of standard names shaped like `KEY-123` (`UTF-8`, `SHA-256`,
+/// `ISO-8601`) that are never issue keys.
+const STANDARD_NAMES: &[&str] = &[
+    "AES", "HTTP", "ISO", "RFC", "RSA", "SHA", "TLS", "UCS", "UTF",
+];
+
+/// The issue ids in `sources` per `config.issue_pattern`. An empty pattern
+/// turns extraction off; an invalid one is warned about and does the same.
+pub fn collect(config: &Config, sources: &[&str]) -> Vec<String> {
+    if config.issue_pattern.is_empty() {
+        return Vec::new();
+    }
+    match Pattern::parse(&config.issue_pattern) {
+        Ok(pattern) => extract(&pattern, sources),
+        Err(e) => {
+            warn!("ignoring issue_pattern: {e}");
+            Vec::new()
+        }
+    }
+}
+
+/// The distinct matches of `pattern` in `sources`, in order of first
+/// appearance. A match must stand on its own: one glued to a letter, digit,
+/// or `_` (`page#3`, `ABC-12x`), or following `&` as in the HTML entity
+/// `&#38;`, is part of something else.
+pub fn extract(pattern: &Pattern, sources: &[&str]) -> Vec<String> {
+    let glued = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+    let mut ids: Vec<String> = Vec::new();
+    for source in sources {
+        for range in pattern.find_all(source) {
+            let before = source[..range.start].chars().next_back();
+            let after = source[range.end..].chars().next();
+            let id = &source[range];
+            if glued(before)
+                || before == Some('&')
+                || glued(after)
+                || is_standard_name(id)
+                || ids.iter().any(|known| known == id)
+            {
+                continue;
+            }
+            ids.push(id.to_string());
+        }
+    }
+    ids
+}
+
+fn is_standard_name(id: &str) -> bool {
+    id.split_once('-')
+        .is_some_and(|(prefix, _)| STANDARD_NAMES.contains(&prefix))
+}
+
+/// `id` as a markdown link when there is a `template`, whose `{id}` is
+/// replaced by the id without a leading `#`.
+pub fn link(id: &str, template: Option<&str>) -> String {
+    match template {
+        Some(template) => format!(
+            "[{id}]({})",
+            template.replace("{id}", id.trim_start_matches('#'))
+        ),
+        None => id.to_string(),
+    }
+}
+
+/// The value of the record's `Related:` line, or `None` without ids.
+pub fn related(ids: &[String], template: Option<&str>) -> Option<String> {
+    (!ids.is_empty()).then(|| {
+        ids.iter()
+            .map(|id| link(id, template))
+            .collect::<Vec<_>>()
+            .join(", ")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(sources: &[&str]) -> Vec<String> {
+        extract(&Pattern::parse(DEFAULT_PATTERN).unwrap(), sources)
+    }
+
+    #[test]
+    fn extract_finds_keys_in_branch_and_requests() {
+        assert_eq!(
+            ids(&[
+                "feat/PROJ-1234-retry-logic",
+                "Fix #512 and finish PROJ-1234 (see #77).",
+            ]),
+            ["PROJ-1234", "#512", "#77"]
+        );
+    }
+
+    #[test]
+    fn extract_dedupes_overlapping_sources() {
+        assert_eq!(ids(&["fix/#512", "#512", "Closes #512, #512"]), ["#512"]);
+    }
+
+    #[test]
+    fn extract_ignores_lookalikes() {
+        assert!(
+            ids(&[
+                "Use UTF-8 and SHA-256 dates per ISO-8601 and RFC-3339",
+                "see page#3, item_#4, &#38; and ABC-12x",
+                "lowercase proj-12 and single-letter X-1",
+            ])
+            .is_empty()
+        );
+    }
+
+    #[test]
+    fn extract_keeps_key_next_to_punctuation() {
+        assert_eq!(
+            ids(&["(ABC-1), [DEF-2]: GH-3.", "`#4`"]),
+            ["ABC-1", "DEF-2", "GH-3", "#4"]
+        );
+    }
+
+    #[test]
+    fn collect_honours_custom_empty_and_invalid_patterns() {
+        let config = |pattern: &str| Config {
+            issue_pattern: pattern.to_string(),
+            ..Config::default()
+        };
+        let sources = ["feat/gh-42-retry PROJ-1"];
+
+        assert_eq!(collect(&config(r"gh-\d+"), &sources), ["gh-42"]);
+        assert!(collect(&config(""), &sources).is_empty());
+        assert!(collect(&config("(gh)"), &sources).is_empty());
+        assert_eq!(collect(&Config::default(), &sources), ["PROJ-1"]);
+    }
+
+    #[test]
+    fn link_fills_template_without_hash() {
+        let template = Some("https://tracker.example.com/browse/{id}");
+        assert_eq!(
+            link("PROJ-1234", template),
+            "[PROJ-1234](https://tracker.example.com/browse/PROJ-1234)"
+        );
+        assert_eq!(
+            link("#512", template),
+            "[#512](https://tracker.example.com/browse/512)"
+        );
+        assert_eq!(link("#512", None), "#512");
+    }
+
+    #[test]
+    fn related_joins_ids_or_is_absent() {
+        assert_eq!(
+            related(&["PROJ-1234".to_string(), "#512".to_string()], None),
+            Some("PROJ-1234, #512".to_string())
+        );
+        assert_eq!(related(&[], None), None);
+    }
+}
@@ -1,22 +1,346 @@
+use serde::{Deserialize, Deserializer};
 use serde_json::Value;
+use std::fmt;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
 use std::path::Path;
 
-pub fn iter_values(path: &Path) -> impl Iterator<Item = Value> {
-    let file = File::open(path).ok();
-    let lines: Box<dyn Iterator<Item = String>> = match file {
-        Some(f) => Box::new(BufReader::new(f).lines().map_while(Result::ok)),
-        None => Box::new(std::iter::empty()),
-    };
-    lines
-        .filter(|l| !l.is_empty())
-        .filter_map(|l| serde_json::from_str(&l).ok())
+/// How much of the file [`iter_values_rev`] reads at a time.
+const REV_BLOCK_SIZE: usize = 64 * 1024;
+
+/// The values in `path`, front to back. Empty and malformed lines are
+/// skipped; [`Values::stats`] says how many.
+pub fn iter_values(path: &Path) -> Values {
+    let mut stats = JsonlStats::default();
+    let reader = File::open(path)
+        .inspect_err(|e| stats.io_error = Some(e.to_string()))
+        .ok()
+        .map(BufReader::new);
+    Values {
+        reader,
+        line: Vec::new(),
+        stats,
+    }
+}
+
+pub struct Values {
+    reader: Option<BufReader<File>>,
+    line: Vec<u8>,
+    stats: JsonlStats,
+}
+
+impl Values {
+    /// Counts for the lines read so far.
+    pub fn stats(&self) -> &JsonlStats {
+        &self.stats
+    }
+}
+
+impl Iterator for Values {
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Value> {
+        let reader = self.reader.as_mut()?;
+        loop {
+            self.line.clear();
+            match reader.read_until(b'\n', &mut self.line) {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(e) => {
+                    self.stats.io_error = Some(e.to_string());
+                    break;
+                }
+            }
+            let text = clean_line(&self.line);
+            if text.is_empty() {
+                continue;
+            }
+            self.stats.lines_total += 1;
+            match serde_json::from_slice(text) {
+                Ok(value) => {
+                    self.stats.lines_parsed += 1;
+                    return Some(value);
+                }
+                Err(_) => self.stats.lines_skipped += 1,
+            }
+        }
+        self.reader = None;
+        None
+    }
+}
+
+/// What [`read_with_stats`] made of a file, for telling "nothing relevant
+/// happened" apart from "the transcript could not be read".
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct JsonlStats {
+    /// Non-empty lines seen.
+    pub lines_total: usize,
+    pub lines_parsed: usize,
+    pub lines_skipped: usize,
+    /// Why reading stopped early (or never started), if it did.
+    pub io_error: Option<String>,
+}
+
+impl JsonlStats {
+    /// A hint for skip messages when the file looks unreadable rather than
+    /// merely uninteresting.
+    pub fn diagnosis(&self) -> Option<String> {
+        if let Some(e) = &self.io_error {
+            return Some(format!("session could not be read: {e}"));
+        }
+        (self.lines_total > 0 && self.lines_parsed == 0).then(|| {
+            format!(
+                "session had {} lines but {} failed to parse - transcript format may have changed",
+                self.lines_total, self.lines_skipped
+            )
+        })
+    }
+}
+
+impl fmt::Display for JsonlStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} lines, {} parsed, {} skipped",
+            self.lines_total, self.lines_parsed, self.lines_skipped
+        )?;
+        if let Some(e) = &self.io_error {
+            write!(f, ", read error: {e}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Every value in `path` along with what was skipped. A line that is not
+/// UTF-8 is skipped like any other malformed line.
+pub fn read_with_stats(path: &Path) -> (Vec<Value>, JsonlStats) {
+    let mut values = iter_values(path);
+    let collected = values.by_ref().collect();
+    (collected, values.stats)
+}
+
+/// Like [`iter_values`], but from the last line to the first, reading the
+/// file backwards in blocks so a long session is never loaded whole.
+pub fn iter_values_rev(path: &Path) -> impl Iterator<Item = Value> {
+    iter_values_rev_with(path, REV_BLOCK_SIZE)
+}
+
+fn iter_values_rev_with(path: &Path, block_size: usize) -> impl Iterator<Item = Value> {
+    let lines = File::open(path).ok().and_then(|mut file| {
+        let end = file.seek(SeekFrom::End(0)).ok()?;
+        Some(RevLines {
+            file,
+            pos: end,
+            pending: Vec::new(),
+            block_size: block_size.max(1),
+        })
+    });
+    lines.into_iter().flatten().filter_map(|l| {
+        let text = clean_line(&l);
+        (!text.is_empty())
+            .then(|| serde_json::from_slice(text).ok())
+            .flatten()
+    })
+}
+
+const BOM: &[u8] = b"\xef\xbb\xbf";
+
+/// `line` without a UTF-8 BOM (transcripts saved by Windows editors start
+/// with one) or surrounding whitespace, including the `\r` of CRLF endings.
+fn clean_line(line: &[u8]) -> &[u8] {
+    let line = line.trim_ascii();
+    line.strip_prefix(BOM).unwrap_or(line).trim_ascii()
+}
+
+/// Lines of a file, last first. `pending` holds the bytes from `pos` that
+/// have been read but not yet returned.
+struct RevLines {
+    file: File,
+    pos: u64,
+    pending: Vec<u8>,
+    block_size: usize,
+}
+
+impl Iterator for RevLines {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Vec<u8>> {
+        loop {
+            if let Some(newline) = self.pending.iter().rposition(|&b| b == b'\n') {
+                let line = self.pending.split_off(newline + 1);
+                self.pending.truncate(newline);
+                return Some(line);
+            }
+            if self.pos == 0 {
+                return (!self.pending.is_empty()).then(|| std::mem::take(&mut self.pending));
+            }
+            let start = self.pos.saturating_sub(self.block_size as u64);
+            let mut block = vec![0; (self.pos - start) as usize];
+            if self.file.seek(SeekFrom::Start(start)).is_err()
+                || self.file.read_exact(&mut block).is_err()
+            {
+                return None;
+            }
+            block.append(&mut self.pending);
+            self.pending = block;
+            self.pos = start;
+        }
+    }
+}
+
+/// One line of a Claude Code transcript, by the shapes claude-idr reads.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionEntry {
+    User(Message),
+    Assistant(Message),
+    /// `{"type":"summary","summary":...}`, written when a session is compacted.
+    Summary(String),
+    /// A message whose `type` is missing or unfamiliar; its blocks still count.
+    Other(Message),
+    Unknown(Value),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    /// `"content": "..."`, how typed user prompts are stored.
+    Text(String),
+    Blocks(Vec<ContentBlock>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ContentBlock {
+    Text(String),
+    ToolUse {
+        name: String,
+        input: Value,
+    },
+    /// A tool's output; `is_error` when the tool reported a failure.
+    ToolResult {
+        content: Value,
+        is_error: bool,
+    },
+    Unknown(Value),
+}
+
+impl SessionEntry {
+    fn message(&self) -> Option<&Message> {
+        match self {
+            SessionEntry::User(m) | SessionEntry::Assistant(m) | SessionEntry::Other(m) => Some(m),
+            SessionEntry::Summary(_) | SessionEntry::Unknown(_) => None,
+        }
+    }
+
+    /// The content blocks of the message; empty for plain-text messages.
+    pub fn blocks(&self) -> &[ContentBlock] {
+        match self.message() {
+            Some(Message::Blocks(blocks)) => blocks,
+            _ => &[],
+        }
+    }
+
+    /// The tool calls in this entry, as `(name, input)`.
+    pub fn tool_uses(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.blocks().iter().filter_map(|block| match block {
+            ContentBlock::ToolUse { name, input } => Some((name.as_str(), input)),
+            _ => None,
+        })
+    }
+
+    /// Whether any tool call in this entry is named `name`.
+    pub fn uses_tool(&self, name: &str) -> bool {
+        self.blocks().iter().any(|b| b.tool_name() == Some(name))
+    }
+
+    /// What the user typed, for user entries stored as plain text. Block
+    /// content (tool results, pasted images) is not a request.
+    pub fn user_text(&self) -> Option<&str> {
+        match self {
+            SessionEntry::User(Message::Text(text)) => Some(text),
+            _ => None,
+        }
+    }
+}
+
+impl ContentBlock {
+    pub fn tool_name(&self) -> Option<&str> {
+        match self {
+            ContentBlock::ToolUse { name, .. } => Some(name),
+            _ => None,
+        }
+    }
+}
+
+impl From<Value> for SessionEntry {
+    fn from(value: Value) -> SessionEntry {
+        let kind = value.get("type").and_then(Value::as_str);
+        if kind == Some("summary")
+            && let Some(summary) = value.get("summary").and_then(Value::as_str)
+        {
+            return SessionEntry::Summary(summary.to_string());
+        }
+        let Some(message) = value
+            .pointer("/message/content")
+            .and_then(Message::from_content)
+        else {
+            return SessionEntry::Unknown(value);
+        };
+        match kind {
+            Some("user") => SessionEntry::User(message),
+            Some("assistant") => SessionEntry::Assistant(message),
+            _ => SessionEntry::Other(message),
+        }
+    }
+}
+
+impl Message {
+    fn from_content(content: &Value) -> Option<Message> {
+        match content {
+            Value::String(text) => Some(Message::Text(text.clone())),
+            Value::Array(items) => Some(Message::Blocks(
+                items.iter().cloned().map(ContentBlock::from).collect(),
+            )),
+            _ => None,
+        }
+    }
+}
+
+impl From<Value> for ContentBlock {
+    /// Blocks without a `type` still count as tool calls when they carry a
+    /// `name`, as older transcripts wrote them.
+    fn from(value: Value) -> ContentBlock {
+        let kind = value.get("type").and_then(Value::as_str);
+        if matches!(kind, None | Some("tool_use"))
+            && let Some(name) = value.get("name").and_then(Value::as_str)
+        {
+            return ContentBlock::ToolUse {
+                name: name.to_string(),
+                input: value.get("input").cloned().unwrap_or(Value::Null),
+            };
+        }
+        match kind {
+            Some("text") => match value.get("text").and_then(Value::as_str) {
+                Some(text) => ContentBlock::Text(text.to_string()),
+                None => ContentBlock::Unknown(value),
+            },
+            Some("tool_result") => ContentBlock::ToolResult {
+                content: value.get("content").cloned().unwrap_or(Value::Null),
+                is_error: value.get("is_error").and_then(Value::as_bool) == Some(true),
+            },
+            _ => ContentBlock::Unknown(value),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for SessionEntry {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Value::deserialize(deserializer).map(SessionEntry::from)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use serde_json::json;
     use std::io::Write;
     use tempfile::TempDir;
 
@@ -51,6 +375,258 @@ mod tests {
         assert!(values.is_empty());
     }
 
+    fn write_lines(dir: &TempDir, text: &str) -> std::path::PathBuf {
+        let path = dir.path().join("test.jsonl");
+        std::fs::write(&path, text).unwrap();
+        path
+    }
+
+    #[test]
+    fn iter_values_rev_yields_last_line_first() {
+        let dir = TempDir::new().unwrap();
+        let path = write_lines(&dir, "{\"n\":1}\nnot json\n\n{\"n\":2}\n{\"n\":3}\n");
+
+        let values: Vec<Value> = iter_values_rev(&path).collect();
+
+        assert_eq!(
+            values,
+            vec![json!({"n": 3}), json!({"n": 2}), json!({"n": 1})]
+        );
+    }
+
+    #[test]
+    fn iter_values_rev_joins_lines_across_blocks() {
+        let dir = TempDir::new().unwrap();
+        let long = "x".repeat(50);
+        let path = write_lines(
+            &dir,
+            &format!("{{\"a\":\"{long}\"}}\r\n{{\"b\":1}}\n{{\"c\":\"{long}\"}}"),
+        );
+
+        let values: Vec<Value> = iter_values_rev_with(&path, 7).collect();
+
+        assert_eq!(
+            values,
+            vec![json!({"c": long}), json!({"b": 1}), json!({"a": long})]
+        );
+    }
+
+    #[test]
+    fn iter_values_rev_returns_empty_for_nonexistent_and_empty() {
+        let dir = TempDir::new().unwrap();
+        let empty = write_lines(&dir, "");
+
+        assert_eq!(iter_values_rev(Path::new("/nonexistent")).count(), 0);
+        assert_eq!(iter_values_rev(&empty).count(), 0);
+    }
+
+    #[test]
+    fn iter_values_rev_matches_forward_order_reversed() {
+        let dir = TempDir::new().unwrap();
+        // A small LCG keeps the cases varied but reproducible.
+        let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = |bound: u64| {
+            seed = seed.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1);
+            (seed >> 33) % bound
+        };
+        for case in 0..200 {
+            let mut text = String::new();
+            for i in 0..next(12) {
+                match next(5) {
+                    0 => text.push('\n'),
+                    1 => text.push_str("{broken\n"),
+                    _ => text.push_str(&format!(
+                        "{{\"i\":{i},\"pad\":\"{}\"}}\n",
+                        "p".repeat(next(40) as usize)
+                    )),
+                }
+            }
+            if next(2) == 0 {
+                text.pop();
+            }
+            let path = write_lines(&dir, &text);
+            let block_size = 1 + next(32) as usize;
+
+            let mut reversed: Vec<Value> = iter_values_rev_with(&path, block_size).collect();
+            reversed.reverse();
+
+            let forward: Vec<Value> = iter_values(&path).collect();
+            assert_eq!(
+                reversed, forward,
+                "case {case}, block {block_size}: {text:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn read_with_stats_counts_mixed_validity() {
+        let dir = TempDir::new().unwrap();
+        let path = write_lines(&dir, "{\"a\":1}\n\nnot json\n{\"b\":2}\n{broken");
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"\n\xff\xfe\n{\"c\":3}\n")
+            .unwrap();
+
+        let (values, stats) = read_with_stats(&path);
+
+        assert_eq!(
+            values,
+            vec![json!({"a": 1}), json!({"b": 2}), json!({"c": 3})]
+        );
+        assert_eq!(
+            stats,
+            JsonlStats {
+                lines_total: 6,
+                lines_parsed: 3,
+                lines_skipped: 3,
+                io_error: None,
+            }
+        );
+        assert_eq!(stats.diagnosis(), None);
+        assert_eq!(stats.to_string(), "6 lines, 3 parsed, 3 skipped");
+    }
+
+    #[test]
+    fn read_with_stats_diagnoses_unparsable_transcript() {
+        let dir = TempDir::new().unwrap();
+        let path = write_lines(&dir, "<xml/>\n<xml/>\n");
+
+        let (values, stats) = read_with_stats(&path);
+
+        assert!(values.is_empty());
+        assert_eq!(
+            stats.diagnosis().as_deref(),
+            Some("session had 2 lines but 2 failed to parse - transcript format may have changed")
+        );
+    }
+
+    #[test]
+    fn read_with_stats_reports_unreadable_path() {
+        let dir = TempDir::new().unwrap();
+
+        let (values, stats) = read_with_stats(dir.path());
+        let (_, missing) = read_with_stats(&dir.path().join("missing.jsonl"));
+
+        assert!(values.is_empty());
+        assert!(stats.io_error.is_some(), "a directory is not a transcript");
+        assert!(
+            missing
+                .diagnosis()
+                .unwrap()
+                .starts_with("session could not be read: ")
+        );
+    }
+
+    const CLEAN: &str = "{\"a\":1}\n{\"b\":[1,2]}\n{\"c\":\"end\"}\n";
+
+    #[test]
+    fn iter_values_tolerates_bom_crlf_and_unterminated_line() {
+        let dir = TempDir::new().unwrap();
+        let clean = write_lines(&dir, CLEAN);
+        let expected: Vec<Value> = iter_values(&clean).collect();
+        let messy_path = dir.path().join("messy.jsonl");
+        let messy = format!("\u{feff}{}", CLEAN.replace('\n', "\r\n"));
+        std::fs::write(&messy_path, messy.trim_end()).unwrap();
+
+        assert_eq!(expected.len(), 3);
+        assert_eq!(iter_values(&messy_path).collect::<Vec<_>>(), expected);
+        let mut reversed: Vec<Value> = iter_values_rev(&messy_path).collect();
+        reversed.reverse();
+        assert_eq!(reversed, expected);
+    }
+
+    #[test]
+    fn iter_values_skips_only_a_truncated_final_line() {
+        let dir = TempDir::new().unwrap();
+        let path = write_lines(&dir, &format!("{CLEAN}{{\"d\":\"cut off"));
+
+        let (values, stats) = read_with_stats(&path);
+
+        assert_eq!(values.len(), 3);
+        assert_eq!(stats.lines_skipped, 1);
+        assert_eq!(iter_values_rev(&path).count(), 3);
+    }
+
+    fn entry(line: &str) -> SessionEntry {
+        serde_json::from_str(line).unwrap()
+    }
+
+    #[test]
+    fn session_entry_reads_user_text() {
+        let user = entry(r#"{"type":"user","message":{"role":"user","content":"fix the bug"}}"#);
+
+        assert_eq!(
+            user,
+            SessionEntry::User(Message::Text("fix the bug".to_string()))
+        );
+        assert_eq!(user.user_text(), Some("fix the bug"));
+        assert!(user.blocks().is_empty());
+    }
+
+    #[test]
+    fn session_entry_reads_array_content_user_message() {
+        let user = entry(
+            r#"{"type":"user","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"t1","content":"ok"},{"type":"tool_result","tool_use_id":"t2","content":"boom","is_error":true},{"type":"text","text":"and then?"}]}}"#,
+        );
+
+        assert_eq!(
+            user.blocks(),
+            [
+                ContentBlock::ToolResult {
+                    content: json!("ok"),
+                    is_error: false,
+                },
+                ContentBlock::ToolResult {
+                    content: json!("boom"),
+                    is_error: true,
+                },
+                ContentBlock::Text("and then?".to_string())
+            ]
+        );
+        assert_eq!(user.user_text(), None);
+    }
+
+    #[test]
+    fn session_entry_reads_assistant_tool_uses() {
+        let assistant = entry(
+            r#"{"type":"assistant","message":{"content":[{"type":"text","text":"Writing"},{"type":"tool_use","id":"t1","name":"Write","input":{"file_path":"a.rs"}},{"type":"thinking","thinking":"..."}]}}"#,
+        );
+
+        assert!(matches!(assistant, SessionEntry::Assistant(_)));
+        assert_eq!(
+            assistant.tool_uses().collect::<Vec<_>>(),
+            vec![("Write", &json!({"file_path": "a.rs"}))]
+        );
+        assert_eq!(assistant.blocks()[1].tool_name(), Some("Write"));
+        assert!(matches!(assistant.blocks()[2], ContentBlock::Unknown(_)));
+        assert!(assistant.uses_tool("Write"));
+        assert!(!assistant.uses_tool("Edit"));
+    }
+
+    #[test]
+    fn session_entry_reads_summary_and_unknown_shapes() {
+        assert_eq!(
+            entry(r#"{"type":"summary","summary":"Auth rework","leafUuid":"x"}"#),
+            SessionEntry::Summary("Auth rework".to_string())
+        );
+        let meta = json!({"type":"file-history-snapshot","snapshot":{}});
+        assert_eq!(
+            SessionEntry::from(meta.clone()),
+            SessionEntry::Unknown(meta)
+        );
+    }
+
+    #[test]
+    fn session_entry_accepts_untyped_tool_blocks() {
+        let untyped =
+            entry(r#"{"message":{"content":[{"name":"Edit","input":{"file_path":"b.rs"}}]}}"#);
+
+        assert!(matches!(untyped, SessionEntry::Other(_)));
+        assert!(untyped.uses_tool("Edit"));
+    }
+
     #[test]
     fn iter_values_skips_empty_lines() {
         let dir = TempDir::new().unwrap();
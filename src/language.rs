@@ -0,0 +1,249 @@
+//! `language: "auto"`: Japanese or English, by the script the user wrote
+//! the session's requests in; and `ja+en`, a record in one language with a
+//! translation into the other.
+
+use crate::config::Config;
+use crate::log::verbose;
+use crate::title;
+
+pub const AUTO: &str = "auto";
+
+/// Below this many counted letters, requests say too little to decide.
+const MIN_LETTERS: usize = 12;
+/// A kana or kanji carries about as much as this many Latin letters.
+const JAPANESE_WEIGHT: usize = 2;
+/// Japanese share of the weighted letters at or above which requests count
+/// as Japanese; English needs the mirror share.
+const THRESHOLD: f64 = 0.6;
+
+/// `ja` or `en` for requests written mostly in that language; `None` when
+/// they are mixed, too short, or nothing but code. Code blocks, inline code,
+/// and words with digits or symbols in them (paths, identifiers) are not
+/// counted.
+pub fn classify<S: AsRef<str>>(requests: &[S]) -> Option<&'static str> {
+    let (mut japanese, mut latin) = (0, 0);
+    for request in requests {
+        let prose = strip_code(request.as_ref());
+        japanese += prose.chars().filter(|&c| is_japanese(c)).count();
+        latin += prose
+            .split(|c: char| c.is_whitespace() || is_japanese(c) || is_cjk_punctuation(c))
+            .map(|word| word.trim_matches(|c: char| c.is_ascii_punctuation()))
+            .filter(|word| word.chars().all(char::is_alphabetic))
+            .map(|word| word.chars().count())
+            .sum::<usize>();
+    }
+    if japanese + latin < MIN_LETTERS {
+        return None;
+    }
+    let weighted = (japanese * JAPANESE_WEIGHT) as f64;
+    let share = weighted / (weighted + latin as f64);
+    if share >= THRESHOLD {
+        Some("ja")
+    } else if share <= 1.0 - THRESHOLD {
+        Some("en")
+    } else {
+        None
+    }
+}
+
+/// `ja+en` as `("ja", Some("en"))`; a single language has no second.
+pub fn split(language: &str) -> (&str, Option<&str>) {
+    match language.split_once('+') {
+        Some((first, second)) => (first.trim(), Some(second.trim())),
+        None => (language, None),
+    }
+}
+
+/// `config` with `language` settled to the one the record is written in:
+/// `auto` from the session's user requests, or from `language_fallback`
+/// when they do not decide it, and only the first of `ja+en`. `None` when
+/// `language` is a single language already.
+pub fn resolve(config: &Config, requests: &[String]) -> Option<Config> {
+    let (first, second) = split(&config.language);
+    if first != AUTO {
+        return second.is_some().then(|| Config {
+            language: first.to_string(),
+            ..config.clone()
+        });
+    }
+    let typed: Vec<&str> = requests
+        .iter()
+        .map(String::as_str)
+        .filter(|request| title::substantive_request(request))
+        .collect();
+    let language = match classify(&typed) {
+        Some(language) => {
+            verbose!(
+                "language: {language} (detected from {} requests)",
+                typed.len()
+            );
+            language.to_string()
+        }
+        None => {
+            verbose!("language: {} (fallback)", config.language_fallback);
+            config.language_fallback.clone()
+        }
+    };
+    Some(Config {
+        language,
+        ..config.clone()
+    })
+}
+
+/// Replaces `auto` with `language_fallback` and drops the second of
+/// `ja+en`, for commands that have no session to detect from and write in
+/// one language.
+pub fn settle(config: &mut Config) {
+    let first = split(&config.language).0.to_string();
+    config.language = if first == AUTO {
+        config.language_fallback.clone()
+    } else {
+        first
+    };
+}
+
+/// `text` without fenced code blocks or inline code spans.
+fn strip_code(text: &str) -> String {
+    let mut prose = String::new();
+    let mut in_fence = false;
+    for line in text.lines() {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+        // Odd-numbered pieces between backticks are inline code.
+        for (i, piece) in line.split('`').enumerate() {
+            if i % 2 == 0 {
+                prose.push_str(piece);
+                prose.push(' ');
+            }
+        }
+        prose.push('\n');
+    }
+    prose
+}
+
+/// Hiragana, katakana (full and half width), and CJK ideographs.
+fn is_japanese(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{309F}'
+        | '\u{30A0}'..='\u{30FF}'
+        | '\u{31F0}'..='\u{31FF}'
+        | '\u{FF66}'..='\u{FF9F}'
+        | '\u{3400}'..='\u{4DBF}'
+        | '\u{4E00}'..='\u{9FFF}'
+        | '\u{F900}'..='\u{FAFF}'
+        | '々')
+}
+
+/// Japanese punctuation and full-width forms, which separate words like
+/// spaces do.
+fn is_cjk_punctuation(c: char) -> bool {
+    matches!(c, '\u{3000}'..='\u{303F}' | '\u{FF00}'..='\u{FF65}')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn auto(fallback: &str) -> Config {
+        Config {
+            language: AUTO.to_string(),
+            language_fallback: fallback.to_string(),
+            ..Config::default()
+        }
+    }
+
+    fn requests(texts: &[&str]) -> Vec<String> {
+        texts.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn classify_recognizes_japanese_requests() {
+        let asked = [
+            "認証をJWTに移行してください",
+            "リフレッシュトークンの有効期限は7日にしたい",
+        ];
+        assert_eq!(classify(&asked), Some("ja"));
+    }
+
+    #[test]
+    fn classify_recognizes_english_requests() {
+        let asked = [
+            "Please migrate authentication to JWT",
+            "Refresh tokens should expire after seven days.",
+        ];
+        assert_eq!(classify(&asked), Some("en"));
+    }
+
+    #[test]
+    fn classify_leaves_mixed_requests_undecided() {
+        let asked = ["Add tests too", "認証をJWTに移行して"];
+        assert_eq!(classify(&asked), None);
+    }
+
+    #[test]
+    fn classify_ignores_code_paths_and_identifiers() {
+        let asked = [
+            "src/api.rs の retry_count を 5 に変更",
+            "`cargo test --workspace` が落ちる",
+            "```\nfn main() { println!(\"hello world from the English code\"); }\n```\n直して",
+        ];
+        assert_eq!(classify(&asked), Some("ja"));
+
+        let only_code = [
+            "`make build`",
+            "src/lib.rs",
+            "```\nlet x = compute_all(y);\n```",
+        ];
+        assert_eq!(classify(&only_code), None);
+        assert_eq!(classify::<&str>(&[]), None);
+    }
+
+    #[test]
+    fn resolve_settles_auto_from_requests_or_the_fallback() {
+        let english = requests(&[
+            "Add exponential backoff to the API client",
+            "<command-name>/compact</command-name>",
+            "ok",
+        ]);
+        let japanese = requests(&["APIクライアントに指数バックオフを追加して", "ok"]);
+
+        assert_eq!(resolve(&auto("ja"), &english).unwrap().language, "en");
+        assert_eq!(resolve(&auto("en"), &japanese).unwrap().language, "ja");
+        assert_eq!(
+            resolve(&auto("en"), &requests(&["ok"])).unwrap().language,
+            "en"
+        );
+        assert!(resolve(&Config::default(), &english).is_none());
+
+        let mut config = auto("en");
+        settle(&mut config);
+        assert_eq!(config.language, "en");
+    }
+
+    #[test]
+    fn resolve_and_settle_keep_the_first_of_two_languages() {
+        let bilingual = Config {
+            language: "ja+en".to_string(),
+            ..Config::default()
+        };
+        let english = requests(&["Add exponential backoff to the API client"]);
+
+        assert_eq!(split("ja+en"), ("ja", Some("en")));
+        assert_eq!(split("en"), ("en", None));
+        assert_eq!(resolve(&bilingual, &english).unwrap().language, "ja");
+        let detected = Config {
+            language: "auto+ja".to_string(),
+            ..Config::default()
+        };
+        assert_eq!(resolve(&detected, &english).unwrap().language, "en");
+
+        let mut config = bilingual.clone();
+        settle(&mut config);
+        assert_eq!(config.language, "ja");
+    }
+}
@@ -0,0 +1,166 @@
+//! `latest.md` in an output directory: a stable path to the record written
+//! last, for editor bindings and notifiers.
+
+use crate::config::LatestMode;
+use crate::log::warn;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub const FILE_NAME: &str = "latest.md";
+
+/// Points `dir/latest.md` at `record`, replacing whatever was there (including
+/// a link to a deleted file). Symlink mode falls back to a copy when the
+/// platform or filesystem refuses symlinks.
+pub fn update(dir: &Path, record: &Path, mode: LatestMode) -> io::Result<()> {
+    let name = record
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "record has no file name"))?;
+    let latest = dir.join(FILE_NAME);
+    let tmp = dir.join(format!(".{FILE_NAME}.tmp"));
+    let _ = fs::remove_file(&tmp);
+
+    if mode == LatestMode::Symlink && symlink(Path::new(name), &tmp).is_ok() {
+        return fs::rename(&tmp, &latest);
+    }
+    let name = name.to_string_lossy();
+    let content = fs::read_to_string(record)?;
+    fs::write(&tmp, format!("> Latest: [{name}]({name})\n\n{content}"))
+        .and_then(|()| fs::rename(&tmp, &latest))
+        .inspect_err(|_| {
+            let _ = fs::remove_file(&tmp);
+        })
+}
+
+/// Like [`update`], but only warns.
+pub fn update_warn(dir: &Path, record: &Path, mode: LatestMode) {
+    if let Err(e) = update(dir, record, mode) {
+        warn!("cannot update {}: {e}", dir.join(FILE_NAME).display());
+    }
+}
+
+/// The record `dir/latest.md` points at, if it still exists.
+pub fn target(dir: &Path) -> Option<PathBuf> {
+    let latest = dir.join(FILE_NAME);
+    let name = match fs::read_link(&latest) {
+        Ok(target) => target,
+        Err(_) => {
+            let text = fs::read_to_string(&latest).ok()?;
+            let line = text.lines().next()?.strip_prefix("> Latest: [")?;
+            PathBuf::from(line.split_once("](")?.0)
+        }
+    };
+    let record = dir.join(name);
+    record.is_file().then_some(record)
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn symlink(target: &Path, link: &Path) -> io::Result<()> {
+    // Needs Developer Mode or an elevated shell; otherwise we copy.
+    std::os::windows::fs::symlink_file(target, link)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn symlink(_target: &Path, _link: &Path) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn record(dir: &Path, name: &str, body: &str) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, body).unwrap();
+        path
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn update_creates_relative_symlink() {
+        let dir = TempDir::new().unwrap();
+        let first = record(dir.path(), "idr-01.md", "first");
+
+        update(dir.path(), &first, LatestMode::Symlink).unwrap();
+
+        let latest = dir.path().join(FILE_NAME);
+        let target = fs::read_link(&latest).unwrap();
+        assert_eq!(target, Path::new("idr-01.md"));
+        assert!(target.is_relative());
+        assert_eq!(fs::read_to_string(&latest).unwrap(), "first");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn update_replaces_stale_symlink() {
+        let dir = TempDir::new().unwrap();
+        let first = record(dir.path(), "idr-01.md", "first");
+        update(dir.path(), &first, LatestMode::Symlink).unwrap();
+        fs::remove_file(&first).unwrap();
+        let second = record(dir.path(), "idr-02.md", "second");
+
+        update(dir.path(), &second, LatestMode::Symlink).unwrap();
+
+        let latest = dir.path().join(FILE_NAME);
+        assert_eq!(fs::read_link(&latest).unwrap(), Path::new("idr-02.md"));
+        assert_eq!(fs::read_to_string(&latest).unwrap(), "second");
+    }
+
+    #[test]
+    fn update_copies_in_copy_mode() {
+        let dir = TempDir::new().unwrap();
+        let first = record(dir.path(), "idr-01.md", "first");
+        update(dir.path(), &first, LatestMode::Copy).unwrap();
+        let second = record(dir.path(), "idr-02-use-jwt.md", "# IDR: Use JWT\n");
+
+        update(dir.path(), &second, LatestMode::Copy).unwrap();
+
+        let latest = dir.path().join(FILE_NAME);
+        assert!(!fs::symlink_metadata(&latest).unwrap().is_symlink());
+        assert_eq!(
+            fs::read_to_string(&latest).unwrap(),
+            "> Latest: [idr-02-use-jwt.md](idr-02-use-jwt.md)\n\n# IDR: Use JWT\n"
+        );
+        assert!(!dir.path().join(format!(".{FILE_NAME}.tmp")).exists());
+    }
+
+    #[test]
+    fn target_follows_symlink_or_copy_header() {
+        let dir = TempDir::new().unwrap();
+        assert_eq!(target(dir.path()), None);
+        let first = record(dir.path(), "idr-01-use-jwt.md", "first");
+
+        update(dir.path(), &first, LatestMode::Copy).unwrap();
+        assert_eq!(target(dir.path()), Some(first.clone()));
+
+        update(dir.path(), &first, LatestMode::Symlink).unwrap();
+        assert_eq!(target(dir.path()), Some(first.clone()));
+
+        fs::remove_file(&first).unwrap();
+        assert_eq!(target(dir.path()), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn update_replaces_symlink_with_copy() {
+        let dir = TempDir::new().unwrap();
+        let first = record(dir.path(), "idr-01.md", "first");
+        update(dir.path(), &first, LatestMode::Symlink).unwrap();
+
+        update(dir.path(), &first, LatestMode::Copy).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&first).unwrap(),
+            "first",
+            "the record behind the old symlink must not be overwritten"
+        );
+        let latest = dir.path().join(FILE_NAME);
+        assert!(!fs::symlink_metadata(&latest).unwrap().is_symlink());
+    }
+}
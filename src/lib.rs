@@ -0,0 +1,124 @@
+//! Implementation Decision Records from staged git diffs and the Claude Code
+//! session that produced them.
+//!
+//! [`generate`] runs the same pipeline as `claude-idr` with no subcommand;
+//! the modules below expose its building blocks for tools that need only
+//! part of it. Everything marked hidden exists for the `claude-idr` binary
+//! and may change without notice.
+
+/// Claude calls through the CLI or the Messages API.
+pub mod claude;
+/// `~/.config/claude-idr/config.json` and its defaults.
+pub mod config;
+/// What a session was about: changed files, user requests, commands.
+pub mod context;
+/// The git commands claude-idr runs in the current directory.
+pub mod git;
+/// Output directories, record file names, and writing records.
+pub mod path;
+/// The prompts sent to claude and the fallback text used without them.
+pub mod prompt;
+/// Finding the Claude Code session behind a commit.
+pub mod session;
+
+#[doc(hidden)]
+pub mod backfill;
+#[doc(hidden)]
+pub mod ci;
+#[doc(hidden)]
+pub mod clean;
+#[doc(hidden)]
+pub mod clipboard;
+#[doc(hidden)]
+pub mod digest;
+#[doc(hidden)]
+pub mod doctor;
+#[doc(hidden)]
+pub mod export;
+#[doc(hidden)]
+pub mod failures;
+#[doc(hidden)]
+pub mod hook;
+#[doc(hidden)]
+pub mod ignore;
+#[doc(hidden)]
+pub mod index;
+#[doc(hidden)]
+pub mod language;
+#[doc(hidden)]
+pub mod lock;
+#[doc(hidden)]
+pub mod log;
+#[doc(hidden)]
+pub mod notes;
+#[doc(hidden)]
+pub mod notify;
+#[doc(hidden)]
+pub mod post_generate;
+#[doc(hidden)]
+pub mod pr;
+#[doc(hidden)]
+pub mod publish;
+#[doc(hidden)]
+pub mod record;
+#[doc(hidden)]
+pub mod regenerate;
+#[doc(hidden)]
+pub mod renumber;
+#[doc(hidden)]
+pub mod runs;
+#[doc(hidden)]
+pub mod search;
+#[doc(hidden)]
+pub mod serve;
+#[doc(hidden)]
+pub mod settings;
+#[doc(hidden)]
+pub mod show;
+#[doc(hidden)]
+pub mod sidecar;
+#[doc(hidden)]
+pub mod signal;
+#[doc(hidden)]
+pub mod sow;
+#[doc(hidden)]
+pub mod stats;
+#[doc(hidden)]
+pub mod trailer;
+
+mod anonymize;
+mod api;
+mod authorship;
+mod crosslink;
+mod diff;
+mod diff_filter;
+mod docsite;
+mod effort;
+mod error;
+mod front_matter;
+mod generate;
+mod history;
+mod issues;
+mod jsonl;
+mod latest;
+mod metadata;
+mod monthly;
+mod noise;
+mod obsidian;
+mod pattern;
+mod redact;
+mod sanitize;
+mod sha256;
+mod shell;
+mod split;
+mod summarize;
+#[cfg(test)]
+mod testutil;
+mod timing;
+mod title;
+mod translate;
+mod validate;
+
+pub use error::IdrError;
+pub use generate::{Gate, GenerateOptions, GenerateReport, generate};
+pub use timing::{PhaseTiming, Timing};
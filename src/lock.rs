@@ -0,0 +1,469 @@
+//! `.idr.lock`: keeps concurrent runs from interleaving number allocation,
+//! index regeneration, and `latest.md` updates in one output directory.
+//! The run lock keeps two generations for the same work tree from running
+//! (and spending tokens) at once.
+
+use crate::log::{verbose, warn};
+use crate::sha256;
+use crate::signal;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub const FILE_NAME: &str = ".idr.lock";
+/// How long to wait for another run before going ahead unlocked.
+const WAIT: Duration = Duration::from_secs(5);
+const POLL: Duration = Duration::from_millis(50);
+/// A lock this old is left over from a run that never cleaned up.
+const STALE_AFTER: Duration = Duration::from_secs(60);
+/// The takeover guard is held only while one stale lock is removed, so an
+/// older one was left by a process that died in between.
+const GUARD_STALE_AFTER: Duration = Duration::from_secs(10);
+
+/// A held lock; the file is removed on drop, or by the signal handler if
+/// the run is interrupted.
+pub struct DirLock {
+    path: PathBuf,
+    tracked: Option<signal::FileGuard>,
+}
+
+impl DirLock {
+    fn new(path: PathBuf) -> DirLock {
+        let tracked = Some(signal::track_file(&path));
+        DirLock { path, tracked }
+    }
+}
+
+impl std::fmt::Debug for DirLock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DirLock").field("path", &self.path).finish()
+    }
+}
+
+impl Drop for DirLock {
+    fn drop(&mut self) {
+        // Unregister first, so an interrupt cannot remove a lock another
+        // run takes once this one is gone.
+        self.tracked.take();
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Locks `dir`, or warns and returns `None` so the caller carries on
+/// unlocked: a busy or read-only directory must never block a commit.
+pub fn acquire_warn(dir: &Path) -> Option<DirLock> {
+    acquire(dir, WAIT, STALE_AFTER)
+        .inspect_err(|e| {
+            warn!(
+                "cannot lock {}: {e}; continuing without the lock",
+                dir.display()
+            );
+        })
+        .ok()
+}
+
+fn acquire(dir: &Path, wait: Duration, stale_after: Duration) -> io::Result<DirLock> {
+    fs::create_dir_all(dir)?;
+    acquire_file(dir.join(FILE_NAME), wait, Some(stale_after))
+}
+
+/// Outcome of [`acquire_run`].
+#[derive(Debug)]
+pub enum RunLock {
+    /// This process holds the lock until the value is dropped.
+    Held(DirLock),
+    /// Another live run holds it; `pid` is `None` if it has not been
+    /// written yet.
+    Busy { pid: Option<u32> },
+    /// The lock file could not be created; the run goes ahead unlocked.
+    Unavailable,
+}
+
+/// Takes the run lock for the work tree at `root`, waiting up to `wait` for
+/// another run to finish. Unlike the directory lock it never expires by age,
+/// since a run lasts as long as claude takes; only a lock whose process is
+/// gone is taken over. A lock without a pid, and any lock where liveness
+/// cannot be checked, expires after the directory lock's threshold instead.
+pub fn acquire_run(root: &Path, wait: Duration) -> RunLock {
+    acquire_run_at(&run_lock_path(root), wait)
+}
+
+/// `claude-idr-<hash>.lock` in `$XDG_RUNTIME_DIR` (the temp directory where
+/// there is none), one per canonical work tree path.
+pub fn run_lock_path(root: &Path) -> PathBuf {
+    let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+    let hash = sha256::hex(root.as_os_str().as_encoded_bytes());
+    dirs::runtime_dir()
+        .unwrap_or_else(env::temp_dir)
+        .join(format!("claude-idr-{}.lock", &hash[..16]))
+}
+
+fn acquire_run_at(path: &Path, wait: Duration) -> RunLock {
+    let stale_after = (!cfg!(any(unix, windows))).then_some(STALE_AFTER);
+    match acquire_file(path.to_path_buf(), wait, stale_after) {
+        Ok(lock) => RunLock::Held(lock),
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock => RunLock::Busy {
+            pid: read_pid(path),
+        },
+        Err(e) => {
+            warn!(
+                "cannot take run lock {}: {e}; continuing without it",
+                path.display()
+            );
+            RunLock::Unavailable
+        }
+    }
+}
+
+/// Creates `path` exclusively, polling until `wait` runs out. A lock whose
+/// process is gone, or (with `stale_after`) that is older than that, is
+/// taken over.
+fn acquire_file(
+    path: PathBuf,
+    wait: Duration,
+    stale_after: Option<Duration>,
+) -> io::Result<DirLock> {
+    let deadline = SystemTime::now() + wait;
+    loop {
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                let lock = DirLock::new(path);
+                writeln!(file, "{} {}", std::process::id(), now_secs())?;
+                return Ok(lock);
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(e),
+        }
+        if is_stale(&path, stale_after) && take_over(&path, stale_after) {
+            continue;
+        }
+        if SystemTime::now() >= deadline {
+            return Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                format!("{} is held by another run", path.display()),
+            ));
+        }
+        thread::sleep(POLL);
+    }
+}
+
+/// Removes the stale lock at `path` while holding `<path>.takeover`, and
+/// only if it is still stale once the guard is held. Of several runs that
+/// found the same lock stale, the first removes it and the others then
+/// find it gone or replaced by the winner's live lock, so none of them can
+/// delete a lock another has just taken. Returns whether it was removed.
+fn take_over(path: &Path, stale_after: Option<Duration>) -> bool {
+    let mut guard = path.as_os_str().to_owned();
+    guard.push(".takeover");
+    let guard = PathBuf::from(guard);
+    match OpenOptions::new().write(true).create_new(true).open(&guard) {
+        Ok(_) => {
+            let _guard = DirLock::new(guard);
+            if !is_stale(path, stale_after) {
+                return false;
+            }
+            verbose!("taking over stale lock {}", path.display());
+            fs::remove_file(path).is_ok()
+        }
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+            let abandoned = fs::metadata(&guard)
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|mtime| mtime.elapsed().ok())
+                .is_some_and(|age| age > GUARD_STALE_AFTER);
+            if abandoned {
+                let _ = fs::remove_file(&guard);
+            }
+            false
+        }
+        Err(_) => false,
+    }
+}
+
+/// Whether the lock at `path` belongs to a process that is gone or is older
+/// than `stale_after`, if given. A lock whose contents are not written yet
+/// is judged by its modification time alone, against [`STALE_AFTER`] when
+/// no `stale_after` is given: its run died between creating and writing it.
+fn is_stale(path: &Path, stale_after: Option<Duration>) -> bool {
+    let text = fs::read_to_string(path).unwrap_or_default();
+    let mut fields = text.split_whitespace();
+    let pid = fields.next().and_then(|f| f.parse::<u32>().ok());
+    if pid.is_some_and(|pid| !process_alive(pid)) {
+        return true;
+    }
+    let stale_after = match (pid, stale_after) {
+        (Some(_), None) => return false,
+        (_, Some(stale_after)) => stale_after,
+        (None, None) => STALE_AFTER,
+    };
+    let taken = fields
+        .next()
+        .and_then(|f| f.parse::<u64>().ok())
+        .or_else(|| {
+            let mtime = fs::metadata(path).and_then(|m| m.modified()).ok()?;
+            Some(mtime.duration_since(UNIX_EPOCH).ok()?.as_secs())
+        });
+    taken.is_some_and(|taken| now_secs().saturating_sub(taken) > stale_after.as_secs())
+}
+
+fn read_pid(path: &Path) -> Option<u32> {
+    fs::read_to_string(path)
+        .ok()?
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(unix)]
+fn process_alive(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // Signal 0 only checks; EPERM still means the process exists.
+    let sent = unsafe { libc::kill(pid, 0) } == 0;
+    sent || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(windows)]
+fn process_alive(pid: u32) -> bool {
+    windows_process::alive(pid)
+}
+
+/// No way to ask; the caller falls back to the lock's age.
+#[cfg(not(any(unix, windows)))]
+fn process_alive(_pid: u32) -> bool {
+    true
+}
+
+#[cfg(windows)]
+mod windows_process {
+    use std::ffi::c_void;
+    use std::io;
+
+    const PROCESS_QUERY_LIMITED_INFORMATION: u32 = 0x1000;
+    const STILL_ACTIVE: u32 = 259;
+    const ERROR_ACCESS_DENIED: i32 = 5;
+
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn OpenProcess(access: u32, inherit_handle: i32, pid: u32) -> *mut c_void;
+        fn GetExitCodeProcess(process: *mut c_void, exit_code: *mut u32) -> i32;
+        fn CloseHandle(handle: *mut c_void) -> i32;
+    }
+
+    pub fn alive(pid: u32) -> bool {
+        // SAFETY: the handle is checked for null and closed exactly once.
+        let process = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid) };
+        if process.is_null() {
+            // Access denied still means the process exists.
+            return io::Error::last_os_error().raw_os_error() == Some(ERROR_ACCESS_DENIED);
+        }
+        let mut exit_code = 0;
+        let queried = unsafe { GetExitCodeProcess(process, &mut exit_code) } != 0;
+        unsafe { CloseHandle(process) };
+        !queried || exit_code == STILL_ACTIVE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const SHORT: Duration = Duration::from_millis(200);
+
+    #[test]
+    fn acquire_writes_pid_and_releases_on_drop() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join(FILE_NAME);
+
+        let lock = acquire(tmp.path(), SHORT, STALE_AFTER).unwrap();
+
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(text.starts_with(&format!("{} ", std::process::id())));
+        drop(lock);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn acquire_times_out_while_lock_is_held() {
+        let tmp = TempDir::new().unwrap();
+        let _held = acquire(tmp.path(), SHORT, STALE_AFTER).unwrap();
+
+        let err = acquire(tmp.path(), SHORT, STALE_AFTER).unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+        assert!(tmp.path().join(FILE_NAME).exists());
+    }
+
+    #[test]
+    fn acquire_takes_over_lock_older_than_threshold() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join(FILE_NAME);
+        fs::write(&path, format!("{} 1000\n", std::process::id())).unwrap();
+
+        let _lock = acquire(tmp.path(), SHORT, STALE_AFTER).unwrap();
+
+        assert!(!fs::read_to_string(&path).unwrap().ends_with(" 1000\n"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn acquire_takes_over_lock_of_dead_process() {
+        let tmp = TempDir::new().unwrap();
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let dead = child.id();
+        child.wait().unwrap();
+        fs::write(
+            tmp.path().join(FILE_NAME),
+            format!("{dead} {}\n", now_secs()),
+        )
+        .unwrap();
+
+        assert!(acquire(tmp.path(), SHORT, STALE_AFTER).is_ok());
+    }
+
+    #[test]
+    fn only_one_of_two_racing_runs_takes_over_a_stale_lock() {
+        for _ in 0..10 {
+            let tmp = TempDir::new().unwrap();
+            let path = tmp.path().join(FILE_NAME);
+            fs::write(&path, format!("{} 1000\n", std::process::id())).unwrap();
+            let start = std::sync::Barrier::new(2);
+            let done = std::sync::Barrier::new(2);
+
+            let won = thread::scope(|scope| {
+                let racers: Vec<_> = (0..2)
+                    .map(|_| {
+                        scope.spawn(|| {
+                            start.wait();
+                            let lock = acquire(tmp.path(), SHORT, STALE_AFTER);
+                            // Hold the lock until both have tried.
+                            done.wait();
+                            lock.is_ok()
+                        })
+                    })
+                    .collect();
+                racers
+                    .into_iter()
+                    .map(|racer| racer.join().unwrap())
+                    .filter(|&won| won)
+                    .count()
+            });
+
+            assert_eq!(won, 1);
+            assert!(!path.exists());
+            assert!(!tmp.path().join(".idr.lock.takeover").exists());
+        }
+    }
+
+    #[test]
+    fn acquire_clears_a_takeover_guard_left_by_a_crash() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join(FILE_NAME);
+        fs::write(&path, format!("{} 1000\n", std::process::id())).unwrap();
+        let guard = tmp.path().join(".idr.lock.takeover");
+        let file = fs::File::create(&guard).unwrap();
+        file.set_modified(SystemTime::now() - GUARD_STALE_AFTER * 2)
+            .unwrap();
+
+        let _lock = acquire(tmp.path(), SHORT, STALE_AFTER).unwrap();
+
+        assert!(!guard.exists());
+    }
+
+    #[test]
+    fn acquire_warn_degrades_to_none() {
+        let tmp = TempDir::new().unwrap();
+        let not_a_dir = tmp.path().join("file");
+        fs::write(&not_a_dir, "x").unwrap();
+
+        assert!(acquire_warn(&not_a_dir).is_none());
+    }
+
+    #[test]
+    fn acquire_run_holds_lock_with_pid() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("run.lock");
+
+        let lock = acquire_run_at(&path, SHORT);
+
+        assert!(matches!(lock, RunLock::Held(_)));
+        assert_eq!(read_pid(&path), Some(std::process::id()));
+        drop(lock);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn acquire_run_reports_busy_holder_regardless_of_age() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("run.lock");
+        fs::write(&path, format!("{} 1000\n", std::process::id())).unwrap();
+
+        let lock = acquire_run_at(&path, Duration::ZERO);
+
+        assert!(matches!(lock, RunLock::Busy { pid: Some(pid) } if pid == std::process::id()));
+        assert!(path.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn acquire_run_reclaims_lock_of_dead_process() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("run.lock");
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let dead = child.id();
+        child.wait().unwrap();
+        fs::write(&path, format!("{dead} {}\n", now_secs())).unwrap();
+
+        assert!(matches!(acquire_run_at(&path, SHORT), RunLock::Held(_)));
+    }
+
+    #[test]
+    fn acquire_run_reclaims_an_old_lock_that_was_never_written() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("run.lock");
+        let file = fs::File::create(&path).unwrap();
+        file.set_modified(SystemTime::now() - STALE_AFTER * 2)
+            .unwrap();
+
+        let lock = acquire_run_at(&path, SHORT);
+
+        assert!(matches!(lock, RunLock::Held(_)));
+        assert_eq!(read_pid(&path), Some(std::process::id()));
+    }
+
+    #[test]
+    fn acquire_run_waits_for_a_fresh_lock_that_is_not_written_yet() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("run.lock");
+        fs::File::create(&path).unwrap();
+
+        let lock = acquire_run_at(&path, Duration::ZERO);
+
+        assert!(matches!(lock, RunLock::Busy { pid: None }));
+    }
+
+    #[test]
+    fn run_lock_path_is_per_work_tree() {
+        let a = TempDir::new().unwrap();
+        let b = TempDir::new().unwrap();
+
+        assert_eq!(run_lock_path(a.path()), run_lock_path(a.path()));
+        assert_ne!(run_lock_path(a.path()), run_lock_path(b.path()));
+        let name = run_lock_path(a.path());
+        let name = name.file_name().unwrap().to_string_lossy();
+        assert!(name.starts_with("claude-idr-") && name.ends_with(".lock"));
+    }
+}
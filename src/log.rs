@@ -0,0 +1,74 @@
+//! Where the library's messages go. Nothing is printed until the binary
+//! installs a sink with [`set_sink`], so the library itself stays quiet.
+
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+type Sink = Box<dyn Fn(Level, &str) + Send + Sync>;
+
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+static SINK: OnceLock<Sink> = OnceLock::new();
+
+/// What a message is, so the sink can decide whether and how to show it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Level {
+    /// How the run is getting on, such as a heartbeat while claude works;
+    /// hidden by `--quiet`.
+    Info,
+    /// Something went wrong, but the run carries on.
+    Warning,
+    /// Detail for `--verbose`; only sent when it is set.
+    Verbose,
+}
+
+pub fn set_verbose(enabled: bool) {
+    VERBOSE.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_verbose() -> bool {
+    VERBOSE.load(Ordering::Relaxed)
+}
+
+/// Sends every later message to `sink`. Only the first call takes effect.
+pub fn set_sink(sink: impl Fn(Level, &str) + Send + Sync + 'static) {
+    let _ = SINK.set(Box::new(sink));
+}
+
+/// Hands `message` to the sink, if one is installed.
+pub fn emit(level: Level, message: &str) {
+    if let Some(sink) = SINK.get() {
+        sink(level, message);
+    }
+}
+
+/// Emits a [`Level::Verbose`] message when `--verbose` is set.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! verbose {
+    ($($arg:tt)*) => {
+        if $crate::log::is_verbose() {
+            $crate::log::emit($crate::log::Level::Verbose, &format!($($arg)*));
+        }
+    };
+}
+pub use crate::verbose;
+
+/// Emits a [`Level::Info`] message.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! info {
+    ($($arg:tt)*) => {
+        $crate::log::emit($crate::log::Level::Info, &format!($($arg)*))
+    };
+}
+pub use crate::info;
+
+/// Emits a [`Level::Warning`] message.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        $crate::log::emit($crate::log::Level::Warning, &format!($($arg)*))
+    };
+}
+pub use crate::warn;
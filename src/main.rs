@@ -1,16 +1,17 @@
-mod claude;
-mod config;
-mod context;
-mod git;
-mod jsonl;
-mod path;
-mod prompt;
-mod session;
-#[cfg(test)]
-mod testutil;
+mod cli;
 
-use config::Config;
+use claude_idr::config::{Config, OnFailure};
+use claude_idr::log::{self, verbose};
+use claude_idr::{
+    GenerateOptions, GenerateReport, IdrError, backfill, ci, clean, clipboard, digest, doctor,
+    export, failures, git, hook, ignore, index, language, lock, notes, notify, path, post_generate,
+    pr, publish, regenerate, renumber, runs, search, serve, settings, show, sidecar, signal, sow,
+    stats, trailer,
+};
 use std::env;
+use std::io::IsTerminal;
+use std::path::Path;
+use std::time::{Duration, Instant};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -19,98 +20,1217 @@ fn main() {
 }
 
 fn run(args: &[String]) {
-    if args.iter().any(|a| a == "--help" || a == "-h") {
+    let started = Instant::now();
+    let mut args = cli::parse(args);
+    if args.help {
         println!("Usage: claude-idr [OPTIONS]");
+        println!("       claude-idr index [DIR]");
+        println!("       claude-idr renumber [DIR] [--width N] [--dry-run]");
+        println!("       claude-idr clean [--older-than AGE] [--delete] [--dry-run]");
+        println!("       claude-idr sow set <PATH> [--create] | sow show | sow clear");
+        println!(
+            "       claude-idr config get <KEY> | config set <KEY> <VALUE> | config unset <KEY>"
+        );
+        println!(
+            "       claude-idr pr [--base REF] [--out PATH] [--publish [--overwrite]] [--dry-run]"
+        );
+        println!("       claude-idr export [DIR] [--format html|json] [--out PATH]");
+        println!("       claude-idr show <N|latest> [--path]");
+        println!("       claude-idr regenerate --failed [DIR] [--dry-run]");
+        println!("       claude-idr retry-last [--dry-run]");
+        println!("       claude-idr doctor [--json]");
+        println!("       claude-idr history [--limit N] [--json]");
+        println!("       claude-idr stats [DIR|--all] [--json]");
+        println!("       claude-idr serve [DIR] [--port N]");
+        println!(
+            "       claude-idr search <QUERY> [--dir DIR] [--title-only] [--type TAG] [--json]"
+        );
+        println!(
+            "       claude-idr digest --since DATE [--until DATE] [DIR|--all] [--no-llm] [--out PATH]"
+        );
         println!();
         println!("Generate Implementation Decision Records from git diffs using Claude.");
         println!();
+        println!("Commands:");
+        println!("  index [DIR]           Rebuild index.md in DIR (default: the output directory)");
+        println!("  renumber [DIR]        Zero-pad record numbers in DIR to --width digits");
+        println!(
+            "  clean                 Archive planning/ date directories older than --older-than (default 90d)"
+        );
+        println!(
+            "  sow set <PATH>        Point .current-sow at PATH (--create makes the directory)"
+        );
+        println!("  sow show              Show .current-sow and where IDRs would be written");
+        println!("  sow clear             Remove .current-sow, back to date-based directories");
+        println!("  config get <KEY>      Print KEY's effective value and where it comes from");
+        println!(
+            "  config set <KEY> <VALUE>  Set KEY in the config file, keeping the rest of it as is"
+        );
+        println!("  config unset <KEY>    Remove KEY from the config file, back to its default");
+        println!(
+            "  pr                    Describe the branch as a pull request (--base, default origin/HEAD or main)"
+        );
+        println!(
+            "                        --publish puts it in the open PR via gh (--overwrite replaces the body)"
+        );
+        println!(
+            "  export [DIR]          Write the records in DIR as HTML pages to --out (default DIR/export)"
+        );
+        println!(
+            "                        --format json writes them parsed into sections as records.json"
+        );
+        println!(
+            "  show <N|latest>       Print record N from the output directory (--path: only its path)"
+        );
+        println!(
+            "  stats [DIR]           Records per month, lines documented, most documented files"
+        );
+        println!(
+            "                        and records left to fill in; --all for the whole workspace"
+        );
+        println!(
+            "  serve [DIR]           Browse the records in DIR (default: the workspace) on localhost"
+        );
+        println!("                        at --port (default 7700); Ctrl-C stops it");
+        println!(
+            "  regenerate --failed   Ask claude again for the records in DIR written with the failure placeholder"
+        );
+        println!(
+            "  retry-last            Send the prompt of the last failed call again and write its IDR"
+        );
+        println!(
+            "  doctor                Check claude, its login, git, sessions, the workspace, config, and hooks"
+        );
+        println!(
+            "  history               The newest runs (default 20): outcome, record, model, tokens, duration"
+        );
+        println!(
+            "  search <QUERY>        Find records by title, reasons, or text in the workspace or --dir"
+        );
+        println!(
+            "                        --title-only and --type TAG narrow it; --json prints the hits"
+        );
+        println!(
+            "  digest                Summarize the records dated --since DATE to --until (default today)"
+        );
+        println!(
+            "                        in DIR or, with --all, the whole workspace; --no-llm for an outline"
+        );
+        println!();
         println!("Options:");
-        println!("  --config <PATH>  Config file path");
-        println!("  --dry-run        Show prompt without calling claude");
-        println!("  --version        Show version");
-        println!("  --help           Show help");
+        println!("  --config <PATH>       Config file path");
+        println!("  --dry-run             Show prompt without calling claude");
+        println!("  --claude-arg <ARG>    Extra argument for the claude CLI (repeatable)");
+        println!("  --commit-msg-out <PATH>  Write a suggested commit message to PATH");
+        println!("  --commit-msg-file <PATH> Add an IDR: trailer to the commit message in PATH");
+        println!(
+            "  --commit-msg-source <SOURCE>  prepare-commit-msg source; merge and squash get no trailer"
+        );
+        println!("  --review              Add a risks-and-concerns section to the IDR");
+        println!(
+            "  --refine              Have Claude check and tighten its draft in a second pass"
+        );
+        println!("  --no-dedupe           Generate even if this diff was already documented");
+        println!("  --no-redact           Send the diff to claude without redacting secrets first");
+        println!(
+            "  --split-by-file       Write one IDR per changed file plus an umbrella IDR linking them"
+        );
+        println!(
+            "  --post-commit         Add the new commit's SHA to its IDR, or write one (post-commit hook)"
+        );
+        println!("  --notes               Attach the IDR to the commit as a git note (notes_mode)");
+        println!(
+            "  --hook-stdin          Run as a Claude Code hook: read the payload from stdin, answer in JSON"
+        );
+        println!(
+            "  --from-session <PATH|--recent>  Write a session report from a transcript alone, without a diff"
+        );
+        println!(
+            "  --copy                Copy the IDR's path to the clipboard (clipboard: content copies the text)"
+        );
+        println!(
+            "  --on-failure <MODE>   When claude fails: placeholder, skip, or fail (see on_failure)"
+        );
+        println!("  --strict              Exit 1 when post_generate_command fails");
+        println!(
+            "  --fail-on-skip        Exit non-zero when no session, no edits, or a too-large diff stops the IDR"
+        );
+        println!(
+            "  --force               Generate even in a CI environment (see run_in_ci); overrides fail_on_skip"
+        );
+        println!("  --json                Print a JSON run report to stdout");
+        println!("  --verbose, -v         Show detailed progress and token usage");
+        println!("  --quiet, -q           Print only warnings and errors, and skip notifications");
+        println!("  --version             Show version");
+        println!("  --help                Show help");
         return;
     }
 
-    if args.iter().any(|a| a == "--version") {
+    if args.version {
         println!("claude-idr {VERSION}");
         return;
     }
 
-    let config_path = args
-        .windows(2)
-        .find(|w| w[0] == "--config")
-        .map(|w| std::path::Path::new(&w[1]));
+    log::set_verbose(args.verbose);
+    let quiet = args.quiet;
+    log::set_sink(move |level, message| match level {
+        log::Level::Warning => eprintln!("claude-idr: warning: {message}"),
+        log::Level::Info if quiet => {}
+        log::Level::Info | log::Level::Verbose => eprintln!("claude-idr: {message}"),
+    });
+    signal::install();
 
-    let dry_run = args.iter().any(|a| a == "--dry-run");
-
-    let config = Config::load(config_path);
-    if !config.enabled {
-        eprintln!("claude-idr: disabled by config");
+    // Before loading, so a file that does not parse is an error here rather
+    // than a warning and the defaults.
+    if let Some(cli::Command::Config(action)) = &args.command {
+        if let Err(e) = run_config(action, args.config.as_deref()) {
+            eprintln!("claude-idr: {e}");
+            std::process::exit(1);
+        }
         return;
     }
+    if args.command == Some(cli::Command::Doctor) {
+        match run_doctor(args.config.as_deref(), args.json) {
+            Ok(true) => return,
+            Ok(false) => std::process::exit(1),
+            Err(e) => {
+                eprintln!("claude-idr: {e}");
+                std::process::exit(1);
+            }
+        }
+    }
 
-    let session_path = match session::find_recent(&config) {
-        None => {
-            eprintln!("claude-idr: no recent session found");
-            return;
+    let mut config = Config::load(args.config.as_deref());
+    config.claude_args.append(&mut args.claude_args);
+    if args.commit_msg_out.is_some() {
+        config.suggest_commit_message = true;
+    }
+    if args.review {
+        config.review_section = true;
+    }
+    if args.refine {
+        config.refine = true;
+    }
+    if args.notes {
+        config.notes_mode = true;
+    }
+    if let Some(name) = &args.on_failure {
+        match OnFailure::parse(name) {
+            Some(mode) => config.on_failure = mode,
+            None => eprintln!(
+                "claude-idr: warning: unknown --on-failure `{name}` (expected placeholder, skip, or fail)"
+            ),
         }
-        Some(p) if !session::has_write_or_edit(&p) => {
-            eprintln!(
-                "claude-idr: session found but no code changes via Claude detected: {}",
-                p.display()
-            );
+    }
+
+    // Only generation has a session to pick the language from.
+    if args.command.is_some() {
+        language::settle(&mut config);
+    }
+
+    if let Some(problem) = config.missing_home() {
+        let explicit_dir = matches!(
+            &args.command,
+            Some(
+                cli::Command::Index { dir: Some(_) }
+                    | cli::Command::Renumber { dir: Some(_), .. }
+                    | cli::Command::Export { dir: Some(_), .. }
+                    | cli::Command::Digest { dir: Some(_), .. }
+                    | cli::Command::Search { dir: Some(_), .. }
+                    | cli::Command::Stats { dir: Some(_), .. }
+                    | cli::Command::Serve { dir: Some(_), .. }
+                    | cli::Command::Regenerate { dir: Some(_), .. }
+                    | cli::Command::Pr { .. }
+            )
+        );
+        if !explicit_dir {
+            if args.command.is_some() {
+                eprintln!("claude-idr: {problem}");
+                std::process::exit(1);
+            }
+            skip(problem, &args, &config, started);
             return;
         }
-        Some(p) => p,
-    };
+    }
 
-    let diff = match git::staged_diff() {
-        None => {
-            eprintln!("claude-idr: git failed");
+    match &args.command {
+        Some(cli::Command::Index { dir }) => {
+            let dir = dir.clone().unwrap_or_else(|| path::resolve(&config));
+            match index::refresh(&dir, config.format) {
+                Ok(()) => eprintln!(
+                    "claude-idr: index written: {}",
+                    dir.join(index::FILE_NAME).display()
+                ),
+                Err(e) => {
+                    eprintln!("claude-idr: cannot write index in {}: {e}", dir.display());
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
+        Some(cli::Command::Renumber { dir, width }) => {
+            let dir = dir.clone().unwrap_or_else(|| path::resolve(&config));
+            let width = width.unwrap_or(config.number_width);
+            if let Err(e) = run_renumber(&dir, width, args.dry_run) {
+                eprintln!("claude-idr: cannot renumber {}: {e}", dir.display());
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(cli::Command::Clean { older_than, delete }) => {
+            if let Err(e) = run_clean(&config, older_than.as_deref(), *delete, args.dry_run) {
+                eprintln!("claude-idr: cannot clean: {e}");
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(cli::Command::Pr {
+            base,
+            out,
+            publish,
+            overwrite,
+        }) => {
+            let publish = publish.then_some(*overwrite);
+            if let Err(e) = run_pr(
+                base.as_deref(),
+                out.as_deref(),
+                publish,
+                &config,
+                args.dry_run,
+                args.no_redact,
+            ) {
+                eprintln!("claude-idr: {e}");
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(cli::Command::Export { dir, format, out }) => {
+            let dir = dir.clone().unwrap_or_else(|| path::resolve(&config));
+            if let Err(e) = run_export(&dir, format.as_deref(), out.as_deref(), &config) {
+                eprintln!("claude-idr: {e}");
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(cli::Command::Digest {
+            dir,
+            since,
+            until,
+            all,
+            no_llm,
+            out,
+        }) => {
+            let options = DigestOptions {
+                dir: dir.as_deref(),
+                since: since.as_deref(),
+                until: until.as_deref(),
+                all: *all,
+                no_llm: *no_llm,
+                out: out.as_deref(),
+            };
+            if let Err(e) = run_digest(&options, &config, args.dry_run) {
+                eprintln!("claude-idr: {e}");
+                std::process::exit(1);
+            }
             return;
         }
-        Some(d) if d.is_empty() => {
-            eprintln!("claude-idr: no staged changes");
+        Some(cli::Command::Search {
+            query,
+            dir,
+            title_only,
+            tag,
+        }) => {
+            let query = search::Query {
+                text: &query.join(" "),
+                title_only: *title_only,
+                tag: tag.as_deref(),
+            };
+            if let Err(e) = run_search(&query, dir.as_deref(), &config, args.json) {
+                eprintln!("claude-idr: {e}");
+                std::process::exit(1);
+            }
             return;
         }
-        Some(d) => d,
+        Some(cli::Command::Stats { dir, all }) => {
+            if let Err(e) = run_stats(dir.as_deref(), *all, &config, args.json) {
+                eprintln!("claude-idr: {e}");
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(cli::Command::Serve { dir, port }) => {
+            if let Err(e) = run_serve(dir.as_deref(), *port, &config) {
+                eprintln!("claude-idr: {e}");
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(cli::Command::Show { target, path_only }) => {
+            if let Err(e) = run_show(target.as_deref(), *path_only, &config) {
+                eprintln!("claude-idr: {e}");
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(cli::Command::Regenerate { dir, failed }) => {
+            if let Err(e) = run_regenerate(dir.as_deref(), *failed, &config, args.dry_run) {
+                eprintln!("claude-idr: {e}");
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(cli::Command::RetryLast) => {
+            if let Err(e) = run_retry_last(&config, args.dry_run) {
+                eprintln!("claude-idr: {e}");
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(cli::Command::Sow(action)) => {
+            if let Err(e) = run_sow(action, &config) {
+                eprintln!("claude-idr: {e}");
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(cli::Command::History { limit }) => {
+            if let Err(e) = run_history(limit.unwrap_or(20), args.json) {
+                eprintln!("claude-idr: {e}");
+                std::process::exit(1);
+            }
+            return;
+        }
+        // These were handled before the config was loaded.
+        Some(cli::Command::Config(_) | cli::Command::Doctor) | None => {}
+    }
+
+    if !args.force
+        && !config.run_in_ci
+        && let Some(variable) = ci::detect(&ci::snapshot())
+    {
+        skip(
+            &format!("CI environment detected ({variable} is set), skipping"),
+            &args,
+            &config,
+            started,
+        );
+        return;
+    }
+
+    if args.post_commit
+        && let Some(report) = post_commit(&config)
+    {
+        runs::record(&report, &config, started.elapsed());
+        emit(&report, &args);
+        return;
+    }
+
+    let payload = if args.hook_stdin {
+        match hook::read_stdin() {
+            Ok(payload) => Some(payload),
+            Err(reason) => {
+                skip(&reason, &args, &config, started);
+                return;
+            }
+        }
+    } else {
+        None
+    };
+    if let Some(cwd) = payload.as_ref().and_then(|p| p.cwd.as_ref())
+        && let Err(e) = env::set_current_dir(cwd)
+    {
+        eprintln!(
+            "claude-idr: warning: cannot change to hook cwd {}: {e}",
+            cwd.display()
+        );
+    }
+
+    let _run_lock = if args.dry_run {
+        None
+    } else {
+        let root = git::repo_root()
+            .or_else(|| env::current_dir().ok())
+            .unwrap_or_default();
+        match lock::acquire_run(&root, Duration::from_secs(config.lock_wait_secs)) {
+            lock::RunLock::Busy { pid } => {
+                let reason = match pid {
+                    Some(pid) => format!("another claude-idr run is in progress (pid {pid})"),
+                    None => "another claude-idr run is in progress".to_string(),
+                };
+                skip(&reason, &args, &config, started);
+                return;
+            }
+            held => Some(held),
+        }
     };
-    let stat = git::staged_stat();
 
-    let changed_lines = git::staged_changed_lines();
-    if changed_lines > config.max_diff_lines {
+    let session = payload.as_ref().map(|p| p.transcript_path.as_path());
+    let report = generate(&config, &args, session);
+    if let Some(message) = &report.commit_message {
+        match &args.commit_msg_out {
+            Some(path) => write_commit_message(path, message),
+            None if !args.json && !args.hook_stdin => println!("{message}"),
+            None => {}
+        }
+    }
+    if args.post_commit
+        && config.notes_mode
+        && let (Some(record), Some((sha, _))) = (&report.output, git::head_commit())
+    {
+        attach_note(&config, record, &sha);
+    }
+    if let (Some(file), Some(record)) = (&args.commit_msg_file, &report.output) {
+        add_trailer(file, record, &config, args.commit_msg_source.as_deref());
+    }
+    if report.status == "generated"
+        && let Some(record) = &report.output
+    {
+        clipboard::copy_record(record, &config, args.copy);
+    }
+    let hook_failed = match post_generate::run(&report, &config) {
+        Ok(()) => false,
+        Err(e) if args.strict => {
+            eprintln!("claude-idr: {e}");
+            true
+        }
+        Err(e) => {
+            eprintln!("claude-idr: warning: {e}");
+            false
+        }
+    };
+    if config.notify
+        && !args.dry_run
+        && let Some(bell) = notify::notify(
+            &report,
+            &notify::Environment::current(args.quiet),
+            &notify::Detached,
+        )
+    {
+        eprintln!("{bell}");
+    }
+    runs::record(&report, &config, started.elapsed());
+    emit(&report, &args);
+    if hook_failed || (report.status == "error" && config.on_failure == OnFailure::Fail) {
+        std::process::exit(1);
+    }
+    if (args.fail_on_skip || config.fail_on_skip)
+        && !args.force
+        && let Some(gate) = report.gate
+    {
         eprintln!(
-            "claude-idr: diff too large ({changed_lines} lines > {} limit), skipping. Split your commit for IDR generation.",
-            config.max_diff_lines
+            "claude-idr: no IDR written: {}; pass --force to commit without one",
+            gate.advice()
         );
+        std::process::exit(gate.exit_code());
+    }
+}
+
+/// Reports a run skipped before generation started, like the library's own
+/// skips: the reason on stderr, the report on stdout, and an entry in the
+/// run history.
+fn skip(reason: &str, args: &cli::Args, config: &Config, started: Instant) {
+    if !args.quiet {
+        eprintln!("claude-idr: {reason}");
+    }
+    let report = GenerateReport {
+        status: "skipped",
+        reason: Some(reason.to_string()),
+        ..GenerateReport::default()
+    };
+    runs::record(&report, config, started.elapsed());
+    emit(&report, args);
+}
+
+/// Prints the run's answer on stdout: the hook response with
+/// `--hook-stdin`, the report with `--json`, otherwise nothing.
+fn emit(report: &GenerateReport, args: &cli::Args) {
+    let json = if args.hook_stdin {
+        serde_json::to_string(&hook::response(report))
+    } else if args.json {
+        serde_json::to_string(report)
+    } else {
         return;
+    };
+    match json {
+        Ok(s) => println!("{s}"),
+        Err(e) => eprintln!("claude-idr: warning: cannot serialize report: {e}"),
     }
+}
+
+/// Runs [`claude_idr::generate`] and prints what the library returns:
+/// skip reasons, errors, and dry-run prompts. Errors never fail the hook.
+fn generate(config: &Config, args: &cli::Args, session: Option<&Path>) -> GenerateReport {
+    let progress = |line: &str| {
+        if !args.quiet {
+            eprintln!("claude-idr: {line}");
+        }
+    };
+    let options = GenerateOptions {
+        dry_run: args.dry_run,
+        no_dedupe: args.no_dedupe,
+        progress: Some(&progress),
+        session: args.session_path.as_deref().or(session),
+        from_head: args.post_commit,
+        session_only: args.from_session,
+        split_by_file: args.split_by_file,
+        no_redact: args.no_redact,
+    };
+    let report = match claude_idr::generate(config, &options) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("claude-idr: {}", describe(&e));
+            return GenerateReport::error(&e);
+        }
+    };
+    if report.status == "skipped"
+        && !args.quiet
+        && let Some(reason) = &report.reason
+    {
+        eprintln!("claude-idr: {reason}");
+    }
+    for (label, prompt) in &report.prompts {
+        eprintln!("--- {label} ---");
+        eprintln!("{prompt}");
+    }
+    if let Some(timing) = report.timing.as_ref().filter(|_| !args.quiet) {
+        eprintln!("claude-idr: {}", timing.summary());
+    }
+    report
+}
+
+/// The line printed for a run that failed; [`IdrError`]'s own text is the
+/// detail, this adds what the run was doing at the time.
+fn describe(error: &IdrError) -> String {
+    match error {
+        IdrError::Io { .. } => format!("failed to write IDR: {error}"),
+        IdrError::Git { .. } => format!("cannot read the staged changes: {error}"),
+        _ => error.to_string(),
+    }
+}
 
+/// Post-commit hook: note HEAD's SHA in the record written for it, and
+/// report the run as `recorded`. Returns `None` when there is no such
+/// record, so one is generated for the commit instead. Only warns, like
+/// generation, so a hook can never fail.
+fn post_commit(config: &Config) -> Option<GenerateReport> {
+    // Excluding what generation excludes keeps the patch comparable with
+    // the one the record was written for.
+    let exclude = ignore::Rules::for_repo(config).excluded(&git::head_paths());
+    let (Some((sha, subject)), Ok(patch)) = (git::head_commit(), git::head_diff(&exclude)) else {
+        return Some(GenerateReport {
+            status: "skipped",
+            reason: Some("cannot read the commit at HEAD".to_string()),
+            ..GenerateReport::default()
+        });
+    };
+    let commit = sidecar::CommitRef { sha, subject };
+    let dir = notes::record_dir(config);
+    let record = match backfill::run(
+        &dir,
+        config.format,
+        &commit,
+        &patch,
+        std::time::SystemTime::now(),
+    ) {
+        Ok(Some(record)) => record,
+        Ok(None) => {
+            verbose!(
+                "no record for commit {} in {}, generating one",
+                commit.sha,
+                dir.display()
+            );
+            return None;
+        }
+        Err(e) => {
+            eprintln!("claude-idr: warning: cannot record commit: {e}");
+            return Some(GenerateReport {
+                status: "error",
+                reason: Some(format!("cannot record commit: {e}")),
+                ..GenerateReport::default()
+            });
+        }
+    };
+    let short = &commit.sha[..commit.sha.len().min(7)];
+    eprintln!(
+        "claude-idr: recorded commit {short} in {}",
+        record.display()
+    );
+    if config.notes_mode {
+        attach_note(config, &record, &commit.sha);
+    }
+    Some(GenerateReport {
+        status: "recorded",
+        output: Some(record),
+        ..GenerateReport::default()
+    })
+}
+
+fn attach_note(config: &Config, record: &Path, sha: &str) {
+    let short = &sha[..sha.len().min(7)];
+    match notes::attach(record, sha, notes::replaces_files(config)) {
+        Ok(()) => eprintln!(
+            "claude-idr: attached IDR to {short} as refs/notes/{}",
+            notes::REF
+        ),
+        Err(e) => eprintln!(
+            "claude-idr: warning: cannot attach note (is refs/notes/{} protected?): {e}",
+            notes::REF
+        ),
+    }
+}
+
+fn run_clean(
+    config: &Config,
+    older_than: Option<&str>,
+    delete: bool,
+    dry_run: bool,
+) -> Result<(), String> {
+    let max_age = match older_than {
+        None => clean::DEFAULT_MAX_AGE_DAYS,
+        Some(text) => clean::parse_age(text)
+            .ok_or_else(|| format!("invalid --older-than `{text}` (expected e.g. 90d or 12w)"))?,
+    };
+    let today = clean::parse_date_dir(&path::today_date()).ok_or("cannot read today's date")?;
+    let planning = config.workspace_dir.join("planning");
+    let sow_dir = sow::read(&config.workspace_dir)
+        .ok()
+        .and_then(|pointer| pointer.dir());
+    let plan = clean::plan(&planning, today, max_age, sow_dir.as_deref())
+        .map_err(|e| format!("{}: {e}", planning.display()))?;
+
+    for (dir, reason) in &plan.skipped {
+        eprintln!("claude-idr: skipping {}: {reason}", dir.display());
+    }
+    for dir in &plan.stale {
+        if delete {
+            eprintln!("delete {}", dir.display());
+        } else {
+            eprintln!(
+                "{} -> {}",
+                dir.display(),
+                clean::archive_path(&planning, dir).display()
+            );
+        }
+    }
+    if plan.stale.is_empty() {
+        eprintln!("claude-idr: no date directories older than {max_age} days");
+        return Ok(());
+    }
+    if dry_run {
+        eprintln!("claude-idr: dry-run mode, nothing moved");
+        return Ok(());
+    }
+    clean::apply(&planning, &plan, delete).map_err(|e| e.to_string())?;
+    eprintln!(
+        "claude-idr: {} {} director{}, skipped {}",
+        if delete { "deleted" } else { "archived" },
+        plan.stale.len(),
+        if plan.stale.len() == 1 { "y" } else { "ies" },
+        plan.skipped.len()
+    );
+    Ok(())
+}
+
+/// `publish` is `Some(overwrite)` with `--publish`.
+fn run_pr(
+    base: Option<&str>,
+    out: Option<&Path>,
+    publish: Option<bool>,
+    config: &Config,
+    dry_run: bool,
+    no_redact: bool,
+) -> Result<(), String> {
+    let base = base.map_or_else(pr::default_base, str::to_string);
+    let mut branch = pr::branch(&base, config)
+        .map_err(|e| format!("cannot compare with {base}: {e}"))?
+        .ok_or_else(|| format!("no commits on this branch since {base}"))?;
+    if !no_redact && let Some(summary) = pr::redact(&mut branch, config) {
+        eprintln!("claude-idr: {summary}");
+    }
+    if dry_run {
+        eprintln!("claude-idr: dry-run mode");
+        for (label, prompt) in pr::prompts(&branch, config).map_err(|e| e.to_string())? {
+            eprintln!("--- {label} ---");
+            eprintln!("{prompt}");
+        }
+        return Ok(());
+    }
+    eprintln!("claude-idr: describing changes since {base}...");
+    let description = pr::describe(&branch, config).map_err(|e| e.to_string())?;
+    let text = format!("{}\n", description.text.trim());
+    match out {
+        Some(path) => {
+            std::fs::write(path, &text).map_err(|e| format!("{}: {e}", path.display()))?;
+            eprintln!("claude-idr: PR description written: {}", path.display());
+        }
+        None => print!("{text}"),
+    }
+    if let Some(overwrite) = publish {
+        match publish::publish(&publish::Gh, &text, overwrite) {
+            Ok(publish::Outcome::Updated(number)) => {
+                eprintln!("claude-idr: description published to PR #{number}")
+            }
+            Ok(publish::Outcome::Unchanged(number)) => {
+                eprintln!("claude-idr: PR #{number} already has this description")
+            }
+            Ok(publish::Outcome::NoPullRequest) => eprintln!(
+                "claude-idr: warning: no open pull request for this branch, nothing published"
+            ),
+            Err(e) => eprintln!("claude-idr: warning: cannot publish description: {e}"),
+        }
+    }
+    Ok(())
+}
+
+fn run_export(
+    dir: &Path,
+    format: Option<&str>,
+    out: Option<&Path>,
+    config: &Config,
+) -> Result<(), String> {
+    let format = match format {
+        None => export::ExportFormat::Html,
+        Some(name) => export::ExportFormat::parse(name)
+            .ok_or_else(|| format!("unknown export format: {name} (expected html or json)"))?,
+    };
+    let out = out.map_or_else(|| dir.join(export::DEFAULT_DIR), Path::to_path_buf);
+    let written = export::export(dir, &out, format, config)
+        .map_err(|e| format!("cannot export {}: {e}", dir.display()))?;
+    eprintln!(
+        "claude-idr: exported {} file{} to {}",
+        written.len(),
+        if written.len() == 1 { "" } else { "s" },
+        out.display()
+    );
+    Ok(())
+}
+
+fn run_stats(dir: Option<&Path>, all: bool, config: &Config, json: bool) -> Result<(), String> {
+    let dirs = match dir {
+        _ if all => digest::record_dirs(&config.workspace_dir),
+        Some(dir) => vec![dir.to_path_buf()],
+        None => vec![path::current_dir(config)],
+    };
+    let stats = stats::aggregate(&stats::load(&dirs, config));
+    if json {
+        let text = serde_json::to_string_pretty(&stats).map_err(|e| e.to_string())?;
+        println!("{text}");
+    } else {
+        print!("{}", stats::render(&stats));
+    }
+    Ok(())
+}
+
+/// Every directory of the workspace, plus the repository's record directory
+/// when records go there.
+fn workspace_record_dirs(config: &Config) -> Vec<std::path::PathBuf> {
+    let mut dirs = digest::record_dirs(&config.workspace_dir);
+    let current = path::planned_dir(config, &path::today_date(), git::repo_root().as_deref());
+    if !current.starts_with(&config.workspace_dir) {
+        dirs.extend(digest::record_dirs(&current));
+    }
+    dirs
+}
+
+fn run_serve(dir: Option<&Path>, port: Option<u16>, config: &Config) -> Result<(), String> {
+    let (root, dirs) = match dir {
+        Some(dir) => (dir.to_path_buf(), digest::record_dirs(dir)),
+        None => (config.workspace_dir.clone(), workspace_record_dirs(config)),
+    };
+    let site = serve::Site::new(&root, dirs, config);
+    let port = port.unwrap_or(serve::DEFAULT_PORT);
+    let listener = serve::bind(port).map_err(|e| format!("cannot listen on port {port}: {e}"))?;
+    let addr = listener.local_addr().map_err(|e| e.to_string())?;
+    eprintln!("claude-idr: serving records at http://{addr}/ (Ctrl-C to stop)");
+    serve::serve(&listener, &site);
+    Ok(())
+}
+
+fn run_show(target: Option<&str>, path_only: bool, config: &Config) -> Result<(), String> {
+    let target = target.ok_or("show: missing <N|latest>")?;
+    let target = show::Target::parse(target).map_err(|e| format!("show: {e}"))?;
+    let file = show::find(&path::current_dir(config), target, config.format)?;
+    if path_only {
+        println!("{}", file.display());
+        return Ok(());
+    }
+    let text = std::fs::read(&file).map_err(|e| format!("cannot read {}: {e}", file.display()))?;
+    page(&String::from_utf8_lossy(&text));
+    Ok(())
+}
+
+fn run_regenerate(
+    dir: Option<&Path>,
+    failed: bool,
+    config: &Config,
+    dry_run: bool,
+) -> Result<(), String> {
+    if !failed {
+        return Err("regenerate: pass --failed to redo the records whose generation failed".into());
+    }
+    let dir = dir.map_or_else(|| path::current_dir(config), Path::to_path_buf);
+    let records = regenerate::failed(&dir, config.format);
+    if records.is_empty() {
+        eprintln!("claude-idr: no failed records in {}", dir.display());
+        return Ok(());
+    }
+    let mut left = 0;
+    for record in &records {
+        if dry_run {
+            eprintln!("claude-idr: would regenerate {}", record.display());
+            continue;
+        }
+        match regenerate::run(record, config) {
+            Ok(()) => eprintln!("claude-idr: regenerated {}", record.display()),
+            Err(e) => {
+                eprintln!("claude-idr: cannot regenerate {}: {e}", record.display());
+                left += 1;
+            }
+        }
+    }
+    if left > 0 {
+        return Err(format!("{left} of {} records still failed", records.len()));
+    }
+    Ok(())
+}
+
+fn run_retry_last(config: &Config, dry_run: bool) -> Result<(), String> {
+    let root = failures::root().ok_or("cannot determine the state directory for debug bundles")?;
+    let bundle = failures::latest(&root)
+        .ok_or_else(|| format!("no failed generation saved in {}", root.display()))?;
     if dry_run {
-        let idr_prompt = prompt::build_idr_prompt(&diff, &stat, &config);
+        eprintln!("claude-idr: would replay {}", bundle.display());
+        return Ok(());
+    }
+    eprintln!("claude-idr: replaying {}", bundle.display());
+    let record = failures::replay(&bundle, config)?;
+    eprintln!("claude-idr: IDR generated: {}", record.display());
+    Ok(())
+}
+
+/// Writes `text` to stdout, through `$PAGER` (default `less`) when stdout is
+/// a terminal.
+fn page(text: &str) {
+    if std::io::stdout().is_terminal() {
+        let pager = env::var("PAGER").unwrap_or_else(|_| "less".to_string());
+        let mut words = pager.split_whitespace();
+        if let Some(program) = words.next()
+            && let Ok(mut child) = std::process::Command::new(program)
+                .args(words)
+                .stdin(std::process::Stdio::piped())
+                .spawn()
+        {
+            if let Some(mut stdin) = child.stdin.take() {
+                let _ = std::io::Write::write_all(&mut stdin, text.as_bytes());
+            }
+            let _ = child.wait();
+            return;
+        }
+    }
+    print!("{text}");
+}
+
+fn run_search(
+    query: &search::Query,
+    dir: Option<&Path>,
+    config: &Config,
+    json: bool,
+) -> Result<(), String> {
+    if query.text.trim().is_empty() {
+        return Err("search: missing <QUERY>".to_string());
+    }
+    let dirs = match dir {
+        Some(dir) => digest::record_dirs(dir),
+        None => workspace_record_dirs(config),
+    };
+    let hits = search::search(&dirs, query, config);
+    if json {
+        let text = serde_json::to_string_pretty(&hits).map_err(|e| e.to_string())?;
+        println!("{text}");
+        return Ok(());
+    }
+    if hits.is_empty() {
+        eprintln!("claude-idr: no records match `{}`", query.text);
+        return Ok(());
+    }
+    let color = std::io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none();
+    let (on, off) = if color {
+        ("\x1b[1;31m", "\x1b[0m")
+    } else {
+        ("", "")
+    };
+    for hit in &hits {
+        let details: Vec<&str> = hit
+            .date
+            .as_deref()
+            .into_iter()
+            .chain(hit.change_type.as_deref())
+            .collect();
+        println!("{}", hit.path.display());
+        println!("  {} ({})", hit.title, details.join(", "));
+        println!("  {}", hit.highlighted(on, off));
+    }
+    Ok(())
+}
+
+struct DigestOptions<'a> {
+    dir: Option<&'a Path>,
+    since: Option<&'a str>,
+    until: Option<&'a str>,
+    all: bool,
+    no_llm: bool,
+    out: Option<&'a Path>,
+}
+
+fn run_digest(options: &DigestOptions, config: &Config, dry_run: bool) -> Result<(), String> {
+    let since = options.since.ok_or("digest: missing --since YYYY-MM-DD")?;
+    let period = digest::Period::new(since, options.until, &path::today_date())
+        .map_err(|e| format!("digest: {e}"))?;
+    let (base, dirs) = match options.dir {
+        _ if options.all => (
+            config.workspace_dir.clone(),
+            digest::record_dirs(&config.workspace_dir),
+        ),
+        Some(dir) => (dir.to_path_buf(), vec![dir.to_path_buf()]),
+        None => {
+            let dir = path::resolve(config);
+            (dir.clone(), vec![dir])
+        }
+    };
+    let entries = digest::collect(&dirs, &period, config);
+    if entries.is_empty() {
+        return Err(format!(
+            "no records dated {} in {}",
+            period.label(),
+            base.display()
+        ));
+    }
+    let out = options
+        .out
+        .map_or_else(|| base.join(period.file_name()), Path::to_path_buf);
+    let base = out.parent().unwrap_or(Path::new(""));
+    let text = if options.no_llm {
+        digest::outline(&entries, &period, base)
+    } else if dry_run {
         eprintln!("claude-idr: dry-run mode");
-        eprintln!("--- IDR prompt ({} chars) ---", idr_prompt.len());
-        eprintln!("{idr_prompt}");
+        eprintln!("{}", digest::prompt(&entries, &period, config));
+        return Ok(());
+    } else {
+        eprintln!(
+            "claude-idr: summarizing {} record{}...",
+            entries.len(),
+            if entries.len() == 1 { "" } else { "s" }
+        );
+        digest::narrate(&entries, &period, base, config).map_err(|e| e.to_string())?
+    };
+    std::fs::write(&out, text).map_err(|e| format!("{}: {e}", out.display()))?;
+    eprintln!("claude-idr: digest written: {}", out.display());
+    Ok(())
+}
+
+fn run_sow(action: &cli::SowCommand, config: &Config) -> Result<(), String> {
+    let workspace = &config.workspace_dir;
+    match action {
+        cli::SowCommand::Set { path: None, .. } => Err("sow set: missing <PATH>".to_string()),
+        cli::SowCommand::Set {
+            path: Some(target),
+            create,
+        } => {
+            let written =
+                sow::set(workspace, target, *create).map_err(|e| format!("cannot set SOW: {e}"))?;
+            eprintln!(
+                "claude-idr: {} now points at {}",
+                sow::FILE_NAME,
+                written.display()
+            );
+            Ok(())
+        }
+        cli::SowCommand::Show => {
+            println!(
+                "{}",
+                sow::show(config).map_err(|e| format!("cannot show SOW: {e}"))?
+            );
+            Ok(())
+        }
+        cli::SowCommand::Clear => {
+            let removed = sow::clear(workspace).map_err(|e| format!("cannot clear SOW: {e}"))?;
+            match removed {
+                sow::Pointer::Unset => eprintln!("claude-idr: no {} to remove", sow::FILE_NAME),
+                sow::Pointer::Outside(p) => eprintln!(
+                    "claude-idr: removed {} (it pointed outside workspace_dir: {})",
+                    sow::FILE_NAME,
+                    p.display()
+                ),
+                _ => eprintln!(
+                    "claude-idr: removed {}; IDRs go to date-based directories again",
+                    sow::FILE_NAME
+                ),
+            }
+            Ok(())
+        }
+        cli::SowCommand::Invalid(given) if given.is_empty() => {
+            Err("sow: expected `set <PATH>`, `show`, or `clear`".to_string())
+        }
+        cli::SowCommand::Invalid(given) => Err(format!(
+            "sow: unknown action `{given}`; expected `set <PATH>`, `show`, or `clear`"
+        )),
+    }
+}
+
+fn run_config(action: &cli::ConfigCommand, explicit: Option<&Path>) -> Result<(), String> {
+    let expected = "expected `get <KEY>`, `set <KEY> <VALUE>`, or `unset <KEY>`";
+    let path = settings::path(explicit)?;
+    match action {
+        cli::ConfigCommand::Get { key: None } => Err("config get: missing <KEY>".to_string()),
+        cli::ConfigCommand::Get { key: Some(key) } => {
+            let (value, source) = settings::get(key, &path)?;
+            println!("{} ({source})", settings::display(&value));
+            Ok(())
+        }
+        cli::ConfigCommand::Set { key: None, .. } => Err("config set: missing <KEY>".to_string()),
+        cli::ConfigCommand::Set {
+            key: Some(key),
+            value: None,
+        } => Err(format!("config set: missing <VALUE> for `{key}`")),
+        cli::ConfigCommand::Set {
+            key: Some(key),
+            value: Some(raw),
+        } => {
+            let value = settings::set(key, raw, &path)?;
+            eprintln!(
+                "claude-idr: {key} = {} in {}",
+                settings::display(&value),
+                path.display()
+            );
+            Ok(())
+        }
+        cli::ConfigCommand::Unset { key: None } => Err("config unset: missing <KEY>".to_string()),
+        cli::ConfigCommand::Unset { key: Some(key) } => {
+            if settings::unset(key, &path)? {
+                eprintln!(
+                    "claude-idr: removed {key} from {}; its default applies again",
+                    path.display()
+                );
+            } else {
+                eprintln!("claude-idr: {key} is not set in {}", path.display());
+            }
+            Ok(())
+        }
+        cli::ConfigCommand::Invalid(given) if given.is_empty() => {
+            Err(format!("config: {expected}"))
+        }
+        cli::ConfigCommand::Invalid(given) => {
+            Err(format!("config: unknown action `{given}`; {expected}"))
+        }
+    }
+}
+
+/// Prints the doctor's results; `Ok(false)` when a check failed.
+fn run_doctor(explicit: Option<&Path>, json: bool) -> Result<bool, String> {
+    let results = doctor::run(&settings::path(explicit)?);
+    if json {
+        println!("{}", doctor::to_json(&results));
+    } else {
+        print!("{}", doctor::render(&results));
+    }
+    Ok(!doctor::failed(&results))
+}
+
+fn run_history(limit: usize, json: bool) -> Result<(), String> {
+    let path = runs::path().ok_or("no state directory for the run history")?;
+    let entries =
+        runs::recent(&path, limit).map_err(|e| format!("cannot read {}: {e}", path.display()))?;
+    if json {
+        let text = serde_json::to_string_pretty(&entries).map_err(|e| e.to_string())?;
+        println!("{text}");
+    } else if entries.is_empty() {
+        eprintln!("claude-idr: no runs recorded in {}", path.display());
+    } else {
+        print!("{}", runs::render(&entries));
+    }
+    Ok(())
+}
+
+fn run_renumber(dir: &Path, width: usize, dry_run: bool) -> std::io::Result<()> {
+    let renames = renumber::plan(dir, width)?;
+    if renames.is_empty() {
+        eprintln!("claude-idr: all records already use {width}-digit numbers");
+        return Ok(());
+    }
+    for rename in &renames {
+        eprintln!(
+            "{} -> {}",
+            rename
+                .from
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy(),
+            rename.to.file_name().unwrap_or_default().to_string_lossy()
+        );
+    }
+    if dry_run {
+        eprintln!("claude-idr: dry-run mode, nothing renamed");
+        return Ok(());
+    }
+    let _lock = lock::acquire_warn(dir);
+    renumber::apply(dir, &renames)?;
+    eprintln!("claude-idr: renamed {} record(s)", renames.len());
+    Ok(())
+}
+
+/// Adds the `IDR:` trailer for `record` to the commit message in `file`.
+/// Like the rest of the hook, failures only warn.
+fn add_trailer(file: &Path, record: &Path, config: &Config, source: Option<&str>) {
+    if trailer::leaves_alone(source) {
+        verbose!(
+            "leaving {} commit message without a trailer",
+            source.unwrap_or_default()
+        );
+        return;
+    }
+    if notes::replaces_files(config) {
+        verbose!("no trailer: the record is removed once attached as a note");
         return;
     }
+    let record = record
+        .canonicalize()
+        .unwrap_or_else(|_| record.to_path_buf());
+    let value = trailer::value(&record, git::repo_root().as_deref());
+    match trailer::apply(file, &value, git::comment_char()) {
+        Ok(true) => verbose!("added {}: {value} to {}", trailer::KEY, file.display()),
+        Ok(false) => verbose!("{} already has {}: {value}", file.display(), trailer::KEY),
+        Err(e) => eprintln!(
+            "claude-idr: warning: cannot add trailer to {}: {e}",
+            file.display()
+        ),
+    }
+}
+
+fn write_commit_message(path: &Path, message: &str) {
+    match std::fs::write(path, format!("{message}\n")) {
+        Ok(()) => eprintln!("claude-idr: commit message written: {}", path.display()),
+        Err(e) => eprintln!(
+            "claude-idr: warning: cannot write commit message to {}: {e}",
+            path.display()
+        ),
+    }
+}
 
-    let purpose = context::extract(&session_path)
-        .and_then(|ctx| {
-            let purpose_prompt = prompt::build_purpose_prompt(&ctx, &config);
-            claude::run(&purpose_prompt, &config)
-        })
-        .map(|s| s.trim().to_string());
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    eprintln!("claude-idr: generating IDR...");
-    let idr_prompt = prompt::build_idr_prompt(&diff, &stat, &config);
-    let idr_content = claude::run(&idr_prompt, &config)
-        .unwrap_or_else(|| "## 変更概要\n\n(IDR生成失敗 - 手動で記載してください)".to_string());
+    #[test]
+    fn write_commit_message_writes_hook_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("COMMIT_EDITMSG");
+        std::fs::write(&path, "# Please enter the commit message\n").unwrap();
 
-    let output_dir = path::resolve(&config);
-    let next_num = path::next_number(&output_dir);
-    let output_file = output_dir.join(format!("idr-{:02}.md", next_num));
+        write_commit_message(&path, "feat(api): add retries\n\nRetry 5xx responses.");
 
-    path::write_idr(&output_file, &purpose, &idr_content, &stat);
-    eprintln!("claude-idr: IDR generated: {}", output_file.display());
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "feat(api): add retries\n\nRetry 5xx responses.\n"
+        );
+    }
 }
@@ -0,0 +1,235 @@
+//! Provenance at the end of each record: a footer saying which session,
+//! models, and branch it came from, and a comment with the claude-idr
+//! version and prompt revision that tools can read back.
+
+use crate::path;
+use crate::prompt::PROMPT_VERSION;
+use serde::Serialize;
+
+const PREFIX: &str = "<!-- claude-idr:";
+const SUFFIX: &str = "-->";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Metadata {
+    pub version: String,
+    pub prompt: String,
+    /// Model that wrote the body.
+    pub model: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub purpose_model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session: Option<String>,
+    /// The session's directory under `~/.claude/projects`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    /// Short hash of the commit the record documents.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    pub generated: String,
+    /// The session's effort line (see `effort::Effort::render`), shown in
+    /// the footer only; the sidecar keeps the numbers.
+    #[serde(skip)]
+    pub effort: Option<String>,
+}
+
+impl Metadata {
+    /// Metadata for a record generated now by this build.
+    pub fn new(model: &str, session: Option<String>) -> Metadata {
+        Metadata {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            prompt: PROMPT_VERSION.to_string(),
+            model: model.to_string(),
+            session,
+            generated: path::now_iso8601(),
+            ..Metadata::default()
+        }
+    }
+
+    /// `<!-- claude-idr: version=.. prompt=.. model=.. [purpose_model=..]
+    /// [session=..] [project=..] [branch=..] [commit=..] generated=.. -->`
+    pub fn render(&self) -> String {
+        let optional: String = [
+            ("purpose_model", &self.purpose_model),
+            ("session", &self.session),
+            ("project", &self.project),
+            ("branch", &self.branch),
+            ("commit", &self.commit),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| Some(format!(" {key}={}", value.as_ref()?)))
+        .collect();
+        format!(
+            "{PREFIX} version={} prompt={} model={}{optional} generated={} {SUFFIX}",
+            self.version, self.prompt, self.model, self.generated
+        )
+    }
+
+    /// The footer lines shown above the comment, leaving out what is not
+    /// known:
+    ///
+    /// ```text
+    /// > session: 5f0c3a2e (project -Users-me-repo)
+    /// > effort: 1h 42m, 23 user turns, 58 tool calls (Edit 31, Bash 19, Read 8), 5 files edited
+    /// > models: purpose haiku, body sonnet
+    /// > claude-idr 0.1.1, branch main, commit abc1234
+    /// ```
+    pub fn render_footer(&self) -> String {
+        let mut lines = Vec::new();
+        let project = self
+            .project
+            .as_ref()
+            .map(|project| format!("project {project}"));
+        match (&self.session, project) {
+            (Some(session), Some(project)) => {
+                lines.push(format!("> session: {session} ({project})"))
+            }
+            (Some(session), None) => lines.push(format!("> session: {session}")),
+            (None, Some(project)) => lines.push(format!("> session: ({project})")),
+            (None, None) => {}
+        }
+        if let Some(effort) = &self.effort {
+            lines.push(format!("> effort: {effort}"));
+        }
+        lines.push(match &self.purpose_model {
+            Some(purpose) => format!("> models: purpose {purpose}, body {}", self.model),
+            None => format!("> models: body {}", self.model),
+        });
+        let mut build = format!("> claude-idr {}", self.version);
+        if let Some(branch) = &self.branch {
+            build.push_str(&format!(", branch {branch}"));
+        }
+        if let Some(commit) = &self.commit {
+            build.push_str(&format!(", commit {commit}"));
+        }
+        lines.push(build);
+        lines.join("\n")
+    }
+
+    /// Reads the last metadata comment in a record. Unknown keys are ignored
+    /// so newer records stay readable; records written before the comment
+    /// existed return `None`.
+    pub fn parse(text: &str) -> Option<Metadata> {
+        let line = text
+            .lines()
+            .rev()
+            .map(str::trim)
+            .find(|l| l.starts_with(PREFIX))?;
+        let fields = line.strip_prefix(PREFIX)?.strip_suffix(SUFFIX)?;
+
+        let mut metadata = Metadata::default();
+        for (key, value) in fields.split_whitespace().filter_map(|f| f.split_once('=')) {
+            let value = value.to_string();
+            match key {
+                "version" => metadata.version = value,
+                "prompt" => metadata.prompt = value,
+                "model" => metadata.model = value,
+                "purpose_model" => metadata.purpose_model = Some(value),
+                "session" => metadata.session = Some(value),
+                "project" => metadata.project = Some(value),
+                "branch" => metadata.branch = Some(value),
+                "commit" => metadata.commit = Some(value),
+                "generated" => metadata.generated = value,
+                _ => {}
+            }
+        }
+        Some(metadata)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Metadata {
+        Metadata {
+            version: "0.1.1".to_string(),
+            prompt: "v3".to_string(),
+            model: "sonnet".to_string(),
+            session: Some("5f0c3a2e-1b7d".to_string()),
+            generated: "2026-02-07T05:30:00Z".to_string(),
+            ..Metadata::default()
+        }
+    }
+
+    fn full() -> Metadata {
+        Metadata {
+            purpose_model: Some("haiku".to_string()),
+            project: Some("-Users-me-repo".to_string()),
+            branch: Some("feature/retry".to_string()),
+            commit: Some("abc1234".to_string()),
+            ..sample()
+        }
+    }
+
+    #[test]
+    fn render_formats_html_comment() {
+        assert_eq!(
+            sample().render(),
+            "<!-- claude-idr: version=0.1.1 prompt=v3 model=sonnet session=5f0c3a2e-1b7d generated=2026-02-07T05:30:00Z -->"
+        );
+    }
+
+    #[test]
+    fn render_footer_leaves_out_what_is_unknown() {
+        assert_eq!(
+            full().render_footer(),
+            "> session: 5f0c3a2e-1b7d (project -Users-me-repo)\n> models: purpose haiku, body sonnet\n> claude-idr 0.1.1, branch feature/retry, commit abc1234"
+        );
+        assert_eq!(
+            sample().render_footer(),
+            "> session: 5f0c3a2e-1b7d\n> models: body sonnet\n> claude-idr 0.1.1"
+        );
+        let bare = Metadata {
+            session: None,
+            ..sample()
+        };
+        assert_eq!(
+            bare.render_footer(),
+            "> models: body sonnet\n> claude-idr 0.1.1"
+        );
+        assert!(!full().render_footer().contains("None"));
+        let with_effort = Metadata {
+            effort: Some("12m, 1 user turn, 1 tool call (Bash 1), 0 files edited".to_string()),
+            ..sample()
+        };
+        assert_eq!(
+            with_effort.render_footer(),
+            "> session: 5f0c3a2e-1b7d\n> effort: 12m, 1 user turn, 1 tool call (Bash 1), 0 files edited\n> models: body sonnet\n> claude-idr 0.1.1"
+        );
+    }
+
+    #[test]
+    fn parse_round_trips_render() {
+        let without_session = Metadata {
+            session: None,
+            ..sample()
+        };
+
+        for metadata in [sample(), without_session, full()] {
+            let text = format!("# IDR: x\n\nbody\n\n{}\n", metadata.render());
+            assert_eq!(Metadata::parse(&text), Some(metadata));
+        }
+    }
+
+    #[test]
+    fn parse_ignores_unknown_keys_and_missing_comment() {
+        let parsed =
+            Metadata::parse("<!-- claude-idr: version=9.0 model=opus extra=1 -->").unwrap();
+        assert_eq!(parsed.version, "9.0");
+        assert_eq!(parsed.model, "opus");
+        assert_eq!(parsed.prompt, "");
+
+        assert_eq!(Metadata::parse("# IDR: old record\n"), None);
+    }
+
+    #[test]
+    fn new_uses_build_and_prompt_versions() {
+        let metadata = Metadata::new("haiku", None);
+
+        assert_eq!(metadata.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(metadata.prompt, PROMPT_VERSION);
+        assert!(metadata.generated.ends_with('Z'));
+    }
+}
@@ -0,0 +1,253 @@
+//! `output_mode = "monthly"`: every record of a month goes into one
+//! `idr-YYYY-MM.md` journal as a `## IDR N: <purpose>` section.
+
+use crate::path::{self, Record};
+use crate::signal;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const SECTION_PREFIX: &str = "## IDR ";
+
+/// `dir/idr-YYYY-MM.md` for `month` (`YYYY-MM`).
+pub fn file_path(dir: &Path, month: &str) -> PathBuf {
+    dir.join(format!("idr-{month}.md"))
+}
+
+/// Whether `filename` is a monthly journal rather than a single record.
+pub fn is_journal_name(filename: &str) -> bool {
+    let Some(stem) = filename
+        .strip_prefix("idr-")
+        .and_then(|s| s.strip_suffix(".md"))
+    else {
+        return false;
+    };
+    let bytes = stem.as_bytes();
+    bytes.len() == 7
+        && bytes[4] == b'-'
+        && bytes
+            .iter()
+            .enumerate()
+            .all(|(i, b)| i == 4 || b.is_ascii_digit())
+}
+
+/// `(number, purpose)` of each section in a journal, in file order.
+/// Headings inside fenced code blocks are not sections.
+pub fn sections(text: &str) -> Vec<(u32, &str)> {
+    let mut in_fence = false;
+    text.lines()
+        .filter(|line| {
+            if line.trim_start().starts_with("```") {
+                in_fence = !in_fence;
+            }
+            !in_fence
+        })
+        .filter_map(|line| {
+            let (number, purpose) = line.strip_prefix(SECTION_PREFIX)?.split_once(':')?;
+            Some((number.parse().ok()?, purpose.trim()))
+        })
+        .collect()
+}
+
+/// One past the highest section number in `text`.
+pub fn next_number(text: &str) -> u32 {
+    sections(text).iter().map(|(n, _)| *n).max().unwrap_or(0) + 1
+}
+
+/// Appends `record` to this month's journal in `dir`, returning the journal
+/// path and the section number used. The whole file is rewritten through a
+/// temp file and a rename, so readers never see a half-appended section.
+pub fn append(dir: &Path, record: &Record) -> io::Result<(PathBuf, u32)> {
+    let month = path::today_date().chars().take(7).collect::<String>();
+    append_at(dir, &month, record, &path::now_datetime())
+}
+
+fn append_at(
+    dir: &Path,
+    month: &str,
+    record: &Record,
+    datetime: &str,
+) -> io::Result<(PathBuf, u32)> {
+    fs::create_dir_all(dir)?;
+    let journal = file_path(dir, month);
+    let existing = match fs::read_to_string(&journal) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => format!("# IDR {month}\n"),
+        Err(e) => return Err(e),
+    };
+    let number = next_number(&existing);
+    let mut text = existing.trim_end().to_string();
+    text.push_str("\n\n");
+    text.push_str(&render_section(number, record, datetime));
+
+    let tmp = dir.join(format!(".idr-{month}.md.tmp"));
+    let _tracked = signal::track_file(&tmp);
+    fs::write(&tmp, text)
+        .and_then(|()| fs::rename(&tmp, &journal))
+        .inspect_err(|_| {
+            let _ = fs::remove_file(&tmp);
+        })?;
+    Ok((journal, number))
+}
+
+fn render_section(number: u32, record: &Record, datetime: &str) -> String {
+    let related = record
+        .related
+        .map_or(String::new(), |related| format!("> Related: {related}\n"));
+    let stat = match record.stat {
+        Some(stat) => format!("#### git diff --stat\n```\n{stat}\n```\n"),
+        None => format!("{}\n", path::SESSION_REPORT_MARKER),
+    };
+    let mut section = format!(
+        "{SECTION_PREFIX}{number}: {}\n\n\
+         > {datetime}\n\
+         {related}\n\
+         {}\n\n\
+         {stat}",
+        record.title,
+        demote_headings(record.content.trim_end()),
+    );
+    if let Some(footer) = record.footer {
+        section.push_str(&format!("\n{footer}\n"));
+    }
+    if let Some(metadata) = record.metadata {
+        section.push_str(&format!("\n{}\n", metadata.render()));
+    }
+    section
+}
+
+/// Pushes the record's own headings one level down so they nest under the
+/// `## IDR N:` section. Lines in fenced code blocks are left alone.
+fn demote_headings(content: &str) -> String {
+    let mut in_fence = false;
+    content
+        .lines()
+        .map(|line| {
+            if line.trim_start().starts_with("```") {
+                in_fence = !in_fence;
+                return line.to_string();
+            }
+            let hashes = line.len() - line.trim_start_matches('#').len();
+            if !in_fence && (1..6).contains(&hashes) && line[hashes..].starts_with(' ') {
+                format!("#{line}")
+            } else {
+                line.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const FIXTURE: &str = "\
+# IDR 2026-02
+
+## IDR 1: Add retries
+
+> 2026-02-03 10:00
+
+### 変更概要
+
+Retries.
+
+```diff
++## IDR 99: not a heading
+```
+
+#### git diff --stat
+```
+ src/api.rs | 3 ++-
+```
+
+## IDR 2: Use JWT
+
+> 2026-02-05 11:00
+
+### 変更概要
+
+JWT.
+";
+
+    fn record<'a>(title: &'a str, content: &'a str) -> Record<'a> {
+        Record {
+            title,
+            content,
+            stat: Some(" src/a.rs | 1 +"),
+            footer: None,
+            related: None,
+            tags: &[],
+            metadata: None,
+            front_matter: None,
+            sidecar: None,
+        }
+    }
+
+    #[test]
+    fn sections_skip_fenced_headings() {
+        assert_eq!(sections(FIXTURE), vec![(1, "Add retries"), (2, "Use JWT")]);
+        assert_eq!(next_number(FIXTURE), 3);
+        assert_eq!(next_number("# IDR 2026-02\n"), 1);
+    }
+
+    #[test]
+    fn append_adds_numbered_section_to_existing_journal() {
+        let dir = TempDir::new().unwrap();
+        fs::write(file_path(dir.path(), "2026-02"), FIXTURE).unwrap();
+
+        let (path, number) = append_at(
+            dir.path(),
+            "2026-02",
+            &record("Cache sessions", "## 変更概要\n\nCache.\n"),
+            "2026-02-07 14:30",
+        )
+        .unwrap();
+
+        assert_eq!(number, 3);
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(text.starts_with(FIXTURE.trim_end()));
+        assert!(text.ends_with(
+            "\n\n## IDR 3: Cache sessions\n\n> 2026-02-07 14:30\n\n### 変更概要\n\nCache.\n\n#### git diff --stat\n```\n src/a.rs | 1 +\n```\n"
+        ));
+        assert_eq!(sections(&text).last(), Some(&(3, "Cache sessions")));
+    }
+
+    #[test]
+    fn append_starts_new_journal_each_month() {
+        let dir = TempDir::new().unwrap();
+        fs::write(file_path(dir.path(), "2026-02"), FIXTURE).unwrap();
+
+        let (path, number) =
+            append_at(dir.path(), "2026-03", &record("t", "c"), "2026-03-01 09:00").unwrap();
+
+        assert_eq!(path, dir.path().join("idr-2026-03.md"));
+        assert_eq!(number, 1);
+        assert!(
+            fs::read_to_string(&path)
+                .unwrap()
+                .starts_with("# IDR 2026-03\n\n## IDR 1: t\n")
+        );
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn demote_headings_leaves_code_blocks_alone() {
+        let content = "## 主要な変更\n### [a](a)\n```md\n# title\n```\n#hashtag";
+
+        assert_eq!(
+            demote_headings(content),
+            "### 主要な変更\n#### [a](a)\n```md\n# title\n```\n#hashtag"
+        );
+    }
+
+    #[test]
+    fn is_journal_name_matches_only_month_files() {
+        assert!(is_journal_name("idr-2026-02.md"));
+        assert!(!is_journal_name("idr-02.md"));
+        assert!(!is_journal_name("idr-2026-ab.md"));
+        assert!(!is_journal_name("idr-02-use-jwt.md"));
+    }
+}
@@ -0,0 +1,186 @@
+//! Hunks not worth a prompt's tokens: whitespace-only changes, reordered
+//! imports, and files matching `noise_paths` (snapshots, minified assets).
+//! They are dropped before any prompt is built; file headers stay, so the
+//! prompt still shows which files changed.
+
+use crate::config::Config;
+use crate::diff;
+use crate::ignore;
+
+/// The diff without its noise, and what was taken out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stripped {
+    pub diff: String,
+    pub elided: Elided,
+}
+
+/// Hunks dropped, by why, and the changed lines they held.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Elided {
+    pub whitespace: usize,
+    pub imports: usize,
+    pub paths: usize,
+    pub lines: usize,
+}
+
+/// `diff` with noise hunks dropped, or `None` when `strip_noise` is off or
+/// nothing was noise.
+pub fn strip(diff: &str, config: &Config) -> Option<Stripped> {
+    if !config.strip_noise {
+        return None;
+    }
+    let mut files = diff::parse(diff);
+    let mut elided = Elided::default();
+    for file in &mut files {
+        let noise_path = config
+            .noise_paths
+            .iter()
+            .any(|glob| ignore::matches(glob, &file.path));
+        let before = file.hunks.len();
+        file.hunks.retain(|hunk| {
+            let counter = if noise_path {
+                &mut elided.paths
+            } else if hunk.is_whitespace_only() {
+                &mut elided.whitespace
+            } else if hunk.is_import_reorder(&config.import_prefixes) {
+                &mut elided.imports
+            } else {
+                return true;
+            };
+            *counter += 1;
+            elided.lines += changed(hunk);
+            false
+        });
+        let dropped = before - file.hunks.len();
+        if dropped > 0 {
+            file.header
+                .push(format!("[noise: {dropped} hunk(s) dropped]"));
+        }
+    }
+    (elided.hunks() > 0).then(|| Stripped {
+        diff: diff::render(&files),
+        elided,
+    })
+}
+
+fn changed(hunk: &diff::Hunk) -> usize {
+    hunk.lines
+        .iter()
+        .filter(|line| line.starts_with('+') || line.starts_with('-'))
+        .count()
+}
+
+impl Elided {
+    pub fn hunks(&self) -> usize {
+        self.whitespace + self.imports + self.paths
+    }
+
+    /// `3 hunk(s), 41 changed line(s): 2 whitespace-only, 1 import reordering`.
+    pub fn summary(&self) -> String {
+        let kinds: Vec<String> = [
+            (self.whitespace, "whitespace-only"),
+            (self.imports, "import reordering"),
+            (self.paths, "in noise_paths files"),
+        ]
+        .into_iter()
+        .filter(|(n, _)| *n > 0)
+        .map(|(n, kind)| format!("{n} {kind}"))
+        .collect();
+        format!(
+            "{} hunk(s), {} changed line(s): {}",
+            self.hunks(),
+            self.lines,
+            kinds.join(", ")
+        )
+    }
+
+    /// The record's footer line.
+    pub fn footer(&self) -> String {
+        format!("> noise dropped from the prompt: {}", self.summary())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIFF: &str = "\
+diff --git a/src/api.rs b/src/api.rs
+--- a/src/api.rs
++++ b/src/api.rs
+@@ -1,3 +1,3 @@
+-use std::io;
+-use std::fs;
++use std::fs;
++use std::io;
+
+@@ -10,3 +10,3 @@ fn call() {
+-    retry( 3 );
++    retry(3);
+ }
+@@ -20,2 +20,2 @@ fn timeout() {
+-    let secs = 5;
++    let secs = 30;
+diff --git a/web/app.min.js b/web/app.min.js
+--- a/web/app.min.js
++++ b/web/app.min.js
+@@ -1 +1 @@
+-var a=1;
++var a=2;
+";
+
+    #[test]
+    fn strip_drops_each_kind_of_noise_and_counts_it() {
+        let stripped = strip(DIFF, &Config::default()).unwrap();
+
+        assert_eq!(
+            stripped.elided,
+            Elided {
+                whitespace: 1,
+                imports: 1,
+                paths: 1,
+                lines: 8,
+            }
+        );
+        let files = diff::parse(&stripped.diff);
+        assert_eq!(files[0].hunks.len(), 1);
+        assert!(
+            files[0].hunks[0]
+                .lines
+                .contains(&"+    let secs = 30;".to_string())
+        );
+        assert_eq!(
+            files[0].header.last().map(String::as_str),
+            Some("[noise: 2 hunk(s) dropped]")
+        );
+        assert!(files[1].hunks.is_empty());
+        assert_eq!(
+            stripped.elided.summary(),
+            "3 hunk(s), 8 changed line(s): 1 whitespace-only, 1 import reordering, 1 in noise_paths files"
+        );
+    }
+
+    #[test]
+    fn strip_respects_the_settings() {
+        let off = Config {
+            strip_noise: false,
+            ..Config::default()
+        };
+        assert_eq!(strip(DIFF, &off), None);
+
+        let no_imports = Config {
+            import_prefixes: Vec::new(),
+            noise_paths: Vec::new(),
+            ..Config::default()
+        };
+        let stripped = strip(DIFF, &no_imports).unwrap();
+        assert_eq!((stripped.elided.imports, stripped.elided.paths), (0, 0));
+        assert_eq!(stripped.elided.whitespace, 1);
+    }
+
+    #[test]
+    fn strip_leaves_a_diff_without_noise_alone() {
+        let diff = "diff --git a/a.rs b/a.rs\n@@ -1 +1 @@\n-a();\n+b();\n";
+        assert_eq!(strip(diff, &Config::default()), None);
+    }
+}
@@ -0,0 +1,50 @@
+//! `notes_mode`: IDRs attached to their commits as git notes under
+//! `refs/notes/idr` instead of (or as well as) files.
+
+use crate::config::Config;
+use crate::git;
+use crate::log::{info, warn};
+use crate::path;
+use crate::sidecar;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const REF: &str = "idr";
+const PENDING_DIR: &str = "claude-idr";
+
+/// Whether notes take the place of the files in the output directory.
+pub fn replaces_files(config: &Config) -> bool {
+    config.notes_mode && !config.notes_keep_files
+}
+
+/// Where records are written. In notes mode without `notes_keep_files`
+/// they wait in `.git/claude-idr/` for the post-commit hook, so nothing
+/// lands in the tree or the workspace.
+pub fn record_dir(config: &Config) -> PathBuf {
+    if replaces_files(config)
+        && let Some(git_dir) = git::git_dir()
+    {
+        let dir = git_dir.join(PENDING_DIR);
+        if let Err(e) = fs::create_dir_all(&dir) {
+            warn!("cannot create directory {}: {e}", dir.display());
+        }
+        return dir;
+    }
+    path::resolve(config)
+}
+
+/// Attaches `record` to `sha`, replacing an older note (after an amend).
+/// With `discard`, the record and its sidecar are removed once attached;
+/// on failure they stay so the next commit's hook, or a person, can retry.
+pub fn attach(record: &Path, sha: &str, discard: bool) -> Result<(), String> {
+    let body = fs::read_to_string(record).map_err(|e| format!("{}: {e}", record.display()))?;
+    if git::show_note(REF, sha).is_some() {
+        info!("replacing the existing {REF} note on {sha}");
+    }
+    git::add_note(REF, sha, &body).map_err(|e| format!("git notes --ref={REF} add failed: {e}"))?;
+    if discard {
+        let _ = fs::remove_file(sidecar::path_for(record));
+        let _ = fs::remove_file(record);
+    }
+    Ok(())
+}
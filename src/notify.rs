@@ -0,0 +1,296 @@
+//! `notify`: a desktop notification when generation finishes, for whoever
+//! switched windows while claude was working. Falls back to a terminal bell
+//! and a bold summary line; never blocks or fails the run.
+
+use crate::generate::GenerateReport;
+use crate::history;
+use std::fs;
+use std::io;
+use std::process::{Command, Stdio};
+
+const APP_NAME: &str = "claude-idr";
+
+/// What the machine running claude-idr offers for notifications.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Environment {
+    pub macos: bool,
+    /// `DISPLAY` or `WAYLAND_DISPLAY` is set.
+    pub display: bool,
+    /// Stderr is a terminal.
+    pub terminal: bool,
+    /// `CI` is set.
+    pub ci: bool,
+    pub quiet: bool,
+}
+
+impl Environment {
+    pub fn current(quiet: bool) -> Environment {
+        use std::io::IsTerminal;
+        let set = |name: &str| std::env::var_os(name).is_some_and(|v| !v.is_empty());
+        Environment {
+            macos: cfg!(target_os = "macos"),
+            display: set("DISPLAY") || set("WAYLAND_DISPLAY"),
+            terminal: io::stderr().is_terminal(),
+            ci: set("CI"),
+            quiet,
+        }
+    }
+}
+
+/// How a notification is delivered.
+#[derive(Debug, PartialEq)]
+pub enum Delivery {
+    /// A notifier program and its arguments.
+    Command(String, Vec<String>),
+    /// A bell and a summary line on stderr.
+    Bell(String),
+}
+
+/// Starts notifier programs; [`Detached`] in the binary, a fake in tests.
+pub trait Spawner {
+    /// Starts `program` without waiting for it.
+    fn spawn(&self, program: &str, args: &[String]) -> io::Result<()>;
+}
+
+/// Starts the program in the background with no input or output.
+pub struct Detached;
+
+impl Spawner for Detached {
+    fn spawn(&self, program: &str, args: &[String]) -> io::Result<()> {
+        Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map(drop)
+    }
+}
+
+/// The headline and detail for `report`, or `None` for outcomes that are
+/// neither a success nor a failure.
+pub fn message(report: &GenerateReport) -> Option<(&'static str, String)> {
+    match report.status {
+        "generated" => {
+            let title = report
+                .output
+                .as_ref()
+                .and_then(|path| fs::read(path).ok())
+                .map(|bytes| history::title(&String::from_utf8_lossy(&bytes)))
+                .unwrap_or_default();
+            Some(("IDR generated", title))
+        }
+        "error" => Some((
+            "IDR generation failed",
+            report.reason.clone().unwrap_or_default(),
+        )),
+        _ => None,
+    }
+}
+
+/// How to deliver `headline` and `detail` in `env`, if at all: nothing
+/// under `--quiet` or in CI, a desktop notification where there is a
+/// desktop, otherwise the bell when a terminal is watching.
+pub fn delivery(env: &Environment, headline: &str, detail: &str) -> Option<Delivery> {
+    if env.quiet || env.ci {
+        return None;
+    }
+    if env.macos {
+        let script = format!(
+            "display notification \"{}\" with title \"{APP_NAME}\" subtitle \"{}\"",
+            applescript_escape(detail),
+            applescript_escape(headline)
+        );
+        return Some(Delivery::Command(
+            "osascript".to_string(),
+            vec!["-e".to_string(), script],
+        ));
+    }
+    if env.display {
+        return Some(Delivery::Command(
+            "notify-send".to_string(),
+            vec![
+                format!("--app-name={APP_NAME}"),
+                headline.to_string(),
+                detail.to_string(),
+            ],
+        ));
+    }
+    bell(env, headline, detail)
+}
+
+fn bell(env: &Environment, headline: &str, detail: &str) -> Option<Delivery> {
+    let line = match detail {
+        "" => headline.to_string(),
+        _ => format!("{headline}: {detail}"),
+    };
+    env.terminal.then_some(Delivery::Bell(line))
+}
+
+fn applescript_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Notifies about `report` in `env`. A notifier that cannot be started
+/// falls back to the bell, which is returned for the caller to print on
+/// stderr.
+pub fn notify(report: &GenerateReport, env: &Environment, spawner: &dyn Spawner) -> Option<String> {
+    let (headline, detail) = message(report)?;
+    let delivery = match delivery(env, headline, &detail) {
+        Some(Delivery::Command(program, args)) => match spawner.spawn(&program, &args) {
+            Ok(()) => return None,
+            Err(_) => bell(env, headline, &detail),
+        },
+        other => other,
+    };
+    let Some(Delivery::Bell(line)) = delivery else {
+        return None;
+    };
+    Some(if std::env::var_os("NO_COLOR").is_none() {
+        format!("\x07\x1b[1m{APP_NAME}: {line}\x1b[0m")
+    } else {
+        format!("\x07{APP_NAME}: {line}")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    const DESKTOP: Environment = Environment {
+        macos: false,
+        display: true,
+        terminal: true,
+        ci: false,
+        quiet: false,
+    };
+
+    #[derive(Default)]
+    struct FakeSpawner {
+        fail: bool,
+        spawned: RefCell<Vec<(String, Vec<String>)>>,
+    }
+
+    impl Spawner for FakeSpawner {
+        fn spawn(&self, program: &str, args: &[String]) -> io::Result<()> {
+            self.spawned
+                .borrow_mut()
+                .push((program.to_string(), args.to_vec()));
+            if self.fail {
+                Err(io::Error::from(io::ErrorKind::NotFound))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    fn failed() -> GenerateReport {
+        GenerateReport {
+            status: "error",
+            reason: Some("claude CLI failed".to_string()),
+            ..GenerateReport::default()
+        }
+    }
+
+    #[test]
+    fn delivery_uses_osascript_on_macos() {
+        let env = Environment {
+            macos: true,
+            display: false,
+            ..DESKTOP
+        };
+
+        assert_eq!(
+            delivery(&env, "IDR generated", "Say \"hi\""),
+            Some(Delivery::Command(
+                "osascript".to_string(),
+                vec![
+                    "-e".to_string(),
+                    "display notification \"Say \\\"hi\\\"\" with title \"claude-idr\" subtitle \"IDR generated\"".to_string()
+                ]
+            ))
+        );
+    }
+
+    #[test]
+    fn delivery_uses_notify_send_with_a_display() {
+        assert_eq!(
+            delivery(&DESKTOP, "IDR generated", "Add retry"),
+            Some(Delivery::Command(
+                "notify-send".to_string(),
+                vec![
+                    "--app-name=claude-idr".to_string(),
+                    "IDR generated".to_string(),
+                    "Add retry".to_string()
+                ]
+            ))
+        );
+    }
+
+    #[test]
+    fn delivery_falls_back_to_the_bell_or_nothing() {
+        let headless = Environment {
+            display: false,
+            ..DESKTOP
+        };
+        assert_eq!(
+            delivery(&headless, "IDR generated", "Add retry"),
+            Some(Delivery::Bell("IDR generated: Add retry".to_string()))
+        );
+
+        let piped = Environment {
+            terminal: false,
+            ..headless
+        };
+        assert_eq!(delivery(&piped, "IDR generated", ""), None);
+        let ci = Environment {
+            ci: true,
+            ..DESKTOP
+        };
+        assert_eq!(delivery(&ci, "IDR generated", ""), None);
+        let quiet = Environment {
+            quiet: true,
+            ..DESKTOP
+        };
+        assert_eq!(delivery(&quiet, "IDR generated", ""), None);
+    }
+
+    #[test]
+    fn message_covers_success_and_failure_only() {
+        assert_eq!(
+            message(&failed()),
+            Some(("IDR generation failed", "claude CLI failed".to_string()))
+        );
+        let skipped = GenerateReport {
+            status: "skipped",
+            ..GenerateReport::default()
+        };
+        assert_eq!(message(&skipped), None);
+    }
+
+    #[test]
+    fn notify_spawns_the_notifier_and_survives_its_absence() {
+        let spawner = FakeSpawner::default();
+        assert_eq!(notify(&failed(), &DESKTOP, &spawner), None);
+        let spawned = spawner.spawned.take();
+        assert_eq!(spawned.len(), 1);
+        assert_eq!(spawned[0].0, "notify-send");
+        assert_eq!(spawned[0].1[1], "IDR generation failed");
+
+        let missing = FakeSpawner {
+            fail: true,
+            ..FakeSpawner::default()
+        };
+        let bell = notify(&failed(), &DESKTOP, &missing).unwrap();
+        assert!(bell.contains("IDR generation failed: claude CLI failed"));
+        assert_eq!(missing.spawned.take().len(), 1);
+
+        let quiet = Environment {
+            quiet: true,
+            ..DESKTOP
+        };
+        assert_eq!(notify(&failed(), &quiet, &spawner), None);
+        assert!(spawner.spawned.take().is_empty());
+    }
+}
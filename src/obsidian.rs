@@ -0,0 +1,63 @@
+//! Obsidian-friendly output (`obsidian_mode`): file headings as wikilinks
+//! and a header line linking the record to its daily note.
+
+/// `### [src/a.rs](src/a.rs)` file headings, outside code blocks, as
+/// `### [[src/a.rs]]`. The prompt already asks for wikilinks; this catches
+/// the markdown links Claude writes anyway.
+pub fn wikilink_headings(content: &str) -> String {
+    let mut in_fence = false;
+    content
+        .lines()
+        .map(|line| {
+            if line.trim_start().starts_with("```") {
+                in_fence = !in_fence;
+                return line.to_string();
+            }
+            let link = line
+                .strip_prefix("### [")
+                .filter(|_| !in_fence)
+                .and_then(|rest| rest.strip_suffix(')'))
+                .and_then(|rest| rest.split_once("]("));
+            match link {
+                Some((text, _)) if !text.starts_with('[') => format!("### [[{text}]]"),
+                _ => line.to_string(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// `[[2026-02-07]]` for the date of `datetime`, followed by `#tag`s when
+/// there are any (front matter carries them otherwise).
+pub fn header(datetime: &str, tags: &[String]) -> String {
+    let date = datetime.split([' ', 'T']).next().unwrap_or(datetime);
+    let mut header = format!("[[{date}]]");
+    for tag in tags {
+        header.push_str(&format!(" #{tag}"));
+    }
+    header
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wikilink_headings_converts_file_links_outside_code() {
+        let content = "## 主要な変更\n\n### [src/a b.rs](src/a%20b.rs)\n#### L1-2: x\n```md\n### [b](b)\n```\n### [[c]]\n### plain";
+
+        assert_eq!(
+            wikilink_headings(content),
+            "## 主要な変更\n\n### [[src/a b.rs]]\n#### L1-2: x\n```md\n### [b](b)\n```\n### [[c]]\n### plain"
+        );
+    }
+
+    #[test]
+    fn header_links_daily_note_and_lists_tags() {
+        assert_eq!(header("2026-02-07 17:30", &[]), "[[2026-02-07]]");
+        assert_eq!(
+            header("2026-02-07", &["idr".to_string(), "fix".to_string()]),
+            "[[2026-02-07]] #idr #fix"
+        );
+    }
+}
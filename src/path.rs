@@ -1,121 +1,633 @@
-use crate::config::Config;
-use std::fs;
+use crate::config::{Config, Format, OutputLayout, OutputLocation};
+use crate::front_matter::FrontMatter;
+use crate::git;
+use crate::log::{verbose, warn};
+use crate::metadata::Metadata;
+use crate::monthly;
+use crate::obsidian;
+use crate::sidecar::{self, Sidecar};
+use crate::signal;
+use crate::sow::{self, Pointer};
+use std::fs::{self, OpenOptions};
+use std::io;
 use std::path::{Path, PathBuf};
 
 pub fn resolve(config: &Config) -> PathBuf {
-    resolve_with_date(config, &today_date())
+    resolve_with(
+        config,
+        &today_date(),
+        git::repo_root().as_deref(),
+        layout_branch(config).as_deref(),
+    )
 }
 
-fn resolve_with_date(config: &Config, date: &str) -> PathBuf {
+/// The directory [`resolve`] picks, without creating it.
+pub fn current_dir(config: &Config) -> PathBuf {
+    let dir = planned_dir(config, &today_date(), git::repo_root().as_deref());
+    match layout_branch(config) {
+        Some(branch) => dir.join(branch),
+        None => dir,
+    }
+}
+
+/// The branch directory `by-branch` layout nests records in.
+fn layout_branch(config: &Config) -> Option<String> {
+    (config.output_layout == OutputLayout::ByBranch).then(|| {
+        branch_dir_name(
+            git::current_branch().as_deref(),
+            git::short_head().as_deref(),
+        )
+    })
+}
+
+/// [`resolve_with_date`], nested one level deeper for `by-branch` layout.
+fn resolve_with(
+    config: &Config,
+    date: &str,
+    repo_root: Option<&Path>,
+    branch: Option<&str>,
+) -> PathBuf {
+    let base = resolve_with_date(config, date, repo_root);
+    let Some(branch) = branch else {
+        return base;
+    };
+    let dir = base.join(branch);
+    create_dir_warn(&dir);
+    dir
+}
+
+/// Directory name for a branch: path separators and other characters that
+/// are awkward in file names become `-`. A detached HEAD is named after the
+/// commit it points at.
+fn branch_dir_name(branch: Option<&str>, short_sha: Option<&str>) -> String {
+    let Some(branch) = branch else {
+        return format!("detached-{}", short_sha.unwrap_or("unknown"));
+    };
+    let name: String = branch
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    let name = name.trim_start_matches('.');
+    if name.is_empty() {
+        "unnamed".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+fn resolve_with_date(config: &Config, date: &str, repo_root: Option<&Path>) -> PathBuf {
+    let dir = planned_dir(config, date, repo_root);
+    create_dir_warn(&dir);
+    dir
+}
+
+/// The directory `resolve_with_date` picks, without creating it.
+/// `repo_root` is the repository being committed to, if any.
+pub fn planned_dir(config: &Config, date: &str, repo_root: Option<&Path>) -> PathBuf {
     if let Some(ref dir) = config.output_dir {
-        create_dir_warn(dir);
         return dir.clone();
     }
 
-    let sow_file = config.workspace_dir.join(".current-sow");
+    if let Some(root) = repo_root {
+        let repo_dir = root.join(&config.repo_output_subdir);
+        let use_repo = match config.output_location {
+            OutputLocation::Workspace => false,
+            OutputLocation::Repo => true,
+            OutputLocation::Auto => repo_dir.is_dir(),
+        };
+        if use_repo {
+            return repo_dir;
+        }
+    }
+
+    let sow_file = config.workspace_dir.join(sow::FILE_NAME);
 
     if let Ok(sow_content) = fs::read_to_string(&sow_file) {
         let sow_path = PathBuf::from(sow_content.trim());
         if let Some(dir) = validate_sow_path(&sow_path, &config.workspace_dir) {
-            create_dir_warn(&dir);
             return dir;
         }
     }
 
-    let date_dir = config.workspace_dir.join("planning").join(date);
-    create_dir_warn(&date_dir);
-    date_dir
+    config.workspace_dir.join("planning").join(date)
 }
 
 fn create_dir_warn(dir: &Path) {
     if let Err(e) = fs::create_dir_all(dir) {
-        eprintln!(
-            "claude-idr: warning: cannot create directory {}: {e}",
-            dir.display()
-        );
+        warn!("cannot create directory {}: {e}", dir.display());
     }
 }
 
+/// The directory `.current-sow` selects: the target itself when it is a
+/// directory, or the parent of a SOW file.
 // SAFETY: canonicalize + is_file has a TOCTOU gap, but the worst case is
 // writing the IDR to a stale directory, which is harmless for this use case.
 fn validate_sow_path(sow_path: &Path, workspace_dir: &Path) -> Option<PathBuf> {
-    let real_sow = fs::canonicalize(sow_path).ok()?;
-    let real_workspace = fs::canonicalize(workspace_dir).ok()?;
-
-    if !real_sow.starts_with(&real_workspace) {
-        return None;
-    }
-    if !real_sow.is_file() {
-        return None;
+    let pointer = sow::classify(sow_path, workspace_dir);
+    match &pointer {
+        Pointer::Outside(p) => verbose!(
+            ".current-sow points outside the workspace, ignoring: {}",
+            p.display()
+        ),
+        Pointer::Dir(p) => verbose!(".current-sow names a directory: {}", p.display()),
+        Pointer::File(p) => verbose!(
+            ".current-sow names a SOW file, using its directory: {}",
+            p.display()
+        ),
+        Pointer::Missing(_) | Pointer::Unset => {}
     }
+    pointer.dir()
+}
 
-    real_sow.parent().map(PathBuf::from)
+/// One past the highest record number in `dir`. Warns when IDR file names
+/// use mixed number widths (e.g. `idr-99.md` next to `idr-100.md`), which
+/// breaks their sort order in file listings.
+pub fn next_number(dir: &Path, format: Format) -> u32 {
+    let records = list_records(dir, format);
+    if format == Format::Idr && has_mixed_widths(&records) {
+        warn!(
+            "records in {} use mixed number widths; `claude-idr renumber` can align them",
+            dir.display()
+        );
+    }
+    records.last().map_or(0, |(number, _)| *number) + 1
 }
 
-pub fn next_number(dir: &Path) -> u32 {
-    let entries = match fs::read_dir(dir) {
-        Ok(e) => e,
-        Err(_) => return 1,
+fn has_mixed_widths(records: &[(u32, PathBuf)]) -> bool {
+    let mut widths = records.iter().filter_map(|(_, p)| {
+        let (digits, _) = split_idr_name(p.file_name()?.to_str()?)?;
+        Some(digits.len())
+    });
+    let Some(first) = widths.next() else {
+        return false;
     };
+    widths.any(|w| w != first)
+}
 
-    let max = entries
+/// Records in `dir` named for `format`, sorted by number.
+pub fn list_records(dir: &Path, format: Format) -> Vec<(u32, PathBuf)> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut records: Vec<(u32, PathBuf)> = entries
         .filter_map(|e| e.ok())
         .filter_map(|e| {
             let name = e.file_name();
-            let name = name.to_str()?;
-            parse_idr_number(name)
+            let number = record_number(name.to_str()?, format)?;
+            Some((number, e.path()))
         })
-        .max()
-        .unwrap_or(0);
+        .collect();
+    records.sort();
+    records
+}
 
-    max + 1
+pub fn record_number(filename: &str, format: Format) -> Option<u32> {
+    match format {
+        Format::Idr => parse_idr_number(filename),
+        Format::Madr => parse_madr_number(filename),
+    }
 }
 
+/// Parses `idr-NN.md` and `idr-NN-slug.md` names.
 fn parse_idr_number(filename: &str) -> Option<u32> {
+    split_idr_name(filename)?.0.parse().ok()
+}
+
+/// The digits and the slug (if any) of an IDR file name.
+pub fn split_idr_name(filename: &str) -> Option<(&str, Option<&str>)> {
+    if monthly::is_journal_name(filename) {
+        return None;
+    }
     let stem = filename.strip_prefix("idr-")?.strip_suffix(".md")?;
-    stem.parse::<u32>().ok()
+    let (number, slug) = match stem.split_once('-') {
+        Some((number, slug)) => (number, Some(slug)),
+        None => (stem, None),
+    };
+    // Slugs never hold a dot; `idr-07-use-jwt.en.md` is a translation.
+    if number.is_empty()
+        || !number.bytes().all(|b| b.is_ascii_digit())
+        || slug.is_some_and(|slug| slug.is_empty() || slug.contains('.'))
+    {
+        return None;
+    }
+    Some((number, slug))
+}
+
+/// Parses adr-tools style `NNNN-title.md` names.
+fn parse_madr_number(filename: &str) -> Option<u32> {
+    let (number, title) = filename.strip_suffix(".md")?.split_once('-')?;
+    if number.len() != 4 || !number.bytes().all(|b| b.is_ascii_digit()) || title.is_empty() {
+        return None;
+    }
+    number.parse().ok()
+}
+
+/// File name for record `number`: `idr-NN-slug.md` (`idr-NN.md` when the
+/// purpose has no ASCII words) with the number zero-padded to `width`, or
+/// `NNNN-title.md` in MADR mode, with the slug taken from `purpose`.
+pub fn file_name(format: Format, number: u32, purpose: Option<&str>, width: usize) -> String {
+    match format {
+        Format::Idr => idr_file_name(
+            number,
+            purpose
+                .and_then(sanitize_purpose)
+                .map(|p| slugify(&p))
+                .filter(|s| !s.is_empty())
+                .as_deref(),
+            width,
+        ),
+        Format::Madr => {
+            let slug = purpose
+                .and_then(sanitize_purpose)
+                .map(|p| slugify(&p))
+                .unwrap_or_default();
+            let title = if slug.is_empty() { "decision" } else { &slug };
+            format!("{number:04}-{title}.md")
+        }
+    }
+}
+
+const SLUG_MAX_CHARS: usize = 40;
+const PURPOSE_MAX_CHARS: usize = 120;
+/// Labels models like to put in front of the purpose line.
+const PURPOSE_LABELS: [&str; 5] = ["main purpose", "purpose", "title", "summary", "目的"];
+
+/// The purpose as a single heading-safe line: the first line with any text
+/// left once heading and list markers, emphasis, backticks, and a leading
+/// `Purpose:`-style label are stripped, whitespace collapsed, and capped at
+/// `PURPOSE_MAX_CHARS`. `None` when nothing is left.
+pub fn sanitize_purpose(text: &str) -> Option<String> {
+    let line = text
+        .lines()
+        .map(clean_purpose_line)
+        .find(|l| !l.is_empty())?;
+    if line.chars().count() <= PURPOSE_MAX_CHARS {
+        return Some(line);
+    }
+    let cut: String = line.chars().take(PURPOSE_MAX_CHARS - 1).collect();
+    Some(format!("{}…", cut.trim_end()))
 }
 
-pub fn write_idr(path: &Path, purpose: &Option<String>, content: &str, stat: &str) {
-    write_idr_at(path, purpose, content, stat, &now_datetime());
+fn clean_purpose_line(line: &str) -> String {
+    let line = line.trim().trim_start_matches('#').trim_start();
+    let line = line
+        .strip_prefix("- ")
+        .or_else(|| line.strip_prefix("* "))
+        .unwrap_or(line);
+    let line: String = line
+        .replace("**", "")
+        .replace("__", "")
+        .chars()
+        .filter(|c| !matches!(c, '`' | '*'))
+        .collect();
+    let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+    strip_purpose_label(&line)
+        .trim_matches(|c| matches!(c, '"' | '\'' | '「' | '」'))
+        .trim()
+        .to_string()
 }
 
-fn write_idr_at(path: &Path, purpose: &Option<String>, content: &str, stat: &str, datetime: &str) {
-    let purpose_text = purpose.as_deref().unwrap_or("(目的抽出失敗)");
+fn strip_purpose_label(line: &str) -> &str {
+    for label in PURPOSE_LABELS {
+        if let Some(head) = line.get(..label.len())
+            && head.eq_ignore_ascii_case(label)
+        {
+            let rest = line[label.len()..].trim_start();
+            if let Some(rest) = rest.strip_prefix(':').or_else(|| rest.strip_prefix('：')) {
+                return rest.trim_start();
+            }
+        }
+    }
+    line
+}
 
-    let body = format!(
-        "# IDR: {purpose_text}\n\n\
-         > {datetime}\n\n\
-         {content}\n\n\
-         ---\n\n\
-         ### git diff --stat\n\
-         ```\n{stat}\n```\n"
-    );
+pub fn idr_file_name(number: u32, slug: Option<&str>, width: usize) -> String {
+    match slug {
+        Some(slug) => format!("idr-{number:0width$}-{slug}.md"),
+        None => format!("idr-{number:0width$}.md"),
+    }
+}
 
-    if let Some(parent) = path.parent() {
-        create_dir_warn(parent);
+/// Lowercase ASCII words joined by hyphens; everything else is dropped.
+/// Whole words are kept up to [`SLUG_MAX_CHARS`]; a first word longer than
+/// that is cut.
+fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    for word in text
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+    {
+        let separator = usize::from(!slug.is_empty());
+        if slug.len() + separator + word.len() > SLUG_MAX_CHARS {
+            if slug.is_empty() {
+                slug = word[..SLUG_MAX_CHARS].to_ascii_lowercase();
+            }
+            break;
+        }
+        if separator == 1 {
+            slug.push('-');
+        }
+        slug.push_str(&word.to_ascii_lowercase());
     }
-    if let Err(e) = fs::write(path, &body) {
-        eprintln!(
-            "claude-idr: warning: failed to write IDR {}: {}",
-            path.display(),
-            e
-        );
+    slug
+}
+
+/// Everything written into a record file besides the header date.
+pub struct Record<'a> {
+    pub title: &'a str,
+    pub content: &'a str,
+    /// `git diff --stat` of the change; `None` for a session report, which
+    /// has [`SESSION_REPORT_MARKER`] in its place.
+    pub stat: Option<&'a str>,
+    pub footer: Option<&'a str>,
+    /// Issue references shown under the date (`Related: PROJ-1, #2`).
+    pub related: Option<&'a str>,
+    /// Shown as `#tag`s in Obsidian mode when there is no front matter.
+    pub tags: &'a [String],
+    pub metadata: Option<&'a Metadata>,
+    /// Replaces the title line when set.
+    pub front_matter: Option<&'a FrontMatter>,
+    /// Written next to the record as `<name>.meta.json`.
+    pub sidecar: Option<&'a Sidecar>,
+}
+
+/// Closes a record written from a session alone, where the diff stat would
+/// be.
+pub const SESSION_REPORT_MARKER: &str =
+    "> Session report: written from the session transcript alone, without a git diff";
+
+/// Numbers tried past `next_number` before giving up on finding a free one.
+const MAX_ALLOCATION_ATTEMPTS: u32 = 100;
+
+/// Writes `record` under the next free number in `dir` and returns the path
+/// used. The file is claimed with create-new semantics, so concurrent runs
+/// that computed the same number end up in different files instead of
+/// overwriting each other.
+pub fn allocate_and_write(
+    dir: &Path,
+    purpose: Option<&str>,
+    record: &Record,
+    config: &Config,
+) -> io::Result<PathBuf> {
+    let path = allocate_and_write_from(
+        dir,
+        next_number(dir, config.format),
+        purpose,
+        &render_record(record, config, &now_datetime()),
+        config.format,
+        config.number_width,
+    )?;
+    if let Some(sidecar) = record.sidecar {
+        sidecar::write_warn(&path, sidecar);
+    }
+    Ok(path)
+}
+
+fn allocate_and_write_from(
+    dir: &Path,
+    first: u32,
+    purpose: Option<&str>,
+    body: &str,
+    format: Format,
+    width: usize,
+) -> io::Result<PathBuf> {
+    create_dir_warn(dir);
+    // Write the content first so the claimed file only ever goes from empty
+    // to complete, never half-written.
+    let tmp = dir.join(format!(".idr-{}.tmp", std::process::id()));
+    let _tracked = signal::track_file(&tmp);
+    let result = fs::write(&tmp, body)
+        .and_then(|()| claim_and_rename(dir, first, purpose, &tmp, format, width));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+fn claim_and_rename(
+    dir: &Path,
+    first: u32,
+    purpose: Option<&str>,
+    tmp: &Path,
+    format: Format,
+    width: usize,
+) -> io::Result<PathBuf> {
+    for number in first..first.saturating_add(MAX_ALLOCATION_ATTEMPTS) {
+        // A record with a different slug can already hold this number.
+        if list_records(dir, format).iter().any(|(n, _)| *n == number) {
+            continue;
+        }
+        let path = dir.join(file_name(format, number, purpose, width));
+        let _reserved = signal::track_reserved(&path);
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+        return fs::rename(tmp, &path)
+            .map(|()| path.clone())
+            .inspect_err(|_| {
+                let _ = fs::remove_file(&path);
+            });
+    }
+    Err(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        format!(
+            "no free record number in {} after {MAX_ALLOCATION_ATTEMPTS} attempts starting at {first}",
+            dir.display()
+        ),
+    ))
+}
+
+/// Files reserved by [`claim_block`], in slug order. While it is alive, an
+/// interrupted run removes those still empty.
+pub struct Claimed {
+    pub paths: Vec<PathBuf>,
+    _tracked: Vec<signal::FileGuard>,
+}
+
+/// Claims one consecutive number per slug in `dir`, starting at the next
+/// free number, by creating the files empty; fill them with
+/// [`write_claimed`]. A block that collides with any existing record moves
+/// up as a whole, so the numbers stay consecutive.
+pub fn claim_block(dir: &Path, slugs: &[Option<&str>], config: &Config) -> io::Result<Claimed> {
+    create_dir_warn(dir);
+    claim_block_from(
+        dir,
+        next_number(dir, config.format),
+        slugs,
+        config.format,
+        config.number_width,
+    )
+}
+
+fn claim_block_from(
+    dir: &Path,
+    first: u32,
+    slugs: &[Option<&str>],
+    format: Format,
+    width: usize,
+) -> io::Result<Claimed> {
+    'block: for start in first..first.saturating_add(MAX_ALLOCATION_ATTEMPTS) {
+        let taken = list_records(dir, format);
+        let numbers = start..start.saturating_add(slugs.len() as u32);
+        if taken.iter().any(|(n, _)| numbers.contains(n)) {
+            continue;
+        }
+        let mut claimed = Vec::new();
+        let mut tracked = Vec::new();
+        for (number, slug) in numbers.zip(slugs) {
+            let path = dir.join(file_name(format, number, *slug, width));
+            let guard = signal::track_reserved(&path);
+            if let Err(e) = OpenOptions::new().write(true).create_new(true).open(&path) {
+                for path in &claimed {
+                    let _ = fs::remove_file(path);
+                }
+                if e.kind() == io::ErrorKind::AlreadyExists {
+                    continue 'block;
+                }
+                return Err(e);
+            }
+            claimed.push(path);
+            tracked.push(guard);
+        }
+        return Ok(Claimed {
+            paths: claimed,
+            _tracked: tracked,
+        });
+    }
+    Err(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        format!(
+            "no {} free consecutive record numbers in {} after {MAX_ALLOCATION_ATTEMPTS} attempts starting at {first}",
+            slugs.len(),
+            dir.display()
+        ),
+    ))
+}
+
+/// Fills a file claimed by [`claim_block`] with `record`.
+pub fn write_claimed(path: &Path, record: &Record, config: &Config) -> io::Result<()> {
+    let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+    let _tracked = signal::track_file(&tmp);
+    fs::write(&tmp, render_record(record, config, &now_datetime()))
+        .and_then(|()| fs::rename(&tmp, path))
+        .inspect_err(|_| {
+            let _ = fs::remove_file(&tmp);
+        })?;
+    if let Some(sidecar) = record.sidecar {
+        sidecar::write_warn(path, sidecar);
+    }
+    Ok(())
+}
+
+fn render_record(record: &Record, config: &Config, datetime: &str) -> String {
+    let Record {
+        title,
+        content,
+        stat,
+        ..
+    } = record;
+    let (mut body, title_line) = match record.front_matter {
+        Some(front_matter) => (front_matter.render() + "\n", String::new()),
+        None if config.format == Format::Idr => (String::new(), format!("# IDR: {title}\n\n")),
+        None => (String::new(), format!("# {title}\n\n")),
+    };
+    let related = record
+        .related
+        .map_or(String::new(), |related| match config.format {
+            Format::Idr => format!("> Related: {related}\n"),
+            Format::Madr => format!("* Related: {related}\n"),
+        });
+    let related = if config.obsidian_mode {
+        let tags = if record.front_matter.is_some() {
+            &[][..]
+        } else {
+            record.tags
+        };
+        let header = obsidian::header(datetime, tags);
+        match config.format {
+            Format::Idr => format!("{related}> {header}\n"),
+            Format::Madr => format!("{related}* {header}\n"),
+        }
+    } else {
+        related
+    };
+    let stat = match stat {
+        Some(stat) => format!("### git diff --stat\n```\n{stat}\n```\n"),
+        None => format!("{SESSION_REPORT_MARKER}\n"),
+    };
+    match config.format {
+        Format::Idr => body.push_str(&format!(
+            "{title_line}\
+             > {datetime}\n\
+             {related}\n\
+             {content}\n\n\
+             ---\n\n\
+             {stat}"
+        )),
+        Format::Madr => {
+            let date = datetime.split(' ').next().unwrap_or(datetime);
+            body.push_str(&format!(
+                "{title_line}\
+                 * Status: {}\n\
+                 * Date: {date}\n\
+                 {related}\n\
+                 {content}\n\n\
+                 ---\n\n\
+                 {stat}",
+                config.madr_status
+            ))
+        }
+    }
+    if let Some(footer) = record.footer {
+        body.push_str(&format!("\n{footer}\n"));
     }
+    if let Some(metadata) = record.metadata {
+        body.push_str(&format!(
+            "\n{}\n\n{}\n",
+            metadata.render_footer(),
+            metadata.render()
+        ));
+    }
+    body
 }
 
-fn today_date() -> String {
+pub fn today_date() -> String {
     let secs = epoch_now();
     let (y, m, d, _, _) = local_datetime(secs);
     format!("{y:04}-{m:02}-{d:02}")
 }
 
-fn now_datetime() -> String {
+pub fn now_datetime() -> String {
     let secs = epoch_now();
     let (y, m, d, h, min) = local_datetime(secs);
     format!("{y:04}-{m:02}-{d:02} {h:02}:{min:02}")
 }
 
+/// UTC timestamp for machine-readable fields, e.g. `2026-02-07T05:30:00Z`.
+pub fn now_iso8601() -> String {
+    iso8601_utc(epoch_now())
+}
+
+fn iso8601_utc(epoch_secs: i64) -> String {
+    let secs = epoch_secs.max(0) as u64;
+    let (y, m, d) = epoch_to_civil_utc(secs);
+    let day_secs = secs % 86400;
+    format!(
+        "{y:04}-{m:02}-{d:02}T{:02}:{:02}:{:02}Z",
+        day_secs / 3600,
+        day_secs % 3600 / 60,
+        day_secs % 60
+    )
+}
+
 fn epoch_now() -> i64 {
     std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
@@ -125,12 +637,17 @@ fn epoch_now() -> i64 {
         .unwrap_or(i64::MAX)
 }
 
+/// Local year, month, day, hour, and minute of `epoch_secs`, with the
+/// offset (and daylight saving) in effect at that moment. Platforms without
+/// a local time source get UTC.
 fn local_datetime(epoch_secs: i64) -> (i32, u32, u32, u32, u32) {
     #[cfg(unix)]
     {
         let mut tm: libc::tm = unsafe { std::mem::zeroed() };
         let time = epoch_secs as libc::time_t;
-        unsafe { libc::localtime_r(&time, &mut tm) };
+        if unsafe { libc::localtime_r(&time, &mut tm) }.is_null() {
+            return datetime_at_offset(epoch_secs, 0);
+        }
         (
             tm.tm_year + 1900,
             tm.tm_mon as u32 + 1,
@@ -139,23 +656,104 @@ fn local_datetime(epoch_secs: i64) -> (i32, u32, u32, u32, u32) {
             tm.tm_min as u32,
         )
     }
-    #[cfg(not(unix))]
+    #[cfg(windows)]
     {
-        let (y, m, d) = epoch_to_civil_utc(epoch_secs as u64);
-        let day_secs = (epoch_secs as u64) % 86400;
-        (
-            y,
-            m,
-            d,
-            (day_secs / 3600) as u32,
-            ((day_secs % 3600) / 60) as u32,
-        )
+        windows_time::local_datetime(epoch_secs)
+            .unwrap_or_else(|| datetime_at_offset(epoch_secs, 0))
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        datetime_at_offset(epoch_secs, 0)
+    }
+}
+
+/// Civil date and time of `epoch_secs` at a fixed `offset_secs` east of
+/// UTC; `0` is the UTC path.
+fn datetime_at_offset(epoch_secs: i64, offset_secs: i64) -> (i32, u32, u32, u32, u32) {
+    let secs = epoch_secs.saturating_add(offset_secs);
+    let (y, m, d) = civil_from_days(secs.div_euclid(86400));
+    let day_secs = secs.rem_euclid(86400);
+    (
+        y,
+        m,
+        d,
+        (day_secs / 3600) as u32,
+        (day_secs % 3600 / 60) as u32,
+    )
+}
+
+/// `SystemTimeToTzSpecificLocalTime` with the current time zone applies the
+/// daylight saving rules of the date being converted, not of today.
+#[cfg(windows)]
+mod windows_time {
+    use std::ffi::c_void;
+
+    #[repr(C)]
+    struct FileTime {
+        low: u32,
+        high: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct SystemTime {
+        year: u16,
+        month: u16,
+        day_of_week: u16,
+        day: u16,
+        hour: u16,
+        minute: u16,
+        second: u16,
+        milliseconds: u16,
+    }
+
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn FileTimeToSystemTime(file_time: *const FileTime, system_time: *mut SystemTime) -> i32;
+        fn SystemTimeToTzSpecificLocalTime(
+            time_zone: *const c_void,
+            universal: *const SystemTime,
+            local: *mut SystemTime,
+        ) -> i32;
+    }
+
+    /// Seconds from 1601-01-01 (the FILETIME epoch) to 1970-01-01.
+    const UNIX_EPOCH_IN_FILETIME_SECS: i64 = 11_644_473_600;
+
+    pub fn local_datetime(epoch_secs: i64) -> Option<(i32, u32, u32, u32, u32)> {
+        let ticks = u64::try_from(epoch_secs.checked_add(UNIX_EPOCH_IN_FILETIME_SECS)?)
+            .ok()?
+            .checked_mul(10_000_000)?;
+        let file_time = FileTime {
+            low: ticks as u32,
+            high: (ticks >> 32) as u32,
+        };
+        let mut utc = SystemTime::default();
+        let mut local = SystemTime::default();
+        // SAFETY: both calls only write to the SYSTEMTIME passed in.
+        let ok = unsafe {
+            FileTimeToSystemTime(&file_time, &mut utc) != 0
+                && SystemTimeToTzSpecificLocalTime(std::ptr::null(), &utc, &mut local) != 0
+        };
+        ok.then(|| {
+            (
+                i32::from(local.year),
+                u32::from(local.month),
+                u32::from(local.day),
+                u32::from(local.hour),
+                u32::from(local.minute),
+            )
+        })
     }
 }
 
-#[cfg(not(unix))]
 fn epoch_to_civil_utc(epoch_secs: u64) -> (i32, u32, u32) {
-    let z = (epoch_secs / 86400) as i64 + 719468;
+    civil_from_days((epoch_secs / 86400) as i64)
+}
+
+/// Proleptic Gregorian date of a day count since 1970-01-01.
+fn civil_from_days(days: i64) -> (i32, u32, u32) {
+    let z = days + 719468;
     let era = if z >= 0 { z } else { z - 146096 } / 146097;
     let doe = (z - era * 146097) as u64;
     let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
@@ -184,7 +782,7 @@ mod tests {
             ..Config::default()
         };
 
-        let result = resolve_with_date(&config, "2026-02-07");
+        let result = resolve_with_date(&config, "2026-02-07", None);
 
         assert_eq!(result, fixed_dir);
         assert!(result.is_dir());
@@ -207,7 +805,7 @@ mod tests {
             ..Config::default()
         };
 
-        let result = resolve_with_date(&config, "2026-02-07");
+        let result = resolve_with_date(&config, "2026-02-07", None);
 
         assert_eq!(result, fixed_dir);
     }
@@ -220,7 +818,7 @@ mod tests {
             ..Config::default()
         };
 
-        let result = resolve_with_date(&config, "2026-02-07");
+        let result = resolve_with_date(&config, "2026-02-07", None);
 
         let expected = tmp.path().join("planning").join("2026-02-07");
         assert_eq!(result, expected);
@@ -242,10 +840,122 @@ mod tests {
             ..Config::default()
         };
 
-        let result = resolve_with_date(&config, "2026-02-07");
+        let result = resolve_with_date(&config, "2026-02-07", None);
+        assert_eq!(result, fs::canonicalize(&sow_dir).unwrap());
+    }
+
+    #[test]
+    fn resolve_uses_sow_directory_when_current_sow_names_directory() {
+        let tmp = TempDir::new().unwrap();
+        let sow_dir = tmp.path().join("sow").join("project-x");
+        fs::create_dir_all(&sow_dir).unwrap();
+        fs::write(tmp.path().join(".current-sow"), sow_dir.to_str().unwrap()).unwrap();
+
+        let config = Config {
+            workspace_dir: tmp.path().to_path_buf(),
+            ..Config::default()
+        };
+
+        let result = resolve_with_date(&config, "2026-02-07", None);
+        assert_eq!(result, fs::canonicalize(&sow_dir).unwrap());
+    }
+
+    #[test]
+    fn resolve_accepts_sow_directory_with_trailing_slash() {
+        let tmp = TempDir::new().unwrap();
+        let sow_dir = tmp.path().join("sow").join("project-x");
+        fs::create_dir_all(&sow_dir).unwrap();
+        fs::write(
+            tmp.path().join(".current-sow"),
+            format!("{}/\n", sow_dir.display()),
+        )
+        .unwrap();
+
+        let config = Config {
+            workspace_dir: tmp.path().to_path_buf(),
+            ..Config::default()
+        };
+
+        let result = resolve_with_date(&config, "2026-02-07", None);
         assert_eq!(result, fs::canonicalize(&sow_dir).unwrap());
     }
 
+    #[test]
+    fn resolve_with_branch_nests_under_date_dir() {
+        let tmp = TempDir::new().unwrap();
+        let config = Config {
+            workspace_dir: tmp.path().to_path_buf(),
+            ..Config::default()
+        };
+
+        let result = resolve_with(&config, "2026-02-07", None, Some("fix-CVE-2025-1234"));
+
+        let expected = tmp
+            .path()
+            .join("planning")
+            .join("2026-02-07")
+            .join("fix-CVE-2025-1234");
+        assert_eq!(result, expected);
+        assert!(expected.is_dir());
+    }
+
+    #[test]
+    fn resolve_with_branch_nests_under_sow_dir() {
+        let tmp = TempDir::new().unwrap();
+        let sow_dir = tmp.path().join("sow").join("project-x");
+        fs::create_dir_all(&sow_dir).unwrap();
+        let sow_file = sow_dir.join("sow.md");
+        fs::write(&sow_file, "# SOW").unwrap();
+        fs::write(tmp.path().join(".current-sow"), sow_file.to_str().unwrap()).unwrap();
+        let config = Config {
+            workspace_dir: tmp.path().to_path_buf(),
+            ..Config::default()
+        };
+
+        let result = resolve_with(&config, "2026-02-07", None, Some("feature-auth"));
+
+        assert_eq!(
+            result,
+            fs::canonicalize(&sow_dir).unwrap().join("feature-auth")
+        );
+    }
+
+    #[test]
+    fn next_number_restarts_per_branch_dir() {
+        let tmp = TempDir::new().unwrap();
+        let config = Config {
+            output_dir: Some(tmp.path().to_path_buf()),
+            ..Config::default()
+        };
+        let main = resolve_with(&config, "2026-02-07", None, Some("main"));
+        fs::write(main.join("idr-04.md"), "content").unwrap();
+
+        let feature = resolve_with(&config, "2026-02-07", None, Some("feature-x"));
+
+        assert_eq!(next_number(&main, Format::Idr), 5);
+        assert_eq!(next_number(&feature, Format::Idr), 1);
+    }
+
+    #[test]
+    fn branch_dir_name_sanitizes_branch() {
+        assert_eq!(
+            branch_dir_name(Some("fix/CVE-2025-1234"), None),
+            "fix-CVE-2025-1234"
+        );
+        assert_eq!(
+            branch_dir_name(Some("user/feat/a b:c"), None),
+            "user-feat-a-b-c"
+        );
+        assert_eq!(branch_dir_name(Some("..hidden"), None), "hidden");
+        assert_eq!(branch_dir_name(Some(".."), None), "unnamed");
+    }
+
+    #[test]
+    fn branch_dir_name_names_detached_head_after_commit() {
+        assert_eq!(branch_dir_name(None, Some("a1b2c3d")), "detached-a1b2c3d");
+        assert_eq!(branch_dir_name(None, None), "detached-unknown");
+    }
+
     #[test]
     fn resolve_falls_back_to_date_when_sow_points_outside_workspace() {
         let workspace = TempDir::new().unwrap();
@@ -261,12 +971,76 @@ mod tests {
             ..Config::default()
         };
 
-        let result = resolve_with_date(&config, "2026-02-07");
+        let result = resolve_with_date(&config, "2026-02-07", None);
 
         let expected = workspace.path().join("planning").join("2026-02-07");
         assert_eq!(result, expected);
     }
 
+    #[test]
+    fn resolve_uses_repo_subdir_inside_repo() {
+        let workspace = TempDir::new().unwrap();
+        let repo = TempDir::new().unwrap();
+        let config = Config {
+            workspace_dir: workspace.path().to_path_buf(),
+            output_location: OutputLocation::Repo,
+            ..Config::default()
+        };
+
+        let result = resolve_with_date(&config, "2026-02-07", Some(repo.path()));
+
+        assert_eq!(result, repo.path().join("docs").join("decisions"));
+        assert!(result.is_dir());
+    }
+
+    #[test]
+    fn resolve_repo_location_falls_back_to_workspace_outside_repo() {
+        let workspace = TempDir::new().unwrap();
+        let config = Config {
+            workspace_dir: workspace.path().to_path_buf(),
+            output_location: OutputLocation::Repo,
+            ..Config::default()
+        };
+
+        let result = resolve_with_date(&config, "2026-02-07", None);
+
+        assert_eq!(result, workspace.path().join("planning").join("2026-02-07"));
+    }
+
+    #[test]
+    fn resolve_fixed_output_dir_takes_priority_over_repo() {
+        let repo = TempDir::new().unwrap();
+        let output = TempDir::new().unwrap();
+        let config = Config {
+            output_dir: Some(output.path().to_path_buf()),
+            output_location: OutputLocation::Repo,
+            ..Config::default()
+        };
+
+        let result = resolve_with_date(&config, "2026-02-07", Some(repo.path()));
+
+        assert_eq!(result, output.path());
+    }
+
+    #[test]
+    fn resolve_auto_location_uses_repo_only_when_subdir_exists() {
+        let workspace = TempDir::new().unwrap();
+        let repo = TempDir::new().unwrap();
+        let config = Config {
+            workspace_dir: workspace.path().to_path_buf(),
+            output_location: OutputLocation::Auto,
+            repo_output_subdir: PathBuf::from("doc/adr"),
+            ..Config::default()
+        };
+
+        let before = planned_dir(&config, "2026-02-07", Some(repo.path()));
+        assert_eq!(before, workspace.path().join("planning").join("2026-02-07"));
+
+        fs::create_dir_all(repo.path().join("doc/adr")).unwrap();
+        let after = planned_dir(&config, "2026-02-07", Some(repo.path()));
+        assert_eq!(after, repo.path().join("doc/adr"));
+    }
+
     #[test]
     fn resolve_falls_back_to_date_when_sow_file_does_not_exist() {
         let tmp = TempDir::new().unwrap();
@@ -278,7 +1052,7 @@ mod tests {
             ..Config::default()
         };
 
-        let result = resolve_with_date(&config, "2026-02-07");
+        let result = resolve_with_date(&config, "2026-02-07", None);
 
         let expected = tmp.path().join("planning").join("2026-02-07");
         assert_eq!(result, expected);
@@ -287,7 +1061,7 @@ mod tests {
     #[test]
     fn next_number_returns_1_for_empty_directory() {
         let tmp = TempDir::new().unwrap();
-        assert_eq!(next_number(tmp.path()), 1);
+        assert_eq!(next_number(tmp.path(), Format::Idr), 1);
     }
 
     #[test]
@@ -296,7 +1070,7 @@ mod tests {
         fs::write(tmp.path().join("idr-01.md"), "content").unwrap();
         fs::write(tmp.path().join("idr-03.md"), "content").unwrap();
 
-        assert_eq!(next_number(tmp.path()), 4);
+        assert_eq!(next_number(tmp.path(), Format::Idr), 4);
     }
 
     #[test]
@@ -307,14 +1081,14 @@ mod tests {
         fs::write(tmp.path().join("readme.txt"), "other").unwrap();
         fs::write(tmp.path().join("idr-summary.md"), "other").unwrap();
 
-        assert_eq!(next_number(tmp.path()), 3);
+        assert_eq!(next_number(tmp.path(), Format::Idr), 3);
     }
 
     #[test]
     fn next_number_returns_1_for_nonexistent_directory() {
         let tmp = TempDir::new().unwrap();
         let nonexistent = tmp.path().join("does-not-exist");
-        assert_eq!(next_number(&nonexistent), 1);
+        assert_eq!(next_number(&nonexistent, Format::Idr), 1);
     }
 
     #[test]
@@ -322,7 +1096,7 @@ mod tests {
         let tmp = TempDir::new().unwrap();
         fs::write(tmp.path().join("idr-99.md"), "content").unwrap();
 
-        assert_eq!(next_number(tmp.path()), 100);
+        assert_eq!(next_number(tmp.path(), Format::Idr), 100);
     }
 
     #[test]
@@ -338,19 +1112,204 @@ mod tests {
         assert_eq!(parse_idr_number("idr-.md"), None);
         assert_eq!(parse_idr_number("idr-abc.md"), None);
         assert_eq!(parse_idr_number("idr-01.txt"), None);
+        assert_eq!(parse_idr_number("idr-01-.md"), None);
+        assert_eq!(parse_idr_number("idr--jwt.md"), None);
+        assert_eq!(parse_idr_number("idr-+1.md"), None);
+        assert_eq!(parse_idr_number("idr-2026-02.md"), None, "monthly journal");
+    }
+
+    #[test]
+    fn parse_idr_number_accepts_slug_suffix() {
+        assert_eq!(parse_idr_number("idr-07-switch-auth-to-jwt.md"), Some(7));
+        assert_eq!(parse_idr_number("idr-100-x.md"), Some(100));
+    }
+
+    #[test]
+    fn next_number_counts_slugged_and_bare_files() {
+        let tmp = TempDir::new().unwrap();
+        fs::write(tmp.path().join("idr-03.md"), "content").unwrap();
+        fs::write(tmp.path().join("idr-09-use-jwt.md"), "content").unwrap();
+        fs::write(tmp.path().join("idr-05-cache.md"), "content").unwrap();
+
+        assert_eq!(next_number(tmp.path(), Format::Idr), 10);
+    }
+
+    #[test]
+    fn sanitize_purpose_takes_first_line_without_label() {
+        assert_eq!(
+            sanitize_purpose("\n\nPurpose: Add retries to the fetcher\nBecause the API flakes.\n")
+                .as_deref(),
+            Some("Add retries to the fetcher")
+        );
+        assert_eq!(
+            sanitize_purpose("目的: キャッシュを追加").as_deref(),
+            Some("キャッシュを追加")
+        );
+    }
+
+    #[test]
+    fn sanitize_purpose_strips_markdown() {
+        assert_eq!(
+            sanitize_purpose("## **Main purpose:** use `JwtAuth`   for   sessions").as_deref(),
+            Some("use JwtAuth for sessions")
+        );
+        assert_eq!(
+            sanitize_purpose("- \"Rename snake_case helpers\"").as_deref(),
+            Some("Rename snake_case helpers")
+        );
+        assert_eq!(
+            sanitize_purpose("**Purpose:**\nCache lookups").as_deref(),
+            Some("Cache lookups")
+        );
+    }
+
+    #[test]
+    fn sanitize_purpose_caps_length_and_rejects_empty() {
+        let capped = sanitize_purpose(&"word ".repeat(60)).unwrap();
+        assert_eq!(capped.chars().count(), PURPOSE_MAX_CHARS);
+        assert!(capped.ends_with("word…"));
+        assert_eq!(sanitize_purpose(""), None);
+        assert_eq!(sanitize_purpose("  \n**``**\n"), None);
+    }
+
+    #[test]
+    fn file_name_slugs_sanitized_purpose() {
+        assert_eq!(
+            file_name(Format::Idr, 3, Some("Purpose: Use JWT\nmore text"), 2),
+            "idr-03-use-jwt.md"
+        );
+    }
+
+    #[test]
+    fn file_name_pads_to_width() {
+        assert_eq!(file_name(Format::Idr, 7, None, 3), "idr-007.md");
+        assert_eq!(file_name(Format::Idr, 1234, Some("x"), 3), "idr-1234-x.md");
+        assert_eq!(file_name(Format::Madr, 7, None, 3), "0007-decision.md");
+    }
+
+    #[test]
+    fn has_mixed_widths_detects_inconsistent_names() {
+        let records = |names: &[&str]| -> Vec<(u32, PathBuf)> {
+            names
+                .iter()
+                .map(|n| (parse_idr_number(n).unwrap(), PathBuf::from(n)))
+                .collect()
+        };
+
+        assert!(!has_mixed_widths(&records(&["idr-01.md", "idr-02-x.md"])));
+        assert!(has_mixed_widths(&records(&["idr-99.md", "idr-100.md"])));
+        assert!(!has_mixed_widths(&records(&[])));
+    }
+
+    #[test]
+    fn slugify_keeps_ascii_words() {
+        assert_eq!(slugify("Switch auth to JWT (v2)"), "switch-auth-to-jwt-v2");
+        assert_eq!(slugify("Café déjà vu"), "caf-d-j-vu");
+        assert_eq!(slugify("認証をJWTに変更"), "jwt");
+        assert_eq!(slugify("認証の変更"), "");
+        assert_eq!(slugify("  --  "), "");
     }
 
     #[test]
-    fn write_idr_creates_file_with_correct_format() {
+    fn slugify_caps_length_at_word_boundary() {
+        let slug = slugify("Add exponential backoff retries to the API client for 429 responses");
+        assert_eq!(slug, "add-exponential-backoff-retries-to-the");
+        assert!(slug.len() <= SLUG_MAX_CHARS);
+
+        assert_eq!(slugify(&"a".repeat(60)), "a".repeat(SLUG_MAX_CHARS));
+    }
+
+    #[test]
+    fn parse_madr_number_reads_four_digit_prefix() {
+        assert_eq!(parse_madr_number("0001-use-jwt.md"), Some(1));
+        assert_eq!(parse_madr_number("0042-switch-db.md"), Some(42));
+        assert_eq!(parse_madr_number("0042-.md"), None);
+        assert_eq!(parse_madr_number("42-switch-db.md"), None);
+        assert_eq!(parse_madr_number("idr-01.md"), None);
+        assert_eq!(parse_madr_number("0001-use-jwt.txt"), None);
+    }
+
+    #[test]
+    fn next_number_uses_numbering_of_selected_format() {
         let tmp = TempDir::new().unwrap();
-        let path = tmp.path().join("idr-01.md");
-        let purpose = Some("テスト目的".to_string());
+        fs::write(tmp.path().join("idr-07.md"), "content").unwrap();
+        fs::write(tmp.path().join("0003-use-jwt.md"), "content").unwrap();
+        fs::write(tmp.path().join("0012-drop-sessions.md"), "content").unwrap();
+
+        assert_eq!(next_number(tmp.path(), Format::Idr), 8);
+        assert_eq!(next_number(tmp.path(), Format::Madr), 13);
+    }
+
+    #[test]
+    fn file_name_follows_format() {
+        assert_eq!(
+            file_name(Format::Idr, 3, Some("Use JWT"), 2),
+            "idr-03-use-jwt.md"
+        );
+        assert_eq!(
+            file_name(Format::Idr, 3, Some("認証の変更"), 2),
+            "idr-03.md"
+        );
+        assert_eq!(file_name(Format::Idr, 3, None, 2), "idr-03.md");
+        assert_eq!(
+            file_name(Format::Madr, 3, Some("Switch auth to JWT (v2)"), 2),
+            "0003-switch-auth-to-jwt-v2.md"
+        );
+        assert_eq!(
+            file_name(Format::Madr, 12, Some("認証をJWTに変更"), 2),
+            "0012-jwt.md"
+        );
+        assert_eq!(
+            file_name(Format::Madr, 1, Some("認証"), 2),
+            "0001-decision.md"
+        );
+        assert_eq!(file_name(Format::Madr, 1, None, 2), "0001-decision.md");
+    }
+
+    fn record<'a>(title: &'a str, content: &'a str, stat: &'a str) -> Record<'a> {
+        Record {
+            title,
+            content,
+            stat: Some(stat),
+            footer: None,
+            related: None,
+            tags: &[],
+            metadata: None,
+            front_matter: None,
+            sidecar: None,
+        }
+    }
+
+    #[test]
+    fn render_record_renders_madr_skeleton() {
+        let config = Config {
+            format: Format::Madr,
+            ..Config::default()
+        };
+
+        let result = render_record(
+            &record("Use JWT", "## Context\n\nctx", "stat"),
+            &config,
+            "2026-02-07 14:30",
+        );
+
+        assert!(result.starts_with(
+            "# Use JWT\n\n* Status: accepted\n* Date: 2026-02-07\n\n## Context\n\nctx\n\n---\n"
+        ));
+        assert!(result.ends_with("```\nstat\n```\n"));
+    }
+
+    #[test]
+    fn render_record_uses_idr_layout() {
         let content = "## 変更概要\n\nテスト内容";
         let stat = " src/main.rs | 10 +++++++---";
 
-        write_idr_at(&path, &purpose, content, stat, "2026-02-07 14:30");
+        let result = render_record(
+            &record("テスト目的", content, stat),
+            &Config::default(),
+            "2026-02-07 14:30",
+        );
 
-        let result = fs::read_to_string(&path).unwrap();
         assert!(result.starts_with("# IDR: テスト目的\n\n> 2026-02-07 14:30"));
         assert!(result.contains(content));
         assert!(result.contains("---\n\n### git diff --stat\n```\n"));
@@ -359,30 +1318,342 @@ mod tests {
     }
 
     #[test]
-    fn write_idr_uses_fallback_purpose_when_none() {
+    fn render_record_puts_related_line_under_date() {
+        let record = Record {
+            related: Some("PROJ-1, #2"),
+            ..record("purpose", "content", "stat")
+        };
+
+        let idr = render_record(&record, &Config::default(), "2026-01-01 00:00");
+        let madr = Config {
+            format: Format::Madr,
+            ..Config::default()
+        };
+        let madr = render_record(&record, &madr, "2026-01-01 00:00");
+
+        assert!(idr.contains("> 2026-01-01 00:00\n> Related: PROJ-1, #2\n\ncontent\n"));
+        assert!(madr.contains("* Date: 2026-01-01\n* Related: PROJ-1, #2\n\ncontent\n"));
+    }
+
+    #[test]
+    fn render_record_links_daily_note_in_obsidian_mode() {
+        let tags = ["idr".to_string(), "fix".to_string()];
+        let record = Record {
+            related: Some("#2"),
+            tags: &tags,
+            ..record("purpose", "content", "stat")
+        };
+        let obsidian = Config {
+            obsidian_mode: true,
+            ..Config::default()
+        };
+        let madr = Config {
+            format: Format::Madr,
+            ..obsidian.clone()
+        };
+
+        let idr = render_record(&record, &obsidian, "2026-02-07 17:30");
+        let madr = render_record(&record, &madr, "2026-02-07 17:30");
+        let plain = render_record(&record, &Config::default(), "2026-02-07 17:30");
+
+        assert!(idr.contains(
+            "> 2026-02-07 17:30\n> Related: #2\n> [[2026-02-07]] #idr #fix\n\ncontent\n"
+        ));
+        assert!(madr.contains("* Related: #2\n* [[2026-02-07]] #idr #fix\n\ncontent\n"));
+        assert!(!plain.contains("[["), "{plain}");
+    }
+
+    #[test]
+    fn render_record_leaves_tags_to_front_matter_in_obsidian_mode() {
+        let front_matter = FrontMatter {
+            title: "t".to_string(),
+            date: "2026-02-07T08:30:00Z".to_string(),
+            model: "sonnet".to_string(),
+            session_id: None,
+            branch: None,
+            files_changed: 1,
+            insertions: 1,
+            deletions: 0,
+            tags: vec!["idr".to_string()],
+        };
+        let tags = ["idr".to_string()];
+        let record = Record {
+            tags: &tags,
+            front_matter: Some(&front_matter),
+            ..record("t", "content", "stat")
+        };
+        let config = Config {
+            obsidian_mode: true,
+            ..Config::default()
+        };
+
+        let result = render_record(&record, &config, "2026-02-07 17:30");
+
+        assert!(result.contains("> 2026-02-07 17:30\n> [[2026-02-07]]\n\ncontent\n"));
+    }
+
+    #[test]
+    fn render_record_marks_session_report_in_place_of_stat() {
+        let record = Record {
+            stat: None,
+            footer: Some("> tokens: 100 in / 20 out"),
+            ..record("purpose", "## What was investigated", "")
+        };
+
+        let result = render_record(&record, &Config::default(), "2026-01-01 00:00");
+
+        assert!(result.ends_with(&format!(
+            "---\n\n{SESSION_REPORT_MARKER}\n\n> tokens: 100 in / 20 out\n"
+        )));
+        assert!(!result.contains("git diff --stat"));
+    }
+
+    #[test]
+    fn render_record_appends_footer_after_stat_block() {
+        let record = Record {
+            footer: Some("> tokens: 100 in / 20 out"),
+            ..record("purpose", "content", "stat")
+        };
+
+        let result = render_record(&record, &Config::default(), "2026-01-01 00:00");
+
+        assert!(result.ends_with("```\nstat\n```\n\n> tokens: 100 in / 20 out\n"));
+    }
+
+    #[test]
+    fn render_record_ends_with_provenance_footer_and_comment() {
+        let metadata = Metadata {
+            version: "0.1.1".to_string(),
+            prompt: "v3".to_string(),
+            model: "sonnet".to_string(),
+            session: Some("5f0c3a2e".to_string()),
+            project: Some("-ws-repo".to_string()),
+            branch: Some("main".to_string()),
+            generated: "2026-01-01T00:00:00Z".to_string(),
+            ..Metadata::default()
+        };
+        let record = Record {
+            footer: Some("> footer"),
+            metadata: Some(&metadata),
+            ..record("purpose", "content", "stat")
+        };
+
+        let result = render_record(&record, &Config::default(), "2026-01-01 00:00");
+
+        assert!(result.ends_with(
+            "```\nstat\n```\n\n> footer\n\n> session: 5f0c3a2e (project -ws-repo)\n> models: body sonnet\n> claude-idr 0.1.1, branch main\n\n<!-- claude-idr: version=0.1.1 prompt=v3 model=sonnet session=5f0c3a2e project=-ws-repo branch=main generated=2026-01-01T00:00:00Z -->\n"
+        ));
+        assert_eq!(Metadata::parse(&result), Some(metadata));
+    }
+
+    #[test]
+    fn render_record_puts_front_matter_in_place_of_title() {
+        let front_matter = FrontMatter {
+            title: "Use JWT".to_string(),
+            date: "2026-01-01T00:00:00Z".to_string(),
+            model: "sonnet".to_string(),
+            session_id: None,
+            branch: Some("main".to_string()),
+            files_changed: 1,
+            insertions: 2,
+            deletions: 0,
+            tags: vec!["idr".to_string()],
+        };
+        let record = Record {
+            front_matter: Some(&front_matter),
+            ..record("Use JWT", "content", "stat")
+        };
+
+        let result = render_record(&record, &Config::default(), "2026-01-01 00:00");
+
+        assert!(result.starts_with("---\ntitle: \"Use JWT\"\n"));
+        assert!(result.contains("---\n\n> 2026-01-01 00:00\n\ncontent\n"));
+        assert!(!result.contains("# IDR:"));
+    }
+
+    #[test]
+    fn iso8601_utc_formats_epoch() {
+        assert_eq!(iso8601_utc(1770442245), "2026-02-07T05:30:45Z");
+        assert_eq!(iso8601_utc(0), "1970-01-01T00:00:00Z");
+    }
+
+    #[test]
+    fn allocate_and_write_uses_next_number_and_leaves_no_temp_file() {
+        let tmp = TempDir::new().unwrap();
+        fs::write(tmp.path().join("idr-01.md"), "content").unwrap();
+
+        let path = allocate_and_write(
+            tmp.path(),
+            Some("Use JWT"),
+            &record("Use JWT", "content", "stat"),
+            &Config::default(),
+        )
+        .unwrap();
+
+        assert_eq!(path, tmp.path().join("idr-02-use-jwt.md"));
+        assert!(
+            fs::read_to_string(&path)
+                .unwrap()
+                .starts_with("# IDR: Use JWT\n")
+        );
+        let mut names: Vec<_> = fs::read_dir(tmp.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["idr-01.md", "idr-02-use-jwt.md"]);
+    }
+
+    #[test]
+    fn allocate_and_write_skips_number_taken_since_it_was_computed() {
+        let tmp = TempDir::new().unwrap();
+        let first = next_number(tmp.path(), Format::Idr);
+        fs::write(tmp.path().join("idr-01.md"), "other run").unwrap();
+
+        let path =
+            allocate_and_write_from(tmp.path(), first, None, "mine", Format::Idr, 2).unwrap();
+
+        assert_eq!(path, tmp.path().join("idr-02.md"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "mine");
+        assert_eq!(
+            fs::read_to_string(tmp.path().join("idr-01.md")).unwrap(),
+            "other run"
+        );
+    }
+
+    #[test]
+    fn allocate_and_write_moves_past_precreated_record() {
+        let tmp = TempDir::new().unwrap();
+        fs::write(tmp.path().join("idr-05.md"), "existing").unwrap();
+
+        let path = allocate_and_write_from(tmp.path(), 5, None, "new", Format::Idr, 2).unwrap();
+
+        assert_eq!(path, tmp.path().join("idr-06.md"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(
+            fs::read_to_string(tmp.path().join("idr-05.md")).unwrap(),
+            "existing"
+        );
+    }
+
+    #[test]
+    fn allocate_and_write_treats_other_slug_as_taken() {
+        let tmp = TempDir::new().unwrap();
+        fs::write(tmp.path().join("idr-05-cache.md"), "existing").unwrap();
+
+        let path =
+            allocate_and_write_from(tmp.path(), 5, Some("Use JWT"), "new", Format::Idr, 2).unwrap();
+
+        assert_eq!(path, tmp.path().join("idr-06-use-jwt.md"));
+    }
+
+    #[test]
+    fn allocate_and_write_gives_up_after_bounded_attempts() {
         let tmp = TempDir::new().unwrap();
-        let path = tmp.path().join("idr-01.md");
+        for n in 1..=MAX_ALLOCATION_ATTEMPTS {
+            fs::write(tmp.path().join(format!("idr-{n:02}.md")), "x").unwrap();
+        }
+
+        let err = allocate_and_write_from(tmp.path(), 1, None, "new", Format::Idr, 2).unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read_dir(tmp.path()).unwrap().count(), 100);
+    }
+
+    #[test]
+    fn allocate_and_write_creates_parent_directories() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path().join("nested").join("dir");
 
-        write_idr_at(&path, &None, "content", "stat", "2026-01-01 00:00");
+        let path = allocate_and_write_from(&dir, 1, None, "body", Format::Idr, 2).unwrap();
+
+        assert_eq!(path, dir.join("idr-01.md"));
+    }
+
+    #[test]
+    fn claim_block_reserves_consecutive_numbers_past_collisions() {
+        let tmp = TempDir::new().unwrap();
+        fs::write(tmp.path().join("idr-01.md"), "existing").unwrap();
+        fs::write(tmp.path().join("idr-04-other.md"), "other run").unwrap();
+
+        let paths = claim_block_from(tmp.path(), 2, &[None, Some("src/a.rs")], Format::Idr, 2)
+            .unwrap()
+            .paths;
+        assert_eq!(
+            paths,
+            [
+                tmp.path().join("idr-02.md"),
+                tmp.path().join("idr-03-src-a-rs.md")
+            ]
+        );
 
-        let result = fs::read_to_string(&path).unwrap();
-        assert!(result.starts_with("# IDR: (目的抽出失敗)\n\n> 2026-01-01 00:00"));
+        let paths = claim_block_from(tmp.path(), 3, &[None, None], Format::Idr, 2)
+            .unwrap()
+            .paths;
+        assert_eq!(
+            paths,
+            [tmp.path().join("idr-05.md"), tmp.path().join("idr-06.md")]
+        );
+        assert_eq!(fs::read_to_string(&paths[0]).unwrap(), "");
     }
 
     #[test]
-    fn write_idr_creates_parent_directories() {
+    fn write_claimed_fills_claimed_file() {
         let tmp = TempDir::new().unwrap();
-        let path = tmp.path().join("nested").join("dir").join("idr-01.md");
+        let config = Config::default();
+        let paths = claim_block(tmp.path(), &[Some("Use JWT")], &config)
+            .unwrap()
+            .paths;
+
+        write_claimed(&paths[0], &record("Use JWT", "content", "stat"), &config).unwrap();
 
-        write_idr_at(&path, &None, "content", "stat", "2026-01-01 00:00");
+        assert!(
+            fs::read_to_string(&paths[0])
+                .unwrap()
+                .starts_with("# IDR: Use JWT\n")
+        );
+        assert_eq!(fs::read_dir(tmp.path()).unwrap().count(), 1);
+    }
 
-        assert!(path.exists());
+    #[test]
+    fn datetime_at_offset_applies_fixed_offsets() {
+        let epoch = 1770442245; // 2026-02-07T05:30:45Z
+        assert_eq!(datetime_at_offset(epoch, 0), (2026, 2, 7, 5, 30));
+        assert_eq!(datetime_at_offset(epoch, 9 * 3600), (2026, 2, 7, 14, 30));
+        assert_eq!(
+            datetime_at_offset(epoch, 5 * 3600 + 1800),
+            (2026, 2, 7, 11, 0)
+        );
+        // West of UTC, early morning is still the previous day.
+        assert_eq!(datetime_at_offset(epoch, -8 * 3600), (2026, 2, 6, 21, 30));
+    }
+
+    #[test]
+    fn datetime_at_offset_crosses_month_and_year_boundaries() {
+        let new_year = 1767225600; // 2026-01-01T00:00:00Z
+        assert_eq!(datetime_at_offset(new_year, -60), (2025, 12, 31, 23, 59));
+        assert_eq!(datetime_at_offset(0, -3600), (1969, 12, 31, 23, 0));
+        let leap = 1709164800; // 2024-02-29T00:00:00Z
+        assert_eq!(datetime_at_offset(leap, 0), (2024, 2, 29, 0, 0));
+        assert_eq!(datetime_at_offset(leap, 86400), (2024, 3, 1, 0, 0));
+    }
+
+    #[test]
+    fn local_datetime_is_utc_shifted_by_a_real_offset() {
+        // Whatever the machine's zone, local time must equal UTC moved by a
+        // whole number of quarter hours within +-14h.
+        for epoch in [0, 1770422400, 1782864000] {
+            let local = local_datetime(epoch);
+            let found =
+                (-56..=56).any(|quarters: i64| datetime_at_offset(epoch, quarters * 900) == local);
+            assert!(found, "{local:?} is not a fixed offset from {epoch}");
+        }
     }
 
     #[test]
     fn local_datetime_returns_valid_components() {
         let (y, m, d, h, min) = local_datetime(1770422400); // 2026-02-07 UTC
-        assert!(y >= 2026 && y <= 2027);
+        assert!((2026..=2027).contains(&y));
         assert!((1..=12).contains(&m));
         assert!((1..=31).contains(&d));
         assert!(h < 24);
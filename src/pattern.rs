@@ -0,0 +1,306 @@
+//! A small regular-expression subset for patterns in the config file, so
+//! they need no regex crate: literals, `.`, `\d` `\w` `\s` and their
+//! negations, `[...]` classes, `^` and `$`, the quantifiers `?` `*` `+`
+//! `{n}` `{n,}` `{n,m}`, and top-level `|`. Groups are not supported.
+//! Quantifiers are greedy and alternatives are tried left to right.
+
+use std::iter::Peekable;
+use std::ops::Range;
+use std::str::Chars;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    alternatives: Vec<Vec<Item>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Item {
+    atom: Atom,
+    min: usize,
+    max: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Atom {
+    Char(char),
+    Any,
+    Class {
+        ranges: Vec<(char, char)>,
+        negated: bool,
+    },
+    Start,
+    End,
+}
+
+impl Atom {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Atom::Char(expected) => c == *expected,
+            Atom::Any => c != '\n',
+            Atom::Class { ranges, negated } => {
+                ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi) != *negated
+            }
+            Atom::Start | Atom::End => false,
+        }
+    }
+}
+
+impl Pattern {
+    pub fn parse(source: &str) -> Result<Pattern, String> {
+        let mut alternatives = vec![Vec::new()];
+        let mut chars = source.chars().peekable();
+        while let Some(c) = chars.next() {
+            let atom = match c {
+                '|' => {
+                    alternatives.push(Vec::new());
+                    continue;
+                }
+                '(' | ')' => return Err(format!("groups are not supported: {source}")),
+                '?' | '*' | '+' | '{' => return Err(format!("nothing to repeat: {source}")),
+                '.' => Atom::Any,
+                '^' => Atom::Start,
+                '$' => Atom::End,
+                '[' => parse_class(&mut chars, source)?,
+                '\\' => parse_escape(&mut chars, source)?,
+                c => Atom::Char(c),
+            };
+            let (min, max) = match parse_quantifier(&mut chars, source)? {
+                Some(_) if matches!(atom, Atom::Start | Atom::End) => {
+                    return Err(format!("nothing to repeat: {source}"));
+                }
+                Some(bounds) => bounds,
+                None => (1, 1),
+            };
+            if let Some(items) = alternatives.last_mut() {
+                items.push(Item { atom, min, max });
+            }
+        }
+        Ok(Pattern { alternatives })
+    }
+
+    /// Byte ranges of the leftmost non-overlapping, non-empty matches.
+    pub fn find_all(&self, text: &str) -> Vec<Range<usize>> {
+        let indexed: Vec<(usize, char)> = text.char_indices().collect();
+        let chars: Vec<char> = indexed.iter().map(|&(_, c)| c).collect();
+        let byte = |i: usize| indexed.get(i).map_or(text.len(), |&(b, _)| b);
+        let mut found = Vec::new();
+        let mut pos = 0;
+        while pos < chars.len() {
+            match self.match_at(&chars, pos) {
+                Some(end) if end > pos => {
+                    found.push(byte(pos)..byte(end));
+                    pos = end;
+                }
+                _ => pos += 1,
+            }
+        }
+        found
+    }
+
+    fn match_at(&self, chars: &[char], pos: usize) -> Option<usize> {
+        self.alternatives
+            .iter()
+            .find_map(|items| match_items(items, chars, pos))
+    }
+}
+
+/// End of the longest match of `items` at `pos`, backtracking through the
+/// repeat counts of each item.
+fn match_items(items: &[Item], chars: &[char], pos: usize) -> Option<usize> {
+    let Some((item, rest)) = items.split_first() else {
+        return Some(pos);
+    };
+    match item.atom {
+        Atom::Start if pos != 0 => return None,
+        Atom::End if pos != chars.len() => return None,
+        Atom::Start | Atom::End => return match_items(rest, chars, pos),
+        _ => {}
+    }
+    let mut count = 0;
+    while count < item.max
+        && chars
+            .get(pos + count)
+            .is_some_and(|&c| item.atom.matches(c))
+    {
+        count += 1;
+    }
+    (item.min..=count)
+        .rev()
+        .find_map(|n| match_items(rest, chars, pos + n))
+}
+
+fn parse_escape(chars: &mut Peekable<Chars>, source: &str) -> Result<Atom, String> {
+    let class = |ranges: &[(char, char)], negated| Atom::Class {
+        ranges: ranges.to_vec(),
+        negated,
+    };
+    const DIGIT: &[(char, char)] = &[('0', '9')];
+    const WORD: &[(char, char)] = &[('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')];
+    const SPACE: &[(char, char)] = &[('\t', '\r'), (' ', ' ')];
+    Ok(match chars.next() {
+        Some('d') => class(DIGIT, false),
+        Some('D') => class(DIGIT, true),
+        Some('w') => class(WORD, false),
+        Some('W') => class(WORD, true),
+        Some('s') => class(SPACE, false),
+        Some('S') => class(SPACE, true),
+        Some('n') => Atom::Char('\n'),
+        Some('t') => Atom::Char('\t'),
+        Some(c) if !c.is_ascii_alphanumeric() => Atom::Char(c),
+        Some(c) => return Err(format!("unsupported escape \\{c}: {source}")),
+        None => return Err(format!("trailing backslash: {source}")),
+    })
+}
+
+fn parse_class(chars: &mut Peekable<Chars>, source: &str) -> Result<Atom, String> {
+    let negated = chars.next_if_eq(&'^').is_some();
+    let mut ranges = Vec::new();
+    let mut first = true;
+    loop {
+        let lo = match chars.next() {
+            None => return Err(format!("unclosed [: {source}")),
+            Some(']') if !first => break,
+            Some('\\') => match parse_escape(chars, source)? {
+                Atom::Char(c) => c,
+                Atom::Class {
+                    ranges: escaped,
+                    negated: false,
+                } => {
+                    ranges.extend(escaped);
+                    first = false;
+                    continue;
+                }
+                _ => return Err(format!("negated escape inside [...]: {source}")),
+            },
+            Some(c) => c,
+        };
+        first = false;
+        let hi = if chars.peek() == Some(&'-') {
+            let mut ahead = chars.clone();
+            ahead.next();
+            match ahead.next() {
+                Some(']') | None => lo,
+                Some(hi) => {
+                    chars.next();
+                    chars.next();
+                    if hi < lo {
+                        return Err(format!("bad range {lo}-{hi}: {source}"));
+                    }
+                    hi
+                }
+            }
+        } else {
+            lo
+        };
+        ranges.push((lo, hi));
+    }
+    Ok(Atom::Class { ranges, negated })
+}
+
+fn parse_quantifier(
+    chars: &mut Peekable<Chars>,
+    source: &str,
+) -> Result<Option<(usize, usize)>, String> {
+    let bounds = match chars.peek() {
+        Some('?') => (0, 1),
+        Some('*') => (0, usize::MAX),
+        Some('+') => (1, usize::MAX),
+        Some('{') => {
+            chars.next();
+            let mut spec = String::new();
+            loop {
+                match chars.next() {
+                    Some('}') => break,
+                    Some(c) => spec.push(c),
+                    None => return Err(format!("unclosed {{: {source}")),
+                }
+            }
+            let number = |s: &str| {
+                s.trim()
+                    .parse::<usize>()
+                    .map_err(|_| format!("bad repetition {{{spec}}}: {source}"))
+            };
+            let bounds = match spec.split_once(',') {
+                None => (number(&spec)?, number(&spec)?),
+                Some((min, "")) => (number(min)?, usize::MAX),
+                Some((min, max)) => (number(min)?, number(max)?),
+            };
+            if bounds.0 > bounds.1 {
+                return Err(format!("bad repetition {{{spec}}}: {source}"));
+            }
+            return Ok(Some(bounds));
+        }
+        _ => return Ok(None),
+    };
+    chars.next();
+    Ok(Some(bounds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches<'t>(pattern: &str, text: &'t str) -> Vec<&'t str> {
+        Pattern::parse(pattern)
+            .unwrap()
+            .find_all(text)
+            .into_iter()
+            .map(|range| &text[range])
+            .collect()
+    }
+
+    #[test]
+    fn find_all_returns_leftmost_greedy_matches() {
+        assert_eq!(
+            matches("[A-Z][A-Z0-9]+-[0-9]+", "PROJ-12 and AB2-345x"),
+            ["PROJ-12", "AB2-345"]
+        );
+        assert_eq!(matches(r"#\d+", "#1, #23"), ["#1", "#23"]);
+        assert_eq!(matches("a{2,3}", "aaaaaaa"), ["aaa", "aaa"]);
+        assert_eq!(matches("ab?c", "ac abc abbc"), ["ac", "abc"]);
+    }
+
+    #[test]
+    fn find_all_backtracks_into_repeats() {
+        assert_eq!(matches(r"\w+-\d", "a-b-1"), ["b-1"]);
+        assert_eq!(matches(".*x", "abxcx!"), ["abxcx"]);
+    }
+
+    #[test]
+    fn alternatives_are_tried_in_order() {
+        assert_eq!(matches("ab|a", "ab a"), ["ab", "a"]);
+        assert_eq!(matches("a|ab", "ab"), ["a"]);
+    }
+
+    #[test]
+    fn anchors_only_match_at_the_ends() {
+        assert_eq!(matches("^x", "xx"), ["x"]);
+        assert_eq!(matches("x$", "xx"), ["x"]);
+        assert!(matches("^$", "").is_empty(), "empty matches are skipped");
+    }
+
+    #[test]
+    fn classes_support_ranges_negation_and_escapes() {
+        assert_eq!(matches(r"[^\s,]+", "a-b, c"), ["a-b", "c"]);
+        assert_eq!(matches("[a-]+", "a-a b"), ["a-a"]);
+        assert_eq!(matches(r"[\].]+", "x].]y"), ["].]"]);
+        assert_eq!(matches(r"\.", "a.b"), ["."]);
+    }
+
+    #[test]
+    fn find_all_returns_byte_ranges_in_unicode_text() {
+        let text = "修正 #12 完了";
+        let ranges = Pattern::parse(r"#\d+").unwrap().find_all(text);
+        assert_eq!(
+            ranges.iter().map(|r| &text[r.clone()]).collect::<Vec<_>>(),
+            ["#12"]
+        );
+    }
+
+    #[test]
+    fn parse_rejects_what_it_cannot_match() {
+        for bad in ["(a)", "*a", "a{3,1}", "a{x}", "[a", r"a\", r"\q", "^+"] {
+            assert!(Pattern::parse(bad).is_err(), "{bad}");
+        }
+    }
+}
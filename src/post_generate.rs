@@ -0,0 +1,160 @@
+//! `post_generate_command`: a shell command of the user's run after a
+//! record is written (and, with `post_generate_on_skip`, after a skip or an
+//! error), told what happened through `IDR_*` environment variables. It can
+//! never hold up the commit for longer than `post_generate_timeout_secs`.
+
+use crate::config::Config;
+use crate::generate::GenerateReport;
+use crate::history;
+use crate::shell;
+use std::fs;
+use std::process::Stdio;
+use std::time::Duration;
+
+/// Runs the configured command for `report`, if the report's status calls
+/// for it. `Err` describes a command that failed or ran out of time.
+pub fn run(report: &GenerateReport, config: &Config) -> Result<(), String> {
+    let Some(shell_command) = config.post_generate_command.as_deref() else {
+        return Ok(());
+    };
+    let fires = match report.status {
+        "generated" => true,
+        "skipped" | "error" => config.post_generate_on_skip,
+        _ => false,
+    };
+    if !fires || shell_command.trim().is_empty() {
+        return Ok(());
+    }
+    // Output goes to stderr, as stdout may carry the hook response or the
+    // JSON report.
+    let mut child = shell::command(shell_command)
+        .envs(env(report))
+        .stdin(Stdio::null())
+        .stdout(std::io::stderr())
+        .spawn()
+        .map_err(|e| format!("cannot run post_generate_command: {e}"))?;
+    let timeout = Duration::from_secs(config.post_generate_timeout_secs);
+    match shell::wait(&mut child, timeout) {
+        Ok(Some(status)) if status.success() => Ok(()),
+        Ok(Some(status)) => Err(format!("post_generate_command failed ({status})")),
+        Ok(None) => Err(format!(
+            "post_generate_command did not finish within {}s and was stopped",
+            timeout.as_secs()
+        )),
+        Err(e) => Err(format!("cannot wait for post_generate_command: {e}")),
+    }
+}
+
+/// The variables describing `report`: `IDR_STATUS` always, the others
+/// empty when they do not apply.
+fn env(report: &GenerateReport) -> Vec<(&'static str, String)> {
+    let title = report
+        .output
+        .as_ref()
+        .and_then(|path| fs::read(path).ok())
+        .map(|bytes| history::title(&String::from_utf8_lossy(&bytes)))
+        .unwrap_or_default();
+    vec![
+        ("IDR_STATUS", report.status.to_string()),
+        (
+            "IDR_PATH",
+            report
+                .output
+                .as_ref()
+                .map(|p| p.display().to_string())
+                .unwrap_or_default(),
+        ),
+        ("IDR_TITLE", title),
+        ("IDR_MODEL", report.model.clone().unwrap_or_default()),
+        ("IDR_REASON", report.reason.clone().unwrap_or_default()),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    fn config(command: &str) -> Config {
+        Config {
+            post_generate_command: Some(command.to_string()),
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn env_describes_the_written_record() {
+        let dir = TempDir::new().unwrap();
+        let record = dir.path().join("idr-03-add-retry.md");
+        fs::write(&record, "# IDR: Add retry\n\nbody\n").unwrap();
+        let report = GenerateReport {
+            status: "generated",
+            output: Some(record.clone()),
+            model: Some("sonnet".to_string()),
+            ..GenerateReport::default()
+        };
+
+        assert_eq!(
+            env(&report),
+            [
+                ("IDR_STATUS", "generated".to_string()),
+                ("IDR_PATH", record.display().to_string()),
+                ("IDR_TITLE", "Add retry".to_string()),
+                ("IDR_MODEL", "sonnet".to_string()),
+                ("IDR_REASON", String::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn env_of_a_skip_carries_the_reason() {
+        let report = GenerateReport {
+            status: "skipped",
+            reason: Some("no staged changes".to_string()),
+            output: Some(PathBuf::from("/nonexistent/idr-01.md")),
+            ..GenerateReport::default()
+        };
+        let env = env(&report);
+
+        assert_eq!(env[0], ("IDR_STATUS", "skipped".to_string()));
+        assert_eq!(env[2], ("IDR_TITLE", String::new()));
+        assert_eq!(env[4], ("IDR_REASON", "no staged changes".to_string()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_fires_on_skips_only_when_asked() {
+        let dir = TempDir::new().unwrap();
+        let marker = dir.path().join("ran");
+        let mut config = config(&format!("echo \"$IDR_STATUS\" > '{}'", marker.display()));
+        let skipped = GenerateReport {
+            status: "skipped",
+            ..GenerateReport::default()
+        };
+
+        run(&skipped, &config).unwrap();
+        assert!(!marker.exists());
+
+        config.post_generate_on_skip = true;
+        run(&skipped, &config).unwrap();
+        assert_eq!(fs::read_to_string(&marker).unwrap(), "skipped\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_reports_failure_and_timeout() {
+        let generated = GenerateReport {
+            status: "generated",
+            ..GenerateReport::default()
+        };
+
+        let failed = run(&generated, &config("exit 3")).unwrap_err();
+        assert!(failed.contains("failed"), "{failed}");
+
+        let mut slow = config("sleep 30");
+        slow.post_generate_timeout_secs = 0;
+        let timed_out = run(&generated, &slow).unwrap_err();
+        assert!(timed_out.contains("did not finish"), "{timed_out}");
+    }
+}
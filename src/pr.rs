@@ -0,0 +1,164 @@
+//! `claude-idr pr`: a pull request description for the current branch,
+//! written from its commits and its diff since the merge base with the base
+//! branch. No session is involved and nothing goes to the output directory.
+
+use crate::claude::{self, ClaudeResult};
+use crate::config::{Backend, Config, LargeDiffStrategy};
+use crate::error::IdrError;
+use crate::git;
+use crate::ignore;
+use crate::log::verbose;
+use crate::prompt::{self, PrChanges};
+use crate::redact;
+use crate::summarize;
+
+/// Base branch when `--base` is not given and the clone has no `origin/HEAD`.
+pub const DEFAULT_BASE: &str = "main";
+
+/// The base to compare with by default: the remote's default branch, or
+/// [`DEFAULT_BASE`].
+pub fn default_base() -> String {
+    git::default_remote_branch().unwrap_or_else(|| DEFAULT_BASE.to_string())
+}
+
+/// What the branch changed since it left its base.
+pub struct Branch {
+    /// `git log --oneline`, oldest first.
+    pub commits: String,
+    pub diff: String,
+    pub stat: String,
+    pub changed_lines: u64,
+}
+
+/// The branch as seen from `base`, or `None` when HEAD has no commits
+/// that `base` does not.
+/// Files matched by `.idrignore` are left out.
+pub fn branch(base: &str, config: &Config) -> Result<Option<Branch>, IdrError> {
+    let merge_base = git::merge_base(base)?;
+    let commits = git::log_oneline(&merge_base)?;
+    if commits.trim().is_empty() {
+        return Ok(None);
+    }
+    let exclude = ignore::Rules::for_repo(config).excluded(&git::paths_since(&merge_base));
+    Ok(Some(Branch {
+        commits,
+        diff: git::diff_since(&merge_base, &exclude)?,
+        stat: git::stat_since(&merge_base, &exclude),
+        changed_lines: git::changed_lines_since(&merge_base, &exclude),
+    }))
+}
+
+/// Redacts secrets from `branch.diff` before it goes into a prompt, and
+/// describes what was redacted, if anything.
+pub fn redact(branch: &mut Branch, config: &Config) -> Option<String> {
+    let mut secrets = redact::Counts::default();
+    branch.diff = redact::Redactor::new(config, true).diff(&branch.diff, &mut secrets);
+    secrets.summary()
+}
+
+/// How the description is written: from one prompt with the (possibly
+/// trimmed) diff, or from chunk summaries when the diff is over
+/// `max_diff_lines` and `large_diff_strategy` is `summarize`.
+enum Plan {
+    Single(String),
+    Chunked,
+}
+
+fn plan(branch: &Branch, config: &Config) -> Result<Plan, IdrError> {
+    if branch.changed_lines <= config.max_diff_lines {
+        let (prompt, notes) =
+            prompt::build_pr_prompt_within(&branch.diff, &branch.commits, &branch.stat, config);
+        for note in &notes {
+            verbose!("diff trimmed to fit max_prompt_tokens: {note}");
+        }
+        return Ok(Plan::Single(prompt));
+    }
+    match config.large_diff_strategy {
+        LargeDiffStrategy::Skip => Err(IdrError::DiffTooLarge {
+            lines: branch.changed_lines,
+            limit: config.max_diff_lines,
+        }),
+        LargeDiffStrategy::Summarize => Ok(Plan::Chunked),
+    }
+}
+
+/// The prompts [`describe`] would send, labelled, for `--dry-run`.
+pub fn prompts(branch: &Branch, config: &Config) -> Result<Vec<(String, String)>, IdrError> {
+    Ok(match plan(branch, config)? {
+        Plan::Single(prompt) => vec![(format!("PR prompt ({} chars)", prompt.len()), prompt)],
+        Plan::Chunked => {
+            let chunks = summarize::chunk_prompts(&branch.diff, config);
+            let total = chunks.len();
+            chunks
+                .into_iter()
+                .enumerate()
+                .map(|(i, (label, prompt))| {
+                    (format!("chunk {}/{total} prompt: {label}", i + 1), prompt)
+                })
+                .collect()
+        }
+    })
+}
+
+/// Asks claude for the description of `branch`.
+pub fn describe(branch: &Branch, config: &Config) -> Result<ClaudeResult, IdrError> {
+    let plan = plan(branch, config)?;
+    if config.backend == Backend::Cli {
+        claude::probe(config, config.probe_claude).map_err(IdrError::claude)?;
+    }
+    match plan {
+        Plan::Single(prompt) => claude::run(&prompt, config),
+        Plan::Chunked => summarize::run_with(&branch.diff, config, |summaries| {
+            prompt::build_pr_prompt(
+                PrChanges::Summaries(summaries),
+                &branch.commits,
+                &branch.stat,
+                config,
+            )
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn branch(changed_lines: u64) -> Branch {
+        Branch {
+            commits: "abc1234 Add retry\n".to_string(),
+            diff: "diff --git a/src/a.rs b/src/a.rs\n@@ -1 +1 @@\n-old\n+new\n".to_string(),
+            stat: " src/a.rs | 2 +-\n".to_string(),
+            changed_lines,
+        }
+    }
+
+    #[test]
+    fn prompts_use_single_prompt_within_limit() {
+        let prompts = prompts(&branch(2), &Config::default()).unwrap();
+
+        assert_eq!(prompts.len(), 1);
+        assert!(prompts[0].0.starts_with("PR prompt ("));
+        assert!(prompts[0].1.contains("abc1234 Add retry"));
+        assert!(prompts[0].1.contains("+new"));
+    }
+
+    #[test]
+    fn prompts_over_limit_follow_large_diff_strategy() {
+        let config = Config {
+            max_diff_lines: 1,
+            ..Config::default()
+        };
+        assert!(matches!(
+            prompts(&branch(2), &config),
+            Err(IdrError::DiffTooLarge { lines: 2, limit: 1 })
+        ));
+
+        let config = Config {
+            large_diff_strategy: LargeDiffStrategy::Summarize,
+            ..config
+        };
+        let prompts = prompts(&branch(2), &config).unwrap();
+        assert_eq!(prompts.len(), 1);
+        assert_eq!(prompts[0].0, "chunk 1/1 prompt: src/a.rs");
+    }
+}
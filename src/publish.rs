@@ -0,0 +1,248 @@
+//! `claude-idr pr --publish`: puts the description into the branch's open
+//! pull request with the gh CLI. The generated text sits between marker
+//! comments, so a later run replaces it instead of adding another copy.
+
+use serde::Deserialize;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+pub const START_MARKER: &str = "<!-- claude-idr:start -->";
+pub const END_MARKER: &str = "<!-- claude-idr:end -->";
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct PullRequest {
+    pub number: u64,
+    #[serde(default)]
+    pub body: String,
+    #[serde(default)]
+    pub state: String,
+}
+
+/// Where pull requests live; [`Gh`] in the binary, a fake in tests.
+pub trait PrHost {
+    /// The pull request for the current branch, if there is one.
+    fn current(&self) -> Result<Option<PullRequest>, String>;
+    fn set_body(&self, number: u64, body: &str) -> Result<(), String>;
+}
+
+/// The GitHub CLI, using its own authentication and repository detection.
+pub struct Gh;
+
+impl PrHost for Gh {
+    fn current(&self) -> Result<Option<PullRequest>, String> {
+        let output = Command::new("gh")
+            .args(["pr", "view", "--json", "number,body,state"])
+            .output()
+            .map_err(|e| format!("cannot run gh (is the GitHub CLI installed?): {e}"))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            if stderr.contains("no pull requests found") {
+                return Ok(None);
+            }
+            return Err(format!("gh pr view failed: {}", stderr.trim()));
+        }
+        serde_json::from_slice(&output.stdout)
+            .map(Some)
+            .map_err(|e| format!("cannot read gh pr view output: {e}"))
+    }
+
+    fn set_body(&self, number: u64, body: &str) -> Result<(), String> {
+        let mut child = Command::new("gh")
+            .args(["pr", "edit", &number.to_string(), "--body-file", "-"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("cannot run gh: {e}"))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(body.as_bytes())
+                .map_err(|e| format!("cannot write to gh: {e}"))?;
+        }
+        let output = child
+            .wait_with_output()
+            .map_err(|e| format!("gh pr edit failed: {e}"))?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(format!(
+                "gh pr edit failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    Updated(u64),
+    /// The pull request already had exactly this description.
+    Unchanged(u64),
+    /// The branch has no open pull request.
+    NoPullRequest,
+}
+
+/// Puts `summary` into the current branch's open pull request: the whole
+/// body when it is empty or `overwrite` is set, otherwise the marked
+/// section of it.
+pub fn publish(host: &dyn PrHost, summary: &str, overwrite: bool) -> Result<Outcome, String> {
+    let pr = match host.current()? {
+        Some(pr) if pr.state.is_empty() || pr.state == "OPEN" => pr,
+        _ => return Ok(Outcome::NoPullRequest),
+    };
+    let body = splice(&pr.body, summary, overwrite);
+    if body == pr.body {
+        return Ok(Outcome::Unchanged(pr.number));
+    }
+    host.set_body(pr.number, &body)?;
+    Ok(Outcome::Updated(pr.number))
+}
+
+/// `body` with `summary` in its marked section: replaced in place when the
+/// markers are there, appended after the existing text when not, or as the
+/// whole body when `body` is empty or `overwrite` is set.
+pub fn splice(body: &str, summary: &str, overwrite: bool) -> String {
+    let section = format!("{START_MARKER}\n{}\n{END_MARKER}", summary.trim());
+    if overwrite || body.trim().is_empty() {
+        return section;
+    }
+    if let Some(start) = body.find(START_MARKER)
+        && let Some(end) = body[start..].find(END_MARKER)
+    {
+        let end = start + end + END_MARKER.len();
+        return format!("{}{section}{}", &body[..start], &body[end..]);
+    }
+    format!("{}\n\n{section}", body.trim_end())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    const SECTION: &str =
+        "<!-- claude-idr:start -->\n## Summary\n\nAdds retries.\n<!-- claude-idr:end -->";
+
+    #[test]
+    fn splice_fills_empty_body() {
+        assert_eq!(
+            splice(" \n", "## Summary\n\nAdds retries.\n", false),
+            SECTION
+        );
+    }
+
+    #[test]
+    fn splice_appends_to_handwritten_body() {
+        assert_eq!(
+            splice("Fixes #12.\n", "## Summary\n\nAdds retries.", false),
+            format!("Fixes #12.\n\n{SECTION}")
+        );
+    }
+
+    #[test]
+    fn splice_replaces_marked_section_in_place() {
+        let body =
+            "Fixes #12.\n\n<!-- claude-idr:start -->\nold text\n<!-- claude-idr:end -->\n\nThanks!";
+
+        assert_eq!(
+            splice(body, "## Summary\n\nAdds retries.", false),
+            format!("Fixes #12.\n\n{SECTION}\n\nThanks!")
+        );
+    }
+
+    #[test]
+    fn splice_overwrite_replaces_everything() {
+        assert_eq!(
+            splice("Fixes #12.", "## Summary\n\nAdds retries.", true),
+            SECTION
+        );
+    }
+
+    #[test]
+    fn splice_appends_when_end_marker_is_missing() {
+        let body = "<!-- claude-idr:start -->\nold text";
+
+        assert_eq!(
+            splice(body, "## Summary\n\nAdds retries.", false),
+            format!("{body}\n\n{SECTION}")
+        );
+    }
+
+    struct FakeHost {
+        pr: Result<Option<PullRequest>, String>,
+        edits: RefCell<Vec<(u64, String)>>,
+    }
+
+    impl FakeHost {
+        fn with(pr: Result<Option<PullRequest>, String>) -> FakeHost {
+            FakeHost {
+                pr,
+                edits: RefCell::new(Vec::new()),
+            }
+        }
+    }
+
+    impl PrHost for FakeHost {
+        fn current(&self) -> Result<Option<PullRequest>, String> {
+            self.pr.clone()
+        }
+
+        fn set_body(&self, number: u64, body: &str) -> Result<(), String> {
+            self.edits.borrow_mut().push((number, body.to_string()));
+            Ok(())
+        }
+    }
+
+    fn open_pr(body: &str) -> PullRequest {
+        PullRequest {
+            number: 7,
+            body: body.to_string(),
+            state: "OPEN".to_string(),
+        }
+    }
+
+    #[test]
+    fn publish_sets_body_of_open_pull_request() {
+        let host = FakeHost::with(Ok(Some(open_pr(""))));
+
+        let outcome = publish(&host, "## Summary\n\nAdds retries.", false).unwrap();
+
+        assert_eq!(outcome, Outcome::Updated(7));
+        assert_eq!(*host.edits.borrow(), vec![(7, SECTION.to_string())]);
+    }
+
+    #[test]
+    fn publish_leaves_identical_body_alone() {
+        let host = FakeHost::with(Ok(Some(open_pr(SECTION))));
+
+        let outcome = publish(&host, "## Summary\n\nAdds retries.", false).unwrap();
+
+        assert_eq!(outcome, Outcome::Unchanged(7));
+        assert!(host.edits.borrow().is_empty());
+    }
+
+    #[test]
+    fn publish_skips_missing_or_closed_pull_request() {
+        let closed = PullRequest {
+            state: "MERGED".to_string(),
+            ..open_pr("")
+        };
+        for pr in [None, Some(closed)] {
+            let host = FakeHost::with(Ok(pr));
+            assert_eq!(
+                publish(&host, "text", false).unwrap(),
+                Outcome::NoPullRequest
+            );
+            assert!(host.edits.borrow().is_empty());
+        }
+    }
+
+    #[test]
+    fn publish_passes_host_errors_on() {
+        let host = FakeHost::with(Err("gh: not logged in".to_string()));
+        assert_eq!(
+            publish(&host, "text", false).unwrap_err(),
+            "gh: not logged in"
+        );
+    }
+}
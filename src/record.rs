@@ -0,0 +1,491 @@
+//! Records parsed back into their parts, following the structure the IDR
+//! prompt asks for (`## 変更概要`, `### [file](file)`, `#### Lx-y: ...`,
+//! `**理由**: ...`). Japanese and English headings are both recognized, as
+//! are `section_headings` overrides. Hand-edited records parse as far as
+//! they keep that shape; `##` sections the parser does not know are kept
+//! under `extra`.
+
+use crate::config::{Config, SectionHeadings};
+use crate::front_matter;
+use crate::history;
+use crate::metadata::Metadata;
+use crate::prompt::{self, Headings};
+use serde::Serialize;
+
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct ParsedRecord {
+    /// Set by callers that know the file name.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub number: Option<u32>,
+    pub title: String,
+    pub datetime: Option<String>,
+    pub summary: Option<String>,
+    pub files: Vec<FileChanges>,
+    pub design_decisions: Option<String>,
+    pub stat: Option<String>,
+    pub extra: Vec<Section>,
+    /// The generation comment: session, models, version, branch, commit.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Metadata>,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct FileChanges {
+    pub path: String,
+    pub hunks: Vec<Hunk>,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct Hunk {
+    /// `L10-25`; `None` when the heading has no line range.
+    pub range: Option<String>,
+    pub summary: String,
+    pub reason: Option<String>,
+    pub diff: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Section {
+    pub heading: String,
+    pub body: String,
+}
+
+const STAT_HEADING: &str = "git diff --stat";
+
+/// Parses the text of one record.
+pub fn parse(text: &str, config: &Config) -> ParsedRecord {
+    let vocabulary = vocabulary(config);
+    let (block, body) = front_matter::split(text);
+    let mut record = ParsedRecord {
+        title: history::title(text),
+        datetime: block.and_then(|b| front_matter::field(b, "date")),
+        metadata: Metadata::parse(text),
+        ..ParsedRecord::default()
+    };
+
+    for (heading, content) in sections(body) {
+        let Some(heading) = heading else {
+            if record.datetime.is_none() {
+                record.datetime = datetime(&content);
+            }
+            continue;
+        };
+        if heading == STAT_HEADING {
+            record.stat = first_code_block(&content);
+            continue;
+        }
+        let content = tidy(&content);
+        if vocabulary
+            .iter()
+            .any(|h| same_heading(&heading, &h.summary))
+        {
+            record.summary.get_or_insert(content);
+        } else if vocabulary
+            .iter()
+            .any(|h| same_heading(&heading, &h.changes))
+        {
+            record.files.extend(files(&content, &vocabulary));
+        } else if vocabulary
+            .iter()
+            .any(|h| same_heading(&heading, &h.decisions))
+        {
+            record.design_decisions.get_or_insert(content);
+        } else {
+            record.extra.push(Section {
+                heading,
+                body: content,
+            });
+        }
+    }
+    record
+}
+
+/// The configured headings plus the built-in Japanese and English ones.
+fn vocabulary(config: &Config) -> Vec<Headings> {
+    let builtin = |language: &str| {
+        prompt::headings(&Config {
+            language: language.to_string(),
+            section_headings: SectionHeadings::default(),
+            ..Config::default()
+        })
+    };
+    vec![prompt::headings(config), builtin("ja"), builtin("en")]
+}
+
+/// Numbering and case aside, `heading` is `expected`.
+fn same_heading(heading: &str, expected: &str) -> bool {
+    let heading = heading
+        .trim_start_matches(|c: char| c.is_ascii_digit() || c == '.')
+        .trim()
+        .trim_end_matches([':', '：']);
+    heading.eq_ignore_ascii_case(expected.trim())
+}
+
+/// Whether `line` opens or closes a fenced code block.
+fn is_fence(line: &str) -> bool {
+    let line = line.trim_start();
+    line.starts_with("```") || line.starts_with("~~~")
+}
+
+/// `body` cut at its `##` headings and at the stat heading (at any level),
+/// ignoring lines inside code blocks. The text before the first heading
+/// comes first, without a heading.
+fn sections(body: &str) -> Vec<(Option<String>, String)> {
+    let mut sections = vec![(None, String::new())];
+    let mut in_fence = false;
+    for line in body.lines() {
+        if is_fence(line) {
+            in_fence = !in_fence;
+        }
+        let heading = (!in_fence)
+            .then(|| {
+                let stripped = line.trim_start_matches('#');
+                let level = line.len() - stripped.len();
+                let text = stripped.strip_prefix(' ')?.trim();
+                (level == 2 || (level >= 2 && text == STAT_HEADING)).then(|| text.to_string())
+            })
+            .flatten();
+        match heading {
+            Some(heading) => sections.push((Some(heading), String::new())),
+            None => {
+                if let Some((_, content)) = sections.last_mut() {
+                    content.push_str(line);
+                    content.push('\n');
+                }
+            }
+        }
+    }
+    sections
+}
+
+/// Section text without surrounding blank lines or the `---` rule that
+/// separates the body from the stat block.
+fn tidy(content: &str) -> String {
+    let content = content.trim();
+    content
+        .strip_suffix("---")
+        .map_or(content, str::trim_end)
+        .to_string()
+}
+
+/// The date line under the title: `> 2026-02-07 17:30`, or MADR's
+/// `* Date: 2026-02-07`.
+fn datetime(preamble: &str) -> Option<String> {
+    preamble.lines().find_map(|line| {
+        line.strip_prefix("> ")
+            .or_else(|| line.strip_prefix("* Date: "))
+            .map(str::trim)
+            .filter(|d| d.len() >= 10 && d.as_bytes()[4] == b'-')
+            .map(str::to_string)
+    })
+}
+
+/// The contents of the first fenced code block in `text`.
+fn first_code_block(text: &str) -> Option<String> {
+    let mut lines = text.lines();
+    lines.find(|line| is_fence(line))?;
+    let mut code = String::new();
+    for line in lines {
+        if is_fence(line) {
+            return Some(code);
+        }
+        code.push_str(line);
+        code.push('\n');
+    }
+    None
+}
+
+/// The `###` file and `####` hunk entries of the changes section.
+fn files(content: &str, vocabulary: &[Headings]) -> Vec<FileChanges> {
+    let mut files: Vec<FileChanges> = Vec::new();
+    let mut in_fence = false;
+    let mut code: Option<String> = None;
+    let mut in_reason = false;
+    for line in content.lines() {
+        if is_fence(line) {
+            in_fence = !in_fence;
+            in_reason = false;
+            if in_fence {
+                code = Some(String::new());
+            } else if let Some(hunk) = current_hunk(&mut files)
+                && hunk.diff.is_none()
+            {
+                hunk.diff = code.take();
+            }
+            continue;
+        }
+        if in_fence {
+            if let Some(code) = &mut code {
+                code.push_str(line);
+                code.push('\n');
+            }
+            continue;
+        }
+        if let Some(heading) = line.strip_prefix("#### ") {
+            if files.is_empty() {
+                files.push(FileChanges::default());
+            }
+            let (range, summary) = hunk_heading(heading.trim());
+            if let Some(file) = files.last_mut() {
+                file.hunks.push(Hunk {
+                    range,
+                    summary,
+                    ..Hunk::default()
+                });
+            }
+            in_reason = false;
+        } else if let Some(heading) = line.strip_prefix("### ") {
+            files.push(FileChanges {
+                path: link_text(heading.trim()),
+                hunks: Vec::new(),
+            });
+            in_reason = false;
+        } else if let Some(reason) = reason(line, vocabulary) {
+            if let Some(hunk) = current_hunk(&mut files) {
+                hunk.reason = Some(reason);
+                in_reason = true;
+            }
+        } else if line.trim().is_empty() {
+            in_reason = false;
+        } else if in_reason
+            && let Some(reason) = current_hunk(&mut files).and_then(|h| h.reason.as_mut())
+        {
+            reason.push('\n');
+            reason.push_str(line.trim());
+        }
+    }
+    files
+}
+
+fn current_hunk(files: &mut [FileChanges]) -> Option<&mut Hunk> {
+    files.last_mut()?.hunks.last_mut()
+}
+
+/// `L10-25: Add retry` -> (`L10-25`, `Add retry`).
+fn hunk_heading(heading: &str) -> (Option<String>, String) {
+    let is_range = |r: &str| {
+        r.strip_prefix('L').is_some_and(|digits| {
+            !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit() || c == '-')
+        })
+    };
+    match heading.split_once(':') {
+        Some((range, summary)) if is_range(range.trim()) => {
+            (Some(range.trim().to_string()), summary.trim().to_string())
+        }
+        _ if is_range(heading) => (Some(heading.to_string()), String::new()),
+        _ => (None, heading.to_string()),
+    }
+}
+
+/// `[src/a.rs](src/a.rs)` and Obsidian's `[[src/a.rs]]` or
+/// `[[src/a.rs|a.rs]]` -> `src/a.rs`; other headings as they are.
+fn link_text(heading: &str) -> String {
+    let wikilink = heading
+        .strip_prefix("[[")
+        .and_then(|rest| rest.strip_suffix("]]"))
+        .map(|target| target.split('|').next().unwrap_or(target));
+    wikilink
+        .or_else(|| {
+            heading
+                .strip_prefix('[')
+                .and_then(|rest| rest.split_once("]("))
+                .map(|(text, _)| text)
+        })
+        .unwrap_or(heading)
+        .trim_matches('`')
+        .to_string()
+}
+
+/// The text of a `**理由**: ...` line, in any known language.
+fn reason(line: &str, vocabulary: &[Headings]) -> Option<String> {
+    let line = line.trim_start().trim_start_matches(['-', '*', ' ']);
+    vocabulary.iter().find_map(|h| {
+        let rest = line.strip_prefix(&h.rationale)?.strip_prefix("**")?;
+        let rest = rest.trim_start();
+        let rest = rest
+            .strip_prefix(':')
+            .or_else(|| rest.strip_prefix('：'))
+            .unwrap_or(rest);
+        Some(rest.trim().to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_default(text: &str) -> ParsedRecord {
+        parse(text, &Config::default())
+    }
+
+    #[test]
+    fn parse_reads_generated_japanese_record() {
+        let record = parse_default(include_str!("../tests/fixtures/export-record.md"));
+
+        assert_eq!(record.title, "リトライ処理を追加");
+        assert_eq!(record.datetime.as_deref(), Some("2026-02-07 17:30"));
+        assert_eq!(
+            record.summary.as_deref(),
+            Some("API 呼び出しが 5xx を返したときに最大 3 回まで再試行する。")
+        );
+        assert_eq!(record.files.len(), 1);
+        let file = &record.files[0];
+        assert_eq!(file.path, "src/client.rs");
+        assert_eq!(file.hunks.len(), 1);
+        let hunk = &file.hunks[0];
+        assert_eq!(hunk.range.as_deref(), Some("L10-18"));
+        assert_eq!(hunk.summary, "再試行ループを追加");
+        assert!(
+            hunk.reason
+                .as_deref()
+                .unwrap()
+                .starts_with("一時的な障害で")
+        );
+        let diff = hunk.diff.as_deref().unwrap();
+        assert!(diff.starts_with("@@ -10,3 +10,8 @@\n-    let response"));
+        assert!(diff.ends_with("+    };\n"));
+        assert_eq!(record.design_decisions, None);
+        assert_eq!(
+            record.stat.as_deref(),
+            Some(" src/client.rs | 8 +++++++-\n 1 file changed, 7 insertions(+), 1 deletion(-)\n")
+        );
+        assert!(record.extra.is_empty());
+    }
+
+    #[test]
+    fn parse_reads_the_provenance_footer_metadata() {
+        let text = "# IDR: Add retries\n\n### git diff --stat\n```\n a | 1 +\n```\n\n> session: 5f0c3a2e (project -ws-repo)\n> claude-idr 0.1.1, branch main\n\n<!-- claude-idr: version=0.1.1 prompt=v5 model=sonnet session=5f0c3a2e project=-ws-repo branch=main generated=2026-10-16T01:00:00Z -->\n";
+
+        let record = parse_default(text);
+
+        let metadata = record.metadata.unwrap();
+        assert_eq!(metadata.session.as_deref(), Some("5f0c3a2e"));
+        assert_eq!(metadata.project.as_deref(), Some("-ws-repo"));
+        assert_eq!(metadata.branch.as_deref(), Some("main"));
+        assert_eq!(record.stat.as_deref(), Some(" a | 1 +\n"));
+        assert_eq!(parse_default("# IDR: Hand-written\n").metadata, None);
+    }
+
+    #[test]
+    fn parse_reads_english_record_with_front_matter() {
+        let record = parse_default(include_str!("../tests/fixtures/record-english.md"));
+
+        assert_eq!(record.title, "Cache session lookups");
+        assert_eq!(record.datetime.as_deref(), Some("2026-03-01T09:15:00Z"));
+        assert_eq!(
+            record.summary.as_deref(),
+            Some("Caches the newest session per project.")
+        );
+        let paths: Vec<&str> = record.files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, ["src/session.rs", "src/config.rs"]);
+        let hunks = &record.files[0].hunks;
+        assert_eq!(hunks.len(), 2);
+        assert_eq!(
+            hunks[1].reason.as_deref(),
+            Some("Stale entries must not outlive a config reload.\nThe TTL keeps memory bounded.")
+        );
+        assert_eq!(record.files[1].hunks[0].range.as_deref(), Some("L3"));
+        assert_eq!(
+            record.design_decisions.as_deref(),
+            Some("- An in-process cache, no file cache.")
+        );
+        assert_eq!(record.extra.len(), 1);
+        assert_eq!(record.extra[0].heading, "Risks and concerns");
+    }
+
+    #[test]
+    fn parse_tolerates_hand_edited_record() {
+        let record = parse_default(include_str!("../tests/fixtures/record-hand-edited.md"));
+
+        assert_eq!(record.title, "Tweak logging");
+        assert_eq!(record.summary.as_deref(), Some("Quieter logs."));
+        assert_eq!(record.files.len(), 2);
+        assert_eq!(record.files[0].path, "");
+        assert_eq!(record.files[0].hunks[0].range, None);
+        assert_eq!(record.files[0].hunks[0].summary, "General cleanup");
+        assert_eq!(record.files[1].path, "src/log.rs");
+        assert_eq!(record.files[1].hunks, []);
+        let headings: Vec<&str> = record.extra.iter().map(|s| s.heading.as_str()).collect();
+        assert_eq!(headings, ["Follow-up", "Notes"]);
+        assert!(
+            record.extra[1].body.contains("## not a heading"),
+            "fenced lines stay in the section: {:?}",
+            record.extra[1]
+        );
+        assert_eq!(record.stat, None);
+    }
+
+    #[test]
+    fn parse_uses_configured_headings() {
+        let config = Config {
+            language: "en".to_string(),
+            section_headings: SectionHeadings {
+                summary: Some("TL;DR".to_string()),
+                rationale: Some("Why".to_string()),
+                ..SectionHeadings::default()
+            },
+            ..Config::default()
+        };
+        let text = "# IDR: x\n\n## TL;DR\n\nShort.\n\n## Key changes\n\n### a.rs\n#### L1-2: y\n**Why**: because\n";
+
+        let record = parse(text, &config);
+
+        assert_eq!(record.summary.as_deref(), Some("Short."));
+        assert_eq!(record.files[0].hunks[0].reason.as_deref(), Some("because"));
+    }
+
+    #[test]
+    fn parse_reads_madr_date_and_keeps_its_sections() {
+        let text = "# Use JWT\n\n* Status: accepted\n* Date: 2026-02-07\n\n## Context and Problem Statement\n\nWhy.\n";
+
+        let record = parse_default(text);
+
+        assert_eq!(record.title, "Use JWT");
+        assert_eq!(record.datetime.as_deref(), Some("2026-02-07"));
+        assert_eq!(record.extra[0].heading, "Context and Problem Statement");
+        assert_eq!(record.extra[0].body, "Why.");
+    }
+
+    #[test]
+    fn hunk_heading_splits_range_from_summary() {
+        assert_eq!(
+            hunk_heading("L10-25: Add retry"),
+            (Some("L10-25".to_string()), "Add retry".to_string())
+        );
+        assert_eq!(hunk_heading("L7"), (Some("L7".to_string()), String::new()));
+        assert_eq!(
+            hunk_heading("Lines: many"),
+            (None, "Lines: many".to_string())
+        );
+    }
+
+    #[test]
+    fn link_text_unwraps_markdown_and_wikilinks() {
+        assert_eq!(link_text("[src/a b.rs](src/a%20b.rs)"), "src/a b.rs");
+        assert_eq!(link_text("`src/a.rs`"), "src/a.rs");
+        assert_eq!(link_text("src/a.rs"), "src/a.rs");
+        assert_eq!(link_text("[[src/a.rs]]"), "src/a.rs");
+        assert_eq!(link_text("[[src/a.rs|a.rs]]"), "src/a.rs");
+    }
+
+    #[test]
+    fn parsed_record_serializes_expected_fields() {
+        let record = ParsedRecord {
+            number: Some(3),
+            title: "t".to_string(),
+            ..ParsedRecord::default()
+        };
+
+        let json = serde_json::to_value(&record).unwrap();
+
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "number": 3, "title": "t", "datetime": null, "summary": null,
+                "files": [], "design_decisions": null, "stat": null, "extra": [],
+            })
+        );
+    }
+}
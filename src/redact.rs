@@ -0,0 +1,409 @@
+//! Secrets taken out of the diff and the session context before either is
+//! put in a prompt: well-known token shapes, private key blocks, long
+//! random-looking values on assignment lines, and `redact_patterns`. Each
+//! is replaced with `[REDACTED:<kind>]`. Files matching `redact_paths` lose
+//! their hunks entirely. Only prompts are redacted; the stat and the diff
+//! hash written with the record come from the original diff.
+
+use crate::config::Config;
+use crate::diff;
+use crate::ignore;
+use crate::log::warn;
+use crate::pattern::Pattern;
+use std::collections::BTreeMap;
+
+/// Token shapes recognized without configuration, by kind.
+const BUILTIN_PATTERNS: &[(&str, &str)] = &[
+    ("aws_access_key", "AKIA[0-9A-Z]{16}|ASIA[0-9A-Z]{16}"),
+    (
+        "github_token",
+        "gh[pousr]_[A-Za-z0-9]{36,}|github_pat_[A-Za-z0-9_]{22,}",
+    ),
+    ("slack_token", "xox[abprs]-[A-Za-z0-9-]{10,}"),
+    ("api_key", "sk-[A-Za-z0-9_-]{20,}"),
+];
+
+/// Kind of matches of `redact_patterns`.
+const CUSTOM_KIND: &str = "custom";
+const PRIVATE_KEY_KIND: &str = "private_key";
+const HIGH_ENTROPY_KIND: &str = "high_entropy";
+const PATH_KIND: &str = "path";
+
+/// Shortest assignment value checked for randomness.
+const HIGH_ENTROPY_MIN_CHARS: usize = 20;
+/// Bits per character above which a value counts as random. English words
+/// and identifiers stay well below; hex digests and base64 keys do not.
+const HIGH_ENTROPY_MIN_BITS: f64 = 3.5;
+
+/// Redactions made, by kind.
+#[derive(Debug, Default, PartialEq)]
+pub struct Counts(BTreeMap<String, usize>);
+
+impl Counts {
+    fn add(&mut self, kind: &str) {
+        *self.0.entry(kind.to_string()).or_insert(0) += 1;
+    }
+
+    pub fn total(&self) -> usize {
+        self.0.values().sum()
+    }
+
+    /// `redacted 3 secrets from the prompt (2 aws_access_key, 1 path)`, or
+    /// `None` when nothing was redacted.
+    pub fn summary(&self) -> Option<String> {
+        let total = self.total();
+        if total == 0 {
+            return None;
+        }
+        let kinds: Vec<String> = self.0.iter().map(|(k, n)| format!("{n} {k}")).collect();
+        Some(format!(
+            "redacted {total} secret{} from the prompt ({})",
+            if total == 1 { "" } else { "s" },
+            kinds.join(", ")
+        ))
+    }
+}
+
+pub struct Redactor {
+    enabled: bool,
+    patterns: Vec<(String, Pattern)>,
+    paths: Vec<String>,
+}
+
+impl Redactor {
+    /// The built-in patterns plus `redact_patterns` and `redact_paths`. An
+    /// invalid pattern is warned about and skipped. A disabled redactor
+    /// (`--no-redact`) returns its input unchanged.
+    pub fn new(config: &Config, enabled: bool) -> Redactor {
+        let mut patterns: Vec<(String, Pattern)> = BUILTIN_PATTERNS
+            .iter()
+            .map(|(kind, source)| {
+                let pattern = Pattern::parse(source).expect("built-in pattern is valid");
+                (kind.to_string(), pattern)
+            })
+            .collect();
+        for source in &config.redact_patterns {
+            match Pattern::parse(source) {
+                Ok(pattern) => patterns.push((CUSTOM_KIND.to_string(), pattern)),
+                Err(e) => warn!("ignoring redact_patterns entry: {e}"),
+            }
+        }
+        Redactor {
+            enabled,
+            patterns,
+            paths: config.redact_paths.clone(),
+        }
+    }
+
+    /// `diff` with the hunks of files matching `redact_paths` dropped and
+    /// secrets in the remaining hunks replaced. File headers are kept, so
+    /// the prompt still shows which files changed.
+    pub fn diff(&self, diff: &str, counts: &mut Counts) -> String {
+        if !self.enabled {
+            return diff.to_string();
+        }
+        let before = counts.total();
+        let mut files = diff::parse(diff);
+        for file in &mut files {
+            if self
+                .paths
+                .iter()
+                .any(|glob| ignore::matches(glob, &file.path))
+            {
+                let hunks = file.hunks.len();
+                if hunks > 0 {
+                    file.hunks.clear();
+                    file.header.push(format!(
+                        "[REDACTED:{PATH_KIND}] {hunks} hunk(s) dropped by redact_paths"
+                    ));
+                    counts.add(PATH_KIND);
+                }
+                continue;
+            }
+            for hunk in &mut file.hunks {
+                hunk.lines = self.lines(&hunk.lines, true, counts);
+            }
+        }
+        if counts.total() == before {
+            return diff.to_string();
+        }
+        diff::render(&files)
+    }
+
+    /// `text` with secrets replaced, line by line.
+    pub fn text(&self, text: &str, counts: &mut Counts) -> String {
+        if !self.enabled {
+            return text.to_string();
+        }
+        let lines: Vec<String> = text.lines().map(str::to_string).collect();
+        let mut redacted = self.lines(&lines, false, counts).join("\n");
+        if text.ends_with('\n') {
+            redacted.push('\n');
+        }
+        redacted
+    }
+
+    /// `lines` with secrets replaced. With `prefixed`, each line starts
+    /// with a diff marker (`+`, `-`, or space) that is kept as is. A
+    /// private key block becomes one marker line followed by empty lines,
+    /// so hunk line counts still add up.
+    fn lines(&self, lines: &[String], prefixed: bool, counts: &mut Counts) -> Vec<String> {
+        let mut in_key = false;
+        lines
+            .iter()
+            .map(|line| {
+                let split = if prefixed {
+                    line.chars().next().map_or(0, char::len_utf8)
+                } else {
+                    0
+                };
+                let (prefix, body) = line.split_at(split);
+                if in_key {
+                    in_key = !body.contains("-----END ");
+                    return prefix.to_string();
+                }
+                if body.contains("-----BEGIN ") && body.contains("PRIVATE KEY-----") {
+                    counts.add(PRIVATE_KEY_KIND);
+                    in_key = !body.contains("-----END ");
+                    return format!("{prefix}[REDACTED:{PRIVATE_KEY_KIND}]");
+                }
+                format!("{prefix}{}", self.line(body, counts))
+            })
+            .collect()
+    }
+
+    fn line(&self, line: &str, counts: &mut Counts) -> String {
+        let mut line = line.to_string();
+        for (kind, pattern) in &self.patterns {
+            for range in pattern.find_all(&line).into_iter().rev() {
+                line.replace_range(range, &format!("[REDACTED:{kind}]"));
+                counts.add(kind);
+            }
+        }
+        if let Some(range) = high_entropy_value(&line) {
+            line.replace_range(range, &format!("[REDACTED:{HIGH_ENTROPY_KIND}]"));
+            counts.add(HIGH_ENTROPY_KIND);
+        }
+        line
+    }
+}
+
+/// Byte range of the first value assigned with `=` or `:` on `line` that is
+/// long and random enough to be a secret.
+fn high_entropy_value(line: &str) -> Option<std::ops::Range<usize>> {
+    let is_value_char = |c: char| c.is_ascii_alphanumeric() || "+/=_-.".contains(c);
+    line.match_indices(['=', ':']).find_map(|(at, _)| {
+        let rest = &line[at + 1..];
+        if rest.starts_with("//") {
+            return None;
+        }
+        let skipped = rest.len()
+            - rest
+                .trim_start_matches(|c: char| c.is_whitespace() || "\"'`".contains(c))
+                .len();
+        let start = at + 1 + skipped;
+        let value_len = line[start..]
+            .find(|c: char| !is_value_char(c))
+            .unwrap_or(line.len() - start);
+        let value = &line[start..start + value_len];
+        let value = value.trim_end_matches('=');
+        let looks_random = value.len() >= HIGH_ENTROPY_MIN_CHARS
+            && value.chars().any(|c| c.is_ascii_digit())
+            && value.chars().any(|c| c.is_ascii_alphabetic())
+            && entropy(value) >= HIGH_ENTROPY_MIN_BITS;
+        looks_random.then(|| start..start + value.len())
+    })
+}
+
+/// Shannon entropy of `text` in bits per character.
+fn entropy(text: &str) -> f64 {
+    let mut counts: BTreeMap<char, usize> = BTreeMap::new();
+    for c in text.chars() {
+        *counts.entry(c).or_insert(0) += 1;
+    }
+    let len = text.chars().count() as f64;
+    counts
+        .values()
+        .map(|&n| {
+            let p = n as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn fixture(name: &str) -> String {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/redact")
+            .join(name);
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    fn redactor() -> Redactor {
+        Redactor::new(&Config::default(), true)
+    }
+
+    fn counts(pairs: &[(&str, usize)]) -> Counts {
+        Counts(pairs.iter().map(|&(k, n)| (k.to_string(), n)).collect())
+    }
+
+    #[test]
+    fn diff_redacts_token_shapes_and_keeps_prefixes() {
+        let mut found = Counts::default();
+
+        let redacted = redactor().diff(&fixture("tokens.diff"), &mut found);
+
+        assert!(
+            redacted.contains("+aws_key = \"[REDACTED:aws_access_key]\"\n"),
+            "{redacted}"
+        );
+        assert!(
+            redacted.contains("-let token = \"[REDACTED:github_token]\";\n"),
+            "{redacted}"
+        );
+        assert!(
+            redacted.contains("+    client(\"[REDACTED:api_key]\")\n"),
+            "{redacted}"
+        );
+        assert!(redacted.contains(" unchanged context line\n"), "{redacted}");
+        assert!(!redacted.contains("AKIA"), "{redacted}");
+        assert_eq!(
+            found,
+            counts(&[("api_key", 1), ("aws_access_key", 1), ("github_token", 1)])
+        );
+    }
+
+    #[test]
+    fn diff_redacts_multiline_private_key_block() {
+        let original = fixture("private-key.diff");
+        let mut found = Counts::default();
+
+        let redacted = redactor().diff(&original, &mut found);
+
+        assert!(
+            redacted.contains("@@ -0,0 +1,6 @@\n+[REDACTED:private_key]\n+\n+\n+\n+\n+after = 1\n"),
+            "{redacted}"
+        );
+        assert!(!redacted.contains("MIIE"), "{redacted}");
+        assert_eq!(redacted.lines().count(), original.lines().count());
+        assert_eq!(found, counts(&[("private_key", 1)]));
+    }
+
+    #[test]
+    fn diff_drops_hunks_of_redact_paths() {
+        let mut found = Counts::default();
+
+        let redacted = redactor().diff(&fixture("env.diff"), &mut found);
+
+        assert!(
+            redacted.contains(
+                "+++ b/config/.env.production\n[REDACTED:path] 1 hunk(s) dropped by redact_paths\n"
+            ),
+            "{redacted}"
+        );
+        assert!(!redacted.contains("DATABASE_URL"), "{redacted}");
+        assert!(redacted.contains("+pub fn connect() {}\n"), "{redacted}");
+        assert_eq!(found, counts(&[("path", 1)]));
+    }
+
+    #[test]
+    fn diff_flags_random_assignment_values_only() {
+        let mut found = Counts::default();
+
+        let redacted = redactor().diff(&fixture("entropy.diff"), &mut found);
+
+        assert!(
+            redacted.contains("+SESSION_SECRET=[REDACTED:high_entropy]\n"),
+            "{redacted}"
+        );
+        assert!(
+            redacted.contains("+  signing_key: '[REDACTED:high_entropy]'\n"),
+            "{redacted}"
+        );
+        assert!(
+            redacted.contains("+description = \"a perfectly ordinary sentence of words\"\n"),
+            "{redacted}"
+        );
+        assert!(
+            redacted.contains("+let url = \"https://example.com/docs/getting-started\";\n"),
+            "{redacted}"
+        );
+        assert!(redacted.contains("+version = 20260214\n"), "{redacted}");
+        assert_eq!(found, counts(&[("high_entropy", 2)]));
+    }
+
+    #[test]
+    fn diff_without_secrets_is_returned_unchanged() {
+        let diff = "preamble\ndiff --git a/a b/a\n@@ -1 +1 @@\n-old\n+new\n";
+        let mut found = Counts::default();
+
+        assert_eq!(redactor().diff(diff, &mut found), diff);
+        assert_eq!(found.summary(), None);
+    }
+
+    #[test]
+    fn disabled_redactor_passes_everything_through() {
+        let diff = fixture("tokens.diff");
+        let mut found = Counts::default();
+
+        let redacted = Redactor::new(&Config::default(), false).diff(&diff, &mut found);
+
+        assert_eq!(redacted, diff);
+        assert_eq!(found.total(), 0);
+    }
+
+    #[test]
+    fn text_applies_user_patterns_and_keeps_trailing_newline() {
+        let config = Config {
+            redact_patterns: vec!["acme-[0-9]{6}".to_string(), "(bad".to_string()],
+            ..Config::default()
+        };
+        let mut found = Counts::default();
+
+        let redacted = Redactor::new(&config, true).text(
+            "# User requests:\n- use license acme-123456 for the build\n",
+            &mut found,
+        );
+
+        assert_eq!(
+            redacted,
+            "# User requests:\n- use license [REDACTED:custom] for the build\n"
+        );
+        assert_eq!(
+            found.summary().as_deref(),
+            Some("redacted 1 secret from the prompt (1 custom)")
+        );
+    }
+
+    #[test]
+    fn summary_counts_every_kind() {
+        let found = counts(&[("aws_access_key", 2), ("path", 1)]);
+
+        assert_eq!(
+            found.summary().as_deref(),
+            Some("redacted 3 secrets from the prompt (2 aws_access_key, 1 path)")
+        );
+    }
+
+    #[test]
+    fn redact_paths_match_file_names_and_full_paths() {
+        let matches = ignore::matches;
+        assert!(matches(".env*", ".env"));
+        assert!(matches(".env*", "deploy/.env.local"));
+        assert!(matches("*.pem", "certs/server.pem"));
+        assert!(matches("secrets/*.json", "secrets/prod.json"));
+        assert!(!matches("secrets/*.json", "secrets/nested/prod.json"));
+        assert!(!matches("*.pem", "server.pem.md"));
+        assert!(matches("id_?sa", "home/id_rsa"));
+    }
+
+    #[test]
+    fn entropy_separates_words_from_keys() {
+        assert!(entropy("aaaaaaaaaaaaaaaaaaaa") < 0.1);
+        assert!(entropy("wJalrXUtnFEMI/K7MDENG/bPxRfiCYEXAMPLEKEY") > HIGH_ENTROPY_MIN_BITS);
+    }
+}
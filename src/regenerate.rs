@@ -0,0 +1,251 @@
+//! `regenerate --failed`: another attempt at the records written with the
+//! failure placeholder (`on_failure: "placeholder"`). The placeholder is
+//! replaced in place, so the record keeps its number, title, and links.
+
+use crate::anonymize::Anonymizer;
+use crate::claude::{self, ClaudeResult};
+use crate::config::{Config, Format};
+use crate::context;
+use crate::diff;
+use crate::diff_filter;
+use crate::git;
+use crate::ignore;
+use crate::lock;
+use crate::obsidian;
+use crate::path;
+use crate::prompt;
+use crate::redact;
+use crate::sidecar::{self, DiffSource, Sidecar};
+use crate::validate;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const FOOTER_PREFIX: &str = "> IDR generation failed: ";
+
+/// The footer line of a record whose body is the failure placeholder.
+/// Only the first line of `reason` is kept.
+pub fn footer_line(reason: &str) -> String {
+    let reason = reason.lines().next().unwrap_or_default().trim();
+    format!("{FOOTER_PREFIX}{reason}; retry with `claude-idr regenerate --failed`")
+}
+
+/// Records in `dir` whose sidecar says generation failed, oldest first.
+pub fn failed(dir: &Path, format: Format) -> Vec<PathBuf> {
+    path::list_records(dir, format)
+        .into_iter()
+        .map(|(_, record)| record)
+        .filter(|record| sidecar::load_metadata(record).is_some_and(|s| s.failure.is_some()))
+        .collect()
+}
+
+/// Asks claude again for the body of `record` and puts it where the
+/// placeholder is. `Err` explains why the record was left as it was.
+pub fn run(record: &Path, config: &Config) -> Result<(), String> {
+    let meta = sidecar::load_metadata(record).ok_or("it has no readable sidecar")?;
+    let text = fs::read_to_string(record).map_err(|e| e.to_string())?;
+    let (diff, stat) = change(&meta, config)?;
+    let placeholder = placeholder(&text, recorded_stat(&text).unwrap_or(&stat), config)?;
+
+    let redactor = redact::Redactor::new(config, true);
+    let mut secrets = redact::Counts::default();
+    let session_context = meta
+        .session_path
+        .as_deref()
+        .and_then(context::extract)
+        .map(|ctx| {
+            let rendered =
+                Anonymizer::for_repo(config).text(&ctx.render_within(config.context_max_chars));
+            redactor.text(&rendered, &mut secrets)
+        });
+    let background = prompt::Background {
+        session_context: session_context.filter(|_| config.include_context_in_idr),
+        previous_idrs: None,
+        authorship: None,
+        file_history: None,
+    };
+    let filtered = diff_filter::apply(&diff, config);
+    let prompt_diff = redactor.diff(filtered.as_deref().unwrap_or(&diff), &mut secrets);
+    let (idr_prompt, _) = prompt::build_idr_prompt_within(&prompt_diff, &stat, &background, config);
+    let hunks = diff::changed_lines(&diff) > 0;
+    let result = validate::run(&idr_prompt, config, hunks, |prompt| {
+        claude::run(prompt, config)
+    })
+    .map_err(|e| e.to_string())?;
+    write_body(record, &text, &placeholder, meta, result, config)
+}
+
+/// Whether `record` is a failed record whose placeholder is untouched.
+pub fn pending(record: &Path, config: &Config) -> bool {
+    let failed = sidecar::load_metadata(record).is_some_and(|meta| meta.failure.is_some());
+    failed
+        && fs::read_to_string(record).is_ok_and(|text| {
+            recorded_stat(&text).is_some_and(|stat| placeholder(&text, stat, config).is_ok())
+        })
+}
+
+/// Puts `result`, an answer obtained elsewhere (see `failures`), where the
+/// placeholder of `record` is.
+pub fn fill(record: &Path, result: ClaudeResult, config: &Config) -> Result<(), String> {
+    let meta = sidecar::load_metadata(record).ok_or("it has no readable sidecar")?;
+    let text = fs::read_to_string(record).map_err(|e| e.to_string())?;
+    let stat = recorded_stat(&text).ok_or("it has no diff stat")?;
+    let placeholder = placeholder(&text, stat, config)?;
+    write_body(record, &text, &placeholder, meta, result, config)
+}
+
+/// The placeholder `text` was written with, unless it has been edited.
+fn placeholder(text: &str, stat: &str, config: &Config) -> Result<String, String> {
+    let mut placeholder = prompt::failure_body(config, stat);
+    if config.obsidian_mode {
+        placeholder = obsidian::wikilink_headings(&placeholder);
+    }
+    if !text.contains(&placeholder) {
+        return Err("the placeholder has been edited since".to_string());
+    }
+    Ok(placeholder)
+}
+
+fn write_body(
+    record: &Path,
+    text: &str,
+    placeholder: &str,
+    mut meta: Sidecar,
+    result: ClaudeResult,
+    config: &Config,
+) -> Result<(), String> {
+    let (body, change_type) = prompt::split_change_type(&validate::strip_outer_fence(&result.text));
+    let mut body = if config.suggest_commit_message {
+        prompt::split_commit_message(&body).0
+    } else {
+        body
+    };
+    if config.obsidian_mode {
+        body = obsidian::wikilink_headings(&body);
+    }
+    let updated = without_footer_line(&text.replacen(placeholder, body.trim_end(), 1));
+
+    meta.failure = None;
+    meta.body_model = result.model;
+    meta.generated = path::now_iso8601();
+    meta.usage = result.usage;
+    meta.change_type = Some(change_type.to_string());
+    let dir = record.parent().unwrap_or(Path::new("."));
+    let _lock = lock::acquire_warn(dir);
+    fs::write(record, updated).map_err(|e| e.to_string())?;
+    sidecar::write(record, &meta).map_err(|e| e.to_string())
+}
+
+/// The diff and stat `meta` describes: the commit's once there is one, or
+/// the staged change while it is still staged unchanged.
+fn change(meta: &Sidecar, config: &Config) -> Result<(String, String), String> {
+    let rules = ignore::Rules::for_repo(config);
+    let sha = match (&meta.commit, &meta.diff_source) {
+        (Some(commit), _) => &commit.sha,
+        (None, DiffSource::Commit { sha }) => sha,
+        (None, DiffSource::Staged) => {
+            let exclude = rules.excluded(&git::staged_paths());
+            let diff = git::staged_diff(&exclude).map_err(|e| e.to_string())?;
+            if sidecar::diff_hash(&diff) != meta.diff_sha256 {
+                return Err(
+                    "the staged change it describes is gone and no commit was recorded for it"
+                        .to_string(),
+                );
+            }
+            return Ok((diff, git::staged_stat(&exclude)));
+        }
+        (None, DiffSource::Range { .. }) => {
+            return Err("records of a range cannot be regenerated".to_string());
+        }
+        (None, DiffSource::Session) => {
+            return Err(
+                "session reports cannot be regenerated; run --from-session again".to_string(),
+            );
+        }
+    };
+    let exclude = rules.excluded(&git::commit_paths(sha));
+    let diff = git::commit_diff(sha, &exclude).map_err(|e| e.to_string())?;
+    Ok((diff, git::commit_stat(sha, &exclude)))
+}
+
+/// The `git diff --stat` block of a record, as the placeholder was given it.
+fn recorded_stat(text: &str) -> Option<&str> {
+    let (_, rest) = text.rsplit_once("### git diff --stat\n```\n")?;
+    rest.split_once("\n```").map(|(stat, _)| stat)
+}
+
+/// `text` without the [`footer_line`], and without the blank line before
+/// it when it was the only footer line.
+fn without_footer_line(text: &str) -> String {
+    let mut lines: Vec<&str> = text.lines().collect();
+    let Some(i) = lines.iter().position(|l| l.starts_with(FOOTER_PREFIX)) else {
+        return text.to_string();
+    };
+    lines.remove(i);
+    let alone = i > 0 && lines[i - 1].is_empty() && lines.get(i).is_none_or(|l| l.is_empty());
+    if alone {
+        lines.remove(i - 1);
+    }
+    let mut out = lines.join("\n");
+    if text.ends_with('\n') {
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RECORD: &str = "\
+# IDR: Add greeting
+
+> 2026-10-16 09:00
+
+## Summary of changes
+
+(IDR generation failed - please fill in manually)
+
+---
+
+### git diff --stat
+```
+ a.txt | 1 +
+```
+";
+
+    #[test]
+    fn footer_line_keeps_the_first_line_of_the_reason() {
+        assert_eq!(
+            footer_line("claude CLI failed: exit 1\nstack trace"),
+            "> IDR generation failed: claude CLI failed: exit 1; retry with `claude-idr regenerate --failed`"
+        );
+    }
+
+    #[test]
+    fn recorded_stat_reads_the_stat_block() {
+        assert_eq!(recorded_stat(RECORD), Some(" a.txt | 1 +"));
+        assert_eq!(recorded_stat("# IDR: x\n"), None);
+    }
+
+    #[test]
+    fn without_footer_line_drops_the_line_and_its_blank_line() {
+        let failed = footer_line("timeout");
+        let meta = "<!-- claude-idr: version=1 -->";
+
+        let alone = format!("{RECORD}\n{failed}\n\n{meta}\n");
+        assert_eq!(without_footer_line(&alone), format!("{RECORD}\n{meta}\n"));
+
+        let shared = format!("{RECORD}\n> Commit: abc1234\n{failed}\n\n{meta}\n");
+        assert_eq!(
+            without_footer_line(&shared),
+            format!("{RECORD}\n> Commit: abc1234\n\n{meta}\n")
+        );
+
+        let first = format!("{RECORD}\n{failed}\n> model: haiku (fallback from sonnet)\n");
+        assert_eq!(
+            without_footer_line(&first),
+            format!("{RECORD}\n> model: haiku (fallback from sonnet)\n")
+        );
+        assert_eq!(without_footer_line(RECORD), RECORD);
+    }
+}
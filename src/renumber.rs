@@ -0,0 +1,304 @@
+//! `claude-idr renumber`: renames `idr-N.md` files so every number has the
+//! same zero-padded width.
+
+use crate::config::{Format, LatestMode};
+use crate::index;
+use crate::latest;
+use crate::path;
+use crate::sidecar;
+use crate::translate;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, PartialEq)]
+pub struct Rename {
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+/// The renames needed to give every IDR file (and its sidecar) in `dir` a
+/// `width`-digit number. Fails without renaming anything when two files would end up
+/// with the same name or a target is already taken by another file.
+pub fn plan(dir: &Path, width: usize) -> io::Result<Vec<Rename>> {
+    let records = path::list_records(dir, Format::Idr);
+    let mut renames = Vec::new();
+    for (number, from) in &records {
+        let Some(name) = from.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        let Some((_, slug)) = path::split_idr_name(name) else {
+            continue;
+        };
+        let to = dir.join(path::idr_file_name(*number, slug, width));
+        if to != *from {
+            renames.push(Rename {
+                from: from.clone(),
+                to,
+            });
+        }
+    }
+
+    let sources: Vec<&PathBuf> = records.iter().map(|(_, p)| p).collect();
+    for (i, rename) in renames.iter().enumerate() {
+        let duplicate = renames[..i].iter().any(|r| r.to == rename.to);
+        let occupied = rename.to.exists() && !sources.contains(&&rename.to);
+        let unchanged_twin = records
+            .iter()
+            .any(|(_, p)| *p == rename.to && !renames.iter().any(|r| r.from == *p));
+        if duplicate || occupied || unchanged_twin {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!(
+                    "{} would collide with another record at {}",
+                    rename.from.display(),
+                    rename.to.display()
+                ),
+            ));
+        }
+    }
+
+    // Translations keep their language suffix.
+    let translations: Vec<Rename> = renames
+        .iter()
+        .flat_map(|r| {
+            let to_name = r.to.file_name().unwrap_or_default().to_string_lossy();
+            translate::existing(&r.from)
+                .into_iter()
+                .map(|(from, language)| Rename {
+                    from,
+                    to: r
+                        .to
+                        .with_file_name(translate::file_name(&to_name, &language)),
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    // Sidecars follow their records; their names mirror the checked ones.
+    let sidecars: Vec<Rename> = renames
+        .iter()
+        .map(|r| Rename {
+            from: sidecar::path_for(&r.from),
+            to: sidecar::path_for(&r.to),
+        })
+        .filter(|r| r.from.exists())
+        .collect();
+    renames.extend(sidecars);
+    renames.extend(translations);
+    Ok(renames)
+}
+
+/// Applies `renames` in two passes through temporary names, so no rename
+/// can land on a file that has not been moved out of the way yet. Then
+/// rebuilds `index.md` and re-points `latest.md` if they exist.
+pub fn apply(dir: &Path, renames: &[Rename]) -> io::Result<()> {
+    let latest_target = latest_target(dir);
+
+    let staged: Vec<PathBuf> = renames
+        .iter()
+        .enumerate()
+        .map(|(i, _)| dir.join(format!(".renumber-{i}.tmp")))
+        .collect();
+    for (rename, tmp) in renames.iter().zip(&staged) {
+        fs::rename(&rename.from, tmp)?;
+    }
+    for (rename, tmp) in renames.iter().zip(&staged) {
+        fs::rename(tmp, &rename.to)?;
+    }
+
+    if dir.join(index::FILE_NAME).exists() {
+        index::refresh(dir, Format::Idr)?;
+    }
+    if let Some((old_name, mode)) = latest_target
+        && let Some(rename) = renames
+            .iter()
+            .find(|r| r.from.file_name() == Some(old_name.as_os_str()))
+    {
+        latest::update(dir, &rename.to, mode)?;
+    }
+    Ok(())
+}
+
+/// The file `latest.md` points at, and how.
+fn latest_target(dir: &Path) -> Option<(PathBuf, LatestMode)> {
+    let latest = dir.join(latest::FILE_NAME);
+    if let Ok(target) = fs::read_link(&latest) {
+        return Some((target, LatestMode::Symlink));
+    }
+    let text = fs::read_to_string(&latest).ok()?;
+    let link = text.lines().next()?.strip_prefix("> Latest: [")?;
+    let (name, _) = link.split_once(']')?;
+    Some((PathBuf::from(name), LatestMode::Copy))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn fixture() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        for (name, body) in [
+            ("idr-1.md", "# IDR: one\n"),
+            ("idr-02-use-jwt.md", "# IDR: two\n"),
+            ("idr-100.md", "# IDR: hundred\n"),
+            ("notes.md", "not a record"),
+        ] {
+            fs::write(dir.path().join(name), body).unwrap();
+        }
+        dir
+    }
+
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn plan_lists_only_files_that_change() {
+        let dir = fixture();
+
+        let renames = plan(dir.path(), 3).unwrap();
+
+        let pairs: Vec<(String, String)> = renames
+            .iter()
+            .map(|r| {
+                (
+                    r.from.file_name().unwrap().to_string_lossy().into_owned(),
+                    r.to.file_name().unwrap().to_string_lossy().into_owned(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            pairs,
+            vec![
+                ("idr-1.md".to_string(), "idr-001.md".to_string()),
+                (
+                    "idr-02-use-jwt.md".to_string(),
+                    "idr-002-use-jwt.md".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn apply_renames_and_keeps_contents() {
+        let dir = fixture();
+
+        apply(dir.path(), &plan(dir.path(), 3).unwrap()).unwrap();
+
+        assert_eq!(
+            names(dir.path()),
+            vec!["idr-001.md", "idr-002-use-jwt.md", "idr-100.md", "notes.md"]
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("idr-002-use-jwt.md")).unwrap(),
+            "# IDR: two\n"
+        );
+    }
+
+    #[test]
+    fn apply_narrows_width_next_to_slugged_record() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("idr-1.md"), "one").unwrap();
+        fs::write(dir.path().join("idr-01-x.md"), "x").unwrap();
+        fs::write(dir.path().join("idr-002.md"), "two").unwrap();
+
+        apply(dir.path(), &plan(dir.path(), 2).unwrap()).unwrap();
+
+        assert_eq!(
+            names(dir.path()),
+            vec!["idr-01-x.md", "idr-01.md", "idr-02.md"]
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("idr-02.md")).unwrap(),
+            "two"
+        );
+    }
+
+    #[test]
+    fn apply_renames_sidecars_with_records() {
+        let dir = fixture();
+        fs::write(dir.path().join("idr-02-use-jwt.meta.json"), "{}").unwrap();
+
+        apply(dir.path(), &plan(dir.path(), 3).unwrap()).unwrap();
+
+        assert!(dir.path().join("idr-002-use-jwt.meta.json").exists());
+        assert!(!dir.path().join("idr-02-use-jwt.meta.json").exists());
+    }
+
+    #[test]
+    fn apply_renames_translations_with_records() {
+        let dir = fixture();
+        fs::write(dir.path().join("idr-02-use-jwt.en.md"), "# IDR: Use JWT\n").unwrap();
+
+        apply(dir.path(), &plan(dir.path(), 3).unwrap()).unwrap();
+
+        assert!(dir.path().join("idr-002-use-jwt.en.md").exists());
+        assert!(!dir.path().join("idr-02-use-jwt.en.md").exists());
+    }
+
+    #[test]
+    fn plan_refuses_collisions() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("idr-1.md"), "one").unwrap();
+        fs::write(dir.path().join("idr-001.md"), "also one").unwrap();
+
+        let err = plan(dir.path(), 2).unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(names(dir.path()), vec!["idr-001.md", "idr-1.md"]);
+    }
+
+    #[test]
+    fn plan_refuses_target_held_by_unchanged_record() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("idr-1.md"), "one").unwrap();
+        fs::write(dir.path().join("idr-01.md"), "also one").unwrap();
+
+        assert!(plan(dir.path(), 2).is_err());
+    }
+
+    #[test]
+    fn apply_updates_index_and_latest() {
+        let dir = fixture();
+        index::refresh(dir.path(), Format::Idr).unwrap();
+        latest::update(
+            dir.path(),
+            &dir.path().join("idr-02-use-jwt.md"),
+            LatestMode::Copy,
+        )
+        .unwrap();
+
+        apply(dir.path(), &plan(dir.path(), 3).unwrap()).unwrap();
+
+        let index = fs::read_to_string(dir.path().join(index::FILE_NAME)).unwrap();
+        assert!(index.contains("(idr-002-use-jwt.md)"));
+        assert!(!index.contains("(idr-02-use-jwt.md)"));
+        let latest = fs::read_to_string(dir.path().join(latest::FILE_NAME)).unwrap();
+        assert!(latest.starts_with("> Latest: [idr-002-use-jwt.md](idr-002-use-jwt.md)\n"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn apply_repoints_latest_symlink() {
+        let dir = fixture();
+        latest::update(
+            dir.path(),
+            &dir.path().join("idr-1.md"),
+            LatestMode::Symlink,
+        )
+        .unwrap();
+
+        apply(dir.path(), &plan(dir.path(), 3).unwrap()).unwrap();
+
+        assert_eq!(
+            fs::read_link(dir.path().join(latest::FILE_NAME)).unwrap(),
+            Path::new("idr-001.md")
+        );
+    }
+}
@@ -0,0 +1,350 @@
+//! The run history: a line of JSON per run in
+//! `$XDG_STATE_HOME/claude-idr/history.jsonl`, to tell whether the hook ran
+//! on a commit and what the runs cost. Past `history_max_bytes` the file
+//! moves to `history.jsonl.1`, replacing the one moved there before.
+
+use crate::claude::Usage;
+use crate::config::{self, Config};
+use crate::generate::GenerateReport;
+use crate::git;
+use crate::log::{verbose, warn};
+use crate::path;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub const FILE_NAME: &str = "history.jsonl";
+
+/// One run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    /// UTC, e.g. `2026-02-07T05:30:00Z`.
+    pub timestamp: String,
+    /// The root of the repository the run was in.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repo: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    /// As in [`GenerateReport::status`].
+    pub status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost_usd: Option<f64>,
+    pub duration_secs: f64,
+}
+
+impl Entry {
+    /// The entry for `report`, a run of `duration` in `repo` on `branch`,
+    /// stamped with the current time.
+    pub fn new(
+        report: &GenerateReport,
+        repo: Option<PathBuf>,
+        branch: Option<String>,
+        duration: Duration,
+    ) -> Entry {
+        Entry {
+            timestamp: path::now_iso8601(),
+            repo,
+            branch,
+            status: report.status.to_string(),
+            reason: report.reason.clone(),
+            output: report.output.clone(),
+            model: report.model.clone(),
+            usage: report.usage,
+            cost_usd: report.cost_usd,
+            // Milliseconds are plenty, and keep the lines short.
+            duration_secs: (duration.as_secs_f64() * 1000.0).round() / 1000.0,
+        }
+    }
+}
+
+/// `history.jsonl` in the state directory.
+pub fn path() -> Option<PathBuf> {
+    Some(config::state_dir()?.join(FILE_NAME))
+}
+
+/// Where [`append`] moves the history at `path` once it is full.
+pub fn rotated(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".1");
+    path.with_file_name(name)
+}
+
+/// Appends `entry` to the history at `path`, creating its directory as
+/// needed. When the line would take the file past `max_bytes`, the file is
+/// first moved to [`rotated`] and a new one begun.
+pub fn append(path: &Path, entry: &Entry, max_bytes: u64) -> io::Result<()> {
+    let mut line = serde_json::to_string(entry).map_err(io::Error::other)?;
+    line.push('\n');
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let size = fs::metadata(path).map(|meta| meta.len()).unwrap_or(0);
+    if size > 0 && size + line.len() as u64 > max_bytes {
+        fs::rename(path, rotated(path))?;
+    }
+    // One write per line, so concurrent runs append whole lines.
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(line.as_bytes())
+}
+
+/// Records `report` in the history, unless `history_max_bytes` is 0. A
+/// history that cannot be written only warns; it never fails the run.
+pub fn record(report: &GenerateReport, config: &Config, duration: Duration) {
+    if config.history_max_bytes == 0 {
+        return;
+    }
+    let Some(path) = path() else {
+        verbose!("no state directory for the run history");
+        return;
+    };
+    let entry = Entry::new(report, git::repo_root(), git::current_branch(), duration);
+    if let Err(e) = append(&path, &entry, config.history_max_bytes) {
+        warn!("cannot write run history {}: {e}", path.display());
+    }
+}
+
+/// The newest `limit` entries in the history at `path` and the file rotated
+/// out of it, newest first. Lines that do not parse are skipped, and a
+/// missing file holds no entries.
+pub fn recent(path: &Path, limit: usize) -> io::Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    for file in [path.to_path_buf(), rotated(path)] {
+        let text = match fs::read_to_string(&file) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        entries.extend(
+            text.lines()
+                .rev()
+                .filter_map(|line| serde_json::from_str::<Entry>(line).ok()),
+        );
+        if entries.len() >= limit {
+            break;
+        }
+    }
+    entries.truncate(limit);
+    Ok(entries)
+}
+
+/// One line per entry: time, outcome, duration, where, and what came of
+/// it.
+pub fn render(entries: &[Entry]) -> String {
+    let mut text = String::new();
+    for entry in entries {
+        let place = match (&entry.repo, &entry.branch) {
+            (Some(repo), Some(branch)) => format!("{} ({branch})", repo.display()),
+            (Some(repo), None) => repo.display().to_string(),
+            (None, _) => "-".to_string(),
+        };
+        let mut line = format!(
+            "{}  {:<9}  {:>6.1}s  {place}",
+            entry.timestamp, entry.status, entry.duration_secs
+        );
+        if let Some(output) = &entry.output {
+            line.push_str(&format!("  {}", output.display()));
+        }
+        if let Some(reason) = &entry.reason {
+            line.push_str(&format!("  {reason}"));
+        }
+        let mut spent = Vec::new();
+        spent.extend(entry.model.clone());
+        if let Some(usage) = entry.usage {
+            spent.push(format!(
+                "{} in / {} out tokens",
+                usage.input_tokens, usage.output_tokens
+            ));
+        }
+        if let Some(cost) = entry.cost_usd {
+            spent.push(format!("${cost:.4}"));
+        }
+        if !spent.is_empty() {
+            line.push_str(&format!("  [{}]", spent.join(", ")));
+        }
+        text.push_str(line.trim_end());
+        text.push('\n');
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn entry(status: &str, timestamp: &str) -> Entry {
+        Entry {
+            timestamp: timestamp.to_string(),
+            repo: Some(PathBuf::from("/src/app")),
+            branch: Some("main".to_string()),
+            status: status.to_string(),
+            reason: None,
+            output: None,
+            model: None,
+            usage: None,
+            cost_usd: None,
+            duration_secs: 0.25,
+        }
+    }
+
+    #[test]
+    fn entry_new_takes_the_outcome_from_the_report() {
+        let report = GenerateReport {
+            status: "generated",
+            output: Some(PathBuf::from("/ws/idr-03-retry.md")),
+            usage: Some(Usage {
+                input_tokens: 1200,
+                output_tokens: 300,
+            }),
+            cost_usd: Some(0.0123),
+            model: Some("sonnet".to_string()),
+            ..GenerateReport::default()
+        };
+
+        let entry = Entry::new(
+            &report,
+            Some(PathBuf::from("/src/app")),
+            None,
+            Duration::from_micros(41_234_567),
+        );
+
+        assert_eq!(entry.status, "generated");
+        assert_eq!(entry.output, report.output);
+        assert_eq!(entry.usage, report.usage);
+        assert_eq!(entry.duration_secs, 41.235);
+        assert!(entry.timestamp.ends_with('Z'));
+    }
+
+    #[test]
+    fn entries_round_trip_and_leave_out_what_is_unset() {
+        let skipped = Entry {
+            reason: Some("no recent session".to_string()),
+            ..entry("skipped", "2026-02-07T05:30:00Z")
+        };
+
+        let line = serde_json::to_string(&skipped).unwrap();
+
+        assert_eq!(
+            line,
+            r#"{"timestamp":"2026-02-07T05:30:00Z","repo":"/src/app","branch":"main","status":"skipped","reason":"no recent session","duration_secs":0.25}"#
+        );
+        assert_eq!(serde_json::from_str::<Entry>(&line).unwrap(), skipped);
+    }
+
+    #[test]
+    fn append_adds_lines_and_creates_the_directory() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("state/claude-idr").join(FILE_NAME);
+
+        append(&path, &entry("skipped", "2026-02-07T05:30:00Z"), 1024).unwrap();
+        append(&path, &entry("generated", "2026-02-07T05:31:00Z"), 1024).unwrap();
+
+        let text = fs::read_to_string(&path).unwrap();
+        assert_eq!(text.lines().count(), 2);
+        assert!(text.ends_with('\n'));
+    }
+
+    #[test]
+    fn append_rotates_a_full_file() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(FILE_NAME);
+        let line_len = serde_json::to_string(&entry("skipped", "2026-02-07T05:30:00Z"))
+            .unwrap()
+            .len() as u64
+            + 1;
+        let max = line_len * 2;
+
+        for minute in 0..5 {
+            let stamp = format!("2026-02-07T05:3{minute}:00Z");
+            append(&path, &entry("skipped", &stamp), max).unwrap();
+        }
+
+        assert_eq!(rotated(&path), dir.path().join("history.jsonl.1"));
+        let current = fs::read_to_string(&path).unwrap();
+        let previous = fs::read_to_string(rotated(&path)).unwrap();
+        assert_eq!(current.lines().count(), 1);
+        assert!(current.contains("05:34:00Z"));
+        assert_eq!(previous.lines().count(), 2);
+        assert!(previous.contains("05:32:00Z") && previous.contains("05:33:00Z"));
+    }
+
+    #[test]
+    fn append_reports_a_history_that_cannot_be_written() {
+        let dir = TempDir::new().unwrap();
+        let blocked = dir.path().join("not-a-dir");
+        fs::write(&blocked, "").unwrap();
+
+        let result = append(
+            &blocked.join(FILE_NAME),
+            &entry("skipped", "2026-02-07T05:30:00Z"),
+            1024,
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn recent_reads_newest_first_across_the_rotated_file() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(FILE_NAME);
+        assert_eq!(recent(&path, 10).unwrap(), Vec::new());
+
+        let line = |minute: u32| {
+            serde_json::to_string(&entry("skipped", &format!("2026-02-07T05:3{minute}:00Z")))
+                .unwrap()
+        };
+        fs::write(rotated(&path), format!("{}\n{}\n", line(0), line(1))).unwrap();
+        fs::write(&path, format!("{}\nnot json\n{}\n", line(2), line(3))).unwrap();
+
+        let stamps = |limit| -> Vec<String> {
+            recent(&path, limit)
+                .unwrap()
+                .into_iter()
+                .map(|e| e.timestamp[11..16].to_string())
+                .collect()
+        };
+        assert_eq!(stamps(2), ["05:33", "05:32"]);
+        assert_eq!(stamps(10), ["05:33", "05:32", "05:31", "05:30"]);
+        assert_eq!(stamps(0), Vec::<String>::new());
+    }
+
+    #[test]
+    fn render_puts_one_run_on_a_line() {
+        let generated = Entry {
+            output: Some(PathBuf::from("/ws/idr-03-retry.md")),
+            model: Some("sonnet".to_string()),
+            usage: Some(Usage {
+                input_tokens: 1200,
+                output_tokens: 300,
+            }),
+            cost_usd: Some(0.0123),
+            duration_secs: 41.2,
+            ..entry("generated", "2026-02-07T05:31:00Z")
+        };
+        let error = Entry {
+            repo: None,
+            branch: None,
+            reason: Some("claude timed out".to_string()),
+            ..entry("error", "2026-02-07T05:30:00Z")
+        };
+
+        assert_eq!(
+            render(&[generated, error]),
+            "2026-02-07T05:31:00Z  generated    41.2s  /src/app (main)  /ws/idr-03-retry.md  [sonnet, 1200 in / 300 out tokens, $0.0123]\n\
+             2026-02-07T05:30:00Z  error         0.2s  -  claude timed out\n"
+        );
+    }
+}
@@ -0,0 +1,201 @@
+//! Binary content git took for text (UTF-16 sources, mixed encodings).
+//! After the lossy UTF-8 conversion it is mostly replacement characters and
+//! control bytes, which waste tokens and can trip up the claude CLI, so such
+//! files lose their hunks before any prompt is built.
+
+use crate::config::Config;
+use crate::diff;
+use crate::log::verbose;
+
+/// Left under the header of a file whose hunks were dropped.
+pub const MARKER: &str = "[binary-like content omitted]";
+
+/// The diff after [`clean`], and what it took out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sanitized {
+    pub diff: String,
+    /// Files whose hunks were replaced with [`MARKER`].
+    pub omitted: Vec<String>,
+    /// NUL and ESC characters removed from the remaining hunks.
+    pub stripped: usize,
+}
+
+/// [`clean`] with the thresholds from `binary_density` and
+/// `binary_min_chars`.
+pub fn apply(diff: &str, config: &Config) -> Option<Sanitized> {
+    let sanitized = clean(diff, config.binary_density, config.binary_min_chars)?;
+    if sanitized.stripped > 0 {
+        verbose!(
+            "stripped {} NUL/ESC character(s) from the prompt diff",
+            sanitized.stripped
+        );
+    }
+    Some(sanitized)
+}
+
+impl Sanitized {
+    /// `binary-like content omitted from the prompt: a.rs, b.rs`, for
+    /// stderr and the record's footer; `None` when no file was omitted.
+    pub fn note(&self) -> Option<String> {
+        (!self.omitted.is_empty()).then(|| {
+            format!(
+                "binary-like content omitted from the prompt: {}",
+                self.omitted.join(", ")
+            )
+        })
+    }
+}
+
+/// `diff` with the hunks of every file that looks binary dropped, and lone
+/// NUL and ESC characters removed from the rest. A file looks binary when
+/// at least `min_chars` of its hunk text are suspicious (see
+/// [`is_suspicious`]) and they make up at least `density` of it. `None`
+/// when there was nothing to clean.
+pub fn clean(diff: &str, density: f64, min_chars: usize) -> Option<Sanitized> {
+    let mut files = diff::parse(diff);
+    let mut omitted = Vec::new();
+    let mut stripped = 0;
+    for file in &mut files {
+        let (suspicious, total) = file
+            .hunks
+            .iter()
+            .flat_map(|hunk| &hunk.lines)
+            .flat_map(|line| line.chars().skip(1))
+            .fold((0, 0), |(suspicious, total), c| {
+                (suspicious + usize::from(is_suspicious(c)), total + 1)
+            });
+        if suspicious > 0 && suspicious >= min_chars && suspicious as f64 >= density * total as f64
+        {
+            file.hunks.clear();
+            file.header.push(MARKER.to_string());
+            omitted.push(file.path.clone());
+            continue;
+        }
+        for line in file.hunks.iter_mut().flat_map(|hunk| hunk.lines.iter_mut()) {
+            if line.contains(is_stripped) {
+                stripped += line.matches(is_stripped).count();
+                line.retain(|c| !is_stripped(c));
+            }
+        }
+    }
+    (!omitted.is_empty() || stripped > 0).then(|| Sanitized {
+        diff: diff::render(&files),
+        omitted,
+        stripped,
+    })
+}
+
+/// Replacement characters and control characters other than tab, carriage
+/// return, form feed, and ESC, which real text has now and then.
+fn is_suspicious(c: char) -> bool {
+    c == char::REPLACEMENT_CHARACTER
+        || (c.is_control() && !matches!(c, '\t' | '\r' | '\x0c' | '\x1b'))
+}
+
+/// Removed wherever they are left: NUL is never text, and ESC alone only
+/// colours terminal output.
+fn is_stripped(c: char) -> bool {
+    matches!(c, '\0' | '\x1b')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DENSITY: f64 = 0.3;
+    const MIN_CHARS: usize = 16;
+
+    fn file_diff(path: &str, lines: &[String]) -> String {
+        let body: String = lines.iter().map(|line| format!("+{line}\n")).collect();
+        format!(
+            "diff --git a/{path} b/{path}\n--- /dev/null\n+++ b/{path}\n@@ -0,0 +1,{} @@\n{body}",
+            lines.len()
+        )
+    }
+
+    /// `text` as UTF-16LE bytes read back as lossy UTF-8, line by line, the
+    /// way a source git took for text reaches the diff.
+    fn utf16_lines(text: &str) -> Vec<String> {
+        let bytes: Vec<u8> = text.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        String::from_utf8_lossy(&bytes)
+            .split('\n')
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn clean_omits_utf16_sources() {
+        let english = utf16_lines("fn main() {\n    println!(\"hello\");\n}\n");
+        let japanese = utf16_lines("// 設定を読み込む\nlet 名前 = \"テスト\";\n");
+        let diff = file_diff("src/main.rs", &english) + &file_diff("src/jp.rs", &japanese);
+
+        let sanitized = clean(&diff, DENSITY, MIN_CHARS).unwrap();
+
+        assert_eq!(sanitized.omitted, ["src/main.rs", "src/jp.rs"]);
+        let files = diff::parse(&sanitized.diff);
+        assert!(files.iter().all(|f| f.hunks.is_empty()));
+        assert_eq!(files[0].header.last().map(String::as_str), Some(MARKER));
+        assert!(!sanitized.diff.contains('\0'));
+    }
+
+    #[test]
+    fn clean_keeps_genuine_text() {
+        let japanese = vec![
+            "// 設定ファイルを読み込む".to_string(),
+            "let name = \"日本語のテキスト\";".to_string(),
+        ];
+        let tabs = vec!["\tindented\twith\ttabs\r".to_string(); 20];
+        let diff = file_diff("src/jp.rs", &japanese) + &file_diff("Makefile", &tabs);
+
+        assert_eq!(clean(&diff, DENSITY, MIN_CHARS), None);
+    }
+
+    #[test]
+    fn clean_keeps_text_with_a_few_bad_bytes() {
+        // Latin-1 French read as UTF-8: one replacement per accent.
+        let latin1: Vec<u8> = "Le café était très animé, près de l'été.\n"
+            .chars()
+            .map(|c| c as u32 as u8)
+            .collect();
+        let lines: Vec<String> = String::from_utf8_lossy(&latin1.repeat(10))
+            .lines()
+            .map(str::to_string)
+            .collect();
+        let diff = file_diff("notes.txt", &lines);
+
+        assert_eq!(clean(&diff, DENSITY, MIN_CHARS), None);
+        // Too few to judge, however dense.
+        let short = file_diff("a.txt", &["\u{FFFD}\u{FFFD}".to_string()]);
+        assert_eq!(clean(&short, DENSITY, MIN_CHARS), None);
+    }
+
+    #[test]
+    fn clean_strips_lone_nul_and_esc_from_text() {
+        let lines = vec![
+            "\x1b[31mred\x1b[0m".to_string(),
+            "plain".to_string(),
+            "trailing nul\0".to_string(),
+        ];
+        let diff = file_diff("tests/output.txt", &lines);
+
+        let sanitized = clean(&diff, DENSITY, MIN_CHARS).unwrap();
+
+        assert!(sanitized.omitted.is_empty());
+        assert_eq!(sanitized.stripped, 3);
+        assert!(
+            sanitized
+                .diff
+                .contains("+[31mred[0m\n+plain\n+trailing nul\n")
+        );
+    }
+
+    #[test]
+    fn clean_follows_the_thresholds() {
+        let lines = vec!["a\u{1}b\u{2}c\u{3}d\u{4}".to_string(); 4];
+        let diff = file_diff("data.txt", &lines);
+
+        assert!(clean(&diff, DENSITY, MIN_CHARS).is_some());
+        assert_eq!(clean(&diff, 0.9, MIN_CHARS), None);
+        assert_eq!(clean(&diff, DENSITY, 17), None);
+    }
+}
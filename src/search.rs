@@ -0,0 +1,315 @@
+//! `claude-idr search`: records whose title, reasons, or body contain a
+//! query, best matches first.
+
+use crate::config::Config;
+use crate::front_matter;
+use crate::path;
+use crate::prompt;
+use crate::record;
+use crate::sidecar;
+use serde::Serialize;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// Characters of a snippet around the match; longer lines are cut.
+const SNIPPET_CHARS: usize = 120;
+/// Characters kept before the match when a line is cut.
+const SNIPPET_LEAD: usize = 40;
+
+#[derive(Debug, Default)]
+pub struct Query<'a> {
+    pub text: &'a str,
+    pub title_only: bool,
+    /// Only records with this change type or front matter tag.
+    pub tag: Option<&'a str>,
+}
+
+/// Where a record matched, best first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Field {
+    Title,
+    Reason,
+    Body,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Hit {
+    pub path: PathBuf,
+    pub title: String,
+    pub date: Option<String>,
+    pub change_type: Option<String>,
+    pub field: Field,
+    /// The matching line, cut to about [`SNIPPET_CHARS`] characters.
+    pub snippet: String,
+    /// Byte range of the match in `snippet`.
+    #[serde(rename = "match")]
+    pub matched: Range<usize>,
+}
+
+impl Hit {
+    /// The snippet with the match between `on` and `off`.
+    pub fn highlighted(&self, on: &str, off: &str) -> String {
+        let Range { start, end } = self.matched.clone();
+        format!(
+            "{}{on}{}{off}{}",
+            &self.snippet[..start],
+            &self.snippet[start..end],
+            &self.snippet[end..]
+        )
+    }
+}
+
+/// The records in `dirs` matching `query`, one hit per record at its best
+/// field: title matches, then reason matches, then body matches, each
+/// newest first, with the path breaking ties.
+pub fn search(dirs: &[PathBuf], query: &Query, config: &Config) -> Vec<Hit> {
+    let mut hits: Vec<Hit> = dirs
+        .iter()
+        .flat_map(|dir| path::list_records(dir, config.format))
+        .filter_map(|(_, file)| {
+            let text = String::from_utf8_lossy(&fs::read(&file).ok()?).into_owned();
+            matches(&file, &text, query, config)
+        })
+        .collect();
+    hits.sort_by(|a, b| {
+        a.field
+            .cmp(&b.field)
+            .then_with(|| b.date.cmp(&a.date))
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    hits
+}
+
+fn matches(file: &Path, text: &str, query: &Query, config: &Config) -> Option<Hit> {
+    let (block, body) = front_matter::split(text);
+    let tags = block.map_or_else(Vec::new, |b| front_matter::list(b, "tags"));
+    let change_type = sidecar::load_metadata(file)
+        .and_then(|s| s.change_type)
+        .or_else(|| {
+            tags.iter()
+                .find(|t| prompt::CHANGE_TYPES.contains(&t.as_str()))
+                .cloned()
+        });
+    if let Some(wanted) = query.tag {
+        let known = change_type.iter().chain(&tags);
+        if !known.into_iter().any(|t| t.eq_ignore_ascii_case(wanted)) {
+            return None;
+        }
+    }
+    let parsed = record::parse(text, config);
+    let reasons = parsed
+        .files
+        .iter()
+        .flat_map(|f| &f.hunks)
+        .filter_map(|h| h.reason.as_deref())
+        .flat_map(str::lines);
+    let body_lines = body
+        .lines()
+        .filter(|line| !line.starts_with("# ") && !line.starts_with("<!-- claude-idr:"));
+    let candidates = std::iter::once((Field::Title, parsed.title.as_str())).chain(
+        (!query.title_only)
+            .then(|| {
+                reasons
+                    .map(|l| (Field::Reason, l))
+                    .chain(body_lines.map(|l| (Field::Body, l)))
+            })
+            .into_iter()
+            .flatten(),
+    );
+    let (field, line, found) = candidates
+        .filter_map(|(field, line)| Some((field, line, find(line, query.text)?)))
+        .next()?;
+    let (snippet, matched) = snippet(line, found);
+    Some(Hit {
+        path: file.to_path_buf(),
+        title: parsed.title.clone(),
+        date: parsed.datetime.clone(),
+        change_type,
+        field,
+        snippet,
+        matched,
+    })
+}
+
+/// Byte range of the first case-insensitive occurrence of `needle`.
+fn find(haystack: &str, needle: &str) -> Option<Range<usize>> {
+    let needle: Vec<char> = needle.chars().flat_map(char::to_lowercase).collect();
+    if needle.is_empty() {
+        return None;
+    }
+    haystack.char_indices().find_map(|(start, _)| {
+        let mut wanted = needle.iter();
+        for (i, c) in haystack[start..].char_indices() {
+            let mut lower = c.to_lowercase();
+            if !lower.all(|l| wanted.next() == Some(&l)) {
+                return None;
+            }
+            if wanted.len() == 0 {
+                return Some(start..start + i + c.len_utf8());
+            }
+        }
+        None
+    })
+}
+
+/// `line` trimmed, and cut around `found` when it is long, with `found`
+/// shifted to match.
+fn snippet(line: &str, found: Range<usize>) -> (String, Range<usize>) {
+    let lead = line.len() - line.trim_start().len();
+    let line = line.trim();
+    // The trim can cut whitespace the match ends (or starts) in.
+    let shifted = |i: usize| i.saturating_sub(lead).min(line.len());
+    let found = shifted(found.start)..shifted(found.end);
+    if line.chars().count() <= SNIPPET_CHARS {
+        return (line.to_string(), found);
+    }
+    let offsets: Vec<usize> = line
+        .char_indices()
+        .map(|(i, _)| i)
+        .chain([line.len()])
+        .collect();
+    let match_char = offsets.iter().position(|&i| i == found.start).unwrap_or(0);
+    let first = match_char.saturating_sub(SNIPPET_LEAD);
+    let last = (first + SNIPPET_CHARS).min(offsets.len() - 1);
+    let (from, to) = (offsets[first], offsets[last]);
+    let prefix = if from > 0 { "…" } else { "" };
+    let suffix = if to < line.len() { "…" } else { "" };
+    let shift = |i: usize| (i.clamp(from, to) - from) + prefix.len();
+    (
+        format!("{prefix}{}{suffix}", &line[from..to]),
+        shift(found.start)..shift(found.end),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixtures() -> Vec<PathBuf> {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/search");
+        crate::digest::record_dirs(&root)
+    }
+
+    fn run(query: &Query) -> Vec<Hit> {
+        search(&fixtures(), query, &Config::default())
+    }
+
+    fn names(hits: &[Hit]) -> Vec<String> {
+        hits.iter()
+            .map(|h| {
+                let dir = h.path.parent().unwrap().file_name().unwrap();
+                format!("{}/{:?}", dir.to_string_lossy(), h.field).to_lowercase()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn search_ranks_title_then_reason_then_body_then_recency() {
+        let hits = run(&Query {
+            text: "BACKOFF",
+            ..Query::default()
+        });
+
+        assert_eq!(
+            names(&hits),
+            [
+                "2026-02-03/title",
+                "2026-01-15/title",
+                "2026-02-10/reason",
+                "2026-02-12/body",
+            ]
+        );
+        assert_eq!(hits[2].title, "Tune HTTP client");
+        assert_eq!(hits[2].date.as_deref(), Some("2026-02-10T00:00:00Z"));
+        assert_eq!(
+            hits[2].highlighted("[", "]"),
+            "Exponential [backoff] keeps retries from piling onto a struggling server."
+        );
+    }
+
+    #[test]
+    fn search_title_only_skips_reasons_and_body() {
+        let hits = run(&Query {
+            text: "backoff",
+            title_only: true,
+            ..Query::default()
+        });
+
+        assert_eq!(names(&hits), ["2026-02-03/title", "2026-01-15/title"]);
+    }
+
+    #[test]
+    fn search_filters_by_change_type_or_tag() {
+        let by_type = run(&Query {
+            text: "backoff",
+            tag: Some("refactor"),
+            ..Query::default()
+        });
+        let by_tag = run(&Query {
+            text: "backoff",
+            tag: Some("IDR"),
+            ..Query::default()
+        });
+
+        assert_eq!(names(&by_type), ["2026-02-10/reason"]);
+        assert_eq!(by_type[0].change_type.as_deref(), Some("refactor"));
+        assert_eq!(names(&by_tag), ["2026-02-10/reason", "2026-02-12/body"]);
+    }
+
+    #[test]
+    fn search_finds_japanese_text() {
+        let hits = run(&Query {
+            text: "再試行",
+            ..Query::default()
+        });
+
+        assert_eq!(names(&hits), ["2026-02-03/reason"]);
+        assert_eq!(
+            hits[0].highlighted("<", ">"),
+            "一時的な障害で失敗しないよう<再試行>する。"
+        );
+    }
+
+    #[test]
+    fn find_ignores_case_and_returns_byte_range() {
+        assert_eq!(find("Große Backoff", "backoff"), Some(7..14));
+        assert_eq!(find("abc", "x"), None);
+        assert_eq!(find("abc", ""), None);
+    }
+
+    #[test]
+    fn snippet_cuts_long_lines_around_match() {
+        let line = format!("  {}needle{}", "a".repeat(100), "b".repeat(100));
+        let found = find(&line, "needle").unwrap();
+
+        let (snippet, matched) = snippet(&line, found);
+
+        assert_eq!(&snippet[matched.clone()], "needle");
+        assert!(snippet.starts_with("…aaa"), "{snippet}");
+        assert!(snippet.ends_with("bbb…"), "{snippet}");
+        assert_eq!(snippet.chars().count(), SNIPPET_CHARS + 2);
+    }
+
+    #[test]
+    fn snippet_keeps_a_match_ending_in_trimmed_whitespace_inside_the_line() {
+        let line = "some foo  ";
+        let found = find(line, "foo  ").unwrap();
+
+        let (snippet, matched) = snippet(line, found);
+
+        assert_eq!(snippet, "some foo");
+        assert_eq!(matched, 5..8);
+        let hit = Hit {
+            path: PathBuf::from("idr-01.md"),
+            title: String::new(),
+            date: None,
+            change_type: None,
+            field: Field::Body,
+            snippet,
+            matched,
+        };
+        assert_eq!(hit.highlighted("[", "]"), "some [foo]");
+    }
+}
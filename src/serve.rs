@@ -0,0 +1,384 @@
+//! `claude-idr serve`: a read-only HTTP server on localhost for browsing
+//! records in a browser, rendered the way `export` renders them.
+
+use crate::config::Config;
+use crate::export;
+use crate::history;
+use crate::log::verbose;
+use crate::search;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub const DEFAULT_PORT: u16 = 7700;
+/// How long a client may take to send its request.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Listens on `port` on the loopback interface only.
+pub fn bind(port: u16) -> io::Result<TcpListener> {
+    TcpListener::bind((Ipv4Addr::LOCALHOST, port))
+}
+
+/// The directories the server shows. Pages are addressed by a directory's
+/// position in the list, so nothing outside it can be requested.
+pub struct Site<'a> {
+    dirs: Vec<(String, PathBuf)>,
+    config: &'a Config,
+}
+
+impl<'a> Site<'a> {
+    /// The directories in `dirs` holding records, labeled relative to
+    /// `root`.
+    pub fn new(root: &Path, dirs: Vec<PathBuf>, config: &'a Config) -> Site<'a> {
+        let dirs = dirs
+            .into_iter()
+            .filter(|dir| !export::sources(dir, config).is_empty())
+            .map(|dir| {
+                let label = match dir.strip_prefix(root) {
+                    Ok(rel) if rel.as_os_str().is_empty() => ".".to_string(),
+                    Ok(rel) => rel.display().to_string(),
+                    Err(_) => dir.display().to_string(),
+                };
+                (label, dir)
+            })
+            .collect();
+        Site { dirs, config }
+    }
+
+    fn index_page(&self) -> String {
+        let mut body = format!("<h1>Decision records</h1>\n{}", search_form(""));
+        if self.dirs.is_empty() {
+            body.push_str("<p>No records found.</p>\n");
+        }
+        for (i, (label, dir)) in self.dirs.iter().enumerate() {
+            body.push_str(&format!("<h2>{}</h2>\n<ul>\n", export::escape(label)));
+            for source in export::sources(dir, self.config) {
+                let Some(name) = source.file_name().map(|n| n.to_string_lossy()) else {
+                    continue;
+                };
+                let title = fs::read(&source)
+                    .map(|bytes| history::title(&String::from_utf8_lossy(&bytes)))
+                    .unwrap_or_default();
+                let title = if title.is_empty() { &*name } else { &title };
+                body.push_str(&format!(
+                    "<li><a href=\"{}\">{}</a> <small>{}</small></li>\n",
+                    page_url(i, &name),
+                    export::escape(title),
+                    export::escape(&name)
+                ));
+            }
+            body.push_str("</ul>\n");
+        }
+        export::document("Decision records", &body, &self.config.language)
+    }
+
+    /// The page for `name` (`idr-07-x.html`) in the directory at `index`.
+    fn record_page(&self, index: &str, name: &str) -> Option<String> {
+        let (_, dir) = self.dirs.get(index.parse::<usize>().ok()?)?;
+        let wanted = format!("{}.md", name.strip_suffix(".html")?);
+        let sources = export::sources(dir, self.config);
+        let names: Vec<String> = sources
+            .iter()
+            .filter_map(|p| Some(p.file_name()?.to_string_lossy().into_owned()))
+            .collect();
+        let source = &sources[names.iter().position(|n| *n == wanted)?];
+        let text = String::from_utf8_lossy(&fs::read(source).ok()?).into_owned();
+        Some(export::render(&text, &names, self.config))
+    }
+
+    fn hits(&self, text: &str) -> Vec<search::Hit> {
+        let dirs: Vec<PathBuf> = self.dirs.iter().map(|(_, dir)| dir.clone()).collect();
+        let query = search::Query {
+            text,
+            ..search::Query::default()
+        };
+        search::search(&dirs, &query, self.config)
+    }
+
+    fn search_page(&self, text: &str) -> String {
+        let hits = self.hits(text);
+        let mut body = format!(
+            "<p><a href=\"/\">All records</a></p>\n{}<p>{} record{} match.</p>\n<ul>\n",
+            search_form(text),
+            hits.len(),
+            if hits.len() == 1 { "" } else { "s" }
+        );
+        for hit in &hits {
+            let place = self
+                .dirs
+                .iter()
+                .position(|(_, dir)| hit.path.parent() == Some(dir.as_path()));
+            let name = hit
+                .path
+                .file_name()
+                .map(|n| n.to_string_lossy())
+                .unwrap_or_default();
+            let title = export::escape(&hit.title);
+            let link = match place {
+                Some(i) => format!("<a href=\"{}\">{title}</a>", page_url(i, &name)),
+                None => title,
+            };
+            let snippet = hit.snippet.as_str();
+            let range = hit.matched.clone();
+            // A range that does not fit the snippet loses its highlight
+            // rather than the server.
+            let (before, matched, after) = match (
+                snippet.get(..range.start),
+                snippet.get(range.clone()),
+                snippet.get(range.end..),
+            ) {
+                (Some(before), Some(matched), Some(after)) => (before, matched, after),
+                _ => (snippet, "", ""),
+            };
+            body.push_str(&format!(
+                "<li>{link} <small>{}</small><br>{}<mark>{}</mark>{}</li>\n",
+                export::escape(hit.date.as_deref().unwrap_or_default()),
+                export::escape(before),
+                export::escape(matched),
+                export::escape(after)
+            ));
+        }
+        body.push_str("</ul>\n");
+        export::document("Search", &body, &self.config.language)
+    }
+}
+
+fn search_form(query: &str) -> String {
+    format!(
+        "<form action=\"/search\"><input name=\"q\" value=\"{}\" placeholder=\"Search records\"> <button>Search</button></form>\n",
+        export::escape(query)
+    )
+}
+
+fn page_url(index: usize, name: &str) -> String {
+    format!(
+        "/d/{index}/{}",
+        export::html_name(name)
+            .replace('%', "%25")
+            .replace(' ', "%20")
+    )
+}
+
+/// Answers requests on `listener`, one at a time, until the process is
+/// stopped.
+pub fn serve(listener: &TcpListener, site: &Site) {
+    for stream in listener.incoming() {
+        let result = stream.and_then(|stream| handle(&stream, site));
+        if let Err(e) = result {
+            verbose!("serve: request failed: {e}");
+        }
+    }
+}
+
+fn handle(stream: &TcpStream, site: &Site) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut header = String::new();
+    loop {
+        header.clear();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+    }
+    let mut writer = stream;
+    writer.write_all(&route(&request_line, site).into_bytes())
+}
+
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    fn html(body: String) -> Response {
+        Response {
+            status: "200 OK",
+            content_type: "text/html; charset=utf-8",
+            body,
+        }
+    }
+
+    fn error(status: &'static str) -> Response {
+        Response {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: format!("{status}\n"),
+        }
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        format!(
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.status,
+            self.content_type,
+            self.body.len(),
+            self.body
+        )
+        .into_bytes()
+    }
+}
+
+/// `/` lists every record, `/d/<dir>/<name>.html` renders one, and
+/// `/search?q=` (`/search.json?q=` for JSON) searches them.
+fn route(request_line: &str, site: &Site) -> Response {
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Response::error("400 Bad Request");
+    };
+    if method != "GET" {
+        return Response::error("405 Method Not Allowed");
+    }
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    match path {
+        "/" => Response::html(site.index_page()),
+        "/search" => Response::html(site.search_page(&param(query, "q"))),
+        "/search.json" => match serde_json::to_string(&site.hits(&param(query, "q"))) {
+            Ok(body) => Response {
+                status: "200 OK",
+                content_type: "application/json",
+                body,
+            },
+            Err(_) => Response::error("500 Internal Server Error"),
+        },
+        _ => path
+            .strip_prefix("/d/")
+            .and_then(|rest| rest.split_once('/'))
+            .and_then(|(index, name)| site.record_page(index, &decode(name, false)))
+            .map_or_else(|| Response::error("404 Not Found"), Response::html),
+    }
+}
+
+/// The value of `key` in a query string, or an empty string.
+fn param(query: &str, key: &str) -> String {
+    query
+        .split('&')
+        .find_map(|pair| {
+            let (k, v) = pair.split_once('=').unwrap_or((pair, ""));
+            (decode(k, true) == key).then(|| decode(v, true))
+        })
+        .unwrap_or_default()
+}
+
+/// Percent-decodes `text`; in a query string, `+` is a space too.
+fn decode(text: &str, query: bool) -> String {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut i = 0;
+    while i < text.len() {
+        let byte = text.as_bytes()[i];
+        let escaped = (byte == b'%')
+            .then(|| text.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(decoded) => {
+                bytes.push(decoded);
+                i += 3;
+            }
+            None => {
+                bytes.push(if query && byte == b'+' { b' ' } else { byte });
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::net::SocketAddr;
+
+    /// A server over the search fixtures on an ephemeral port.
+    fn start() -> SocketAddr {
+        let listener = bind(0).unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/search");
+            let config = Config::default();
+            let site = Site::new(&root, crate::digest::record_dirs(&root), &config);
+            serve(&listener, &site);
+        });
+        addr
+    }
+
+    fn get(addr: SocketAddr, target: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {target} HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn bind_listens_on_loopback_only() {
+        let listener = bind(0).unwrap();
+        assert!(listener.local_addr().unwrap().ip().is_loopback());
+    }
+
+    #[test]
+    fn serves_index_record_and_search() {
+        let addr = start();
+
+        let index = get(addr, "/");
+        assert!(index.starts_with("HTTP/1.1 200 OK\r\n"), "{index}");
+        assert!(index.contains("<h2>planning/2026-02-03</h2>"), "{index}");
+        let link = "<a href=\"/d/1/idr-01-add-retry.html\">Add retry with exponential backoff</a>";
+        assert!(index.contains(link), "{index}");
+
+        let page = get(addr, "/d/1/idr-01-add-retry.html");
+        assert!(page.starts_with("HTTP/1.1 200 OK\r\n"), "{page}");
+        assert!(
+            page.contains("<title>Add retry with exponential backoff</title>"),
+            "{page}"
+        );
+
+        let results = get(addr, "/search?q=%E5%86%8D%E8%A9%A6%E8%A1%8C");
+        assert!(results.contains("1 record match."), "{results}");
+        assert!(results.contains("<mark>再試行</mark>"), "{results}");
+
+        let json = get(addr, "/search.json?q=backoff");
+        let body = json.split_once("\r\n\r\n").unwrap().1;
+        let hits: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(hits.as_array().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn search_for_trailing_whitespace_keeps_the_server_up() {
+        let addr = start();
+
+        // Matches a line that ends in a markdown hard break.
+        let results = get(addr, "/search?q=settings.++");
+        assert!(results.starts_with("HTTP/1.1 200 OK\r\n"), "{results}");
+        assert!(results.contains("<mark>settings.</mark>"), "{results}");
+        assert!(get(addr, "/").starts_with("HTTP/1.1 200 OK\r\n"));
+    }
+
+    #[test]
+    fn refuses_unknown_paths_and_writes() {
+        let addr = start();
+
+        assert!(get(addr, "/d/1/../../Cargo.html").starts_with("HTTP/1.1 404"));
+        assert!(get(addr, "/d/9/idr-01-add-retry.html").starts_with("HTTP/1.1 404"));
+        assert!(get(addr, "/etc/passwd").starts_with("HTTP/1.1 404"));
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "POST / HTTP/1.1\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 405"), "{response}");
+    }
+
+    #[test]
+    fn decode_handles_percent_escapes_and_plus() {
+        assert_eq!(decode("a%20b+c", true), "a b c");
+        assert_eq!(decode("a+b", false), "a+b");
+        assert_eq!(decode("100%", false), "100%");
+        assert_eq!(param("x=1&q=exp+backoff", "q"), "exp backoff");
+        assert_eq!(param("x=1", "q"), "");
+    }
+}
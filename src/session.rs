@@ -1,16 +1,44 @@
-use crate::config::Config;
-use crate::jsonl;
+use crate::config::{self, Config};
+use crate::error::IdrError;
+use crate::jsonl::{self, SessionEntry};
+use crate::log::warn;
 use std::path::{Path, PathBuf};
 use std::time::SystemTime;
 
-pub fn find_recent(config: &Config) -> Option<PathBuf> {
-    let project_dir = dirs::home_dir()?.join(".claude").join("projects");
+/// Environment variable that replaces `~/.claude/projects` (and the
+/// `projects_dir` setting) as the place transcripts are looked up.
+pub const PROJECTS_DIR_ENV: &str = "CLAUDE_IDR_PROJECTS_DIR";
+
+pub fn find_recent(config: &Config) -> Result<PathBuf, IdrError> {
+    let project_dir = projects_dir(config).ok_or_else(|| {
+        IdrError::SessionNotFound(
+            "no recent session found (cannot determine the home directory; set projects_dir)"
+                .to_string(),
+        )
+    })?;
     find_recent_in(config, SystemTime::now(), &project_dir)
 }
 
-fn find_recent_in(config: &Config, now: SystemTime, project_dir: &Path) -> Option<PathBuf> {
+/// Where transcripts are looked up: [`PROJECTS_DIR_ENV`], `projects_dir`,
+/// or `~/.claude/projects`. `None` without a home directory.
+pub fn projects_dir(config: &Config) -> Option<PathBuf> {
+    std::env::var_os(PROJECTS_DIR_ENV)
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| config.projects_dir.clone())
+        .or_else(|| Some(config::home_dir()?.join(".claude").join("projects")))
+}
+
+pub(crate) fn find_recent_in(
+    config: &Config,
+    now: SystemTime,
+    project_dir: &Path,
+) -> Result<PathBuf, IdrError> {
     if !project_dir.is_dir() {
-        return None;
+        return Err(IdrError::SessionNotFound(format!(
+            "no recent session found ({} does not exist)",
+            project_dir.display()
+        )));
     }
 
     let max_age = std::time::Duration::from_secs(config.session_max_age_min * 60);
@@ -26,31 +54,29 @@ fn find_recent_in(config: &Config, now: SystemTime, project_dir: &Path) -> Optio
         })
         .max_by_key(|(_, mtime)| *mtime)
         .map(|(path, _)| path)
+        .ok_or_else(|| IdrError::SessionNotFound("no recent session found".to_string()))
+}
+
+/// Claude Code names each transcript `<session-id>.jsonl`.
+pub fn session_id(path: &Path) -> Option<String> {
+    path.file_stem()
+        .and_then(|s| s.to_str())
+        .filter(|s| !s.is_empty())
+        .map(String::from)
 }
 
 pub fn has_write_or_edit(path: &Path) -> bool {
-    jsonl::iter_values(path).any(|v| {
-        v.pointer("/message/content")
-            .and_then(|c| c.as_array())
-            .is_some_and(|arr| {
-                arr.iter().any(|item| {
-                    matches!(
-                        item.get("name").and_then(|n| n.as_str()),
-                        Some("Write" | "Edit")
-                    )
-                })
-            })
-    })
+    // Edits cluster at the end of a session, so scan from there.
+    jsonl::iter_values_rev(path)
+        .map(SessionEntry::from)
+        .any(|entry| entry.uses_tool("Write") || entry.uses_tool("Edit"))
 }
 
 fn collect_jsonl_files(dir: &Path, out: &mut Vec<(PathBuf, SystemTime)>) {
     let entries = match std::fs::read_dir(dir) {
         Ok(e) => e,
         Err(e) => {
-            eprintln!(
-                "claude-idr: warning: cannot read directory {}: {e}",
-                dir.display()
-            );
+            warn!("cannot read directory {}: {e}", dir.display());
             return;
         }
     };
@@ -164,6 +190,12 @@ mod tests {
         assert!(has_write_or_edit(&jsonl));
     }
 
+    #[test]
+    fn session_id_is_the_file_stem() {
+        let path = Path::new("/home/user/.claude/projects/foo/4f9c2a1e-7b3d.jsonl");
+        assert_eq!(session_id(path).as_deref(), Some("4f9c2a1e-7b3d"));
+    }
+
     #[test]
     fn path_contains_subagents_detects_subagents() {
         let path = Path::new("/home/user/.claude/projects/foo/subagents/session.jsonl");
@@ -177,11 +209,27 @@ mod tests {
     }
 
     #[test]
-    fn find_recent_in_returns_none_for_empty_dir() {
+    fn find_recent_in_reports_empty_dir() {
         let dir = TempDir::new().unwrap();
         let config = Config::default();
         let now = SystemTime::now();
-        assert!(find_recent_in(&config, now, dir.path()).is_none());
+        assert!(matches!(
+            find_recent_in(&config, now, dir.path()),
+            Err(IdrError::SessionNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn find_recent_in_names_missing_projects_dir() {
+        let dir = TempDir::new().unwrap();
+        let missing = dir.path().join("projects");
+
+        let err = find_recent_in(&Config::default(), SystemTime::now(), &missing).unwrap_err();
+
+        assert!(
+            matches!(&err, IdrError::SessionNotFound(reason) if reason.contains("does not exist")),
+            "{err:?}"
+        );
     }
 
     #[test]
@@ -193,7 +241,7 @@ mod tests {
 
         let config = Config::default();
         let result = find_recent_in(&config, SystemTime::now(), dir.path());
-        assert_eq!(result, Some(newer));
+        assert_eq!(result.unwrap(), newer);
     }
 
     #[test]
@@ -204,7 +252,7 @@ mod tests {
 
         let config = Config::default();
         let result = find_recent_in(&config, SystemTime::now(), dir.path());
-        assert_eq!(result, Some(main));
+        assert_eq!(result.unwrap(), main);
     }
 
     #[test]
@@ -212,9 +260,14 @@ mod tests {
         let dir = TempDir::new().unwrap();
         write_jsonl(dir.path(), "session.jsonl", &[r#"{"a":1}"#]);
 
-        let mut config = Config::default();
-        config.session_max_age_min = 0; // 0 min = everything is too old
+        let config = Config {
+            session_max_age_min: 0, // 0 min = everything is too old
+            ..Config::default()
+        };
         let future = SystemTime::now() + std::time::Duration::from_secs(120);
-        assert!(find_recent_in(&config, future, dir.path()).is_none());
+        assert!(matches!(
+            find_recent_in(&config, future, dir.path()),
+            Err(IdrError::SessionNotFound(_))
+        ));
     }
 }
@@ -0,0 +1,615 @@
+//! `claude-idr config get|set|unset`: one key of the config file at a time.
+//! The file is edited as text, so keys this version does not know, the
+//! order of the others, and their layout all survive a change.
+
+use crate::config::{self, CLAUDE_BIN_ENV, Config};
+use crate::session::PROJECTS_DIR_ENV;
+use serde_json::Value;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Keys whose values are paths, where a leading `~` is expanded on `set`.
+const PATH_KEYS: &[&str] = &["output_dir", "workspace_dir", "claude_bin", "projects_dir"];
+
+/// Where an effective value comes from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    Default,
+    File(PathBuf),
+    Env(&'static str),
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Source::Default => write!(f, "default"),
+            Source::File(path) => write!(f, "{}", path.display()),
+            Source::Env(variable) => write!(f, "{variable}"),
+        }
+    }
+}
+
+/// The file `--config` names, or the default one.
+pub fn path(explicit: Option<&Path>) -> Result<PathBuf, String> {
+    explicit
+        .map(Path::to_path_buf)
+        .or_else(Config::default_path)
+        .ok_or_else(|| "no config directory; pass --config <PATH>".to_string())
+}
+
+/// Every key the config file takes, sorted.
+pub fn keys() -> Vec<String> {
+    match defaults() {
+        Value::Object(map) => map.keys().cloned().collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn defaults() -> Value {
+    serde_json::to_value(Config::default()).expect("Config serializes")
+}
+
+/// The default value of `key`, or an error naming it when there is no such
+/// key.
+fn default_of(key: &str) -> Result<Value, String> {
+    defaults()
+        .get(key)
+        .cloned()
+        .ok_or_else(|| format!("unknown config key `{key}`"))
+}
+
+/// Environment variables that take the place of a key's value.
+const ENV_OVERRIDES: &[(&str, &str)] = &[
+    ("claude_bin", CLAUDE_BIN_ENV),
+    ("projects_dir", PROJECTS_DIR_ENV),
+];
+
+/// The effective value of `key` with the config file at `path`, and where
+/// it comes from.
+pub fn get(key: &str, path: &Path) -> Result<(Value, Source), String> {
+    default_of(key)?;
+    let override_env = ENV_OVERRIDES
+        .iter()
+        .filter(|(name, _)| *name == key)
+        .find_map(|&(_, variable)| {
+            let value = std::env::var(variable).ok().filter(|v| !v.is_empty())?;
+            Some((value, variable))
+        });
+    let config = Config::try_load(Some(path)).map_err(|e| e.to_string())?;
+    if let Some((value, variable)) = override_env {
+        return Ok((Value::String(value), Source::Env(variable)));
+    }
+    let value = serde_json::to_value(&config)
+        .ok()
+        .and_then(|all| all.get(key).cloned())
+        .unwrap_or(Value::Null);
+    let in_file = read(path)?.is_some_and(|text| {
+        serde_json::from_str::<Value>(&text).is_ok_and(|file| file.get(key).is_some())
+    });
+    let source = if in_file {
+        Source::File(path.to_path_buf())
+    } else {
+        Source::Default
+    };
+    Ok((value, source))
+}
+
+/// `value` as `get` prints it: strings bare, everything else as JSON.
+pub fn display(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Sets `key` to `raw` in the config file at `path`, creating the file
+/// when it does not exist yet. Returns the value written.
+pub fn set(key: &str, raw: &str, path: &Path) -> Result<Value, String> {
+    let value = parse_value(key, raw)?;
+    let text = read(path)?.unwrap_or_else(|| "{}\n".to_string());
+    let updated =
+        with_member(&text, key, &value).map_err(|e| format!("{}: {e}", path.display()))?;
+    write(path, &updated)?;
+    Ok(value)
+}
+
+/// Removes `key` from the config file at `path`, so its default applies
+/// again. Returns whether the file had it.
+pub fn unset(key: &str, path: &Path) -> Result<bool, String> {
+    default_of(key)?;
+    let Some(text) = read(path)? else {
+        return Ok(false);
+    };
+    match without_member(&text, key).map_err(|e| format!("{}: {e}", path.display()))? {
+        Some(updated) => write(path, &updated).map(|()| true),
+        None => Ok(false),
+    }
+}
+
+fn read(path: &Path) -> Result<Option<String>, String> {
+    match fs::read_to_string(path) {
+        Ok(text) => Ok(Some(text)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("cannot read {}: {e}", path.display())),
+    }
+}
+
+fn write(path: &Path, text: &str) -> Result<(), String> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).map_err(|e| format!("cannot create {}: {e}", dir.display()))?;
+    }
+    fs::write(path, text).map_err(|e| format!("cannot write {}: {e}", path.display()))
+}
+
+/// `raw` as the JSON value `key` takes, judged by the type of its default:
+/// `true`/`false` (also `yes`/`no`, `on`/`off`) for booleans, numbers for
+/// numbers, a JSON array or a comma-separated list for lists, a JSON object
+/// for objects. Keys unset by default take JSON when `raw` is JSON of the
+/// right type and a string otherwise. Paths get a leading `~` expanded.
+pub fn parse_value(key: &str, raw: &str) -> Result<Value, String> {
+    let default = default_of(key)?;
+    let raw = raw.trim();
+    let candidates: Vec<Value> = match &default {
+        Value::Bool(_) => match raw.to_ascii_lowercase().as_str() {
+            "true" | "yes" | "on" => vec![Value::Bool(true)],
+            "false" | "no" | "off" => vec![Value::Bool(false)],
+            _ => return Err(format!("`{key}` takes true or false, not `{raw}`")),
+        },
+        Value::Number(_) => match serde_json::from_str::<Value>(raw) {
+            Ok(number @ Value::Number(_)) => vec![number],
+            _ => return Err(format!("`{key}` takes a number, not `{raw}`")),
+        },
+        Value::Array(_) if raw.starts_with('[') => vec![
+            serde_json::from_str(raw).map_err(|e| format!("`{key}`: invalid JSON list: {e}"))?,
+        ],
+        Value::Array(_) => vec![Value::Array(
+            raw.split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(|item| Value::String(item.to_string()))
+                .collect(),
+        )],
+        Value::Object(_) => vec![
+            serde_json::from_str(raw).map_err(|e| format!("`{key}`: invalid JSON object: {e}"))?,
+        ],
+        Value::String(_) => vec![string_value(key, raw)],
+        Value::Null => serde_json::from_str::<Value>(raw)
+            .ok()
+            .filter(|json| !json.is_string() && !json.is_null())
+            .into_iter()
+            .chain([string_value(key, raw)])
+            .collect(),
+    };
+    let mut error = None;
+    for candidate in candidates {
+        let alone = serde_json::json!({ key: candidate.clone() });
+        match serde_json::from_value::<Config>(alone) {
+            Ok(_) => return Ok(candidate),
+            Err(e) => {
+                error.get_or_insert(e);
+            }
+        }
+    }
+    Err(match error {
+        Some(e) => format!("invalid value for `{key}`: {e}"),
+        None => format!("invalid value for `{key}`"),
+    })
+}
+
+fn string_value(key: &str, raw: &str) -> Value {
+    let text = if PATH_KEYS.contains(&key) {
+        config::expand_home(Path::new(raw))
+            .to_string_lossy()
+            .into_owned()
+    } else {
+        raw.to_string()
+    };
+    Value::String(text)
+}
+
+/// One `"key": value` member of the top-level object, as byte offsets into
+/// the text.
+#[derive(Debug)]
+struct Member {
+    key: String,
+    /// The opening quote of the key.
+    start: usize,
+    /// Just past the value.
+    end: usize,
+    value_start: usize,
+}
+
+/// The members of the top-level object in `text` and the offset of its
+/// closing brace.
+fn members(text: &str) -> Result<(Vec<Member>, usize), String> {
+    let parsed: Value = serde_json::from_str(text).map_err(|e| format!("invalid JSON: {e}"))?;
+    if !parsed.is_object() {
+        return Err("not a JSON object".to_string());
+    }
+    // Valid JSON from here on, so the scan below can be simple.
+    let bytes = text.as_bytes();
+    let skip_space = |mut i: usize| {
+        while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        i
+    };
+    let mut i = skip_space(0) + 1;
+    let mut found = Vec::new();
+    loop {
+        i = skip_space(i);
+        match bytes[i] {
+            b'}' => return Ok((found, i)),
+            b',' => {
+                i += 1;
+                continue;
+            }
+            _ => {}
+        }
+        let start = i;
+        let key_end = string_end(bytes, i);
+        let key: String =
+            serde_json::from_str(&text[start..key_end]).map_err(|e| format!("invalid key: {e}"))?;
+        i = skip_space(key_end) + 1;
+        let value_start = skip_space(i);
+        let end = value_end(bytes, value_start);
+        found.push(Member {
+            key,
+            start,
+            end,
+            value_start,
+        });
+        i = end;
+    }
+}
+
+/// Just past the string starting at `start`.
+fn string_end(bytes: &[u8], start: usize) -> usize {
+    let mut i = start + 1;
+    while bytes[i] != b'"' {
+        i += if bytes[i] == b'\\' { 2 } else { 1 };
+    }
+    i + 1
+}
+
+/// Just past the value starting at `start`.
+fn value_end(bytes: &[u8], start: usize) -> usize {
+    match bytes[start] {
+        b'"' => string_end(bytes, start),
+        b'{' | b'[' => {
+            let mut depth = 0;
+            let mut i = start;
+            loop {
+                match bytes[i] {
+                    b'"' => {
+                        i = string_end(bytes, i);
+                        continue;
+                    }
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return i + 1;
+                        }
+                    }
+                    _ => {}
+                }
+                i += 1;
+            }
+        }
+        _ => {
+            let mut i = start;
+            while i < bytes.len() && !matches!(bytes[i], b',' | b'}' | b']') {
+                i += 1;
+            }
+            // Back over whitespace before the delimiter.
+            while bytes[i - 1].is_ascii_whitespace() {
+                i -= 1;
+            }
+            i
+        }
+    }
+}
+
+/// `value` as it goes into the file: compact, except that lists and
+/// objects get a space after each comma and colon.
+fn render(value: &Value) -> String {
+    match value {
+        Value::Array(items) => {
+            let items: Vec<String> = items.iter().map(render).collect();
+            format!("[{}]", items.join(", "))
+        }
+        Value::Object(map) => {
+            let members: Vec<String> = map
+                .iter()
+                .map(|(k, v)| format!("{}: {}", Value::String(k.clone()), render(v)))
+                .collect();
+            format!("{{{}}}", members.join(", "))
+        }
+        other => other.to_string(),
+    }
+}
+
+/// `text` with `key` set to `value`: its value replaced where the key is
+/// already there, otherwise a new member after the last one, laid out like
+/// it.
+pub fn with_member(text: &str, key: &str, value: &Value) -> Result<String, String> {
+    let (found, close) = members(text)?;
+    let rendered = render(value);
+    if let Some(member) = found.iter().rev().find(|m| m.key == key) {
+        return Ok(format!(
+            "{}{rendered}{}",
+            &text[..member.value_start],
+            &text[member.end..]
+        ));
+    }
+    let quoted = Value::String(key.to_string());
+    let Some(last) = found.last() else {
+        let open = text.find('{').unwrap_or(0);
+        return Ok(format!(
+            "{}{{\n  {quoted}: {rendered}\n}}{}",
+            &text[..open],
+            &text[close + 1..]
+        ));
+    };
+    // Same indentation and separator as the member before.
+    let line_start = text[..last.start].rfind('\n').map_or(0, |i| i + 1);
+    let indent = &text[line_start..last.start];
+    let indent = if indent.trim().is_empty() { indent } else { "" };
+    let key_end = string_end(text.as_bytes(), last.start);
+    let separator = &text[key_end..last.value_start];
+    let newline = if text[last.end..close].contains('\n') || text[..last.start].contains('\n') {
+        "\n"
+    } else {
+        " "
+    };
+    Ok(format!(
+        "{},{newline}{indent}{quoted}{separator}{rendered}{}",
+        &text[..last.end],
+        &text[last.end..]
+    ))
+}
+
+/// `text` without `key`, or `None` when it does not have it.
+pub fn without_member(text: &str, key: &str) -> Result<Option<String>, String> {
+    let (found, close) = members(text)?;
+    let Some(index) = found.iter().position(|m| m.key == key) else {
+        return Ok(None);
+    };
+    let member = &found[index];
+    let (from, to) = match (index.checked_sub(1), found.get(index + 1)) {
+        // Up to the next key, taking the comma along; whole lines when
+        // each sits on its own.
+        (_, Some(next)) => match (own_line(text, member.start), own_line(text, next.start)) {
+            (Some(from), Some(to)) => (from, to),
+            _ => (member.start, next.start),
+        },
+        // The last one: from the end of the one before, taking its comma.
+        (Some(previous), None) => (found[previous].end, member.end),
+        // The only one.
+        (None, None) => {
+            let open = text[..member.start].rfind('{').unwrap_or(0);
+            return Ok(Some(format!("{}{{}}{}", &text[..open], &text[close + 1..])));
+        }
+    };
+    Ok(Some(format!("{}{}", &text[..from], &text[to..])))
+}
+
+/// The start of the line `offset` is on, when only whitespace comes before
+/// it there.
+fn own_line(text: &str, offset: usize) -> Option<usize> {
+    let line_start = text[..offset].rfind('\n')? + 1;
+    text[line_start..offset]
+        .trim()
+        .is_empty()
+        .then_some(line_start)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const FILE: &str = r#"{
+  "model": "opus",
+  "my_note": "kept as is",
+  "claude_args": ["--verbose"],
+  "section_headings": {"summary": "Overview", "risks": null},
+    "notify":true
+}
+"#;
+
+    #[test]
+    fn keys_list_every_config_field() {
+        let keys = keys();
+        assert!(keys.contains(&"model".to_string()));
+        assert!(keys.contains(&"prompt_context_lines".to_string()));
+        assert!(!keys.contains(&"my_note".to_string()));
+    }
+
+    #[test]
+    fn parse_value_follows_the_type_of_the_default() {
+        assert_eq!(parse_value("notify", "yes"), Ok(Value::Bool(true)));
+        assert_eq!(
+            parse_value("max_diff_lines", "800"),
+            Ok(serde_json::json!(800))
+        );
+        assert_eq!(
+            parse_value("binary_density", "0.5"),
+            Ok(serde_json::json!(0.5))
+        );
+        assert_eq!(parse_value("model", "opus"), Ok(serde_json::json!("opus")));
+        assert_eq!(
+            parse_value("redact_paths", ".env*, *.pem"),
+            Ok(serde_json::json!([".env*", "*.pem"]))
+        );
+        assert_eq!(
+            parse_value("claude_args", r#"["--max-turns", "3"]"#),
+            Ok(serde_json::json!(["--max-turns", "3"]))
+        );
+        assert_eq!(
+            parse_value("prompt_context_lines", "0"),
+            Ok(serde_json::json!(0))
+        );
+        assert_eq!(
+            parse_value("translation_model", "123"),
+            Ok(serde_json::json!("123"))
+        );
+        assert_eq!(
+            parse_value("output_mode", "monthly"),
+            Ok(serde_json::json!("monthly"))
+        );
+    }
+
+    #[test]
+    fn parse_value_rejects_unknown_keys_and_wrong_types() {
+        assert_eq!(
+            parse_value("modle", "opus"),
+            Err("unknown config key `modle`".to_string())
+        );
+        assert!(
+            parse_value("notify", "maybe")
+                .unwrap_err()
+                .contains("true or false")
+        );
+        assert!(
+            parse_value("max_diff_lines", "many")
+                .unwrap_err()
+                .contains("a number")
+        );
+        assert!(
+            parse_value("max_diff_lines", "-3")
+                .unwrap_err()
+                .contains("invalid value")
+        );
+        assert!(
+            parse_value("output_mode", "weekly")
+                .unwrap_err()
+                .contains("invalid value")
+        );
+        assert!(
+            parse_value("prompt_context_lines", "few")
+                .unwrap_err()
+                .contains("invalid value")
+        );
+    }
+
+    #[test]
+    fn parse_value_expands_home_in_paths() {
+        let Some(home) = config::home_dir() else {
+            return;
+        };
+        assert_eq!(
+            parse_value("output_dir", "~/notes/idr"),
+            Ok(Value::String(home.join("notes/idr").display().to_string()))
+        );
+        assert_eq!(
+            parse_value("model", "~sonnet"),
+            Ok(Value::String("~sonnet".to_string()))
+        );
+    }
+
+    #[test]
+    fn with_member_replaces_a_value_in_place() {
+        let updated = with_member(FILE, "model", &serde_json::json!("haiku")).unwrap();
+        assert_eq!(updated, FILE.replace(r#""opus""#, r#""haiku""#));
+
+        let updated = with_member(FILE, "notify", &Value::Bool(false)).unwrap();
+        assert_eq!(
+            updated,
+            FILE.replace(r#""notify":true"#, r#""notify":false"#)
+        );
+
+        let updated = with_member(FILE, "claude_args", &serde_json::json!(["-a", "b"])).unwrap();
+        assert_eq!(updated, FILE.replace(r#"["--verbose"]"#, r#"["-a", "b"]"#));
+    }
+
+    #[test]
+    fn with_member_appends_a_new_key_like_the_last_one() {
+        let updated = with_member(FILE, "max_diff_lines", &serde_json::json!(800)).unwrap();
+        assert_eq!(
+            updated,
+            FILE.replace(
+                "    \"notify\":true\n",
+                "    \"notify\":true,\n    \"max_diff_lines\":800\n"
+            )
+        );
+
+        assert_eq!(
+            with_member("{\"a\": 1}", "model", &serde_json::json!("x")).unwrap(),
+            "{\"a\": 1, \"model\": \"x\"}"
+        );
+        assert_eq!(
+            with_member("{}\n", "model", &serde_json::json!("x")).unwrap(),
+            "{\n  \"model\": \"x\"\n}\n"
+        );
+        assert!(with_member("[1]", "model", &Value::Null).is_err());
+        assert!(with_member("{\"a\": ", "model", &Value::Null).is_err());
+    }
+
+    #[test]
+    fn without_member_removes_only_that_key() {
+        let first = without_member(FILE, "model").unwrap().unwrap();
+        assert_eq!(first, FILE.replace("  \"model\": \"opus\",\n", ""));
+
+        let last = without_member(FILE, "notify").unwrap().unwrap();
+        assert_eq!(last, FILE.replace(",\n    \"notify\":true", ""));
+
+        let nested = without_member(FILE, "section_headings").unwrap().unwrap();
+        assert!(!nested.contains("Overview"), "{nested}");
+        assert!(
+            nested.contains("\"claude_args\": [\"--verbose\"],\n    \"notify\""),
+            "{nested}"
+        );
+
+        assert_eq!(without_member(FILE, "missing").unwrap(), None);
+        assert_eq!(
+            without_member("{\"model\": \"x\"}\n", "model").unwrap(),
+            Some("{}\n".to_string())
+        );
+        for edited in [first, last, nested] {
+            serde_json::from_str::<Value>(&edited).unwrap();
+        }
+    }
+
+    #[test]
+    fn set_get_unset_round_trip_through_the_file() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("nested/config.json");
+
+        assert_eq!(
+            get("model", &path).unwrap(),
+            (serde_json::json!("sonnet"), Source::Default)
+        );
+        set("model", "opus", &path).unwrap();
+        set("notify", "true", &path).unwrap();
+        fs::write(
+            &path,
+            fs::read_to_string(&path)
+                .unwrap()
+                .replace("{\n", "{\n  \"my_note\": \"hand-written\",\n"),
+        )
+        .unwrap();
+        set("model", "haiku", &path).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "{\n  \"my_note\": \"hand-written\",\n  \"model\": \"haiku\",\n  \"notify\": true\n}\n"
+        );
+        assert_eq!(
+            get("model", &path).unwrap(),
+            (serde_json::json!("haiku"), Source::File(path.clone()))
+        );
+
+        assert!(unset("model", &path).unwrap());
+        assert!(!unset("model", &path).unwrap());
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "{\n  \"my_note\": \"hand-written\",\n  \"notify\": true\n}\n"
+        );
+        assert_eq!(get("model", &path).unwrap().1, Source::Default);
+        assert!(get("modle", &path).is_err());
+        assert!(unset("modle", &path).is_err());
+    }
+}
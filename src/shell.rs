@@ -0,0 +1,81 @@
+//! Commands from the config (`post_generate_command`,
+//! `diff_filter_command`), run by the platform's shell under a time limit.
+
+use std::io;
+use std::process::{Child, Command, ExitStatus};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often a running command is checked on.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// `shell_command` run by `sh -c`, or `cmd /C` on Windows, in a process
+/// group of its own so [`wait`] can stop everything it starts.
+pub fn command(shell_command: &str) -> Command {
+    let mut command = if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.args(["/C", shell_command]);
+        command
+    } else {
+        let mut command = Command::new("sh");
+        command.args(["-c", shell_command]);
+        command
+    };
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+    command
+}
+
+/// Waits up to `timeout` for `child`. `None` means it was still running
+/// and has been killed, along with its process group.
+pub fn wait(child: &mut Child, timeout: Duration) -> io::Result<Option<ExitStatus>> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if Instant::now() >= deadline {
+            #[cfg(unix)]
+            // SAFETY: kill(2) on the group `command` put the child in.
+            unsafe {
+                libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+            }
+            let _ = child.kill();
+            let _ = child.wait();
+            return Ok(None);
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_runs_through_the_shell() {
+        let command = command("echo hi && exit 3");
+        let args: Vec<_> = command.get_args().collect();
+
+        if cfg!(windows) {
+            assert_eq!(command.get_program(), "cmd");
+            assert_eq!(args, ["/C", "echo hi && exit 3"]);
+        } else {
+            assert_eq!(command.get_program(), "sh");
+            assert_eq!(args, ["-c", "echo hi && exit 3"]);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn wait_returns_the_status_or_kills_on_timeout() {
+        let mut quick = command("exit 3").spawn().unwrap();
+        let status = wait(&mut quick, Duration::from_secs(5)).unwrap().unwrap();
+        assert_eq!(status.code(), Some(3));
+
+        let started = Instant::now();
+        let mut slow = command("sleep 30").spawn().unwrap();
+        assert!(wait(&mut slow, Duration::ZERO).unwrap().is_none());
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,165 @@
+//! `claude-idr show`: finding one record in the output directory by number.
+
+use crate::config::Format;
+use crate::latest;
+use crate::path;
+use std::path::{Path, PathBuf};
+
+/// Which record to show.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Target {
+    Number(u32),
+    /// The record `latest.md` points at, or the highest-numbered one.
+    Latest,
+}
+
+impl Target {
+    /// `7`, `07`, `idr-07`, or `latest`.
+    pub fn parse(text: &str) -> Result<Target, String> {
+        if text == "latest" {
+            return Ok(Target::Latest);
+        }
+        text.strip_prefix("idr-")
+            .unwrap_or(text)
+            .parse()
+            .map(Target::Number)
+            .map_err(|_| format!("expected a record number or `latest`, got `{text}`"))
+    }
+}
+
+/// The record in `dir` that `target` names. A missing number is reported
+/// with the nearest numbers that do exist.
+pub fn find(dir: &Path, target: Target, format: Format) -> Result<PathBuf, String> {
+    let records = path::list_records(dir, format);
+    let number = match target {
+        Target::Latest => {
+            return latest::target(dir)
+                .or_else(|| records.last().map(|(_, file)| file.clone()))
+                .ok_or_else(|| format!("no records in {}", dir.display()));
+        }
+        Target::Number(number) => number,
+    };
+    if let Some((_, file)) = records.iter().find(|(n, _)| *n == number) {
+        return Ok(file.clone());
+    }
+    if records.is_empty() {
+        return Err(format!("no records in {}", dir.display()));
+    }
+    let nearest: Vec<String> = nearest(&records, number)
+        .iter()
+        .map(u32::to_string)
+        .collect();
+    Err(format!(
+        "no record {number} in {} (nearest: {})",
+        dir.display(),
+        nearest.join(", ")
+    ))
+}
+
+/// The closest existing numbers below and above `number`.
+fn nearest(records: &[(u32, PathBuf)], number: u32) -> Vec<u32> {
+    let below = records.iter().rev().find(|(n, _)| *n < number);
+    let above = records.iter().find(|(n, _)| *n > number);
+    below.into_iter().chain(above).map(|(n, _)| *n).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, LatestMode};
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn write(dir: &Path, names: &[&str]) {
+        fs::create_dir_all(dir).unwrap();
+        for name in names {
+            fs::write(dir.join(name), format!("# {name}\n")).unwrap();
+        }
+    }
+
+    fn found(dir: &Path, target: Target) -> String {
+        let file = find(dir, target, Format::Idr).unwrap();
+        file.file_name().unwrap().to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn target_accepts_numbers_prefixes_and_latest() {
+        assert_eq!(Target::parse("7"), Ok(Target::Number(7)));
+        assert_eq!(Target::parse("07"), Ok(Target::Number(7)));
+        assert_eq!(Target::parse("idr-007"), Ok(Target::Number(7)));
+        assert_eq!(Target::parse("latest"), Ok(Target::Latest));
+        assert!(Target::parse("seven").is_err());
+    }
+
+    #[test]
+    fn find_matches_padded_and_slugged_names() {
+        let dir = TempDir::new().unwrap();
+        write(
+            dir.path(),
+            &["idr-07-use-jwt.md", "idr-100.md", "latest.md"],
+        );
+
+        assert_eq!(found(dir.path(), Target::Number(7)), "idr-07-use-jwt.md");
+        assert_eq!(found(dir.path(), Target::Number(100)), "idr-100.md");
+    }
+
+    #[test]
+    fn find_resolves_date_sow_and_fixed_directories() {
+        let ws = TempDir::new().unwrap();
+        let config = Config {
+            workspace_dir: ws.path().to_path_buf(),
+            ..Config::default()
+        };
+        let date_dir = path::planned_dir(&config, "2026-02-07", None);
+        assert_eq!(date_dir, ws.path().join("planning/2026-02-07"));
+        write(&date_dir, &["idr-07-by-date.md"]);
+        assert_eq!(found(&date_dir, Target::Number(7)), "idr-07-by-date.md");
+
+        let sow_dir = ws.path().join("sow/project-x");
+        write(&sow_dir, &["sow.md", "idr-07-in-sow.md"]);
+        fs::write(
+            ws.path().join(".current-sow"),
+            sow_dir.join("sow.md").to_str().unwrap(),
+        )
+        .unwrap();
+        let dir = path::planned_dir(&config, "2026-02-07", None);
+        assert_eq!(dir, fs::canonicalize(&sow_dir).unwrap());
+        assert_eq!(found(&dir, Target::Number(7)), "idr-07-in-sow.md");
+
+        let fixed = ws.path().join("fixed");
+        write(&fixed, &["idr-07-fixed.md"]);
+        let config = Config {
+            output_dir: Some(fixed.clone()),
+            ..config
+        };
+        let dir = path::planned_dir(&config, "2026-02-07", None);
+        assert_eq!(dir, fixed);
+        assert_eq!(found(&dir, Target::Number(7)), "idr-07-fixed.md");
+    }
+
+    #[test]
+    fn find_latest_prefers_latest_md_then_highest_number() {
+        let dir = TempDir::new().unwrap();
+        write(dir.path(), &["idr-01-a.md", "idr-03-c.md"]);
+        assert_eq!(found(dir.path(), Target::Latest), "idr-03-c.md");
+
+        let first = dir.path().join("idr-01-a.md");
+        latest::update(dir.path(), &first, LatestMode::Copy).unwrap();
+        assert_eq!(found(dir.path(), Target::Latest), "idr-01-a.md");
+    }
+
+    #[test]
+    fn find_hints_nearest_numbers_when_missing() {
+        let dir = TempDir::new().unwrap();
+        write(dir.path(), &["idr-02.md", "idr-05.md", "idr-09.md"]);
+
+        let err = find(dir.path(), Target::Number(7), Format::Idr).unwrap_err();
+        assert!(err.ends_with("(nearest: 5, 9)"), "{err}");
+        let err = find(dir.path(), Target::Number(12), Format::Idr).unwrap_err();
+        assert!(err.ends_with("(nearest: 9)"), "{err}");
+
+        let empty = TempDir::new().unwrap();
+        let err = find(empty.path(), Target::Latest, Format::Idr).unwrap_err();
+        assert!(err.starts_with("no records in"), "{err}");
+    }
+}
@@ -0,0 +1,287 @@
+//! `idr-NN.meta.json` next to each record: the inputs that produced it, for
+//! tools that need more than the metadata comment in the record itself.
+
+use crate::authorship::Authorship;
+use crate::claude::Usage;
+use crate::config::Format;
+use crate::effort::Effort;
+use crate::log::warn;
+use crate::path;
+use crate::sha256;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub const SCHEMA_VERSION: u32 = 1;
+const EXTENSION: &str = "meta.json";
+
+/// Where the diff a record describes came from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum DiffSource {
+    Staged,
+    Range {
+        range: String,
+    },
+    Commit {
+        sha: String,
+    },
+    /// No diff: a session report (`--from-session`).
+    Session,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Sidecar {
+    pub schema: u32,
+    pub diff_source: DiffSource,
+    /// Hex SHA-256 of the diff text sent to the model (see [`diff_hash`]).
+    pub diff_sha256: String,
+    pub session_path: Option<PathBuf>,
+    pub session_id: Option<String>,
+    /// Model of the purpose call; `None` when there was no session context.
+    pub purpose_model: Option<String>,
+    /// Model that wrote the body, after any fallbacks.
+    pub body_model: String,
+    pub started: String,
+    pub generated: String,
+    #[serde(default)]
+    pub usage: Option<Usage>,
+    /// Issue ids from the branch name and user requests.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub issues: Vec<String>,
+    /// feat, fix, refactor, docs, test, or chore; `None` for records from
+    /// before classification.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub change_type: Option<String>,
+    /// Filled in by `--post-commit` once the commit exists.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<CommitRef>,
+    /// Why claude could not write the body, for a record holding the
+    /// failure placeholder; cleared by `regenerate --failed`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure: Option<String>,
+    /// What the title was made from (see `title::TitleSource`); `None` for
+    /// records from before the fallbacks.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title_source: Option<String>,
+    /// The diff's files split by whether the session edited them; `None`
+    /// without session context.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub authorship: Option<Authorship>,
+    /// Session duration, user turns, and tool calls; `None` without
+    /// session context.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub effort: Option<Effort>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommitRef {
+    pub sha: String,
+    pub subject: String,
+}
+
+/// Fingerprint of a diff for [`Sidecar::diff_sha256`].
+pub fn diff_hash(diff: &str) -> String {
+    sha256::hex(diff.as_bytes())
+}
+
+/// `idr-07-use-jwt.md` -> `idr-07-use-jwt.meta.json`.
+pub fn path_for(record: &Path) -> PathBuf {
+    record.with_extension(EXTENSION)
+}
+
+/// Whether `name` is a sidecar file name.
+pub fn is_sidecar_name(name: &str) -> bool {
+    name.ends_with(&format!(".{EXTENSION}"))
+}
+
+/// Writes the sidecar for `record`.
+pub fn write(record: &Path, sidecar: &Sidecar) -> io::Result<()> {
+    let json = serde_json::to_string_pretty(sidecar).map_err(io::Error::other)?;
+    fs::write(path_for(record), json + "\n")
+}
+
+/// Like [`write`], but only warns: a missing sidecar never fails a run.
+pub fn write_warn(record: &Path, sidecar: &Sidecar) {
+    if let Err(e) = write(record, sidecar) {
+        warn!("cannot write {}: {e}", path_for(record).display());
+    }
+}
+
+/// The sidecar of `record`, if it has a readable one. Records written
+/// before sidecars existed simply have none.
+pub fn load_metadata(record: &Path) -> Option<Sidecar> {
+    let path = path_for(record);
+    let text = fs::read_to_string(&path).ok()?;
+    match serde_json::from_str(&text) {
+        Ok(sidecar) => Some(sidecar),
+        Err(e) => {
+            warn!("ignoring malformed {}: {e}", path.display());
+            None
+        }
+    }
+}
+
+/// The record in `dir` whose sidecar shows it was generated from exactly
+/// `diff` in `session_path`, if any (newest first).
+pub fn find_duplicate(
+    dir: &Path,
+    format: Format,
+    diff: &str,
+    session_path: &Path,
+) -> Option<PathBuf> {
+    let hash = diff_hash(diff);
+    path::list_records(dir, format)
+        .into_iter()
+        .rev()
+        .map(|(_, record)| record)
+        .find(|record| {
+            load_metadata(record).is_some_and(|s| {
+                s.diff_sha256 == hash && s.session_path.as_deref() == Some(session_path)
+            })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn sample() -> Sidecar {
+        Sidecar {
+            schema: SCHEMA_VERSION,
+            diff_source: DiffSource::Staged,
+            diff_sha256: diff_hash("diff --git a/x b/x\n"),
+            session_path: Some(PathBuf::from("/home/u/.claude/projects/p/s.jsonl")),
+            session_id: Some("5f0c3a2e".to_string()),
+            purpose_model: Some("haiku".to_string()),
+            body_model: "sonnet".to_string(),
+            started: "2026-02-07T05:29:40Z".to_string(),
+            generated: "2026-02-07T05:30:00Z".to_string(),
+            usage: Some(Usage {
+                input_tokens: 1200,
+                output_tokens: 300,
+            }),
+            issues: vec!["PROJ-1234".to_string()],
+            change_type: Some("feat".to_string()),
+            commit: None,
+            failure: None,
+            title_source: None,
+            authorship: None,
+            effort: None,
+        }
+    }
+
+    #[test]
+    fn sidecar_round_trips_through_file() {
+        let dir = TempDir::new().unwrap();
+        let record = dir.path().join("idr-07-use-jwt.md");
+        fs::write(&record, "# IDR: Use JWT\n").unwrap();
+
+        write(&record, &sample()).unwrap();
+
+        assert!(dir.path().join("idr-07-use-jwt.meta.json").exists());
+        assert_eq!(load_metadata(&record), Some(sample()));
+    }
+
+    #[test]
+    fn diff_source_serializes_with_kind_tag() {
+        let commit = DiffSource::Commit {
+            sha: "abc1234".to_string(),
+        };
+
+        let json = serde_json::to_string(&commit).unwrap();
+
+        assert_eq!(json, r#"{"kind":"commit","sha":"abc1234"}"#);
+        assert_eq!(serde_json::from_str::<DiffSource>(&json).unwrap(), commit);
+        assert_eq!(
+            serde_json::to_string(&DiffSource::Staged).unwrap(),
+            r#"{"kind":"staged"}"#
+        );
+    }
+
+    #[test]
+    fn load_metadata_tolerates_missing_and_malformed_sidecars() {
+        let dir = TempDir::new().unwrap();
+        let legacy = dir.path().join("idr-01.md");
+        fs::write(&legacy, "# IDR: old\n").unwrap();
+        assert_eq!(load_metadata(&legacy), None);
+
+        fs::write(path_for(&legacy), "{not json").unwrap();
+        assert_eq!(load_metadata(&legacy), None);
+    }
+
+    #[test]
+    fn load_metadata_accepts_sidecar_without_usage() {
+        let dir = TempDir::new().unwrap();
+        let record = dir.path().join("idr-02.md");
+        let mut json = serde_json::to_value(sample()).unwrap();
+        json.as_object_mut().unwrap().remove("usage");
+        fs::write(path_for(&record), json.to_string()).unwrap();
+
+        assert_eq!(load_metadata(&record).unwrap().usage, None);
+    }
+
+    fn record_with_sidecar(dir: &Path, name: &str, diff: &str, session: &str) -> PathBuf {
+        let record = dir.join(name);
+        fs::write(&record, "# IDR: x\n").unwrap();
+        let sidecar = Sidecar {
+            diff_sha256: diff_hash(diff),
+            session_path: Some(PathBuf::from(session)),
+            ..sample()
+        };
+        write(&record, &sidecar).unwrap();
+        record
+    }
+
+    #[test]
+    fn find_duplicate_matches_diff_and_session() {
+        let dir = TempDir::new().unwrap();
+        let diff = "diff --git a/x b/x\n+one\n";
+        fs::write(dir.path().join("idr-01.md"), "# IDR: legacy\n").unwrap();
+        let documented = record_with_sidecar(dir.path(), "idr-02.md", diff, "/s/a.jsonl");
+        record_with_sidecar(dir.path(), "idr-03.md", "other", "/s/a.jsonl");
+
+        assert_eq!(
+            find_duplicate(dir.path(), Format::Idr, diff, Path::new("/s/a.jsonl")),
+            Some(documented)
+        );
+        assert_eq!(
+            find_duplicate(dir.path(), Format::Idr, diff, Path::new("/s/b.jsonl")),
+            None,
+            "same diff from another session is a new record"
+        );
+    }
+
+    #[test]
+    fn find_duplicate_ignores_diff_with_same_stat() {
+        let dir = TempDir::new().unwrap();
+        // Same file, same line counts, so `git diff --stat` is identical.
+        let first = "diff --git a/x b/x\n-a\n+b\n";
+        let second = "diff --git a/x b/x\n-a\n+c\n";
+        record_with_sidecar(dir.path(), "idr-01.md", first, "/s/a.jsonl");
+
+        assert_eq!(
+            find_duplicate(dir.path(), Format::Idr, second, Path::new("/s/a.jsonl")),
+            None
+        );
+    }
+
+    #[test]
+    fn find_duplicate_handles_missing_directory() {
+        let dir = TempDir::new().unwrap();
+
+        assert_eq!(
+            find_duplicate(&dir.path().join("nope"), Format::Idr, "d", Path::new("/s")),
+            None
+        );
+    }
+
+    #[test]
+    fn is_sidecar_name_matches_extension() {
+        assert!(is_sidecar_name("idr-01.meta.json"));
+        assert!(!is_sidecar_name("idr-01.md"));
+        assert!(!is_sidecar_name("meta.json"));
+    }
+}
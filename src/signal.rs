@@ -0,0 +1,183 @@
+//! Ctrl-C / SIGTERM handling.
+//!
+//! The handler only touches lock-free atomics and calls async-signal-safe
+//! functions (`kill`, `stat`, `unlink`, `_exit`): it terminates every
+//! tracked claude child, removes the tracked temp and lock files and any
+//! reserved record that is still empty, and exits with 130.
+
+use std::path::Path;
+
+/// Exit code for a run interrupted by a signal (128 + SIGINT).
+pub const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+#[cfg(unix)]
+mod imp {
+    use super::INTERRUPTED_EXIT_CODE;
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+    use std::sync::atomic::{AtomicI32, AtomicPtr, Ordering};
+
+    const MAX_CHILDREN: usize = 8;
+    const MAX_FILES: usize = 16;
+
+    type Slots = [AtomicPtr<libc::c_char>; MAX_FILES];
+
+    static CHILDREN: [AtomicI32; MAX_CHILDREN] = [const { AtomicI32::new(0) }; MAX_CHILDREN];
+    /// Removed on interrupt.
+    static FILES: Slots = [const { AtomicPtr::new(std::ptr::null_mut()) }; MAX_FILES];
+    /// Removed on interrupt only while still empty, so a record filled in
+    /// just before the signal is kept.
+    static RESERVED: Slots = [const { AtomicPtr::new(std::ptr::null_mut()) }; MAX_FILES];
+
+    extern "C" fn handle(_signal: libc::c_int) {
+        for slot in &CHILDREN {
+            let pid = slot.load(Ordering::SeqCst);
+            if pid > 0 {
+                // Children run in their own process group, so this also
+                // reaches anything they spawned.
+                unsafe { libc::kill(-pid, libc::SIGTERM) };
+            }
+        }
+        // Swapping the paths out takes them from their guards, which then
+        // leave them alone; the process is about to exit anyway.
+        for slot in &FILES {
+            let path = slot.swap(std::ptr::null_mut(), Ordering::SeqCst);
+            if !path.is_null() {
+                unsafe { libc::unlink(path) };
+            }
+        }
+        for slot in &RESERVED {
+            let path = slot.swap(std::ptr::null_mut(), Ordering::SeqCst);
+            if path.is_null() {
+                continue;
+            }
+            let mut stat = unsafe { std::mem::zeroed::<libc::stat>() };
+            if unsafe { libc::stat(path, &mut stat) } == 0 && stat.st_size == 0 {
+                unsafe { libc::unlink(path) };
+            }
+        }
+        unsafe { libc::_exit(INTERRUPTED_EXIT_CODE) };
+    }
+
+    pub fn install() {
+        let handler = handle as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        unsafe {
+            libc::signal(libc::SIGINT, handler);
+            libc::signal(libc::SIGTERM, handler);
+        }
+    }
+
+    pub struct ChildGuard(Option<usize>);
+
+    pub fn track_child(pid: u32) -> ChildGuard {
+        let Ok(pid) = i32::try_from(pid) else {
+            return ChildGuard(None);
+        };
+        let slot = CHILDREN.iter().position(|slot| {
+            slot.compare_exchange(0, pid, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+        });
+        ChildGuard(slot)
+    }
+
+    impl Drop for ChildGuard {
+        fn drop(&mut self) {
+            if let Some(i) = self.0 {
+                CHILDREN[i].store(0, Ordering::SeqCst);
+            }
+        }
+    }
+
+    pub struct FileGuard(Option<(&'static Slots, usize)>);
+
+    pub fn track_file(path: &Path) -> FileGuard {
+        track(&FILES, path)
+    }
+
+    pub fn track_reserved(path: &Path) -> FileGuard {
+        track(&RESERVED, path)
+    }
+
+    fn track(slots: &'static Slots, path: &Path) -> FileGuard {
+        let Ok(c_path) = CString::new(path.as_os_str().as_bytes()) else {
+            return FileGuard(None);
+        };
+        let raw = c_path.into_raw();
+        let slot = slots.iter().position(|slot| {
+            slot.compare_exchange(
+                std::ptr::null_mut(),
+                raw,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            )
+            .is_ok()
+        });
+        if slot.is_none() {
+            drop(unsafe { CString::from_raw(raw) });
+        }
+        FileGuard(slot.map(|i| (slots, i)))
+    }
+
+    impl Drop for FileGuard {
+        fn drop(&mut self) {
+            let Some((slots, i)) = self.0 else {
+                return;
+            };
+            let raw = slots[i].swap(std::ptr::null_mut(), Ordering::SeqCst);
+            if !raw.is_null() {
+                // SAFETY: `raw` came from `CString::into_raw` in `track`, and
+                // the swap took it back from the handler.
+                drop(unsafe { CString::from_raw(raw) });
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use std::path::Path;
+
+    pub fn install() {}
+
+    pub struct ChildGuard;
+
+    pub fn track_child(_pid: u32) -> ChildGuard {
+        ChildGuard
+    }
+
+    pub struct FileGuard;
+
+    pub fn track_file(_path: &Path) -> FileGuard {
+        FileGuard
+    }
+
+    pub fn track_reserved(_path: &Path) -> FileGuard {
+        FileGuard
+    }
+}
+
+pub use imp::{ChildGuard, FileGuard};
+
+/// Installs the SIGINT/SIGTERM handler. No-op on non-unix platforms.
+pub fn install() {
+    imp::install();
+}
+
+/// Registers a running child to be terminated on interrupt until the guard
+/// is dropped. The child must lead its own process group.
+pub fn track_child(pid: u32) -> ChildGuard {
+    imp::track_child(pid)
+}
+
+/// Registers a temp or lock file to be removed on interrupt until the guard
+/// is dropped.
+pub fn track_file(path: &Path) -> FileGuard {
+    imp::track_file(path)
+}
+
+/// Registers a reserved record to be removed on interrupt, if it is still
+/// empty, until the guard is dropped.
+pub fn track_reserved(path: &Path) -> FileGuard {
+    imp::track_reserved(path)
+}
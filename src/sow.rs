@@ -0,0 +1,348 @@
+//! The `.current-sow` pointer in `workspace_dir`, and `claude-idr sow` for
+//! managing it.
+
+use crate::config::{Config, OutputLayout};
+use crate::git;
+use crate::path;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub const FILE_NAME: &str = ".current-sow";
+
+/// What a `.current-sow` pointer resolves to.
+#[derive(Debug, PartialEq)]
+pub enum Pointer {
+    /// No `.current-sow` file.
+    Unset,
+    /// A directory inside the workspace; records go straight into it.
+    Dir(PathBuf),
+    /// A SOW file inside the workspace; records go next to it.
+    File(PathBuf),
+    /// Exists, but outside the workspace. Ignored.
+    Outside(PathBuf),
+    /// Does not exist (or cannot be resolved). Ignored.
+    Missing(PathBuf),
+}
+
+impl Pointer {
+    /// The directory records are written to, for valid pointers.
+    pub fn dir(&self) -> Option<PathBuf> {
+        match self {
+            Pointer::Dir(dir) => Some(dir.clone()),
+            Pointer::File(file) => file.parent().map(PathBuf::from),
+            _ => None,
+        }
+    }
+}
+
+/// Classifies `sow_path` against `workspace_dir`. Both are canonicalized,
+/// so symlinks are judged by where they lead.
+pub fn classify(sow_path: &Path, workspace_dir: &Path) -> Pointer {
+    let (Ok(real_sow), Ok(real_workspace)) =
+        (fs::canonicalize(sow_path), fs::canonicalize(workspace_dir))
+    else {
+        return Pointer::Missing(sow_path.to_path_buf());
+    };
+    if !real_sow.starts_with(&real_workspace) {
+        Pointer::Outside(real_sow)
+    } else if real_sow.is_dir() {
+        Pointer::Dir(real_sow)
+    } else if real_sow.is_file() {
+        Pointer::File(real_sow)
+    } else {
+        Pointer::Missing(real_sow)
+    }
+}
+
+/// The pointer currently in `workspace_dir/.current-sow`.
+pub fn read(workspace_dir: &Path) -> io::Result<Pointer> {
+    match fs::read_to_string(workspace_dir.join(FILE_NAME)) {
+        Ok(content) => Ok(classify(Path::new(content.trim()), workspace_dir)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Pointer::Unset),
+        Err(e) => Err(e),
+    }
+}
+
+/// `sow set`: points `.current-sow` at `target`, which must lie inside
+/// `workspace_dir`. With `create`, a missing target is created as a
+/// directory (with its parents). Returns the canonical path written.
+pub fn set(workspace_dir: &Path, target: &Path, create: bool) -> io::Result<PathBuf> {
+    let real_workspace = canonical_workspace(workspace_dir)?;
+    if create && !target.exists() {
+        let anchor = existing_ancestor(target)?;
+        if !anchor.starts_with(&real_workspace) {
+            return Err(outside(target, workspace_dir));
+        }
+        fs::create_dir_all(target)?;
+    }
+    match classify(target, &real_workspace) {
+        Pointer::Dir(real) | Pointer::File(real) => {
+            fs::write(
+                real_workspace.join(FILE_NAME),
+                format!("{}\n", real.display()),
+            )?;
+            Ok(real)
+        }
+        Pointer::Outside(_) => Err(outside(target, workspace_dir)),
+        Pointer::Missing(_) | Pointer::Unset => Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "{} does not exist (use --create to create it)",
+                target.display()
+            ),
+        )),
+    }
+}
+
+/// `sow clear`: removes `.current-sow`, returning what it pointed at.
+pub fn clear(workspace_dir: &Path) -> io::Result<Pointer> {
+    let real_workspace = canonical_workspace(workspace_dir)?;
+    let pointer = read(&real_workspace)?;
+    if pointer != Pointer::Unset {
+        fs::remove_file(real_workspace.join(FILE_NAME))?;
+    }
+    Ok(pointer)
+}
+
+/// `sow show`: a description of the pointer and where the next record
+/// would be written. Resolves without creating any directory.
+pub fn show(config: &Config) -> io::Result<String> {
+    canonical_workspace(&config.workspace_dir)?;
+    let pointer = read(&config.workspace_dir)?;
+    let status = match &pointer {
+        Pointer::Unset => "not set".to_string(),
+        Pointer::Dir(dir) => format!("{} (directory)", dir.display()),
+        Pointer::File(file) => format!("{} (SOW file)", file.display()),
+        Pointer::Outside(p) => format!(
+            "{} (ignored: outside workspace_dir {})",
+            p.display(),
+            config.workspace_dir.display()
+        ),
+        Pointer::Missing(p) => format!("{} (ignored: does not exist)", p.display()),
+    };
+    let mut target = path::planned_dir(config, &path::today_date(), git::repo_root().as_deref())
+        .display()
+        .to_string();
+    if config.output_dir.is_some() {
+        target.push_str(" (output_dir overrides .current-sow)");
+    }
+    if config.output_layout == OutputLayout::ByBranch {
+        target.push_str(", in a subdirectory per branch");
+    }
+    Ok(format!("{FILE_NAME}: {status}\nIDRs go to: {target}"))
+}
+
+fn canonical_workspace(workspace_dir: &Path) -> io::Result<PathBuf> {
+    fs::canonicalize(workspace_dir).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("workspace_dir {} does not exist", workspace_dir.display()),
+        )
+    })
+}
+
+fn outside(target: &Path, workspace_dir: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::PermissionDenied,
+        format!(
+            "{} is outside workspace_dir {}",
+            target.display(),
+            workspace_dir.display()
+        ),
+    )
+}
+
+/// The nearest existing ancestor of `path`, canonicalized.
+fn existing_ancestor(path: &Path) -> io::Result<PathBuf> {
+    let absolute = std::path::absolute(path)?;
+    let existing = absolute
+        .ancestors()
+        .find(|p| p.exists())
+        .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))?;
+    fs::canonicalize(existing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn workspace_with_sow() -> (TempDir, PathBuf) {
+        let tmp = TempDir::new().unwrap();
+        let sow_dir = tmp.path().join("sow").join("project-x");
+        fs::create_dir_all(&sow_dir).unwrap();
+        let sow_file = sow_dir.join("sow.md");
+        fs::write(&sow_file, "# SOW").unwrap();
+        (tmp, sow_file)
+    }
+
+    fn pointer_file(workspace: &Path) -> String {
+        fs::read_to_string(workspace.join(FILE_NAME)).unwrap()
+    }
+
+    #[test]
+    fn set_writes_canonical_path_for_sow_file() {
+        let (tmp, sow_file) = workspace_with_sow();
+
+        let written = set(tmp.path(), &sow_file, false).unwrap();
+
+        assert_eq!(written, fs::canonicalize(&sow_file).unwrap());
+        assert_eq!(pointer_file(tmp.path()), format!("{}\n", written.display()));
+        assert_eq!(
+            read(tmp.path()).unwrap().dir(),
+            Some(fs::canonicalize(sow_file.parent().unwrap()).unwrap())
+        );
+    }
+
+    #[test]
+    fn set_accepts_directory() {
+        let (tmp, sow_file) = workspace_with_sow();
+        let dir = sow_file.parent().unwrap();
+
+        set(tmp.path(), dir, false).unwrap();
+
+        assert_eq!(
+            read(tmp.path()).unwrap(),
+            Pointer::Dir(fs::canonicalize(dir).unwrap())
+        );
+    }
+
+    #[test]
+    fn set_rejects_path_outside_workspace() {
+        let workspace = TempDir::new().unwrap();
+        let outside_dir = TempDir::new().unwrap();
+        let outside_file = outside_dir.path().join("sow.md");
+        fs::write(&outside_file, "# SOW").unwrap();
+
+        let err = set(workspace.path(), &outside_file, false).unwrap_err();
+
+        assert!(
+            err.to_string().contains("is outside workspace_dir"),
+            "{err}"
+        );
+        assert!(!workspace.path().join(FILE_NAME).exists());
+    }
+
+    #[test]
+    fn set_rejects_nonexistent_target_without_create() {
+        let tmp = TempDir::new().unwrap();
+
+        let err = set(tmp.path(), &tmp.path().join("nope"), false).unwrap_err();
+
+        assert!(err.to_string().contains("--create"), "{err}");
+        assert!(!tmp.path().join(FILE_NAME).exists());
+    }
+
+    #[test]
+    fn set_creates_missing_directory_with_create() {
+        let tmp = TempDir::new().unwrap();
+        let target = tmp.path().join("sow").join("new-project");
+
+        set(tmp.path(), &target, true).unwrap();
+
+        assert!(target.is_dir());
+        assert_eq!(
+            read(tmp.path()).unwrap(),
+            Pointer::Dir(fs::canonicalize(&target).unwrap())
+        );
+    }
+
+    #[test]
+    fn set_with_create_refuses_to_create_outside_workspace() {
+        let workspace = TempDir::new().unwrap();
+        let outside_dir = TempDir::new().unwrap();
+        let target = outside_dir.path().join("new-project");
+
+        let err = set(workspace.path(), &target, true).unwrap_err();
+
+        assert!(
+            err.to_string().contains("is outside workspace_dir"),
+            "{err}"
+        );
+        assert!(!target.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn set_rejects_symlink_outside_workspace() {
+        let workspace = TempDir::new().unwrap();
+        let outside_dir = TempDir::new().unwrap();
+        let outside_file = outside_dir.path().join("secret.md");
+        fs::write(&outside_file, "secret").unwrap();
+        let link = workspace.path().join("link.md");
+        std::os::unix::fs::symlink(&outside_file, &link).unwrap();
+
+        assert!(set(workspace.path(), &link, false).is_err());
+    }
+
+    #[test]
+    fn set_and_clear_report_missing_workspace() {
+        let tmp = TempDir::new().unwrap();
+        let workspace = tmp.path().join("gone");
+
+        let err = set(&workspace, tmp.path(), false).unwrap_err();
+        assert!(err.to_string().contains("does not exist"), "{err}");
+        let err = clear(&workspace).unwrap_err();
+        assert!(err.to_string().contains("workspace_dir"), "{err}");
+    }
+
+    #[test]
+    fn clear_removes_pointer_and_returns_it() {
+        let (tmp, sow_file) = workspace_with_sow();
+        set(tmp.path(), &sow_file, false).unwrap();
+
+        let removed = clear(tmp.path()).unwrap();
+
+        assert_eq!(removed, Pointer::File(fs::canonicalize(&sow_file).unwrap()));
+        assert!(!tmp.path().join(FILE_NAME).exists());
+        assert_eq!(clear(tmp.path()).unwrap(), Pointer::Unset);
+    }
+
+    #[test]
+    fn show_describes_valid_and_ignored_pointers() {
+        let (tmp, sow_file) = workspace_with_sow();
+        let config = Config {
+            workspace_dir: tmp.path().to_path_buf(),
+            ..Config::default()
+        };
+
+        let unset = show(&config).unwrap();
+        assert!(unset.starts_with(".current-sow: not set\n"), "{unset}");
+        assert!(unset.contains("planning"), "{unset}");
+
+        set(tmp.path(), &sow_file, false).unwrap();
+        let valid = show(&config).unwrap();
+        assert!(valid.contains("(SOW file)"), "{valid}");
+        let sow_dir = fs::canonicalize(sow_file.parent().unwrap()).unwrap();
+        assert!(
+            valid.ends_with(&format!("IDRs go to: {}", sow_dir.display())),
+            "{valid}"
+        );
+
+        fs::write(tmp.path().join(FILE_NAME), "/nonexistent/sow.md").unwrap();
+        let missing = show(&config).unwrap();
+        assert!(missing.contains("(ignored: does not exist)"), "{missing}");
+    }
+
+    #[test]
+    fn show_notes_outside_pointer_and_output_dir_override() {
+        let workspace = TempDir::new().unwrap();
+        let outside_dir = TempDir::new().unwrap();
+        fs::write(
+            workspace.path().join(FILE_NAME),
+            outside_dir.path().to_str().unwrap(),
+        )
+        .unwrap();
+        let config = Config {
+            workspace_dir: workspace.path().to_path_buf(),
+            output_dir: Some(PathBuf::from("/tmp/idr")),
+            ..Config::default()
+        };
+
+        let shown = show(&config).unwrap();
+
+        assert!(shown.contains("ignored: outside workspace_dir"), "{shown}");
+        assert!(shown.contains("output_dir overrides"), "{shown}");
+    }
+}
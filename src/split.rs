@@ -0,0 +1,229 @@
+//! `--split-by-file`: one record per changed file, each opening with the
+//! same header, plus an umbrella record linking them. Generation itself is
+//! in `generate`; this is the planning around it.
+
+use crate::config::Config;
+use crate::diff::{self, FileDiff};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+/// One file's share of the diff.
+#[derive(Debug, PartialEq)]
+pub struct Part {
+    pub path: String,
+    pub diff: String,
+    /// `git diff --stat` for this file alone.
+    pub stat: String,
+}
+
+/// The parts of `diff`, in diff order.
+pub fn plan(diff: &str) -> Vec<Part> {
+    diff::parse(diff)
+        .into_iter()
+        .map(|file| Part {
+            stat: file_stat(&file),
+            diff: diff::render(std::slice::from_ref(&file)),
+            path: file.path,
+        })
+        .collect()
+}
+
+/// A `git diff --stat` block for one file, without the bar scaling git
+/// applies to wide terminals.
+fn file_stat(file: &FileDiff) -> String {
+    let lines = file.hunks.iter().flat_map(|h| &h.lines);
+    let insertions = lines.clone().filter(|l| l.starts_with('+')).count();
+    let deletions = lines.filter(|l| l.starts_with('-')).count();
+    let plural = |n: usize, word: &str| format!("{n} {word}{}", if n == 1 { "" } else { "s" });
+    let mut summary = " 1 file changed".to_string();
+    if insertions > 0 {
+        summary.push_str(&format!(", {}(+)", plural(insertions, "insertion")));
+    }
+    if deletions > 0 {
+        summary.push_str(&format!(", {}(-)", plural(deletions, "deletion")));
+    }
+    format!(
+        " {} | {} {}{}\n{summary}",
+        file.path,
+        insertions + deletions,
+        "+".repeat(insertions),
+        "-".repeat(deletions)
+    )
+}
+
+/// The block every part record opens with: where it belongs and what the
+/// whole change was.
+pub fn header(
+    number: usize,
+    total: usize,
+    umbrella: &str,
+    purpose: &str,
+    overall_stat: &str,
+) -> String {
+    let mut header = format!("> Part {number} of {total} of [{umbrella}]({umbrella}): {purpose}");
+    if let Some(summary) = overall_stat.lines().rev().find(|l| l.contains("changed")) {
+        header.push_str(&format!("\n> Overall:{}", summary.trim_end()));
+    }
+    header
+}
+
+/// An entry of the umbrella record.
+pub struct Entry<'a> {
+    pub path: &'a str,
+    /// The part record's file name.
+    pub name: &'a str,
+    pub failed: bool,
+}
+
+/// The umbrella record's body: a link to every part, with failed ones
+/// marked.
+pub fn umbrella_content(entries: &[Entry], config: &Config) -> String {
+    let failed = match config.language.as_str() {
+        "ja" => " (生成失敗)",
+        _ => " (generation failed)",
+    };
+    entries
+        .iter()
+        .map(|e| {
+            let mark = if e.failed { failed } else { "" };
+            format!("- [`{}`]({}){mark}", e.path, e.name)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// `run` applied to every item on up to `limit` threads at once, results
+/// in item order.
+pub fn map_limited<T: Sync, R: Send>(
+    items: &[T],
+    limit: usize,
+    run: impl Fn(&T) -> R + Sync,
+) -> Vec<R> {
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<R>>> = Mutex::new(items.iter().map(|_| None).collect());
+    thread::scope(|scope| {
+        for _ in 0..limit.clamp(1, items.len().max(1)) {
+            scope.spawn(|| {
+                loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(item) = items.get(i) else {
+                        break;
+                    };
+                    let result = run(item);
+                    results.lock().unwrap()[i] = Some(result);
+                }
+            });
+        }
+    });
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|r| r.expect("every item is run"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const DIFF: &str = "\
+diff --git a/src/a.rs b/src/a.rs
+--- a/src/a.rs
++++ b/src/a.rs
+@@ -1,2 +1,2 @@
+-old
++new
++more
+diff --git a/README.md b/README.md
+--- a/README.md
++++ b/README.md
+@@ -3 +3 @@
+-gone
+";
+
+    #[test]
+    fn plan_gives_each_file_its_diff_and_stat() {
+        let parts = plan(DIFF);
+
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].path, "src/a.rs");
+        assert!(
+            parts[0]
+                .diff
+                .starts_with("diff --git a/src/a.rs b/src/a.rs\n")
+        );
+        assert!(!parts[0].diff.contains("README"));
+        assert_eq!(
+            parts[0].stat,
+            " src/a.rs | 3 ++-\n 1 file changed, 2 insertions(+), 1 deletion(-)"
+        );
+        assert_eq!(
+            parts[1].stat,
+            " README.md | 1 -\n 1 file changed, 1 deletion(-)"
+        );
+        assert_eq!(crate::git::stat_totals(&parts[0].stat), (1, 2, 1));
+    }
+
+    #[test]
+    fn header_names_umbrella_purpose_and_overall_totals() {
+        let stat = " a | 2 +-\n b | 1 +\n 2 files changed, 2 insertions(+), 1 deletion(-)\n";
+
+        assert_eq!(
+            header(2, 5, "idr-04.md", "Rename Foo", stat),
+            "> Part 2 of 5 of [idr-04.md](idr-04.md): Rename Foo\n\
+             > Overall: 2 files changed, 2 insertions(+), 1 deletion(-)"
+        );
+        assert_eq!(
+            header(1, 1, "idr-04.md", "Rename Foo", ""),
+            "> Part 1 of 1 of [idr-04.md](idr-04.md): Rename Foo"
+        );
+    }
+
+    #[test]
+    fn umbrella_content_links_parts_and_marks_failures() {
+        let entries = [
+            Entry {
+                path: "src/a.rs",
+                name: "idr-05-src-a-rs.md",
+                failed: false,
+            },
+            Entry {
+                path: "README.md",
+                name: "idr-06-readme-md.md",
+                failed: true,
+            },
+        ];
+        let en = Config {
+            language: "en".to_string(),
+            ..Config::default()
+        };
+
+        assert_eq!(
+            umbrella_content(&entries, &en),
+            "- [`src/a.rs`](idr-05-src-a-rs.md)\n\
+             - [`README.md`](idr-06-readme-md.md) (generation failed)"
+        );
+    }
+
+    #[test]
+    fn map_limited_keeps_order_and_respects_limit() {
+        let running = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+        let items: Vec<u64> = (0..8).collect();
+
+        let results = map_limited(&items, 3, |&n| {
+            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(now, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(10 * (8 - n)));
+            running.fetch_sub(1, Ordering::SeqCst);
+            n * 2
+        });
+
+        assert_eq!(results, [0, 2, 4, 6, 8, 10, 12, 14]);
+        assert!(peak.load(Ordering::SeqCst) <= 3);
+        assert!(map_limited(&[] as &[u8], 0, |_| ()).is_empty());
+    }
+}
@@ -0,0 +1,266 @@
+//! `claude-idr stats`: how many records were written when, how much change
+//! they document, which files they keep coming back to, and which still
+//! need filling in by hand.
+
+use crate::config::Config;
+use crate::front_matter;
+use crate::git;
+use crate::path;
+use crate::prompt;
+use crate::record::{self, ParsedRecord};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::PathBuf;
+
+/// Files listed in [`Stats::top_files`].
+const TOP_FILES: usize = 10;
+
+/// One record as [`aggregate`] sees it.
+#[derive(Debug)]
+pub struct Sample {
+    pub path: PathBuf,
+    pub record: ParsedRecord,
+    pub lines: usize,
+    /// Insertions and deletions from the stat footer, or the front matter
+    /// when there is no footer.
+    pub totals: Option<(u64, u64)>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Stats {
+    pub records: usize,
+    /// Records per `YYYY-MM`.
+    pub per_month: BTreeMap<String, usize>,
+    pub undated: usize,
+    pub insertions: u64,
+    pub deletions: u64,
+    /// The files the most records cover, most first.
+    pub top_files: Vec<FileCount>,
+    pub average_lines: f64,
+    /// Records whose summary is still the generation-failure note.
+    pub needs_attention: Vec<PathBuf>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct FileCount {
+    pub path: String,
+    pub records: usize,
+}
+
+/// Reads and parses the records in `dirs`.
+pub fn load(dirs: &[PathBuf], config: &Config) -> Vec<Sample> {
+    dirs.iter()
+        .flat_map(|dir| path::list_records(dir, config.format))
+        .filter_map(|(_, file)| {
+            let text = String::from_utf8_lossy(&fs::read(&file).ok()?).into_owned();
+            let record = record::parse(&text, config);
+            let totals = record
+                .stat
+                .as_deref()
+                .map(git::stat_totals)
+                .filter(|(files, _, _)| *files > 0)
+                .map(|(_, insertions, deletions)| (insertions, deletions))
+                .or_else(|| {
+                    let block = front_matter::split(&text).0?;
+                    let count = |key| front_matter::field(block, key)?.parse().ok();
+                    Some((count("insertions")?, count("deletions")?))
+                });
+            Some(Sample {
+                path: file,
+                lines: text.lines().count(),
+                record,
+                totals,
+            })
+        })
+        .collect()
+}
+
+pub fn aggregate(samples: &[Sample]) -> Stats {
+    let mut per_month = BTreeMap::new();
+    let mut undated = 0;
+    let mut files: BTreeMap<&str, usize> = BTreeMap::new();
+    for sample in samples {
+        match sample.record.datetime.as_deref().and_then(|d| d.get(..7)) {
+            Some(month) => *per_month.entry(month.to_string()).or_insert(0) += 1,
+            None => undated += 1,
+        }
+        let covered: BTreeSet<&str> = sample
+            .record
+            .files
+            .iter()
+            .map(|f| f.path.as_str())
+            .collect();
+        for file in covered {
+            *files.entry(file).or_insert(0) += 1;
+        }
+    }
+    let mut top_files: Vec<FileCount> = files
+        .into_iter()
+        .map(|(path, records)| FileCount {
+            path: path.to_string(),
+            records,
+        })
+        .collect();
+    top_files.sort_by(|a, b| b.records.cmp(&a.records).then_with(|| a.path.cmp(&b.path)));
+    top_files.truncate(TOP_FILES);
+    let (insertions, deletions) = samples
+        .iter()
+        .filter_map(|s| s.totals)
+        .fold((0, 0), |(ai, ad), (i, d)| (ai + i, ad + d));
+    let lines: usize = samples.iter().map(|s| s.lines).sum();
+    Stats {
+        records: samples.len(),
+        per_month,
+        undated,
+        insertions,
+        deletions,
+        top_files,
+        average_lines: if samples.is_empty() {
+            0.0
+        } else {
+            lines as f64 / samples.len() as f64
+        },
+        needs_attention: samples
+            .iter()
+            .filter(|s| {
+                s.record
+                    .summary
+                    .as_deref()
+                    .is_some_and(prompt::is_failure_note)
+            })
+            .map(|s| s.path.clone())
+            .collect(),
+    }
+}
+
+/// `stats` as the plain-text report `claude-idr stats` prints.
+pub fn render(stats: &Stats) -> String {
+    let mut out = format!(
+        "{} records, +{} / -{} lines documented, {:.1} lines per record on average\n",
+        stats.records, stats.insertions, stats.deletions, stats.average_lines
+    );
+    if !stats.per_month.is_empty() || stats.undated > 0 {
+        out.push_str("\nMonth      Records\n");
+        for (month, count) in &stats.per_month {
+            out.push_str(&format!("{month:<10} {count:>7}\n"));
+        }
+        if stats.undated > 0 {
+            out.push_str(&format!("{:<10} {:>7}\n", "undated", stats.undated));
+        }
+    }
+    if !stats.top_files.is_empty() {
+        out.push_str("\nRecords  Most documented files\n");
+        for file in &stats.top_files {
+            out.push_str(&format!("{:>7}  {}\n", file.records, file.path));
+        }
+    }
+    if !stats.needs_attention.is_empty() {
+        let count = stats.needs_attention.len();
+        let (noun, verb) = if count == 1 { ("", "s") } else { ("s", "") };
+        out.push_str(&format!(
+            "\n{count} record{noun} still need{verb} filling in:\n"
+        ));
+        for path in &stats.needs_attention {
+            out.push_str(&format!("  {}\n", path.display()));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn fixture_stats() -> Stats {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/stats");
+        let dirs = crate::digest::record_dirs(&root);
+        aggregate(&load(&dirs, &Config::default()))
+    }
+
+    #[test]
+    fn aggregate_counts_months_totals_and_files() {
+        let stats = fixture_stats();
+
+        assert_eq!(stats.records, 5);
+        assert_eq!(
+            stats.per_month,
+            BTreeMap::from([
+                ("2026-01".to_string(), 3),
+                ("2026-02".to_string(), 1),
+                ("2026-03".to_string(), 1),
+            ])
+        );
+        assert_eq!(stats.undated, 0);
+        assert_eq!((stats.insertions, stats.deletions), (31, 8));
+        assert_eq!(
+            stats.top_files,
+            [
+                FileCount {
+                    path: "src/client.rs".to_string(),
+                    records: 3,
+                },
+                FileCount {
+                    path: "src/config.rs".to_string(),
+                    records: 1,
+                },
+                FileCount {
+                    path: "src/server.rs".to_string(),
+                    records: 1,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn aggregate_flags_failure_placeholders() {
+        let stats = fixture_stats();
+
+        let names: Vec<&str> = stats
+            .needs_attention
+            .iter()
+            .map(|p| p.file_name().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(names, ["idr-02-broken.md"]);
+        assert!(stats.average_lines > 5.0, "{}", stats.average_lines);
+    }
+
+    #[test]
+    fn aggregate_counts_undated_records_and_handles_none() {
+        let sample = Sample {
+            path: PathBuf::from("idr-01.md"),
+            record: ParsedRecord::default(),
+            lines: 4,
+            totals: None,
+        };
+
+        let stats = aggregate(&[sample]);
+        assert_eq!(stats.undated, 1);
+        assert!(stats.per_month.is_empty());
+        assert_eq!(stats.average_lines, 4.0);
+
+        let empty = aggregate(&[]);
+        assert_eq!(empty.records, 0);
+        assert_eq!(empty.average_lines, 0.0);
+    }
+
+    #[test]
+    fn render_prints_tables() {
+        let text = render(&fixture_stats());
+
+        assert!(
+            text.starts_with("5 records, +31 / -8 lines documented, "),
+            "{text}"
+        );
+        assert!(
+            text.contains("\nMonth      Records\n2026-01          3\n"),
+            "{text}"
+        );
+        assert!(text.contains("      3  src/client.rs\n"), "{text}");
+        assert!(
+            text.contains("1 record still needs filling in:\n"),
+            "{text}"
+        );
+    }
+}
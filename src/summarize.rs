@@ -0,0 +1,248 @@
+//! Two-stage generation for diffs over `max_diff_lines`: a cheap summary
+//! call per chunk of files, then one call that writes the IDR from those
+//! summaries and the stat.
+
+use crate::claude::{self, ClaudeResult};
+use crate::config::Config;
+use crate::diff::{self, FileDiff};
+use crate::error::IdrError;
+use crate::log::{info, verbose, warn};
+use crate::prompt::{self, Background};
+
+/// Groups consecutive files into chunks of at most `max_lines` changed
+/// lines. A single file over the limit gets a chunk of its own.
+pub fn plan_chunks(files: Vec<FileDiff>, max_lines: u64) -> Vec<Vec<FileDiff>> {
+    let mut chunks: Vec<Vec<FileDiff>> = Vec::new();
+    let mut current_lines = 0;
+    for file in files {
+        let lines = changed_lines(&file);
+        match chunks.last_mut() {
+            Some(chunk) if current_lines + lines <= max_lines => {
+                chunk.push(file);
+                current_lines += lines;
+            }
+            _ => {
+                chunks.push(vec![file]);
+                current_lines = lines;
+            }
+        }
+    }
+    chunks
+}
+
+fn changed_lines(file: &FileDiff) -> u64 {
+    file.hunks
+        .iter()
+        .flat_map(|h| &h.lines)
+        .filter(|l| l.starts_with('+') || l.starts_with('-'))
+        .count() as u64
+}
+
+/// Prompts for each chunk, labelled with the files they cover.
+pub fn chunk_prompts(diff: &str, config: &Config) -> Vec<(String, String)> {
+    plan_chunks(diff::parse(diff), config.max_diff_lines)
+        .into_iter()
+        .map(|chunk| {
+            let label = chunk
+                .iter()
+                .map(|f| f.path.as_str())
+                .collect::<Vec<_>>()
+                .join(", ");
+            let prompt = prompt::build_chunk_summary_prompt(&diff::render(&chunk), config);
+            (label, prompt)
+        })
+        .collect()
+}
+
+/// Summarizes every chunk with `summary_model`, then assembles the IDR with
+/// the main model. Usage and cost of all calls are summed into the result.
+/// A failed chunk is noted in place of its summary; if every chunk fails,
+/// so does the whole run.
+pub fn run(
+    diff: &str,
+    stat: &str,
+    background: &Background,
+    config: &Config,
+) -> Result<ClaudeResult, IdrError> {
+    run_with(diff, config, |summaries| {
+        prompt::build_summarized_idr_prompt(summaries, stat, background, config)
+    })
+}
+
+/// Like [`run`], with the final prompt built by `assemble` from the joined
+/// chunk summaries.
+pub fn run_with(
+    diff: &str,
+    config: &Config,
+    assemble: impl FnOnce(&str) -> String,
+) -> Result<ClaudeResult, IdrError> {
+    let summary_config = Config {
+        model: config.summary_model.clone(),
+        model_fallbacks: Vec::new(),
+        ..config.clone()
+    };
+    let chunks = chunk_prompts(diff, config);
+    let total = chunks.len();
+
+    let mut summaries = Vec::new();
+    let mut chunk_results = Vec::new();
+    for (i, (label, chunk_prompt)) in chunks.iter().enumerate() {
+        info!("summarizing chunk {}/{total} ({label})...", i + 1);
+        match claude::run(chunk_prompt, &summary_config) {
+            Ok(result) => {
+                summaries.push(format!("## {label}\n{}", result.text.trim()));
+                chunk_results.push(result);
+            }
+            Err(e) => {
+                warn!("summary of {label} failed: {e}");
+                summaries.push(format!("## {label}\n- (summary unavailable)"));
+            }
+        }
+    }
+    if chunk_results.is_empty() {
+        return Err(IdrError::claude(format!(
+            "all {total} chunk summaries failed"
+        )));
+    }
+
+    let assembly_prompt = assemble(&summaries.join("\n\n"));
+    let mut result = claude::run(&assembly_prompt, config)?;
+    let chunk_usage = chunk_results
+        .iter()
+        .filter_map(|r| r.usage)
+        .reduce(|a, b| a + b);
+    let chunk_cost = chunk_results
+        .iter()
+        .filter_map(|r| r.cost_usd)
+        .reduce(|a, b| a + b);
+    if let Some(usage) = chunk_usage {
+        verbose!(
+            "{} chunk summary call(s) used {} in / {} out tokens{}",
+            chunk_results.len(),
+            usage.input_tokens,
+            usage.output_tokens,
+            chunk_cost.map_or(String::new(), |c| format!(", cost ${c:.4}"))
+        );
+    }
+    result.usage = [result.usage, chunk_usage]
+        .into_iter()
+        .flatten()
+        .reduce(|a, b| a + b);
+    result.cost_usd = [result.cost_usd, chunk_cost]
+        .into_iter()
+        .flatten()
+        .reduce(|a, b| a + b);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(unix)]
+    use crate::testutil::write_script;
+    #[cfg(unix)]
+    use tempfile::TempDir;
+
+    fn file(path: &str, changed: usize) -> FileDiff {
+        FileDiff {
+            path: path.to_string(),
+            header: vec![format!("diff --git a/{path} b/{path}")],
+            hunks: vec![diff::Hunk {
+                header: "@@ -1 +1 @@".to_string(),
+                lines: (0..changed).map(|i| format!("+line {i}")).collect(),
+            }],
+        }
+    }
+
+    fn paths(chunks: &[Vec<FileDiff>]) -> Vec<Vec<&str>> {
+        chunks
+            .iter()
+            .map(|c| c.iter().map(|f| f.path.as_str()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn plan_chunks_groups_files_up_to_limit() {
+        let files = vec![file("a", 40), file("b", 50), file("c", 30), file("d", 10)];
+
+        let chunks = plan_chunks(files, 100);
+
+        assert_eq!(paths(&chunks), vec![vec!["a", "b"], vec!["c", "d"]]);
+    }
+
+    #[test]
+    fn plan_chunks_gives_oversized_file_its_own_chunk() {
+        let files = vec![file("a", 10), file("big", 500), file("c", 10)];
+
+        let chunks = plan_chunks(files, 100);
+
+        assert_eq!(paths(&chunks), vec![vec!["a"], vec!["big"], vec!["c"]]);
+    }
+
+    #[test]
+    fn plan_chunks_handles_empty_diff() {
+        assert!(plan_chunks(Vec::new(), 100).is_empty());
+    }
+
+    const TWO_FILES: &str = "\
+diff --git a/a.rs b/a.rs
+@@ -1 +1 @@
+-a
++b
+diff --git a/b.rs b/b.rs
+@@ -1 +1 @@
+-c
++d
+";
+
+    #[cfg(unix)]
+    #[test]
+    fn run_notes_failed_chunks_and_assembles_idr() {
+        let dir = TempDir::new().unwrap();
+        let seen = dir.path().join("assembly-prompt");
+        let script = write_script(
+            dir.path(),
+            "fake-claude",
+            &format!(
+                "prompt=$(cat)\n\
+                 case \"$prompt\" in\n\
+                 *file_summaries*) printf '%s' \"$prompt\" > {}; echo '## IDR body';;\n\
+                 *b.rs*) exit 1;;\n\
+                 *) echo '- changed a';;\n\
+                 esac\n",
+                seen.display()
+            ),
+        );
+        let config = Config {
+            claude_bin: script,
+            json_output: false,
+            max_diff_lines: 2,
+            ..Config::default()
+        };
+
+        let result = run(TWO_FILES, "stat", &Background::default(), &config).unwrap();
+
+        assert_eq!(result.text, "## IDR body\n");
+        let assembly = std::fs::read_to_string(&seen).unwrap();
+        assert!(assembly.contains("## a.rs\n- changed a"));
+        assert!(assembly.contains("## b.rs\n- (summary unavailable)"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_fails_when_every_chunk_fails() {
+        let dir = TempDir::new().unwrap();
+        let script = write_script(dir.path(), "fake-claude", "cat >/dev/null\nexit 1\n");
+        let config = Config {
+            claude_bin: script,
+            json_output: false,
+            max_diff_lines: 2,
+            ..Config::default()
+        };
+
+        assert!(matches!(
+            run(TWO_FILES, "stat", &Background::default(), &config),
+            Err(IdrError::ClaudeFailed { .. })
+        ));
+    }
+}
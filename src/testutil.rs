@@ -15,3 +15,13 @@ pub fn write_jsonl(dir: &Path, name: &str, lines: &[&str]) -> PathBuf {
     }
     path
 }
+
+#[cfg(unix)]
+pub fn write_script(dir: &Path, name: &str, body: &str) -> PathBuf {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = dir.join(name);
+    std::fs::write(&path, format!("#!/bin/sh\n{body}")).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    path
+}
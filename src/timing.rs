@@ -0,0 +1,173 @@
+//! Wall time per phase of a run (session scan, claude calls, write), for the
+//! `done in ...` line and the `timing` field of the `--json` report.
+
+use crate::log::verbose;
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Phases shorter than this are left out of the one-line summary.
+const SUMMARY_MIN: Duration = Duration::from_millis(50);
+
+/// Records how long each phase took. Phases may run on several threads at
+/// once (purpose and IDR calls overlap), so their sum can exceed the total.
+pub struct Timer {
+    clock: Box<dyn Fn() -> Duration + Send + Sync>,
+    started: Duration,
+    phases: Mutex<Vec<(&'static str, Duration)>>,
+}
+
+impl Timer {
+    pub fn new() -> Timer {
+        let origin = Instant::now();
+        Timer::with_clock(move || origin.elapsed())
+    }
+
+    /// A timer reading `clock`, the time since some fixed origin.
+    fn with_clock(clock: impl Fn() -> Duration + Send + Sync + 'static) -> Timer {
+        let started = clock();
+        Timer {
+            clock: Box::new(clock),
+            started,
+            phases: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Runs `f` as phase `name`, logging its duration with `--verbose`.
+    pub fn time<T>(&self, name: &'static str, f: impl FnOnce() -> T) -> T {
+        let started = (self.clock)();
+        let result = f();
+        let took = (self.clock)().saturating_sub(started);
+        verbose!("{name} took {:.1}s", took.as_secs_f64());
+        if let Ok(mut phases) = self.phases.lock() {
+            phases.push((name, took));
+        }
+        result
+    }
+
+    /// The phases so far and the time since the timer was created.
+    pub fn finish(&self) -> Timing {
+        let phases = self
+            .phases
+            .lock()
+            .map(|phases| phases.clone())
+            .unwrap_or_default();
+        Timing {
+            total_secs: secs((self.clock)().saturating_sub(self.started)),
+            phases: phases
+                .into_iter()
+                .map(|(name, took)| PhaseTiming {
+                    name,
+                    secs: secs(took),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Where a run spent its time; part of the `--json` report.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Timing {
+    pub total_secs: f64,
+    pub phases: Vec<PhaseTiming>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PhaseTiming {
+    pub name: &'static str,
+    pub secs: f64,
+}
+
+impl Timing {
+    /// `done in 41.2s (scan 0.3s, purpose 6.1s, idr 34.5s)`; phases under
+    /// 50ms are left out.
+    pub fn summary(&self) -> String {
+        let phases: Vec<String> = self
+            .phases
+            .iter()
+            .filter(|p| p.secs >= SUMMARY_MIN.as_secs_f64())
+            .map(|p| format!("{} {:.1}s", p.name, p.secs))
+            .collect();
+        if phases.is_empty() {
+            format!("done in {:.1}s", self.total_secs)
+        } else {
+            format!("done in {:.1}s ({})", self.total_secs, phases.join(", "))
+        }
+    }
+}
+
+/// Seconds rounded to milliseconds, so the report stays readable.
+fn secs(d: Duration) -> f64 {
+    d.as_millis() as f64 / 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    /// A clock that only moves when told to.
+    fn fake_clock() -> (
+        Arc<AtomicU64>,
+        impl Fn() -> Duration + Send + Sync + 'static,
+    ) {
+        let millis = Arc::new(AtomicU64::new(0));
+        let reader = Arc::clone(&millis);
+        (millis, move || {
+            Duration::from_millis(reader.load(Ordering::SeqCst))
+        })
+    }
+
+    #[test]
+    fn summary_lists_phases_in_order() {
+        let (now, clock) = fake_clock();
+        let timer = Timer::with_clock(clock);
+        let advance = |ms| now.fetch_add(ms, Ordering::SeqCst);
+
+        timer.time("scan", || advance(300));
+        timer.time("context", || advance(10));
+        timer.time("purpose", || advance(6100));
+        timer.time("idr", || advance(34500));
+        advance(300);
+
+        let timing = timer.finish();
+
+        assert_eq!(
+            timing.summary(),
+            "done in 41.2s (scan 0.3s, purpose 6.1s, idr 34.5s)"
+        );
+        assert_eq!(timing.total_secs, 41.21);
+        assert_eq!(
+            timing.phases[1],
+            PhaseTiming {
+                name: "context",
+                secs: 0.01
+            }
+        );
+    }
+
+    #[test]
+    fn summary_without_noticeable_phases_is_just_the_total() {
+        let (now, clock) = fake_clock();
+        let timer = Timer::with_clock(clock);
+        timer.time("scan", || now.fetch_add(20, Ordering::SeqCst));
+
+        assert_eq!(timer.finish().summary(), "done in 0.0s");
+    }
+
+    #[test]
+    fn timing_serializes_phase_names_and_seconds() {
+        let timing = Timing {
+            total_secs: 1.5,
+            phases: vec![PhaseTiming {
+                name: "idr",
+                secs: 1.25,
+            }],
+        };
+        assert_eq!(
+            serde_json::to_string(&timing).unwrap(),
+            r#"{"total_secs":1.5,"phases":[{"name":"idr","secs":1.25}]}"#
+        );
+    }
+}
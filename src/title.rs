@@ -0,0 +1,280 @@
+//! The record's title. claude's answer to the purpose prompt comes first;
+//! when that call fails or answers with nothing usable, the session and the
+//! commit usually still hold a line that names the change better than the
+//! missing-purpose placeholder does.
+
+use crate::config::Config;
+use crate::path;
+use crate::prompt;
+
+/// Where a title came from, recorded in the sidecar as `title_source`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TitleSource {
+    /// claude's answer to the purpose prompt.
+    Purpose,
+    /// The session's summary entry, written when it was compacted.
+    Summary,
+    /// The last substantive user request.
+    Request,
+    /// The first line of the commit message.
+    Commit,
+    /// `missing_purpose_text` or the built-in placeholder.
+    Placeholder,
+}
+
+impl TitleSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TitleSource::Purpose => "purpose",
+            TitleSource::Summary => "summary",
+            TitleSource::Request => "request",
+            TitleSource::Commit => "commit",
+            TitleSource::Placeholder => "placeholder",
+        }
+    }
+}
+
+/// What a title can be made from; any of it may be missing.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TitleInputs<'a> {
+    /// The purpose call's response, as claude wrote it.
+    pub purpose: Option<&'a str>,
+    pub summary: Option<&'a str>,
+    /// User requests in the order they were made.
+    pub requests: &'a [String],
+    pub commit_message: Option<&'a str>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Title {
+    pub text: String,
+    pub source: TitleSource,
+}
+
+impl Title {
+    /// The title as a purpose to name the file after; `None` for the
+    /// placeholder, which names nothing.
+    pub fn purpose(&self) -> Option<&str> {
+        (self.source != TitleSource::Placeholder).then_some(self.text.as_str())
+    }
+}
+
+/// Answers that are no title at all, compared without case or trailing
+/// punctuation.
+const NON_ANSWERS: [&str; 8] = [
+    "unknown",
+    "n/a",
+    "none",
+    "null",
+    "untitled",
+    "no purpose",
+    "不明",
+    "なし",
+];
+/// How refusals and apologies start.
+const REFUSALS: [&str; 6] = [
+    "sorry",
+    "i'm sorry",
+    "i cannot",
+    "i can't",
+    "i am unable",
+    "申し訳",
+];
+/// Requests that steer the session instead of saying what it is for.
+const ACKNOWLEDGEMENTS: [&str; 16] = [
+    "yes",
+    "y",
+    "no",
+    "ok",
+    "okay",
+    "continue",
+    "go on",
+    "go ahead",
+    "proceed",
+    "thanks",
+    "thank you",
+    "lgtm",
+    "はい",
+    "いいえ",
+    "続けて",
+    "お願いします",
+];
+
+/// The first usable title of: claude's purpose, the session summary, the
+/// last substantive user request, the commit message's first line, and
+/// finally the placeholder.
+pub fn choose_title(inputs: &TitleInputs, config: &Config) -> Title {
+    let last_request = || {
+        inputs
+            .requests
+            .iter()
+            .rev()
+            .filter(|request| substantive_request(request))
+            .find_map(|request| usable(request))
+    };
+    let commit = || usable(inputs.commit_message?.lines().next()?);
+    let chosen = inputs
+        .purpose
+        .and_then(usable)
+        .map(|text| (text, TitleSource::Purpose))
+        .or_else(|| Some((usable(inputs.summary?)?, TitleSource::Summary)))
+        .or_else(|| Some((last_request()?, TitleSource::Request)))
+        .or_else(|| Some((commit()?, TitleSource::Commit)));
+    match chosen {
+        Some((text, source)) => Title { text, source },
+        None => Title {
+            text: prompt::missing_purpose(config),
+            source: TitleSource::Placeholder,
+        },
+    }
+}
+
+/// `text` cleaned up as a title, unless nothing of it names anything.
+fn usable(text: &str) -> Option<String> {
+    let title = path::sanitize_purpose(text)?;
+    let key = normalized(&title);
+    let named = title.chars().any(char::is_alphanumeric)
+        && !NON_ANSWERS.contains(&key.as_str())
+        && !REFUSALS.iter().any(|refusal| key.starts_with(refusal));
+    named.then_some(title)
+}
+
+/// Whether a user request says something about the work, rather than being
+/// a slash command, command output the CLI recorded as a request, or a
+/// one-word nudge.
+pub fn substantive_request(request: &str) -> bool {
+    let request = request.trim();
+    let markup = request.starts_with('<') || request.starts_with('/');
+    let caveat = request.starts_with("Caveat:");
+    !markup && !caveat && !ACKNOWLEDGEMENTS.contains(&normalized(request).as_str())
+}
+
+fn normalized(text: &str) -> String {
+    text.trim()
+        .trim_end_matches(|c: char| c.is_ascii_punctuation() || matches!(c, '。' | '！' | '？'))
+        .trim()
+        .to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn requests(texts: &[&str]) -> Vec<String> {
+        texts.iter().map(|t| t.to_string()).collect()
+    }
+
+    fn choose(inputs: TitleInputs) -> (String, TitleSource) {
+        let title = choose_title(&inputs, &Config::default());
+        (title.text, title.source)
+    }
+
+    #[test]
+    fn choose_title_prefers_the_purpose() {
+        let asked = requests(&["Add retries to the client"]);
+        let inputs = TitleInputs {
+            purpose: Some("**Purpose**: Retry transient API errors\n"),
+            summary: Some("API client work"),
+            requests: &asked,
+            commit_message: Some("Add retry"),
+        };
+
+        assert_eq!(
+            choose(inputs),
+            (
+                "Retry transient API errors".to_string(),
+                TitleSource::Purpose
+            )
+        );
+    }
+
+    #[test]
+    fn choose_title_falls_back_to_the_summary() {
+        let asked = requests(&["Add retries to the client"]);
+        let inputs = TitleInputs {
+            purpose: Some("I'm sorry, but I can't determine the purpose."),
+            summary: Some("API client retry rework"),
+            requests: &asked,
+            commit_message: None,
+        };
+
+        assert_eq!(
+            choose(inputs),
+            ("API client retry rework".to_string(), TitleSource::Summary)
+        );
+    }
+
+    #[test]
+    fn choose_title_takes_the_last_substantive_request() {
+        let asked = requests(&[
+            "Add exponential backoff to the API client",
+            "<command-name>/compact</command-name>",
+            "/clear",
+            "Caveat: The messages below were generated by the user while running local commands.",
+            "ok",
+            "続けて",
+        ]);
+        let inputs = TitleInputs {
+            purpose: Some("\n  \n"),
+            summary: Some("N/A"),
+            requests: &asked,
+            commit_message: Some("WIP"),
+        };
+
+        assert_eq!(
+            choose(inputs),
+            (
+                "Add exponential backoff to the API client".to_string(),
+                TitleSource::Request
+            )
+        );
+    }
+
+    #[test]
+    fn choose_title_uses_the_commit_subject() {
+        let asked = requests(&["yes", "Thanks!"]);
+        let inputs = TitleInputs {
+            purpose: None,
+            summary: None,
+            requests: &asked,
+            commit_message: Some("Retry on 503\n\nThe gateway sheds load."),
+        };
+
+        assert_eq!(
+            choose(inputs),
+            ("Retry on 503".to_string(), TitleSource::Commit)
+        );
+    }
+
+    #[test]
+    fn choose_title_ends_at_the_placeholder() {
+        let title = choose_title(&TitleInputs::default(), &Config::default());
+        assert_eq!(title.text, "(目的抽出失敗)");
+        assert_eq!(title.source, TitleSource::Placeholder);
+        assert_eq!(title.purpose(), None);
+
+        let config = Config {
+            missing_purpose_text: Some("untitled change".to_string()),
+            ..Config::default()
+        };
+        let empty = requests(&["", "  "]);
+        let inputs = TitleInputs {
+            purpose: Some("--"),
+            summary: Some(""),
+            requests: &empty,
+            commit_message: Some(""),
+        };
+        assert_eq!(choose_title(&inputs, &config).text, "untitled change");
+    }
+
+    #[test]
+    fn usable_rejects_non_answers_and_keeps_japanese() {
+        assert_eq!(usable("Unknown."), None);
+        assert_eq!(usable("不明"), None);
+        assert_eq!(usable("```"), None);
+        assert_eq!(
+            usable("認証をJWTに移行").as_deref(),
+            Some("認証をJWTに移行")
+        );
+    }
+}
@@ -0,0 +1,217 @@
+//! `IDR: <path>` trailer in the commit message, so a record can be found
+//! from `git log`. Placement follows `git interpret-trailers`: the trailer
+//! joins the message's trailer block (or starts one after a blank line),
+//! above any trailing comments and the `>8` scissors line.
+
+use std::io;
+use std::path::Path;
+
+pub const KEY: &str = "IDR";
+
+/// `prepare-commit-msg` sources whose message git wrote for the user; the
+/// trailer would misdescribe a merge or a squash of several commits.
+pub fn leaves_alone(source: Option<&str>) -> bool {
+    matches!(source, Some("merge" | "squash"))
+}
+
+/// The trailer value for `record`: relative to `repo_root` when the record
+/// is inside the repository, absolute otherwise.
+pub fn value(record: &Path, repo_root: Option<&Path>) -> String {
+    let relative = repo_root.and_then(|root| record.strip_prefix(root).ok());
+    match relative {
+        Some(relative) => relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/"),
+        None => record.display().to_string(),
+    }
+}
+
+/// Adds `IDR: <value>` to the message in `file`. Returns `false`, leaving
+/// the file untouched, when the message already carries that trailer.
+pub fn apply(file: &Path, value: &str, comment_char: char) -> io::Result<bool> {
+    let message = std::fs::read_to_string(file)?;
+    match add(&message, value, comment_char) {
+        Some(updated) => std::fs::write(file, updated).map(|()| true),
+        None => Ok(false),
+    }
+}
+
+/// `message` with the trailer added, or `None` if it is already there.
+fn add(message: &str, value: &str, comment_char: char) -> Option<String> {
+    let trailer = format!("{KEY}: {value}");
+    let lines: Vec<&str> = message.split_inclusive('\n').collect();
+    let scissors = format!("{comment_char} ------------------------ >8 ------------------------");
+    let body_end = lines
+        .iter()
+        .position(|line| line.trim_end_matches(['\n', '\r']) == scissors)
+        .unwrap_or(lines.len());
+    let is_content = |line: &&str| !line.trim().is_empty() && !line.starts_with(comment_char);
+    let content_end = lines[..body_end]
+        .iter()
+        .rposition(is_content)
+        .map_or(0, |i| i + 1);
+
+    if content_end == 0 {
+        // No message yet: leave the first line free for the subject.
+        let rest: String = lines
+            .iter()
+            .skip_while(|line| line.trim().is_empty())
+            .copied()
+            .collect();
+        return Some(format!("\n{trailer}\n{rest}"));
+    }
+
+    let content = &lines[..content_end];
+    let paragraph_start = content
+        .iter()
+        .rposition(|line| line.trim().is_empty())
+        .map_or(0, |i| i + 1);
+    let paragraph = &content[paragraph_start..];
+    let has_trailer_block = paragraph_start > 0 && is_trailer_block(paragraph);
+    if has_trailer_block && paragraph.iter().any(|line| line.trim_end() == trailer) {
+        return None;
+    }
+
+    let mut updated: String = content.concat();
+    if !updated.ends_with('\n') {
+        updated.push('\n');
+    }
+    if !has_trailer_block {
+        updated.push('\n');
+    }
+    updated.push_str(&trailer);
+    updated.push('\n');
+    updated.extend(lines[content_end..].iter().copied());
+    Some(updated)
+}
+
+/// Every line is a `Token: value` trailer or indented continuation of one.
+fn is_trailer_block(paragraph: &[&str]) -> bool {
+    paragraph.first().is_some_and(|line| is_trailer(line))
+        && paragraph
+            .iter()
+            .all(|line| is_trailer(line) || line.starts_with([' ', '\t']))
+}
+
+fn is_trailer(line: &str) -> bool {
+    line.split_once(':').is_some_and(|(token, _)| {
+        !token.is_empty() && token.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    const VALUE: &str = "planning/2026-02-07/idr-03.md";
+
+    #[test]
+    fn add_starts_trailer_block_above_comments_and_scissors() {
+        let message = include_str!("../tests/fixtures/commit-msg-scissors.txt");
+
+        let updated = add(message, VALUE, '#').unwrap();
+
+        assert!(
+            updated.starts_with(
+                "Add retry with backoff\n\nRetry 5xx responses up to three times.\n\n\
+                 IDR: planning/2026-02-07/idr-03.md\n\n# Please enter"
+            ),
+            "{updated}"
+        );
+        assert!(updated.ends_with("+Signed-off-by: not a trailer <x@example.com>\n"));
+        assert_eq!(updated.matches("IDR:").count(), 1);
+    }
+
+    #[test]
+    fn add_skips_existing_trailer() {
+        let message = include_str!("../tests/fixtures/commit-msg-trailers.txt");
+        assert_eq!(add(message, VALUE, '#'), None);
+    }
+
+    #[test]
+    fn add_joins_existing_trailer_block() {
+        let message = include_str!("../tests/fixtures/commit-msg-trailers.txt");
+
+        let updated = add(message, "planning/2026-02-07/idr-04.md", '#').unwrap();
+
+        assert!(
+            updated.contains(
+                "Signed-off-by: Jane Doe <jane@example.com>\n\
+                 IDR: planning/2026-02-07/idr-03.md\n\
+                 IDR: planning/2026-02-07/idr-04.md\n\n# Please"
+            ),
+            "{updated}"
+        );
+    }
+
+    #[test]
+    fn add_to_empty_message_keeps_subject_line_free() {
+        let message = include_str!("../tests/fixtures/commit-msg-empty.txt");
+
+        let updated = add(message, VALUE, '#').unwrap();
+
+        assert!(
+            updated.starts_with("\nIDR: planning/2026-02-07/idr-03.md\n# Please enter"),
+            "{updated}"
+        );
+    }
+
+    #[test]
+    fn add_does_not_take_subject_for_a_trailer_block() {
+        assert_eq!(
+            add("Fixes: 12", VALUE, '#').unwrap(),
+            "Fixes: 12\n\nIDR: planning/2026-02-07/idr-03.md\n"
+        );
+    }
+
+    #[test]
+    fn add_respects_comment_char() {
+        let message = "Subject\n\n; comment\n; ------------------------ >8 ------------------------\nBody: x\n";
+
+        assert_eq!(
+            add(message, VALUE, ';').unwrap(),
+            "Subject\n\nIDR: planning/2026-02-07/idr-03.md\n\n; comment\n\
+             ; ------------------------ >8 ------------------------\nBody: x\n"
+        );
+    }
+
+    #[test]
+    fn value_is_relative_inside_repository() {
+        let root = PathBuf::from("/repo");
+        assert_eq!(
+            value(&root.join("planning/2026-02-07/idr-03.md"), Some(&root)),
+            VALUE
+        );
+        assert_eq!(
+            value(Path::new("/home/me/idr/idr-03.md"), Some(&root)),
+            "/home/me/idr/idr-03.md"
+        );
+        assert_eq!(value(Path::new("/w/idr-03.md"), None), "/w/idr-03.md");
+    }
+
+    #[test]
+    fn leaves_merge_and_squash_messages_alone() {
+        assert!(leaves_alone(Some("merge")));
+        assert!(leaves_alone(Some("squash")));
+        assert!(!leaves_alone(Some("message")));
+        assert!(!leaves_alone(Some("template")));
+        assert!(!leaves_alone(None));
+    }
+
+    #[test]
+    fn apply_rewrites_file_once() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let file = dir.path().join("COMMIT_EDITMSG");
+        std::fs::write(&file, "Subject\n").unwrap();
+
+        assert!(apply(&file, VALUE, '#').unwrap());
+        assert!(!apply(&file, VALUE, '#').unwrap());
+        assert_eq!(
+            std::fs::read_to_string(&file).unwrap(),
+            "Subject\n\nIDR: planning/2026-02-07/idr-03.md\n"
+        );
+    }
+}
@@ -0,0 +1,229 @@
+//! Bilingual records (`language: "ja+en"`): the body is written in the
+//! first language, then claude translates the finished body into the
+//! second, which goes into the same file or into one next to it.
+
+use crate::claude::{self, ClaudeResult};
+use crate::config::{BilingualLayout, Config, Format, OutputMode};
+use crate::error::IdrError;
+use crate::notes;
+use crate::path;
+use crate::prompt;
+use crate::validate;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A translated record body.
+#[derive(Debug)]
+pub struct Translation {
+    /// The title, when claude kept the `# ` line it was given.
+    pub title: Option<String>,
+    pub body: String,
+    pub result: ClaudeResult,
+}
+
+/// Translates `title` and `body` from `config.language` into `target` with
+/// `translation_model` (the body model when unset).
+pub fn run(
+    title: &str,
+    body: &str,
+    target: &str,
+    config: &Config,
+) -> Result<Translation, IdrError> {
+    let document = format!("# {title}\n\n{}", body.trim());
+    let translation_config = Config {
+        model: config
+            .translation_model
+            .clone()
+            .unwrap_or_else(|| config.model.clone()),
+        ..config.clone()
+    };
+    let result = claude::run(
+        &prompt::build_translation_prompt(&document, target, config),
+        &translation_config,
+    )?;
+    let text = validate::strip_outer_fence(&result.text);
+    let text = text.trim();
+    if text.is_empty() {
+        return Err(IdrError::ClaudeFailed {
+            reason: "empty translation".to_string(),
+            stderr: String::new(),
+        });
+    }
+    let (title, body) = match text.strip_prefix("# ") {
+        Some(rest) => {
+            let (line, body) = rest.split_once('\n').unwrap_or((rest, ""));
+            (Some(line.trim().to_string()), body.trim().to_string())
+        }
+        None => (None, text.to_string()),
+    };
+    Ok(Translation {
+        title,
+        body,
+        result,
+    })
+}
+
+/// Whether the translation goes into a file of its own: `bilingual_layout:
+/// "files"` where records are files in the output directory. Journals and
+/// notes always get both languages in one record.
+pub fn separate_file(config: &Config) -> bool {
+    config.bilingual_layout == BilingualLayout::Files
+        && config.output_mode == OutputMode::Files
+        && !notes::replaces_files(config)
+}
+
+/// `## 日本語` / `## English` and so on.
+pub fn section_heading(language: &str) -> &str {
+    match language {
+        "ja" => "日本語",
+        "en" => "English",
+        other => other,
+    }
+}
+
+/// `body` and its translation as the record's two top-level sections.
+pub fn sections(language: &str, body: &str, target: &str, translated: &str) -> String {
+    format!(
+        "## {}\n\n{}\n\n## {}\n\n{}",
+        section_heading(language),
+        body.trim(),
+        section_heading(target),
+        translated.trim()
+    )
+}
+
+/// `idr-07-use-jwt.md` -> `idr-07-use-jwt.en.md`. The dot keeps the copy
+/// from being read as a record of its own.
+pub fn file_name(record: &str, target: &str) -> String {
+    let stem = record.strip_suffix(".md").unwrap_or(record);
+    format!("{stem}.{target}.md")
+}
+
+/// Whether `name` is a translation written next to a record.
+pub fn is_file_name(name: &str) -> bool {
+    name.strip_suffix(".md")
+        .and_then(|stem| stem.rsplit_once('.'))
+        .is_some_and(|(record, _)| {
+            path::record_number(&format!("{record}.md"), Format::Idr).is_some()
+        })
+}
+
+/// Translations written next to `record`, with their languages.
+pub fn existing(record: &Path) -> Vec<(PathBuf, String)> {
+    let Some(stem) = record.file_stem().and_then(|stem| stem.to_str()) else {
+        return Vec::new();
+    };
+    let dir = record.parent().unwrap_or(Path::new("."));
+    let mut found: Vec<(PathBuf, String)> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let language = name
+                .strip_prefix(stem)?
+                .strip_prefix('.')?
+                .strip_suffix(".md")?;
+            let plain = !language.is_empty() && !language.contains('.');
+            plain.then(|| (entry.path(), language.to_string()))
+        })
+        .collect();
+    found.sort();
+    found
+}
+
+/// The translation file's text: the title, a link back to `record`, and
+/// the translated body.
+pub fn document(title: &str, record: &str, translation: &Translation) -> String {
+    let title = translation.title.as_deref().unwrap_or(title);
+    format!(
+        "# IDR: {title}\n\n> [{record}]({record})\n\n{}\n",
+        translation.body
+    )
+}
+
+/// Writes the translation of `record` next to it.
+pub fn write(
+    record: &Path,
+    title: &str,
+    target: &str,
+    translation: &Translation,
+) -> io::Result<PathBuf> {
+    let name = record
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let path = record.with_file_name(file_name(&name, target));
+    fs::write(&path, document(title, &name, translation))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::OutputMode;
+
+    fn translation(title: Option<&str>, body: &str) -> Translation {
+        Translation {
+            title: title.map(str::to_string),
+            body: body.to_string(),
+            result: ClaudeResult {
+                text: String::new(),
+                usage: None,
+                cost_usd: None,
+                session_id: None,
+                model: "haiku".to_string(),
+            },
+        }
+    }
+
+    #[test]
+    fn sections_put_each_language_under_its_heading() {
+        let body = "## 変更概要\n\nリトライを追加。\n";
+        let translated = "## Summary of changes\n\nAdds retries.";
+
+        assert_eq!(
+            sections("ja", body, "en", translated),
+            "## 日本語\n\n## 変更概要\n\nリトライを追加。\n\n## English\n\n## Summary of changes\n\nAdds retries."
+        );
+        assert_eq!(section_heading("fr"), "fr");
+    }
+
+    #[test]
+    fn file_name_and_document_link_back_to_the_record() {
+        assert_eq!(file_name("idr-07-use-jwt.md", "en"), "idr-07-use-jwt.en.md");
+        assert!(is_file_name("idr-07-use-jwt.en.md"));
+        assert!(is_file_name("idr-07.ja.md"));
+        assert!(!is_file_name("idr-07-use-jwt.md"));
+        assert!(!is_file_name("notes.en.md"));
+        assert_eq!(
+            document(
+                "JWTに移行",
+                "idr-07-use-jwt.md",
+                &translation(Some("Move to JWT"), "## Summary of changes\n\nJWT.")
+            ),
+            "# IDR: Move to JWT\n\n> [idr-07-use-jwt.md](idr-07-use-jwt.md)\n\n## Summary of changes\n\nJWT.\n"
+        );
+        assert!(
+            document("JWTに移行", "idr-07.md", &translation(None, "body"))
+                .starts_with("# IDR: JWTに移行\n")
+        );
+    }
+
+    #[test]
+    fn separate_file_only_for_files_in_the_output_directory() {
+        let files = Config {
+            bilingual_layout: BilingualLayout::Files,
+            ..Config::default()
+        };
+        let monthly = Config {
+            output_mode: OutputMode::Monthly,
+            ..files.clone()
+        };
+
+        assert!(separate_file(&files));
+        assert!(!separate_file(&monthly));
+        assert!(!separate_file(&Config::default()));
+    }
+}
@@ -0,0 +1,295 @@
+//! Checks that claude answered an IDR prompt with a record, not a refusal,
+//! loose prose, or a record wrapped in a code fence, and asks once more
+//! when it did not.
+
+use crate::claude::ClaudeResult;
+use crate::config::{Config, Format};
+use crate::error::IdrError;
+use crate::log::warn;
+use crate::prompt::{self, Headings};
+
+/// Runs `call` on `prompt` and checks the response. A malformed one is
+/// asked for again once, with what was wrong appended to the prompt; a
+/// second malformed response is an error. `files_changed` says whether the
+/// prompt's diff touched any file, so per-file sections are expected.
+pub fn run(
+    prompt: &str,
+    config: &Config,
+    files_changed: bool,
+    call: impl Fn(&str) -> Result<ClaudeResult, IdrError>,
+) -> Result<ClaudeResult, IdrError> {
+    let first = call(prompt)?;
+    let Some(problem) = problem(&first.text, config, files_changed) else {
+        return Ok(first);
+    };
+    warn!("malformed response ({problem}), asking again");
+    let mut second = call(&format!("{prompt}{}", correction(&problem)))?;
+    if let Some(problem) = self::problem(&second.text, config, files_changed) {
+        return Err(IdrError::claude(format!("malformed response: {problem}")));
+    }
+    second.usage = match (first.usage, second.usage) {
+        (Some(a), Some(b)) => Some(a + b),
+        (a, b) => a.or(b),
+    };
+    second.cost_usd = match (first.cost_usd, second.cost_usd) {
+        (Some(a), Some(b)) => Some(a + b),
+        (a, b) => a.or(b),
+    };
+    Ok(second)
+}
+
+fn correction(problem: &str) -> String {
+    format!(
+        "\n\n<system>\nYour previous response was rejected: {problem}. Reply with the record itself in the format above, starting with its `##` headings, with no surrounding code fence and no other text.\n</system>"
+    )
+}
+
+/// What keeps `response` from being a record, or `None` when it has the
+/// structure the prompt asks for. Section names are accepted in either
+/// built-in language as well as the configured ones.
+pub fn problem(response: &str, config: &Config, files_changed: bool) -> Option<String> {
+    let body = strip_outer_fence(response);
+    let headings: Vec<String> = body.lines().filter_map(heading).collect();
+    if config.format == Format::Madr {
+        return (!headings.iter().any(|h| h == "Decision Outcome"))
+            .then(|| "no \"Decision Outcome\" section".to_string());
+    }
+    let sets = heading_sets(config);
+    if !sets.iter().any(|set| headings.contains(&set.summary)) {
+        let names: Vec<&str> = sets.iter().map(|set| set.summary.as_str()).collect();
+        return Some(format!("no {} section", names.join("/")));
+    }
+    if !files_changed {
+        return None;
+    }
+    if !body.lines().any(|l| l.starts_with("### ")) {
+        return Some("no per-file `###` heading".to_string());
+    }
+    let rationale = |line: &str| {
+        sets.iter().any(|set| {
+            let label = &set.rationale;
+            let item = line.trim_start_matches(['-', '*', ' ']);
+            line.contains(&format!("**{label}**"))
+                || item.starts_with(&format!("{label}:"))
+                || item.starts_with(&format!("{label}："))
+        })
+    };
+    if !body.lines().any(rationale) {
+        let names: Vec<&str> = sets.iter().map(|set| set.rationale.as_str()).collect();
+        return Some(format!("no {} line", names.join("/")));
+    }
+    None
+}
+
+/// The configured headings, then the built-in ones of both languages.
+fn heading_sets(config: &Config) -> Vec<Headings> {
+    let builtin = |language: &str| {
+        prompt::headings(&Config {
+            language: language.to_string(),
+            ..Config::default()
+        })
+    };
+    let mut sets = vec![prompt::headings(config)];
+    for set in [builtin("ja"), builtin("en")] {
+        if !sets.contains(&set) {
+            sets.push(set);
+        }
+    }
+    sets
+}
+
+/// The text of a markdown heading line, without `#`s or bold markers.
+fn heading(line: &str) -> Option<String> {
+    let text = line.trim_end().strip_prefix('#')?.trim_start_matches('#');
+    text.starts_with(' ')
+        .then(|| text.trim().trim_matches('*').trim().to_string())
+}
+
+/// `response` without a ```` ```markdown ```` (or bare ```` ``` ````) fence
+/// wrapped around all of it, ending where the record ends as an unwrapped
+/// response does. A leading `<type>` line stays in front.
+pub fn strip_outer_fence(response: &str) -> String {
+    let lines: Vec<&str> = response.lines().collect();
+    let start = match lines.first() {
+        Some(first) if first.trim_start().starts_with("<type>") => 1,
+        _ => 0,
+    };
+    let mut content = start;
+    while lines.get(content).is_some_and(|l| l.trim().is_empty()) {
+        content += 1;
+    }
+    let Some(mut end) = lines.iter().rposition(|l| !l.trim().is_empty()) else {
+        return response.to_string();
+    };
+    let opens = lines
+        .get(content)
+        .map(|l| l.trim())
+        .is_some_and(|l| matches!(l, "```" | "```markdown" | "```md"));
+    if !opens || end <= content || lines[end].trim() != "```" {
+        return response.to_string();
+    }
+    end -= 1;
+    let mut out: Vec<&str> = lines[..start].to_vec();
+    out.extend(&lines[content + 1..=end]);
+    out.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    const VALID_JA: &str = "\
+## 変更概要
+
+リトライを追加した。
+
+## 主要な変更
+
+### [src/api.rs](src/api.rs)
+
+#### L10-20: リトライ
+
+```diff
++retry();
+```
+
+**理由**: 一時的な 5xx を吸収するため
+";
+
+    const VALID_EN: &str = "\
+## Summary of changes
+
+Adds retries.
+
+## Key changes
+
+### [src/api.rs](src/api.rs)
+
+#### L10-20: Retry
+
+- **Rationale**: absorb transient 5xx responses
+";
+
+    fn result(text: &str) -> ClaudeResult {
+        ClaudeResult {
+            text: text.to_string(),
+            usage: None,
+            cost_usd: None,
+            session_id: None,
+            model: "sonnet".to_string(),
+        }
+    }
+
+    #[test]
+    fn problem_accepts_records_in_either_language() {
+        let ja = Config::default();
+        let en = Config {
+            language: "en".to_string(),
+            ..Config::default()
+        };
+
+        assert_eq!(problem(VALID_JA, &ja, true), None);
+        assert_eq!(problem(VALID_EN, &ja, true), None);
+        assert_eq!(problem(VALID_JA, &en, true), None);
+        let typed = format!("<type>feat</type>\n{VALID_EN}");
+        assert_eq!(problem(&typed, &en, true), None);
+    }
+
+    #[test]
+    fn problem_rejects_refusals_and_prose() {
+        let config = Config::default();
+
+        let refusal = problem("I'm sorry, but I can't help with that.", &config, true).unwrap();
+        assert_eq!(refusal, "no 変更概要/Summary of changes section");
+        let prose = "The diff adds a retry loop to the API client.\n";
+        assert!(problem(prose, &config, true).is_some());
+    }
+
+    #[test]
+    fn problem_wants_files_and_rationale_only_when_files_changed() {
+        let config = Config::default();
+        let summary_only = "## 変更概要\n\nリトライを追加した。\n";
+
+        assert_eq!(
+            problem(summary_only, &config, true).unwrap(),
+            "no per-file `###` heading"
+        );
+        assert_eq!(problem(summary_only, &config, false), None);
+        let no_rationale = VALID_JA.replace("**理由**", "理由なし");
+        assert_eq!(
+            problem(&no_rationale, &config, true).unwrap(),
+            "no 理由/Rationale line"
+        );
+    }
+
+    #[test]
+    fn problem_follows_configured_headings_and_madr() {
+        let mut config = Config::default();
+        config.section_headings.summary = Some("Overview".to_string());
+        let custom = VALID_EN.replace("Summary of changes", "Overview");
+        assert_eq!(problem(&custom, &config, true), None);
+
+        let madr = Config {
+            format: Format::Madr,
+            ..Config::default()
+        };
+        assert!(problem(VALID_EN, &madr, true).is_some());
+        let decision = "## Context and Problem Statement\n\nx\n\n## Decision Outcome\n\ny\n";
+        assert_eq!(problem(decision, &madr, true), None);
+    }
+
+    #[test]
+    fn strip_outer_fence_unwraps_fenced_records() {
+        let fenced = format!("```markdown\n{VALID_EN}```\n");
+        assert_eq!(strip_outer_fence(&fenced), VALID_EN.trim_end());
+        assert_eq!(problem(&fenced, &Config::default(), true), None);
+
+        let typed = format!("<type>fix</type>\n\n```\n{VALID_EN}```");
+        assert_eq!(
+            strip_outer_fence(&typed),
+            format!("<type>fix</type>\n{}", VALID_EN.trim_end())
+        );
+        // Fences inside the record are left alone.
+        assert_eq!(strip_outer_fence(VALID_JA), VALID_JA);
+        assert_eq!(
+            strip_outer_fence("```diff\n+x\n```\n"),
+            "```diff\n+x\n```\n"
+        );
+    }
+
+    #[test]
+    fn run_asks_again_once_with_the_problem() {
+        let config = Config::default();
+        let prompts = RefCell::new(Vec::new());
+        let answers = RefCell::new(vec![VALID_JA, "Sorry, I cannot do that."]);
+        let call = |prompt: &str| {
+            prompts.borrow_mut().push(prompt.to_string());
+            Ok(result(answers.borrow_mut().pop().unwrap()))
+        };
+
+        let answer = run("idr prompt", &config, true, call).unwrap();
+        assert_eq!(answer.text, VALID_JA);
+        let prompts = prompts.take();
+        assert_eq!(prompts.len(), 2);
+        assert!(prompts[1].starts_with("idr prompt"));
+        assert!(prompts[1].contains("rejected: no 変更概要/Summary of changes section"));
+    }
+
+    #[test]
+    fn run_fails_after_a_second_malformed_response() {
+        let config = Config::default();
+        let calls = RefCell::new(0);
+        let call = |_: &str| {
+            *calls.borrow_mut() += 1;
+            Ok(result("Sorry."))
+        };
+
+        let error = run("idr prompt", &config, true, call).unwrap_err();
+        assert!(
+            error.to_string().starts_with("malformed response: "),
+            "{error}"
+        );
+        assert_eq!(calls.take(), 2);
+    }
+}
@@ -1,9 +1,14 @@
-use assert_cmd::Command;
+#[cfg(unix)]
+mod common;
+
+use assert_cmd::cargo::cargo_bin_cmd;
+#[cfg(unix)]
+use common::{RECORD_BODY, Scratch};
 use predicates::prelude::*;
 
 #[test]
 fn help_flag_shows_help_text() {
-    let mut cmd = Command::cargo_bin("claude-idr").unwrap();
+    let mut cmd = cargo_bin_cmd!("claude-idr");
     cmd.arg("--help");
     cmd.assert()
         .success()
@@ -12,44 +17,845 @@ fn help_flag_shows_help_text() {
 
 #[test]
 fn version_flag_shows_version() {
-    let mut cmd = Command::cargo_bin("claude-idr").unwrap();
+    let mut cmd = cargo_bin_cmd!("claude-idr");
     cmd.arg("--version");
     cmd.assert()
         .success()
         .stdout(predicate::str::contains(env!("CARGO_PKG_VERSION")));
 }
 
+/// `claude-idr` for a generation run in the real environment, with the run
+/// history kept in `state` rather than the user's state directory.
+fn generation(state: &tempfile::TempDir) -> assert_cmd::Command {
+    let mut cmd = cargo_bin_cmd!("claude-idr");
+    cmd.env("XDG_STATE_HOME", state.path());
+    cmd
+}
+
 #[test]
 fn exits_zero_when_no_staged_diff() {
-    let mut cmd = Command::cargo_bin("claude-idr").unwrap();
-    cmd.assert()
-        .success()
-        .stderr(
-            predicate::str::contains("no staged changes")
-                .or(predicate::str::contains("no code changes via Claude detected"))
-                .or(predicate::str::contains("no recent session")),
-        );
+    let state = tempfile::TempDir::new().unwrap();
+    let mut cmd = generation(&state);
+    cmd.assert().success().stderr(
+        predicate::str::contains("no staged changes")
+            .or(predicate::str::contains(
+                "no code changes via Claude detected",
+            ))
+            .or(predicate::str::contains("no recent session")),
+    );
 }
 
 #[test]
 fn dry_run_flag_prevents_claude_call() {
-    let mut cmd = Command::cargo_bin("claude-idr").unwrap();
+    let state = tempfile::TempDir::new().unwrap();
+    let mut cmd = generation(&state);
     cmd.arg("--dry-run");
     cmd.assert().success();
 }
 
 #[test]
 fn dry_run_outputs_prompt_when_session_and_diff_available() {
-    let mut cmd = Command::cargo_bin("claude-idr").unwrap();
+    let state = tempfile::TempDir::new().unwrap();
+    let mut cmd = generation(&state);
     cmd.arg("--dry-run");
     // dry-run always succeeds; when session+diff are present it prints the prompt,
     // otherwise it exits early with a skip message — both are valid outcomes.
+    cmd.assert().success().stderr(
+        predicate::str::contains("dry-run mode")
+            .or(predicate::str::contains("no staged changes"))
+            .or(predicate::str::contains(
+                "no code changes via Claude detected",
+            ))
+            .or(predicate::str::contains("no recent session")),
+    );
+}
+
+#[test]
+fn json_flag_prints_run_report() {
+    let state = tempfile::TempDir::new().unwrap();
+    let mut cmd = generation(&state);
+    cmd.args(["--json", "--dry-run"]);
     cmd.assert()
         .success()
-        .stderr(
-            predicate::str::contains("dry-run mode")
-                .or(predicate::str::contains("no staged changes"))
-                .or(predicate::str::contains("no code changes via Claude detected"))
-                .or(predicate::str::contains("no recent session")),
+        .stdout(predicate::str::starts_with(r#"{"status":""#));
+}
+
+#[cfg(unix)]
+#[test]
+fn sigint_kills_claude_child_and_exits_130() {
+    use std::process::{Command, Stdio};
+    use std::time::{Duration, Instant};
+
+    let scratch = Scratch::new(serde_json::json!({}));
+    let pids = scratch.dir.path().join("pids");
+    scratch.fake_claude(&format!("echo $$ >> {}\nsleep 30\n", pids.display()));
+
+    let mut child = scratch
+        .command()
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let started = Instant::now();
+    let fake_pids = loop {
+        let pids: Vec<i32> = std::fs::read_to_string(&pids)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|l| l.trim().parse().ok())
+            .collect();
+        if pids.len() == 2 {
+            break pids;
+        }
+        assert!(
+            started.elapsed() < Duration::from_secs(10),
+            "fake claude never started"
         );
+        std::thread::sleep(Duration::from_millis(50));
+    };
+
+    unsafe { libc::kill(child.id() as i32, libc::SIGINT) };
+    let status = child.wait().unwrap();
+    assert_eq!(status.code(), Some(130));
+
+    let is_running = |pid: i32| {
+        let out = Command::new("ps")
+            .args(["-o", "stat=", "-p", &pid.to_string()])
+            .output()
+            .unwrap();
+        let stat = String::from_utf8_lossy(&out.stdout);
+        !stat.trim().is_empty() && !stat.trim().starts_with('Z')
+    };
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while fake_pids.iter().any(|&p| is_running(p)) && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    for pid in fake_pids {
+        assert!(!is_running(pid), "fake claude {pid} still running");
+    }
+    let written = std::fs::read_dir(scratch.out())
+        .map(|entries| entries.count())
+        .unwrap_or(0);
+    assert_eq!(written, 0, "no partial IDR left behind");
+    assert_eq!(leftovers(&scratch), Vec::<String>::new());
+}
+
+/// Run locks (kept in `XDG_RUNTIME_DIR`, the scratch directory), directory
+/// locks, and empty records an interrupted run would have left behind.
+#[cfg(unix)]
+fn leftovers(scratch: &Scratch) -> Vec<String> {
+    let names = |dir: &std::path::Path| -> Vec<(String, u64)> {
+        std::fs::read_dir(dir)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .map(|e| {
+                        let len = e.metadata().map(|m| m.len()).unwrap_or(0);
+                        (e.file_name().to_string_lossy().into_owned(), len)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    let run_locks = names(scratch.dir.path())
+        .into_iter()
+        .filter(|(name, _)| name.starts_with("claude-idr-") && name.ends_with(".lock"));
+    let in_out = names(&scratch.out()).into_iter().filter(|(name, len)| {
+        name.starts_with(".idr.lock") || (name.ends_with(".md") && *len == 0)
+    });
+    run_locks.chain(in_out).map(|(name, _)| name).collect()
+}
+
+#[cfg(unix)]
+#[test]
+fn sigint_while_writing_removes_locks_and_empty_records() {
+    use std::time::{Duration, Instant};
+
+    let scratch = Scratch::new(serde_json::json!({"max_retries": 0}));
+    std::fs::write(scratch.repo().join("b.txt"), "second\n").unwrap();
+    scratch.git(&["add", "b.txt"]);
+    std::fs::create_dir_all(scratch.out()).unwrap();
+    // The first record is filled through `<name>.<pid>.tmp`; a FIFO there
+    // blocks the write with the directory lock held and the block claimed.
+    let fifo = scratch.out().join("idr-02-a-txt.$PPID.tmp");
+    scratch.fake_claude(&format!(
+        "cat > /dev/null\nmkfifo \"{}\" 2>/dev/null\nprintf '## 変更概要\\n\\nDone.\\n'\n",
+        fifo.display()
+    ));
+
+    let mut child = scratch
+        .command()
+        .arg("--split-by-file")
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let lock = scratch.out().join(".idr.lock");
+    let umbrella = scratch.out().join("idr-01.md");
+    let started = Instant::now();
+    while !(lock.exists() && umbrella.exists()) {
+        assert!(
+            started.elapsed() < Duration::from_secs(10),
+            "the write never started"
+        );
+        std::thread::sleep(Duration::from_millis(20));
+    }
+
+    unsafe { libc::kill(child.id() as i32, libc::SIGINT) };
+    let status = child.wait().unwrap();
+    assert_eq!(status.code(), Some(130));
+    assert_eq!(leftovers(&scratch), Vec::<String>::new());
+}
+
+#[test]
+fn index_subcommand_rebuilds_index_for_directory() {
+    let dir = tempfile::TempDir::new().unwrap();
+    std::fs::write(
+        dir.path().join("idr-01-use-jwt.md"),
+        "# IDR: Use JWT\n\n> 2026-02-07 14:30\n\nbody\n",
+    )
+    .unwrap();
+
+    let mut cmd = cargo_bin_cmd!("claude-idr");
+    cmd.arg("index").arg(dir.path());
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("index written"));
+
+    let index = std::fs::read_to_string(dir.path().join("index.md")).unwrap();
+    assert!(index.contains("| 1 | 2026-02-07 | [Use JWT](idr-01-use-jwt.md) |  |"));
+}
+
+#[test]
+fn export_subcommand_writes_html_pages() {
+    let dir = tempfile::TempDir::new().unwrap();
+    std::fs::write(
+        dir.path().join("idr-01-use-jwt.md"),
+        "# IDR: Use JWT\n\n> 2026-02-07 14:30\n\nbody\n",
+    )
+    .unwrap();
+
+    let mut cmd = cargo_bin_cmd!("claude-idr");
+    cmd.arg("export").arg(dir.path());
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("exported 1 file to"));
+    let page = std::fs::read_to_string(dir.path().join("export/idr-01-use-jwt.html")).unwrap();
+    assert!(page.contains("<title>Use JWT</title>"), "{page}");
+
+    let mut cmd = cargo_bin_cmd!("claude-idr");
+    cmd.args(["export", "--format", "pdf"]).arg(dir.path());
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("unknown export format: pdf"));
+}
+
+#[test]
+fn digest_subcommand_without_llm_writes_outline() {
+    let dir = tempfile::TempDir::new().unwrap();
+    for (name, date) in [
+        ("idr-01-old.md", "2026-01-20"),
+        ("idr-02-use-jwt.md", "2026-02-07"),
+    ] {
+        std::fs::write(
+            dir.path().join(name),
+            format!("# IDR: {name}\n\n> {date} 14:30\n\n## 変更概要\n\nbody of {name}\n"),
+        )
+        .unwrap();
+    }
+
+    let mut cmd = cargo_bin_cmd!("claude-idr");
+    cmd.args([
+        "digest",
+        "--since",
+        "2026-02-01",
+        "--until",
+        "2026-02-14",
+        "--no-llm",
+    ])
+    .arg(dir.path());
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("digest written"));
+
+    let digest =
+        std::fs::read_to_string(dir.path().join("digest-2026-02-01_2026-02-14.md")).unwrap();
+    assert!(digest.contains("body of idr-02-use-jwt.md"), "{digest}");
+    assert!(!digest.contains("idr-01-old"), "{digest}");
+
+    let mut cmd = cargo_bin_cmd!("claude-idr");
+    cmd.args(["digest", "--no-llm"]).arg(dir.path());
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("digest: missing --since"));
+}
+
+#[cfg(unix)]
+#[test]
+fn digest_subcommand_asks_claude_for_narrative() {
+    let scratch = Scratch::new(serde_json::json!({}));
+    scratch.respond(
+        &[("digest of the decisions", "## Themes\n\nAuth.")],
+        "unexpected prompt",
+    );
+    let dir = scratch.repo().join("docs");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("idr-01-use-jwt.md"),
+        "# IDR: Use JWT\n\n> 2026-02-07 14:30\n\n## 変更概要\n\nStateless auth.\n",
+    )
+    .unwrap();
+
+    assert_cmd::Command::from_std(scratch.subcommand(&["digest", "docs", "--since", "2026-02-01"]))
+        .assert()
+        .success();
+
+    let prompts = scratch.prompts();
+    assert_eq!(prompts.len(), 1);
+    assert!(
+        prompts[0].contains("Use JWT\n\nStateless auth."),
+        "{}",
+        prompts[0]
+    );
+    let written: Vec<_> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().into_string().unwrap())
+        .filter(|name| name.starts_with("digest-2026-02-01_"))
+        .collect();
+    assert_eq!(written.len(), 1);
+    let digest = std::fs::read_to_string(dir.join(&written[0])).unwrap();
+    assert!(
+        digest.ends_with(
+            "## Themes\n\nAuth.\n\n## Records\n\n- 2026-02-07 [Use JWT](idr-01-use-jwt.md)\n"
+        ),
+        "{digest}"
+    );
+}
+
+#[test]
+fn search_subcommand_prints_ranked_hits() {
+    let fixtures = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/search");
+
+    let mut cmd = cargo_bin_cmd!("claude-idr");
+    cmd.args(["search", "backoff", "--title-only", "--dir", fixtures]);
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let output = String::from_utf8(output).unwrap();
+    assert!(
+        output.contains(
+            "idr-01-add-retry.md\n  Add retry with exponential backoff (2026-02-03 10:00)\n  \
+             Add retry with exponential backoff\n"
+        ),
+        "{output}"
+    );
+    assert_eq!(output.lines().count(), 6, "{output}");
+
+    let mut cmd = cargo_bin_cmd!("claude-idr");
+    cmd.args([
+        "search", "backoff", "--type", "docs", "--json", "--dir", fixtures,
+    ]);
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let hits: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(hits.as_array().unwrap().len(), 1);
+    assert_eq!(hits[0]["field"], "body");
+    assert_eq!(hits[0]["change_type"], "docs");
+}
+
+#[test]
+fn show_subcommand_prints_record_or_path() {
+    let dir = tempfile::TempDir::new().unwrap();
+    std::fs::write(dir.path().join("idr-07-use-jwt.md"), "# IDR: Use JWT\n").unwrap();
+    std::fs::write(dir.path().join("idr-09.md"), "# IDR: Later\n").unwrap();
+    let config = dir.path().join("config.json");
+    std::fs::write(
+        &config,
+        format!(r#"{{"output_dir": "{}"}}"#, dir.path().display()),
+    )
+    .unwrap();
+    let config = config.to_str().unwrap();
+
+    let mut cmd = cargo_bin_cmd!("claude-idr");
+    cmd.args(["show", "7", "--config", config]);
+    cmd.assert().success().stdout("# IDR: Use JWT\n");
+
+    let mut cmd = cargo_bin_cmd!("claude-idr");
+    cmd.args(["show", "latest", "--path", "--config", config]);
+    cmd.assert()
+        .success()
+        .stdout(format!("{}\n", dir.path().join("idr-09.md").display()));
+
+    let mut cmd = cargo_bin_cmd!("claude-idr");
+    cmd.args(["show", "8", "--config", config]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("(nearest: 7, 9)"));
+}
+
+#[test]
+fn clean_subcommand_archives_old_date_directories() {
+    let workspace = tempfile::TempDir::new().unwrap();
+    let planning = workspace.path().join("planning");
+    for (dir, file) in [
+        ("2020-01-01", "idr-01.md"),
+        ("2020-01-02", "notes.txt"),
+        ("2999-01-01", "idr-01.md"),
+    ] {
+        std::fs::create_dir_all(planning.join(dir)).unwrap();
+        std::fs::write(planning.join(dir).join(file), "x").unwrap();
+    }
+    let config = workspace.path().join("config.json");
+    std::fs::write(
+        &config,
+        format!(r#"{{"workspace_dir": "{}"}}"#, workspace.path().display()),
+    )
+    .unwrap();
+
+    let mut cmd = cargo_bin_cmd!("claude-idr");
+    cmd.args(["clean", "--older-than", "90d", "--config"])
+        .arg(&config);
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("contains non-IDR file notes.txt"))
+        .stderr(predicate::str::contains("archived 1 directory, skipped 1"));
+
+    assert!(planning.join("archive/2020/2020-01-01/idr-01.md").exists());
+    assert!(planning.join("2020-01-02").exists());
+    assert!(planning.join("2999-01-01").exists());
+}
+
+#[test]
+fn config_subcommand_sets_gets_and_unsets_keys() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let config = dir.path().join("nested/config.json");
+    let run = |args: &[&str]| {
+        let mut cmd = cargo_bin_cmd!("claude-idr");
+        cmd.args(args).arg("--config").arg(&config);
+        cmd.assert()
+    };
+
+    run(&["config", "get", "model"])
+        .success()
+        .stdout("sonnet (default)\n");
+    run(&["config", "set", "model", "haiku"]).success();
+    std::fs::write(
+        &config,
+        "{\n  \"future_key\": [1, 2],\n  \"model\": \"haiku\"\n}\n",
+    )
+    .unwrap();
+    run(&["config", "set", "strip_noise", "off"]).success();
+    run(&["config", "set", "model", "opus"]).success();
+    assert_eq!(
+        std::fs::read_to_string(&config).unwrap(),
+        "{\n  \"future_key\": [1, 2],\n  \"model\": \"opus\",\n  \"strip_noise\": false\n}\n"
+    );
+    run(&["config", "get", "model"])
+        .success()
+        .stdout(format!("opus ({})\n", config.display()));
+
+    run(&["config", "unset", "model"]).success();
+    run(&["config", "get", "model"])
+        .success()
+        .stdout("sonnet (default)\n");
+    run(&["config", "set", "max_diff_lines", "many"])
+        .failure()
+        .stderr(predicate::str::contains("takes a number"));
+    run(&["config", "set", "modle", "opus"])
+        .failure()
+        .stderr(predicate::str::contains("unknown config key `modle`"));
+    assert_eq!(
+        std::fs::read_to_string(&config).unwrap(),
+        "{\n  \"future_key\": [1, 2],\n  \"strip_noise\": false\n}\n"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn post_commit_backfills_sha_into_generated_record() {
+    let scratch = Scratch::new(serde_json::json!({}));
+    let record_path = scratch.out().join("idr-01-add-greeting.md");
+
+    scratch
+        .claude_idr(&[])
+        .stderr(predicate::str::contains("IDR generated"));
+    scratch.git(&["commit", "-q", "-m", "Add greeting"]);
+    let sha = scratch.git(&["rev-parse", "HEAD"]).trim().to_string();
+    scratch
+        .claude_idr(&["--post-commit"])
+        .stderr(predicate::str::contains("recorded commit"));
+    let record = std::fs::read_to_string(&record_path).unwrap();
+    scratch.claude_idr(&["--post-commit"]);
+
+    assert!(record.contains(&format!("> Commit: {}\n", &sha[..7])));
+    assert_eq!(
+        std::fs::read_to_string(&record_path).unwrap(),
+        record,
+        "a second run changes nothing"
+    );
+    let sidecar: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(scratch.out().join("idr-01-add-greeting.meta.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(sidecar["commit"]["sha"], sha);
+    assert_eq!(sidecar["commit"]["subject"], "Add greeting");
+}
+
+#[cfg(unix)]
+#[test]
+fn post_commit_without_record_documents_each_commit() {
+    let scratch = Scratch::new(serde_json::json!({}));
+    scratch.respond(&[("Extract the main purpose", "")], RECORD_BODY);
+    scratch.git(&["commit", "-q", "-m", "Add greeting"]);
+    std::fs::write(scratch.repo().join("a.txt"), "hello\nworld\n").unwrap();
+    scratch.git(&["commit", "-q", "-am", "Greet the world"]);
+    let sha = scratch.git(&["rev-parse", "HEAD"]).trim().to_string();
+
+    scratch.git(&["checkout", "-q", "HEAD^"]);
+    scratch.claude_idr(&["--post-commit"]);
+    scratch.git(&["checkout", "-q", &sha]);
+    scratch
+        .claude_idr(&["--post-commit"])
+        .stderr(predicate::str::contains("IDR generated"));
+    let calls = scratch.prompts().len();
+    scratch
+        .claude_idr(&["--post-commit"])
+        .stderr(predicate::str::contains("recorded commit"));
+
+    assert_eq!(
+        scratch.prompts().len(),
+        calls,
+        "HEAD was already documented"
+    );
+    let first = std::fs::read_to_string(scratch.out().join("idr-01-add-greeting.md")).unwrap();
+    assert!(first.contains("a.txt | 1 +"), "{first}");
+    let second = std::fs::read_to_string(scratch.out().join("idr-02-greet-the-world.md")).unwrap();
+    assert!(second.starts_with("# IDR: Greet the world\n"), "{second}");
+    assert!(
+        second.contains(&format!("> Commit: {}\n", &sha[..7])),
+        "{second}"
+    );
+    assert!(
+        scratch
+            .prompts()
+            .iter()
+            .any(|p| p.contains("+world") && !p.contains("+hello"))
+    );
+    let sidecar: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(scratch.out().join("idr-02-greet-the-world.meta.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        sidecar["diff_source"],
+        serde_json::json!({"kind": "commit", "sha": sha})
+    );
+    assert_eq!(sidecar["commit"]["subject"], "Greet the world");
+}
+
+/// A repository with a first commit on `main` and two on `topic`.
+#[cfg(unix)]
+fn scratch_with_topic_branch() -> Scratch {
+    let scratch = Scratch::new(serde_json::json!({}));
+    scratch.git(&["commit", "-q", "-m", "Add greeting"]);
+    scratch.git(&["branch", "-M", "main"]);
+    scratch.git(&["checkout", "-q", "-b", "topic"]);
+    std::fs::write(scratch.repo().join("retry.rs"), "fn retry() {}\n").unwrap();
+    scratch.git(&["add", "retry.rs"]);
+    scratch.git(&["commit", "-q", "-m", "Add retry"]);
+    std::fs::write(scratch.repo().join("retry_test.rs"), "fn retries() {}\n").unwrap();
+    scratch.git(&["add", "retry_test.rs"]);
+    scratch.git(&["commit", "-q", "-m", "Test retry"]);
+    scratch
+}
+
+#[cfg(unix)]
+#[test]
+fn pr_describes_branch_since_merge_base() {
+    let scratch = scratch_with_topic_branch();
+    scratch.respond(
+        &[("pull request description", "## Summary\n\nAdds retries.")],
+        "unexpected prompt",
+    );
+
+    assert_cmd::Command::from_std(scratch.subcommand(&["pr", "--base", "main"]))
+        .assert()
+        .success()
+        .stdout("## Summary\n\nAdds retries.\n");
+
+    let prompts = scratch.prompts();
+    assert_eq!(prompts.len(), 1);
+    assert!(prompts[0].contains("Add retry\n"), "{}", prompts[0]);
+    assert!(prompts[0].contains("Test retry\n"));
+    assert!(prompts[0].contains("+fn retries() {}"));
+    assert!(
+        !prompts[0].contains("+hello"),
+        "main's changes are not the branch's"
+    );
+    assert!(!scratch.out().exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn pr_writes_out_file_and_fails_without_commits() {
+    let scratch = scratch_with_topic_branch();
+    let out = scratch.dir.path().join("pr.md");
+
+    let pr = ["pr", "--base", "main", "--out", out.to_str().unwrap()];
+    assert_cmd::Command::from_std(scratch.subcommand(&pr))
+        .assert()
+        .success();
+    assert_eq!(
+        std::fs::read_to_string(&out).unwrap(),
+        format!("{RECORD_BODY}\n")
+    );
+
+    assert_cmd::Command::from_std(scratch.subcommand(&["pr", "--base", "topic"]))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "claude-idr: no commits on this branch since topic",
+        ));
+}
+
+#[cfg(unix)]
+#[test]
+fn pr_publish_updates_marked_section_of_open_pull_request() {
+    let scratch = scratch_with_topic_branch();
+    scratch.respond(&[], "## Summary\n\nAdds retries.");
+    let body = scratch.dir.path().join("body.md");
+    scratch.fake_bin(
+        "gh",
+        &format!(
+            "case \"$2\" in\n\
+             view) echo '{{\"number\":7,\"state\":\"OPEN\",\"body\":\"Fixes #12.\"}}' ;;\n\
+             edit) cat > {} ;;\n\
+             esac\n",
+            body.display()
+        ),
+    );
+
+    assert_cmd::Command::from_std(scratch.subcommand(&["pr", "--base", "main", "--publish"]))
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "claude-idr: description published to PR #7",
+        ));
+
+    assert_eq!(
+        std::fs::read_to_string(&body).unwrap(),
+        "Fixes #12.\n\n<!-- claude-idr:start -->\n## Summary\n\nAdds retries.\n<!-- claude-idr:end -->"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn pr_publish_only_warns_when_gh_fails() {
+    let scratch = scratch_with_topic_branch();
+    scratch.fake_bin(
+        "gh",
+        "echo 'To get started with GitHub CLI, please run: gh auth login' >&2\nexit 4\n",
+    );
+
+    assert_cmd::Command::from_std(scratch.subcommand(&["pr", "--base", "main", "--publish"]))
+        .assert()
+        .success()
+        .stdout(format!("{RECORD_BODY}\n"))
+        .stderr(predicate::str::contains(
+            "claude-idr: warning: cannot publish description: gh pr view failed: To get started",
+        ));
+}
+
+#[cfg(unix)]
+#[test]
+fn notes_mode_attaches_record_to_commit() {
+    let scratch = Scratch::new(serde_json::json!({"notes_mode": true, "notes_keep_files": true}));
+    let record_path = scratch.out().join("idr-01-add-greeting.md");
+
+    scratch.claude_idr(&[]);
+    scratch.git(&["commit", "-q", "-m", "Add greeting"]);
+    scratch
+        .claude_idr(&["--post-commit"])
+        .stderr(predicate::str::contains("as refs/notes/idr"));
+
+    let note = scratch.git(&["notes", "--ref=idr", "show", "HEAD"]);
+    assert_eq!(note, std::fs::read_to_string(&record_path).unwrap());
+}
+
+#[cfg(unix)]
+#[test]
+fn notes_mode_without_files_leaves_output_dir_alone() {
+    let scratch = Scratch::new(serde_json::json!({}));
+
+    scratch.claude_idr(&["--notes"]);
+    scratch.git(&["commit", "-q", "-m", "Add greeting"]);
+    scratch.claude_idr(&["--notes", "--post-commit"]);
+
+    let note = scratch.git(&["notes", "--ref=idr", "show", "HEAD"]);
+    assert!(note.starts_with("# IDR: Add greeting\n"));
+    assert!(!scratch.out().exists());
+    let pending = scratch.repo().join(".git/claude-idr");
+    assert_eq!(std::fs::read_dir(pending).unwrap().count(), 0);
+}
+
+#[cfg(unix)]
+#[test]
+fn concurrent_run_in_same_repository_exits_without_calling_claude() {
+    use std::time::{Duration, Instant};
+
+    let scratch = Scratch::new(serde_json::json!({}));
+    let calls = scratch.dir.path().join("calls");
+    scratch.fake_claude(&format!(
+        "cat >/dev/null\necho $PPID >> {}\nsleep 2\necho '{{\"result\":\"Add greeting\"}}'\n",
+        calls.display()
+    ));
+
+    let mut first = scratch
+        .command()
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+    let started = Instant::now();
+    while !calls.exists() {
+        assert!(
+            started.elapsed() < Duration::from_secs(10),
+            "first run never called claude"
+        );
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    scratch
+        .claude_idr(&[])
+        .stderr(predicate::str::contains(format!(
+            "another claude-idr run is in progress (pid {})",
+            first.id()
+        )));
+    assert!(first.wait().unwrap().success());
+
+    let callers = std::fs::read_to_string(&calls).unwrap();
+    assert!(
+        callers.lines().all(|pid| pid == first.id().to_string()),
+        "second run must not call claude: {callers}"
+    );
+    assert!(scratch.out().join("idr-01-add-greeting.md").exists());
+    assert!(!scratch.out().join("idr-02-add-greeting.md").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn fail_on_skip_exits_with_the_gate_code() {
+    let scratch = Scratch::new(serde_json::json!({}));
+    let fail_on_skip = |extra: &[&str]| {
+        let mut cmd = scratch.command();
+        cmd.arg("--fail-on-skip").args(extra);
+        assert_cmd::Command::from_std(cmd).assert()
+    };
+
+    scratch.session(
+        "session.jsonl",
+        &[r#"{"type":"user","message":{"content":"explain a.txt"}}"#],
+    );
+    fail_on_skip(&[])
+        .code(4)
+        .stderr(predicate::str::contains("did not write or edit any file"));
+    fail_on_skip(&["--force"]).success();
+
+    std::fs::remove_dir_all(scratch.projects()).unwrap();
+    fail_on_skip(&[])
+        .code(3)
+        .stderr(predicate::str::contains("session_max_age_min"));
+
+    // With nothing staged there is nothing left undocumented.
+    scratch.git(&["reset", "-q"]);
+    fail_on_skip(&[])
+        .success()
+        .stderr(predicate::str::contains("no recent session"));
+    assert!(scratch.prompts().is_empty());
+}
+
+#[cfg(unix)]
+#[test]
+fn fail_on_skip_config_rejects_an_oversized_diff() {
+    let scratch = Scratch::new(serde_json::json!({
+        "fail_on_skip": true,
+        "max_diff_lines": 1,
+    }));
+    std::fs::write(scratch.repo().join("a.txt"), "one\ntwo\nthree\n").unwrap();
+    scratch.git(&["add", "a.txt"]);
+
+    let mut cmd = scratch.command();
+    cmd.arg("--json");
+    assert_cmd::Command::from_std(cmd)
+        .assert()
+        .code(5)
+        .stdout(predicate::str::contains(r#""gate":"diff_too_large""#))
+        .stderr(predicate::str::contains("split the commit"));
+}
+
+#[cfg(unix)]
+#[test]
+fn doctor_reports_each_check_and_fails_only_on_failures() {
+    let scratch = Scratch::new(serde_json::json!({}));
+    scratch.fake_claude("echo '2.1.0 (Claude Code)'");
+    let doctor = || {
+        let mut cmd = scratch.subcommand(&["doctor", "--json"]);
+        for variable in [
+            "ANTHROPIC_API_KEY",
+            "CLAUDE_CODE_OAUTH_TOKEN",
+            "CLAUDE_CODE_USE_BEDROCK",
+            "CLAUDE_CODE_USE_VERTEX",
+        ] {
+            cmd.env_remove(variable);
+        }
+        let output = cmd.output().unwrap();
+        let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        let statuses: Vec<(String, String)> = report["checks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|check| {
+                (
+                    check["name"].as_str().unwrap().to_string(),
+                    check["status"].as_str().unwrap().to_string(),
+                )
+            })
+            .collect();
+        (output.status.code(), report["ok"].clone(), statuses)
+    };
+    let status_of = |statuses: &[(String, String)], name: &str| {
+        statuses
+            .iter()
+            .find(|(check, _)| check == name)
+            .map(|(_, status)| status.clone())
+            .unwrap()
+    };
+
+    let (code, ok, statuses) = doctor();
+    assert_eq!((code, ok), (Some(0), serde_json::json!(true)));
+    for (name, expected) in [
+        ("config", "pass"),
+        ("claude", "pass"),
+        ("login", "warn"),
+        ("sessions", "pass"),
+        ("workspace", "pass"),
+        ("hooks", "warn"),
+    ] {
+        assert_eq!(status_of(&statuses, name), expected, "{name}");
+    }
+
+    // A hook git would skip, and a config file that does not parse.
+    let hook = scratch.repo().join(".git/hooks/pre-commit");
+    std::fs::write(&hook, "#!/bin/sh\nclaude-idr\n").unwrap();
+    std::fs::write(scratch.config(), r#"{"max_diff_lines": "many"}"#).unwrap();
+    let (code, ok, statuses) = doctor();
+    assert_eq!((code, ok), (Some(1), serde_json::json!(false)));
+    assert_eq!(status_of(&statuses, "hooks"), "fail");
+    assert_eq!(status_of(&statuses, "config"), "fail");
+
+    let mut cmd = scratch.subcommand(&["doctor"]);
+    cmd.env_remove("ANTHROPIC_API_KEY");
+    assert_cmd::Command::from_std(cmd)
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("FAIL hooks: "))
+        .stdout(predicate::str::contains(format!(
+            "-> chmod +x {}",
+            hook.display()
+        )));
 }
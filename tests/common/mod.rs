@@ -0,0 +1,190 @@
+//! Scratch environment for end-to-end tests: a git repository with a staged
+//! file, a Claude Code projects directory holding a session transcript, and
+//! a fake `claude` first on `PATH` that answers with canned responses.
+#![allow(dead_code)] // each test crate uses a different part of it
+
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::process::Command;
+use tempfile::TempDir;
+
+/// A response with the structure of a record for the staged `a.txt`.
+pub const RECORD_BODY: &str =
+    "## 変更概要\n\nAdds a greeting file.\n\n### [a.txt](a.txt)\n\n**理由**: greets new users";
+
+/// A transcript in which Claude wrote `a.txt`.
+pub const WRITE_SESSION: &str = r#"{"type":"assistant","message":{"content":[{"type":"tool_use","name":"Write","input":{"file_path":"a.txt"}}]}}"#;
+
+pub struct Scratch {
+    pub dir: TempDir,
+}
+
+impl Scratch {
+    /// Writes `config` (plus `probe_claude: false` and `output_dir`) and a
+    /// fake claude that answers the purpose prompt with `Add greeting` and
+    /// every other prompt with [`RECORD_BODY`].
+    pub fn new(config: serde_json::Value) -> Scratch {
+        let scratch = Scratch {
+            dir: TempDir::new().unwrap(),
+        };
+        scratch.session("session.jsonl", &[WRITE_SESSION]);
+
+        std::fs::create_dir_all(scratch.repo()).unwrap();
+        scratch.git(&["init", "-q"]);
+        scratch.git(&["config", "user.name", "t"]);
+        scratch.git(&["config", "user.email", "t@example.com"]);
+        std::fs::write(scratch.repo().join("a.txt"), "hello\n").unwrap();
+        scratch.git(&["add", "a.txt"]);
+
+        scratch.respond(&[("Extract the main purpose", "Add greeting")], RECORD_BODY);
+        let mut config = config;
+        config["probe_claude"] = serde_json::json!(false);
+        config["output_dir"] = serde_json::json!(scratch.out());
+        scratch.write_config(&config);
+        scratch
+    }
+
+    /// Replaces the config file with exactly `config`.
+    pub fn write_config(&self, config: &serde_json::Value) {
+        std::fs::write(self.config(), config.to_string()).unwrap();
+    }
+
+    /// Writes a transcript named `name` into the projects directory.
+    pub fn session(&self, name: &str, lines: &[&str]) -> PathBuf {
+        let path = self.projects().join("repo").join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, lines.join("\n") + "\n").unwrap();
+        path
+    }
+
+    /// Makes the fake claude answer a prompt containing a marker with the
+    /// response paired with it (first match wins), and anything else with
+    /// `fallback`. Every prompt is kept for [`Scratch::prompts`].
+    pub fn respond(&self, responses: &[(&str, &str)], fallback: &str) {
+        let canned = self.dir.path().join("canned");
+        let _ = std::fs::remove_dir_all(&canned);
+        std::fs::create_dir_all(&canned).unwrap();
+        std::fs::create_dir_all(self.dir.path().join("prompts")).unwrap();
+        for (i, (marker, response)) in responses.iter().enumerate() {
+            std::fs::write(canned.join(format!("{i:03}.marker")), marker).unwrap();
+            std::fs::write(canned.join(format!("{i:03}.response")), response).unwrap();
+        }
+        std::fs::write(canned.join("fallback"), fallback).unwrap();
+        self.fake_claude(&format!(
+            r#"dir='{}'
+prompt="$dir/prompts/$$"
+cat > "$prompt"
+for marker in "$dir"/canned/*.marker; do
+  [ -e "$marker" ] || continue
+  if grep -qF -f "$marker" "$prompt"; then
+    cat "${{marker%.marker}}.response"
+    exit 0
+  fi
+done
+cat "$dir/canned/fallback"
+"#,
+            self.dir.path().display()
+        ));
+    }
+
+    /// Replaces the fake claude with a shell script running `body`.
+    pub fn fake_claude(&self, body: &str) {
+        self.fake_bin("claude", body);
+    }
+
+    /// Puts a shell script running `body` on PATH as `name`.
+    pub fn fake_bin(&self, name: &str, body: &str) {
+        let bin = self.dir.path().join("bin");
+        std::fs::create_dir_all(&bin).unwrap();
+        let fake = bin.join(name);
+        std::fs::write(&fake, format!("#!/bin/sh\n{body}")).unwrap();
+        std::fs::set_permissions(&fake, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    /// Prompts the fake claude from [`Scratch::respond`] has received, in no
+    /// particular order.
+    pub fn prompts(&self) -> Vec<String> {
+        let Ok(entries) = std::fs::read_dir(self.dir.path().join("prompts")) else {
+            return Vec::new();
+        };
+        entries
+            .flatten()
+            .map(|e| std::fs::read_to_string(e.path()).unwrap())
+            .collect()
+    }
+
+    pub fn home(&self) -> PathBuf {
+        self.dir.path().join("home")
+    }
+
+    pub fn projects(&self) -> PathBuf {
+        self.dir.path().join("projects")
+    }
+
+    pub fn repo(&self) -> PathBuf {
+        self.dir.path().join("repo")
+    }
+
+    pub fn out(&self) -> PathBuf {
+        self.dir.path().join("out")
+    }
+
+    pub fn config(&self) -> PathBuf {
+        self.dir.path().join("config.json")
+    }
+
+    pub fn git(&self, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .args(args)
+            .current_dir(self.repo())
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {args:?} failed");
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    }
+
+    /// `claude-idr --config <scratch config>` in the repository, with HOME,
+    /// PATH, the projects directory, and the runtime directory redirected.
+    pub fn command(&self) -> Command {
+        self.subcommand(&[])
+    }
+
+    /// Like [`Scratch::command`], with `args` (a subcommand and its
+    /// arguments) first, where subcommands have to be.
+    pub fn subcommand(&self, args: &[&str]) -> Command {
+        let path = std::env::join_paths(std::iter::once(self.dir.path().join("bin")).chain(
+            std::env::split_paths(&std::env::var_os("PATH").unwrap_or_default()),
+        ))
+        .unwrap();
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("claude-idr"));
+        cmd.args(args)
+            .arg("--config")
+            .arg(self.config())
+            .current_dir(self.repo())
+            .env("HOME", self.home())
+            .env("PATH", path)
+            .env("CLAUDE_IDR_PROJECTS_DIR", self.projects())
+            .env("XDG_RUNTIME_DIR", self.dir.path())
+            .env_remove("CLAUDE_IDR_CLAUDE_BIN")
+            .env_remove("XDG_CONFIG_HOME")
+            .env_remove("XDG_STATE_HOME");
+        // The tests must generate even when they run on a CI agent.
+        for variable in [
+            "CI",
+            "GITHUB_ACTIONS",
+            "GITLAB_CI",
+            "BUILDKITE",
+            "JENKINS_URL",
+        ] {
+            cmd.env_remove(variable);
+        }
+        cmd
+    }
+
+    /// Runs `claude-idr` with `extra` arguments and asserts it exits 0.
+    pub fn claude_idr(&self, extra: &[&str]) -> assert_cmd::assert::Assert {
+        let mut cmd = self.command();
+        cmd.args(extra);
+        assert_cmd::Command::from_std(cmd).assert().success()
+    }
+}